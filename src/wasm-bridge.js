@@ -40,14 +40,18 @@ export function validateExperienceWasm(experience) {
 
 /**
  * High-performance domain network generation using WASM
+ * `options` ({min_edge_weight, top_k, min_node_size, normalize}) simplifies
+ * dense networks; omit it for the full network
  */
-export function generateDomainNetworkWasm(experiences) {
+export function generateDomainNetworkWasm(experiences, options) {
   if (!wasmModule) {
     throw new Error('WASM module not initialized. Call initWasm() first.');
   }
 
   const json = JSON.stringify(experiences);
-  const result = wasmModule.generate_domain_network(json);
+  const result = options
+    ? wasmModule.generate_domain_network_with_options(json, JSON.stringify(options))
+    : wasmModule.generate_domain_network(json);
   return JSON.parse(result);
 }
 
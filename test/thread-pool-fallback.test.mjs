@@ -0,0 +1,35 @@
+// SPDX-License-Identifier: MPL-2.0
+/**
+ * The WASM thread pool's single-threaded fallback (wasm/core/src/thread_pool.rs)
+ *
+ * Loads a `threads` build without atomics, as made by
+ * `just test-threads-fallback`; skipped when that build is absent.
+ */
+
+import { test } from 'node:test';
+import assert from 'node:assert';
+import { existsSync, readFileSync } from 'node:fs';
+
+const pkg = new URL('../wasm/target/pkg-threads-fallback/', import.meta.url);
+const built = existsSync(new URL('ubicity_wasm.js', pkg));
+
+test('init_thread_pool falls back to the calling thread without cross-origin isolation', { skip: !built }, async () => {
+  globalThis.crossOriginIsolated = false;
+  const wasm = await import(new URL('ubicity_wasm.js', pkg));
+  wasm.initSync({ module: readFileSync(new URL('ubicity_wasm_bg.wasm', pkg)) });
+
+  assert.strictEqual(await wasm.init_thread_pool(4), false);
+  await assert.rejects(wasm.init_thread_pool(4), /already started/);
+
+  // rayon builds its one-thread pool at the first parallel call
+  const experiences = ['a', 'b', 'c'].map((id) => ({
+    id,
+    timestamp: '2024-05-01T10:00:00Z',
+    learner: { id: `learner-${id}` },
+    context: { location: { name: 'library' } },
+    experience: { type: 'observation', description: 'pond dipping', domains: ['ecology', id] },
+  }));
+  const network = JSON.parse(wasm.generate_domain_network(JSON.stringify(experiences)));
+  assert.strictEqual(network.nodes.length, 4);
+  assert.deepStrictEqual(JSON.parse(wasm.parallelism()), { threads: true, pool_size: 1 });
+});
@@ -0,0 +1,318 @@
+//! `ubicity` command-line tool
+//!
+//! Runs the same `ubicity-core` code the browser bindings use over
+//! experience files, so batch pipelines and the web client agree exactly:
+//!
+//! ```text
+//! ubicity validate [--strict] FILE...
+//! ubicity network [--format json|cytoscape|d3|graphml|csv|csv-nodes] [--prune OPTIONS] [--output PATH] FILE...
+//! ubicity stats FILE...
+//! ```
+//!
+//! Each FILE is a JSON array of experiences or NDJSON (one experience per
+//! line); `-` reads standard input. `validate` exits with status 1 if any
+//! experience is invalid; usage, input and output errors exit with status 2.
+//! A closed standard output (`ubicity stats big.ndjson | head -1`) ends the
+//! command quietly.
+
+#![forbid(unsafe_code)]
+
+use serde_json::{json, Value};
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+
+use ubicity_core::aggregate::aggregate;
+use ubicity_core::export::export_network_csv;
+use ubicity_core::graph_formats::{
+    generate_domain_network_cytoscape, generate_domain_network_d3, generate_domain_network_graphml,
+};
+use ubicity_core::network::{connected_components, network_metrics};
+use ubicity_core::pruning::generate_domain_network_with_options;
+use ubicity_core::{generate_domain_network, ExperienceValidator};
+
+const USAGE: &str = "\
+usage: ubicity <command> [options] FILE...
+
+commands:
+  validate [--strict]          validate every experience; exit 1 if any is invalid
+  network  [--format FORMAT]   domain co-occurrence network
+           [--prune OPTIONS]   (FORMAT: json, cytoscape, d3, graphml,
+           [--output PATH]     csv for the edge list, csv-nodes for nodes;
+                               OPTIONS: generate_domain_network_with_options
+                               JSON, json and csv formats only)
+  stats                        counts, learners, domains, types, months and
+                               network shape as JSON
+
+FILE is a JSON array or NDJSON; `-` reads standard input.";
+
+/// One experience as found in an input file
+struct Record {
+    /// `file:line` for NDJSON, `file[index]` for JSON arrays
+    source: String,
+    json: String,
+}
+
+struct Options {
+    strict: bool,
+    format: String,
+    prune: String,
+    output: Option<String>,
+    files: Vec<String>,
+}
+
+/// Why a command stopped early
+enum Failure {
+    /// Usage, input or output error, reported with status 2
+    Message(String),
+    /// Standard output was closed by the reader
+    BrokenPipe,
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Failure::Message(message)
+    }
+}
+
+impl From<io::Error> for Failure {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::BrokenPipe => Failure::BrokenPipe,
+            _ => Failure::Message(format!("stdout: {}", error)),
+        }
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        strict: false,
+        format: "json".to_string(),
+        prune: String::new(),
+        output: None,
+        files: Vec::new(),
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--strict" => options.strict = true,
+            "--format" => options.format = args.next().ok_or("--format needs a value")?.clone(),
+            "--prune" => options.prune = args.next().ok_or("--prune needs options JSON")?.clone(),
+            "--output" | "-o" => options.output = Some(args.next().ok_or("--output needs a path")?.clone()),
+            "-" => options.files.push(arg.clone()),
+            flag if flag.starts_with('-') => return Err(format!("unknown option {}", flag)),
+            file => options.files.push(file.to_string()),
+        }
+    }
+    if options.files.is_empty() {
+        return Err("no input files".to_string());
+    }
+    Ok(options)
+}
+
+fn read_input(path: &str) -> Result<String, String> {
+    if path == "-" {
+        let mut text = String::new();
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|e| format!("stdin: {}", e))?;
+        return Ok(text);
+    }
+    std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Split a file into records; JSON arrays are recognised by a leading `[`
+fn load(path: &str) -> Result<Vec<Record>, String> {
+    let text = read_input(path)?;
+    let name = if path == "-" { "<stdin>" } else { path };
+
+    if text.trim_start().starts_with('[') {
+        let items: Vec<Value> = serde_json::from_str(&text).map_err(|e| format!("{}: {}", name, e))?;
+        return Ok(items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| Record {
+                source: format!("{}[{}]", name, i),
+                json: item.to_string(),
+            })
+            .collect());
+    }
+    Ok(text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| Record {
+            source: format!("{}:{}", name, i + 1),
+            json: line.to_string(),
+        })
+        .collect())
+}
+
+fn load_all(files: &[String]) -> Result<Vec<Record>, String> {
+    let mut records = Vec::new();
+    for file in files {
+        records.extend(load(file)?);
+    }
+    Ok(records)
+}
+
+/// All records as one JSON array, reporting the first malformed record by
+/// its source position
+fn experiences_json(records: &[Record]) -> Result<String, String> {
+    let mut items = Vec::with_capacity(records.len());
+    for record in records {
+        let item: Value = serde_json::from_str(&record.json).map_err(|e| format!("{}: {}", record.source, e))?;
+        items.push(item);
+    }
+    Ok(Value::Array(items).to_string())
+}
+
+fn validate(options: &Options, out: &mut impl Write) -> Result<bool, Failure> {
+    let records = load_all(&options.files)?;
+    let validator = ExperienceValidator::new(options.strict);
+    let mut invalid = 0;
+    for record in &records {
+        let result: Value = serde_json::from_str(&validator.validate(&record.json).map_err(|e| e.to_string())?)
+            .map_err(|e| e.to_string())?;
+        if result["valid"] == Value::Bool(true) {
+            continue;
+        }
+        invalid += 1;
+        let id = serde_json::from_str::<Value>(&record.json)
+            .ok()
+            .and_then(|v| v.get("id").and_then(Value::as_str).map(str::to_string))
+            .unwrap_or_else(|| "-".to_string());
+        let errors: Vec<&str> = result["errors"].as_array().into_iter().flatten().filter_map(Value::as_str).collect();
+        writeln!(out, "{}: {}: {}", record.source, id, errors.join("; "))?;
+    }
+    eprintln!(
+        "checked {} experiences: {} valid, {} invalid",
+        records.len(),
+        records.len() - invalid,
+        invalid
+    );
+    Ok(invalid == 0)
+}
+
+fn network(options: &Options, out: &mut impl Write) -> Result<(), Failure> {
+    let experiences = experiences_json(&load_all(&options.files)?)?;
+    if !options.prune.is_empty() && !matches!(options.format.as_str(), "json" | "csv" | "csv-nodes") {
+        return Err(format!("--prune does not apply to the {} format", options.format).into());
+    }
+    let output = match options.format.as_str() {
+        "json" => generate_domain_network_with_options(&experiences, &options.prune),
+        "cytoscape" => generate_domain_network_cytoscape(&experiences),
+        "d3" => generate_domain_network_d3(&experiences),
+        "graphml" => generate_domain_network_graphml(&experiences),
+        "csv" | "csv-nodes" => generate_domain_network_with_options(&experiences, &options.prune)
+            .and_then(|network| export_network_csv(&network)),
+        other => {
+            return Err(format!(
+                "unknown format {} (expected json, cytoscape, d3, graphml, csv or csv-nodes)",
+                other
+            )
+            .into())
+        }
+    }
+    .map_err(|e| e.to_string())?;
+    // export_network_csv returns both tables; a file holds one
+    let output = match options.format.as_str() {
+        "csv" | "csv-nodes" => {
+            let tables: Value = serde_json::from_str(&output).map_err(|e| e.to_string())?;
+            let table = if options.format == "csv" { "edges" } else { "nodes" };
+            tables[table].as_str().unwrap_or_default().to_string()
+        }
+        _ => output,
+    };
+
+    match &options.output {
+        Some(path) => Ok(std::fs::write(path, output).map_err(|e| format!("{}: {}", path, e))?),
+        None => {
+            out.write_all(output.as_bytes())?;
+            if !output.ends_with('\n') {
+                writeln!(out)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn stats(options: &Options, out: &mut impl Write) -> Result<(), Failure> {
+    let records = load_all(&options.files)?;
+    let experiences = experiences_json(&records)?;
+    let core = |result: Result<String, ubicity_core::error::Error>| -> Result<Value, String> {
+        let text = result.map_err(|e| e.to_string())?;
+        serde_json::from_str(&text).map_err(|e| e.to_string())
+    };
+    let grouped = |by: &str| core(aggregate(&experiences, &json!([{"group": {"by": by}}, {"sort": {"by": "count", "desc": true}}]).to_string()));
+
+    let validator = ExperienceValidator::new(false);
+    let valid = records
+        .iter()
+        .filter(|r| validator.validate(&r.json).is_ok_and(|result| result.contains("\"valid\":true")))
+        .count();
+
+    let network = generate_domain_network(&experiences).map_err(|e| e.to_string())?;
+    let metrics = core(network_metrics(&network))?;
+    let components = core(connected_components(&network))?;
+    let counts = |rows: Value| -> Value {
+        rows.as_array()
+            .into_iter()
+            .flatten()
+            .map(|row| json!({"key": row["key"], "count": row["count"]}))
+            .collect()
+    };
+
+    let summary = json!({
+        "experiences": records.len(),
+        "valid": valid,
+        "invalid": records.len() - valid,
+        "learners": grouped("learner")?.as_array().map_or(0, Vec::len),
+        "domains": counts(grouped("domain")?),
+        "types": counts(grouped("type")?),
+        "months": counts(core(aggregate(&experiences, r#"[{"group": {"by": "month"}}, {"sort": {"by": "key"}}]"#))?),
+        "network": {
+            "nodes": metrics["nodes"],
+            "edges": metrics["edges"],
+            "density": metrics["density"],
+            "components": components["count"],
+        },
+    });
+    writeln!(out, "{}", serde_json::to_string_pretty(&summary).map_err(|e| e.to_string())?)?;
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some((command, rest)) = args.split_first() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let result = run(command, rest, &mut out).and_then(|valid| {
+        out.flush()?;
+        Ok(valid)
+    });
+    match result {
+        Ok(true) | Err(Failure::BrokenPipe) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(Failure::Message(message)) => {
+            eprintln!("ubicity: {}", message);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(command: &str, args: &[String], out: &mut impl Write) -> Result<bool, Failure> {
+    if matches!(command, "-h" | "--help" | "help") {
+        writeln!(out, "{}", USAGE)?;
+        return Ok(true);
+    }
+    let options = parse_options(args)?;
+    match command {
+        "validate" => validate(&options, out),
+        "network" => network(&options, out).map(|_| true),
+        "stats" => stats(&options, out).map(|_| true),
+        other => Err(format!("unknown command {}\n\n{}", other, USAGE).into()),
+    }
+}
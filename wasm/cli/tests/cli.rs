@@ -0,0 +1,161 @@
+//! End-to-end runs of the `ubicity` binary: exit statuses, input formats,
+//! every network format and a closed standard output
+
+use serde_json::{json, Value};
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn experience(id: &str, domains: &[&str]) -> Value {
+    json!({
+        "id": id,
+        "timestamp": "2024-05-01T10:00:00Z",
+        "learner": {"id": format!("learner-{}", id)},
+        "context": {"location": {"name": "library"}},
+        "experience": {"type": "observation", "description": "pond dipping", "domains": domains},
+    })
+}
+
+fn experiences() -> Vec<Value> {
+    vec![
+        experience("a", &["ecology", "chemistry"]),
+        experience("b", &["ecology", "art"]),
+        experience("c", &["ecology", "chemistry"]),
+    ]
+}
+
+fn ndjson(records: &[Value]) -> String {
+    records.iter().map(|r| format!("{}\n", r)).collect()
+}
+
+/// Run `ubicity` with `input` on standard input
+fn ubicity(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ubicity"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn validate_exit_statuses() {
+    let valid = ubicity(&["validate", "-"], &ndjson(&experiences()));
+    assert_eq!(valid.status.code(), Some(0));
+    assert_eq!(stdout(&valid), "");
+
+    let mut records = experiences();
+    records[1].as_object_mut().unwrap().remove("learner");
+    let invalid = ubicity(&["validate", "-"], &ndjson(&records));
+    assert_eq!(invalid.status.code(), Some(1));
+    assert!(stdout(&invalid).starts_with("<stdin>:2: b: "), "{}", stdout(&invalid));
+    assert!(String::from_utf8_lossy(&invalid.stderr).contains("3 experiences: 2 valid, 1 invalid"));
+
+    for args in [&["validate"][..], &["validate", "--bogus", "-"], &["frobnicate", "-"], &["validate", "missing.json"]] {
+        let output = ubicity(args, "");
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("ubicity: "));
+    }
+    let malformed = ubicity(&["validate", "-"], "[{\"id\": ");
+    assert_eq!(malformed.status.code(), Some(2));
+}
+
+#[test]
+fn ndjson_and_arrays_read_the_same() {
+    let records = experiences();
+    let from_ndjson = ubicity(&["stats", "-"], &ndjson(&records));
+    let from_array = ubicity(&["stats", "-"], &Value::from(records).to_string());
+    assert_eq!(from_ndjson.status.code(), Some(0));
+    assert_eq!(stdout(&from_ndjson), stdout(&from_array));
+
+    let stats: Value = serde_json::from_str(&stdout(&from_ndjson)).unwrap();
+    assert_eq!(stats["experiences"], 3);
+    assert_eq!(stats["valid"], 3);
+    assert_eq!(stats["learners"], 3);
+    assert_eq!(stats["network"]["nodes"], 3);
+    assert_eq!(stats["network"]["edges"], 2);
+
+    let malformed = ubicity(&["network", "-"], &format!("{}\n{{oops\n", experience("a", &["art"])));
+    assert_eq!(malformed.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&malformed.stderr).contains("<stdin>:2"));
+}
+
+#[test]
+fn every_network_format() {
+    let input = ndjson(&experiences());
+    let run = |args: &[&str]| {
+        let output = ubicity(args, &input);
+        assert_eq!(output.status.code(), Some(0), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        stdout(&output)
+    };
+
+    let network: Value = serde_json::from_str(&run(&["network", "-"])).unwrap();
+    assert_eq!(network["nodes"].as_array().unwrap().len(), 3);
+    assert_eq!(serde_json::from_str::<Value>(&run(&["network", "--format", "json", "-"])).unwrap(), network);
+
+    let cytoscape: Value = serde_json::from_str(&run(&["network", "--format", "cytoscape", "-"])).unwrap();
+    assert!(cytoscape.is_object() || cytoscape.is_array());
+    let d3: Value = serde_json::from_str(&run(&["network", "--format", "d3", "-"])).unwrap();
+    assert_eq!(d3["nodes"].as_array().unwrap().len(), 3);
+    assert!(run(&["network", "--format", "graphml", "-"]).contains("<graphml"));
+
+    let edges = run(&["network", "--format", "csv", "-"]);
+    assert_eq!(edges.lines().count(), 3, "{}", edges);
+    let nodes = run(&["network", "--format", "csv-nodes", "-"]);
+    assert_eq!(nodes.lines().count(), 4, "{}", nodes);
+
+    let pruned: Value = serde_json::from_str(&run(&["network", "--prune", r#"{"min_edge_weight": 2}"#, "-"])).unwrap();
+    assert_eq!(pruned["edges"].as_array().unwrap().len(), 1);
+
+    for args in [&["network", "--format", "dot", "-"][..], &["network", "--format", "d3", "--prune", "{}", "-"]] {
+        assert_eq!(ubicity(args, &input).status.code(), Some(2), "{:?}", args);
+    }
+
+    let path = std::env::temp_dir().join(format!("ubicity-cli-test-{}.graphml", std::process::id()));
+    let written = ubicity(&["network", "--format", "graphml", "--output", path.to_str().unwrap(), "-"], &input);
+    assert_eq!(written.status.code(), Some(0));
+    assert_eq!(stdout(&written), "");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), run(&["network", "--format", "graphml", "-"]));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn closed_stdout_exits_quietly() {
+    let records: Vec<Value> = (0..2_000).map(|i| experience(&format!("e{}", i), &["ecology", "art"])).collect();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ubicity"))
+        .args(["stats", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Close the reading end before the binary can write anything
+    drop(child.stdout.take());
+    child.stdin.take().unwrap().write_all(ndjson(&records).as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr);
+    assert_eq!(stderr, "");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn other_output_errors_exit_with_status_2() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ubicity"))
+        .args(["stats", "-"])
+        .stdin(Stdio::piped())
+        .stdout(std::fs::File::create("/dev/full").unwrap())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(ndjson(&experiences()).as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("ubicity: stdout: "));
+}
@@ -0,0 +1,117 @@
+//! `ubicity-core` as a WebAssembly component
+//!
+//! Exports the `ubicity:core` WIT world (`wit/ubicity.wit`) for hosts such
+//! as wasmtime or Spin that embed components rather than JS modules. Build
+//! with:
+//!
+//! ```sh
+//! cargo build -p ubicity-component --target wasm32-wasip2 --release
+//! ```
+//!
+//! Every function forwards to the export of the same name in
+//! `ubicity-core`; errors become the WIT `error` record.
+
+wit_bindgen::generate!({
+    world: "ubicity",
+    path: "wit",
+});
+
+use exports::ubicity::core::{analytics, network, sync, validation};
+use ubicity::core::types::{Error, ErrorKind};
+use ubicity_core::error::{Error as CoreError, ErrorKind as CoreErrorKind};
+use ubicity_core::ExperienceValidator;
+
+// Exports are only emitted for wasm32; native builds just type-check the
+// bindings
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+struct Component;
+
+impl From<CoreError> for Error {
+    fn from(error: CoreError) -> Self {
+        let kind = match error.kind() {
+            CoreErrorKind::Parse => ErrorKind::Parse,
+            CoreErrorKind::InvalidInput => ErrorKind::InvalidInput,
+            CoreErrorKind::NotFound => ErrorKind::NotFound,
+            CoreErrorKind::Conflict => ErrorKind::Conflict,
+            CoreErrorKind::Crypto => ErrorKind::Crypto,
+            CoreErrorKind::Host => ErrorKind::Host,
+            CoreErrorKind::Serialization => ErrorKind::Serialization,
+        };
+        Error {
+            kind,
+            message: error.message().to_string(),
+            context: serde_json::Value::Object(error.context().clone()).to_string(),
+        }
+    }
+}
+
+impl validation::Guest for Component {
+    fn validate(experience_json: String, strict: bool) -> Result<String, Error> {
+        Ok(ExperienceValidator::new(strict).validate(&experience_json)?)
+    }
+}
+
+impl network::Guest for Component {
+    fn generate_domain_network(experiences_json: String) -> Result<String, Error> {
+        Ok(ubicity_core::generate_domain_network(&experiences_json)?)
+    }
+
+    fn generate_domain_network_with_options(experiences_json: String, options_json: String) -> Result<String, Error> {
+        Ok(ubicity_core::pruning::generate_domain_network_with_options(&experiences_json, &options_json)?)
+    }
+
+    fn network_metrics(network_json: String) -> Result<String, Error> {
+        Ok(ubicity_core::network::network_metrics(&network_json)?)
+    }
+
+    fn shortest_path(network_json: String, from_domain: String, to_domain: String) -> Result<String, Error> {
+        Ok(ubicity_core::network::shortest_path(&network_json, &from_domain, &to_domain)?)
+    }
+
+    fn connected_components(network_json: String) -> Result<String, Error> {
+        Ok(ubicity_core::network::connected_components(&network_json)?)
+    }
+}
+
+impl analytics::Guest for Component {
+    fn query_experiences(experiences_json: String, query_json: String) -> Result<String, Error> {
+        Ok(ubicity_core::query::query_experiences(&experiences_json, &query_json)?)
+    }
+
+    fn aggregate(experiences_json: String, pipeline_json: String) -> Result<String, Error> {
+        Ok(ubicity_core::aggregate::aggregate(&experiences_json, &pipeline_json)?)
+    }
+
+    fn jaccard_similarity(set1_json: String, set2_json: String) -> Result<f64, Error> {
+        Ok(ubicity_core::jaccard_similarity(&set1_json, &set2_json)?)
+    }
+
+    fn to_triples(experiences_json: String) -> Result<String, Error> {
+        Ok(ubicity_core::triples::to_triples(&experiences_json)?)
+    }
+
+    fn match_patterns(triples_or_experiences_json: String, pattern_json: String) -> Result<String, Error> {
+        Ok(ubicity_core::patterns::match_patterns(&triples_or_experiences_json, &pattern_json)?)
+    }
+
+    fn apply_rules(experiences_json: String, rules_json: String) -> Result<String, Error> {
+        Ok(ubicity_core::rules::apply_rules(&experiences_json, &rules_json)?)
+    }
+}
+
+impl sync::Guest for Component {
+    fn merge(local_json: String, remote_json: String) -> Result<String, Error> {
+        Ok(ubicity_core::sync::merge(&local_json, &remote_json)?)
+    }
+
+    fn diff_logs(old_json: String, new_json: String) -> Result<String, Error> {
+        Ok(ubicity_core::sync::diff_logs(&old_json, &new_json)?)
+    }
+
+    fn apply_changeset(log_json: String, changeset_json: String) -> Result<String, Error> {
+        Ok(ubicity_core::sync::apply_changeset(&log_json, &changeset_json)?)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+export!(Component);
@@ -0,0 +1,151 @@
+//! Accessibility metadata and accommodation-aware reporting
+//!
+//! Experiences may record how they were accessed and which accommodations
+//! were in place:
+//!
+//! ```json
+//! "experience": {"accessibility": {"modality": "audio_description",
+//!                                  "accommodations": ["extra_time", "step_free_access"]}}
+//! ```
+//!
+//! Values are free vocabulary but must be lowercase tokens so segments line
+//! up across clients. [`accessibility_report`] segments participation by
+//! modality and by accommodation so inclusion officers can check whether
+//! accommodations go with participation; the aggregation pipeline can also
+//! group by `modality` and `accommodation`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::query::domains;
+use crate::severity::Issue;
+
+/// Segment label for records without the attribute
+const NONE: &str = "none";
+
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify_next::Tsify))]
+pub(crate) struct Accessibility {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) modality: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) accommodations: Option<Vec<String>>,
+}
+
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.len() <= 64
+        && s.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_' || b == b'-')
+}
+
+/// Validation errors for an experience's accessibility block
+pub(crate) fn validate_accessibility(accessibility: &Accessibility) -> Vec<Issue> {
+    let mut errors = Vec::new();
+    if accessibility.modality.as_deref().is_some_and(|m| !is_token(m)) {
+        errors.push(Issue::new("accessibility.modality.format"));
+    }
+    if let Some(ref accommodations) = accessibility.accommodations {
+        if !accommodations.iter().all(|a| is_token(a)) {
+            errors.push(Issue::new("accessibility.accommodations.format"));
+        }
+        if accommodations.iter().collect::<BTreeSet<_>>().len() != accommodations.len() {
+            errors.push(Issue::new("accessibility.accommodations.duplicate"));
+        }
+    }
+    errors
+}
+
+/// Modality of a raw experience, if recorded
+pub(crate) fn modality(exp: &Value) -> Option<&str> {
+    lookup(exp, "experience.accessibility.modality").and_then(Value::as_str)
+}
+
+/// Accommodations of a raw experience
+pub(crate) fn accommodations(exp: &Value) -> impl Iterator<Item = &str> {
+    lookup(exp, "experience.accessibility.accommodations")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+}
+
+#[derive(Default)]
+struct SegmentAcc<'a> {
+    records: usize,
+    learners: BTreeSet<&'a str>,
+    domains: BTreeSet<&'a str>,
+}
+
+impl<'a> SegmentAcc<'a> {
+    fn add(&mut self, learner: &'a str, exp: &'a Value) {
+        self.records += 1;
+        self.learners.insert(learner);
+        self.domains.extend(domains(exp));
+    }
+}
+
+#[derive(Serialize)]
+struct Segment {
+    key: String,
+    records: usize,
+    learners: usize,
+    records_per_learner: f64,
+    distinct_domains: usize,
+}
+
+#[derive(Serialize)]
+struct AccessibilityReport {
+    records: usize,
+    by_modality: Vec<Segment>,
+    by_accommodation: Vec<Segment>,
+}
+
+fn segments(acc: BTreeMap<&str, SegmentAcc>) -> Vec<Segment> {
+    acc.into_iter()
+        .map(|(key, s)| Segment {
+            key: key.to_string(),
+            records: s.records,
+            learners: s.learners.len(),
+            records_per_learner: s.records as f64 / s.learners.len().max(1) as f64,
+            distinct_domains: s.domains.len(),
+        })
+        .collect()
+}
+
+fn report(experiences: &[Value]) -> AccessibilityReport {
+    let mut by_modality: BTreeMap<&str, SegmentAcc> = BTreeMap::new();
+    let mut by_accommodation: BTreeMap<&str, SegmentAcc> = BTreeMap::new();
+
+    for exp in experiences {
+        let learner = lookup(exp, "learner.id").and_then(Value::as_str).unwrap_or_default();
+        let mut keys: Vec<&str> = accommodations(exp).collect::<BTreeSet<_>>().into_iter().collect();
+        if keys.is_empty() {
+            keys.push(NONE);
+        }
+        by_modality.entry(modality(exp).unwrap_or(NONE)).or_default().add(learner, exp);
+        for key in keys {
+            by_accommodation.entry(key).or_default().add(learner, exp);
+        }
+    }
+
+    AccessibilityReport {
+        records: experiences.len(),
+        by_modality: segments(by_modality),
+        by_accommodation: segments(by_accommodation),
+    }
+}
+
+/// Participation (records, learners, records per learner, domain coverage)
+/// segmented by modality and by accommodation; records without either are
+/// reported under `"none"`
+/// Returns the report as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn accessibility_report(experiences_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    to_json(&report(&experiences))
+}
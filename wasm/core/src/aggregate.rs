@@ -0,0 +1,260 @@
+//! Aggregation pipelines over experiences
+//!
+//! A pipeline is an array of stages applied in order, a small cousin of
+//! MongoDB's aggregation framework:
+//!
+//! ```json
+//! [
+//!   {"filter": {"date_range": {"from": "2024-01-01"}}},
+//!   {"group": {"by": "domain", "accumulators": {"learners": {"distinct": "learner.id"}}}},
+//!   {"sort": {"by": "count", "desc": true}},
+//!   {"limit": 10}
+//! ]
+//! ```
+//!
+//! `group.by` is one of `domain` (a record joins one group per domain),
+//! `type`, `location`, `learner`, `geohash` (with `precision`, default 5),
+//! `month`, `modality`, `accommodation` (one group per accommodation), or
+//! any dotted field path. Each group row is `{key, count, ...}`
+//! with one field per accumulator; later stages see the rows, so they can be
+//! filtered, sorted and limited like records.
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::accessibility::{accommodations, modality};
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::geo::geohash;
+use crate::query::{compare, coordinates, domains, Filter};
+use crate::tenancy::TenantScope;
+use crate::time::{civil_from_days, parse_timestamp, MS_PER_DAY};
+
+const DEFAULT_GEOHASH_PRECISION: usize = 5;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Stage {
+    Filter(Filter),
+    Group(Group),
+    Sort(Sort),
+    Limit(usize),
+    Skip(usize),
+}
+
+#[derive(Deserialize)]
+struct Group {
+    by: String,
+    #[serde(default)]
+    precision: Option<usize>,
+    #[serde(default)]
+    accumulators: BTreeMap<String, Accumulator>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Accumulator {
+    Sum(String),
+    Avg(String),
+    Min(String),
+    Max(String),
+    Distinct(String),
+}
+
+#[derive(Deserialize)]
+struct Sort {
+    by: String,
+    #[serde(default)]
+    desc: bool,
+}
+
+/// Running state of one accumulator in one group
+enum AccState {
+    Sum(f64),
+    Avg(f64, usize),
+    Extreme(Option<Value>),
+    Distinct(HashSet<String>),
+}
+
+impl AccState {
+    fn new(acc: &Accumulator) -> Self {
+        match acc {
+            Accumulator::Sum(_) => AccState::Sum(0.0),
+            Accumulator::Avg(_) => AccState::Avg(0.0, 0),
+            Accumulator::Min(_) | Accumulator::Max(_) => AccState::Extreme(None),
+            Accumulator::Distinct(_) => AccState::Distinct(HashSet::new()),
+        }
+    }
+
+    fn add(&mut self, acc: &Accumulator, record: &Value) {
+        let (Accumulator::Sum(path) | Accumulator::Avg(path) | Accumulator::Min(path) | Accumulator::Max(path) | Accumulator::Distinct(path)) = acc;
+        let Some(value) = lookup(record, path).filter(|v| !v.is_null()) else {
+            return;
+        };
+        match self {
+            AccState::Sum(total) => *total += value.as_f64().unwrap_or(0.0),
+            AccState::Avg(total, n) => {
+                if let Some(x) = value.as_f64() {
+                    *total += x;
+                    *n += 1;
+                }
+            }
+            AccState::Extreme(current) => {
+                let wanted = if matches!(acc, Accumulator::Min(_)) { Ordering::Less } else { Ordering::Greater };
+                if current.as_ref().is_none_or(|c| compare(value, c) == Some(wanted)) {
+                    *current = Some(value.clone());
+                }
+            }
+            AccState::Distinct(seen) => {
+                seen.insert(value.as_str().map_or_else(|| value.to_string(), str::to_string));
+            }
+        }
+    }
+
+    fn finish(self) -> Value {
+        match self {
+            AccState::Sum(total) => Value::from(total),
+            AccState::Avg(_, 0) => Value::Null,
+            AccState::Avg(total, n) => Value::from(total / n as f64),
+            AccState::Extreme(value) => value.unwrap_or(Value::Null),
+            AccState::Distinct(seen) => Value::from(seen.len()),
+        }
+    }
+}
+
+fn month(record: &Value) -> Option<String> {
+    let ms = parse_timestamp(record.get("timestamp")?.as_str()?)?;
+    let (year, month, _) = civil_from_days(ms.div_euclid(MS_PER_DAY));
+    Some(format!("{:04}-{:02}", year, month))
+}
+
+/// Group keys of a record; empty when the record has no value for the key
+fn group_keys(group: &Group, record: &Value) -> Vec<Value> {
+    let path = match group.by.as_str() {
+        "domain" => {
+            let unique: BTreeSet<&str> = domains(record).collect();
+            return unique.into_iter().map(Value::from).collect();
+        }
+        "geohash" => {
+            let precision = group.precision.unwrap_or(DEFAULT_GEOHASH_PRECISION);
+            return coordinates(record)
+                .map(|(lat, lon)| Value::from(geohash(lat, lon, precision)))
+                .into_iter()
+                .collect();
+        }
+        "month" => return month(record).map(Value::from).into_iter().collect(),
+        "modality" => return modality(record).map(Value::from).into_iter().collect(),
+        "accommodation" => {
+            let unique: BTreeSet<&str> = accommodations(record).collect();
+            return unique.into_iter().map(Value::from).collect();
+        }
+        "type" => "experience.type",
+        "location" => "context.location.name",
+        "learner" => "learner.id",
+        path => path,
+    };
+    lookup(record, path)
+        .filter(|v| !v.is_null())
+        .cloned()
+        .into_iter()
+        .collect()
+}
+
+fn run_group(records: Vec<Value>, group: &Group) -> Vec<Value> {
+    // Keyed by the key's JSON text so any value type can be a key
+    let mut groups: BTreeMap<String, (Value, usize, Vec<AccState>)> = BTreeMap::new();
+    for record in &records {
+        for key in group_keys(group, record) {
+            let entry = groups
+                .entry(key.to_string())
+                .or_insert_with(|| (key, 0, group.accumulators.values().map(AccState::new).collect()));
+            entry.1 += 1;
+            for (state, acc) in entry.2.iter_mut().zip(group.accumulators.values()) {
+                state.add(acc, record);
+            }
+        }
+    }
+
+    groups
+        .into_values()
+        .map(|(key, count, states)| {
+            let mut row = Map::new();
+            row.insert("key".to_string(), key);
+            row.insert("count".to_string(), Value::from(count));
+            for (name, state) in group.accumulators.keys().zip(states) {
+                row.insert(name.clone(), state.finish());
+            }
+            Value::Object(row)
+        })
+        .collect()
+}
+
+fn run_pipeline(mut records: Vec<Value>, stages: &[Stage]) -> Vec<Value> {
+    for stage in stages {
+        records = match stage {
+            Stage::Filter(filter) => records.into_iter().filter(|r| filter.matches(r)).collect(),
+            Stage::Group(group) => run_group(records, group),
+            Stage::Sort(sort) => {
+                // Missing and incomparable values sort last either way
+                records.sort_by(|a, b| match (lookup(a, &sort.by), lookup(b, &sort.by)) {
+                    (Some(x), Some(y)) => {
+                        let order = compare(x, y).unwrap_or(Ordering::Equal);
+                        if sort.desc { order.reverse() } else { order }
+                    }
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                });
+                records
+            }
+            Stage::Limit(n) => {
+                records.truncate(*n);
+                records
+            }
+            Stage::Skip(n) => records.into_iter().skip(*n).collect(),
+        };
+    }
+    records
+}
+
+fn check_stages(stages: &[Stage]) -> Result<(), String> {
+    for stage in stages {
+        match stage {
+            Stage::Filter(filter) => filter.check()?,
+            Stage::Group(group) if group.accumulators.keys().any(|k| k == "key" || k == "count") => {
+                return Err("accumulators cannot be named key or count".to_string());
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Run an aggregation pipeline (`filter`, `group`, `sort`, `limit`, `skip`
+/// stages) over experiences
+/// Returns the resulting rows as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn aggregate(experiences_json: &str, pipeline_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let stages: Vec<Stage> = from_json(pipeline_json, "pipeline_json")?;
+    check_stages(&stages).map_err(Error::invalid)?;
+
+    to_json(&run_pipeline(experiences, &stages))
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl TenantScope {
+    /// Tenant-scoped `aggregate`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn aggregate(&self, experiences_json: &str, pipeline_json: &str) -> Result<String, Error> {
+        let experiences = self.parse_values(experiences_json)?;
+        let stages: Vec<Stage> = from_json(pipeline_json, "pipeline_json")?;
+        check_stages(&stages).map_err(Error::invalid)?;
+
+        to_json(&run_pipeline(experiences, &stages))
+    }
+}
@@ -0,0 +1,465 @@
+//! Alert rules over live metrics for the chaperone safety view
+//!
+//! Rules are evaluated against a [`crate::metrics::MetricsWindow`] snapshot,
+//! so checking them is as cheap as the snapshot itself:
+//!
+//! ```json
+//! [
+//!   {"id": "quiet", "type": "inactivity", "group": "B", "minutes": 45},
+//!   {"id": "stray", "type": "dispersion", "meters": 500, "max_age_minutes": 15},
+//!   {"id": "busy", "type": "threshold", "window": "hour", "metric": "count", "op": "gt", "value": 40},
+//!   {"id": "lull", "type": "trend", "window": "hour", "baseline": "day", "metric": "count",
+//!    "direction": "drop", "ratio": 3}
+//! ]
+//! ```
+//!
+//! - `inactivity` fires per group (or for the named one) whose last
+//!   activity is more than `minutes` before the snapshot's `as_of`.
+//! - `dispersion` fires per learner whose last position is more than
+//!   `meters` from their group's centroid; `max_age_minutes` ignores stale
+//!   positions.
+//! - `threshold` compares a window metric (`count`, `rate_per_hour`,
+//!   `learners`, `domains.<name>` or `groups.<name>`) with `value`.
+//! - `trend` compares a metric's hourly rate in `window` with its rate in
+//!   the longer `baseline` window and fires when it has risen or dropped by
+//!   at least `ratio`; an empty baseline never fires.
+//!
+//! Every rule may carry a `severity` label (default `warning`) that is
+//! passed through to its alerts.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::geo::haversine_m;
+use crate::group_geometry::centroid;
+use crate::time::{parse_timestamp, MS_PER_HOUR, MS_PER_MINUTE};
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum ThresholdOp {
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+impl ThresholdOp {
+    fn holds(self, actual: f64, value: f64) -> bool {
+        match self {
+            ThresholdOp::Gt => actual > value,
+            ThresholdOp::Gte => actual >= value,
+            ThresholdOp::Lt => actual < value,
+            ThresholdOp::Lte => actual <= value,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            ThresholdOp::Gt => ">",
+            ThresholdOp::Gte => ">=",
+            ThresholdOp::Lt => "<",
+            ThresholdOp::Lte => "<=",
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum Direction {
+    Rise,
+    Drop,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Condition {
+    Inactivity {
+        #[serde(default)]
+        group: Option<String>,
+        minutes: f64,
+    },
+    Dispersion {
+        #[serde(default)]
+        group: Option<String>,
+        meters: f64,
+        #[serde(default)]
+        max_age_minutes: Option<f64>,
+    },
+    Threshold {
+        window: String,
+        metric: String,
+        op: ThresholdOp,
+        value: f64,
+    },
+    Trend {
+        window: String,
+        baseline: String,
+        metric: String,
+        direction: Direction,
+        ratio: f64,
+    },
+}
+
+#[derive(Deserialize)]
+struct AlertRule {
+    id: String,
+    #[serde(default = "default_severity")]
+    severity: String,
+    #[serde(flatten)]
+    condition: Condition,
+}
+
+fn default_severity() -> String {
+    "warning".to_string()
+}
+
+/// The parts of a `MetricsWindow.snapshot` the rules read
+#[derive(Deserialize)]
+struct MetricsState {
+    as_of: String,
+    #[serde(default)]
+    windows: Vec<Value>,
+    #[serde(default)]
+    groups: BTreeMap<String, GroupState>,
+    #[serde(default)]
+    learners: BTreeMap<String, LearnerState>,
+}
+
+#[derive(Deserialize)]
+struct GroupState {
+    last_activity: String,
+}
+
+#[derive(Deserialize)]
+struct LearnerState {
+    #[serde(default)]
+    group: Option<String>,
+    last_seen: String,
+    #[serde(default)]
+    latitude: Option<f64>,
+    #[serde(default)]
+    longitude: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct Alert {
+    rule: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    severity: String,
+    message: String,
+    context: Value,
+}
+
+#[derive(Serialize)]
+struct AlertReport {
+    as_of: String,
+    rules: usize,
+    fired: Vec<Alert>,
+}
+
+fn positive(value: f64, field: &str, rule: &AlertRule) -> Result<(), Error> {
+    if value.is_nan() || value <= 0.0 {
+        return Err(Error::invalid(format!("{} must be positive in rule {}", field, rule.id)).with("rule", rule.id.as_str()));
+    }
+    Ok(())
+}
+
+fn check_metric(metric: &str, rule: &AlertRule) -> Result<(), Error> {
+    let known = matches!(metric, "count" | "rate_per_hour" | "learners")
+        || metric.strip_prefix("domains.").or_else(|| metric.strip_prefix("groups.")).is_some_and(|k| !k.is_empty());
+    if !known {
+        return Err(Error::invalid(format!("unknown metric {} in rule {}", metric, rule.id))
+            .with("rule", rule.id.as_str())
+            .with("metric", metric));
+    }
+    Ok(())
+}
+
+fn check_rule(rule: &AlertRule) -> Result<(), Error> {
+    match rule.condition {
+        Condition::Inactivity { minutes, .. } => positive(minutes, "minutes", rule),
+        Condition::Dispersion { meters, max_age_minutes, .. } => {
+            positive(meters, "meters", rule)?;
+            max_age_minutes.map_or(Ok(()), |age| positive(age, "max_age_minutes", rule))
+        }
+        Condition::Threshold { ref metric, value, .. } => {
+            if value.is_nan() {
+                return Err(Error::invalid(format!("value must be a number in rule {}", rule.id)).with("rule", rule.id.as_str()));
+            }
+            check_metric(metric, rule)
+        }
+        Condition::Trend { ref metric, ratio, .. } => {
+            if ratio.is_nan() || ratio < 1.0 {
+                return Err(Error::invalid(format!("ratio must be at least 1 in rule {}", rule.id)).with("rule", rule.id.as_str()));
+            }
+            check_metric(metric, rule)
+        }
+    }
+}
+
+fn find_window<'a>(state: &'a MetricsState, name: &str, rule: &AlertRule) -> Result<&'a Value, Error> {
+    state
+        .windows
+        .iter()
+        .find(|w| w.get("name").and_then(Value::as_str) == Some(name))
+        .ok_or_else(|| {
+            Error::not_found(format!("window {} used by rule {} is not in the metrics state", name, rule.id))
+                .with("rule", rule.id.as_str())
+                .with("window", name)
+        })
+}
+
+/// A metric's value in a window; domains and groups absent from the window
+/// count as zero
+fn metric(window: &Value, metric: &str) -> f64 {
+    lookup(window, metric).and_then(Value::as_f64).unwrap_or(0.0)
+}
+
+fn window_hours(window: &Value) -> f64 {
+    window.get("minutes").and_then(Value::as_f64).unwrap_or(0.0) * MS_PER_MINUTE as f64 / MS_PER_HOUR as f64
+}
+
+/// Per-hour rate of a metric; `rate_per_hour` already is one
+fn hourly(window: &Value, name: &str) -> Option<f64> {
+    let value = metric(window, name);
+    if name == "rate_per_hour" {
+        return Some(value);
+    }
+    let hours = window_hours(window);
+    (hours > 0.0).then(|| value / hours)
+}
+
+fn inactivity(state: &MetricsState, as_of: i64, rule: &AlertRule, group: Option<&str>, minutes: f64, out: &mut Vec<Alert>) {
+    let mut check = |name: &str, last: Option<&str>| {
+        let last_ms = last.and_then(parse_timestamp);
+        let idle = last_ms.map(|ms| (as_of - ms) as f64 / MS_PER_MINUTE as f64);
+        if idle.is_none_or(|idle| idle > minutes) {
+            let message = match idle {
+                Some(idle) => format!("no activity from group {} for {} minutes", name, idle.floor()),
+                None => format!("no activity recorded from group {}", name),
+            };
+            out.push(Alert {
+                rule: rule.id.clone(),
+                kind: "inactivity",
+                severity: rule.severity.clone(),
+                message,
+                context: json!({"group": name, "last_activity": last, "idle_minutes": idle, "minutes": minutes}),
+            });
+        }
+    };
+    match group {
+        Some(name) => check(name, state.groups.get(name).map(|g| g.last_activity.as_str())),
+        None => {
+            for (name, g) in &state.groups {
+                check(name, Some(&g.last_activity));
+            }
+        }
+    }
+}
+
+fn dispersion(
+    state: &MetricsState,
+    as_of: i64,
+    rule: &AlertRule,
+    group: Option<&str>,
+    meters: f64,
+    max_age_minutes: Option<f64>,
+    out: &mut Vec<Alert>,
+) {
+    // Current positions by group; learners without a group form one cohort
+    let mut cohorts: BTreeMap<Option<&str>, Vec<(&str, f64, f64)>> = BTreeMap::new();
+    for (id, learner) in &state.learners {
+        let (Some(lat), Some(lon)) = (learner.latitude, learner.longitude) else {
+            continue;
+        };
+        let fresh = max_age_minutes.is_none_or(|age| {
+            parse_timestamp(&learner.last_seen).is_some_and(|ms| (as_of - ms) as f64 <= age * MS_PER_MINUTE as f64)
+        });
+        let cohort = learner.group.as_deref();
+        if fresh && group.is_none_or(|g| cohort == Some(g)) {
+            cohorts.entry(cohort).or_default().push((id, lat, lon));
+        }
+    }
+    for (cohort, members) in cohorts {
+        if members.len() < 2 {
+            continue;
+        }
+        let points: Vec<(f64, f64)> = members.iter().map(|m| (m.1, m.2)).collect();
+        let Some((c_lat, c_lon)) = centroid(&points) else {
+            continue;
+        };
+        for (id, lat, lon) in members {
+            let distance = haversine_m(lat, lon, c_lat, c_lon);
+            if distance > meters {
+                let message = match cohort {
+                    Some(g) => format!("learner {} is {} m from the group {} centroid", id, distance.round(), g),
+                    None => format!("learner {} is {} m from the group centroid", id, distance.round()),
+                };
+                out.push(Alert {
+                    rule: rule.id.clone(),
+                    kind: "dispersion",
+                    severity: rule.severity.clone(),
+                    message,
+                    context: json!({
+                        "learner": id,
+                        "group": cohort,
+                        "distance_m": distance,
+                        "meters": meters,
+                        "latitude": lat,
+                        "longitude": lon,
+                        "centroid": {"latitude": c_lat, "longitude": c_lon},
+                    }),
+                });
+            }
+        }
+    }
+}
+
+fn evaluate(state: &MetricsState, rules: &[AlertRule]) -> Result<Vec<Alert>, Error> {
+    let as_of = parse_timestamp(&state.as_of)
+        .ok_or_else(|| Error::invalid(format!("invalid as_of in metrics state: {}", state.as_of)))?;
+    let mut fired = Vec::new();
+    for rule in rules {
+        match rule.condition {
+            Condition::Inactivity { ref group, minutes } => {
+                inactivity(state, as_of, rule, group.as_deref(), minutes, &mut fired)
+            }
+            Condition::Dispersion { ref group, meters, max_age_minutes } => {
+                dispersion(state, as_of, rule, group.as_deref(), meters, max_age_minutes, &mut fired)
+            }
+            Condition::Threshold { ref window, metric: ref name, op, value } => {
+                let actual = metric(find_window(state, window, rule)?, name);
+                if op.holds(actual, value) {
+                    fired.push(Alert {
+                        rule: rule.id.clone(),
+                        kind: "threshold",
+                        severity: rule.severity.clone(),
+                        message: format!("{} {} is {} ({} {})", window, name, actual, op.symbol(), value),
+                        context: json!({"window": window, "metric": name, "actual": actual, "value": value}),
+                    });
+                }
+            }
+            Condition::Trend { ref window, ref baseline, metric: ref name, direction, ratio } => {
+                let recent = hourly(find_window(state, window, rule)?, name);
+                let base = hourly(find_window(state, baseline, rule)?, name);
+                let (Some(recent), Some(base)) = (recent, base) else {
+                    continue;
+                };
+                if base <= 0.0 {
+                    continue;
+                }
+                let change = recent / base;
+                let (holds, verb) = match direction {
+                    Direction::Rise => (change >= ratio, "risen"),
+                    Direction::Drop => (change * ratio <= 1.0, "dropped"),
+                };
+                if holds {
+                    fired.push(Alert {
+                        rule: rule.id.clone(),
+                        kind: "trend",
+                        severity: rule.severity.clone(),
+                        message: format!("{} per hour has {} to {:.2}x its {} rate over the last {}", name, verb, change, baseline, window),
+                        context: json!({
+                            "window": window,
+                            "baseline": baseline,
+                            "metric": name,
+                            "rate_per_hour": recent,
+                            "baseline_rate_per_hour": base,
+                            "change": change,
+                        }),
+                    });
+                }
+            }
+        }
+    }
+    Ok(fired)
+}
+
+/// Evaluate alert rules against a `MetricsWindow.snapshot`
+/// Returns `{as_of, rules, fired: [{rule, type, severity, message, context}]}`
+/// as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn evaluate_alerts(metrics_state: &str, alert_rules_json: &str) -> Result<String, Error> {
+    let state: MetricsState = from_json(metrics_state, "metrics_state")?;
+    let rules: Vec<AlertRule> = from_json(alert_rules_json, "alert_rules_json")?;
+    for rule in &rules {
+        check_rule(rule)?;
+    }
+    let fired = evaluate(&state, &rules)?;
+    to_json(&AlertReport { as_of: state.as_of, rules: rules.len(), fired })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> Value {
+        json!({
+            "as_of": "2024-05-01T12:00:00Z",
+            "windows": [
+                {"name": "hour", "minutes": 60, "count": 40, "learners": 5, "domains": {"art": 3}},
+                {"name": "day", "minutes": 1440, "count": 960},
+            ],
+            "groups": {"A": {"last_activity": "2024-05-01T11:15:00Z"}, "B": {"last_activity": "2024-05-01T11:14:59Z"}},
+        })
+    }
+
+    /// Ids of the rules that fired
+    fn fired(rules: Value) -> Vec<String> {
+        let report: Value = serde_json::from_str(&evaluate_alerts(&state().to_string(), &rules.to_string()).unwrap()).unwrap();
+        report["fired"].as_array().unwrap().iter().map(|a| a["rule"].as_str().unwrap().to_string()).collect()
+    }
+
+    fn threshold(op: &str, metric: &str, value: f64) -> Value {
+        json!({"id": format!("{} {} {}", metric, op, value), "type": "threshold", "window": "hour", "metric": metric, "op": op, "value": value})
+    }
+
+    #[test]
+    fn threshold_equality_fires_only_inclusive_ops() {
+        let rules = json!(["gt", "gte", "lt", "lte"].map(|op| threshold(op, "count", 40.0)));
+        assert_eq!(fired(rules), ["count gte 40", "count lte 40"]);
+        assert_eq!(fired(json!([threshold("gt", "count", 39.999)])), ["count gt 39.999"]);
+        // Missing domains count as zero
+        assert_eq!(fired(json!([threshold("gte", "domains.art", 3.0), threshold("lte", "domains.music", 0.0)])).len(), 2);
+    }
+
+    #[test]
+    fn inactivity_fires_strictly_after_the_limit() {
+        let rules = json!([{"id": "quiet", "type": "inactivity", "minutes": 45}]);
+        let report: Value = serde_json::from_str(&evaluate_alerts(&state().to_string(), &rules.to_string()).unwrap()).unwrap();
+        assert_eq!(report["fired"].as_array().unwrap().len(), 1);
+        assert_eq!(report["fired"][0]["context"]["group"], "B");
+        assert_eq!(report["fired"][0]["severity"], "warning");
+        assert_eq!(fired(json!([{"id": "missing", "type": "inactivity", "group": "C", "minutes": 45}])), ["missing"]);
+    }
+
+    #[test]
+    fn trend_fires_at_exactly_the_ratio() {
+        // 40 an hour against a day at 40 an hour: no change
+        let trend = |direction: &str, ratio: f64, baseline: &str| {
+            json!({"id": format!("{} {}", direction, ratio), "type": "trend", "window": "hour", "baseline": baseline,
+                   "metric": "count", "direction": direction, "ratio": ratio})
+        };
+        assert_eq!(fired(json!([trend("rise", 1.0, "day"), trend("drop", 1.0, "day")])), ["rise 1", "drop 1"]);
+        assert!(fired(json!([trend("rise", 1.01, "day"), trend("drop", 1.01, "day")])).is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        for rule in [
+            json!({"id": "r", "type": "threshold", "window": "hour", "metric": "bogus", "op": "gt", "value": 1}),
+            json!({"id": "r", "type": "inactivity", "minutes": 0}),
+            json!({"id": "r", "type": "trend", "window": "hour", "baseline": "day", "metric": "count", "direction": "rise", "ratio": 0.5}),
+        ] {
+            assert!(evaluate_alerts(&state().to_string(), &json!([rule]).to_string()).is_err());
+        }
+        let missing = evaluate_alerts(&state().to_string(), &json!([threshold("gt", "count", 1.0)]).to_string().replace("hour", "week"));
+        assert_eq!(missing.unwrap_err().kind(), crate::error::ErrorKind::NotFound);
+    }
+}
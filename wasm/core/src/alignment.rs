@@ -0,0 +1,166 @@
+//! Pathway alignment between learners
+//!
+//! [`align_pathways`] lines two learners' domain sequences up with
+//! Needleman–Wunsch: the minimum-cost edit turning one into the other, where
+//! skipping a step costs `gap_cost` and pairing two steps costs
+//! `substitution_cost × (1 − similarity)`. Identical domains have
+//! similarity 1 and unrelated ones 0, unless the caller supplies a
+//! similarity table such as `{"biology": {"ecology": 0.8}}` (symmetric;
+//! either direction may be given). A step with several domains takes the
+//! most similar pair.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::domain_transitions::{pathway_steps, PathwayStep, Step};
+use crate::error::{from_json, to_json, Error};
+
+/// Steps per sequence; the cost table is quadratic in length
+const MAX_STEPS: usize = 2_000;
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct AlignOptions {
+    gap_cost: f64,
+    substitution_cost: f64,
+    similarity: BTreeMap<String, BTreeMap<String, f64>>,
+}
+
+impl Default for AlignOptions {
+    fn default() -> Self {
+        Self {
+            gap_cost: 1.0,
+            substitution_cost: 1.0,
+            similarity: BTreeMap::new(),
+        }
+    }
+}
+
+impl AlignOptions {
+    fn domain_similarity(&self, a: &str, b: &str) -> f64 {
+        if a == b {
+            return 1.0;
+        }
+        let get = |x: &str, y: &str| self.similarity.get(x).and_then(|row| row.get(y)).copied();
+        get(a, b).or_else(|| get(b, a)).unwrap_or(0.0)
+    }
+
+    fn pair_cost(&self, a: &Step, b: &Step) -> f64 {
+        let best = a
+            .iter()
+            .flat_map(|x| b.iter().map(move |y| (x, y)))
+            .map(|(x, y)| self.domain_similarity(x, y))
+            .fold(0.0, f64::max);
+        self.substitution_cost * (1.0 - best)
+    }
+}
+
+#[derive(Serialize)]
+struct AlignedStep<'a> {
+    op: &'static str,
+    a: Option<&'a Step<'a>>,
+    b: Option<&'a Step<'a>>,
+    cost: f64,
+}
+
+#[derive(Serialize)]
+struct Alignment<'a> {
+    distance: f64,
+    /// `1 − distance / (gap_cost × (len_a + len_b))`, 1 for identical
+    /// pathways and 0 when nothing lines up
+    similarity: f64,
+    matches: usize,
+    substitutions: usize,
+    gaps: usize,
+    alignment: Vec<AlignedStep<'a>>,
+}
+
+fn parse_pathway<'a>(steps: &'a [PathwayStep], argument: &str) -> Result<Vec<Step<'a>>, Error> {
+    let steps = pathway_steps(steps);
+    if steps.len() > MAX_STEPS {
+        return Err(Error::invalid(format!("{} has more than {} steps", argument, MAX_STEPS)).with("argument", argument));
+    }
+    Ok(steps)
+}
+
+/// Align two learners' domain pathways
+/// Each sequence is oldest first: experiences, domain names, or arrays of
+/// domain names per step. `options_json` sets `gap_cost`,
+/// `substitution_cost` (empty for 1 and 1) and a `similarity` table
+/// Returns `{distance, similarity, matches, substitutions, gaps,
+/// alignment: [{op, a, b, cost}]}` as JSON, where `op` is `match`,
+/// `substitute`, `delete` (a step only in A) or `insert` (only in B)
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn align_pathways(seq_a_json: &str, seq_b_json: &str, options_json: &str) -> Result<String, Error> {
+    let seq_a: Vec<PathwayStep> = from_json(seq_a_json, "seq_a_json")?;
+    let seq_b: Vec<PathwayStep> = from_json(seq_b_json, "seq_b_json")?;
+    let options: AlignOptions = if options_json.trim().is_empty() {
+        AlignOptions::default()
+    } else {
+        from_json(options_json, "options_json")?
+    };
+    for (name, value) in [("gap_cost", options.gap_cost), ("substitution_cost", options.substitution_cost)] {
+        if !value.is_finite() || value < 0.0 {
+            return Err(Error::invalid(format!("{} must be a non-negative number", name)).with("field", name));
+        }
+    }
+    if options.similarity.values().flat_map(BTreeMap::values).any(|s| !(0.0..=1.0).contains(s)) {
+        return Err(Error::invalid("similarity values must be between 0 and 1"));
+    }
+    let a = parse_pathway(&seq_a, "seq_a_json")?;
+    let b = parse_pathway(&seq_b, "seq_b_json")?;
+
+    // cost[i][j]: aligning the first i steps of A with the first j of B
+    let (n, m) = (a.len(), b.len());
+    let mut cost = vec![vec![0.0; m + 1]; n + 1];
+    for i in 1..=n {
+        cost[i][0] = cost[i - 1][0] + options.gap_cost;
+    }
+    for j in 1..=m {
+        cost[0][j] = cost[0][j - 1] + options.gap_cost;
+    }
+    for i in 1..=n {
+        for j in 1..=m {
+            let pair = cost[i - 1][j - 1] + options.pair_cost(&a[i - 1], &b[j - 1]);
+            let delete = cost[i - 1][j] + options.gap_cost;
+            let insert = cost[i][j - 1] + options.gap_cost;
+            cost[i][j] = pair.min(delete).min(insert);
+        }
+    }
+
+    // Trace back, preferring pairs over gaps on ties
+    let mut steps = Vec::new();
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 {
+            let pair_cost = options.pair_cost(&a[i - 1], &b[j - 1]);
+            if cost[i][j] == cost[i - 1][j - 1] + pair_cost {
+                let op = if pair_cost == 0.0 { "match" } else { "substitute" };
+                steps.push(AlignedStep { op, a: Some(&a[i - 1]), b: Some(&b[j - 1]), cost: pair_cost });
+                (i, j) = (i - 1, j - 1);
+                continue;
+            }
+        }
+        if i > 0 && cost[i][j] == cost[i - 1][j] + options.gap_cost {
+            steps.push(AlignedStep { op: "delete", a: Some(&a[i - 1]), b: None, cost: options.gap_cost });
+            i -= 1;
+        } else {
+            steps.push(AlignedStep { op: "insert", a: None, b: Some(&b[j - 1]), cost: options.gap_cost });
+            j -= 1;
+        }
+    }
+    steps.reverse();
+
+    let distance = cost[n][m];
+    let worst = options.gap_cost * (n + m) as f64;
+    to_json(&Alignment {
+        distance,
+        similarity: if worst > 0.0 { (1.0 - distance / worst).clamp(0.0, 1.0) } else { 1.0 },
+        matches: steps.iter().filter(|s| s.op == "match").count(),
+        substitutions: steps.iter().filter(|s| s.op == "substitute").count(),
+        gaps: steps.iter().filter(|s| s.a.is_none() || s.b.is_none()).count(),
+        alignment: steps,
+    })
+}
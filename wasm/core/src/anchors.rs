@@ -0,0 +1,190 @@
+//! Anchor location inference
+//!
+//! Distance-band analytics ([`crate::distance_bands`]) need each learner's
+//! home and school, which most rosters do not record. [`infer_anchors`]
+//! estimates them from when and where a learner records experiences:
+//!
+//! - `home`: the most frequent location at night (21:00-06:00),
+//! - `school`: the most frequent location on weekday school hours
+//!   (Monday-Friday, 08:00-16:00),
+//!
+//! reading hours in each timestamp's own UTC offset. Both are sensitive
+//! inferences, so nothing is computed unless the caller names the kinds it
+//! has consent for in `opt_in`, and every result is snapped to a grid of at
+//! least 1 km: the reported position is the centre of that cell, never an
+//! observed one. The `anchors` in the result can be passed straight to
+//! `distance_band_stats`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::geo::EARTH_RADIUS_M;
+use crate::identity::canonical_learner_id;
+use crate::numeric::cos;
+use crate::query::coordinates;
+use crate::time::{local_ms, MS_PER_DAY, MS_PER_HOUR};
+
+const KINDS: &[&str] = &["home", "school"];
+/// Anchors are never reported more precisely than this
+const MIN_PRECISION_M: f64 = 1_000.0;
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct AnchorOptions {
+    /// Anchor kinds the learner has consented to infer
+    opt_in: Vec<String>,
+    precision_m: f64,
+    min_observations: usize,
+    min_days: usize,
+    /// Share of the window's observations the winning cell must hold
+    min_share: f64,
+}
+
+impl Default for AnchorOptions {
+    fn default() -> Self {
+        Self {
+            opt_in: Vec::new(),
+            precision_m: MIN_PRECISION_M,
+            min_observations: 3,
+            min_days: 2,
+            min_share: 0.5,
+        }
+    }
+}
+
+/// Observations in one grid cell
+struct Tally {
+    centre: (f64, f64),
+    observations: usize,
+    /// Local days seen, so one long evening does not make a home
+    days: BTreeSet<i64>,
+}
+
+#[derive(Serialize)]
+struct InferredAnchor {
+    kind: &'static str,
+    learner: String,
+    latitude: f64,
+    longitude: f64,
+    precision_m: f64,
+    observations: usize,
+    days: usize,
+    share: f64,
+}
+
+#[derive(Serialize)]
+struct Skipped {
+    kind: &'static str,
+    reason: &'static str,
+}
+
+#[derive(Serialize)]
+struct AnchorReport {
+    learner: String,
+    anchors: Vec<InferredAnchor>,
+    skipped: Vec<Skipped>,
+}
+
+/// Whether a local wall-clock time falls in the window for `kind`
+fn in_window(kind: &str, local: i64) -> bool {
+    let days = local.div_euclid(MS_PER_DAY);
+    let hour = local.rem_euclid(MS_PER_DAY) / MS_PER_HOUR;
+    // 1970-01-01 was a Thursday; 0 is Monday
+    let weekday = (days + 3).rem_euclid(7);
+    match kind {
+        "home" => !(6..21).contains(&hour),
+        _ => weekday < 5 && (8..16).contains(&hour),
+    }
+}
+
+/// Cell of a grid of `precision_m` squares, and its centre
+fn cell(lat: f64, lon: f64, precision_m: f64) -> ((i64, i64), (f64, f64)) {
+    let lat_step = precision_m / (EARTH_RADIUS_M * std::f64::consts::PI / 180.0);
+    let row = (lat / lat_step).floor() as i64;
+    let centre_lat = (row as f64 + 0.5) * lat_step;
+    let lon_step = (lat_step / cos(centre_lat.to_radians()).max(0.01)).min(360.0);
+    let col = (lon / lon_step).floor() as i64;
+    ((row, col), (centre_lat.clamp(-90.0, 90.0), ((col as f64 + 0.5) * lon_step).clamp(-180.0, 180.0)))
+}
+
+/// Estimate a learner's home and school locations from recurring
+/// night-time and weekday patterns
+/// `options_json` must list the consented kinds in `opt_in` (`home`,
+/// `school`), and may set `precision_m` (at least and by default 1000),
+/// `min_observations` (3), `min_days` (2) and `min_share` (0.5)
+/// Returns `{learner, anchors: [{kind, learner, latitude, longitude,
+/// precision_m, observations, days, share}], skipped: [{kind, reason}]}` as
+/// JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn infer_anchors(experiences_json: &str, learner_id: &str, options_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let options: AnchorOptions = if options_json.trim().is_empty() {
+        AnchorOptions::default()
+    } else {
+        from_json(options_json, "options_json")?
+    };
+    if options.opt_in.is_empty() {
+        return Err(Error::invalid("anchor inference needs an explicit opt_in listing home and/or school"));
+    }
+    if let Some(kind) = options.opt_in.iter().find(|k| !KINDS.contains(&k.as_str())) {
+        return Err(Error::invalid(format!("unknown anchor kind: {}", kind)).with("kind", kind.as_str()));
+    }
+    if options.precision_m.is_nan() || options.precision_m < MIN_PRECISION_M {
+        return Err(Error::invalid(format!("precision_m must be at least {}", MIN_PRECISION_M))
+            .with("precision_m", options.precision_m));
+    }
+    if !(0.0..=1.0).contains(&options.min_share) {
+        return Err(Error::invalid("min_share must be between 0 and 1"));
+    }
+
+    let learner = canonical_learner_id(learner_id);
+    let observations: Vec<(i64, (f64, f64))> = experiences
+        .iter()
+        .filter(|exp| lookup(exp, "learner.id").and_then(Value::as_str).map(canonical_learner_id).as_deref() == Some(&learner))
+        .filter_map(|exp| Some((lookup(exp, "timestamp").and_then(Value::as_str).and_then(local_ms)?, coordinates(exp)?)))
+        .collect();
+
+    let mut report = AnchorReport { learner, anchors: Vec::new(), skipped: Vec::new() };
+    for &kind in KINDS.iter().filter(|k| options.opt_in.iter().any(|o| o == *k)) {
+        let mut cells: BTreeMap<(i64, i64), Tally> = BTreeMap::new();
+        let mut total = 0;
+        for &(local, (lat, lon)) in observations.iter().filter(|(local, _)| in_window(kind, *local)) {
+            // Night-time after midnight belongs to the previous evening
+            let day = (local - 6 * MS_PER_HOUR).div_euclid(MS_PER_DAY);
+            let (key, centre) = cell(lat, lon, options.precision_m);
+            let tally = cells.entry(key).or_insert_with(|| Tally { centre, observations: 0, days: BTreeSet::new() });
+            tally.observations += 1;
+            tally.days.insert(day);
+            total += 1;
+        }
+        let best = cells
+            .into_values()
+            .max_by(|a, b| a.observations.cmp(&b.observations).then_with(|| a.days.len().cmp(&b.days.len())));
+        let reason = match &best {
+            None => Some("no_observations"),
+            Some(t) if t.observations < options.min_observations => Some("too_few_observations"),
+            Some(t) if t.days.len() < options.min_days => Some("too_few_days"),
+            Some(t) if (t.observations as f64) < options.min_share * total as f64 => Some("no_dominant_location"),
+            Some(_) => None,
+        };
+        match (best, reason) {
+            (Some(best), None) => report.anchors.push(InferredAnchor {
+                kind,
+                learner: report.learner.clone(),
+                latitude: best.centre.0,
+                longitude: best.centre.1,
+                precision_m: options.precision_m,
+                observations: best.observations,
+                days: best.days.len(),
+                share: best.observations as f64 / total as f64,
+            }),
+            (_, reason) => report.skipped.push(Skipped { kind, reason: reason.unwrap_or("no_observations") }),
+        }
+    }
+    to_json(&report)
+}
@@ -0,0 +1,201 @@
+//! Plausibility checks over experience logs
+//!
+//! Records can be individually valid yet implausible together: a learner
+//! who is in London and Sydney an hour apart, hundreds of captures in a
+//! minute, a reused id, or a log that jumps back in time. These are reported
+//! as warnings for review rather than validation failures, since each has
+//! legitimate explanations (imported backlogs, clock drift, shared devices).
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::geo::haversine_m;
+use crate::query::coordinates;
+use crate::time::parse_timestamp;
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct AnomalyOptions {
+    /// Fastest plausible speed between consecutive experiences
+    max_speed_kmh: f64,
+    /// More records than this by one learner within a minute is a burst
+    max_per_minute: usize,
+}
+
+impl Default for AnomalyOptions {
+    fn default() -> Self {
+        AnomalyOptions { max_speed_kmh: 300.0, max_per_minute: 10 }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum AnomalyKind {
+    ImpossibleTravel,
+    Burst,
+    DuplicateId,
+    OutOfOrder,
+}
+
+#[derive(Serialize)]
+struct Anomaly {
+    kind: AnomalyKind,
+    ids: Vec<Value>,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    speed_kmh: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    count: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct AnomalyReport {
+    records: usize,
+    warnings: Vec<Anomaly>,
+}
+
+struct Stamped<'a> {
+    id: Value,
+    ms: i64,
+    exp: &'a Value,
+}
+
+fn record_id(exp: &Value) -> Value {
+    exp.get("id").cloned().unwrap_or(Value::Null)
+}
+
+fn check_ids_and_order(experiences: &[Value], warnings: &mut Vec<Anomaly>) {
+    let mut seen: BTreeMap<&str, usize> = BTreeMap::new();
+    for id in experiences.iter().filter_map(|exp| exp.get("id").and_then(Value::as_str)) {
+        *seen.entry(id).or_insert(0) += 1;
+    }
+    for (id, n) in seen.into_iter().filter(|&(_, n)| n > 1) {
+        warnings.push(Anomaly {
+            kind: AnomalyKind::DuplicateId,
+            ids: vec![Value::from(id)],
+            message: format!("id {} appears {} times", id, n),
+            speed_kmh: None,
+            count: Some(n),
+        });
+    }
+
+    let mut latest: Option<(i64, &Value)> = None;
+    for exp in experiences {
+        let Some(ms) = exp.get("timestamp").and_then(Value::as_str).and_then(parse_timestamp) else {
+            continue;
+        };
+        match latest {
+            Some((prev, prev_exp)) if ms < prev => warnings.push(Anomaly {
+                kind: AnomalyKind::OutOfOrder,
+                ids: vec![record_id(prev_exp), record_id(exp)],
+                message: format!("timestamp is {} s earlier than a preceding record", (prev - ms) / 1000),
+                speed_kmh: None,
+                count: None,
+            }),
+            _ => latest = Some((ms, exp)),
+        }
+    }
+}
+
+fn check_learner(records: &[Stamped], options: &AnomalyOptions, warnings: &mut Vec<Anomaly>) {
+    for pair in records.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        let (Some((lat1, lon1)), Some((lat2, lon2))) = (coordinates(a.exp), coordinates(b.exp)) else {
+            continue;
+        };
+        let km = haversine_m(lat1, lon1, lat2, lon2) / 1000.0;
+        let hours = (b.ms - a.ms) as f64 / 3_600_000.0;
+        // Simultaneous records in different places imply infinite speed
+        let speed = if hours > 0.0 { km / hours } else if km > 0.0 { f64::INFINITY } else { 0.0 };
+        if speed > options.max_speed_kmh {
+            warnings.push(Anomaly {
+                kind: AnomalyKind::ImpossibleTravel,
+                ids: vec![a.id.clone(), b.id.clone()],
+                message: format!("{:.0} km in {:.0} min", km, hours * 60.0),
+                speed_kmh: speed.is_finite().then_some(speed),
+                count: None,
+            });
+        }
+    }
+
+    // Maximal runs where some 60 s window holds too many records
+    let mut start = 0;
+    let mut burst: Option<(usize, usize)> = None;
+    for end in 0..records.len() {
+        while records[end].ms - records[start].ms >= 60_000 {
+            start += 1;
+        }
+        if end - start < options.max_per_minute {
+            continue;
+        }
+        burst = match burst {
+            Some((from, to)) if start <= to => Some((from, end)),
+            previous => {
+                if let Some((from, to)) = previous {
+                    warnings.push(burst_warning(&records[from..=to]));
+                }
+                Some((start, end))
+            }
+        };
+    }
+    if let Some((from, to)) = burst {
+        warnings.push(burst_warning(&records[from..=to]));
+    }
+}
+
+fn burst_warning(run: &[Stamped]) -> Anomaly {
+    let seconds = (run[run.len() - 1].ms - run[0].ms) / 1000;
+    Anomaly {
+        kind: AnomalyKind::Burst,
+        ids: run.iter().map(|r| r.id.clone()).collect(),
+        message: format!("{} records within {} s", run.len(), seconds),
+        speed_kmh: None,
+        count: Some(run.len()),
+    }
+}
+
+fn detect(experiences: &[Value], options: &AnomalyOptions) -> Vec<Anomaly> {
+    let mut warnings = Vec::new();
+    check_ids_and_order(experiences, &mut warnings);
+
+    let mut by_learner: HashMap<&str, Vec<Stamped>> = HashMap::new();
+    for exp in experiences {
+        let Some(ms) = exp.get("timestamp").and_then(Value::as_str).and_then(parse_timestamp) else {
+            continue;
+        };
+        let learner = lookup(exp, "learner.id").and_then(Value::as_str).unwrap_or("");
+        by_learner.entry(learner).or_default().push(Stamped { id: record_id(exp), ms, exp });
+    }
+    let mut learners: Vec<_> = by_learner.into_iter().collect();
+    learners.sort_by(|a, b| a.0.cmp(b.0));
+    for (_, mut records) in learners {
+        records.sort_by_key(|r| r.ms);
+        check_learner(&records, options, &mut warnings);
+    }
+
+    warnings
+}
+
+/// Flag implausible records: impossible travel, capture bursts, duplicate
+/// ids and out-of-order timestamps
+/// Returns `{records, warnings}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn detect_anomalies(experiences_json: &str, options_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let options: AnomalyOptions = if options_json.trim().is_empty() {
+        AnomalyOptions::default()
+    } else {
+        from_json(options_json, "options_json")?
+    };
+    if options.max_speed_kmh.is_nan() || options.max_speed_kmh <= 0.0 {
+        return Err(Error::invalid("max_speed_kmh must be positive"));
+    }
+
+    let report = AnomalyReport { records: experiences.len(), warnings: detect(&experiences, &options) };
+    to_json(&report)
+}
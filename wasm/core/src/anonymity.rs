@@ -0,0 +1,363 @@
+//! k-anonymity checks for location data before export
+//!
+//! Each geo-tagged record's quasi-identifier is its (geohash cell, UTC day).
+//! A cell-day visited by fewer than `k` distinct learners can single those
+//! learners out, so the report lists them and evaluates coarser geohash
+//! precisions (and dropping coordinates altogether) as remedies.
+//!
+//! `k_anonymize` applies the finest of those remedies that leaves no class
+//! below `k`, replacing coordinates with the geohash cell and timestamps with
+//! the day. Records on a day with fewer than `k` learners cannot be hidden by
+//! any coarsening and are suppressed first. Only coordinates and time are
+//! generalised; other fields are released as they are.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::geo::geohash;
+use crate::privacy::remove_at_path;
+use crate::query::coordinates;
+use crate::time::{format_timestamp, parse_timestamp, MS_PER_DAY};
+
+struct Record<'a> {
+    /// Position in the input
+    index: usize,
+    id: &'a str,
+    learner: &'a str,
+    lat: f64,
+    lon: f64,
+    day: i64,
+}
+
+#[derive(Serialize)]
+struct AtRiskLearner {
+    learner: String,
+    experience_ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct CoarseningOption {
+    /// Geohash precision, or `null` for "drop coordinates"
+    precision: Option<usize>,
+    violating_classes: usize,
+    at_risk_learners: usize,
+}
+
+#[derive(Serialize)]
+struct KAnonymityReport {
+    k: usize,
+    precision: usize,
+    classes: usize,
+    violating_classes: usize,
+    at_risk: Vec<AtRiskLearner>,
+    options: Vec<CoarseningOption>,
+    /// Finest option that satisfies k, if any
+    recommendation: Option<CoarseningOption>,
+}
+
+/// Records sharing one quasi-identifier value
+#[derive(Default)]
+struct EquivalenceClass<'a> {
+    learners: BTreeSet<&'a str>,
+    records: Vec<usize>,
+}
+
+/// Equivalence classes at `precision` (`None`: day only), keyed by
+/// (geohash cell, day)
+fn classes<'a>(records: &[Record<'a>], precision: Option<usize>) -> BTreeMap<(String, i64), EquivalenceClass<'a>> {
+    let mut classes: BTreeMap<(String, i64), EquivalenceClass> = BTreeMap::new();
+    for (index, r) in records.iter().enumerate() {
+        let cell = precision.map(|p| geohash(r.lat, r.lon, p)).unwrap_or_default();
+        let class = classes.entry((cell, r.day)).or_default();
+        class.learners.insert(r.learner);
+        class.records.push(index);
+    }
+    classes
+}
+
+fn evaluate(records: &[Record], k: usize, precision: Option<usize>) -> CoarseningOption {
+    let classes = classes(records, precision);
+    let violating: Vec<_> = classes.values().filter(|class| class.learners.len() < k).collect();
+    let learners: BTreeSet<&str> = violating.iter().flat_map(|class| class.learners.iter().copied()).collect();
+    CoarseningOption {
+        precision,
+        violating_classes: violating.len(),
+        at_risk_learners: learners.len(),
+    }
+}
+
+/// Geo-tagged experiences with a timestamp; the rest carry no quasi-identifier
+fn records(experiences: &[Value]) -> Vec<Record<'_>> {
+    experiences
+        .iter()
+        .enumerate()
+        .filter_map(|(index, exp)| {
+            let (lat, lon) = coordinates(exp)?;
+            let ts = exp.get("timestamp").and_then(Value::as_str).and_then(parse_timestamp)?;
+            Some(Record {
+                index,
+                id: exp.get("id").and_then(Value::as_str).unwrap_or_default(),
+                learner: lookup(exp, "learner.id").and_then(Value::as_str).unwrap_or_default(),
+                lat,
+                lon,
+                day: ts.div_euclid(MS_PER_DAY),
+            })
+        })
+        .collect()
+}
+
+fn report(experiences: &[Value], k: usize, precision: usize) -> KAnonymityReport {
+    let records = records(experiences);
+
+    let current = classes(&records, Some(precision));
+    let mut at_risk: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    let mut violating_classes = 0;
+    for class in current.values().filter(|class| class.learners.len() < k) {
+        violating_classes += 1;
+        for &index in &class.records {
+            let r = &records[index];
+            at_risk.entry(r.learner).or_default().push(r.id.to_string());
+        }
+    }
+
+    let options: Vec<CoarseningOption> = (1..precision)
+        .rev()
+        .map(Some)
+        .chain(std::iter::once(None))
+        .map(|p| evaluate(&records, k, p))
+        .collect();
+    let recommendation = if violating_classes == 0 {
+        None
+    } else {
+        options
+            .iter()
+            .find(|o| o.violating_classes == 0)
+            .map(|o| evaluate(&records, k, o.precision))
+    };
+
+    KAnonymityReport {
+        k,
+        precision,
+        classes: current.len(),
+        violating_classes,
+        at_risk: at_risk
+            .into_iter()
+            .map(|(learner, experience_ids)| AtRiskLearner {
+                learner: learner.to_string(),
+                experience_ids,
+            })
+            .collect(),
+        options,
+        recommendation,
+    }
+}
+
+fn check_arguments(k: usize, geohash_precision: usize) -> Result<(), Error> {
+    if k < 2 {
+        return Err(Error::invalid("k must be at least 2"));
+    }
+    if !(1..=12).contains(&geohash_precision) {
+        return Err(Error::invalid("geohash_precision must be between 1 and 12"));
+    }
+    Ok(())
+}
+
+/// Check geo-tagged experiences for k-anonymity over (geohash cell, day)
+/// Returns `{k, precision, classes, violating_classes, at_risk, options,
+/// recommendation}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn k_anonymity_report(experiences_json: &str, k: usize, geohash_precision: usize) -> Result<String, Error> {
+    check_arguments(k, geohash_precision)?;
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+
+    to_json(&report(&experiences, k, geohash_precision))
+}
+
+#[derive(Serialize)]
+struct Anonymized {
+    experiences: Vec<Value>,
+    /// Geohash precision released, or `null` when coordinates were dropped
+    precision: Option<usize>,
+    /// Ids of records whose day-only class still had fewer than `k` learners
+    suppressed: Vec<String>,
+}
+
+fn anonymize(mut experiences: Vec<Value>, k: usize, precision: usize) -> Anonymized {
+    let mut records = records(&experiences);
+    // Classes are nested within days, so a day with fewer than k learners
+    // cannot be hidden at any level and dropping it leaves the rest intact
+    let mut suppressed = BTreeSet::new();
+    for class in classes(&records, None).into_values().filter(|class| class.learners.len() < k) {
+        suppressed.extend(class.records.iter().map(|&index| records[index].index));
+    }
+    records.retain(|r| !suppressed.contains(&r.index));
+
+    let level = std::iter::once(Some(precision))
+        .chain((1..precision).rev().map(Some))
+        .find(|&p| evaluate(&records, k, p).violating_classes == 0)
+        .unwrap_or(None);
+    let generalised: Vec<(usize, String, i64)> = classes(&records, level)
+        .into_iter()
+        .flat_map(|((cell, day), class)| class.records.into_iter().map(move |index| (index, cell.clone(), day)))
+        .map(|(index, cell, day)| (records[index].index, cell, day))
+        .collect();
+
+    for (index, cell, day) in generalised {
+        let exp = &mut experiences[index];
+        remove_at_path(exp, &["context", "location", "coordinates"]);
+        if level.is_some() {
+            if let Some(location) = exp.pointer_mut("/context/location").and_then(Value::as_object_mut) {
+                location.insert("geohash".to_string(), Value::from(cell));
+            }
+        }
+        exp["timestamp"] = Value::from(format_timestamp(day * MS_PER_DAY));
+    }
+    let suppressed_ids = suppressed
+        .iter()
+        .map(|&index| experiences[index].get("id").and_then(Value::as_str).unwrap_or_default().to_string())
+        .collect();
+    let experiences = experiences
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| !suppressed.contains(index))
+        .map(|(_, exp)| exp)
+        .collect();
+
+    Anonymized {
+        experiences,
+        precision: level,
+        suppressed: suppressed_ids,
+    }
+}
+
+/// Generalise geo-tagged experiences until every (geohash cell, day) class
+/// has at least `k` distinct learners, suppressing records that no level of
+/// generalisation can hide
+/// Returns `{experiences, precision, suppressed}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn k_anonymize(experiences_json: &str, k: usize, geohash_precision: usize) -> Result<String, Error> {
+    check_arguments(k, geohash_precision)?;
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+
+    to_json(&anonymize(experiences, k, geohash_precision))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use serde_json::json;
+
+    fn experience(id: &str, learner: &str, lat: f64, lon: f64, timestamp: &str) -> Value {
+        json!({
+            "id": id,
+            "timestamp": timestamp,
+            "learner": {"id": learner},
+            "context": {"location": {"name": "field", "coordinates": {"latitude": lat, "longitude": lon}}},
+            "experience": {"type": "observation", "description": "", "domains": []},
+        })
+    }
+
+    /// Distinct learners per released (geohash, day) class, recomputed from
+    /// the output alone
+    fn released_classes(experiences: &[Value]) -> BTreeMap<(Option<String>, String), BTreeSet<String>> {
+        let mut classes: BTreeMap<_, BTreeSet<String>> = BTreeMap::new();
+        for exp in experiences.iter().filter(|exp| exp["context"]["location"].is_object()) {
+            assert!(coordinates(exp).is_none(), "coordinates released: {}", exp);
+            let cell = exp["context"]["location"]["geohash"].as_str().map(str::to_string);
+            let day = exp["timestamp"].as_str().unwrap();
+            assert!(day.ends_with("T00:00:00.000Z"), "timestamp not generalised: {}", day);
+            classes
+                .entry((cell, day.to_string()))
+                .or_default()
+                .insert(exp["learner"]["id"].as_str().unwrap().to_string());
+        }
+        classes
+    }
+
+    #[test]
+    fn coarsens_to_the_finest_safe_precision() {
+        let experiences = vec![
+            experience("a", "ada", 51.5000, -0.2500, "2024-05-01T09:00:00Z"),
+            experience("b", "bo", 51.5000, -0.2500, "2024-05-01T17:30:00Z"),
+            experience("c", "cy", 51.5100, -0.2400, "2024-05-01T10:00:00Z"),
+            experience("d", "dee", 51.5101, -0.2401, "2024-05-01T11:00:00Z"),
+            experience("e", "ada", 51.5100, -0.2400, "2024-05-01T12:00:00Z"),
+        ];
+        let result = anonymize(experiences.clone(), 2, 8);
+        assert!(result.suppressed.is_empty());
+        assert_eq!(result.experiences.len(), 5);
+        let precision = result.precision.unwrap();
+        assert!((1..8).contains(&precision));
+        // One character finer and cy or dee would be alone in a cell
+        assert!(evaluate(&records(&experiences), 2, Some(precision + 1)).violating_classes > 0);
+
+        let classes = released_classes(&result.experiences);
+        assert!(classes.values().all(|learners| learners.len() >= 2), "{:?}", classes);
+        assert_eq!(result.experiences[0]["context"]["location"]["name"], "field");
+        assert_eq!(result.experiences[0]["timestamp"], "2024-05-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn suppresses_records_no_level_can_hide() {
+        let experiences = vec![
+            experience("a", "ada", 51.5, -0.25, "2024-05-01T09:00:00Z"),
+            experience("b", "bo", 51.5, -0.25, "2024-05-01T10:00:00Z"),
+            // Alone on its day, and twice by the same learner on another
+            experience("lone", "cy", 51.5, -0.25, "2024-05-02T10:00:00Z"),
+            experience("twice-1", "dee", 10.0, 10.0, "2024-05-03T10:00:00Z"),
+            experience("twice-2", "dee", -10.0, -10.0, "2024-05-03T11:00:00Z"),
+            json!({"id": "untagged", "learner": {"id": "ed"}, "timestamp": "2024-05-04T10:00:00Z"}),
+        ];
+        let result = anonymize(experiences, 2, 7);
+        assert_eq!(result.suppressed, ["lone", "twice-1", "twice-2"]);
+        // The outliers do not force the rest to lose their coordinates
+        assert_eq!(result.precision, Some(7));
+        let ids: Vec<&str> = result.experiences.iter().map(|exp| exp["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, ["a", "b", "untagged"]);
+        assert_eq!(result.experiences[2]["timestamp"], "2024-05-04T10:00:00Z");
+        assert!(released_classes(&result.experiences).values().all(|learners| learners.len() >= 2));
+    }
+
+    #[test]
+    fn drops_coordinates_when_no_cell_is_safe() {
+        let experiences = vec![
+            experience("a", "ada", 60.0, 170.0, "2024-05-01T09:00:00Z"),
+            experience("b", "bo", -60.0, -170.0, "2024-05-01T10:00:00Z"),
+        ];
+        let result = anonymize(experiences, 2, 5);
+        assert_eq!(result.precision, None);
+        assert!(result.suppressed.is_empty());
+        for exp in &result.experiences {
+            assert_eq!(exp["context"]["location"], json!({"name": "field"}));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn every_released_class_has_k_learners(
+            points in prop::collection::vec((0..6usize, 51.0..51.2f64, -0.3..-0.1f64, 0..3i64), 0..40),
+            k in 2..4usize,
+            precision in 1..9usize,
+        ) {
+            let experiences: Vec<Value> = points
+                .iter()
+                .enumerate()
+                .map(|(i, &(learner, lat, lon, day))| {
+                    let ts = format_timestamp(1_714_521_600_000 + day * MS_PER_DAY + i as i64 * 60_000);
+                    experience(&format!("e{}", i), &format!("l{}", learner), lat, lon, &ts)
+                })
+                .collect();
+            let result = anonymize(experiences, k, precision);
+            prop_assert_eq!(result.experiences.len() + result.suppressed.len(), points.len());
+            for (class, learners) in released_classes(&result.experiences) {
+                prop_assert!(learners.len() >= k, "{:?} has {:?}", class, learners);
+            }
+        }
+    }
+}
@@ -0,0 +1,276 @@
+//! Tiered archive segments for cold experiences
+//!
+//! Layout (all integers little-endian):
+//!
+//! ```text
+//! header  "UBIA" | version u8 | codec u8 | reserved u16
+//! blocks  codec-compressed CBOR arrays of up to BLOCK_RECORDS experiences
+//! footer  CBOR index { blocks: [{offset, length, count, min_ts, max_ts}],
+//!                      entries: [{id, ts, block}] }
+//! trailer footer length u32 | "UBIA"
+//! ```
+//!
+//! Records are time-sorted into blocks so a time-range read only inflates the
+//! blocks it overlaps, and the id index gives random access by id. Appending
+//! writes new blocks and a fresh footer after the existing bytes, leaving
+//! everything already written untouched; readers always use the last footer.
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::io::{Read, Write};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::time::parse_timestamp;
+
+const MAGIC: &[u8; 4] = b"UBIA";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 8;
+const TRAILER_LEN: usize = 8;
+const BLOCK_RECORDS: usize = 256;
+/// Refuse to inflate blocks beyond this size (zip-bomb guard)
+const MAX_BLOCK_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Codec {
+    None = 0,
+    Deflate = 1,
+}
+
+impl Codec {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "none" => Ok(Codec::None),
+            "deflate" => Ok(Codec::Deflate),
+            other => Err(format!("unknown archive codec: {}", other)),
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self, String> {
+        match byte {
+            0 => Ok(Codec::None),
+            1 => Ok(Codec::Deflate),
+            other => Err(format!("unknown archive codec id {}", other)),
+        }
+    }
+
+    fn compress(self, data: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            Codec::None => Ok(data.to_vec()),
+            Codec::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data).map_err(|e| e.to_string())?;
+                encoder.finish().map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    fn decompress(self, data: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            Codec::None => Ok(data.to_vec()),
+            Codec::Deflate => {
+                let mut out = Vec::new();
+                DeflateDecoder::new(data)
+                    .take(MAX_BLOCK_BYTES + 1)
+                    .read_to_end(&mut out)
+                    .map_err(|e| e.to_string())?;
+                if out.len() as u64 > MAX_BLOCK_BYTES {
+                    return Err("archive block exceeds size limit".to_string());
+                }
+                Ok(out)
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct BlockInfo {
+    offset: u64,
+    length: u64,
+    count: u32,
+    min_ts: Option<i64>,
+    max_ts: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct IndexEntry {
+    id: String,
+    ts: Option<i64>,
+    block: u32,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Footer {
+    blocks: Vec<BlockInfo>,
+    entries: Vec<IndexEntry>,
+}
+
+/// `{ids: [...]}` and/or `{from, to}` (half-open, RFC 3339); an empty query
+/// returns everything
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ArchiveQuery {
+    ids: Option<Vec<String>>,
+    from: Option<String>,
+    to: Option<String>,
+}
+
+fn timestamp_of(exp: &Value) -> Option<i64> {
+    exp.get("timestamp").and_then(Value::as_str).and_then(parse_timestamp)
+}
+
+fn cbor<T: Serialize>(value: &T) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    ciborium::ser::into_writer(value, &mut out).map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
+/// Append blocks for `experiences` plus a new footer to `out`
+fn write_segment(out: &mut Vec<u8>, codec: Codec, mut footer: Footer, mut experiences: Vec<Value>) -> Result<(), String> {
+    experiences.sort_by_key(timestamp_of);
+
+    for chunk in experiences.chunks(BLOCK_RECORDS) {
+        let block = footer.blocks.len() as u32;
+        let compressed = codec.compress(&cbor(&chunk)?)?;
+        let timestamps: Vec<i64> = chunk.iter().filter_map(timestamp_of).collect();
+
+        footer.blocks.push(BlockInfo {
+            offset: out.len() as u64,
+            length: compressed.len() as u64,
+            count: chunk.len() as u32,
+            min_ts: timestamps.iter().copied().min(),
+            max_ts: timestamps.iter().copied().max(),
+        });
+        footer.entries.extend(chunk.iter().map(|exp| IndexEntry {
+            id: exp.get("id").and_then(Value::as_str).unwrap_or_default().to_string(),
+            ts: timestamp_of(exp),
+            block,
+        }));
+        out.extend_from_slice(&compressed);
+    }
+
+    let footer_bytes = cbor(&footer)?;
+    out.extend_from_slice(&footer_bytes);
+    out.extend_from_slice(&(footer_bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(MAGIC);
+    Ok(())
+}
+
+fn read_footer(bytes: &[u8]) -> Result<(Codec, Footer), String> {
+    if bytes.len() < HEADER_LEN + TRAILER_LEN || &bytes[..4] != MAGIC || &bytes[bytes.len() - 4..] != MAGIC {
+        return Err("not an archive segment".to_string());
+    }
+    if bytes[4] != VERSION {
+        return Err(format!("unsupported archive version {}", bytes[4]));
+    }
+    let codec = Codec::from_byte(bytes[5])?;
+
+    let trailer = bytes.len() - TRAILER_LEN;
+    let footer_len = u32::from_le_bytes([bytes[trailer], bytes[trailer + 1], bytes[trailer + 2], bytes[trailer + 3]]) as usize;
+    let footer_start = trailer
+        .checked_sub(footer_len)
+        .filter(|&start| start >= HEADER_LEN)
+        .ok_or("archive footer length out of range")?;
+    let footer: Footer = ciborium::de::from_reader(&bytes[footer_start..trailer]).map_err(|e| e.to_string())?;
+    Ok((codec, footer))
+}
+
+fn read_block(bytes: &[u8], codec: Codec, info: &BlockInfo) -> Result<Vec<Value>, String> {
+    let start = usize::try_from(info.offset).map_err(|_| "block offset out of range")?;
+    let end = start
+        .checked_add(usize::try_from(info.length).map_err(|_| "block length out of range")?)
+        .filter(|&end| end <= bytes.len())
+        .ok_or("block extends past end of archive")?;
+    let raw = codec.decompress(&bytes[start..end])?;
+    ciborium::de::from_reader(raw.as_slice()).map_err(|e| e.to_string())
+}
+
+fn query_archive(bytes: &[u8], query: &ArchiveQuery) -> Result<Vec<Value>, String> {
+    let (codec, footer) = read_footer(bytes)?;
+    let bound = |s: &Option<String>| -> Result<Option<i64>, String> {
+        s.as_deref()
+            .map(|s| parse_timestamp(s).ok_or_else(|| format!("invalid time bound: {}", s)))
+            .transpose()
+    };
+    let (from, to) = (bound(&query.from)?, bound(&query.to)?);
+    let time_filtered = from.is_some() || to.is_some();
+    let in_range = |ts: Option<i64>| {
+        !time_filtered
+            || ts.is_some_and(|ts| from.is_none_or(|from| ts >= from) && to.is_none_or(|to| ts < to))
+    };
+
+    let ids: Option<HashSet<&str>> = query.ids.as_ref().map(|ids| ids.iter().map(String::as_str).collect());
+
+    // Entry positions, grouped by block, that satisfy the index-level filter
+    let mut wanted: Vec<Vec<usize>> = vec![Vec::new(); footer.blocks.len()];
+    let mut positions = vec![0usize; footer.blocks.len()];
+    for entry in &footer.entries {
+        let block = entry.block as usize;
+        let Some(position) = positions.get_mut(block) else {
+            return Err("index refers to a missing block".to_string());
+        };
+        let id_match = ids.as_ref().is_none_or(|ids| ids.contains(entry.id.as_str()));
+        if id_match && in_range(entry.ts) {
+            wanted[block].push(*position);
+        }
+        *position += 1;
+    }
+
+    let mut results = Vec::new();
+    for (info, positions) in footer.blocks.iter().zip(wanted) {
+        if positions.is_empty() {
+            continue;
+        }
+        let mut records = read_block(bytes, codec, info)?;
+        for position in positions {
+            if let Some(record) = records.get_mut(position) {
+                results.push(std::mem::take(record));
+            }
+        }
+    }
+    Ok(results)
+}
+
+/// Write a new archive segment; `codec` is `"none"` or `"deflate"`
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn archive(experiences_json: &str, codec: &str) -> Result<Vec<u8>, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let codec = Codec::parse(codec).map_err(Error::invalid)?;
+
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&[VERSION, codec as u8, 0, 0]);
+    write_segment(&mut out, codec, Footer::default(), experiences).map_err(Error::invalid)?;
+    Ok(out)
+}
+
+/// Append experiences to an existing segment without rewriting it
+/// Returns the extended archive bytes
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn append_archive(bytes: &[u8], experiences_json: &str) -> Result<Vec<u8>, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let (codec, footer) = read_footer(bytes).map_err(Error::parse)?;
+
+    let mut out = bytes.to_vec();
+    write_segment(&mut out, codec, footer, experiences).map_err(Error::invalid)?;
+    Ok(out)
+}
+
+/// Read experiences from an archive by `{ids}` and/or `{from, to}`
+/// Returns the matching experiences as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn read_archive(bytes: &[u8], query_json: &str) -> Result<String, Error> {
+    let query: ArchiveQuery = if query_json.trim().is_empty() {
+        ArchiveQuery::default()
+    } else {
+        from_json(query_json, "query_json")?
+    };
+
+    let results = query_archive(bytes, &query).map_err(Error::invalid)?;
+    to_json(&results)
+}
@@ -0,0 +1,185 @@
+//! Incremental parsing of one large JSON array
+//!
+//! Some upstream systems export a single top-level JSON array rather than
+//! NDJSON. [`ArrayStream`] takes the bytes in arbitrary chunks and hands
+//! back each element as soon as its closing byte arrives, holding only the
+//! element in progress, so memory is bounded by the largest element rather
+//! than the file. `NetworkStreamBuilder` uses it when its input starts with
+//! `[`; JS callers validate the elements returned by `push` one at a time.
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+
+/// Refuse elements beyond this size, which would defeat bounded memory
+const MAX_ELEMENT_BYTES: usize = 16 * 1024 * 1024;
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    /// Before the opening `[`
+    Start,
+    /// After `[` or `,`; `first` allows `]` for an empty array
+    BeforeElement { first: bool },
+    InElement,
+    AfterElement,
+    /// After the closing `]`; only whitespace may follow
+    Done,
+}
+
+/// Incremental parser for a top-level JSON array
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct ArrayStream {
+    state: State,
+    element: Vec<u8>,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    offset: usize,
+    elements: usize,
+}
+
+impl ArrayStream {
+    fn fail(&self, message: impl std::fmt::Display) -> Error {
+        Error::parse(format!("byte {}: {}", self.offset, message)).with("offset", self.offset)
+    }
+
+    fn complete(&mut self, on_element: &mut impl FnMut(&[u8], usize) -> Result<(), Error>) -> Result<(), Error> {
+        let element = std::mem::take(&mut self.element);
+        serde_json::from_slice::<serde::de::IgnoredAny>(&element).map_err(|e| {
+            Error::parse(format!("element {}: {}", self.elements, e)).with("element", self.elements)
+        })?;
+        on_element(&element, self.elements)?;
+        self.elements += 1;
+        self.element = element;
+        self.element.clear();
+        self.state = State::AfterElement;
+        Ok(())
+    }
+
+    /// Feed bytes, calling `on_element` with each complete element and its
+    /// index
+    pub(crate) fn feed(
+        &mut self,
+        chunk: &[u8],
+        mut on_element: impl FnMut(&[u8], usize) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        for &b in chunk {
+            match self.state {
+                State::InElement => {
+                    let scalar_end = self.depth == 0 && !self.in_string && matches!(b, b',' | b']' | b' ' | b'\t' | b'\r' | b'\n');
+                    if scalar_end {
+                        // A number or literal ends at the first byte after it
+                        self.complete(&mut on_element)?;
+                        match b {
+                            b',' => self.state = State::BeforeElement { first: false },
+                            b']' => self.state = State::Done,
+                            _ => {}
+                        }
+                    } else {
+                        if self.element.len() >= MAX_ELEMENT_BYTES {
+                            return Err(self.fail(format!("element {} exceeds {} bytes", self.elements, MAX_ELEMENT_BYTES)));
+                        }
+                        self.element.push(b);
+                        self.offset += 1;
+                        if self.in_string {
+                            match b {
+                                _ if self.escaped => self.escaped = false,
+                                b'\\' => self.escaped = true,
+                                b'"' => self.in_string = false,
+                                _ => {}
+                            }
+                        } else {
+                            match b {
+                                b'"' => self.in_string = true,
+                                b'{' | b'[' => self.depth += 1,
+                                b'}' | b']' => self.depth = self.depth.saturating_sub(1),
+                                _ => {}
+                            }
+                        }
+                        let closed = self.depth == 0 && !self.in_string && matches!(b, b'}' | b']' | b'"');
+                        if closed {
+                            self.complete(&mut on_element)?;
+                        }
+                        continue;
+                    }
+                }
+                _ if b.is_ascii_whitespace() => {}
+                State::Start if b == b'[' => self.state = State::BeforeElement { first: true },
+                State::BeforeElement { first: true } if b == b']' => self.state = State::Done,
+                State::BeforeElement { .. } if !matches!(b, b',' | b']' | b'}' | b':') => {
+                    self.state = State::InElement;
+                    self.element.push(b);
+                    match b {
+                        b'"' => self.in_string = true,
+                        b'{' | b'[' => self.depth = 1,
+                        _ => {}
+                    }
+                }
+                State::AfterElement if b == b',' => self.state = State::BeforeElement { first: false },
+                State::AfterElement if b == b']' => self.state = State::Done,
+                State::Start => return Err(self.fail("expected a JSON array")),
+                State::Done => return Err(self.fail("unexpected data after the array")),
+                _ => return Err(self.fail(format!("unexpected {:?}", b as char))),
+            }
+            self.offset += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl ArrayStream {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> Self {
+        Self {
+            state: State::Start,
+            element: Vec::new(),
+            depth: 0,
+            in_string: false,
+            escaped: false,
+            offset: 0,
+            elements: 0,
+        }
+    }
+
+    /// Feed the next chunk of the array. Chunks may split elements (and
+    /// multi-byte characters) anywhere.
+    /// Returns the elements completed by this chunk as a JSON array
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn push(&mut self, chunk: &[u8]) -> Result<String, Error> {
+        let mut out = String::from("[");
+        self.feed(chunk, |element, _| {
+            if out.len() > 1 {
+                out.push(',');
+            }
+            // Checked as JSON, so also as UTF-8
+            out.push_str(std::str::from_utf8(element).map_err(|e| Error::parse(e.to_string()))?);
+            Ok(())
+        })?;
+        out.push(']');
+        Ok(out)
+    }
+
+    /// Number of elements completed so far
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn elements(&self) -> usize {
+        self.elements
+    }
+
+    /// Check that the input ended with the array closed
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn finish(&self) -> Result<(), Error> {
+        match self.state {
+            State::Done => Ok(()),
+            State::Start => Err(self.fail("expected a JSON array")),
+            _ => Err(self.fail("the array is not closed")),
+        }
+    }
+}
+
+impl Default for ArrayStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
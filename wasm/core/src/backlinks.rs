@@ -0,0 +1,157 @@
+//! Bidirectional link index between experiences
+//!
+//! Links come from two places: explicit `metadata.related_experiences` (or
+//! the `relatedExperienceIds` spelling some clients send) and experience ids
+//! mentioned in descriptions, found the same way as
+//! [`crate::references::extract_references`]. The index answers both
+//! directions in a map lookup, so the journal can show connected experiences
+//! as soon as one is opened.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::references::{description, scan};
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+enum LinkKind {
+    Related,
+    Reference,
+}
+
+type LinkMap = BTreeMap<String, BTreeMap<String, BTreeSet<LinkKind>>>;
+
+#[derive(Serialize)]
+struct Link<'a> {
+    id: &'a str,
+    via: &'a BTreeSet<LinkKind>,
+}
+
+fn related_ids(exp: &Value) -> impl Iterator<Item = &str> {
+    ["metadata.related_experiences", "relatedExperienceIds"]
+        .into_iter()
+        .filter_map(|path| lookup(exp, path).and_then(Value::as_array))
+        .flatten()
+        .filter_map(Value::as_str)
+}
+
+/// Experience link index with `links_to` / `linked_from` lookups
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct BacklinkIndex {
+    outgoing: LinkMap,
+    incoming: LinkMap,
+    /// Descriptions by id, rescanned when a newly inserted id may already
+    /// be mentioned elsewhere
+    descriptions: HashMap<String, String>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl BacklinkIndex {
+    /// Build the index from a JSON array of experiences
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(experiences_json: &str) -> Result<BacklinkIndex, Error> {
+        let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+
+        let mut index = BacklinkIndex { outgoing: LinkMap::new(), incoming: LinkMap::new(), descriptions: HashMap::new() };
+        let known: HashSet<&str> = experiences.iter().filter_map(|e| e.get("id").and_then(Value::as_str)).collect();
+        for exp in &experiences {
+            index.index(exp, &known)?;
+        }
+        Ok(index)
+    }
+
+    /// Add or replace one experience, re-deriving its outgoing links
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn insert(&mut self, experience_json: &str) -> Result<(), Error> {
+        let exp: Value = from_json(experience_json, "experience_json")?;
+        let id = exp.get("id").and_then(Value::as_str).ok_or_else(|| Error::invalid("experience has no id"))?;
+
+        let is_new = !self.descriptions.contains_key(id);
+        let known: HashSet<String> = self.descriptions.keys().cloned().chain([id.to_string()]).collect();
+        self.index(&exp, &known.iter().map(String::as_str).collect())?;
+
+        // Earlier descriptions may mention this id without it looking like one
+        if is_new {
+            let only_new = HashSet::from([id]);
+            let mentions: Vec<String> = self
+                .descriptions
+                .iter()
+                .filter(|(other, text)| other.as_str() != id && scan(text, &only_new).experiences.iter().any(|e| e == id))
+                .map(|(other, _)| other.clone())
+                .collect();
+            for source in mentions {
+                self.link(&source, id, LinkKind::Reference);
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of experiences indexed
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn size(&self) -> usize {
+        self.descriptions.len()
+    }
+
+    /// Experiences that `id` links to
+    /// Returns `[{id, via}]` as JSON, `via` listing `related` and/or `reference`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn links_to(&self, id: &str) -> Result<String, Error> {
+        links_json(self.outgoing.get(id))
+    }
+
+    /// Experiences that link to `id` (its backlinks)
+    /// Returns `[{id, via}]` as JSON
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn linked_from(&self, id: &str) -> Result<String, Error> {
+        links_json(self.incoming.get(id))
+    }
+}
+
+impl BacklinkIndex {
+    fn index(&mut self, exp: &Value, known: &HashSet<&str>) -> Result<(), Error> {
+        let id = exp.get("id").and_then(Value::as_str).ok_or_else(|| Error::invalid("experience has no id"))?;
+        self.unlink_outgoing(id);
+
+        for target in related_ids(exp) {
+            self.link(id, target, LinkKind::Related);
+        }
+        for target in scan(description(exp), known).experiences {
+            self.link(id, &target, LinkKind::Reference);
+        }
+        self.descriptions.insert(id.to_string(), description(exp).to_string());
+        Ok(())
+    }
+
+    fn link(&mut self, source: &str, target: &str, kind: LinkKind) {
+        if source == target {
+            return;
+        }
+        self.outgoing.entry(source.to_string()).or_default().entry(target.to_string()).or_default().insert(kind);
+        self.incoming.entry(target.to_string()).or_default().entry(source.to_string()).or_default().insert(kind);
+    }
+
+    fn unlink_outgoing(&mut self, source: &str) {
+        for target in self.outgoing.remove(source).into_iter().flat_map(BTreeMap::into_keys) {
+            if let Some(backlinks) = self.incoming.get_mut(&target) {
+                backlinks.remove(source);
+                if backlinks.is_empty() {
+                    self.incoming.remove(&target);
+                }
+            }
+        }
+    }
+}
+
+fn links_json(links: Option<&BTreeMap<String, BTreeSet<LinkKind>>>) -> Result<String, Error> {
+    let links: Vec<Link> = links
+        .into_iter()
+        .flatten()
+        .map(|(id, via)| Link { id, via })
+        .collect();
+    to_json(&links)
+}
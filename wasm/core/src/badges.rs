@@ -0,0 +1,300 @@
+//! Open Badges 3.0 credentials minted from experiences
+//!
+//! [`issue_badge`] checks a learner's validated experiences against a
+//! criteria definition and, if they qualify, returns an unsigned
+//! `OpenBadgeCredential` shaped after the W3C Verifiable Credentials 2.0 data
+//! model, with each qualifying experience listed as evidence:
+//!
+//! ```json
+//! {
+//!   "achievement": {"id": "https://school.example/badges/field-naturalist",
+//!                   "name": "Field Naturalist",
+//!                   "description": "Recorded repeated ecology observations",
+//!                   "criteria": "Five ecology observations"},
+//!   "min_experiences": 5,
+//!   "filter": {"domain": "ecology"}
+//! }
+//! ```
+//!
+//! `filter` uses the query DSL. [`sign_badge`] attaches a
+//! `DataIntegrityProof` using the `eddsa-jcs-2022` cryptosuite (W3C Data
+//! Integrity EdDSA): the Ed25519 signature covers
+//! `SHA-256(JCS(proof config)) || SHA-256(JCS(credential without proof))`,
+//! with JCS the RFC 8785 canonical form, and is carried in `proofValue` as
+//! multibase base58btc (`z…`), so standard verifiers can check it.
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use ed25519_dalek::{Signature, Signer, Verifier};
+
+use crate::crypto::{from_base58btc, jcs, random_bytes, sha256, to_base58btc, to_hex};
+use crate::error::{from_json, to_json, Error};
+use crate::identity::{canonical_learner_id, LearnerId};
+use crate::query::Filter;
+use crate::signing::{signing_key, verifying_key};
+use crate::time::{format_timestamp, now_ms};
+use crate::{Experience, ExperienceValidator};
+
+const CONTEXTS: [&str; 2] = [
+    "https://www.w3.org/ns/credentials/v2",
+    "https://purl.imsglobal.org/spec/ob/v3p0/context-3.0.3.json",
+];
+const CRYPTOSUITE: &str = "eddsa-jcs-2022";
+
+#[derive(Deserialize)]
+struct Criteria {
+    achievement: Achievement,
+    #[serde(default = "default_min_experiences")]
+    min_experiences: usize,
+    filter: Option<Filter>,
+}
+
+fn default_min_experiences() -> usize {
+    1
+}
+
+#[derive(Deserialize)]
+struct Achievement {
+    id: String,
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    criteria: String,
+}
+
+#[derive(Deserialize)]
+struct Issuer {
+    id: String,
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BadgeOptions {
+    issuer: Issuer,
+    /// Learner the badge is for; may be omitted when all experiences belong
+    /// to a single learner
+    recipient: Option<String>,
+    /// Credential id; a random `urn:uuid:` is generated when omitted
+    id: Option<String>,
+    /// RFC 3339 `validFrom`; defaults to now
+    issued_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SignOptions {
+    verification_method: String,
+    created: Option<String>,
+}
+
+fn uuid_v4() -> Result<String, String> {
+    let mut b = random_bytes::<16>()?;
+    b[6] = (b[6] & 0x0f) | 0x40;
+    b[8] = (b[8] & 0x3f) | 0x80;
+    let hex = to_hex(&b);
+    Ok(format!(
+        "urn:uuid:{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    ))
+}
+
+/// `credentialSubject` for a learner. DIDs and IRIs are used as the subject
+/// id directly; local ids are only disclosed as a SHA-256 identity hash.
+fn subject(learner_id: &str, achievement: Value) -> Value {
+    let canonical = canonical_learner_id(learner_id);
+    match LearnerId::parse(&canonical) {
+        Ok(LearnerId::Local(_)) | Err(_) => json!({
+            "type": ["AchievementSubject"],
+            "identifier": [{
+                "type": "IdentityObject",
+                "identityType": "identifier",
+                "hashed": true,
+                "identityHash": format!("sha256${}", to_hex(&sha256(canonical.as_bytes()))),
+            }],
+            "achievement": achievement,
+        }),
+        Ok(_) => json!({
+            "id": canonical,
+            "type": ["AchievementSubject"],
+            "achievement": achievement,
+        }),
+    }
+}
+
+fn build_badge(
+    experiences: &[Value],
+    criteria: &Criteria,
+    options: &BadgeOptions,
+    id: String,
+    valid_from: String,
+) -> Result<Value, String> {
+    let validator = ExperienceValidator::new(true);
+    let mut recipient = options.recipient.as_deref().map(canonical_learner_id);
+    let mut evidence = Vec::new();
+
+    for value in experiences {
+        let exp: Experience = match serde_json::from_value(value.clone()) {
+            Ok(exp) => exp,
+            Err(_) => continue,
+        };
+        if !validator.validate_experience(&exp).valid {
+            continue;
+        }
+        let learner = canonical_learner_id(&exp.learner.id);
+        match recipient {
+            None => recipient = Some(learner),
+            Some(ref r) if *r != learner => {
+                if options.recipient.is_none() {
+                    return Err("experiences belong to several learners; set options.recipient".to_string());
+                }
+                continue;
+            }
+            Some(_) => {}
+        }
+        if criteria.filter.as_ref().is_some_and(|f| !f.matches(value)) {
+            continue;
+        }
+        evidence.push(json!({
+            "id": format!("urn:ubicity:experience:{}", exp.id),
+            "type": ["Evidence"],
+            "name": exp.experience.type_field,
+            "description": exp.experience.description,
+        }));
+    }
+
+    let recipient = recipient.ok_or("no valid experiences to assess")?;
+    if evidence.len() < criteria.min_experiences {
+        return Err(format!(
+            "criteria not met: {} of {} qualifying experiences",
+            evidence.len(),
+            criteria.min_experiences
+        ));
+    }
+
+    let a = &criteria.achievement;
+    let achievement = json!({
+        "id": a.id,
+        "type": ["Achievement"],
+        "name": a.name,
+        "description": a.description,
+        "criteria": {"narrative": a.criteria},
+    });
+    let mut issuer = json!({"id": options.issuer.id, "type": ["Profile"]});
+    if let Some(ref name) = options.issuer.name {
+        issuer["name"] = Value::from(name.as_str());
+    }
+
+    Ok(json!({
+        "@context": CONTEXTS,
+        "id": id,
+        "type": ["VerifiableCredential", "OpenBadgeCredential"],
+        "issuer": issuer,
+        "validFrom": valid_from,
+        "name": a.name,
+        "credentialSubject": subject(&recipient, achievement),
+        "evidence": evidence,
+    }))
+}
+
+/// Assess experiences against a badge criteria definition
+/// Returns an unsigned OpenBadgeCredential as JSON, or an error when the
+/// criteria are not met
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn issue_badge(experiences_json: &str, criteria_json: &str, options_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let criteria: Criteria = from_json(criteria_json, "criteria_json")?;
+    if let Some(ref filter) = criteria.filter {
+        filter.check().map_err(Error::invalid)?;
+    }
+    let options: BadgeOptions = from_json(options_json, "options_json")?;
+
+    let id = match options.id {
+        Some(ref id) => id.clone(),
+        None => uuid_v4().map_err(Error::host)?,
+    };
+    let valid_from = options.issued_at.clone().unwrap_or_else(|| format_timestamp(now_ms()));
+
+    let badge = build_badge(&experiences, &criteria, &options, id, valid_from).map_err(Error::invalid)?;
+    to_json(&badge)
+}
+
+/// Bytes signed for `eddsa-jcs-2022`: the proof configuration (the proof
+/// without `proofValue`, under the credential's `@context`) and the
+/// credential without its proof, each JCS-canonicalised and hashed
+fn hash_data(credential: &Map<String, Value>, proof: &Map<String, Value>) -> Vec<u8> {
+    let mut config = proof.clone();
+    config.remove("proofValue");
+    if let Some(context) = credential.get("@context") {
+        config.insert("@context".to_string(), context.clone());
+    }
+    let mut document = credential.clone();
+    document.remove("proof");
+
+    let mut data = sha256(jcs(&Value::Object(config)).as_bytes()).to_vec();
+    data.extend_from_slice(&sha256(jcs(&Value::Object(document)).as_bytes()));
+    data
+}
+
+/// Sign a credential with a 32-byte Ed25519 secret seed; options give the
+/// `verification_method` (key id) and optionally `created`
+/// Returns the credential with its `proof` attached as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn sign_badge(credential_json: &str, private_key: &[u8], options_json: &str) -> Result<String, Error> {
+    let mut credential: Value = from_json(credential_json, "credential_json")?;
+    let options: SignOptions = from_json(options_json, "options_json")?;
+    let key = signing_key(private_key).map_err(Error::crypto)?;
+    let obj = credential
+        .as_object_mut()
+        .ok_or_else(|| Error::invalid("credential must be a JSON object"))?;
+    obj.remove("proof");
+
+    let mut proof: Map<String, Value> = [
+        ("type", Value::from("DataIntegrityProof")),
+        ("cryptosuite", Value::from(CRYPTOSUITE)),
+        ("created", Value::from(options.created.unwrap_or_else(|| format_timestamp(now_ms())))),
+        ("verificationMethod", Value::from(options.verification_method)),
+        ("proofPurpose", Value::from("assertionMethod")),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value))
+    .collect();
+    let signature = key.sign(&hash_data(obj, &proof));
+    proof.insert(
+        "proofValue".to_string(),
+        Value::from(format!("z{}", to_base58btc(&signature.to_bytes()))),
+    );
+    obj.insert("proof".to_string(), Value::Object(proof));
+    to_json(&credential)
+}
+
+/// Verify the proof on a credential signed with [`sign_badge`] against a
+/// 32-byte public key
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn verify_badge(credential_json: &str, public_key: &[u8]) -> Result<bool, Error> {
+    let credential: Value = from_json(credential_json, "credential_json")?;
+    let key = verifying_key(public_key).map_err(Error::crypto)?;
+    let Some(obj) = credential.as_object() else {
+        return Ok(false);
+    };
+    let Some(proof) = obj
+        .get("proof")
+        .and_then(Value::as_object)
+        .filter(|proof| proof.get("cryptosuite").and_then(Value::as_str) == Some(CRYPTOSUITE))
+    else {
+        return Ok(false);
+    };
+    let signature = proof
+        .get("proofValue")
+        .and_then(Value::as_str)
+        .and_then(|value| value.strip_prefix('z'))
+        .and_then(from_base58btc)
+        .and_then(|bytes| Signature::from_slice(&bytes).ok());
+    Ok(signature.is_some_and(|sig| key.verify(&hash_data(obj, proof), &sig).is_ok()))
+}
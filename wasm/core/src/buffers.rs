@@ -0,0 +1,52 @@
+//! Typed-array results for moving between Web Workers
+//!
+//! A JSON string posted from a worker is copied, and a 5k×5k similarity
+//! matrix or a 250k-cell heatmap is hundreds of megabytes of text. The
+//! `_typed` exports return [`Buffers`] instead: a small JSON index that
+//! describes the layout, plus the numbers in flat `Float64Array` and
+//! `Uint32Array` buffers. Each `take_*` call copies its buffer out of WASM
+//! memory once, and the array it returns owns its `ArrayBuffer`, so it can
+//! be listed in `postMessage`'s transfer list and moved without a copy.
+
+use serde::Serialize;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{to_json, Error};
+
+/// A JSON index plus float and integer buffers
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct Buffers {
+    index: String,
+    floats: Vec<f64>,
+    ints: Vec<u32>,
+}
+
+impl Buffers {
+    pub(crate) fn new(index: &impl Serialize, floats: Vec<f64>, ints: Vec<u32>) -> Result<Self, Error> {
+        Ok(Self { index: to_json(index)?, floats, ints })
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Buffers {
+    /// The index describing both buffers, as JSON
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn index(&self) -> String {
+        self.index.clone()
+    }
+
+    /// Move out the float buffer (a `Float64Array` in JS); later calls
+    /// return an empty one
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn take_floats(&mut self) -> Vec<f64> {
+        std::mem::take(&mut self.floats)
+    }
+
+    /// Move out the integer buffer (a `Uint32Array` in JS); later calls
+    /// return an empty one
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn take_ints(&mut self) -> Vec<u32> {
+        std::mem::take(&mut self.ints)
+    }
+}
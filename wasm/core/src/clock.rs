@@ -0,0 +1,217 @@
+//! Device clock-skew estimation and timestamp reconciliation
+//!
+//! Shared tablets often have wildly wrong clocks. During sync the client
+//! records NTP-style round trips against the server, estimates its offset,
+//! and rewrites capture timestamps, keeping the original in
+//! `metadata.timestamp_provenance` so the correction is auditable. Adjusting
+//! twice keeps the first original and adds the skews.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::time::{format_timestamp, parse_timestamp, MS_PER_DAY};
+
+/// Largest accepted correction, a century: far beyond any real device clock
+const MAX_SKEW_MS: f64 = 100.0 * 365.25 * MS_PER_DAY as f64;
+
+/// One request/response round trip, all in epoch milliseconds
+#[derive(Deserialize)]
+struct TimeSample {
+    client_send_ms: f64,
+    server_ms: f64,
+    client_receive_ms: f64,
+}
+
+#[derive(Serialize)]
+struct SkewEstimate {
+    /// Add to device time to get server time
+    skew_ms: f64,
+    /// Half the round-trip time of the best samples: the offset is known to
+    /// within ± this much
+    uncertainty_ms: f64,
+    samples_used: usize,
+}
+
+#[derive(Serialize)]
+struct AdjustReport {
+    experiences: Vec<Value>,
+    adjusted: usize,
+    unparseable: Vec<String>,
+}
+
+fn median(sorted: &[f64]) -> f64 {
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+fn estimate(samples: &[TimeSample]) -> Option<SkewEstimate> {
+    // (round trip, offset) for causally sane samples
+    let mut measured: Vec<(f64, f64)> = samples
+        .iter()
+        .filter(|s| s.client_receive_ms >= s.client_send_ms)
+        .map(|s| {
+            let rtt = s.client_receive_ms - s.client_send_ms;
+            (rtt, s.server_ms - (s.client_send_ms + s.client_receive_ms) / 2.0)
+        })
+        .filter(|(rtt, offset)| rtt.is_finite() && offset.is_finite())
+        .collect();
+    if measured.is_empty() {
+        return None;
+    }
+
+    // Low-latency round trips are the least distorted by asymmetric delay;
+    // keep the fastest third and take the median offset among them
+    measured.sort_by(|a, b| a.0.total_cmp(&b.0));
+    measured.truncate(measured.len().div_ceil(3));
+    let mut offsets: Vec<f64> = measured.iter().map(|&(_, offset)| offset).collect();
+    offsets.sort_by(f64::total_cmp);
+
+    Some(SkewEstimate {
+        skew_ms: median(&offsets),
+        uncertainty_ms: measured.iter().map(|&(rtt, _)| rtt).fold(0.0, f64::max) / 2.0,
+        samples_used: measured.len(),
+    })
+}
+
+/// The original timestamp and total skew of an earlier adjustment
+fn previous_adjustment(exp: &Value) -> Option<(String, i64)> {
+    let provenance = exp.get("metadata")?.get("timestamp_provenance")?;
+    let original = provenance.get("original_timestamp")?.as_str()?;
+    Some((original.to_string(), provenance.get("skew_ms")?.as_i64()?))
+}
+
+fn adjust(mut experiences: Vec<Value>, skew_ms: i64) -> Result<AdjustReport, Error> {
+    let mut adjusted = 0;
+    let mut unparseable = Vec::new();
+
+    for exp in &mut experiences {
+        let id = exp.get("id").and_then(Value::as_str).unwrap_or_default().to_string();
+        let Some(ms) = exp.get("timestamp").and_then(Value::as_str).and_then(parse_timestamp) else {
+            unparseable.push(id);
+            continue;
+        };
+        let (original, total_skew_ms) = match previous_adjustment(exp) {
+            Some((original, previous_ms)) => (original, previous_ms.checked_add(skew_ms)),
+            None => (exp["timestamp"].as_str().unwrap_or_default().to_string(), Some(skew_ms)),
+        };
+        // The shifted time must still be a four-digit-year timestamp
+        let shifted = ms
+            .checked_add(skew_ms)
+            .map(format_timestamp)
+            .filter(|ts| parse_timestamp(ts).is_some());
+        let (Some(shifted), Some(total_skew_ms)) = (shifted, total_skew_ms) else {
+            return Err(Error::invalid(format!("shifting experience {} by {} ms leaves the representable range", id, skew_ms))
+                .with("id", id));
+        };
+        let Some(obj) = exp.as_object_mut() else {
+            continue;
+        };
+
+        obj.insert("timestamp".to_string(), Value::from(shifted));
+        let metadata = obj.entry("metadata").or_insert_with(|| json!({}));
+        if let Some(metadata) = metadata.as_object_mut() {
+            metadata.insert(
+                "timestamp_provenance".to_string(),
+                json!({
+                    "original_timestamp": original,
+                    "skew_ms": total_skew_ms,
+                    "method": "server_round_trip",
+                }),
+            );
+        }
+        adjusted += 1;
+    }
+
+    Ok(AdjustReport {
+        experiences,
+        adjusted,
+        unparseable,
+    })
+}
+
+/// Estimate device clock skew from `[{client_send_ms, server_ms,
+/// client_receive_ms}]` round-trip samples
+/// Returns `{skew_ms, uncertainty_ms, samples_used}` as JSON, or `null` when
+/// no sample is usable
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn estimate_skew(server_time_samples_json: &str) -> Result<String, Error> {
+    let samples: Vec<TimeSample> = from_json(server_time_samples_json, "server_time_samples_json")?;
+    to_json(&estimate(&samples))
+}
+
+/// Shift every experience timestamp by `skew_ms` (at most a century either
+/// way), recording the original in `metadata.timestamp_provenance`
+/// Returns `{experiences, adjusted, unparseable}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn adjust_timestamps(experiences_json: &str, skew_ms: f64) -> Result<String, Error> {
+    if !skew_ms.is_finite() || skew_ms.abs() > MAX_SKEW_MS {
+        return Err(Error::invalid(format!("skew_ms must be finite and within ±{} ms", MAX_SKEW_MS)).with("skew_ms", skew_ms));
+    }
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+
+    to_json(&adjust(experiences, skew_ms.round() as i64)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adjusted(experiences: Value, skew_ms: f64) -> Result<Value, Error> {
+        Ok(serde_json::from_str(&adjust_timestamps(&experiences.to_string(), skew_ms)?).unwrap())
+    }
+
+    #[test]
+    fn estimate_uses_the_fastest_round_trips() {
+        let samples = json!([
+            {"client_send_ms": 0, "server_ms": 1_050, "client_receive_ms": 100},
+            {"client_send_ms": 1_000, "server_ms": 2_060, "client_receive_ms": 1_120},
+            {"client_send_ms": 2_000, "server_ms": 9_000, "client_receive_ms": 4_000},
+            {"client_send_ms": 5_000, "server_ms": 0, "client_receive_ms": 4_000},
+        ]);
+        let estimate: Value = serde_json::from_str(&estimate_skew(&samples.to_string()).unwrap()).unwrap();
+        assert_eq!(estimate, json!({"skew_ms": 1_000.0, "uncertainty_ms": 50.0, "samples_used": 1}));
+        assert_eq!(estimate_skew("[]").unwrap(), "null");
+    }
+
+    #[test]
+    fn shifts_timestamps_and_records_provenance() {
+        let result = adjusted(json!([{"id": "a", "timestamp": "2024-05-01T10:00:00Z"}, {"id": "b", "timestamp": "soon"}]), -1_500.4).unwrap();
+        assert_eq!(result["experiences"][0]["timestamp"], "2024-05-01T09:59:58.500Z");
+        assert_eq!(
+            result["experiences"][0]["metadata"]["timestamp_provenance"],
+            json!({"original_timestamp": "2024-05-01T10:00:00Z", "skew_ms": -1_500, "method": "server_round_trip"})
+        );
+        assert_eq!(result["adjusted"], 1);
+        assert_eq!(result["unparseable"], json!(["b"]));
+    }
+
+    #[test]
+    fn adjusting_twice_keeps_the_first_original() {
+        let once = adjusted(json!([{"id": "a", "timestamp": "2024-05-01T10:00:00Z"}]), 60_000.0).unwrap();
+        let twice = adjusted(once["experiences"].clone(), 1_000.0).unwrap();
+        assert_eq!(twice["experiences"][0]["timestamp"], "2024-05-01T10:01:01.000Z");
+        let provenance = &twice["experiences"][0]["metadata"]["timestamp_provenance"];
+        assert_eq!(provenance["original_timestamp"], "2024-05-01T10:00:00Z");
+        assert_eq!(provenance["skew_ms"], 61_000);
+    }
+
+    #[test]
+    fn rejects_skews_out_of_range() {
+        let experiences = json!([{"id": "a", "timestamp": "2024-05-01T10:00:00Z"}]);
+        for skew_ms in [f64::NAN, f64::INFINITY, 1e300, -1e300, MAX_SKEW_MS + 1.0] {
+            assert!(adjusted(experiences.clone(), skew_ms).is_err(), "{}", skew_ms);
+        }
+        assert!(adjusted(experiences, MAX_SKEW_MS).is_ok());
+
+        let late = json!([{"id": "late", "timestamp": "9990-01-01T00:00:00Z"}]);
+        let err = adjusted(late, MAX_SKEW_MS).unwrap_err();
+        assert_eq!(err.context()["id"], "late");
+    }
+}
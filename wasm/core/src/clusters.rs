@@ -0,0 +1,328 @@
+//! Map marker clustering
+//!
+//! [`PointClusterIndex`] does the hierarchical greedy clustering of the JS
+//! `supercluster` package, so the map no longer needs it: for each zoom
+//! from `max_zoom` down to `min_zoom`, every point or cluster of the zoom
+//! above takes its unclaimed neighbours within `radius` pixels into one
+//! cluster at their weighted centre, provided that makes at least
+//! `min_points` points. Work is done in Web Mercator units. The hierarchy is
+//! rebuilt on the first query after a change. After that, each query is a
+//! binary search in one zoom level.
+//!
+//! The index stores only each experience's id and position, not the
+//! records. It starts from the store's contents and then follows
+//! `ExperienceStore.subscribe`'s change feed, the way `HeavyHitters` does,
+//! so the map never holds a second parsed copy of the experiences.
+//! Experiences without an id or coordinates are not indexed.
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::f64::consts::PI;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::heavy_hitters::Change;
+use crate::numeric::{atan2, exp, ln, sin, sum};
+use crate::query::coordinates;
+
+const MAX_ZOOM: u8 = 24;
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct ClusterOptions {
+    /// Cluster radius in pixels
+    radius: f64,
+    /// Tile size in pixels that `radius` is relative to
+    extent: f64,
+    min_zoom: u8,
+    /// Highest zoom that clusters; points are shown unclustered above it
+    max_zoom: u8,
+    /// Fewest points that make a cluster
+    min_points: u32,
+}
+
+impl Default for ClusterOptions {
+    fn default() -> Self {
+        Self { radius: 40.0, extent: 512.0, min_zoom: 0, max_zoom: 16, min_points: 2 }
+    }
+}
+
+/// A point or cluster at one zoom level, in Web Mercator units (0-1)
+#[derive(Clone, Copy)]
+struct Node {
+    x: f64,
+    y: f64,
+    count: u32,
+    /// Index into the points for a point, cluster id for a cluster
+    id: u32,
+    /// Zoom at which a cluster splits into several nodes
+    expansion_zoom: u8,
+}
+
+fn project_x(lon: f64) -> f64 {
+    lon / 360.0 + 0.5
+}
+
+fn project_y(lat: f64) -> f64 {
+    let s = sin(lat.to_radians());
+    (0.5 - 0.25 * ln((1.0 + s) / (1.0 - s)) / PI).clamp(0.0, 1.0)
+}
+
+fn unproject(x: f64, y: f64) -> (f64, f64) {
+    let lat = 360.0 * atan2(exp((180.0 - y * 360.0).to_radians()), 1.0) / PI - 90.0;
+    ((x - 0.5) * 360.0, lat)
+}
+
+fn sort(level: &mut [Node]) {
+    level.sort_by(|a, b| {
+        a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)).then(a.count.cmp(&b.count)).then(a.id.cmp(&b.id))
+    });
+}
+
+/// The level at `zoom` from the one at `zoom + 1`
+fn cluster(level: &[Node], zoom: u8, options: &ClusterOptions, next_id: &mut u32) -> Vec<Node> {
+    let r = options.radius / (options.extent * f64::from(1u32 << zoom));
+    let cell = |v: f64| (v / r).floor() as i64;
+    let mut grid: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (i, node) in level.iter().enumerate() {
+        grid.entry((cell(node.x), cell(node.y))).or_default().push(i);
+    }
+
+    let mut claimed = vec![false; level.len()];
+    let mut clustered = Vec::new();
+    for (i, node) in level.iter().enumerate() {
+        if claimed[i] {
+            continue;
+        }
+        claimed[i] = true;
+        let (cx, cy) = (cell(node.x), cell(node.y));
+        let mut neighbours = Vec::new();
+        for key in [-1, 0, 1].into_iter().flat_map(|dx| [-1, 0, 1].map(|dy| (cx + dx, cy + dy))) {
+            for &j in grid.get(&key).into_iter().flatten() {
+                let other = &level[j];
+                if !claimed[j] && (other.x - node.x).powi(2) + (other.y - node.y).powi(2) <= r * r {
+                    neighbours.push(j);
+                }
+            }
+        }
+        neighbours.sort_unstable();
+        for &j in &neighbours {
+            claimed[j] = true;
+        }
+        let count = node.count + neighbours.iter().map(|&j| level[j].count).sum::<u32>();
+        if neighbours.is_empty() || count < options.min_points {
+            clustered.push(*node);
+            clustered.extend(neighbours.iter().map(|&j| level[j]));
+            continue;
+        }
+        let members = || std::iter::once(node).chain(neighbours.iter().map(|&j| &level[j]));
+        let weighted = |v: fn(&Node) -> f64| sum(members().map(|n| v(n) * f64::from(n.count))) / f64::from(count);
+        let (x, y) = (weighted(|n| n.x), weighted(|n| n.y));
+        clustered.push(Node { x, y, count, id: *next_id, expansion_zoom: zoom + 1 });
+        *next_id += 1;
+    }
+    sort(&mut clustered);
+    clustered
+}
+
+/// `count` as supercluster's `point_count_abbreviated`
+fn abbreviated(count: u32) -> Value {
+    match count {
+        0..=999 => Value::from(count),
+        1000..=9999 => Value::from(format!("{}k", (f64::from(count) / 100.0).round() / 10.0)),
+        _ => Value::from(format!("{}k", (f64::from(count) / 1000.0).round())),
+    }
+}
+
+/// Clustering of the current points, until the next change
+struct Hierarchy {
+    /// Ids and `[longitude, latitude]` in `points` order
+    leaves: Vec<(String, [f64; 2])>,
+    /// Levels from `min_zoom` to `max_zoom + 1`
+    levels: Vec<Vec<Node>>,
+}
+
+/// Zoom levels of clustered experience points, kept current by the store's
+/// change feed
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct PointClusterIndex {
+    options: ClusterOptions,
+    /// `[longitude, latitude]` by experience id
+    points: BTreeMap<String, [f64; 2]>,
+    hierarchy: Option<Hierarchy>,
+}
+
+impl PointClusterIndex {
+    fn insert(&mut self, record: &Value) -> bool {
+        let (Some(id), Some((lat, lon))) = (record.get("id").and_then(Value::as_str), coordinates(record)) else {
+            return false;
+        };
+        self.points.insert(id.to_string(), [lon, lat]);
+        self.hierarchy = None;
+        true
+    }
+
+    fn remove(&mut self, record: &Value) {
+        if let Some(id) = record.get("id").and_then(Value::as_str) {
+            if self.points.remove(id).is_some() {
+                self.hierarchy = None;
+            }
+        }
+    }
+
+    fn hierarchy(&mut self) -> &Hierarchy {
+        let (options, points) = (&self.options, &self.points);
+        self.hierarchy.get_or_insert_with(|| {
+            let leaves = points.iter().map(|(id, &position)| (id.clone(), position)).collect();
+            let mut level: Vec<Node> = points
+                .values()
+                .enumerate()
+                .map(|(i, &[lon, lat])| {
+                    Node { x: project_x(lon), y: project_y(lat), count: 1, id: i as u32, expansion_zoom: 0 }
+                })
+                .collect();
+            sort(&mut level);
+            let mut levels = Vec::with_capacity(usize::from(options.max_zoom - options.min_zoom) + 2);
+            let mut next_id = 0;
+            for zoom in (options.min_zoom..=options.max_zoom).rev() {
+                let clustered = cluster(&level, zoom, options, &mut next_id);
+                levels.push(std::mem::replace(&mut level, clustered));
+            }
+            levels.push(level);
+            levels.reverse();
+            Hierarchy { leaves, levels }
+        })
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl PointClusterIndex {
+    /// Empty index; `options_json` sets `radius` (pixels, default 40),
+    /// `extent` (tile pixels, 512), `min_zoom` (0), `max_zoom` (16, at most
+    /// 24) and `min_points` (2), as in supercluster, or is empty for those
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(options_json: &str) -> Result<PointClusterIndex, Error> {
+        let options: ClusterOptions = if options_json.trim().is_empty() {
+            ClusterOptions::default()
+        } else {
+            from_json(options_json, "options_json")?
+        };
+        if ![options.radius, options.extent].iter().all(|v| v.is_finite() && *v > 0.0) {
+            return Err(Error::invalid("radius and extent must be positive"));
+        }
+        if options.min_zoom > options.max_zoom || options.max_zoom > MAX_ZOOM {
+            return Err(Error::invalid(format!("zooms must satisfy min_zoom <= max_zoom <= {}", MAX_ZOOM))
+                .with("min_zoom", options.min_zoom)
+                .with("max_zoom", options.max_zoom));
+        }
+        if options.min_points < 2 {
+            return Err(Error::invalid("min_points must be at least 2").with("min_points", options.min_points));
+        }
+        Ok(Self { options, points: BTreeMap::new(), hierarchy: None })
+    }
+
+    /// Index a JSON array of experiences, e.g. the store's contents when the
+    /// map opens; an experience with an indexed id replaces it
+    /// Returns the number of experiences indexed
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn add_experiences(&mut self, experiences_json: &str) -> Result<usize, Error> {
+        let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+        Ok(experiences.iter().filter(|exp| self.insert(exp)).count())
+    }
+
+    /// Apply one change feed entry: `{op: "put", record, previous}` moves or
+    /// adds `record`; `{op: "delete", previous}` removes `previous`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn apply_change(&mut self, change_json: &str) -> Result<(), Error> {
+        let change: Change = from_json(change_json, "change_json")?;
+        let previous = change.previous.filter(|v| !v.is_null());
+        match change.op.as_str() {
+            "put" => {
+                let record = change
+                    .record
+                    .filter(|v| v.is_object())
+                    .ok_or_else(|| Error::invalid("put change needs a record object"))?;
+                if let Some(previous) = previous {
+                    self.remove(&previous);
+                }
+                self.insert(&record);
+            }
+            "delete" => {
+                if let Some(previous) = previous {
+                    self.remove(&previous);
+                }
+            }
+            other => {
+                return Err(Error::invalid(format!("unknown change op: {} (expected put or delete)", other)).with("op", other))
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of experiences indexed
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn points(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Clusters and single points inside `bbox_json` (`[west, south, east,
+    /// north]`, crossing the antimeridian when west > east) at map `zoom`
+    /// (fractions round down)
+    /// Returns `[Feature]` as JSON, like supercluster's `getClusters`: a
+    /// cluster's properties are `{cluster: true, cluster_id, point_count,
+    /// point_count_abbreviated, expansion_zoom}`, a point's `{id}` (the
+    /// experience id, also the feature's `id`)
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn get_clusters(&mut self, bbox_json: &str, zoom: f64) -> Result<String, Error> {
+        let [west, south, east, north]: [f64; 4] = from_json(bbox_json, "bbox_json")?;
+        if ![west, south, east, north, zoom].iter().all(|v| v.is_finite()) {
+            return Err(Error::invalid("bbox and zoom must be finite numbers"));
+        }
+        let (min_zoom, max_zoom) = (f64::from(self.options.min_zoom), f64::from(self.options.max_zoom) + 1.0);
+        let level = (zoom.floor().clamp(min_zoom, max_zoom) - min_zoom) as usize;
+
+        let wrap = |lon: f64| (lon + 180.0).rem_euclid(360.0) - 180.0;
+        let spans = match (wrap(west), wrap(east)) {
+            _ if east - west >= 360.0 => vec![(0.0, 1.0)],
+            (w, e) if w > e => vec![(project_x(w), 1.0), (0.0, project_x(e))],
+            (w, e) => vec![(project_x(w), project_x(e))],
+        };
+        let (top, bottom) = (project_y(north.clamp(-90.0, 90.0)), project_y(south.clamp(-90.0, 90.0)));
+
+        let Hierarchy { leaves, levels } = self.hierarchy();
+        let nodes = &levels[level];
+        let mut features = Vec::new();
+        for (left, right) in spans {
+            let start = nodes.partition_point(|n| n.x.total_cmp(&left) == Ordering::Less);
+            for node in nodes[start..].iter().take_while(|n| n.x <= right).filter(|n| (top..=bottom).contains(&n.y)) {
+                features.push(if node.count == 1 {
+                    let (id, position) = &leaves[node.id as usize];
+                    json!({
+                        "type": "Feature",
+                        "id": id,
+                        "geometry": {"type": "Point", "coordinates": position},
+                        "properties": {"id": id},
+                    })
+                } else {
+                    let (lon, lat) = unproject(node.x, node.y);
+                    json!({
+                        "type": "Feature",
+                        "id": node.id,
+                        "geometry": {"type": "Point", "coordinates": [lon, lat]},
+                        "properties": {
+                            "cluster": true,
+                            "cluster_id": node.id,
+                            "point_count": node.count,
+                            "point_count_abbreviated": abbreviated(node.count),
+                            "expansion_zoom": node.expansion_zoom,
+                        },
+                    })
+                });
+            }
+        }
+        to_json(&features)
+    }
+}
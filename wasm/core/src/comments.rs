@@ -0,0 +1,225 @@
+//! Threaded comments on shared experiences and their moderation
+//!
+//! Comments live beside the experience they discuss; replies point at their
+//! parent comment by id:
+//!
+//! ```json
+//! "comments": [
+//!   {"id": "c1", "author": "sam", "timestamp": "2024-05-01T10:00:00Z", "text": "Which bridge was this?"},
+//!   {"id": "c2", "author": "alex", "timestamp": "2024-05-01T10:05:00Z", "text": "Tower Bridge", "parent": "c1"}
+//! ]
+//! ```
+//!
+//! [`moderate_comments`] applies a declarative policy: blocked terms (whole
+//! words or phrases, case-insensitive), blocked regular expressions (matched
+//! against the lowercased text), a length limit and a link limit. Offending comments are either flagged for review
+//! or removed; removal keeps a tombstone so replies stay threaded.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::identity::LearnerId;
+use crate::severity::Issue;
+use crate::text::tokenize;
+use crate::time::parse_timestamp;
+
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify_next::Tsify))]
+pub(crate) struct Comment {
+    pub(crate) id: String,
+    pub(crate) author: String,
+    pub(crate) timestamp: String,
+    pub(crate) text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) parent: Option<String>,
+}
+
+/// Validation errors for an experience's comment thread
+pub(crate) fn validate_comments(comments: &[Comment]) -> Vec<Issue> {
+    let mut errors = Vec::new();
+    let mut by_id: HashMap<&str, &Comment> = HashMap::new();
+    for (i, c) in comments.iter().enumerate() {
+        if c.id.is_empty() {
+            errors.push(Issue::new("comments.id.required").with("index", i));
+        } else if by_id.insert(c.id.as_str(), c).is_some() {
+            errors.push(Issue::new("comments.id.duplicate").with("index", i).with("id", &c.id));
+        }
+        if c.author.is_empty() {
+            errors.push(Issue::new("comments.author.required").with("index", i));
+        } else if let Err(e) = LearnerId::parse(&c.author) {
+            errors.push(Issue::new("comments.author.invalid").with("index", i).with("reason", e));
+        }
+        if c.text.trim().is_empty() {
+            errors.push(Issue::new("comments.text.required").with("index", i));
+        }
+        if parse_timestamp(&c.timestamp).is_none() {
+            errors.push(Issue::new("comments.timestamp.invalid").with("index", i));
+        }
+    }
+
+    for (i, c) in comments.iter().enumerate() {
+        let Some(ref parent_id) = c.parent else {
+            continue;
+        };
+        let Some(parent) = by_id.get(parent_id.as_str()) else {
+            errors.push(Issue::new("comments.parent.missing").with("index", i).with("parent", parent_id));
+            continue;
+        };
+        if let (Some(reply), Some(original)) = (parse_timestamp(&c.timestamp), parse_timestamp(&parent.timestamp)) {
+            if reply < original {
+                errors.push(Issue::new("comments.parent.order").with("index", i));
+            }
+        }
+        // Walk up the thread; more steps than comments means a cycle
+        let mut current = Some(parent_id.as_str());
+        let mut steps = 0;
+        while let Some(id) = current {
+            if id == c.id || steps > comments.len() {
+                errors.push(Issue::new("comments.cycle").with("index", i));
+                break;
+            }
+            current = by_id.get(id).and_then(|p| p.parent.as_deref());
+            steps += 1;
+        }
+    }
+    errors
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+enum Action {
+    #[default]
+    Flag,
+    Remove,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct PolicySpec {
+    blocked_terms: Vec<String>,
+    blocked_patterns: Vec<String>,
+    max_length: Option<usize>,
+    max_links: Option<usize>,
+    action: Action,
+}
+
+struct Policy {
+    blocked_terms: Vec<(String, Vec<String>)>,
+    blocked_patterns: Vec<(String, Regex)>,
+    max_length: Option<usize>,
+    max_links: Option<usize>,
+    action: Action,
+}
+
+impl Policy {
+    fn compile(spec: PolicySpec) -> Result<Self, String> {
+        let blocked_patterns = spec
+            .blocked_patterns
+            .into_iter()
+            .map(|p| {
+                let re = Regex::new(&p).map_err(|e| format!("invalid pattern {}: {}", p, e))?;
+                Ok((p, re))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Policy {
+            blocked_terms: spec
+                .blocked_terms
+                .into_iter()
+                .map(|term| {
+                    let phrase = tokenize(&term);
+                    (term, phrase)
+                })
+                .collect(),
+            blocked_patterns,
+            max_length: spec.max_length,
+            max_links: spec.max_links,
+            action: spec.action,
+        })
+    }
+
+    /// Reasons the text breaks the policy, empty when it complies
+    fn check(&self, text: &str) -> Vec<String> {
+        let mut reasons = Vec::new();
+        let tokens = tokenize(text);
+        for (term, phrase) in &self.blocked_terms {
+            if !phrase.is_empty() && tokens.windows(phrase.len()).any(|w| w == phrase.as_slice()) {
+                reasons.push(format!("term:{}", term));
+            }
+        }
+        let lower = text.to_lowercase();
+        for (pattern, re) in &self.blocked_patterns {
+            if re.is_match(&lower) {
+                reasons.push(format!("pattern:{}", pattern));
+            }
+        }
+        if self.max_length.is_some_and(|max| text.chars().count() > max) {
+            reasons.push("length".to_string());
+        }
+        let links = text.matches("http://").count() + text.matches("https://").count();
+        if self.max_links.is_some_and(|max| links > max) {
+            reasons.push("links".to_string());
+        }
+        reasons
+    }
+}
+
+#[derive(Serialize, Default)]
+struct ModerationStats {
+    checked: usize,
+    flagged: usize,
+    removed: usize,
+    by_reason: BTreeMap<String, usize>,
+}
+
+fn moderate(experiences: &mut [Value], policy: &Policy) -> ModerationStats {
+    let mut stats = ModerationStats::default();
+    let comments = experiences
+        .iter_mut()
+        .filter_map(|exp| exp.get_mut("comments").and_then(Value::as_array_mut))
+        .flatten();
+
+    for comment in comments {
+        let Some(text) = comment.get("text").and_then(Value::as_str) else {
+            continue;
+        };
+        stats.checked += 1;
+        let reasons = policy.check(text);
+        if reasons.is_empty() {
+            continue;
+        }
+        for reason in &reasons {
+            *stats.by_reason.entry(reason.clone()).or_insert(0) += 1;
+        }
+        let status = match policy.action {
+            Action::Flag => {
+                stats.flagged += 1;
+                "flagged"
+            }
+            Action::Remove => {
+                stats.removed += 1;
+                comment["text"] = Value::Null;
+                "removed"
+            }
+        };
+        comment["moderation"] = json!({"status": status, "reasons": reasons});
+    }
+    stats
+}
+
+/// Apply a moderation policy to every comment thread
+/// Returns `{experiences, stats}` as JSON; offending comments gain a
+/// `moderation` block and removed ones lose their text
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn moderate_comments(experiences_json: &str, policy_json: &str) -> Result<String, Error> {
+    let mut experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let spec: PolicySpec = from_json(policy_json, "policy_json")?;
+    let policy = Policy::compile(spec).map_err(Error::invalid)?;
+
+    let stats = moderate(&mut experiences, &policy);
+    to_json(&json!({"experiences": experiences, "stats": stats}))
+}
@@ -0,0 +1,75 @@
+//! Coordinate validation
+//!
+//! Latitude and longitude ranges are always checked, as are the optional
+//! `accuracy` (horizontal accuracy radius in metres, also accepted as
+//! `accuracyMeters`), `altitude` (metres above sea level) and `source` (one
+//! of [`SOURCES`]) fields. Two further checks catch placeholder or made-up
+//! positions and are off unless enabled with
+//! `ExperienceValidator::set_coordinate_checks`:
+//!
+//! ```json
+//! {"null_island": true, "max_decimals": 7}
+//! ```
+//!
+//! - `null_island` rejects exactly `(0, 0)`, the usual result of a missing
+//!   fix being written as zeros.
+//! - `max_decimals` flags coordinates written with more decimal places than
+//!   any receiver resolves (7 is about a centimetre), which suggests
+//!   computed or fabricated values.
+
+use serde::Deserialize;
+
+use crate::severity::Issue;
+use crate::Coordinates;
+
+/// Lowest and highest plausible altitudes in metres (Challenger Deep,
+/// Everest)
+const MIN_ALTITUDE_M: f64 = -11_000.0;
+const MAX_ALTITUDE_M: f64 = 9_000.0;
+
+/// How a position may have been obtained
+pub(crate) const SOURCES: &[&str] = &["gps", "network", "manual", "imputed"];
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct CoordinateChecks {
+    null_island: bool,
+    max_decimals: Option<u32>,
+}
+
+/// Decimal places in the shortest representation that round-trips
+fn decimals(value: f64) -> usize {
+    let text = value.to_string();
+    text.split_once('.').map_or(0, |(_, fraction)| fraction.len())
+}
+
+/// Validation issues for an experience's coordinates
+pub(crate) fn validate_coordinates(coords: &Coordinates, checks: &CoordinateChecks) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    if coords.latitude < -90.0 || coords.latitude > 90.0 {
+        issues.push(Issue::new("coordinates.latitude.range"));
+    }
+    if coords.longitude < -180.0 || coords.longitude > 180.0 {
+        issues.push(Issue::new("coordinates.longitude.range"));
+    }
+    if checks.null_island && coords.latitude == 0.0 && coords.longitude == 0.0 {
+        issues.push(Issue::new("coordinates.null_island"));
+    }
+    if let Some(max) = checks.max_decimals {
+        if decimals(coords.latitude).max(decimals(coords.longitude)) > max as usize {
+            issues.push(Issue::new("coordinates.precision").with("max", max));
+        }
+    }
+    if coords.accuracy.is_some_and(|accuracy| accuracy < 0.0 || !accuracy.is_finite()) {
+        issues.push(Issue::new("coordinates.accuracy.range"));
+    }
+    if coords.altitude.is_some_and(|altitude| !(MIN_ALTITUDE_M..=MAX_ALTITUDE_M).contains(&altitude)) {
+        issues.push(Issue::new("coordinates.altitude.range").with("min", MIN_ALTITUDE_M).with("max", MAX_ALTITUDE_M));
+    }
+    if let Some(ref source) = coords.source {
+        if !SOURCES.contains(&source.as_str()) {
+            issues.push(Issue::new("coordinates.source.unknown").with("source", source));
+        }
+    }
+    issues
+}
@@ -0,0 +1,137 @@
+//! Exploration coverage of a region
+//!
+//! [`coverage_of_region`] lays a grid of square cells over a park, campus or
+//! other area given as GeoJSON polygons and reports how much of it has been
+//! visited: a cell belongs to the region when its centre lies inside it, and
+//! counts as visited when an experience was recorded in it. A cell on the
+//! edge whose centre falls outside joins the region once an experience
+//! inside the region is recorded in it, so no visit is lost. Pass one
+//! learner's experiences for personal progress or a cohort's for the
+//! group's. The cell mask comes back row by row, ready to draw as a
+//! "fog of war" overlay.
+
+use serde::Serialize;
+use serde_json::Value;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::geo::EARTH_RADIUS_M;
+use crate::geojson::{parse_features, Geometry};
+use crate::numeric::cos;
+use crate::query::coordinates;
+
+/// Grid cells over the region's bounding box, past which the caller should
+/// pick a larger cell
+const MAX_CELLS: usize = 250_000;
+
+const OUTSIDE: u8 = 0;
+const UNVISITED: u8 = 1;
+const VISITED: u8 = 2;
+
+#[derive(Serialize)]
+struct Coverage {
+    cell_size_m: f64,
+    lat_step: f64,
+    lon_step: f64,
+    /// `[west, south, east, north]` of the grid
+    bounds: [f64; 4],
+    rows: usize,
+    cols: usize,
+    region_cells: usize,
+    visited_cells: usize,
+    fraction: f64,
+    points_inside: usize,
+    points_outside: usize,
+    /// Rows north to south, columns west to east: 0 outside the region, 1
+    /// unvisited, 2 visited
+    mask: Vec<Vec<u8>>,
+}
+
+/// Fraction of a region's grid cells with at least one experience
+/// `region_geojson` is a Polygon or MultiPolygon, bare or as a Feature or
+/// FeatureCollection; `cell_size` is the cell edge in metres
+/// Returns `{cell_size_m, lat_step, lon_step, bounds, rows, cols,
+/// region_cells, visited_cells, fraction, points_inside, points_outside,
+/// mask}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn coverage_of_region(experiences_json: &str, region_geojson: &str, cell_size: f64) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let geojson: Value = from_json(region_geojson, "region_geojson")?;
+    if cell_size.is_nan() || cell_size <= 0.0 {
+        return Err(Error::invalid("cell_size must be positive"));
+    }
+    let features = parse_features(&geojson).map_err(|e| Error::invalid(e).with("argument", "region_geojson"))?;
+    let region: Vec<Geometry> = features
+        .into_iter()
+        .map(|f| f.geometry)
+        .filter(|g| matches!(g, Geometry::Polygon(_) | Geometry::MultiPolygon(_)))
+        .collect();
+    let inside = |lat: f64, lon: f64| region.iter().any(|g| g.contains(lon, lat));
+
+    let (mut west, mut south, mut east, mut north) = (f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for ring in region.iter().flat_map(|g| match g {
+        Geometry::Polygon(rings) => rings.iter().take(1).collect::<Vec<_>>(),
+        Geometry::MultiPolygon(polygons) => polygons.iter().filter_map(|rings| rings.first()).collect(),
+        _ => Vec::new(),
+    }) {
+        for &[lon, lat] in ring {
+            (west, south, east, north) = (west.min(lon), south.min(lat), east.max(lon), north.max(lat));
+        }
+    }
+    if !(west < east && south < north) {
+        return Err(Error::invalid("region_geojson has no polygon with an area").with("argument", "region_geojson"));
+    }
+
+    let lat_step = cell_size / (EARTH_RADIUS_M * std::f64::consts::PI / 180.0);
+    let lon_step = (lat_step / cos(((south + north) / 2.0).to_radians()).max(0.01)).min(360.0);
+    let rows = ((north - south) / lat_step).ceil().max(1.0);
+    let cols = ((east - west) / lon_step).ceil().max(1.0);
+    if rows * cols > MAX_CELLS as f64 {
+        return Err(Error::invalid(format!("region grid would have more than {} cells; use a larger cell size", MAX_CELLS))
+            .with("cell_size", cell_size));
+    }
+    // Centre the grid on the region so the overhang is even on all sides
+    let north = north + (rows * lat_step - (north - south)) / 2.0;
+    let west = west - (cols * lon_step - (east - west)) / 2.0;
+    let (rows, cols) = (rows as usize, cols as usize);
+
+    // Row 0 is the northernmost
+    let mut mask: Vec<Vec<u8>> = (0..rows)
+        .map(|row| {
+            let lat = north - (row as f64 + 0.5) * lat_step;
+            (0..cols)
+                .map(|col| if inside(lat, west + (col as f64 + 0.5) * lon_step) { UNVISITED } else { OUTSIDE })
+                .collect()
+        })
+        .collect();
+
+    let (mut points_inside, mut points_outside) = (0, 0);
+    for (lat, lon) in experiences.iter().filter_map(coordinates) {
+        if !inside(lat, lon) {
+            points_outside += 1;
+            continue;
+        }
+        points_inside += 1;
+        let row = (((north - lat) / lat_step).floor() as usize).min(rows - 1);
+        let col = (((lon - west) / lon_step).floor() as usize).min(cols - 1);
+        mask[row][col] = VISITED;
+    }
+
+    let region_cells = mask.iter().flatten().filter(|&&c| c != OUTSIDE).count();
+    let visited_cells = mask.iter().flatten().filter(|&&c| c == VISITED).count();
+    to_json(&Coverage {
+        cell_size_m: cell_size,
+        lat_step,
+        lon_step,
+        bounds: [west, north - rows as f64 * lat_step, west + cols as f64 * lon_step, north],
+        rows,
+        cols,
+        region_cells,
+        visited_cells,
+        fraction: if region_cells > 0 { visited_cells as f64 / region_cells as f64 } else { 0.0 },
+        points_inside,
+        points_outside,
+        mask,
+    })
+}
@@ -0,0 +1,594 @@
+//! Coordinate reference system conversion for partner GIS layers
+//!
+//! Municipal and national partners publish boundaries in projected grids
+//! rather than WGS84 longitude/latitude. [`reproject_geojson`] converts a
+//! whole layer (geofences, coverage regions, POIs) so it can go straight
+//! into `match_context` or `coverage_of_region`, and
+//! [`transform_coordinates`] does the same for bare positions. Positions are
+//! always easting/longitude first, as in GeoJSON.
+//!
+//! Every build supports WGS84 (EPSG:4326), Web Mercator (EPSG:3857) and the
+//! WGS84 UTM zones (EPSG:32601-32660, 32701-32760). National grids are
+//! behind features, to keep their tables out of builds that don't need them:
+//!
+//! - `grid-osgb36`: OSGB36 (EPSG:4277) and the British National Grid
+//!   (EPSG:27700), through the Ordnance Survey's Helmert transformation,
+//!   good to about 5 m (OSTN15 would need its 15 MB grid);
+//! - `grid-etrs89`: ETRS89 (EPSG:4258), its UTM zones 28-38 (EPSG:25828-
+//!   25838) and the pan-European LAEA grid (EPSG:3035). ETRS89 is taken to
+//!   coincide with WGS84, which it does to under a metre.
+//!
+//! Transverse Mercator uses Krüger's series to sixth order, accurate to
+//! well under a millimetre across a UTM zone.
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI, TAU};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+#[cfg(feature = "grid-etrs89")]
+use crate::numeric::asin;
+use crate::numeric::{atan2, cos, exp, ln, powi, sin, tan};
+
+struct Ellipsoid {
+    a: f64,
+    /// Inverse flattening
+    rf: f64,
+}
+
+impl Ellipsoid {
+    fn e2(&self) -> f64 {
+        let f = 1.0 / self.rf;
+        f * (2.0 - f)
+    }
+}
+
+const WGS84: Ellipsoid = Ellipsoid { a: 6_378_137.0, rf: 298.257_223_563 };
+#[cfg(feature = "grid-etrs89")]
+const GRS80: Ellipsoid = Ellipsoid { a: 6_378_137.0, rf: 298.257_222_101 };
+#[cfg(feature = "grid-osgb36")]
+const AIRY_1830: Ellipsoid = Ellipsoid { a: 6_377_563.396, rf: 299.324_964_6 };
+
+/// Seven-parameter transformation from WGS84 (position vector convention)
+#[cfg(feature = "grid-osgb36")]
+struct Helmert {
+    /// Translations in metres
+    t: [f64; 3],
+    /// Scale in parts per million
+    s_ppm: f64,
+    /// Rotations in arcseconds
+    r_arcsec: [f64; 3],
+}
+
+/// WGS84 to OSGB36, from the Ordnance Survey's "A guide to coordinate
+/// systems in Great Britain"
+#[cfg(feature = "grid-osgb36")]
+const WGS84_TO_OSGB36: Helmert = Helmert {
+    t: [-446.448, 125.157, -542.060],
+    s_ppm: 20.4894,
+    r_arcsec: [-0.1502, -0.2470, -0.8421],
+};
+
+#[cfg(feature = "grid-osgb36")]
+impl Helmert {
+    /// Apply, or with `inverse` undo (to first order, well within the
+    /// transformation's own accuracy)
+    fn apply(&self, [x, y, z]: [f64; 3], inverse: bool) -> [f64; 3] {
+        let sign = if inverse { -1.0 } else { 1.0 };
+        let s = 1.0 + sign * self.s_ppm * 1e-6;
+        let [rx, ry, rz] = self.r_arcsec.map(|r| sign * (r / 3600.0).to_radians());
+        let [tx, ty, tz] = self.t.map(|t| sign * t);
+        [
+            tx + s * x - rz * y + ry * z,
+            ty + rz * x + s * y - rx * z,
+            tz - ry * x + rx * y + s * z,
+        ]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Datum {
+    /// WGS84, and ETRS89 taken as equal to it
+    Wgs84,
+    #[cfg(feature = "grid-osgb36")]
+    Osgb36,
+}
+
+fn sinh(x: f64) -> f64 {
+    (exp(x) - exp(-x)) / 2.0
+}
+
+fn cosh(x: f64) -> f64 {
+    (exp(x) + exp(-x)) / 2.0
+}
+
+fn asinh(x: f64) -> f64 {
+    let y = x.abs();
+    (ln(y + (y * y + 1.0).sqrt())).copysign(x)
+}
+
+fn atanh(x: f64) -> f64 {
+    ln((1.0 + x) / (1.0 - x)) / 2.0
+}
+
+fn atan(x: f64) -> f64 {
+    atan2(x, 1.0)
+}
+
+/// Transverse Mercator by Krüger's series in the third flattening
+struct TransverseMercator {
+    e: f64,
+    lon0: f64,
+    k0: f64,
+    false_easting: f64,
+    false_northing: f64,
+    /// Rectifying radius
+    big_a: f64,
+    alpha: [f64; 6],
+    beta: [f64; 6],
+    /// Northing of the origin latitude, in units of `k0 * big_a`
+    xi0: f64,
+}
+
+impl TransverseMercator {
+    fn new(ellipsoid: &Ellipsoid, lat0: f64, lon0: f64, k0: f64, false_easting: f64, false_northing: f64) -> Self {
+        let n = 1.0 / (2.0 * ellipsoid.rf - 1.0);
+        let p = |k: i32| powi(n, k);
+        let alpha = [
+            n / 2.0 - 2.0 / 3.0 * p(2) + 5.0 / 16.0 * p(3) + 41.0 / 180.0 * p(4) - 127.0 / 288.0 * p(5) + 7891.0 / 37800.0 * p(6),
+            13.0 / 48.0 * p(2) - 3.0 / 5.0 * p(3) + 557.0 / 1440.0 * p(4) + 281.0 / 630.0 * p(5) - 1983433.0 / 1935360.0 * p(6),
+            61.0 / 240.0 * p(3) - 103.0 / 140.0 * p(4) + 15061.0 / 26880.0 * p(5) + 167603.0 / 181440.0 * p(6),
+            49561.0 / 161280.0 * p(4) - 179.0 / 168.0 * p(5) + 6601661.0 / 7257600.0 * p(6),
+            34729.0 / 80640.0 * p(5) - 3418889.0 / 1995840.0 * p(6),
+            212378941.0 / 319334400.0 * p(6),
+        ];
+        let beta = [
+            n / 2.0 - 2.0 / 3.0 * p(2) + 37.0 / 96.0 * p(3) - 1.0 / 360.0 * p(4) - 81.0 / 512.0 * p(5) + 96199.0 / 604800.0 * p(6),
+            1.0 / 48.0 * p(2) + 1.0 / 15.0 * p(3) - 437.0 / 1440.0 * p(4) + 46.0 / 105.0 * p(5) - 1118711.0 / 3870720.0 * p(6),
+            17.0 / 480.0 * p(3) - 37.0 / 840.0 * p(4) - 209.0 / 4480.0 * p(5) + 5569.0 / 90720.0 * p(6),
+            4397.0 / 161280.0 * p(4) - 11.0 / 504.0 * p(5) - 830251.0 / 7257600.0 * p(6),
+            4583.0 / 161280.0 * p(5) - 108847.0 / 3991680.0 * p(6),
+            20648693.0 / 638668800.0 * p(6),
+        ];
+        let mut tm = Self {
+            e: ellipsoid.e2().sqrt(),
+            lon0: lon0.to_radians(),
+            k0,
+            false_easting,
+            false_northing,
+            big_a: ellipsoid.a / (1.0 + n) * (1.0 + p(2) / 4.0 + p(4) / 64.0 + p(6) / 256.0),
+            alpha,
+            beta,
+            xi0: 0.0,
+        };
+        tm.xi0 = tm.xi_eta(lat0.to_radians(), 0.0).0;
+        tm
+    }
+
+    /// Conformal latitude's tangent from the geodetic latitude's
+    fn conformal(&self, tau: f64) -> f64 {
+        let sigma = sinh(self.e * atanh(self.e * tau / (1.0 + tau * tau).sqrt()));
+        tau * (1.0 + sigma * sigma).sqrt() - sigma * (1.0 + tau * tau).sqrt()
+    }
+
+    fn xi_eta(&self, lat: f64, dlon: f64) -> (f64, f64) {
+        let tau_prime = self.conformal(sin(lat) / cos(lat));
+        let (xi_prime, eta_prime) = (atan2(tau_prime, cos(dlon)), asinh(sin(dlon) / (tau_prime * tau_prime + powi(cos(dlon), 2)).sqrt()));
+        let (mut xi, mut eta) = (xi_prime, eta_prime);
+        for (j, alpha) in self.alpha.iter().enumerate() {
+            let k = 2.0 * (j + 1) as f64;
+            xi += alpha * sin(k * xi_prime) * cosh(k * eta_prime);
+            eta += alpha * cos(k * xi_prime) * sinh(k * eta_prime);
+        }
+        (xi, eta)
+    }
+
+    fn forward(&self, lon: f64, lat: f64) -> [f64; 2] {
+        let (xi, eta) = self.xi_eta(lat, lon - self.lon0);
+        let scale = self.k0 * self.big_a;
+        [self.false_easting + scale * eta, self.false_northing + scale * (xi - self.xi0)]
+    }
+
+    fn inverse(&self, x: f64, y: f64) -> (f64, f64) {
+        let scale = self.k0 * self.big_a;
+        let (xi, eta) = ((y - self.false_northing) / scale + self.xi0, (x - self.false_easting) / scale);
+        let (mut xi_prime, mut eta_prime) = (xi, eta);
+        for (j, beta) in self.beta.iter().enumerate() {
+            let k = 2.0 * (j + 1) as f64;
+            xi_prime -= beta * sin(k * xi) * cosh(k * eta);
+            eta_prime -= beta * cos(k * xi) * sinh(k * eta);
+        }
+        let tau_prime = sin(xi_prime) / (powi(sinh(eta_prime), 2) + powi(cos(xi_prime), 2)).sqrt();
+        // Newton's method for the geodetic latitude's tangent
+        let e2 = self.e * self.e;
+        let mut tau = tau_prime;
+        for _ in 0..8 {
+            let tau_i = self.conformal(tau);
+            let delta = (tau_prime - tau_i) / (1.0 + tau_i * tau_i).sqrt() * (1.0 + (1.0 - e2) * tau * tau)
+                / ((1.0 - e2) * (1.0 + tau * tau).sqrt());
+            tau += delta;
+            if delta.abs() < 1e-12 {
+                break;
+            }
+        }
+        (self.lon0 + atan2(sinh(eta_prime), cos(xi_prime)), atan(tau))
+    }
+}
+
+/// Lambert azimuthal equal-area on the ellipsoid (Snyder, "Map projections:
+/// a working manual", pp. 187-190)
+#[cfg(feature = "grid-etrs89")]
+struct LambertAzimuthal {
+    e: f64,
+    lat0: f64,
+    lon0: f64,
+    false_easting: f64,
+    false_northing: f64,
+    qp: f64,
+    rq: f64,
+    d: f64,
+    beta1: f64,
+}
+
+#[cfg(feature = "grid-etrs89")]
+impl LambertAzimuthal {
+    fn new(ellipsoid: &Ellipsoid, lat0: f64, lon0: f64, false_easting: f64, false_northing: f64) -> Self {
+        let e = ellipsoid.e2().sqrt();
+        let lat0 = lat0.to_radians();
+        let q = |lat: f64| {
+            let s = sin(lat);
+            (1.0 - e * e) * (s / (1.0 - e * e * s * s) - ln((1.0 - e * s) / (1.0 + e * s)) / (2.0 * e))
+        };
+        let qp = q(FRAC_PI_2);
+        let beta1 = asin(q(lat0) / qp);
+        let rq = ellipsoid.a * (qp / 2.0).sqrt();
+        let m1 = cos(lat0) / (1.0 - e * e * powi(sin(lat0), 2)).sqrt();
+        Self {
+            e,
+            lat0,
+            lon0: lon0.to_radians(),
+            false_easting,
+            false_northing,
+            qp,
+            rq,
+            d: ellipsoid.a * m1 / (rq * cos(beta1)),
+            beta1,
+        }
+    }
+
+    fn forward(&self, lon: f64, lat: f64) -> [f64; 2] {
+        let (e, s) = (self.e, sin(lat));
+        let q = (1.0 - e * e) * (s / (1.0 - e * e * s * s) - ln((1.0 - e * s) / (1.0 + e * s)) / (2.0 * e));
+        let beta = asin((q / self.qp).clamp(-1.0, 1.0));
+        let dlon = lon - self.lon0;
+        let b = self.rq * (2.0 / (1.0 + sin(self.beta1) * sin(beta) + cos(self.beta1) * cos(beta) * cos(dlon))).sqrt();
+        [
+            self.false_easting + b * self.d * cos(beta) * sin(dlon),
+            self.false_northing + b / self.d * (cos(self.beta1) * sin(beta) - sin(self.beta1) * cos(beta) * cos(dlon)),
+        ]
+    }
+
+    fn inverse(&self, x: f64, y: f64) -> (f64, f64) {
+        let (x, y) = (x - self.false_easting, y - self.false_northing);
+        let rho = (powi(x / self.d, 2) + powi(self.d * y, 2)).sqrt();
+        if rho == 0.0 {
+            return (self.lon0, self.lat0);
+        }
+        let c = 2.0 * asin((rho / (2.0 * self.rq)).clamp(-1.0, 1.0));
+        let beta = asin(cos(c) * sin(self.beta1) + self.d * y * sin(c) * cos(self.beta1) / rho);
+        let lon = self.lon0 + atan2(x * sin(c), self.d * cos(self.beta1) * rho * cos(c) - self.d * self.d * y * sin(self.beta1) * sin(c));
+        let (e2, e4, e6) = (self.e * self.e, powi(self.e, 4), powi(self.e, 6));
+        let lat = beta
+            + (e2 / 3.0 + 31.0 * e4 / 180.0 + 517.0 * e6 / 5040.0) * sin(2.0 * beta)
+            + (23.0 * e4 / 360.0 + 251.0 * e6 / 3780.0) * sin(4.0 * beta)
+            + 761.0 * e6 / 45360.0 * sin(6.0 * beta);
+        (lon, lat)
+    }
+}
+
+enum Projection {
+    Geographic,
+    WebMercator,
+    TransverseMercator(Box<TransverseMercator>),
+    #[cfg(feature = "grid-etrs89")]
+    LambertAzimuthal(LambertAzimuthal),
+}
+
+struct Crs {
+    code: u32,
+    datum: Datum,
+    /// Only needed to change datum
+    #[cfg_attr(not(feature = "grid-osgb36"), allow(dead_code))]
+    ellipsoid: &'static Ellipsoid,
+    projection: Projection,
+}
+
+/// UTM zone `zone` (1-60), northern or southern hemisphere
+fn utm(ellipsoid: &Ellipsoid, zone: u32, north: bool) -> Projection {
+    let lon0 = zone as f64 * 6.0 - 183.0;
+    let false_northing = if north { 0.0 } else { 10_000_000.0 };
+    Projection::TransverseMercator(Box::new(TransverseMercator::new(ellipsoid, 0.0, lon0, 0.9996, 500_000.0, false_northing)))
+}
+
+/// The EPSG code in `EPSG:27700`, `27700`, `urn:ogc:def:crs:EPSG::27700`
+/// or `http://www.opengis.net/def/crs/EPSG/0/27700`; the OGC's CRS84 is
+/// 4326 in longitude/latitude order, which is the order used here anyway
+fn epsg_code(name: &str) -> Option<u32> {
+    let name = name.trim();
+    if name.eq_ignore_ascii_case("CRS84") || name.ends_with(":CRS84") || name.ends_with("/CRS84") {
+        return Some(4326);
+    }
+    let lower = name.to_ascii_lowercase();
+    let digits = ["epsg:", "urn:ogc:def:crs:epsg::", "http://www.opengis.net/def/crs/epsg/0/", "https://www.opengis.net/def/crs/epsg/0/"]
+        .iter()
+        .find_map(|prefix| lower.strip_prefix(prefix))
+        .unwrap_or(&lower);
+    digits.parse().ok()
+}
+
+impl Crs {
+    fn resolve(name: &str, argument: &str) -> Result<Self, Error> {
+        let unsupported = |reason: String| Error::invalid(reason).with("argument", argument).with("crs", name);
+        let code = epsg_code(name).ok_or_else(|| unsupported(format!("{} is not an EPSG code", name)))?;
+        let (datum, ellipsoid, projection) = match code {
+            4326 => (Datum::Wgs84, &WGS84, Projection::Geographic),
+            3857 => (Datum::Wgs84, &WGS84, Projection::WebMercator),
+            32601..=32660 => (Datum::Wgs84, &WGS84, utm(&WGS84, code - 32600, true)),
+            32701..=32760 => (Datum::Wgs84, &WGS84, utm(&WGS84, code - 32700, false)),
+            #[cfg(feature = "grid-etrs89")]
+            4258 => (Datum::Wgs84, &GRS80, Projection::Geographic),
+            #[cfg(feature = "grid-etrs89")]
+            25828..=25838 => (Datum::Wgs84, &GRS80, utm(&GRS80, code - 25800, true)),
+            #[cfg(feature = "grid-etrs89")]
+            3035 => (Datum::Wgs84, &GRS80, Projection::LambertAzimuthal(LambertAzimuthal::new(&GRS80, 52.0, 10.0, 4_321_000.0, 3_210_000.0))),
+            #[cfg(feature = "grid-osgb36")]
+            4277 => (Datum::Osgb36, &AIRY_1830, Projection::Geographic),
+            #[cfg(feature = "grid-osgb36")]
+            27700 => (
+                Datum::Osgb36,
+                &AIRY_1830,
+                Projection::TransverseMercator(Box::new(TransverseMercator::new(&AIRY_1830, 49.0, -2.0, 0.999_601_271_7, 400_000.0, -100_000.0))),
+            ),
+            #[cfg(not(feature = "grid-osgb36"))]
+            4277 | 27700 => return Err(unsupported(format!("EPSG:{} needs the grid-osgb36 feature", code))),
+            #[cfg(not(feature = "grid-etrs89"))]
+            4258 | 3035 | 25828..=25838 => return Err(unsupported(format!("EPSG:{} needs the grid-etrs89 feature", code))),
+            _ => return Err(unsupported(format!("EPSG:{} is not supported", code))),
+        };
+        Ok(Self { code, datum, ellipsoid, projection })
+    }
+
+    /// Longitude and latitude in radians on this CRS's datum
+    fn to_geographic(&self, x: f64, y: f64) -> (f64, f64) {
+        match &self.projection {
+            Projection::Geographic => (x.to_radians(), y.to_radians()),
+            Projection::WebMercator => (x / WGS84.a, 2.0 * atan(exp(y / WGS84.a)) - FRAC_PI_2),
+            Projection::TransverseMercator(tm) => tm.inverse(x, y),
+            #[cfg(feature = "grid-etrs89")]
+            Projection::LambertAzimuthal(laea) => laea.inverse(x, y),
+        }
+    }
+
+    fn project(&self, lon: f64, lat: f64) -> [f64; 2] {
+        match &self.projection {
+            Projection::Geographic => [lon.to_degrees(), lat.to_degrees()],
+            Projection::WebMercator => [WGS84.a * lon, WGS84.a * ln(tan(FRAC_PI_4 + lat / 2.0))],
+            Projection::TransverseMercator(tm) => tm.forward(lon, lat),
+            #[cfg(feature = "grid-etrs89")]
+            Projection::LambertAzimuthal(laea) => laea.forward(lon, lat),
+        }
+    }
+}
+
+/// Earth-centred Cartesian coordinates of a point at zero height
+#[cfg(feature = "grid-osgb36")]
+fn to_cartesian(ellipsoid: &Ellipsoid, lon: f64, lat: f64) -> [f64; 3] {
+    let e2 = ellipsoid.e2();
+    let nu = ellipsoid.a / (1.0 - e2 * powi(sin(lat), 2)).sqrt();
+    [nu * cos(lat) * cos(lon), nu * cos(lat) * sin(lon), (1.0 - e2) * nu * sin(lat)]
+}
+
+#[cfg(feature = "grid-osgb36")]
+fn from_cartesian(ellipsoid: &Ellipsoid, [x, y, z]: [f64; 3]) -> (f64, f64) {
+    let e2 = ellipsoid.e2();
+    let p = (x * x + y * y).sqrt();
+    let mut lat = atan2(z, p * (1.0 - e2));
+    for _ in 0..10 {
+        let nu = ellipsoid.a / (1.0 - e2 * powi(sin(lat), 2)).sqrt();
+        let next = atan2(z + e2 * nu * sin(lat), p);
+        let done = (next - lat).abs() < 1e-12;
+        lat = next;
+        if done {
+            break;
+        }
+    }
+    (atan2(y, x), lat)
+}
+
+/// A resolved source and target pair
+struct Transform {
+    from: Crs,
+    to: Crs,
+}
+
+impl Transform {
+    fn new(from_crs: &str, to_crs: &str) -> Result<Self, Error> {
+        Ok(Self { from: Crs::resolve(from_crs, "from_crs")?, to: Crs::resolve(to_crs, "to_crs")? })
+    }
+
+    fn shift_datum(&self, lon: f64, lat: f64) -> (f64, f64) {
+        if self.from.datum == self.to.datum {
+            return (lon, lat);
+        }
+        #[cfg(feature = "grid-osgb36")]
+        {
+            let mut xyz = to_cartesian(self.from.ellipsoid, lon, lat);
+            if self.from.datum == Datum::Osgb36 {
+                xyz = WGS84_TO_OSGB36.apply(xyz, true);
+            }
+            if self.to.datum == Datum::Osgb36 {
+                xyz = WGS84_TO_OSGB36.apply(xyz, false);
+            }
+            from_cartesian(self.to.ellipsoid, xyz)
+        }
+        #[cfg(not(feature = "grid-osgb36"))]
+        unreachable!("only one datum is compiled in")
+    }
+
+    fn apply(&self, x: f64, y: f64) -> Result<[f64; 2], String> {
+        if !x.is_finite() || !y.is_finite() {
+            return Err("coordinates must be finite".to_string());
+        }
+        if matches!(self.from.projection, Projection::Geographic) && !(-90.0..=90.0).contains(&y) {
+            return Err(format!("latitude {} is out of range", y));
+        }
+        let (lon, lat) = self.from.to_geographic(x, y);
+        let (lon, lat) = self.shift_datum(lon, lat);
+        // Keep geographic output in -180..180 whatever the zone arithmetic
+        let lon = if lon.abs() > PI { (lon + PI).rem_euclid(TAU) - PI } else { lon };
+        let out = self.to.project(lon, lat);
+        if out.iter().all(|v| v.is_finite()) {
+            Ok(out)
+        } else {
+            Err(format!("[{}, {}] has no position in EPSG:{}", x, y, self.to.code))
+        }
+    }
+
+    /// Convert a position in place, keeping any elevation after x and y
+    fn position(&self, position: &mut Value) -> Result<(), String> {
+        let coords = position.as_array_mut().filter(|c| c.len() >= 2).ok_or("position must be [x, y]")?;
+        let (Some(x), Some(y)) = (coords[0].as_f64(), coords[1].as_f64()) else {
+            return Err("position must be numeric".to_string());
+        };
+        let [x, y] = self.apply(x, y)?;
+        coords[0] = Value::from(x);
+        coords[1] = Value::from(y);
+        Ok(())
+    }
+
+    /// Convert nested coordinate arrays down to their positions
+    fn coordinates(&self, value: &mut Value) -> Result<(), String> {
+        match value.as_array_mut() {
+            Some(items) if items.first().is_some_and(Value::is_array) => items.iter_mut().try_for_each(|item| self.coordinates(item)),
+            Some(items) if items.is_empty() => Ok(()),
+            _ => self.position(value),
+        }
+    }
+
+    /// Convert a FeatureCollection, Feature, geometry or GeometryCollection
+    fn geojson(&self, value: &mut Value) -> Result<(), String> {
+        let Some(object) = value.as_object_mut() else {
+            return Err("expected a GeoJSON object".to_string());
+        };
+        // A bounding box would no longer hold
+        object.remove("bbox");
+        object.remove("crs");
+        match object.get("type").and_then(Value::as_str) {
+            Some("FeatureCollection") => object
+                .get_mut("features")
+                .and_then(Value::as_array_mut)
+                .ok_or("FeatureCollection needs features")?
+                .iter_mut()
+                .try_for_each(|feature| self.geojson(feature)),
+            Some("Feature") => match object.get_mut("geometry") {
+                Some(Value::Null) | None => Ok(()),
+                Some(geometry) => self.geojson(geometry),
+            },
+            Some("GeometryCollection") => object
+                .get_mut("geometries")
+                .and_then(Value::as_array_mut)
+                .ok_or("GeometryCollection needs geometries")?
+                .iter_mut()
+                .try_for_each(|geometry| self.geojson(geometry)),
+            Some(_) => self.coordinates(object.get_mut("coordinates").ok_or("geometry needs coordinates")?),
+            None => Err("GeoJSON object needs a type".to_string()),
+        }
+    }
+}
+
+/// Convert `[x, y]` positions (easting or longitude first) between
+/// coordinate reference systems, named as `EPSG:27700`, `27700` or an OGC
+/// URN or URL
+/// Returns the positions in `to_crs`, elevations kept, as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn transform_coordinates(positions_json: &str, from_crs: &str, to_crs: &str) -> Result<String, Error> {
+    let mut positions: Vec<Value> = from_json(positions_json, "positions_json")?;
+    let transform = Transform::new(from_crs, to_crs)?;
+    for (index, position) in positions.iter_mut().enumerate() {
+        transform
+            .position(position)
+            .map_err(|e| Error::invalid(format!("position {}: {}", index, e)).with("index", index))?;
+    }
+    to_json(&positions)
+}
+
+/// Reproject every geometry in a GeoJSON layer; an empty `from_crs` reads
+/// the layer's legacy `crs` member (as written by QGIS and ogr2ogr)
+/// Returns the layer in `to_crs`, without `bbox` members and naming its
+/// `crs` only when that is not WGS84, as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn reproject_geojson(geojson: &str, from_crs: &str, to_crs: &str) -> Result<String, Error> {
+    let mut layer: Value = from_json(geojson, "geojson")?;
+    let named = layer.pointer("/crs/properties/name").and_then(Value::as_str).map(str::to_string);
+    let from_crs = match (from_crs.trim(), named) {
+        ("", Some(named)) => named,
+        ("", None) => return Err(Error::invalid("from_crs is empty and the layer names no crs").with("argument", "from_crs")),
+        (given, _) => given.to_string(),
+    };
+    reproject(&mut layer, &from_crs, to_crs)?;
+    to_json(&layer)
+}
+
+/// Reproject a GeoJSON value in place, naming its `crs` unless WGS84
+pub(crate) fn reproject(layer: &mut Value, from_crs: &str, to_crs: &str) -> Result<(), Error> {
+    let transform = Transform::new(from_crs, to_crs)?;
+    transform.geojson(layer).map_err(|e| Error::invalid(e).with("argument", "geojson"))?;
+    if transform.to.code != 4326 {
+        if let Some(object) = layer.as_object_mut() {
+            let mut name = Map::new();
+            name.insert("name".to_string(), Value::from(format!("urn:ogc:def:crs:EPSG::{}", transform.to.code)));
+            let mut crs = Map::new();
+            crs.insert("type".to_string(), Value::from("name"));
+            crs.insert("properties".to_string(), Value::Object(name));
+            object.insert("crs".to_string(), Value::Object(crs));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct SupportedCrs {
+    code: String,
+    name: String,
+}
+
+/// Coordinate reference systems this build converts between, which depends
+/// on its `grid-*` features
+/// Returns `[{code, name}]` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn supported_crs() -> Result<String, Error> {
+    let mut supported = Vec::new();
+    let mut add = |code: u32, name: String| supported.push(SupportedCrs { code: format!("EPSG:{}", code), name });
+    add(4326, "WGS 84".to_string());
+    add(3857, "WGS 84 / Pseudo-Mercator".to_string());
+    for zone in 1..=60 {
+        add(32600 + zone, format!("WGS 84 / UTM zone {}N", zone));
+    }
+    for zone in 1..=60 {
+        add(32700 + zone, format!("WGS 84 / UTM zone {}S", zone));
+    }
+    if cfg!(feature = "grid-etrs89") {
+        add(4258, "ETRS89".to_string());
+        add(3035, "ETRS89-extended / LAEA Europe".to_string());
+        for zone in 28..=38 {
+            add(25800 + zone, format!("ETRS89 / UTM zone {}N", zone));
+        }
+    }
+    if cfg!(feature = "grid-osgb36") {
+        add(4277, "OSGB36".to_string());
+        add(27700, "OSGB36 / British National Grid".to_string());
+    }
+    to_json(&supported)
+}
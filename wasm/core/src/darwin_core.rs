@@ -0,0 +1,224 @@
+//! Darwin Core occurrence export for citizen-science platforms
+//!
+//! School biodiversity projects log sightings as experiences; GBIF and the
+//! platforms that feed it take Darwin Core. [`to_darwin_core`] writes the
+//! core file and `meta.xml` descriptor of a Darwin Core Archive, one
+//! occurrence per experience, ready to be zipped (with an `eml.xml` for the
+//! dataset) and uploaded.
+//!
+//! The mapping names the experience types and quality grades to export and,
+//! per Darwin Core term, the dotted path it is read from or a constant
+//! written on every row. The defaults read `scientificName` from
+//! `experience.scientific_name`; mapping a default term to `""` drops it,
+//! for example `occurrenceRemarks` when descriptions may identify learners.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::{lookup, write_raw_csv_row};
+
+const CORE_FILE: &str = "occurrence.csv";
+const DWC_NS: &str = "http://rs.tdwg.org/dwc/terms/";
+const DC_NS: &str = "http://purl.org/dc/terms/";
+/// Record-level terms that Darwin Core borrows from Dublin Core
+const DC_TERMS: &[&str] = &[
+    "accessRights",
+    "bibliographicCitation",
+    "language",
+    "license",
+    "modified",
+    "references",
+    "rightsHolder",
+    "type",
+];
+
+/// Terms read from each experience when the mapping does not say otherwise
+const DEFAULT_TERMS: &[(&str, &str)] = &[
+    ("occurrenceID", "id"),
+    ("eventDate", "timestamp"),
+    ("scientificName", "experience.scientific_name"),
+    ("locality", "context.location.name"),
+    ("decimalLatitude", "context.location.coordinates.latitude"),
+    ("decimalLongitude", "context.location.coordinates.longitude"),
+    ("coordinateUncertaintyInMeters", "context.location.coordinates.accuracy"),
+    ("occurrenceRemarks", "experience.description"),
+];
+
+const DEFAULT_CONSTANTS: &[(&str, &str)] = &[("basisOfRecord", "HumanObservation"), ("geodeticDatum", "EPSG:4326")];
+
+/// Terms a row cannot be published without
+const REQUIRED_TERMS: &[&str] = &["occurrenceID", "scientificName", "eventDate"];
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct DarwinCoreMapping {
+    /// Experience types to export; empty for every type
+    types: Vec<String>,
+    /// `experience.quality_grade` values to export, as set by
+    /// `grade_observations`; empty for every record
+    quality_grades: Vec<String>,
+    /// Term to dotted path, added to or replacing the defaults
+    terms: BTreeMap<String, String>,
+    /// Term to a value written on every row, added to or replacing the
+    /// defaults
+    constants: BTreeMap<String, String>,
+}
+
+enum Source {
+    Path(String),
+    Constant(String),
+}
+
+#[derive(Serialize)]
+struct Skipped<'a> {
+    index: usize,
+    id: Option<&'a str>,
+    reason: String,
+}
+
+#[derive(Serialize)]
+struct Files {
+    #[serde(rename = "occurrence.csv")]
+    occurrence: String,
+    #[serde(rename = "meta.xml")]
+    meta: String,
+}
+
+#[derive(Serialize)]
+struct DarwinCoreExport<'a> {
+    exported: usize,
+    /// Records of an exported type missing a required term or repeating
+    /// an occurrenceID
+    skipped: Vec<Skipped<'a>>,
+    files: Files,
+}
+
+fn term_uri(term: &str) -> String {
+    let namespace = if DC_TERMS.contains(&term) { DC_NS } else { DWC_NS };
+    format!("{}{}", namespace, term)
+}
+
+/// Columns in output order: the default terms, further mapped terms, then
+/// constants; `occurrenceID` always comes first as the archive's id
+fn columns(mapping: DarwinCoreMapping) -> Result<Vec<(String, Source)>, Error> {
+    for term in mapping.terms.keys().chain(mapping.constants.keys()) {
+        if !term.starts_with(|c: char| c.is_ascii_lowercase()) || !term.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(Error::invalid(format!("{} is not a Darwin Core term name", term)).with("argument", "mapping_json"));
+        }
+        if mapping.terms.contains_key(term) && mapping.constants.contains_key(term) {
+            return Err(Error::invalid(format!("{} is both mapped and constant", term)).with("argument", "mapping_json"));
+        }
+    }
+    let mut columns: Vec<(String, Source)> = Vec::new();
+    for (term, path) in DEFAULT_TERMS {
+        if !mapping.constants.contains_key(*term) {
+            let path = mapping.terms.get(*term).map_or(*path, String::as_str);
+            if !path.is_empty() {
+                columns.push((term.to_string(), Source::Path(path.to_string())));
+            }
+        }
+    }
+    for (term, path) in mapping.terms {
+        if !path.is_empty() && !DEFAULT_TERMS.iter().any(|(t, _)| *t == term) {
+            columns.push((term, Source::Path(path)));
+        }
+    }
+    for (term, value) in DEFAULT_CONSTANTS {
+        if !mapping.constants.contains_key(*term) && !columns.iter().any(|(t, _)| t == term) {
+            columns.push((term.to_string(), Source::Constant(value.to_string())));
+        }
+    }
+    columns.extend(mapping.constants.into_iter().map(|(term, value)| (term, Source::Constant(value))));
+    if columns.first().map(|(term, _)| term.as_str()) != Some("occurrenceID") {
+        return Err(Error::invalid("occurrenceID must be read from the records").with("argument", "mapping_json"));
+    }
+    Ok(columns)
+}
+
+fn meta_xml(columns: &[(String, Source)]) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<archive xmlns=\"http://rs.tdwg.org/dwc/text/\">\n");
+    out.push_str("  <core encoding=\"UTF-8\" fieldsTerminatedBy=\",\" linesTerminatedBy=\"\\r\\n\" fieldsEnclosedBy=\"&quot;\" ");
+    out.push_str(&format!("ignoreHeaderLines=\"1\" rowType=\"{}Occurrence\">\n", DWC_NS));
+    out.push_str(&format!("    <files><location>{}</location></files>\n    <id index=\"0\"/>\n", CORE_FILE));
+    for (index, (term, _)) in columns.iter().enumerate() {
+        out.push_str(&format!("    <field index=\"{}\" term=\"{}\"/>\n", index, term_uri(term)));
+    }
+    out.push_str("  </core>\n</archive>\n");
+    out
+}
+
+fn text(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.trim().to_string(),
+        // Darwin Core lists are pipe separated
+        Some(Value::Array(items)) => items.iter().map(|item| text(Some(item))).collect::<Vec<_>>().join(" | "),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// Export experiences as Darwin Core occurrences
+/// `mapping_json` sets `types`, `quality_grades`, `terms` (term to dotted
+/// path) and `constants` (term to value); empty for every record and the
+/// default terms
+/// Returns `{exported, skipped: [{index, id, reason}], files:
+/// {"occurrence.csv", "meta.xml"}}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn to_darwin_core(experiences_json: &str, mapping_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let mapping: DarwinCoreMapping = if mapping_json.trim().is_empty() {
+        DarwinCoreMapping::default()
+    } else {
+        from_json(mapping_json, "mapping_json")?
+    };
+    let (types, grades) = (mapping.types.clone(), mapping.quality_grades.clone());
+    let columns = columns(mapping)?;
+
+    let mut occurrence = String::new();
+    write_raw_csv_row(&mut occurrence, &columns.iter().map(|(term, _)| term.as_str()).collect::<Vec<_>>());
+    let mut exported = 0;
+    let mut skipped = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    for (index, exp) in experiences.iter().enumerate() {
+        let kind = lookup(exp, "experience.type").and_then(Value::as_str).unwrap_or_default();
+        let grade = lookup(exp, "experience.quality_grade").and_then(Value::as_str).unwrap_or_default();
+        if (!types.is_empty() && !types.iter().any(|t| t == kind)) || (!grades.is_empty() && !grades.iter().any(|g| g == grade)) {
+            continue;
+        }
+        let row: Vec<String> = columns
+            .iter()
+            .map(|(_, source)| match source {
+                Source::Path(path) => text(lookup(exp, path)),
+                Source::Constant(value) => value.clone(),
+            })
+            .collect();
+        let missing: Vec<&str> = REQUIRED_TERMS
+            .iter()
+            .copied()
+            .filter(|term| columns.iter().zip(&row).all(|((t, _), cell)| t != term || cell.is_empty()))
+            .collect();
+        let reason = if !missing.is_empty() {
+            Some(format!("missing {}", missing.join(", ")))
+        } else if !seen.insert(row[0].clone()) {
+            Some(format!("occurrenceID {} is not unique", row[0]))
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            skipped.push(Skipped { index, id: lookup(exp, "id").and_then(Value::as_str), reason });
+            continue;
+        }
+        write_raw_csv_row(&mut occurrence, &row);
+        exported += 1;
+    }
+
+    to_json(&DarwinCoreExport {
+        exported,
+        skipped,
+        files: Files { occurrence, meta: meta_xml(&columns) },
+    })
+}
@@ -0,0 +1,189 @@
+//! Activity by distance from anchor locations
+//!
+//! Place-based learning research keeps asking how much learning happens
+//! near school or home versus farther afield. [`distance_band_stats`]
+//! measures each located experience against the nearest anchor of each
+//! kind and buckets it into distance bands:
+//!
+//! ```json
+//! [{"kind": "school", "latitude": 52.41, "longitude": -4.08},
+//!  {"kind": "home", "learner": "did:example:alice", "latitude": 52.42, "longitude": -4.07}]
+//! ```
+//!
+//! An anchor with a `learner` applies to that learner's experiences only;
+//! one without applies to everyone. Bands are ascending upper edges in
+//! metres, so `[500, 2000]` gives 0-500 m, 500-2000 m and 2000 m or more.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::geo::haversine_m;
+use crate::identity::canonical_learner_id;
+use crate::numeric;
+use crate::query::{coordinates, domains};
+
+const DEFAULT_BANDS: &[f64] = &[500.0, 2_000.0, 10_000.0];
+const MAX_BANDS: usize = 64;
+
+#[derive(Deserialize)]
+struct Anchor {
+    #[serde(default = "default_kind")]
+    kind: String,
+    #[serde(default)]
+    learner: Option<String>,
+    latitude: f64,
+    longitude: f64,
+}
+
+fn default_kind() -> String {
+    "anchor".to_string()
+}
+
+#[derive(Serialize)]
+struct Band<'a> {
+    label: String,
+    min_m: f64,
+    max_m: Option<f64>,
+    experiences: usize,
+    share: f64,
+    learners: usize,
+    mean_distance_m: Option<f64>,
+    domains: BTreeMap<&'a str, usize>,
+}
+
+#[derive(Serialize)]
+struct KindStats<'a> {
+    anchored: usize,
+    /// Located experiences with no anchor of this kind for their learner
+    unanchored: usize,
+    median_distance_m: Option<f64>,
+    bands: Vec<Band<'a>>,
+}
+
+#[derive(Serialize)]
+struct DistanceBandReport<'a> {
+    bands: Vec<f64>,
+    located: usize,
+    unlocated: usize,
+    anchors: BTreeMap<String, KindStats<'a>>,
+}
+
+fn label(min: f64, max: Option<f64>) -> String {
+    match max {
+        Some(max) => format!("{}-{} m", min, max),
+        None => format!("{}+ m", min),
+    }
+}
+
+/// Experience counts, learners and domains per distance band from the
+/// nearest anchor of each kind
+/// `anchor_points_json` is `[{kind, learner, latitude, longitude}]` and
+/// `bands_json` ascending band edges in metres (empty for `[500, 2000,
+/// 10000]`)
+/// Returns `{bands, located, unlocated, anchors: {kind: {anchored,
+/// unanchored, median_distance_m, bands: [{label, min_m, max_m,
+/// experiences, share, learners, mean_distance_m, domains}]}}}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn distance_band_stats(experiences_json: &str, anchor_points_json: &str, bands_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let mut anchors: Vec<Anchor> = from_json(anchor_points_json, "anchor_points_json")?;
+    let edges: Vec<f64> = if bands_json.trim().is_empty() {
+        DEFAULT_BANDS.to_vec()
+    } else {
+        from_json(bands_json, "bands_json")?
+    };
+    if edges.len() > MAX_BANDS {
+        return Err(Error::invalid(format!("at most {} band edges are supported", MAX_BANDS)).with("bands", edges.len()));
+    }
+    if edges.iter().any(|e| !e.is_finite() || *e <= 0.0) || edges.windows(2).any(|w| w[0] >= w[1]) {
+        return Err(Error::invalid("band edges must be positive and strictly ascending"));
+    }
+    for (i, a) in anchors.iter().enumerate() {
+        if !(-90.0..=90.0).contains(&a.latitude) || !(-180.0..=180.0).contains(&a.longitude) {
+            return Err(Error::invalid(format!("anchor {} is out of range", i)).with("index", i));
+        }
+    }
+    for a in &mut anchors {
+        a.learner = a.learner.as_deref().map(canonical_learner_id);
+    }
+    let mut by_kind: BTreeMap<&str, Vec<&Anchor>> = BTreeMap::new();
+    for a in &anchors {
+        by_kind.entry(a.kind.as_str()).or_default().push(a);
+    }
+
+    let mut located: Vec<(Option<String>, f64, f64, &Value)> = Vec::new();
+    let mut unlocated = 0;
+    for exp in &experiences {
+        match coordinates(exp) {
+            Some((lat, lon)) => {
+                let learner = lookup(exp, "learner.id").and_then(Value::as_str).map(canonical_learner_id);
+                located.push((learner, lat, lon, exp));
+            }
+            None => unlocated += 1,
+        }
+    }
+
+    let mut stats = BTreeMap::new();
+    for (kind, kind_anchors) in by_kind {
+        // (band, distance, learner, experience) per anchored experience
+        let mut measured: Vec<(usize, f64, Option<&str>, &Value)> = Vec::new();
+        for (learner, lat, lon, exp) in &located {
+            let nearest = kind_anchors
+                .iter()
+                .filter(|a| a.learner.is_none() || a.learner == *learner)
+                .map(|a| haversine_m(*lat, *lon, a.latitude, a.longitude))
+                .fold(None, |best: Option<f64>, d| Some(best.map_or(d, |b| b.min(d))));
+            if let Some(d) = nearest {
+                measured.push((edges.partition_point(|&e| e <= d), d, learner.as_deref(), exp));
+            }
+        }
+        let mut distances: Vec<f64> = measured.iter().map(|m| m.1).collect();
+        distances.sort_by(f64::total_cmp);
+        let median_distance_m = match distances.len() {
+            0 => None,
+            n if n % 2 == 1 => Some(distances[n / 2]),
+            n => Some((distances[n / 2 - 1] + distances[n / 2]) / 2.0),
+        };
+        let bands: Vec<Band> = (0..=edges.len())
+            .map(|band| {
+                let min_m = if band == 0 { 0.0 } else { edges[band - 1] };
+                let max_m = edges.get(band).copied();
+                let members: Vec<&(usize, f64, Option<&str>, &Value)> = measured.iter().filter(|m| m.0 == band).collect();
+                let mut domain_counts: BTreeMap<&str, usize> = BTreeMap::new();
+                for domain in members.iter().flat_map(|m| domains(m.3)) {
+                    *domain_counts.entry(domain).or_insert(0) += 1;
+                }
+                Band {
+                    label: label(min_m, max_m),
+                    min_m,
+                    max_m,
+                    experiences: members.len(),
+                    share: if measured.is_empty() { 0.0 } else { members.len() as f64 / measured.len() as f64 },
+                    learners: members.iter().filter_map(|m| m.2).collect::<BTreeSet<_>>().len(),
+                    mean_distance_m: numeric::mean(&members.iter().map(|m| m.1).collect::<Vec<_>>()),
+                    domains: domain_counts,
+                }
+            })
+            .collect();
+        stats.insert(
+            kind.to_string(),
+            KindStats {
+                anchored: measured.len(),
+                unanchored: located.len() - measured.len(),
+                median_distance_m,
+                bands,
+            },
+        );
+    }
+    to_json(&DistanceBandReport {
+        bands: edges,
+        located: located.len(),
+        unlocated,
+        anchors: stats,
+    })
+}
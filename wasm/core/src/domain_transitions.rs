@@ -0,0 +1,170 @@
+//! Learning pathways as a Markov chain over domains
+//!
+//! Co-occurrence says which domains go together; transitions say which
+//! lead to which. [`domain_transition_matrix`] orders each learner's
+//! experiences by time and counts, for every consecutive pair, a move from
+//! each domain of the first to each domain of the second, then normalizes
+//! the rows into first-order transition probabilities.
+//! [`predict_next_domains`] reads the same model off one learner's history
+//! and ranks what is likely to come next.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::identity::canonical_learner_id;
+use crate::query::domains;
+use crate::time::parse_timestamp;
+
+/// Distinct domains of one experience, sorted
+pub(crate) type Step<'a> = Vec<&'a str>;
+
+/// Each learner's domain sets in time order, skipping experiences without
+/// a timestamp or domains
+pub(crate) fn domain_sequences(experiences: &[Value]) -> BTreeMap<String, Vec<Step<'_>>> {
+    let mut by_learner: BTreeMap<String, Vec<(i64, &str, Step)>> = BTreeMap::new();
+    for exp in experiences {
+        let (Some(learner), Some(ms)) = (
+            lookup(exp, "learner.id").and_then(Value::as_str),
+            lookup(exp, "timestamp").and_then(Value::as_str).and_then(parse_timestamp),
+        ) else {
+            continue;
+        };
+        let step: Step = domains(exp).collect::<BTreeSet<_>>().into_iter().collect();
+        if !step.is_empty() {
+            let id = lookup(exp, "id").and_then(Value::as_str).unwrap_or_default();
+            by_learner.entry(canonical_learner_id(learner)).or_default().push((ms, id, step));
+        }
+    }
+    by_learner
+        .into_iter()
+        .map(|(learner, mut steps)| {
+            steps.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(b.1)));
+            (learner, steps.into_iter().map(|s| s.2).collect())
+        })
+        .collect()
+}
+
+fn count_transitions<'a>(steps: &[Step<'a>], counts: &mut BTreeMap<&'a str, BTreeMap<&'a str, u64>>) {
+    for pair in steps.windows(2) {
+        for &from in &pair[0] {
+            let row = counts.entry(from).or_default();
+            for &to in &pair[1] {
+                *row.entry(to).or_insert(0) += 1;
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TransitionMatrix<'a> {
+    learners: usize,
+    transitions: u64,
+    /// Row and column order of `counts` and `probabilities`
+    domains: Vec<&'a str>,
+    counts: Vec<Vec<u64>>,
+    /// Rows sum to 1, or are all zero for domains nothing followed
+    probabilities: Vec<Vec<f64>>,
+}
+
+/// First-order transition probabilities between domains over each
+/// learner's chronological sequence
+/// Returns `{learners, transitions, domains, counts, probabilities}` as
+/// JSON, where `counts[i][j]` is how often `domains[j]` followed
+/// `domains[i]`
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn domain_transition_matrix(experiences_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let sequences = domain_sequences(&experiences);
+    let mut counts: BTreeMap<&str, BTreeMap<&str, u64>> = BTreeMap::new();
+    for steps in sequences.values() {
+        count_transitions(steps, &mut counts);
+    }
+    let names: Vec<&str> = sequences.values().flatten().flatten().copied().collect::<BTreeSet<_>>().into_iter().collect();
+    let matrix: Vec<Vec<u64>> = names
+        .iter()
+        .map(|from| {
+            let row = counts.get(from);
+            names.iter().map(|to| row.and_then(|r| r.get(to)).copied().unwrap_or(0)).collect()
+        })
+        .collect();
+    let probabilities = matrix
+        .iter()
+        .map(|row| {
+            let total: u64 = row.iter().sum();
+            row.iter().map(|&n| if total > 0 { n as f64 / total as f64 } else { 0.0 }).collect()
+        })
+        .collect();
+    to_json(&TransitionMatrix {
+        learners: sequences.len(),
+        transitions: matrix.iter().flatten().sum(),
+        domains: names,
+        counts: matrix,
+        probabilities,
+    })
+}
+
+/// One step of a pathway given by the caller
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(crate) enum PathwayStep {
+    Domain(String),
+    Domains(Vec<String>),
+    Experience(Value),
+}
+
+/// Domains of each step, dropping steps without any
+pub(crate) fn pathway_steps(pathway: &[PathwayStep]) -> Vec<Step<'_>> {
+    pathway
+        .iter()
+        .map(|step| match step {
+            PathwayStep::Domain(domain) => vec![domain.as_str()],
+            PathwayStep::Domains(list) => list.iter().map(String::as_str).collect::<BTreeSet<_>>().into_iter().collect(),
+            PathwayStep::Experience(exp) => domains(exp).collect::<BTreeSet<_>>().into_iter().collect(),
+        })
+        .filter(|step: &Step| !step.is_empty())
+        .collect()
+}
+
+#[derive(Serialize)]
+struct Prediction<'a> {
+    domain: &'a str,
+    probability: f64,
+}
+
+/// Most likely next domains for one learner
+/// `history_json` is the learner's pathway, oldest first: experiences,
+/// domain names, or arrays of domain names per step. The chain is fitted
+/// to the history itself and the last step's domains are averaged; when
+/// nothing has yet followed them, overall domain frequency is used instead
+/// Returns up to `k` `[{domain, probability}]`, most likely first, as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn predict_next_domains(history_json: &str, k: usize) -> Result<String, Error> {
+    let history: Vec<PathwayStep> = from_json(history_json, "history_json")?;
+    let steps = pathway_steps(&history);
+    let mut counts: BTreeMap<&str, BTreeMap<&str, u64>> = BTreeMap::new();
+    count_transitions(&steps, &mut counts);
+
+    let mut scores: BTreeMap<&str, f64> = BTreeMap::new();
+    let rows: Vec<&BTreeMap<&str, u64>> = steps.last().into_iter().flatten().filter_map(|d| counts.get(d)).collect();
+    for row in &rows {
+        let total: u64 = row.values().sum();
+        for (&to, &n) in row.iter() {
+            *scores.entry(to).or_insert(0.0) += n as f64 / total as f64 / rows.len() as f64;
+        }
+    }
+    if scores.is_empty() {
+        let total = steps.iter().map(Vec::len).sum::<usize>() as f64;
+        for &domain in steps.iter().flatten() {
+            *scores.entry(domain).or_insert(0.0) += 1.0 / total;
+        }
+    }
+    let mut ranked: Vec<Prediction> = scores.into_iter().map(|(domain, probability)| Prediction { domain, probability }).collect();
+    ranked.sort_by(|a, b| b.probability.total_cmp(&a.probability).then_with(|| a.domain.cmp(b.domain)));
+    ranked.truncate(k);
+    to_json(&ranked)
+}
@@ -0,0 +1,150 @@
+//! Joining environmental time series to experiences
+//!
+//! Hosts supply observations from weather, air-quality or daylight feeds as
+//! a flat array:
+//!
+//! ```json
+//! [{"series": "weather", "timestamp": "2024-05-01T10:00:00Z",
+//!   "latitude": 51.5, "longitude": -0.12, "values": {"temp_c": 14.2}}]
+//! ```
+//!
+//! [`join_context_series`] attaches, per series, the observation closest to
+//! each experience in time and space, within the tolerance, under
+//! `context.environment.<series>`. Observations without coordinates apply
+//! everywhere (e.g. a city-wide pollen index).
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::geo::haversine_m;
+use crate::query::coordinates;
+use crate::time::{format_timestamp, parse_timestamp, MS_PER_MINUTE};
+
+#[derive(Deserialize)]
+struct Observation {
+    series: String,
+    timestamp: String,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    #[serde(default)]
+    values: Map<String, Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct Tolerance {
+    max_minutes: f64,
+    max_distance_m: f64,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            max_minutes: 60.0,
+            max_distance_m: 25_000.0,
+        }
+    }
+}
+
+struct Point {
+    ms: i64,
+    location: Option<(f64, f64)>,
+    values: Map<String, Value>,
+}
+
+/// Observations per series, sorted by time
+fn index_series(observations: Vec<Observation>) -> Result<BTreeMap<String, Vec<Point>>, String> {
+    let mut series: BTreeMap<String, Vec<Point>> = BTreeMap::new();
+    for obs in observations {
+        let ms = parse_timestamp(&obs.timestamp).ok_or_else(|| format!("invalid timestamp: {}", obs.timestamp))?;
+        series.entry(obs.series).or_default().push(Point {
+            ms,
+            location: obs.latitude.zip(obs.longitude),
+            values: obs.values,
+        });
+    }
+    for points in series.values_mut() {
+        points.sort_by_key(|p| p.ms);
+    }
+    Ok(series)
+}
+
+/// Best observation for an experience: smallest combined time and distance
+/// offset, each relative to its tolerance
+fn best_match<'a>(points: &'a [Point], ms: i64, at: Option<(f64, f64)>, tol: &Tolerance) -> Option<(&'a Point, f64, Option<f64>)> {
+    let window = (tol.max_minutes * MS_PER_MINUTE as f64) as i64;
+    let start = points.partition_point(|p| p.ms < ms - window);
+    points[start..]
+        .iter()
+        .take_while(|p| p.ms <= ms + window)
+        .filter_map(|p| {
+            let minutes = (p.ms - ms) as f64 / MS_PER_MINUTE as f64;
+            let distance = match (p.location, at) {
+                (Some((plat, plon)), Some((lat, lon))) => Some(haversine_m(plat, plon, lat, lon)),
+                (Some(_), None) => return None,
+                (None, _) => None,
+            };
+            if distance.is_some_and(|d| d > tol.max_distance_m) {
+                return None;
+            }
+            let time_cost = if tol.max_minutes > 0.0 { minutes.abs() / tol.max_minutes } else { 0.0 };
+            let distance_cost = match distance {
+                Some(d) if tol.max_distance_m > 0.0 => d / tol.max_distance_m,
+                _ => 0.0,
+            };
+            Some((p, minutes, distance, time_cost + distance_cost))
+        })
+        .min_by(|a, b| a.3.total_cmp(&b.3))
+        .map(|(p, minutes, distance, _)| (p, minutes, distance))
+}
+
+fn join(experiences: &mut [Value], series: &BTreeMap<String, Vec<Point>>, tol: &Tolerance) {
+    for exp in experiences {
+        let Some(ms) = exp.get("timestamp").and_then(Value::as_str).and_then(parse_timestamp) else {
+            continue;
+        };
+        let at = coordinates(exp);
+        let mut environment = Map::new();
+        for (name, points) in series {
+            if let Some((point, minutes, distance)) = best_match(points, ms, at, tol) {
+                let mut entry = point.values.clone();
+                entry.insert("observed_at".to_string(), Value::from(format_timestamp(point.ms)));
+                entry.insert("time_offset_minutes".to_string(), json!(minutes));
+                entry.insert("distance_m".to_string(), json!(distance));
+                environment.insert(name.clone(), Value::Object(entry));
+            }
+        }
+        if environment.is_empty() {
+            continue;
+        }
+        if let Some(context) = exp.get_mut("context").and_then(Value::as_object_mut) {
+            context.insert("environment".to_string(), Value::Object(environment));
+        }
+    }
+}
+
+/// Attach the nearest observation of each series to every experience
+/// `tolerance_json` may set `max_minutes` (default 60) and `max_distance_m`
+/// (default 25 km); pass an empty string for defaults
+/// Returns the annotated experiences as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn join_context_series(experiences_json: &str, timeseries_json: &str, tolerance_json: &str) -> Result<String, Error> {
+    let mut experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let observations: Vec<Observation> = from_json(timeseries_json, "timeseries_json")?;
+    let tolerance: Tolerance = if tolerance_json.trim().is_empty() {
+        Tolerance::default()
+    } else {
+        from_json(tolerance_json, "tolerance_json")?
+    };
+    if tolerance.max_minutes.is_nan() || tolerance.max_minutes < 0.0 || tolerance.max_distance_m.is_nan() || tolerance.max_distance_m < 0.0 {
+        return Err(Error::invalid("tolerances must be non-negative"));
+    }
+
+    let series = index_series(observations).map_err(Error::invalid)?;
+    join(&mut experiences, &series, &tolerance);
+    to_json(&experiences)
+}
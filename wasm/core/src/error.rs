@@ -0,0 +1,170 @@
+//! Structured errors for the JS boundary
+//!
+//! Every export fails with a JS `Error` named `UbicityError` that carries a
+//! machine-readable `kind` and a `context` object next to the message, so
+//! callers can branch on the failure instead of parsing text:
+//!
+//! ```js
+//! try {
+//!   query_experiences(experiences, "{\"filter\":");
+//! } catch (e) {
+//!   e.kind;    // "parse"
+//!   e.context; // {argument: "query_json", line: 1, column: 10}
+//! }
+//! ```
+//!
+//! Errors stay plain Rust values until they cross into JS, so the library
+//! can be exercised natively (tests, fuzzing) without a JS host.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::{prelude::*, JsCast};
+
+/// Broad failure category, exposed to JS as `error.kind`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Input was not well-formed JSON / CBOR / MessagePack or did not match
+    /// the expected shape
+    Parse,
+    /// Input parsed but a value is out of range or inconsistent
+    InvalidInput,
+    /// A referenced id, domain or record does not exist
+    NotFound,
+    /// The operation conflicts with existing state
+    Conflict,
+    /// Signature, hash or key material was rejected
+    Crypto,
+    /// A host API (IndexedDB, timers, callbacks) failed or is missing
+    Host,
+    /// A result could not be serialized
+    Serialization,
+}
+
+impl ErrorKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorKind::Parse => "parse",
+            ErrorKind::InvalidInput => "invalid_input",
+            ErrorKind::NotFound => "not_found",
+            ErrorKind::Conflict => "conflict",
+            ErrorKind::Crypto => "crypto",
+            ErrorKind::Host => "host",
+            ErrorKind::Serialization => "serialization",
+        }
+    }
+}
+
+/// Error returned by every export
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    message: String,
+    context: Map<String, Value>,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, message: impl fmt::Display) -> Self {
+        Error { kind, message: message.to_string(), context: Map::new() }
+    }
+
+    pub(crate) fn parse(message: impl fmt::Display) -> Self {
+        Error::new(ErrorKind::Parse, message)
+    }
+
+    pub(crate) fn invalid(message: impl fmt::Display) -> Self {
+        Error::new(ErrorKind::InvalidInput, message)
+    }
+
+    pub(crate) fn not_found(message: impl fmt::Display) -> Self {
+        Error::new(ErrorKind::NotFound, message)
+    }
+
+    pub(crate) fn conflict(message: impl fmt::Display) -> Self {
+        Error::new(ErrorKind::Conflict, message)
+    }
+
+    pub(crate) fn crypto(message: impl fmt::Display) -> Self {
+        Error::new(ErrorKind::Crypto, message)
+    }
+
+    pub(crate) fn host(message: impl fmt::Display) -> Self {
+        Error::new(ErrorKind::Host, message)
+    }
+
+    /// Attach a context entry, e.g. the argument at fault
+    pub(crate) fn with(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.context.insert(key.to_string(), value.into());
+        self
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn context(&self) -> &Map<String, Value> {
+        &self.context
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind.as_str(), self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(feature = "wasm")]
+impl From<Error> for JsValue {
+    fn from(error: Error) -> JsValue {
+        let js = js_sys::Error::new(&error.message);
+        js.set_name("UbicityError");
+        let context = js_sys::JSON::parse(&Value::Object(error.context).to_string()).unwrap_or(JsValue::NULL);
+        let _ = js_sys::Reflect::set(&js, &JsValue::from_str("kind"), &JsValue::from_str(error.kind.as_str()));
+        let _ = js_sys::Reflect::set(&js, &JsValue::from_str("context"), &context);
+        js.into()
+    }
+}
+
+/// Failures raised by the JS host (rejected promises, throwing callbacks)
+#[cfg(feature = "wasm")]
+impl From<JsValue> for Error {
+    fn from(value: JsValue) -> Error {
+        let message = match value.dyn_ref::<js_sys::Error>() {
+            Some(e) => String::from(e.message()),
+            None => value.as_string().unwrap_or_else(|| "host error".to_string()),
+        };
+        Error::host(message)
+    }
+}
+
+/// Parse a JSON argument, recording which argument failed and where
+pub(crate) fn from_json<T: DeserializeOwned>(json: &str, argument: &str) -> Result<T, Error> {
+    serde_json::from_str(json).map_err(|e| {
+        Error::parse(&e)
+            .with("argument", argument)
+            .with("line", e.line())
+            .with("column", e.column())
+    })
+}
+
+/// Serialize an export's result
+pub(crate) fn to_json<T: Serialize + ?Sized>(value: &T) -> Result<String, Error> {
+    serde_json::to_string(value).map_err(|e| Error::new(ErrorKind::Serialization, e))
+}
+
+/// Install a panic hook that reports Rust panics (with message and
+/// location) to the browser console instead of a bare "unreachable
+/// executed". Safe to call more than once.
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn init() {
+    console_error_panic_hook::set_once();
+}
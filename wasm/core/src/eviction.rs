@@ -0,0 +1,209 @@
+//! Importance scoring for local storage eviction
+//!
+//! Constrained devices keep the most valuable records locally and archive the
+//! rest to the server. Each record gets a score in [0, 1] from four weighted
+//! components:
+//!
+//! - recency: exponential decay with a configurable half-life
+//! - uniqueness: how rare its domains and location are in the local set
+//! - attachments: whether it carries artifacts (costly to re-fetch)
+//! - goal relevance: share of its domains that match the learner's goals
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::numeric::{self, ln, mean};
+use crate::time::{parse_timestamp, MS_PER_DAY};
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct Weights {
+    recency: f64,
+    uniqueness: f64,
+    attachments: f64,
+    goal_relevance: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            recency: 0.4,
+            uniqueness: 0.25,
+            attachments: 0.15,
+            goal_relevance: 0.2,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct EvictionPolicy {
+    weights: Weights,
+    half_life_days: f64,
+    goal_domains: Vec<String>,
+    /// Reference instant for recency; defaults to the newest record
+    as_of: Option<String>,
+    /// How many records to keep locally; the rest are marked for archive
+    keep: Option<usize>,
+}
+
+impl Default for EvictionPolicy {
+    fn default() -> Self {
+        Self {
+            weights: Weights::default(),
+            half_life_days: 30.0,
+            goal_domains: Vec::new(),
+            as_of: None,
+            keep: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Components {
+    recency: f64,
+    uniqueness: f64,
+    attachments: f64,
+    goal_relevance: f64,
+}
+
+#[derive(Serialize)]
+struct ScoredRecord {
+    id: String,
+    score: f64,
+    components: Components,
+}
+
+#[derive(Serialize)]
+struct EvictionPlan {
+    /// Most valuable first
+    ranked: Vec<ScoredRecord>,
+    keep: Vec<String>,
+    archive: Vec<String>,
+}
+
+fn domains(exp: &Value) -> Vec<&str> {
+    lookup(exp, "experience.domains")
+        .and_then(Value::as_array)
+        .map(|items| items.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default()
+}
+
+/// Normalised inverse document frequency in [0, 1]
+fn rarity(count: usize, total: usize) -> f64 {
+    if total <= 1 || count == 0 {
+        return 1.0;
+    }
+    ln(total as f64 / count as f64) / ln(total as f64)
+}
+
+fn score(experiences: &[Value], policy: &EvictionPolicy) -> Result<EvictionPlan, String> {
+    let total = experiences.len();
+    let mut domain_counts: HashMap<&str, usize> = HashMap::new();
+    let mut location_counts: HashMap<&str, usize> = HashMap::new();
+    for exp in experiences {
+        let mut seen = domains(exp);
+        seen.sort_unstable();
+        seen.dedup();
+        for d in seen {
+            *domain_counts.entry(d).or_insert(0) += 1;
+        }
+        if let Some(name) = lookup(exp, "context.location.name").and_then(Value::as_str) {
+            *location_counts.entry(name).or_insert(0) += 1;
+        }
+    }
+
+    let timestamps: Vec<Option<i64>> = experiences
+        .iter()
+        .map(|exp| exp.get("timestamp").and_then(Value::as_str).and_then(parse_timestamp))
+        .collect();
+    let as_of = match policy.as_of {
+        Some(ref s) => Some(parse_timestamp(s).ok_or_else(|| format!("invalid as_of: {}", s))?),
+        None => timestamps.iter().flatten().copied().max(),
+    };
+    let half_life_ms = policy.half_life_days.max(f64::MIN_POSITIVE) * MS_PER_DAY as f64;
+
+    let w = &policy.weights;
+    let weight_sum = w.recency + w.uniqueness + w.attachments + w.goal_relevance;
+    if weight_sum.is_nan() || weight_sum <= 0.0 {
+        return Err("weights must sum to a positive number".to_string());
+    }
+
+    let mut ranked: Vec<ScoredRecord> = experiences
+        .iter()
+        .zip(&timestamps)
+        .map(|(exp, ts)| {
+            let recency = match (ts, as_of) {
+                (Some(ts), Some(as_of)) => numeric::exp(-std::f64::consts::LN_2 * (as_of - ts).max(0) as f64 / half_life_ms),
+                _ => 0.0,
+            };
+
+            let exp_domains = domains(exp);
+            let mut parts: Vec<f64> = exp_domains.iter().map(|d| rarity(domain_counts[d], total)).collect();
+            if let Some(name) = lookup(exp, "context.location.name").and_then(Value::as_str) {
+                parts.push(rarity(location_counts[name], total));
+            }
+            let uniqueness = mean(&parts).unwrap_or(0.0);
+
+            let attachments = lookup(exp, "experience.artifacts")
+                .and_then(Value::as_array)
+                .map_or(0.0, |a| if a.is_empty() { 0.0 } else { 1.0 });
+
+            let goal_relevance = if exp_domains.is_empty() {
+                0.0
+            } else {
+                exp_domains
+                    .iter()
+                    .filter(|d| policy.goal_domains.iter().any(|g| g == *d))
+                    .count() as f64
+                    / exp_domains.len() as f64
+            };
+
+            let score = (w.recency * recency
+                + w.uniqueness * uniqueness
+                + w.attachments * attachments
+                + w.goal_relevance * goal_relevance)
+                / weight_sum;
+
+            ScoredRecord {
+                id: exp.get("id").and_then(Value::as_str).unwrap_or_default().to_string(),
+                score,
+                components: Components {
+                    recency,
+                    uniqueness,
+                    attachments,
+                    goal_relevance,
+                },
+            }
+        })
+        .collect();
+
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.id.cmp(&b.id)));
+    let keep_count = policy.keep.unwrap_or(ranked.len()).min(ranked.len());
+    let keep = ranked[..keep_count].iter().map(|r| r.id.clone()).collect();
+    let archive = ranked[keep_count..].iter().map(|r| r.id.clone()).collect();
+
+    Ok(EvictionPlan { ranked, keep, archive })
+}
+
+/// Rank experiences by how valuable they are to keep on the device
+/// `policy_json` may set `weights` (`recency`, `uniqueness`, `attachments`,
+/// `goal_relevance`), `half_life_days`, `goal_domains`, `as_of` and `keep`
+/// Returns `{ranked, keep, archive}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn score_for_eviction(experiences_json: &str, policy_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let policy: EvictionPolicy = if policy_json.trim().is_empty() {
+        EvictionPolicy::default()
+    } else {
+        from_json(policy_json, "policy_json")?
+    };
+
+    let plan = score(&experiences, &policy).map_err(Error::invalid)?;
+    to_json(&plan)
+}
@@ -0,0 +1,200 @@
+//! Tabular exports for spreadsheet tooling
+//!
+//! CSV output follows RFC 4180: comma separated, CRLF line endings, and
+//! fields quoted only when they contain a comma, quote or line break.
+//!
+//! Spreadsheets run a cell starting with `=`, `+`, `-`, `@`, tab or carriage
+//! return as a formula, so free text like a description of `=HYPERLINK(…)`
+//! could execute when a teacher opens the file. Such cells are written with a
+//! leading `'`, which spreadsheets show as plain text; cells that are
+//! numbers (negative coordinates, say) are left alone. Darwin Core archives
+//! are read by GBIF tooling rather than spreadsheets and keep their values
+//! verbatim.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::borrow::Cow;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::DomainNetwork;
+use crate::error::{from_json, to_json, Error};
+
+/// Columns used when no spec is supplied
+const DEFAULT_COLUMNS: &[&str] = &[
+    "id",
+    "timestamp",
+    "learner.id",
+    "context.location.name",
+    "context.location.coordinates.latitude",
+    "context.location.coordinates.longitude",
+    "experience.type",
+    "experience.description",
+    "experience.domains",
+];
+
+/// Separator used when an array field is flattened into a single cell
+const LIST_SEPARATOR: &str = ";";
+
+/// A column is either a dotted path (`"learner.id"`) or `{path, header}`
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(crate) enum ColumnSpec {
+    Path(String),
+    Named { path: String, header: Option<String> },
+}
+
+impl ColumnSpec {
+    fn path(&self) -> &str {
+        match self {
+            ColumnSpec::Path(path) | ColumnSpec::Named { path, .. } => path,
+        }
+    }
+
+    fn header(&self) -> &str {
+        match self {
+            ColumnSpec::Path(path) | ColumnSpec::Named { path, header: None } => path,
+            ColumnSpec::Named {
+                header: Some(header),
+                ..
+            } => header,
+        }
+    }
+}
+
+pub(crate) fn escape_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// `field`, prefixed with `'` if a spreadsheet would read it as a formula
+fn neutralize_formula(field: &str) -> Cow<'_, str> {
+    let risky = field.starts_with(['=', '+', '-', '@', '\t', '\r']);
+    if risky && field.parse::<f64>().is_err() {
+        Cow::Owned(format!("'{}", field))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// A CSV row for spreadsheets, with formula-like cells neutralized
+pub(crate) fn write_csv_row<S: AsRef<str>>(out: &mut String, fields: &[S]) {
+    let row: Vec<String> = fields.iter().map(|f| escape_csv_field(&neutralize_formula(f.as_ref()))).collect();
+    out.push_str(&row.join(","));
+    out.push_str("\r\n");
+}
+
+/// A CSV row with every value written as given, for machine consumers
+pub(crate) fn write_raw_csv_row<S: AsRef<str>>(out: &mut String, fields: &[S]) {
+    let row: Vec<String> = fields.iter().map(|f| escape_csv_field(f.as_ref())).collect();
+    out.push_str(&row.join(","));
+    out.push_str("\r\n");
+}
+
+/// Look up a dotted path in a JSON value
+pub(crate) fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(value, |current, key| current.get(key))
+}
+
+/// Parse a columns spec, falling back to the default columns when empty
+pub(crate) fn parse_columns(columns_spec: &str) -> Result<Vec<ColumnSpec>, String> {
+    if columns_spec.trim().is_empty() {
+        Ok(DEFAULT_COLUMNS
+            .iter()
+            .map(|path| ColumnSpec::Path(path.to_string()))
+            .collect())
+    } else {
+        serde_json::from_str(columns_spec).map_err(|e| e.to_string())
+    }
+}
+
+fn cell(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| cell(Some(item)))
+            .collect::<Vec<_>>()
+            .join(LIST_SEPARATOR),
+        Some(other) => other.to_string(),
+    }
+}
+
+pub(crate) fn experiences_csv(experiences: &[Value], columns: &[ColumnSpec]) -> String {
+    let mut out = String::new();
+    let headers: Vec<&str> = columns.iter().map(ColumnSpec::header).collect();
+    write_csv_row(&mut out, &headers);
+
+    for exp in experiences {
+        let row: Vec<String> = columns.iter().map(|c| cell(lookup(exp, c.path()))).collect();
+        write_csv_row(&mut out, &row);
+    }
+
+    out
+}
+
+#[derive(Serialize)]
+struct NetworkTables {
+    nodes: String,
+    edges: String,
+}
+
+fn network_csv(network: &DomainNetwork) -> NetworkTables {
+    let mut nodes: Vec<_> = network.nodes.iter().collect();
+    nodes.sort_by(|a, b| a.id.cmp(&b.id));
+    let mut edges: Vec<_> = network.edges.iter().collect();
+    edges.sort_by(|a, b| (&a.source, &a.target).cmp(&(&b.source, &b.target)));
+
+    let mut node_csv = String::new();
+    write_csv_row(&mut node_csv, &["id", "size"]);
+    for node in nodes {
+        write_csv_row(&mut node_csv, &[node.id.clone(), node.size.to_string()]);
+    }
+
+    // Networks from generate_domain_network_with_options carry a score
+    let scored = edges.iter().any(|edge| edge.score.is_some());
+    let mut edge_csv = String::new();
+    if scored {
+        write_csv_row(&mut edge_csv, &["source", "target", "weight", "score"]);
+    } else {
+        write_csv_row(&mut edge_csv, &["source", "target", "weight"]);
+    }
+    for edge in edges {
+        let mut row = vec![edge.source.clone(), edge.target.clone(), edge.weight.to_string()];
+        if scored {
+            row.push(edge.score.map(|score| score.to_string()).unwrap_or_default());
+        }
+        write_csv_row(&mut edge_csv, &row);
+    }
+
+    NetworkTables {
+        nodes: node_csv,
+        edges: edge_csv,
+    }
+}
+
+/// Export experiences as CSV
+/// `columns_spec` is a JSON array of dotted paths or `{path, header}` objects;
+/// pass an empty string for the default column set
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn export_experiences_csv(experiences_json: &str, columns_spec: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+
+    let columns = parse_columns(columns_spec).map_err(Error::invalid)?;
+
+    Ok(experiences_csv(&experiences, &columns))
+}
+
+/// Export a domain network as two CSV tables
+/// Returns `{nodes, edges}` as JSON, each value a complete CSV document; the
+/// edge table gains a `score` column when edges are scored
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn export_network_csv(network_json: &str) -> Result<String, Error> {
+    let network: DomainNetwork = from_json(network_json, "network_json")?;
+
+    to_json(&network_csv(&network))
+}
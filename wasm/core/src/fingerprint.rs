@@ -0,0 +1,177 @@
+//! Dataset statistics fingerprints for drift detection
+//!
+//! A fingerprint is a small summary of field distributions. Comparing the
+//! fingerprint of a new batch with a baseline reveals client updates that
+//! start producing systematically different data. Categorical and histogram
+//! components are compared with the Jensen–Shannon divergence (base 2, so it
+//! lies in [0, 1]); scalar components by bounded relative change.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::Experience;
+use crate::error::{from_json, to_json, Error};
+use crate::geo::haversine_m;
+use crate::numeric::{log2, sum};
+
+/// Upper edges of the description length histogram bins (characters); the
+/// last bin is open-ended
+const LENGTH_BINS: &[usize] = &[20, 50, 100, 200, 500];
+/// Drift score above which a component counts as drifted
+const DRIFT_THRESHOLD: f64 = 0.1;
+
+#[derive(Serialize, Deserialize)]
+struct Fingerprint {
+    count: usize,
+    type_frequencies: BTreeMap<String, f64>,
+    domain_frequencies: BTreeMap<String, f64>,
+    description_length_histogram: Vec<f64>,
+    coordinate_rate: f64,
+    domain_rate: f64,
+    /// Mean distance of geo-tagged records from their centroid, in metres
+    coordinate_dispersion_m: f64,
+}
+
+#[derive(Serialize)]
+struct DriftReport {
+    type_divergence: f64,
+    domain_divergence: f64,
+    description_length_divergence: f64,
+    coordinate_rate_change: f64,
+    domain_rate_change: f64,
+    dispersion_change: f64,
+    overall: f64,
+    drifted: Vec<&'static str>,
+}
+
+fn normalise(counts: BTreeMap<String, usize>, total: usize) -> BTreeMap<String, f64> {
+    counts
+        .into_iter()
+        .map(|(key, count)| (key, count as f64 / total.max(1) as f64))
+        .collect()
+}
+
+fn fingerprint(experiences: &[Experience]) -> Fingerprint {
+    let count = experiences.len();
+    let mut types: BTreeMap<String, usize> = BTreeMap::new();
+    let mut domains: BTreeMap<String, usize> = BTreeMap::new();
+    let mut histogram = vec![0usize; LENGTH_BINS.len() + 1];
+    let mut with_domains = 0;
+    let mut points = Vec::new();
+
+    for exp in experiences {
+        *types.entry(exp.experience.type_field.clone()).or_insert(0) += 1;
+        if let Some(ref ds) = exp.experience.domains {
+            with_domains += usize::from(!ds.is_empty());
+            for d in ds.iter().collect::<BTreeSet<_>>() {
+                *domains.entry(d.clone()).or_insert(0) += 1;
+            }
+        }
+        let length = exp.experience.description.chars().count();
+        let bin = LENGTH_BINS.iter().position(|&edge| length < edge).unwrap_or(LENGTH_BINS.len());
+        histogram[bin] += 1;
+        if let Some(ref c) = exp.context.location.coordinates {
+            points.push((c.latitude, c.longitude));
+        }
+    }
+
+    let dispersion = if points.is_empty() {
+        0.0
+    } else {
+        let n = points.len() as f64;
+        let (lat, lon) = points.iter().fold((0.0, 0.0), |acc, p| (acc.0 + p.0 / n, acc.1 + p.1 / n));
+        sum(points.iter().map(|p| haversine_m(lat, lon, p.0, p.1))) / n
+    };
+
+    Fingerprint {
+        count,
+        type_frequencies: normalise(types, count),
+        domain_frequencies: normalise(domains, count),
+        description_length_histogram: histogram.iter().map(|&c| c as f64 / count.max(1) as f64).collect(),
+        coordinate_rate: points.len() as f64 / count.max(1) as f64,
+        domain_rate: with_domains as f64 / count.max(1) as f64,
+        coordinate_dispersion_m: dispersion,
+    }
+}
+
+/// Jensen–Shannon divergence (base 2) of two distributions given as aligned
+/// weight vectors; each is renormalised first
+fn js_divergence(p: &[f64], q: &[f64]) -> f64 {
+    let (sp, sq) = (sum(p.iter().copied()), sum(q.iter().copied()));
+    if sp <= 0.0 || sq <= 0.0 {
+        return if sp <= 0.0 && sq <= 0.0 { 0.0 } else { 1.0 };
+    }
+    let kl = |a: f64, m: f64| if a > 0.0 { a * log2(a / m) } else { 0.0 };
+    sum(p.iter().zip(q).map(|(&a, &b)| {
+        let (a, b) = (a / sp, b / sq);
+        let m = (a + b) / 2.0;
+        (kl(a, m) + kl(b, m)) / 2.0
+    }))
+    .clamp(0.0, 1.0)
+}
+
+fn map_divergence(a: &BTreeMap<String, f64>, b: &BTreeMap<String, f64>) -> f64 {
+    let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+    let p: Vec<f64> = keys.iter().map(|k| a.get(*k).copied().unwrap_or(0.0)).collect();
+    let q: Vec<f64> = keys.iter().map(|k| b.get(*k).copied().unwrap_or(0.0)).collect();
+    js_divergence(&p, &q)
+}
+
+/// Relative change bounded to [0, 1]
+fn relative_change(a: f64, b: f64) -> f64 {
+    let scale = a.abs().max(b.abs());
+    if scale == 0.0 {
+        0.0
+    } else {
+        ((a - b).abs() / scale).min(1.0)
+    }
+}
+
+fn compare(a: &Fingerprint, b: &Fingerprint) -> DriftReport {
+    let components = [
+        ("type", map_divergence(&a.type_frequencies, &b.type_frequencies)),
+        ("domain", map_divergence(&a.domain_frequencies, &b.domain_frequencies)),
+        (
+            "description_length",
+            js_divergence(&a.description_length_histogram, &b.description_length_histogram),
+        ),
+        ("coordinate_rate", (a.coordinate_rate - b.coordinate_rate).abs()),
+        ("domain_rate", (a.domain_rate - b.domain_rate).abs()),
+        ("dispersion", relative_change(a.coordinate_dispersion_m, b.coordinate_dispersion_m)),
+    ];
+
+    DriftReport {
+        type_divergence: components[0].1,
+        domain_divergence: components[1].1,
+        description_length_divergence: components[2].1,
+        coordinate_rate_change: components[3].1,
+        domain_rate_change: components[4].1,
+        dispersion_change: components[5].1,
+        overall: sum(components.iter().map(|c| c.1)) / components.len() as f64,
+        drifted: components
+            .iter()
+            .filter(|c| c.1 > DRIFT_THRESHOLD)
+            .map(|c| c.0)
+            .collect(),
+    }
+}
+
+/// Summarise field distributions of a dataset
+/// Returns the fingerprint as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn dataset_fingerprint(experiences_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Experience> = from_json(experiences_json, "experiences_json")?;
+    to_json(&fingerprint(&experiences))
+}
+
+/// Compare two fingerprints
+/// Returns per-component drift scores in [0, 1], their mean as `overall`,
+/// and the names of components above the drift threshold, as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn compare_fingerprints(a_json: &str, b_json: &str) -> Result<String, Error> {
+    let a: Fingerprint = from_json(a_json, "a_json")?;
+    let b: Fingerprint = from_json(b_json, "b_json")?;
+    to_json(&compare(&a, &b))
+}
@@ -0,0 +1,92 @@
+//! Binary (CBOR / MessagePack) entry points
+//!
+//! Clients that already persist experiences in a binary encoding can hand the
+//! bytes straight to the validator and network builder instead of
+//! re-serializing to JSON first. Results are returned in the same encoding as
+//! the input.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{Error, ErrorKind};
+use crate::pruning;
+use crate::{accumulate_network, build_network, Experience, ExperienceValidator, ValidationResult};
+
+fn from_cbor<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, String> {
+    ciborium::de::from_reader(bytes).map_err(|e| e.to_string())
+}
+
+fn to_cbor<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    ciborium::ser::into_writer(value, &mut out).map_err(|e| Error::new(ErrorKind::Serialization, e))?;
+    Ok(out)
+}
+
+fn from_msgpack<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, String> {
+    rmp_serde::from_slice(bytes).map_err(|e| e.to_string())
+}
+
+fn to_msgpack<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+    // Named (map) encoding so generic JS msgpack decoders get field names
+    rmp_serde::to_vec_named(value).map_err(|e| Error::new(ErrorKind::Serialization, e))
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl ExperienceValidator {
+    /// Validate a CBOR-encoded learning experience
+    /// Returns the validation result as CBOR
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn validate_cbor(&self, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        to_cbor(&self.validate_decoded(from_cbor(bytes)))
+    }
+
+    /// Validate a MessagePack-encoded learning experience
+    /// Returns the validation result as MessagePack
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn validate_msgpack(&self, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        to_msgpack(&self.validate_decoded(from_msgpack(bytes)))
+    }
+
+    fn validate_decoded(&self, decoded: Result<Experience, String>) -> ValidationResult {
+        match decoded {
+            Ok(exp) => self.validate_experience(&exp),
+            Err(e) => self.parse_failure(e),
+        }
+    }
+}
+
+/// Domain network generation from a CBOR array of experiences
+/// Returns the network as CBOR
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn generate_domain_network_cbor(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let experiences: Vec<Experience> = from_cbor(bytes).map_err(|e| Error::parse(e).with("argument", "bytes"))?;
+    to_cbor(&build_network(&experiences))
+}
+
+/// `generate_domain_network_cbor` with the
+/// `generate_domain_network_with_options` options applied
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn generate_domain_network_cbor_with_options(bytes: &[u8], options_json: &str) -> Result<Vec<u8>, Error> {
+    let experiences: Vec<Experience> = from_cbor(bytes).map_err(|e| Error::parse(e).with("argument", "bytes"))?;
+    let options = pruning::parse_options(options_json)?;
+    to_cbor(&pruning::simplified(accumulate_network(&experiences), &options))
+}
+
+/// Domain network generation from a MessagePack array of experiences
+/// Returns the network as MessagePack
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn generate_domain_network_msgpack(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let experiences: Vec<Experience> = from_msgpack(bytes).map_err(|e| Error::parse(e).with("argument", "bytes"))?;
+    to_msgpack(&build_network(&experiences))
+}
+
+/// `generate_domain_network_msgpack` with the
+/// `generate_domain_network_with_options` options applied
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn generate_domain_network_msgpack_with_options(bytes: &[u8], options_json: &str) -> Result<Vec<u8>, Error> {
+    let experiences: Vec<Experience> = from_msgpack(bytes).map_err(|e| Error::parse(e).with("argument", "bytes"))?;
+    let options = pruning::parse_options(options_json)?;
+    to_msgpack(&pruning::simplified(accumulate_network(&experiences), &options))
+}
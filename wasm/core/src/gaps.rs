@@ -0,0 +1,181 @@
+//! Learning-gap analysis against a competency framework
+//!
+//! A framework lists competencies (matched against experience domains) and
+//! their prerequisites:
+//!
+//! ```json
+//! {"competencies": [
+//!   {"id": "arithmetic"},
+//!   {"id": "algebra", "prerequisites": ["arithmetic"], "target": 3},
+//!   {"id": "calculus", "prerequisites": ["algebra"]}
+//! ]}
+//! ```
+//!
+//! A competency is covered once any experience touches its domain; its depth
+//! is the number of such experiences relative to `target` (default 1).
+//! Every missing competency gets the shortest prerequisite path leading to it
+//! from something already covered (or from a foundation with no
+//! prerequisites), so a learner can see the next concrete steps.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::query::domains;
+
+#[derive(Deserialize)]
+struct Framework {
+    competencies: Vec<Competency>,
+}
+
+#[derive(Deserialize)]
+struct Competency {
+    id: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    prerequisites: Vec<String>,
+    #[serde(default = "default_target")]
+    target: u32,
+}
+
+fn default_target() -> u32 {
+    1
+}
+
+#[derive(Serialize)]
+struct Covered {
+    id: String,
+    name: Option<String>,
+    experiences: u32,
+    /// Experiences relative to the target, capped at 1
+    depth: f64,
+}
+
+#[derive(Serialize)]
+struct Missing {
+    id: String,
+    name: Option<String>,
+    /// Whether every prerequisite is already covered
+    ready: bool,
+    /// Shortest chain ending at this competency, starting from a covered
+    /// competency or a foundation
+    path: Vec<String>,
+    /// Competencies on the path still to be covered, this one included
+    steps: usize,
+}
+
+#[derive(Serialize)]
+struct GapReport {
+    coverage: f64,
+    mean_depth: f64,
+    covered: Vec<Covered>,
+    missing: Vec<Missing>,
+}
+
+fn check_framework(framework: &Framework) -> Result<HashMap<&str, &Competency>, String> {
+    let mut by_id = HashMap::new();
+    for c in &framework.competencies {
+        if by_id.insert(c.id.as_str(), c).is_some() {
+            return Err(format!("duplicate competency: {}", c.id));
+        }
+    }
+    for c in &framework.competencies {
+        if let Some(p) = c.prerequisites.iter().find(|p| !by_id.contains_key(p.as_str())) {
+            return Err(format!("{} requires unknown competency {}", c.id, p));
+        }
+    }
+    Ok(by_id)
+}
+
+/// Breadth-first search back along prerequisite edges to the nearest
+/// covered competency or foundation; ties go to the lexicographically
+/// smallest prerequisite for determinism
+fn path_to<'a>(target: &'a str, by_id: &HashMap<&'a str, &'a Competency>, counts: &BTreeMap<&str, u32>) -> Vec<String> {
+    let mut came_from: HashMap<&str, &str> = HashMap::new();
+    let mut queue = VecDeque::from([target]);
+    let mut seen = BTreeSet::from([target]);
+    let mut start = target;
+
+    while let Some(current) = queue.pop_front() {
+        let competency = by_id[current];
+        if current != target && counts.contains_key(current) || competency.prerequisites.is_empty() {
+            start = current;
+            break;
+        }
+        let prerequisites: BTreeSet<&str> = competency.prerequisites.iter().map(String::as_str).collect();
+        for p in prerequisites {
+            if seen.insert(p) {
+                came_from.insert(p, current);
+                queue.push_back(p);
+            }
+        }
+    }
+
+    let mut path = vec![start.to_string()];
+    let mut current = start;
+    while let Some(&next) = came_from.get(current) {
+        path.push(next.to_string());
+        current = next;
+    }
+    path
+}
+
+fn analyse(experiences: &[Value], framework: &Framework) -> Result<GapReport, String> {
+    let by_id = check_framework(framework)?;
+    let mut counts: BTreeMap<&str, u32> = BTreeMap::new();
+    for exp in experiences {
+        for d in domains(exp).collect::<BTreeSet<_>>() {
+            if let Some((&id, _)) = by_id.get_key_value(d) {
+                *counts.entry(id).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let mut covered = Vec::new();
+    let mut missing = Vec::new();
+    for c in &framework.competencies {
+        match counts.get(c.id.as_str()) {
+            Some(&n) => covered.push(Covered {
+                id: c.id.clone(),
+                name: c.name.clone(),
+                experiences: n,
+                depth: (f64::from(n) / f64::from(c.target.max(1))).min(1.0),
+            }),
+            None => {
+                let path = path_to(&c.id, &by_id, &counts);
+                missing.push(Missing {
+                    id: c.id.clone(),
+                    name: c.name.clone(),
+                    ready: c.prerequisites.iter().all(|p| counts.contains_key(p.as_str())),
+                    steps: path.iter().filter(|id| !counts.contains_key(id.as_str())).count(),
+                    path,
+                })
+            }
+        }
+    }
+    missing.sort_by(|a, b| a.steps.cmp(&b.steps).then_with(|| a.id.cmp(&b.id)));
+
+    let total = framework.competencies.len().max(1) as f64;
+    Ok(GapReport {
+        coverage: covered.len() as f64 / total,
+        mean_depth: covered.iter().fold(0.0, |acc, c| acc + c.depth) / total,
+        covered,
+        missing,
+    })
+}
+
+/// Compare a learner's experiences with a competency framework
+/// Returns `{coverage, mean_depth, covered, missing}` as JSON, missing
+/// competencies nearest first
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn gap_analysis(experiences_json: &str, framework_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let framework: Framework = from_json(framework_json, "framework_json")?;
+
+    let report = analyse(&experiences, &framework).map_err(Error::invalid)?;
+    to_json(&report)
+}
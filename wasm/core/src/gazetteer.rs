@@ -0,0 +1,266 @@
+//! Offline reverse geocoding
+//!
+//! Field devices often have a GPS fix but no network, so
+//! `context.location.name` is left for the learner to type. A [`Gazetteer`]
+//! answers "what is near here" locally. The host builds one from place
+//! records once (e.g. a GeoNames extract for the region),
+//!
+//! ```json
+//! [{"name": "Aberystwyth", "region": "Ceredigion", "country": "GB",
+//!   "latitude": 52.4153, "longitude": -4.0829}]
+//! ```
+//!
+//! ships the compact binary from `to_bytes` with the app, and restores it
+//! with `from_bytes` at start-up. Positions are stored to 1e-5 degrees
+//! (about a metre) and region and country names once each, which keeps a
+//! national gazetteer of tens of thousands of places to a few hundred KB.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error, ErrorKind};
+use crate::geo::{haversine_m, EARTH_RADIUS_M};
+
+const FORMAT_VERSION: u32 = 1;
+const SCALE: f64 = 1e5;
+const DEFAULT_MAX_DISTANCE_M: f64 = 50_000.0;
+
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    kind: String,
+    version: u32,
+    gazetteer: T,
+}
+
+#[derive(Deserialize)]
+struct PlaceRecord {
+    name: String,
+    #[serde(default)]
+    region: Option<String>,
+    #[serde(default)]
+    country: Option<String>,
+    latitude: f64,
+    longitude: f64,
+}
+
+/// Columnar storage, sorted by latitude; `region` and `country` index into
+/// the string tables, `u32::MAX` meaning none
+#[derive(Serialize, Deserialize, Default)]
+struct Columns {
+    names: Vec<String>,
+    latitudes: Vec<i32>,
+    longitudes: Vec<i32>,
+    region: Vec<u32>,
+    country: Vec<u32>,
+    regions: Vec<String>,
+    countries: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct NearestPlace<'a> {
+    name: &'a str,
+    region: Option<&'a str>,
+    country: Option<&'a str>,
+    latitude: f64,
+    longitude: f64,
+    distance_m: f64,
+}
+
+fn intern(table: &mut Vec<String>, index: &mut HashMap<String, u32>, value: Option<String>) -> u32 {
+    match value {
+        Some(value) => *index.entry(value.clone()).or_insert_with(|| {
+            table.push(value);
+            (table.len() - 1) as u32
+        }),
+        None => u32::MAX,
+    }
+}
+
+fn lookup_table(table: &[String], i: u32) -> Option<&str> {
+    table.get(i as usize).map(String::as_str)
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Error> {
+    let envelope: Envelope<ciborium::Value> =
+        ciborium::de::from_reader(bytes).map_err(|e| Error::parse(e).with("argument", "bytes"))?;
+    if envelope.kind != "gazetteer" {
+        return Err(Error::invalid(format!("expected a gazetteer, got {}", envelope.kind)).with("kind", envelope.kind));
+    }
+    if envelope.version != FORMAT_VERSION {
+        return Err(Error::invalid(format!("unsupported gazetteer version {}", envelope.version))
+            .with("version", envelope.version));
+    }
+    envelope
+        .gazetteer
+        .deserialized()
+        .map_err(|e| Error::parse(e).with("argument", "bytes"))
+}
+
+/// Places indexed for nearest-neighbour lookup
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct Gazetteer {
+    columns: Columns,
+    max_distance_m: f64,
+}
+
+impl Gazetteer {
+    fn position(&self, i: usize) -> (f64, f64) {
+        (self.columns.latitudes[i] as f64 / SCALE, self.columns.longitudes[i] as f64 / SCALE)
+    }
+
+    /// Index of the nearest place within `max_distance_m`: scan outwards
+    /// from the query's latitude until the latitude gap alone exceeds the
+    /// best distance found
+    fn nearest(&self, lat: f64, lon: f64) -> Option<(usize, f64)> {
+        let metres_per_degree = EARTH_RADIUS_M * std::f64::consts::PI / 180.0;
+        let target = (lat * SCALE).round() as i32;
+        let split = self.columns.latitudes.partition_point(|&l| l < target);
+        let mut best: Option<(usize, f64)> = None;
+        let mut bound = self.max_distance_m;
+        let (mut below, mut above) = (split, split);
+        loop {
+            let next_below = below.checked_sub(1).map(|i| (i, (lat - self.position(i).0).abs()));
+            let next_above = (above < self.columns.names.len()).then(|| (above, (self.position(above).0 - lat).abs()));
+            let (i, gap) = match (next_below, next_above) {
+                (Some(b), Some(a)) if b.1 <= a.1 => b,
+                (Some(_), Some(a)) => a,
+                (Some(b), None) => b,
+                (None, Some(a)) => a,
+                (None, None) => break,
+            };
+            if gap * metres_per_degree > bound {
+                break;
+            }
+            if i < split {
+                below = i;
+            } else {
+                above = i + 1;
+            }
+            let (p_lat, p_lon) = self.position(i);
+            let d = haversine_m(lat, lon, p_lat, p_lon);
+            if d <= bound && best.is_none_or(|(_, best_d)| d < best_d) {
+                best = Some((i, d));
+                bound = d;
+            }
+        }
+        best
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Gazetteer {
+    /// Build a gazetteer from a JSON array of `{name, region, country,
+    /// latitude, longitude}`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn from_json(places_json: &str) -> Result<Gazetteer, Error> {
+        let mut places: Vec<PlaceRecord> = from_json(places_json, "places_json")?;
+        for (i, p) in places.iter().enumerate() {
+            if p.name.is_empty() {
+                return Err(Error::invalid(format!("place {} has no name", i)).with("index", i));
+            }
+            if !(-90.0..=90.0).contains(&p.latitude) || !(-180.0..=180.0).contains(&p.longitude) {
+                return Err(Error::invalid(format!("place {} is out of range", i)).with("index", i));
+            }
+        }
+        places.sort_by(|a, b| a.latitude.total_cmp(&b.latitude).then_with(|| a.name.cmp(&b.name)));
+
+        let mut columns = Columns::default();
+        let (mut regions, mut countries) = (HashMap::new(), HashMap::new());
+        for p in places {
+            columns.latitudes.push((p.latitude * SCALE).round() as i32);
+            columns.longitudes.push((p.longitude * SCALE).round() as i32);
+            columns.region.push(intern(&mut columns.regions, &mut regions, p.region));
+            columns.country.push(intern(&mut columns.countries, &mut countries, p.country));
+            columns.names.push(p.name);
+        }
+        Ok(Self {
+            columns,
+            max_distance_m: DEFAULT_MAX_DISTANCE_M,
+        })
+    }
+
+    /// Serialize to the compact binary form
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let envelope = Envelope {
+            kind: "gazetteer".to_string(),
+            version: FORMAT_VERSION,
+            gazetteer: &self.columns,
+        };
+        let mut out = Vec::new();
+        ciborium::ser::into_writer(&envelope, &mut out).map_err(|e| Error::new(ErrorKind::Serialization, e))?;
+        Ok(out)
+    }
+
+    /// Restore a gazetteer produced by `to_bytes`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Gazetteer, Error> {
+        let columns: Columns = decode(bytes)?;
+        let n = columns.names.len();
+        let consistent = [columns.latitudes.len(), columns.longitudes.len(), columns.region.len(), columns.country.len()]
+            .iter()
+            .all(|&len| len == n)
+            && columns.latitudes.windows(2).all(|w| w[0] <= w[1])
+            && columns.region.iter().all(|&r| r == u32::MAX || (r as usize) < columns.regions.len())
+            && columns.country.iter().all(|&c| c == u32::MAX || (c as usize) < columns.countries.len());
+        if !consistent {
+            return Err(Error::invalid("gazetteer columns are inconsistent"));
+        }
+        Ok(Self {
+            columns,
+            max_distance_m: DEFAULT_MAX_DISTANCE_M,
+        })
+    }
+
+    /// Number of places
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn len(&self) -> usize {
+        self.columns.names.len()
+    }
+
+    /// Whether the gazetteer has no places
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn is_empty(&self) -> bool {
+        self.columns.names.is_empty()
+    }
+
+    /// Places farther than this are not returned (default 50 km)
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn max_distance_m(&self) -> f64 {
+        self.max_distance_m
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter))]
+    pub fn set_max_distance_m(&mut self, meters: f64) -> Result<(), Error> {
+        if meters.is_nan() || meters <= 0.0 {
+            return Err(Error::invalid("max_distance_m must be positive"));
+        }
+        self.max_distance_m = meters;
+        Ok(())
+    }
+
+    /// Nearest place to a position
+    /// Returns `{name, region, country, latitude, longitude, distance_m}` as
+    /// JSON, or `null` when no place is within `max_distance_m`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn nearest_place(&self, lat: f64, lon: f64) -> Result<String, Error> {
+        if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+            return Err(Error::invalid("coordinates are out of range").with("latitude", lat).with("longitude", lon));
+        }
+        let place = self.nearest(lat, lon).map(|(i, distance_m)| {
+            let (latitude, longitude) = self.position(i);
+            NearestPlace {
+                name: &self.columns.names[i],
+                region: lookup_table(&self.columns.regions, self.columns.region[i]),
+                country: lookup_table(&self.columns.countries, self.columns.country[i]),
+                latitude,
+                longitude,
+                distance_m,
+            }
+        });
+        to_json(&place)
+    }
+}
@@ -0,0 +1,63 @@
+//! Spherical geometry helpers shared by the spatial analytics
+
+use crate::numeric::{asin, atan2, coordinate, cos, powi, sin};
+
+/// Mean Earth radius in metres (IUGG)
+pub(crate) const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+/// Great-circle distance in metres between two WGS84 points
+pub(crate) fn haversine_m(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (lat1, lon1, lat2, lon2) = (coordinate(lat1), coordinate(lon1), coordinate(lat2), coordinate(lon2));
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let dphi = (lat2 - lat1).to_radians();
+    let dlambda = (lon2 - lon1).to_radians();
+
+    let a = powi(sin(dphi / 2.0), 2) + cos(phi1) * cos(phi2) * powi(sin(dlambda / 2.0), 2);
+    2.0 * EARTH_RADIUS_M * asin(a.sqrt().min(1.0))
+}
+
+/// Initial great-circle bearing in degrees clockwise from north, 0-360
+pub(crate) fn bearing_deg(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (phi1, phi2) = (coordinate(lat1).to_radians(), coordinate(lat2).to_radians());
+    let dlambda = (coordinate(lon2) - coordinate(lon1)).to_radians();
+    let y = sin(dlambda) * cos(phi2);
+    let x = cos(phi1) * sin(phi2) - sin(phi1) * cos(phi2) * cos(dlambda);
+    atan2(y, x).to_degrees().rem_euclid(360.0)
+}
+
+const GEOHASH_ALPHABET: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// Standard base-32 geohash of a point at `precision` characters (1-12)
+pub(crate) fn geohash(lat: f64, lon: f64, precision: usize) -> String {
+    let (lat, lon) = (coordinate(lat), coordinate(lon));
+    let (mut lat_range, mut lon_range) = ((-90.0, 90.0), (-180.0, 180.0));
+    let mut hash = String::with_capacity(precision);
+    let mut even = true;
+    let mut bits = 0u8;
+    let mut bit_count = 0;
+
+    while hash.len() < precision.clamp(1, 12) {
+        let (range, value) = if even {
+            (&mut lon_range, lon)
+        } else {
+            (&mut lat_range, lat)
+        };
+        let mid = (range.0 + range.1) / 2.0;
+        bits <<= 1;
+        if value >= mid {
+            bits |= 1;
+            range.0 = mid;
+        } else {
+            range.1 = mid;
+        }
+        even = !even;
+        bit_count += 1;
+        if bit_count == 5 {
+            hash.push(GEOHASH_ALPHABET[bits as usize] as char);
+            bits = 0;
+            bit_count = 0;
+        }
+    }
+
+    hash
+}
@@ -0,0 +1,166 @@
+//! Minimal GeoJSON (RFC 7946) geometry reading shared by the spatial
+//! features. Positions are `[longitude, latitude]`; altitude is ignored.
+
+use serde_json::{Map, Value};
+
+use crate::geo::{haversine_m, EARTH_RADIUS_M};
+use crate::numeric::cos;
+
+pub(crate) type Position = [f64; 2];
+
+pub(crate) enum Geometry {
+    Point(Position),
+    MultiPoint(Vec<Position>),
+    LineString(Vec<Position>),
+    MultiLineString(Vec<Vec<Position>>),
+    /// Outer ring followed by holes
+    Polygon(Vec<Vec<Position>>),
+    MultiPolygon(Vec<Vec<Vec<Position>>>),
+}
+
+pub(crate) struct Feature {
+    pub(crate) id: Option<Value>,
+    pub(crate) geometry: Geometry,
+    pub(crate) properties: Map<String, Value>,
+}
+
+fn position(value: &Value) -> Result<Position, String> {
+    let coords = value.as_array().filter(|c| c.len() >= 2).ok_or("position must be [lon, lat]")?;
+    match (coords[0].as_f64(), coords[1].as_f64()) {
+        (Some(lon), Some(lat)) => Ok([lon, lat]),
+        _ => Err("position must be numeric".to_string()),
+    }
+}
+
+fn positions(value: &Value) -> Result<Vec<Position>, String> {
+    value.as_array().ok_or("expected an array of positions")?.iter().map(position).collect()
+}
+
+fn nested<T>(value: &Value, inner: fn(&Value) -> Result<T, String>) -> Result<Vec<T>, String> {
+    value.as_array().ok_or("expected a coordinate array")?.iter().map(inner).collect()
+}
+
+fn rings(value: &Value) -> Result<Vec<Vec<Position>>, String> {
+    nested(value, positions)
+}
+
+impl Geometry {
+    pub(crate) fn parse(value: &Value) -> Result<Self, String> {
+        let kind = value.get("type").and_then(Value::as_str).ok_or("geometry needs a type")?;
+        let coords = value.get("coordinates").ok_or("geometry needs coordinates")?;
+        Ok(match kind {
+            "Point" => Geometry::Point(position(coords)?),
+            "MultiPoint" => Geometry::MultiPoint(positions(coords)?),
+            "LineString" => Geometry::LineString(positions(coords)?),
+            "MultiLineString" => Geometry::MultiLineString(rings(coords)?),
+            "Polygon" => Geometry::Polygon(rings(coords)?),
+            "MultiPolygon" => Geometry::MultiPolygon(nested(coords, rings)?),
+            other => return Err(format!("unsupported geometry type: {}", other)),
+        })
+    }
+
+    /// Whether a point lies inside a (multi)polygon; always false for
+    /// points and lines
+    pub(crate) fn contains(&self, lon: f64, lat: f64) -> bool {
+        match self {
+            Geometry::Polygon(rings) => polygon_contains(rings, lon, lat),
+            Geometry::MultiPolygon(polygons) => polygons.iter().any(|rings| polygon_contains(rings, lon, lat)),
+            _ => false,
+        }
+    }
+
+    /// Approximate distance in metres from a point to the geometry; zero
+    /// inside polygons
+    pub(crate) fn distance_m(&self, lon: f64, lat: f64) -> f64 {
+        if self.contains(lon, lat) {
+            return 0.0;
+        }
+        self.edge_distance_m(lon, lat)
+    }
+
+    /// Approximate distance in metres from a point to the nearest point,
+    /// line or polygon ring of the geometry, from inside polygons too
+    pub(crate) fn edge_distance_m(&self, lon: f64, lat: f64) -> f64 {
+        let to_points = |points: &[Position]| {
+            points
+                .iter()
+                .map(|p| haversine_m(lat, lon, p[1], p[0]))
+                .fold(f64::INFINITY, f64::min)
+        };
+        let to_lines = |lines: &mut dyn Iterator<Item = &Vec<Position>>| {
+            lines.map(|line| path_distance_m(line, lon, lat)).fold(f64::INFINITY, f64::min)
+        };
+        match self {
+            Geometry::Point(p) => to_points(std::slice::from_ref(p)),
+            Geometry::MultiPoint(points) => to_points(points),
+            Geometry::LineString(line) => path_distance_m(line, lon, lat),
+            Geometry::MultiLineString(lines) | Geometry::Polygon(lines) => to_lines(&mut lines.iter()),
+            Geometry::MultiPolygon(polygons) => to_lines(&mut polygons.iter().flatten()),
+        }
+    }
+}
+
+/// Even–odd ray casting against the outer ring, excluding holes
+fn polygon_contains(rings: &[Vec<Position>], lon: f64, lat: f64) -> bool {
+    let mut inside = false;
+    for ring in rings {
+        let mut j = ring.len().wrapping_sub(1);
+        for i in 0..ring.len() {
+            let ([xi, yi], [xj, yj]) = (ring[i], ring[j]);
+            if (yi > lat) != (yj > lat) && lon < (xj - xi) * (lat - yi) / (yj - yi) + xi {
+                inside = !inside;
+            }
+            j = i;
+        }
+    }
+    inside
+}
+
+/// Distance to a polyline in a local equirectangular projection around the
+/// query point; accurate to well under a percent at neighbourhood scale
+fn path_distance_m(path: &[Position], lon: f64, lat: f64) -> f64 {
+    let scale = EARTH_RADIUS_M * std::f64::consts::PI / 180.0;
+    let cos_lat = cos(lat.to_radians());
+    let project = |p: &Position| ((p[0] - lon) * cos_lat * scale, (p[1] - lat) * scale);
+    match path {
+        [] => f64::INFINITY,
+        [single] => haversine_m(lat, lon, single[1], single[0]),
+        _ => path
+            .windows(2)
+            .map(|segment| {
+                let ((ax, ay), (bx, by)) = (project(&segment[0]), project(&segment[1]));
+                let (dx, dy) = (bx - ax, by - ay);
+                let length2 = dx * dx + dy * dy;
+                let t = if length2 > 0.0 { (-(ax * dx + ay * dy) / length2).clamp(0.0, 1.0) } else { 0.0 };
+                let (px, py) = (ax + t * dx, ay + t * dy);
+                (px * px + py * py).sqrt()
+            })
+            .fold(f64::INFINITY, f64::min),
+    }
+}
+
+/// Features of a FeatureCollection, a single Feature or a bare geometry
+pub(crate) fn parse_features(value: &Value) -> Result<Vec<Feature>, String> {
+    let feature = |f: &Value| -> Result<Feature, String> {
+        Ok(Feature {
+            id: f.get("id").cloned(),
+            geometry: Geometry::parse(f.get("geometry").ok_or("feature needs a geometry")?)?,
+            properties: f.get("properties").and_then(Value::as_object).cloned().unwrap_or_default(),
+        })
+    };
+    match value.get("type").and_then(Value::as_str) {
+        Some("FeatureCollection") => value
+            .get("features")
+            .and_then(Value::as_array)
+            .ok_or("FeatureCollection needs features")?
+            .iter()
+            .map(feature)
+            .collect(),
+        Some("Feature") => Ok(vec![feature(value)?]),
+        _ => Ok(vec![Feature {
+            id: None,
+            geometry: Geometry::parse(value)?,
+            properties: Map::new(),
+        }]),
+    }
+}
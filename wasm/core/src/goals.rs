@@ -0,0 +1,185 @@
+//! Learning goals and what-if progress projection
+//!
+//! A goal counts the experiences matching a query filter (see
+//! [`crate::query`]) towards a target:
+//!
+//! ```json
+//! {"id": "outdoor-20", "name": "20 outdoor sessions",
+//!  "filter": {"field": {"path": "experience.type", "op": "eq", "value": "fieldwork"}},
+//!  "target": 20}
+//! ```
+//!
+//! [`simulate_progress`] projects when goals would be met under a
+//! hypothetical weekly pattern of sessions, each described by a template
+//! experience that goal filters are evaluated against.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::numeric::sum;
+use crate::query::Filter;
+use crate::time::{format_timestamp, now_ms, parse_timestamp, MS_PER_DAY};
+
+const MS_PER_WEEK: i64 = 7 * MS_PER_DAY;
+/// Upper bound on simulated sessions per call
+const MAX_SESSIONS: f64 = 100_000.0;
+
+#[derive(Deserialize)]
+pub(crate) struct Goal {
+    pub(crate) id: String,
+    #[serde(default)]
+    pub(crate) name: Option<String>,
+    /// Experiences counted towards the goal; all experiences when omitted
+    #[serde(default)]
+    pub(crate) filter: Option<Filter>,
+    pub(crate) target: u32,
+}
+
+impl Goal {
+    pub(crate) fn counts(&self, exp: &Value) -> bool {
+        self.filter.as_ref().is_none_or(|f| f.matches(exp))
+    }
+}
+
+pub(crate) fn check_goals(goals: &[Goal]) -> Result<(), String> {
+    for goal in goals {
+        if let Some(ref filter) = goal.filter {
+            filter.check().map_err(|e| format!("goal {}: {}", goal.id, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Parse and check a goals array
+pub(crate) fn parse_goals(goals_json: &str) -> Result<Vec<Goal>, String> {
+    let goals: Vec<Goal> = serde_json::from_str(goals_json).map_err(|e| e.to_string())?;
+    check_goals(&goals)?;
+    Ok(goals)
+}
+
+#[derive(Deserialize)]
+struct WeeklyPattern {
+    /// Projection start; defaults to now
+    #[serde(default)]
+    start: Option<String>,
+    sessions: Vec<Session>,
+}
+
+#[derive(Deserialize)]
+struct Session {
+    /// Sessions per week; fractions spread sessions over several weeks
+    per_week: f64,
+    /// Experience the session would produce; its `timestamp` is filled in
+    template: Value,
+}
+
+#[derive(Serialize)]
+struct GoalProjection {
+    id: String,
+    name: Option<String>,
+    target: u32,
+    current: u32,
+    projected: u32,
+    /// When the target is reached: the start for goals already met, `null`
+    /// if not within the horizon
+    completed_at: Option<String>,
+    weeks_to_complete: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct Projection {
+    start: String,
+    horizon_weeks: u32,
+    goals: Vec<GoalProjection>,
+}
+
+/// Simulated sessions in time order: session `k` of a pattern runs at
+/// `(k + 0.5) / per_week` weeks after the start
+fn simulated(sessions: &[Session], start: i64, horizon_weeks: u32) -> Vec<Value> {
+    let end = start + i64::from(horizon_weeks) * MS_PER_WEEK;
+    let mut timed = Vec::new();
+    for session in sessions {
+        let interval = MS_PER_WEEK as f64 / session.per_week;
+        let mut k = 0.0;
+        loop {
+            let at = start + ((k + 0.5) * interval) as i64;
+            if at >= end {
+                break;
+            }
+            let mut exp = session.template.clone();
+            if let Some(obj) = exp.as_object_mut() {
+                obj.insert("timestamp".to_string(), Value::from(format_timestamp(at)));
+            }
+            timed.push((at, exp));
+            k += 1.0;
+        }
+    }
+    timed.sort_by_key(|(at, _)| *at);
+    timed.into_iter().map(|(_, exp)| exp).collect()
+}
+
+fn project(experiences: &[Value], pattern: &WeeklyPattern, goals: &[Goal], start: i64, horizon_weeks: u32) -> Projection {
+    let future = simulated(&pattern.sessions, start, horizon_weeks);
+    let projections = goals
+        .iter()
+        .map(|goal| {
+            let current = experiences.iter().filter(|exp| goal.counts(exp)).count() as u32;
+            let mut projected = current;
+            let mut completed_at = (current >= goal.target).then_some(start);
+            for exp in future.iter().filter(|exp| goal.counts(exp)) {
+                projected += 1;
+                if completed_at.is_none() && projected >= goal.target {
+                    completed_at = exp.get("timestamp").and_then(Value::as_str).and_then(parse_timestamp);
+                }
+            }
+            GoalProjection {
+                id: goal.id.clone(),
+                name: goal.name.clone(),
+                target: goal.target,
+                current,
+                projected,
+                completed_at: completed_at.map(format_timestamp),
+                weeks_to_complete: completed_at.map(|at| (at - start) as f64 / MS_PER_WEEK as f64),
+            }
+        })
+        .collect();
+
+    Projection {
+        start: format_timestamp(start),
+        horizon_weeks,
+        goals: projections,
+    }
+}
+
+/// Project goal completion under an assumed weekly activity pattern
+/// `{start?, sessions: [{per_week, template}]}` over `horizon_weeks`
+/// Returns per-goal current and projected counts and completion dates as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn simulate_progress(
+    current_experiences_json: &str,
+    weekly_pattern_json: &str,
+    goals_json: &str,
+    horizon_weeks: u32,
+) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(current_experiences_json, "current_experiences_json")?;
+    let pattern: WeeklyPattern = from_json(weekly_pattern_json, "weekly_pattern_json")?;
+    let goals = parse_goals(goals_json).map_err(Error::invalid)?;
+
+    if let Some(session) = pattern.sessions.iter().find(|s| !s.per_week.is_finite() || s.per_week <= 0.0) {
+        return Err(Error::invalid(format!("per_week must be positive, got {}", session.per_week)));
+    }
+    let total = sum(pattern.sessions.iter().map(|s| s.per_week * f64::from(horizon_weeks)));
+    if total > MAX_SESSIONS {
+        return Err(Error::invalid(format!("pattern would simulate more than {} sessions", MAX_SESSIONS)));
+    }
+    let start = match pattern.start {
+        Some(ref s) => parse_timestamp(s).ok_or_else(|| Error::invalid(format!("invalid start: {}", s)))?,
+        None => now_ms(),
+    };
+
+    let projection = project(&experiences, &pattern, &goals, start, horizon_weeks);
+    to_json(&projection)
+}
@@ -0,0 +1,172 @@
+//! Quality grades for citizen-science observations
+//!
+//! Platforms such as iNaturalist only pass on observations that can be
+//! verified. [`grade_observations`] applies the same heuristics to
+//! experiences and tags each one `experience.quality_grade` for export
+//! filters such as `to_darwin_core`'s `quality_grades`:
+//!
+//! - `casual`: no plausible date, no coordinates or no photo or sound, so
+//!   nobody can check it;
+//! - `needs_review`: verifiable, but without an identification or with
+//!   coordinates of missing or poor accuracy;
+//! - `research_grade`: passes every check.
+//!
+//! Media are `experience.artifacts[]` entries whose `media_type` starts
+//! with one of the configured prefixes. A date is plausible when it parses,
+//! is not before `earliest`, is not more than a day after `as_of` (allowing
+//! for time zone mistakes) and is not after a ULID or UUIDv7 id says the
+//! record was created.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::ids::id_created_ms;
+use crate::query::{accuracy, coordinates};
+use crate::time::{now_ms, parse_timestamp, MS_PER_DAY, MS_PER_MINUTE};
+
+/// Ids may be minted slightly before the event is stamped
+const CLOCK_SKEW_MS: i64 = 5 * MS_PER_MINUTE;
+
+/// Checks that make a record casual when they fail
+const VERIFIABILITY: &[&str] = &["date", "location", "media"];
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct GradingRules {
+    /// `media_type` prefixes that count as evidence
+    media_types: Vec<String>,
+    /// Coarsest coordinate accuracy, in metres, for research grade
+    max_accuracy_m: f64,
+    /// Dotted path of the identification
+    identification: String,
+    /// Earliest plausible date
+    earliest: Option<String>,
+    /// Reference instant for future dates; defaults to now
+    as_of: Option<String>,
+}
+
+impl Default for GradingRules {
+    fn default() -> Self {
+        Self {
+            media_types: vec!["image/".to_string(), "audio/".to_string()],
+            max_accuracy_m: 1_000.0,
+            identification: "experience.scientific_name".to_string(),
+            earliest: None,
+            as_of: None,
+        }
+    }
+}
+
+#[derive(Serialize, Default)]
+struct GradeCounts {
+    casual: usize,
+    needs_review: usize,
+    research_grade: usize,
+}
+
+#[derive(Serialize)]
+struct Grade {
+    index: usize,
+    id: Option<String>,
+    grade: &'static str,
+    /// Checks the record failed: `date`, `location`, `media`, `accuracy`
+    /// and `identification`
+    failed: Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+struct Grading<'a> {
+    counts: GradeCounts,
+    grades: Vec<Grade>,
+    /// The input with `experience.quality_grade` set
+    experiences: &'a [Value],
+}
+
+fn instant(value: &Option<String>, name: &str) -> Result<Option<i64>, Error> {
+    value
+        .as_deref()
+        .map(|s| parse_timestamp(s).ok_or_else(|| Error::invalid(format!("invalid {}: {}", name, s)).with("argument", "rules_json")))
+        .transpose()
+}
+
+fn failed_checks(exp: &Value, rules: &GradingRules, earliest: Option<i64>, latest: i64) -> Vec<&'static str> {
+    let mut failed = Vec::new();
+    let created = lookup(exp, "id").and_then(Value::as_str).and_then(id_created_ms);
+    let plausible = lookup(exp, "timestamp").and_then(Value::as_str).and_then(parse_timestamp).is_some_and(|ms| {
+        earliest.is_none_or(|e| ms >= e) && ms <= latest && created.is_none_or(|c| ms <= c + CLOCK_SKEW_MS)
+    });
+    if !plausible {
+        failed.push("date");
+    }
+    if coordinates(exp).is_none() {
+        failed.push("location");
+    }
+    let has_media = lookup(exp, "experience.artifacts").and_then(Value::as_array).into_iter().flatten().any(|artifact| {
+        artifact
+            .get("media_type")
+            .and_then(Value::as_str)
+            .is_some_and(|t| rules.media_types.iter().any(|prefix| t.starts_with(prefix.as_str())))
+    });
+    if !has_media {
+        failed.push("media");
+    }
+    if !accuracy(exp).is_some_and(|a| a <= rules.max_accuracy_m) {
+        failed.push("accuracy");
+    }
+    let identified = lookup(exp, &rules.identification).and_then(Value::as_str).is_some_and(|s| !s.trim().is_empty());
+    if !identified {
+        failed.push("identification");
+    }
+    failed
+}
+
+/// Grade observations casual, needs_review or research_grade
+/// `rules_json` sets `media_types`, `max_accuracy_m`, `identification`,
+/// `earliest` and `as_of` (empty for image and audio media, 1000 m,
+/// `experience.scientific_name`, no earliest date and now)
+/// Returns `{counts: {casual, needs_review, research_grade}, grades:
+/// [{index, id, grade, failed}], experiences}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn grade_observations(experiences_json: &str, rules_json: &str) -> Result<String, Error> {
+    let mut experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let rules: GradingRules = if rules_json.trim().is_empty() {
+        GradingRules::default()
+    } else {
+        from_json(rules_json, "rules_json")?
+    };
+    if rules.max_accuracy_m.is_nan() || rules.max_accuracy_m < 0.0 {
+        return Err(Error::invalid("max_accuracy_m must not be negative"));
+    }
+    let earliest = instant(&rules.earliest, "earliest")?;
+    let latest = instant(&rules.as_of, "as_of")?.unwrap_or_else(now_ms) + MS_PER_DAY;
+
+    let mut counts = GradeCounts::default();
+    let mut grades = Vec::with_capacity(experiences.len());
+    for (index, exp) in experiences.iter_mut().enumerate() {
+        let failed = failed_checks(exp, &rules, earliest, latest);
+        let grade = if failed.iter().any(|check| VERIFIABILITY.contains(check)) {
+            counts.casual += 1;
+            "casual"
+        } else if !failed.is_empty() {
+            counts.needs_review += 1;
+            "needs_review"
+        } else {
+            counts.research_grade += 1;
+            "research_grade"
+        };
+        if let Some(data) = exp.get_mut("experience").and_then(Value::as_object_mut) {
+            data.insert("quality_grade".to_string(), Value::from(grade));
+        }
+        grades.push(Grade {
+            index,
+            id: lookup(exp, "id").and_then(Value::as_str).map(str::to_string),
+            grade,
+            failed,
+        });
+    }
+    to_json(&Grading { counts, grades, experiences: &experiences })
+}
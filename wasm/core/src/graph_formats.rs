@@ -0,0 +1,184 @@
+//! Visualisation-ready network outputs
+//!
+//! The same domain network as [`crate::generate_domain_network`], shaped for
+//! the graph libraries the web client uses so no adapter code is needed:
+//! Cytoscape.js `elements` and the D3 force-graph `{nodes, links}` layout,
+//! plus GraphML for desktop tools (Gephi, yEd, NetworkX). D3 nodes get a
+//! `group` per connected component (numbered by size, largest first) for
+//! colouring. Nodes and edges come out sorted for stable renders.
+
+use serde::Serialize;
+use std::fmt::Write;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::network::component_groups;
+use crate::{build_network, DomainNetwork, Experience};
+
+#[derive(Serialize)]
+struct CytoscapeElements {
+    nodes: Vec<CytoscapeElement<CytoscapeNode>>,
+    edges: Vec<CytoscapeElement<CytoscapeEdge>>,
+}
+
+#[derive(Serialize)]
+struct CytoscapeElement<T> {
+    data: T,
+}
+
+#[derive(Serialize)]
+struct CytoscapeNode {
+    id: String,
+    label: String,
+    size: usize,
+}
+
+#[derive(Serialize)]
+struct CytoscapeEdge {
+    id: String,
+    source: String,
+    target: String,
+    weight: usize,
+}
+
+#[derive(Serialize)]
+struct D3Graph {
+    nodes: Vec<D3Node>,
+    links: Vec<D3Link>,
+}
+
+#[derive(Serialize)]
+struct D3Node {
+    id: String,
+    group: usize,
+    size: usize,
+}
+
+#[derive(Serialize)]
+struct D3Link {
+    source: String,
+    target: String,
+    value: usize,
+}
+
+fn sorted_network(experiences_json: &str) -> Result<DomainNetwork, Error> {
+    let experiences: Vec<Experience> = from_json(experiences_json, "experiences_json")?;
+    let mut network = build_network(&experiences);
+    network.nodes.sort_by(|a, b| a.id.cmp(&b.id));
+    network
+        .edges
+        .sort_by(|a, b| (&a.source, &a.target).cmp(&(&b.source, &b.target)));
+    Ok(network)
+}
+
+/// Domain network as Cytoscape.js elements
+/// Returns `{nodes: [{data}], edges: [{data}]}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn generate_domain_network_cytoscape(experiences_json: &str) -> Result<String, Error> {
+    let network = sorted_network(experiences_json)?;
+    let elements = CytoscapeElements {
+        nodes: network
+            .nodes
+            .into_iter()
+            .map(|n| CytoscapeElement {
+                data: CytoscapeNode {
+                    label: n.id.clone(),
+                    id: n.id,
+                    size: n.size,
+                },
+            })
+            .collect(),
+        edges: network
+            .edges
+            .into_iter()
+            .map(|e| CytoscapeElement {
+                data: CytoscapeEdge {
+                    id: format!("{}--{}", e.source, e.target),
+                    source: e.source,
+                    target: e.target,
+                    weight: e.weight,
+                },
+            })
+            .collect(),
+    };
+    to_json(&elements)
+}
+
+/// Domain network in D3 force-graph shape
+/// Returns `{nodes: [{id, group, size}], links: [{source, target, value}]}`
+/// as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn generate_domain_network_d3(experiences_json: &str) -> Result<String, Error> {
+    let network = sorted_network(experiences_json)?;
+    let groups = component_groups(&network);
+    let graph = D3Graph {
+        links: network
+            .edges
+            .into_iter()
+            .map(|e| D3Link {
+                source: e.source,
+                target: e.target,
+                value: e.weight,
+            })
+            .collect(),
+        nodes: network
+            .nodes
+            .into_iter()
+            .zip(groups)
+            .map(|(n, group)| D3Node {
+                id: n.id,
+                group,
+                size: n.size,
+            })
+            .collect(),
+    };
+    to_json(&graph)
+}
+
+/// Escape text for XML attribute values and character data
+fn escape_xml(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            // Control characters other than tab and newlines are not
+            // allowed in XML 1.0 at all
+            c if c < ' ' && !matches!(c, '\t' | '\n' | '\r') => out.push(char::REPLACEMENT_CHARACTER),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Domain network as an undirected GraphML document with `size` node and
+/// `weight` edge attributes
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn generate_domain_network_graphml(experiences_json: &str) -> Result<String, Error> {
+    let network = sorted_network(experiences_json)?;
+    let mut out = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        "  <key id=\"size\" for=\"node\" attr.name=\"size\" attr.type=\"int\"/>\n",
+        "  <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"int\"/>\n",
+        "  <graph id=\"domains\" edgedefault=\"undirected\">\n",
+    ));
+    for n in &network.nodes {
+        let _ = writeln!(out, "    <node id=\"{}\"><data key=\"size\">{}</data></node>", escape_xml(&n.id), n.size);
+    }
+    for e in &network.edges {
+        let _ = writeln!(
+            out,
+            "    <edge source=\"{}\" target=\"{}\"><data key=\"weight\">{}</data></edge>",
+            escape_xml(&e.source),
+            escape_xml(&e.target),
+            e.weight
+        );
+    }
+    out.push_str("  </graph>\n</graphml>\n");
+    Ok(out)
+}
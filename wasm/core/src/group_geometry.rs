@@ -0,0 +1,213 @@
+//! Group centroid, hull and spread for field trips
+//!
+//! [`group_geometry`] takes each learner's latest position, either as an
+//! array of `{learner, group, latitude, longitude}` or straight from a
+//! `MetricsWindow.snapshot` (its `learners` map), and describes every group:
+//! where its centre is, the convex hull the live trip map outlines, how far
+//! apart its two most distant members are and how far each learner is from
+//! the centre. Learners without a group form one group of their own.
+//!
+//! Hulls are computed in a local equirectangular projection around the
+//! centroid, which is accurate to well under a metre over the few
+//! kilometres a trip covers.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::geo::{haversine_m, EARTH_RADIUS_M};
+use crate::numeric::{self, cos};
+
+#[derive(Deserialize)]
+struct LatestLocation {
+    learner: String,
+    #[serde(default)]
+    group: Option<String>,
+    latitude: f64,
+    longitude: f64,
+}
+
+#[derive(Deserialize)]
+struct SnapshotLearner {
+    #[serde(default)]
+    group: Option<String>,
+    #[serde(default)]
+    latitude: Option<f64>,
+    #[serde(default)]
+    longitude: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Locations {
+    List(Vec<LatestLocation>),
+    Snapshot { learners: BTreeMap<String, SnapshotLearner> },
+}
+
+#[derive(Serialize)]
+struct Centroid {
+    latitude: f64,
+    longitude: f64,
+}
+
+#[derive(Serialize)]
+struct LearnerDistance {
+    learner: String,
+    distance_m: f64,
+}
+
+#[derive(Serialize)]
+struct GroupGeometry {
+    group: Option<String>,
+    learners: usize,
+    centroid: Centroid,
+    /// Closed ring of `[longitude, latitude]` positions, counter-clockwise;
+    /// fewer than four positions when the group is a point or a line
+    hull: Vec<[f64; 2]>,
+    area_m2: f64,
+    max_spread_m: f64,
+    /// The two learners `max_spread_m` apart
+    farthest_pair: Option<[String; 2]>,
+    /// Farthest from the centroid first
+    distances: Vec<LearnerDistance>,
+}
+
+#[derive(Serialize)]
+struct GeometryReport {
+    groups: Vec<GroupGeometry>,
+}
+
+/// Mean position of `(latitude, longitude)` points
+pub(crate) fn centroid(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    let lats: Vec<f64> = points.iter().map(|p| p.0).collect();
+    let lons: Vec<f64> = points.iter().map(|p| p.1).collect();
+    Some((numeric::mean(&lats)?, numeric::mean(&lons)?))
+}
+
+fn cross(o: [f64; 2], a: [f64; 2], b: [f64; 2]) -> f64 {
+    (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+}
+
+/// Indices of the convex hull of planar points, counter-clockwise
+/// (Andrew's monotone chain); collinear points are dropped
+fn hull_indices(points: &[[f64; 2]]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..points.len()).collect();
+    order.sort_by(|&a, &b| points[a][0].total_cmp(&points[b][0]).then(points[a][1].total_cmp(&points[b][1])));
+    order.dedup_by(|a, b| points[*a] == points[*b]);
+    if order.len() < 3 {
+        return order;
+    }
+    let mut hull: Vec<usize> = Vec::with_capacity(order.len() * 2);
+    for pass in [order.clone(), order.iter().rev().copied().collect()] {
+        let start = hull.len();
+        for i in pass {
+            while hull.len() >= start + 2 && cross(points[hull[hull.len() - 2]], points[hull[hull.len() - 1]], points[i]) <= 0.0 {
+                hull.pop();
+            }
+            hull.push(i);
+        }
+        // The last point of each chain starts the other
+        hull.pop();
+    }
+    hull
+}
+
+fn describe(group: Option<String>, members: Vec<(String, f64, f64)>) -> GroupGeometry {
+    let points: Vec<(f64, f64)> = members.iter().map(|m| (m.1, m.2)).collect();
+    let (c_lat, c_lon) = centroid(&points).unwrap_or_default();
+
+    // Local plane in metres around the centroid
+    let scale = EARTH_RADIUS_M * std::f64::consts::PI / 180.0;
+    let plane: Vec<[f64; 2]> = points
+        .iter()
+        .map(|&(lat, lon)| [(lon - c_lon) * scale * cos(c_lat.to_radians()), (lat - c_lat) * scale])
+        .collect();
+    let indices = hull_indices(&plane);
+    let area_m2 = if indices.len() >= 3 {
+        let twice: f64 = numeric::sum((0..indices.len()).map(|k| {
+            let (a, b) = (plane[indices[k]], plane[indices[(k + 1) % indices.len()]]);
+            a[0] * b[1] - b[0] * a[1]
+        }));
+        twice.abs() / 2.0
+    } else {
+        0.0
+    };
+    let mut hull: Vec<[f64; 2]> = indices.iter().map(|&i| [points[i].1, points[i].0]).collect();
+    if hull.len() >= 3 {
+        hull.push(hull[0]);
+    }
+
+    // The two most distant points are both hull vertices
+    let mut max_spread_m = 0.0;
+    let mut farthest_pair = None;
+    for (k, &i) in indices.iter().enumerate() {
+        for &j in &indices[k + 1..] {
+            let d = haversine_m(points[i].0, points[i].1, points[j].0, points[j].1);
+            if d > max_spread_m {
+                max_spread_m = d;
+                farthest_pair = Some([members[i].0.clone(), members[j].0.clone()]);
+            }
+        }
+    }
+
+    let mut distances: Vec<LearnerDistance> = members
+        .iter()
+        .map(|(learner, lat, lon)| LearnerDistance {
+            learner: learner.clone(),
+            distance_m: haversine_m(*lat, *lon, c_lat, c_lon),
+        })
+        .collect();
+    distances.sort_by(|a, b| b.distance_m.total_cmp(&a.distance_m).then_with(|| a.learner.cmp(&b.learner)));
+
+    GroupGeometry {
+        group,
+        learners: members.len(),
+        centroid: Centroid { latitude: c_lat, longitude: c_lon },
+        hull,
+        area_m2,
+        max_spread_m,
+        farthest_pair,
+        distances,
+    }
+}
+
+/// Centroid, convex hull, maximum spread and per-learner distance from the
+/// centroid for every group, from each learner's latest position
+/// Returns `{groups: [{group, learners, centroid, hull, area_m2,
+/// max_spread_m, farthest_pair, distances}]}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn group_geometry(latest_locations_json: &str) -> Result<String, Error> {
+    let locations: Locations = from_json(latest_locations_json, "latest_locations_json")?;
+    let locations: Vec<LatestLocation> = match locations {
+        Locations::List(list) => list,
+        Locations::Snapshot { learners } => learners
+            .into_iter()
+            .filter_map(|(learner, l)| {
+                Some(LatestLocation {
+                    learner,
+                    group: l.group,
+                    latitude: l.latitude?,
+                    longitude: l.longitude?,
+                })
+            })
+            .collect(),
+    };
+
+    let mut groups: BTreeMap<Option<String>, Vec<(String, f64, f64)>> = BTreeMap::new();
+    let mut seen = std::collections::HashSet::new();
+    for (i, l) in locations.into_iter().enumerate() {
+        if !(-90.0..=90.0).contains(&l.latitude) || !(-180.0..=180.0).contains(&l.longitude) {
+            return Err(Error::invalid(format!("location {} is out of range", i)).with("index", i));
+        }
+        if !seen.insert(l.learner.clone()) {
+            return Err(Error::invalid(format!("learner {} appears more than once", l.learner)).with("learner", l.learner));
+        }
+        groups.entry(l.group).or_default().push((l.learner, l.latitude, l.longitude));
+    }
+
+    to_json(&GeometryReport {
+        groups: groups.into_iter().map(|(group, members)| describe(group, members)).collect(),
+    })
+}
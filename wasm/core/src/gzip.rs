@@ -0,0 +1,109 @@
+//! Gzip compression for export payloads
+//!
+//! CSV, GraphML, N-Triples and the other text exports of a whole deployment
+//! run to tens of megabytes of highly repetitive text, which gzip typically
+//! shrinks by an order of magnitude before upload. Every text export (CSV,
+//! Darwin Core, GeoJSON, the graph formats and RDF) has a `_gzip` variant
+//! that returns the compressed bytes straight from WASM, so the uncompressed
+//! string never crosses into JS. The binary exports (CBOR, MessagePack,
+//! Parquet, MVT) have none: they are already compact, and Parquet compresses
+//! internally. Any other payload can go through [`compress_gzip`].
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{Read, Write};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{Error, ErrorKind};
+use crate::crs::reproject_geojson;
+use crate::darwin_core::to_darwin_core;
+use crate::export::{export_experiences_csv, export_network_csv};
+use crate::graph_formats::{
+    generate_domain_network_cytoscape, generate_domain_network_d3, generate_domain_network_graphml,
+};
+use crate::triples::{to_ntriples, to_triples};
+
+/// Refuse to inflate beyond this size (gzip-bomb guard)
+const MAX_INFLATED_BYTES: u64 = 256 * 1024 * 1024;
+
+pub(crate) fn gzip(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).map_err(|e| Error::new(ErrorKind::Serialization, e))?;
+    encoder.finish().map_err(|e| Error::new(ErrorKind::Serialization, e))
+}
+
+/// Gzip-compress bytes
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn compress_gzip(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    gzip(bytes)
+}
+
+/// Decompress gzip bytes, including concatenated members
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn decompress_gzip(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    MultiGzDecoder::new(bytes)
+        .take(MAX_INFLATED_BYTES + 1)
+        .read_to_end(&mut out)
+        .map_err(|e| Error::parse(format!("invalid gzip data: {}", e)))?;
+    if out.len() as u64 > MAX_INFLATED_BYTES {
+        return Err(Error::invalid(format!("gzip data inflates beyond {} bytes", MAX_INFLATED_BYTES)));
+    }
+    Ok(out)
+}
+
+/// `export_experiences_csv`, gzip-compressed
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn export_experiences_csv_gzip(experiences_json: &str, columns_spec: &str) -> Result<Vec<u8>, Error> {
+    gzip(export_experiences_csv(experiences_json, columns_spec)?.as_bytes())
+}
+
+/// `export_network_csv`, gzip-compressed
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn export_network_csv_gzip(network_json: &str) -> Result<Vec<u8>, Error> {
+    gzip(export_network_csv(network_json)?.as_bytes())
+}
+
+/// `to_darwin_core`, gzip-compressed
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn to_darwin_core_gzip(experiences_json: &str, mapping_json: &str) -> Result<Vec<u8>, Error> {
+    gzip(to_darwin_core(experiences_json, mapping_json)?.as_bytes())
+}
+
+/// `reproject_geojson`, gzip-compressed
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn reproject_geojson_gzip(geojson: &str, from_crs: &str, to_crs: &str) -> Result<Vec<u8>, Error> {
+    gzip(reproject_geojson(geojson, from_crs, to_crs)?.as_bytes())
+}
+
+/// `generate_domain_network_cytoscape`, gzip-compressed
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn generate_domain_network_cytoscape_gzip(experiences_json: &str) -> Result<Vec<u8>, Error> {
+    gzip(generate_domain_network_cytoscape(experiences_json)?.as_bytes())
+}
+
+/// `generate_domain_network_d3`, gzip-compressed
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn generate_domain_network_d3_gzip(experiences_json: &str) -> Result<Vec<u8>, Error> {
+    gzip(generate_domain_network_d3(experiences_json)?.as_bytes())
+}
+
+/// `generate_domain_network_graphml`, gzip-compressed
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn generate_domain_network_graphml_gzip(experiences_json: &str) -> Result<Vec<u8>, Error> {
+    gzip(generate_domain_network_graphml(experiences_json)?.as_bytes())
+}
+
+/// `to_ntriples`, gzip-compressed
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn to_ntriples_gzip(experiences_json: &str) -> Result<Vec<u8>, Error> {
+    gzip(to_ntriples(experiences_json)?.as_bytes())
+}
+
+/// `to_triples`, gzip-compressed
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn to_triples_gzip(experiences_json: &str) -> Result<Vec<u8>, Error> {
+    gzip(to_triples(experiences_json)?.as_bytes())
+}
@@ -0,0 +1,308 @@
+//! Grid heatmaps for map overlays
+//!
+//! [`heatmap`] bins geo-tagged experiences into square cells of a given size
+//! in metres and returns only the non-empty cells, each with its bounds, so
+//! the result can be drawn as an overlay without further processing. Cells
+//! are laid on a fixed grid: rows are `cell_size_meters` of latitude, and
+//! columns `cell_size_meters` of longitude at the mean latitude of the
+//! points, which keeps cells close to square across a city or region.
+//!
+//! With a kernel radius the counts are spread with a truncated Gaussian
+//! (σ = radius / 2, in cells) that preserves the total, which reads better
+//! than raw counts when points are sparse.
+//!
+//! [`heatmap_typed`] returns the same cells as flat buffers, for posting
+//! from a worker without copying.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::buffers::Buffers;
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::geo::EARTH_RADIUS_M;
+use crate::numeric::{self, cos, exp};
+use crate::query::coordinates;
+use crate::time::parse_timestamp;
+
+const MAX_KERNEL_RADIUS: u32 = 10;
+/// Cells in the output, past which the caller should pick a larger cell
+const MAX_CELLS: usize = 250_000;
+
+/// Half-open `[from, to)` range over `timestamp`; either bound may be omitted
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct TimeRange {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Cell {
+    row: i64,
+    col: i64,
+    /// `[west, south, east, north]`
+    bounds: [f64; 4],
+    latitude: f64,
+    longitude: f64,
+    count: u64,
+    intensity: f64,
+}
+
+#[derive(Serialize)]
+struct Heatmap {
+    cell_size_m: f64,
+    lat_step: f64,
+    lon_step: f64,
+    points: usize,
+    max_intensity: f64,
+    cells: Vec<Cell>,
+}
+
+/// Weights of a normalized, truncated Gaussian over `[-radius, radius]²`
+fn kernel(radius: u32) -> Vec<(i64, i64, f64)> {
+    let r = radius as i64;
+    let two_sigma_sq = 2.0 * numeric::powi(radius as f64 / 2.0, 2);
+    let mut weights: Vec<(i64, i64, f64)> = Vec::new();
+    for dr in -r..=r {
+        for dc in -r..=r {
+            weights.push((dr, dc, exp(-((dr * dr + dc * dc) as f64) / two_sigma_sq)));
+        }
+    }
+    let total = numeric::sum(weights.iter().map(|w| w.2));
+    for w in &mut weights {
+        w.2 /= total;
+    }
+    weights
+}
+
+/// Grid index of `value` such that `index * step <= value < (index + 1) * step`
+/// holds for the bounds as they are reported; `value / step` alone can round
+/// a point on a grid line into the neighbouring cell
+fn cell_index(value: f64, step: f64) -> i64 {
+    let index = (value / step).floor() as i64;
+    if value < index as f64 * step {
+        index - 1
+    } else if value >= (index + 1) as f64 * step {
+        index + 1
+    } else {
+        index
+    }
+}
+
+#[derive(Serialize)]
+struct HeatmapIndex {
+    cell_size_m: f64,
+    lat_step: f64,
+    lon_step: f64,
+    points: usize,
+    max_intensity: f64,
+    cells: usize,
+    /// Names of the values per cell in the float buffer
+    floats: [&'static str; 3],
+    /// Names of the values per cell in the integer buffer
+    ints: [&'static str; 1],
+}
+
+fn compute(
+    experiences_json: &str,
+    cell_size_meters: f64,
+    time_range_json: &str,
+    kernel_radius_cells: u32,
+) -> Result<Heatmap, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    if cell_size_meters.is_nan() || cell_size_meters <= 0.0 {
+        return Err(Error::invalid("cell_size_meters must be positive"));
+    }
+    if kernel_radius_cells > MAX_KERNEL_RADIUS {
+        return Err(Error::invalid(format!("kernel_radius_cells must be at most {}", MAX_KERNEL_RADIUS))
+            .with("kernel_radius_cells", kernel_radius_cells));
+    }
+    let range: TimeRange = if time_range_json.trim().is_empty() {
+        TimeRange::default()
+    } else {
+        from_json(time_range_json, "time_range_json")?
+    };
+    let bound = |b: &Option<String>| -> Result<Option<i64>, Error> {
+        b.as_deref()
+            .map(|s| parse_timestamp(s).ok_or_else(|| Error::invalid(format!("invalid time bound: {}", s)).with("bound", s)))
+            .transpose()
+    };
+    let (from, to) = (bound(&range.from)?, bound(&range.to)?);
+
+    let points: Vec<(f64, f64)> = experiences
+        .iter()
+        .filter(|exp| {
+            if from.is_none() && to.is_none() {
+                return true;
+            }
+            lookup(exp, "timestamp")
+                .and_then(Value::as_str)
+                .and_then(parse_timestamp)
+                .is_some_and(|ts| from.is_none_or(|f| ts >= f) && to.is_none_or(|t| ts < t))
+        })
+        .filter_map(coordinates)
+        .collect();
+
+    let lat_step = cell_size_meters / (EARTH_RADIUS_M * std::f64::consts::PI / 180.0);
+    let lats: Vec<f64> = points.iter().map(|p| p.0).collect();
+    let reference = numeric::mean(&lats).unwrap_or(0.0);
+    // Longitude degrees shrink towards the poles; cap the stretch near them
+    let lon_step = (lat_step / cos(reference.to_radians()).max(0.01)).min(360.0);
+
+    let mut counts: BTreeMap<(i64, i64), u64> = BTreeMap::new();
+    for &(lat, lon) in &points {
+        let cell = (cell_index(lat, lat_step), cell_index(lon, lon_step));
+        *counts.entry(cell).or_insert(0) += 1;
+    }
+    let mut intensity: BTreeMap<(i64, i64), f64> = BTreeMap::new();
+    if kernel_radius_cells == 0 {
+        intensity.extend(counts.iter().map(|(&cell, &n)| (cell, n as f64)));
+    } else {
+        let weights = kernel(kernel_radius_cells);
+        for (&(row, col), &n) in &counts {
+            for &(dr, dc, w) in &weights {
+                *intensity.entry((row + dr, col + dc)).or_insert(0.0) += n as f64 * w;
+            }
+            if intensity.len() > MAX_CELLS {
+                break;
+            }
+        }
+    }
+    if intensity.len() > MAX_CELLS {
+        return Err(Error::invalid(format!("heatmap would have more than {} cells; use a larger cell size", MAX_CELLS))
+            .with("cell_size_meters", cell_size_meters));
+    }
+
+    let cells: Vec<Cell> = intensity
+        .into_iter()
+        .map(|((row, col), intensity)| {
+            let (south, west) = (row as f64 * lat_step, col as f64 * lon_step);
+            Cell {
+                row,
+                col,
+                bounds: [west, south, (col + 1) as f64 * lon_step, (row + 1) as f64 * lat_step],
+                latitude: south + lat_step / 2.0,
+                longitude: west + lon_step / 2.0,
+                count: counts.get(&(row, col)).copied().unwrap_or(0),
+                intensity,
+            }
+        })
+        .collect();
+    Ok(Heatmap {
+        cell_size_m: cell_size_meters,
+        lat_step,
+        lon_step,
+        points: points.len(),
+        max_intensity: cells.iter().map(|c| c.intensity).fold(0.0, f64::max),
+        cells,
+    })
+}
+
+/// Heatmap of experience locations within a time range
+/// `time_range_json` is `{from, to}` (RFC 3339, either optional; empty for
+/// all time) and `kernel_radius_cells` (0-10) smooths over that many
+/// neighbouring cells, 0 for raw counts
+/// Returns `{cell_size_m, lat_step, lon_step, points, max_intensity, cells:
+/// [{row, col, bounds, latitude, longitude, count, intensity}]}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn heatmap(
+    experiences_json: &str,
+    cell_size_meters: f64,
+    time_range_json: &str,
+    kernel_radius_cells: u32,
+) -> Result<String, Error> {
+    to_json(&compute(experiences_json, cell_size_meters, time_range_json, kernel_radius_cells)?)
+}
+
+/// `heatmap` as typed arrays: `[latitude, longitude, intensity]` per cell
+/// as floats and the count per cell as integers. A cell's bounds are half
+/// a step either side of its centre.
+/// Returns Buffers indexed by `{cell_size_m, lat_step, lon_step, points,
+/// max_intensity, cells, floats, ints}`
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn heatmap_typed(
+    experiences_json: &str,
+    cell_size_meters: f64,
+    time_range_json: &str,
+    kernel_radius_cells: u32,
+) -> Result<Buffers, Error> {
+    let heatmap = compute(experiences_json, cell_size_meters, time_range_json, kernel_radius_cells)?;
+    let floats = heatmap.cells.iter().flat_map(|c| [c.latitude, c.longitude, c.intensity]).collect();
+    let ints = heatmap.cells.iter().map(|c| u32::try_from(c.count).unwrap_or(u32::MAX)).collect();
+    let index = HeatmapIndex {
+        cell_size_m: heatmap.cell_size_m,
+        lat_step: heatmap.lat_step,
+        lon_step: heatmap.lon_step,
+        points: heatmap.points,
+        max_intensity: heatmap.max_intensity,
+        cells: heatmap.cells.len(),
+        floats: ["latitude", "longitude", "intensity"],
+        ints: ["count"],
+    };
+    Buffers::new(&index, floats, ints)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use serde_json::json;
+
+    fn at(lat: f64, lon: f64) -> Value {
+        json!({"id": "p", "timestamp": "2024-05-01T10:00:00Z", "context": {"location": {"coordinates": {"latitude": lat, "longitude": lon}}}})
+    }
+
+    fn single(lat: f64, lon: f64, cell_size_meters: f64) -> Heatmap {
+        compute(&json!([at(lat, lon)]).to_string(), cell_size_meters, "", 0).unwrap()
+    }
+
+    proptest! {
+        /// A point on a grid line belongs to the cell whose south or west
+        /// edge it is, and always lies inside the bounds it is reported with
+        #[test]
+        fn boundary_points_fall_in_their_own_cell(north in -1.0..1.0f64, east in -1.0..1.0f64, size in 1.0..5_000.0f64) {
+            // Grid lines up to 80° of latitude and 180° of longitude either way
+            let lat_step = single(0.0, 0.0, size).lat_step;
+            let row = (north * 80.0 / lat_step) as i64;
+            let lat = row as f64 * lat_step;
+            let lon_step = single(lat, 0.0, size).lon_step;
+            let col = (east * 180.0 / lon_step) as i64;
+            let lon = col as f64 * lon_step;
+
+            let heatmap = single(lat, lon, size);
+            let cell = &heatmap.cells[0];
+            prop_assert_eq!((cell.row, cell.col), (row, col));
+            let [west, south, east, north] = cell.bounds;
+            prop_assert!(south <= lat && lat < north, "{} not in [{}, {})", lat, south, north);
+            prop_assert!(west <= lon && lon < east, "{} not in [{}, {})", lon, west, east);
+        }
+    }
+
+    #[test]
+    fn cells_are_half_open_and_floor_negative_coordinates() {
+        let heatmap = compute(&json!([at(0.0, 0.0), at(-1e-9, -1e-9), at(1e-9, 0.0)]).to_string(), 1_000.0, "", 0).unwrap();
+        let cells: Vec<(i64, i64, u64)> = heatmap.cells.iter().map(|c| (c.row, c.col, c.count)).collect();
+        assert_eq!(cells, [(-1, -1, 1), (0, 0, 2)]);
+        assert_eq!(heatmap.points, 3);
+        assert_eq!(heatmap.max_intensity, 2.0);
+    }
+
+    #[test]
+    fn time_range_is_half_open_and_kernel_preserves_the_total() {
+        let points = json!([at(51.5, -0.1), at(51.5, -0.1), at(51.51, -0.1)]);
+        let range = r#"{"from": "2024-05-01T10:00:00Z", "to": "2024-05-01T10:00:00.001Z"}"#;
+        assert_eq!(compute(&points.to_string(), 100.0, range, 0).unwrap().points, 3);
+        assert_eq!(compute(&points.to_string(), 100.0, r#"{"to": "2024-05-01T10:00:00Z"}"#, 0).unwrap().points, 0);
+
+        let smoothed = compute(&points.to_string(), 100.0, "", 3).unwrap();
+        let total: f64 = smoothed.cells.iter().map(|c| c.intensity).sum();
+        assert!((total - 3.0).abs() < 1e-9);
+        assert_eq!(smoothed.cells.iter().map(|c| c.count).sum::<u64>(), 3);
+        assert!(compute(&points.to_string(), 100.0, "", MAX_KERNEL_RADIUS + 1).is_err());
+        assert!(compute(&points.to_string(), 0.0, "", 0).is_err());
+    }
+}
@@ -0,0 +1,268 @@
+//! Live top-k domains, places and types
+//!
+//! [`HeavyHitters`] keeps approximate counts with the space-saving
+//! algorithm (Metwally et al.): at most `capacity` counters per dimension,
+//! and an unseen key takes over the smallest counter, inheriting its count
+//! as an error bound. Any key whose true count exceeds `total / capacity`
+//! is always tracked, and the reported count never underestimates. Feed it
+//! the store's change feed (`ExperienceStore.subscribe`) and the "trending"
+//! lists update per write without recomputing over the whole store.
+//! Replaced records are subtracted; counts for keys that were evicted in
+//! the meantime are an estimate either way.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::query::domains;
+
+const MAX_CAPACITY: usize = 10_000;
+
+#[derive(Clone, Copy)]
+struct Counter {
+    count: u64,
+    /// Upper bound on how much of `count` was inherited from evicted keys
+    error: u64,
+}
+
+/// Space-saving counters for one dimension
+struct SpaceSaving {
+    capacity: usize,
+    counters: HashMap<String, Counter>,
+    /// Largest count ever evicted, which bounds every untracked key
+    max_evicted: u64,
+}
+
+impl SpaceSaving {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            counters: HashMap::with_capacity(capacity),
+            max_evicted: 0,
+        }
+    }
+
+    fn add(&mut self, key: &str) {
+        if let Some(counter) = self.counters.get_mut(key) {
+            counter.count += 1;
+            return;
+        }
+        if self.counters.len() < self.capacity {
+            self.counters.insert(key.to_string(), Counter { count: 1, error: 0 });
+            return;
+        }
+        // Evict the smallest counter; ties go to the greatest key so the
+        // outcome does not depend on hash order
+        let (evicted, min) = self
+            .counters
+            .iter()
+            .min_by(|a, b| a.1.count.cmp(&b.1.count).then_with(|| b.0.cmp(a.0)))
+            .map(|(k, c)| (k.clone(), c.count))
+            .expect("capacity is at least 1");
+        self.counters.remove(&evicted);
+        self.max_evicted = self.max_evicted.max(min);
+        self.counters.insert(
+            key.to_string(),
+            Counter {
+                count: min + 1,
+                error: min,
+            },
+        );
+    }
+
+    fn remove(&mut self, key: &str) {
+        let Some(counter) = self.counters.get_mut(key) else {
+            return;
+        };
+        counter.count -= 1;
+        counter.error = counter.error.min(counter.count);
+        if counter.count == 0 {
+            self.counters.remove(key);
+        }
+    }
+
+    fn top(&self, k: usize) -> Vec<Hitter> {
+        let mut ranked: Vec<(&String, &Counter)> = self.counters.iter().collect();
+        ranked.sort_by(|a, b| b.1.count.cmp(&a.1.count).then_with(|| a.0.cmp(b.0)));
+        // A key is surely in the true top k when even its lower bound beats
+        // the best count outside the list, tracked or not
+        let threshold = ranked.get(k).map_or(0, |(_, c)| c.count).max(self.max_evicted);
+        ranked
+            .into_iter()
+            .take(k)
+            .map(|(key, c)| Hitter {
+                key: key.clone(),
+                count: c.count,
+                error: c.error,
+                guaranteed: c.count - c.error >= threshold,
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+struct Hitter {
+    key: String,
+    count: u64,
+    error: u64,
+    guaranteed: bool,
+}
+
+#[derive(Serialize)]
+struct Snapshot {
+    records: u64,
+    domains: Vec<Hitter>,
+    places: Vec<Hitter>,
+    types: Vec<Hitter>,
+}
+
+/// Change feed entry as emitted by `ExperienceStore.subscribe`
+#[derive(Deserialize)]
+pub(crate) struct Change {
+    pub(crate) op: String,
+    #[serde(default)]
+    pub(crate) record: Option<Value>,
+    #[serde(default)]
+    pub(crate) previous: Option<Value>,
+}
+
+/// Approximate top domains, places and types over a stream of writes
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct HeavyHitters {
+    domains: SpaceSaving,
+    places: SpaceSaving,
+    types: SpaceSaving,
+    records: u64,
+}
+
+impl HeavyHitters {
+    fn dimension(&self, name: &str) -> Result<&SpaceSaving, Error> {
+        match name {
+            "domain" => Ok(&self.domains),
+            "place" => Ok(&self.places),
+            "type" => Ok(&self.types),
+            other => Err(Error::invalid(format!("unknown dimension: {} (expected domain, place or type)", other))
+                .with("dimension", other)),
+        }
+    }
+
+    fn apply(&mut self, record: &Value, add: bool) {
+        let unique: BTreeSet<&str> = domains(record).collect();
+        let place = lookup(record, "context.location.name").and_then(Value::as_str);
+        let kind = lookup(record, "experience.type").and_then(Value::as_str);
+        let update = |dimension: &mut SpaceSaving, key: &str| {
+            if add {
+                dimension.add(key);
+            } else {
+                dimension.remove(key);
+            }
+        };
+        for domain in unique {
+            update(&mut self.domains, domain);
+        }
+        if let Some(place) = place {
+            update(&mut self.places, place);
+        }
+        if let Some(kind) = kind {
+            update(&mut self.types, kind);
+        }
+        if add {
+            self.records += 1;
+        } else {
+            self.records = self.records.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl HeavyHitters {
+    /// Tracker with `capacity` counters per dimension (1-10000); a few times
+    /// the number of entries shown keeps the top of the list exact in
+    /// practice
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(capacity: usize) -> Result<HeavyHitters, Error> {
+        if !(1..=MAX_CAPACITY).contains(&capacity) {
+            return Err(Error::invalid(format!("capacity must be between 1 and {}", MAX_CAPACITY))
+                .with("capacity", capacity));
+        }
+        Ok(Self {
+            domains: SpaceSaving::new(capacity),
+            places: SpaceSaving::new(capacity),
+            types: SpaceSaving::new(capacity),
+            records: 0,
+        })
+    }
+
+    /// Count a JSON array of experiences, e.g. the store's contents when a
+    /// widget starts
+    /// Returns the number of experiences added
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn add_experiences(&mut self, experiences_json: &str) -> Result<usize, Error> {
+        let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+        for exp in &experiences {
+            self.apply(exp, true);
+        }
+        Ok(experiences.len())
+    }
+
+    /// Apply one change feed entry: `{op: "put", record, previous}` counts
+    /// `record` and uncounts `previous`; `{op: "delete", previous}` uncounts
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn apply_change(&mut self, change_json: &str) -> Result<(), Error> {
+        let change: Change = from_json(change_json, "change_json")?;
+        let previous = change.previous.filter(|v| !v.is_null());
+        match change.op.as_str() {
+            "put" => {
+                let record = change
+                    .record
+                    .filter(|v| v.is_object())
+                    .ok_or_else(|| Error::invalid("put change needs a record object"))?;
+                if let Some(previous) = previous {
+                    self.apply(&previous, false);
+                }
+                self.apply(&record, true);
+            }
+            "delete" => {
+                if let Some(previous) = previous {
+                    self.apply(&previous, false);
+                }
+            }
+            other => {
+                return Err(Error::invalid(format!("unknown change op: {} (expected put or delete)", other)).with("op", other))
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of experiences currently counted
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn records(&self) -> f64 {
+        self.records as f64
+    }
+
+    /// The `k` most frequent keys of `dimension` (`domain`, `place` or
+    /// `type`), most frequent first
+    /// Returns `[{key, count, error, guaranteed}]` as JSON; `count` may
+    /// overestimate by up to `error`, and `guaranteed` marks keys certain to
+    /// be in the true top `k`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn top(&self, dimension: &str, k: usize) -> Result<String, Error> {
+        to_json(&self.dimension(dimension)?.top(k))
+    }
+
+    /// Top `k` of every dimension at once, for refreshing a widget
+    /// Returns `{records, domains, places, types}` as JSON
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn snapshot(&self, k: usize) -> Result<String, Error> {
+        to_json(&Snapshot {
+            records: self.records,
+            domains: self.domains.top(k),
+            places: self.places.top(k),
+            types: self.types.top(k),
+        })
+    }
+}
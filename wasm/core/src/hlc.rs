@@ -0,0 +1,264 @@
+//! Hybrid logical clocks
+//!
+//! An HLC pairs physical time with a logical counter so that events on
+//! offline devices get a total order that respects causality even when the
+//! devices' clocks disagree. Timestamps are encoded as fixed-width strings,
+//!
+//! ```text
+//! 000001714570200123-0000-<node id>
+//!   wall ms (18 dig.)  counter (hex)
+//! ```
+//!
+//! so plain string comparison gives HLC order in JS, SQL and IndexedDB.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::cmp::Ordering;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+
+/// Remote clocks further than this ahead of local wall time are rejected
+/// rather than dragging every local clock forward with them
+const MAX_DRIFT_MS: u64 = 24 * 60 * 60 * 1000;
+const MAX_COUNTER: u16 = u16::MAX;
+/// First wall time that no longer fits the 18-digit field
+const WALL_LIMIT_MS: u64 = 1_000_000_000_000_000_000;
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub(crate) struct Hlc {
+    pub(crate) wall_ms: u64,
+    pub(crate) counter: u16,
+    pub(crate) node: String,
+}
+
+impl Hlc {
+    pub(crate) fn parse(s: &str) -> Result<Self, String> {
+        let mut parts = s.splitn(3, '-');
+        let (Some(wall), Some(counter), Some(node)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(format!("malformed HLC: {}", s));
+        };
+        if wall.len() != 18 || counter.len() != 4 || node.is_empty() {
+            return Err(format!("malformed HLC: {}", s));
+        }
+        // `parse` and `from_str_radix` would take a sign, and uppercase hex
+        // would not sort with the encoded form
+        if !wall.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(format!("malformed HLC wall time: {}", s));
+        }
+        if !counter.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+            return Err(format!("malformed HLC counter: {}", s));
+        }
+        Ok(Self {
+            wall_ms: wall.parse().map_err(|_| format!("malformed HLC wall time: {}", s))?,
+            counter: u16::from_str_radix(counter, 16).map_err(|_| format!("malformed HLC counter: {}", s))?,
+            node: node.to_string(),
+        })
+    }
+
+    pub(crate) fn encode(&self) -> String {
+        format!("{:018}-{:04x}-{}", self.wall_ms, self.counter, self.node)
+    }
+
+    /// Next local event
+    pub(crate) fn tick(last: Option<&Hlc>, wall_ms: u64, node: &str) -> Result<Self, String> {
+        check_node(node)?;
+        let (wall, counter) = match last {
+            Some(last) if last.wall_ms >= wall_ms => (last.wall_ms, bump(last.counter)?),
+            _ => (wall_ms, 0),
+        };
+        Ok(Self {
+            wall_ms: wall,
+            counter,
+            node: node.to_string(),
+        })
+    }
+
+    /// Merge a timestamp received from another node
+    pub(crate) fn receive(last: Option<&Hlc>, remote: &Hlc, wall_ms: u64, node: &str) -> Result<Self, String> {
+        check_node(node)?;
+        if remote.wall_ms > wall_ms.saturating_add(MAX_DRIFT_MS) {
+            return Err(format!("remote clock is more than {} ms ahead", MAX_DRIFT_MS));
+        }
+        let last_wall = last.map_or(0, |l| l.wall_ms);
+        let wall = wall_ms.max(last_wall).max(remote.wall_ms);
+
+        let counter = match last {
+            Some(last) if wall == last.wall_ms && wall == remote.wall_ms => bump(last.counter.max(remote.counter))?,
+            Some(last) if wall == last.wall_ms => bump(last.counter)?,
+            _ if wall == remote.wall_ms => bump(remote.counter)?,
+            _ => 0,
+        };
+        Ok(Self {
+            wall_ms: wall,
+            counter,
+            node: node.to_string(),
+        })
+    }
+}
+
+fn check_node(node: &str) -> Result<(), String> {
+    if node.is_empty() {
+        Err("HLC node id must not be empty".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+fn bump(counter: u16) -> Result<u16, String> {
+    if counter == MAX_COUNTER {
+        Err("HLC counter overflow".to_string())
+    } else {
+        Ok(counter + 1)
+    }
+}
+
+fn parse_optional(s: &str) -> Result<Option<Hlc>, Error> {
+    if s.is_empty() {
+        Ok(None)
+    } else {
+        Hlc::parse(s).map(Some).map_err(Error::invalid)
+    }
+}
+
+fn wall(wall_clock_ms: f64) -> Result<u64, Error> {
+    if !(wall_clock_ms.is_finite() && wall_clock_ms >= 0.0) {
+        return Err(Error::invalid("wall_clock_ms must be a non-negative finite number"));
+    }
+    if wall_clock_ms >= WALL_LIMIT_MS as f64 {
+        return Err(Error::invalid("wall_clock_ms must be below 10^18 to fit the HLC encoding"));
+    }
+    Ok(wall_clock_ms as u64)
+}
+
+/// Generate the HLC for a local event, given the last HLC issued on this node
+/// (empty string if none) and the current wall clock
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn hlc_now(last: &str, wall_clock_ms: f64, node_id: &str) -> Result<String, Error> {
+    let last = parse_optional(last)?;
+    Hlc::tick(last.as_ref(), wall(wall_clock_ms)?, node_id)
+        .map(|hlc| hlc.encode())
+        .map_err(Error::invalid)
+}
+
+/// Advance the local HLC on receipt of a remote one
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn hlc_receive(last: &str, remote: &str, wall_clock_ms: f64, node_id: &str) -> Result<String, Error> {
+    let last = parse_optional(last)?;
+    let remote = Hlc::parse(remote).map_err(Error::invalid)?;
+    Hlc::receive(last.as_ref(), &remote, wall(wall_clock_ms)?, node_id)
+        .map(|hlc| hlc.encode())
+        .map_err(Error::invalid)
+}
+
+/// Total order of two HLCs: -1, 0 or 1
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn hlc_compare(a: &str, b: &str) -> Result<i32, Error> {
+    let a = Hlc::parse(a).map_err(Error::invalid)?;
+    let b = Hlc::parse(b).map_err(Error::invalid)?;
+    Ok(match a.cmp(&b) {
+        Ordering::Less => -1,
+        Ordering::Equal => 0,
+        Ordering::Greater => 1,
+    })
+}
+
+#[derive(Serialize)]
+struct StampedOperations {
+    operations: Vec<Value>,
+    last: String,
+}
+
+/// Attach a fresh `hlc` to each operation object in order
+/// Returns `{operations, last}` as JSON; persist `last` for the next call
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn stamp_operations(operations_json: &str, last: &str, wall_clock_ms: f64, node_id: &str) -> Result<String, Error> {
+    let mut operations: Vec<Value> = from_json(operations_json, "operations_json")?;
+    let wall_ms = wall(wall_clock_ms)?;
+    let mut clock = parse_optional(last)?;
+
+    for op in &mut operations {
+        let obj = op
+            .as_object_mut()
+            .ok_or_else(|| Error::invalid("operations must be objects"))?;
+        let next = Hlc::tick(clock.as_ref(), wall_ms, node_id).map_err(Error::invalid)?;
+        obj.insert("hlc".to_string(), Value::from(next.encode()));
+        clock = Some(next);
+    }
+
+    let result = StampedOperations {
+        operations,
+        last: clock.map(|c| c.encode()).unwrap_or_else(|| last.to_string()),
+    };
+    to_json(&result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn hlc(wall_ms: u64, counter: u16, node: &str) -> Hlc {
+        Hlc {
+            wall_ms,
+            counter,
+            node: node.to_string(),
+        }
+    }
+
+    #[test]
+    fn stays_monotonic_when_the_wall_clock_goes_back() {
+        let first = Hlc::tick(None, 10_000, "a").unwrap();
+        let second = Hlc::tick(Some(&first), 4_000, "a").unwrap();
+        let third = Hlc::tick(Some(&second), 4_000, "a").unwrap();
+        assert_eq!(second, hlc(10_000, 1, "a"));
+        assert_eq!(third, hlc(10_000, 2, "a"));
+        assert!(first < second && second < third);
+        assert_eq!(Hlc::tick(Some(&third), 10_001, "a").unwrap(), hlc(10_001, 0, "a"));
+    }
+
+    #[test]
+    fn counter_overflow_is_an_error() {
+        let last = hlc(10_000, 0xfffe, "a");
+        let full = Hlc::tick(Some(&last), 10_000, "a").unwrap();
+        assert_eq!(full.encode(), "000000000000010000-ffff-a");
+        assert_eq!(Hlc::tick(Some(&full), 9_000, "a").unwrap_err(), "HLC counter overflow");
+        assert!(Hlc::receive(Some(&full), &hlc(10_000, 3, "b"), 10_000, "a").is_err());
+        assert!(Hlc::receive(None, &full, 10_000, "a").is_err());
+    }
+
+    #[test]
+    fn receive_moves_to_a_remote_clock_ahead() {
+        let last = hlc(10_000, 5, "a");
+        let remote = hlc(12_000, 7, "b");
+        let merged = Hlc::receive(Some(&last), &remote, 11_000, "a").unwrap();
+        assert_eq!(merged, hlc(12_000, 8, "a"));
+        assert!(merged > remote && merged > last);
+
+        // Equal walls take the larger counter; a later local clock resets it
+        assert_eq!(Hlc::receive(Some(&hlc(12_000, 9, "a")), &remote, 11_000, "a").unwrap(), hlc(12_000, 10, "a"));
+        assert_eq!(Hlc::receive(Some(&last), &remote, 13_000, "a").unwrap(), hlc(13_000, 0, "a"));
+        assert!(Hlc::receive(Some(&last), &hlc(11_000 + MAX_DRIFT_MS + 1, 0, "b"), 11_000, "a").is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn encoding_sorts_like_the_tuple(
+            a in (0..WALL_LIMIT_MS, any::<u16>(), "[a-z0-9-]{1,6}"),
+            b in (0..WALL_LIMIT_MS, any::<u16>(), "[a-z0-9-]{1,6}"),
+        ) {
+            let (a, b) = (hlc(a.0, a.1, &a.2), hlc(b.0, b.1, &b.2));
+            prop_assert_eq!(a.encode().cmp(&b.encode()), a.cmp(&b));
+            prop_assert_eq!(Hlc::parse(&a.encode()).unwrap(), a);
+        }
+
+        #[test]
+        fn close_values_sort_like_the_tuple(wall in 0..1_000u64, counter in 0..300u16, node in "[ab]{1,2}") {
+            let a = hlc(wall, counter, &node);
+            for b in [hlc(wall + 1, 0, "a"), hlc(wall, counter.saturating_add(1), "a"), hlc(wall, counter, "b")] {
+                prop_assert_eq!(a.encode().cmp(&b.encode()), a.cmp(&b));
+            }
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! Federation-ready learner addressing
+//!
+//! Learner ids may be opaque local strings (`alex-maker`), W3C DIDs
+//! (`did:web:school.example:learners:42`) or HTTPS IRIs
+//! (`https://school.example/learners/42`). The latter two carry their issuing
+//! namespace with them, so portfolios from several institutions can be mixed
+//! without id collisions. Anything that hashes or signs a learner id should go
+//! through [`canonical_learner_id`] so equivalent spellings agree.
+
+use serde::Serialize;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{to_json, Error};
+
+/// Parsed form of a learner identifier
+#[derive(Debug, PartialEq)]
+pub(crate) enum LearnerId {
+    Local(String),
+    Did { method: String, specific_id: String },
+    Iri { host: String, port: Option<u16>, path: String },
+}
+
+impl LearnerId {
+    /// Parse and validate a learner id. Only ids that claim to be a DID or an
+    /// http(s) IRI are held to a format; everything else is a local id.
+    pub(crate) fn parse(id: &str) -> Result<Self, String> {
+        let lower = id.to_ascii_lowercase();
+        if lower.starts_with("did:") {
+            parse_did(id)
+        } else if lower.starts_with("https://") {
+            parse_iri(&id["https://".len()..])
+        } else if lower.starts_with("http://") {
+            Err("IRI learner ids must use https".to_string())
+        } else {
+            Ok(LearnerId::Local(id.to_string()))
+        }
+    }
+
+    /// Canonical string form: lowercase DID method, lowercase IRI host and no
+    /// default port. Local ids are returned unchanged.
+    pub(crate) fn canonical(&self) -> String {
+        match self {
+            LearnerId::Local(id) => id.clone(),
+            LearnerId::Did { method, specific_id } => format!("did:{}:{}", method, specific_id),
+            LearnerId::Iri { host, port, path } => match port {
+                Some(port) => format!("https://{}:{}{}", host, port, path),
+                None => format!("https://{}{}", host, path),
+            },
+        }
+    }
+}
+
+fn parse_did(id: &str) -> Result<LearnerId, String> {
+    let mut parts = id.splitn(3, ':');
+    parts.next();
+    let method = parts.next().unwrap_or_default().to_ascii_lowercase();
+    let specific_id = parts.next().unwrap_or_default();
+
+    if method.is_empty() || !method.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit()) {
+        return Err("DID method must be lowercase letters and digits".to_string());
+    }
+    if specific_id.is_empty() || specific_id.ends_with(':') {
+        return Err("DID method-specific id is empty".to_string());
+    }
+
+    let bytes = specific_id.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = bytes.get(i + 1..i + 3).unwrap_or_default();
+                if hex.len() != 2 || !hex.iter().all(u8::is_ascii_hexdigit) {
+                    return Err("DID contains a malformed percent-encoding".to_string());
+                }
+                i += 3;
+                continue;
+            }
+            b if b.is_ascii_alphanumeric() || matches!(b, b'.' | b'-' | b'_' | b':') => {}
+            _ => return Err("DID must not contain a path, query, fragment or invalid characters".to_string()),
+        }
+        i += 1;
+    }
+
+    Ok(LearnerId::Did {
+        method,
+        specific_id: specific_id.to_string(),
+    })
+}
+
+fn parse_iri(rest: &str) -> Result<LearnerId, String> {
+    if rest.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err("IRI must not contain whitespace".to_string());
+    }
+    if rest.contains('#') {
+        return Err("IRI learner ids must not have a fragment".to_string());
+    }
+
+    let (authority, path) = match rest.find(['/', '?']) {
+        Some(idx) => rest.split_at(idx),
+        None => (rest, ""),
+    };
+    if authority.contains('@') {
+        return Err("IRI must not embed user credentials".to_string());
+    }
+
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => {
+            let port: u16 = port.parse().map_err(|_| "IRI has an invalid port".to_string())?;
+            (host, (port != 443).then_some(port))
+        }
+        None => (authority, None),
+    };
+    if host.is_empty() || host.starts_with('.') || host.ends_with('.') {
+        return Err("IRI has an empty or malformed host".to_string());
+    }
+
+    Ok(LearnerId::Iri {
+        host: host.to_lowercase(),
+        port,
+        path: if path.is_empty() { "/".to_string() } else { path.to_string() },
+    })
+}
+
+/// Canonical form of a learner id, falling back to the raw id when it does not
+/// parse (validation reports that separately)
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn canonical_learner_id(id: &str) -> String {
+    LearnerId::parse(id)
+        .map(|parsed| parsed.canonical())
+        .unwrap_or_else(|_| id.to_string())
+}
+
+#[derive(Serialize)]
+struct ResolveHints {
+    kind: &'static str,
+    canonical: String,
+    method: Option<String>,
+    namespace: Option<String>,
+    resolver: Option<String>,
+    self_resolving: bool,
+}
+
+fn resolve(parsed: &LearnerId) -> ResolveHints {
+    let canonical = parsed.canonical();
+    match parsed {
+        LearnerId::Local(_) => ResolveHints {
+            kind: "local",
+            canonical,
+            method: None,
+            namespace: None,
+            resolver: None,
+            self_resolving: false,
+        },
+        LearnerId::Did { method, specific_id } => {
+            let (namespace, resolver) = if method == "web" {
+                // did:web:host%3Aport:path:segments -> https://host:port/path/segments/did.json
+                let mut segments = specific_id.split(':');
+                let host = segments.next().unwrap_or_default().replace("%3A", ":").replace("%3a", ":");
+                let path: Vec<&str> = segments.collect();
+                let url = if path.is_empty() {
+                    format!("https://{}/.well-known/did.json", host)
+                } else {
+                    format!("https://{}/{}/did.json", host, path.join("/"))
+                };
+                (Some(host), Some(url))
+            } else {
+                (None, None)
+            };
+            ResolveHints {
+                kind: "did",
+                canonical,
+                method: Some(method.clone()),
+                namespace,
+                resolver,
+                self_resolving: method == "key",
+            }
+        }
+        LearnerId::Iri { host, .. } => ResolveHints {
+            kind: "iri",
+            resolver: Some(canonical.clone()),
+            canonical,
+            method: None,
+            namespace: Some(host.clone()),
+            self_resolving: false,
+        },
+    }
+}
+
+/// Describe how a learner id can be resolved across institutions
+/// Returns `{kind, canonical, method, namespace, resolver, self_resolving}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn resolve_hints(learner_id: &str) -> Result<String, Error> {
+    let parsed = LearnerId::parse(learner_id).map_err(Error::invalid)?;
+    to_json(&resolve(&parsed))
+}
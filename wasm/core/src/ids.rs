@@ -0,0 +1,99 @@
+//! Experience id formats
+//!
+//! New ids are ULIDs: 48 bits of Unix milliseconds followed by 80 random
+//! bits, in Crockford base32, so they sort by creation time as plain strings.
+//! Deployments that already use UUIDs can restrict or widen the accepted
+//! formats through `ExperienceValidator::set_id_formats`.
+
+use serde::Deserialize;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::crypto::random_bytes;
+use crate::error::Error;
+use crate::time::now_ms;
+
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum IdFormat {
+    Ulid,
+    UuidV4,
+    UuidV7,
+}
+
+/// Formats accepted when a deployment has not configured any
+pub(crate) const DEFAULT_ID_FORMATS: &[IdFormat] = &[IdFormat::Ulid, IdFormat::UuidV4, IdFormat::UuidV7];
+
+impl IdFormat {
+    pub(crate) fn matches(self, id: &str) -> bool {
+        match self {
+            IdFormat::Ulid => is_ulid(id),
+            IdFormat::UuidV4 => uuid_version(id) == Some(4),
+            IdFormat::UuidV7 => uuid_version(id) == Some(7),
+        }
+    }
+}
+
+fn is_ulid(id: &str) -> bool {
+    id.len() == 26
+        && id.as_bytes()[0] <= b'7'
+        && id
+            .bytes()
+            .all(|b| CROCKFORD.contains(&b.to_ascii_uppercase()))
+}
+
+/// Version of an RFC 9562 UUID (hyphenated, either case) with the RFC
+/// variant bits, or `None` if `id` is not one
+fn uuid_version(id: &str) -> Option<u8> {
+    let bytes = id.as_bytes();
+    if bytes.len() != 36 {
+        return None;
+    }
+    for (i, &b) in bytes.iter().enumerate() {
+        let hyphen = matches!(i, 8 | 13 | 18 | 23);
+        if hyphen != (b == b'-') || (!hyphen && !b.is_ascii_hexdigit()) {
+            return None;
+        }
+    }
+    if !matches!(bytes[19].to_ascii_lowercase(), b'8' | b'9' | b'a' | b'b') {
+        return None;
+    }
+    (bytes[14] as char).to_digit(16).map(|v| v as u8)
+}
+
+/// Encode a ULID from its timestamp and random parts
+pub(crate) fn ulid(ms: u64, random: [u8; 10]) -> String {
+    let mut value = u128::from(ms & 0xFFFF_FFFF_FFFF) << 80;
+    for (i, b) in random.iter().enumerate() {
+        value |= u128::from(*b) << (72 - 8 * i);
+    }
+    (0..26)
+        .rev()
+        .map(|i| CROCKFORD[((value >> (5 * i)) & 0x1F) as usize] as char)
+        .collect()
+}
+
+/// Creation time embedded in a ULID or UUIDv7, in epoch milliseconds
+pub(crate) fn id_created_ms(id: &str) -> Option<i64> {
+    if is_ulid(id) {
+        let mut ms = 0i64;
+        for b in id.bytes().take(10) {
+            let digit = CROCKFORD.iter().position(|&c| c == b.to_ascii_uppercase())?;
+            ms = ms << 5 | digit as i64;
+        }
+        return Some(ms);
+    }
+    if uuid_version(id) == Some(7) {
+        return i64::from_str_radix(&format!("{}{}", &id[..8], &id[9..13]), 16).ok();
+    }
+    None
+}
+
+/// Generate a new time-ordered experience id (ULID)
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn generate_experience_id() -> Result<String, Error> {
+    let random = random_bytes::<10>().map_err(Error::host)?;
+    Ok(ulid(now_ms().max(0) as u64, random))
+}
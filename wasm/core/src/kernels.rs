@@ -0,0 +1,175 @@
+//! Vector kernels behind the similarity measures
+//!
+//! [`pair_sums`] walks two dense count vectors once and returns the sums
+//! every multiset measure is built from: Σ min, Σ max and the dot product.
+//! With the `simd` feature on a WASM build with `simd128` enabled
+//! (`RUSTFLAGS="-C target-feature=+simd128"`, see `just build-simd`) it
+//! runs two lanes at a time with `core::arch::wasm32` intrinsics.
+//!
+//! [`sparse_dot`] is the TF-IDF cosine: it merge-joins two sparse vectors
+//! and, since simd128 has no gather, packs the weights of each two shared
+//! features into a vector for the multiply-accumulate.
+//!
+//! The scalar versions accumulate in the same two lanes, even and odd
+//! indices (or shared features), and add them in the same order at the
+//! end, so both produce the same bits, as `numeric.rs` requires.
+
+#[cfg(all(feature = "simd", target_arch = "wasm32", not(target_feature = "simd128")))]
+compile_error!("the simd feature needs RUSTFLAGS=\"-C target-feature=+simd128\" on wasm32");
+
+/// Σ min(a, b), Σ max(a, b) and Σ a·b of two equal-length vectors
+#[derive(Clone, Copy, Default)]
+pub(crate) struct PairSums {
+    pub(crate) min: f64,
+    pub(crate) max: f64,
+    pub(crate) dot: f64,
+}
+
+#[cfg(not(all(feature = "simd", target_arch = "wasm32")))]
+pub(crate) fn pair_sums(a: &[f64], b: &[f64]) -> PairSums {
+    debug_assert_eq!(a.len(), b.len());
+    let mut lanes = [PairSums::default(); 2];
+    for (pair_a, pair_b) in a.chunks_exact(2).zip(b.chunks_exact(2)) {
+        for (lane, (&x, &y)) in lanes.iter_mut().zip(pair_a.iter().zip(pair_b)) {
+            lane.min += x.min(y);
+            lane.max += x.max(y);
+            lane.dot += x * y;
+        }
+    }
+    finish(lanes, a, b)
+}
+
+#[cfg(all(feature = "simd", target_arch = "wasm32"))]
+pub(crate) fn pair_sums(a: &[f64], b: &[f64]) -> PairSums {
+    use core::arch::wasm32::*;
+
+    debug_assert_eq!(a.len(), b.len());
+    let (mut min, mut max, mut dot) = (f64x2_splat(0.0), f64x2_splat(0.0), f64x2_splat(0.0));
+    for (pair_a, pair_b) in a.chunks_exact(2).zip(b.chunks_exact(2)) {
+        // Built from the slices: the crate forbids the unsafe v128_load
+        let (x, y) = (f64x2(pair_a[0], pair_a[1]), f64x2(pair_b[0], pair_b[1]));
+        min = f64x2_add(min, f64x2_min(x, y));
+        max = f64x2_add(max, f64x2_max(x, y));
+        dot = f64x2_add(dot, f64x2_mul(x, y));
+    }
+    let lane = |v: v128| [f64x2_extract_lane::<0>(v), f64x2_extract_lane::<1>(v)];
+    let (min, max, dot) = (lane(min), lane(max), lane(dot));
+    let lanes = [0, 1].map(|i| PairSums { min: min[i], max: max[i], dot: dot[i] });
+    finish(lanes, a, b)
+}
+
+/// Add an odd trailing element to the first lane, then the lanes together
+fn finish(mut lanes: [PairSums; 2], a: &[f64], b: &[f64]) -> PairSums {
+    if a.len() % 2 == 1 {
+        let (x, y) = (a[a.len() - 1], b[b.len() - 1]);
+        lanes[0].min += x.min(y);
+        lanes[0].max += x.max(y);
+        lanes[0].dot += x * y;
+    }
+    PairSums {
+        min: lanes[0].min + lanes[1].min,
+        max: lanes[0].max + lanes[1].max,
+        dot: lanes[0].dot + lanes[1].dot,
+    }
+}
+
+/// Weight pairs of the features two sparse vectors, sorted by feature id,
+/// have in common
+fn shared<'a>(a: &'a [(u32, f32)], b: &'a [(u32, f32)]) -> impl Iterator<Item = (f64, f64)> + 'a {
+    let (mut i, mut j) = (0, 0);
+    std::iter::from_fn(move || {
+        while i < a.len() && j < b.len() {
+            match a[i].0.cmp(&b[j].0) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    let pair = (f64::from(a[i].1), f64::from(b[j].1));
+                    i += 1;
+                    j += 1;
+                    return Some(pair);
+                }
+            }
+        }
+        None
+    })
+}
+
+/// Σ a·b of two sparse vectors sorted by feature id
+pub(crate) fn sparse_dot(a: &[(u32, f32)], b: &[(u32, f32)]) -> f64 {
+    #[cfg(all(feature = "simd", target_arch = "wasm32"))]
+    {
+        sparse_dot_simd(a, b)
+    }
+    #[cfg(not(all(feature = "simd", target_arch = "wasm32")))]
+    {
+        sparse_dot_scalar(a, b)
+    }
+}
+
+#[cfg_attr(all(feature = "simd", target_arch = "wasm32", not(test)), allow(dead_code))]
+fn sparse_dot_scalar(a: &[(u32, f32)], b: &[(u32, f32)]) -> f64 {
+    let mut lanes = [0.0; 2];
+    for (k, (x, y)) in shared(a, b).enumerate() {
+        lanes[k % 2] += x * y;
+    }
+    lanes[0] + lanes[1]
+}
+
+#[cfg(all(feature = "simd", target_arch = "wasm32"))]
+fn sparse_dot_simd(a: &[(u32, f32)], b: &[(u32, f32)]) -> f64 {
+    use core::arch::wasm32::*;
+
+    let mut dot = f64x2_splat(0.0);
+    let mut pending = None;
+    for (x, y) in shared(a, b) {
+        match pending.take() {
+            None => pending = Some((x, y)),
+            Some((px, py)) => dot = f64x2_add(dot, f64x2_mul(f64x2(px, x), f64x2(py, y))),
+        }
+    }
+    let mut lanes = [f64x2_extract_lane::<0>(dot), f64x2_extract_lane::<1>(dot)];
+    if let Some((x, y)) = pending {
+        lanes[0] += x * y;
+    }
+    lanes[0] + lanes[1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// A sparse vector over `0..64` with the given weights
+    fn sparse() -> impl Strategy<Value = Vec<(u32, f32)>> {
+        prop::collection::btree_map(0u32..64, -1.0f32..1.0, 0..40).prop_map(|map| map.into_iter().collect())
+    }
+
+    fn dense(vector: &[(u32, f32)]) -> Vec<f64> {
+        let mut dense = vec![0.0; 64];
+        for &(id, w) in vector {
+            dense[id as usize] = f64::from(w);
+        }
+        dense
+    }
+
+    proptest! {
+        #[test]
+        fn sparse_dot_matches_the_scalar_and_dense_sums(a in sparse(), b in sparse()) {
+            let naive: f64 = shared(&a, &b).map(|(x, y)| x * y).sum();
+            let dot = sparse_dot(&a, &b);
+            prop_assert_eq!(dot.to_bits(), sparse_dot_scalar(&a, &b).to_bits());
+            prop_assert!((dot - naive).abs() < 1e-9, "{} vs {}", dot, naive);
+            prop_assert!((dot - pair_sums(&dense(&a), &dense(&b)).dot).abs() < 1e-9);
+        }
+
+        #[test]
+        fn pair_sums_match_a_plain_loop(pairs in prop::collection::vec((0.0f64..10.0, 0.0f64..10.0), 0..33)) {
+            let (a, b): (Vec<f64>, Vec<f64>) = pairs.into_iter().unzip();
+            let sums = pair_sums(&a, &b);
+            let zip = || a.iter().zip(&b);
+            prop_assert!((sums.min - zip().map(|(x, y)| x.min(*y)).sum::<f64>()).abs() < 1e-9);
+            prop_assert!((sums.max - zip().map(|(x, y)| x.max(*y)).sum::<f64>()).abs() < 1e-9);
+            prop_assert!((sums.dot - zip().map(|(x, y)| x * y).sum::<f64>()).abs() < 1e-9);
+        }
+    }
+}
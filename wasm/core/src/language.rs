@@ -0,0 +1,83 @@
+//! Language detection for experience descriptions
+//!
+//! Uses whatlang's trigram models. Languages are reported as ISO 639-3 codes
+//! (`eng`, `cym`, `spa`, ...). Deployments that know their languages should
+//! pass an allowlist: restricting the candidate set makes short descriptions
+//! far more reliable.
+
+use serde::Serialize;
+use serde_json::Value;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use whatlang::{Detector, Lang};
+
+use crate::error::{from_json, to_json, Error};
+
+#[derive(Serialize)]
+struct LanguageGuess {
+    code: &'static str,
+    name: &'static str,
+    script: String,
+    confidence: f64,
+    reliable: bool,
+}
+
+fn detector(allowlist_json: &str) -> Result<Detector, String> {
+    if allowlist_json.trim().is_empty() {
+        return Ok(Detector::new());
+    }
+    let codes: Vec<String> = serde_json::from_str(allowlist_json).map_err(|e| e.to_string())?;
+    let langs = codes
+        .iter()
+        .map(|code| Lang::from_code(code).ok_or_else(|| format!("unknown language code: {}", code)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Detector::with_allowlist(langs))
+}
+
+fn guess(detector: &Detector, text: &str) -> Option<LanguageGuess> {
+    detector.detect(text).map(|info| LanguageGuess {
+        code: info.lang().code(),
+        name: info.lang().eng_name(),
+        script: info.script().name().to_string(),
+        confidence: info.confidence(),
+        reliable: info.is_reliable(),
+    })
+}
+
+/// Detect the language of `text`, optionally restricted to a JSON array of
+/// ISO 639-3 codes
+/// Returns `{code, name, script, confidence, reliable}` as JSON, or `null`
+/// when no language could be determined
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn detect_language(text: &str, allowlist_json: &str) -> Result<String, Error> {
+    let detector = detector(allowlist_json).map_err(Error::invalid)?;
+    to_json(&guess(&detector, text))
+}
+
+/// Stamp `experience.language` onto experiences that lack one, using the
+/// detected language of the description when it meets `min_confidence`
+/// Returns the experiences as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn stamp_languages(experiences_json: &str, allowlist_json: &str, min_confidence: f64) -> Result<String, Error> {
+    let mut experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let detector = detector(allowlist_json).map_err(Error::invalid)?;
+
+    for exp in &mut experiences {
+        let Some(data) = exp.get_mut("experience").and_then(Value::as_object_mut) else {
+            continue;
+        };
+        if data.get("language").is_some_and(|lang| !lang.is_null()) {
+            continue;
+        }
+        let detected = data
+            .get("description")
+            .and_then(Value::as_str)
+            .and_then(|description| guess(&detector, description))
+            .filter(|g| g.confidence >= min_confidence);
+        if let Some(g) = detected {
+            data.insert("language".to_string(), Value::from(g.code));
+        }
+    }
+
+    to_json(&experiences)
+}
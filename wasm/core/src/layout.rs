@@ -0,0 +1,282 @@
+//! Force-directed network layout
+//!
+//! Fruchterman–Reingold with grid-bucketed repulsion (nodes further apart
+//! than `2k` do not repel), so each iteration is close to linear in the
+//! number of nodes and a 5k-node domain network lays out without freezing
+//! the UI. [`NetworkLayout`] keeps positions and temperature between calls,
+//! letting JS run a few iterations per animation frame; nodes that already
+//! carry `x`/`y` start from there, the rest start on a phyllotaxis spiral.
+//! [`layout_network_typed`] and [`NetworkLayout::positions_array`] return
+//! positions as a flat `Float64Array` for posting from a worker.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::buffers::Buffers;
+use crate::error::{from_json, to_json, Error};
+use crate::numeric::{cos, sin};
+
+/// Side of the square layout area
+const AREA_SIDE: f64 = 1000.0;
+const COOLING: f64 = 0.95;
+const MIN_TEMPERATURE: f64 = 0.01;
+
+#[derive(Deserialize)]
+struct LayoutNetwork {
+    nodes: Vec<LayoutNodeIn>,
+    #[serde(default)]
+    edges: Vec<LayoutEdgeIn>,
+}
+
+#[derive(Deserialize)]
+struct LayoutNodeIn {
+    id: String,
+    x: Option<f64>,
+    y: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct LayoutEdgeIn {
+    source: String,
+    target: String,
+    #[serde(default = "default_weight")]
+    weight: f64,
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+#[derive(Serialize)]
+struct Positioned<'a> {
+    id: &'a str,
+    x: f64,
+    y: f64,
+}
+
+#[derive(Serialize)]
+struct LayoutIndex<'a> {
+    /// Node ids, in the order of the `[x, y]` pairs in the float buffer
+    ids: &'a [String],
+    temperature: f64,
+    iterations: u32,
+}
+
+#[derive(Serialize)]
+struct LayoutResult<'a> {
+    nodes: Vec<Positioned<'a>>,
+    temperature: f64,
+    iterations: u32,
+}
+
+/// Resumable Fruchterman–Reingold layout of a domain network
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct NetworkLayout {
+    ids: Vec<String>,
+    positions: Vec<(f64, f64)>,
+    edges: Vec<(usize, usize, f64)>,
+    k: f64,
+    temperature: f64,
+    iterations: u32,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl NetworkLayout {
+    /// Prepare a layout for a `{nodes, edges}` network
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(network_json: &str) -> Result<NetworkLayout, Error> {
+        let network: LayoutNetwork = from_json(network_json, "network_json")?;
+        Self::from_network(network).map_err(Error::invalid)
+    }
+
+    /// Current temperature (maximum displacement per iteration)
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn temperature(&self) -> f64 {
+        self.temperature
+    }
+
+    /// Iterations run so far
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+
+    /// Whether the layout has cooled down and further steps barely move it
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn converged(&self) -> bool {
+        self.temperature <= MIN_TEMPERATURE
+    }
+
+    /// Run up to `iterations` more iterations
+    /// Returns `{nodes: [{id, x, y}], temperature, iterations}` as JSON
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn step(&mut self, iterations: u32) -> Result<String, Error> {
+        self.run(iterations);
+        self.positions_json()
+    }
+
+    /// Current positions, in the same shape as `step`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn positions_json(&self) -> Result<String, Error> {
+        let result = LayoutResult {
+            nodes: self
+                .ids
+                .iter()
+                .zip(&self.positions)
+                .map(|(id, &(x, y))| Positioned { id, x, y })
+                .collect(),
+            temperature: self.temperature,
+            iterations: self.iterations,
+        };
+        to_json(&result)
+    }
+
+    /// Current positions as `[x0, y0, x1, y1, ...]` (a `Float64Array` in
+    /// JS), nodes in input order
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn positions_array(&self) -> Vec<f64> {
+        self.positions.iter().flat_map(|&(x, y)| [x, y]).collect()
+    }
+}
+
+impl NetworkLayout {
+    fn run(&mut self, iterations: u32) {
+        for _ in 0..iterations {
+            if self.converged() {
+                break;
+            }
+            self.iterate();
+        }
+    }
+
+    fn from_network(network: LayoutNetwork) -> Result<Self, String> {
+        let n = network.nodes.len();
+        let mut index = HashMap::with_capacity(n);
+        let mut ids = Vec::with_capacity(n);
+        let mut positions = Vec::with_capacity(n);
+        let golden_angle = std::f64::consts::PI * (3.0 - 5f64.sqrt());
+        let spiral_step = AREA_SIDE / 2.0 / (n.max(1) as f64).sqrt();
+
+        for (i, node) in network.nodes.into_iter().enumerate() {
+            if index.insert(node.id.clone(), i).is_some() {
+                return Err(format!("duplicate node id: {}", node.id));
+            }
+            let position = match (node.x, node.y) {
+                (Some(x), Some(y)) if x.is_finite() && y.is_finite() => (x, y),
+                _ => {
+                    let radius = spiral_step * (0.5 + i as f64).sqrt();
+                    let angle = i as f64 * golden_angle;
+                    (radius * cos(angle), radius * sin(angle))
+                }
+            };
+            ids.push(node.id);
+            positions.push(position);
+        }
+
+        let mut edges = Vec::with_capacity(network.edges.len());
+        for edge in network.edges {
+            let (Some(&s), Some(&t)) = (index.get(&edge.source), index.get(&edge.target)) else {
+                return Err(format!("edge {} -> {} references an unknown node", edge.source, edge.target));
+            };
+            if s != t {
+                edges.push((s, t, edge.weight.max(0.0)));
+            }
+        }
+
+        Ok(Self {
+            ids,
+            positions,
+            edges,
+            k: (AREA_SIDE * AREA_SIDE / n.max(1) as f64).sqrt(),
+            temperature: AREA_SIDE / 10.0,
+            iterations: 0,
+        })
+    }
+
+    fn iterate(&mut self) {
+        let n = self.positions.len();
+        let k = self.k;
+        let cell = 2.0 * k;
+        let mut displacement = vec![(0.0f64, 0.0f64); n];
+
+        // Repulsion between nodes in the same or adjacent grid cells
+        let cell_of = |(x, y): (f64, f64)| ((x / cell).floor() as i64, (y / cell).floor() as i64);
+        let mut grid: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+        for (i, &p) in self.positions.iter().enumerate() {
+            grid.entry(cell_of(p)).or_default().push(i);
+        }
+        for (i, &(xi, yi)) in self.positions.iter().enumerate() {
+            let (cx, cy) = cell_of((xi, yi));
+            for gx in cx - 1..=cx + 1 {
+                for gy in cy - 1..=cy + 1 {
+                    for &j in grid.get(&(gx, gy)).into_iter().flatten() {
+                        if i == j {
+                            continue;
+                        }
+                        let (mut dx, mut dy) = (xi - self.positions[j].0, yi - self.positions[j].1);
+                        let mut distance = (dx * dx + dy * dy).sqrt();
+                        if distance < 1e-6 {
+                            // Coincident nodes: separate along a fixed direction
+                            dx = (i as f64 - j as f64).signum() * 0.01;
+                            dy = 0.0;
+                            distance = 0.01;
+                        }
+                        if distance < cell {
+                            let force = k * k / distance;
+                            displacement[i].0 += dx / distance * force;
+                            displacement[i].1 += dy / distance * force;
+                        }
+                    }
+                }
+            }
+        }
+
+        // Attraction along edges, scaled by weight
+        for &(s, t, weight) in &self.edges {
+            let (dx, dy) = (self.positions[s].0 - self.positions[t].0, self.positions[s].1 - self.positions[t].1);
+            let distance = (dx * dx + dy * dy).sqrt();
+            if distance < 1e-6 {
+                continue;
+            }
+            let force = distance * distance / k * weight.sqrt();
+            let (fx, fy) = (dx / distance * force, dy / distance * force);
+            displacement[s].0 -= fx;
+            displacement[s].1 -= fy;
+            displacement[t].0 += fx;
+            displacement[t].1 += fy;
+        }
+
+        // Move by at most the temperature, then cool
+        for (p, (dx, dy)) in self.positions.iter_mut().zip(displacement) {
+            let length = (dx * dx + dy * dy).sqrt();
+            if length > 0.0 {
+                let step = length.min(self.temperature);
+                p.0 += dx / length * step;
+                p.1 += dy / length * step;
+            }
+        }
+        self.temperature *= COOLING;
+        self.iterations += 1;
+    }
+}
+
+/// Lay out a `{nodes, edges}` network with Fruchterman–Reingold
+/// Returns `{nodes: [{id, x, y}], temperature, iterations}` as JSON; use
+/// `NetworkLayout` to continue from where it stopped
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn layout_network(network_json: &str, iterations: u32) -> Result<String, Error> {
+    let mut layout = NetworkLayout::new(network_json)?;
+    layout.step(iterations)
+}
+
+/// `layout_network` as typed arrays: `[x, y]` per node as floats
+/// Returns Buffers indexed by `{ids, temperature, iterations}`
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn layout_network_typed(network_json: &str, iterations: u32) -> Result<Buffers, Error> {
+    let mut layout = NetworkLayout::new(network_json)?;
+    layout.run(iterations);
+    let index = LayoutIndex { ids: &layout.ids, temperature: layout.temperature, iterations: layout.iterations };
+    Buffers::new(&index, layout.positions_array(), Vec::new())
+}
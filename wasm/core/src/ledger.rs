@@ -0,0 +1,263 @@
+//! Tamper-evident experience ledgers
+//!
+//! Each experience is hashed over its JSON Canonicalization Scheme form
+//! (RFC 8785, as the signed badges use) with SHA-256, so any JCS library
+//! reproduces the entry hashes. An ordered log is then linked
+//! into a hash chain, `link[i] = SHA-256(link[i-1] || hash[i])` with an
+//! all-zero genesis link, and summarised by an RFC 6962-style Merkle root
+//! (domain-separated leaf and node hashes), so a portfolio can prove that no
+//! record was edited, removed or reordered after the fact. A log can always
+//! be rebuilt with fresh hashes, so verification is against a head kept
+//! somewhere the log's holder cannot rewrite.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::crypto::{from_hex, jcs, sha256, to_hex};
+use crate::error::{from_json, to_json, Error};
+
+const GENESIS: [u8; 32] = [0; 32];
+
+#[derive(Serialize, Deserialize)]
+struct LedgerEntry {
+    experience: Value,
+    hash: String,
+    prev: String,
+    link: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Ledger {
+    entries: Vec<LedgerEntry>,
+    head: String,
+    merkle_root: String,
+}
+
+#[derive(Serialize)]
+struct ChainVerification {
+    valid: bool,
+    /// Index of the first entry that fails verification
+    first_invalid: Option<usize>,
+    reason: Option<String>,
+    head: String,
+    merkle_root: String,
+}
+
+pub(crate) fn content_hash(experience: &Value) -> [u8; 32] {
+    sha256(jcs(experience).as_bytes())
+}
+
+fn chain_link(prev: &[u8; 32], hash: &[u8; 32]) -> [u8; 32] {
+    let mut buf = [0u8; 64];
+    buf[..32].copy_from_slice(prev);
+    buf[32..].copy_from_slice(hash);
+    sha256(&buf)
+}
+
+/// RFC 6962 Merkle tree hash; an odd node is promoted to the next level
+pub(crate) fn merkle_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    if leaves.is_empty() {
+        return sha256(&[]);
+    }
+    let mut level: Vec<[u8; 32]> = leaves
+        .iter()
+        .map(|leaf| {
+            let mut buf = Vec::with_capacity(33);
+            buf.push(0x00);
+            buf.extend_from_slice(leaf);
+            sha256(&buf)
+        })
+        .collect();
+
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => {
+                    let mut buf = Vec::with_capacity(65);
+                    buf.push(0x01);
+                    buf.extend_from_slice(left);
+                    buf.extend_from_slice(right);
+                    sha256(&buf)
+                }
+                [single] => *single,
+                _ => unreachable!("chunks(2) yields one or two items"),
+            })
+            .collect();
+    }
+    level[0]
+}
+
+fn build(experiences: Vec<Value>) -> Ledger {
+    let mut prev = GENESIS;
+    let mut hashes = Vec::with_capacity(experiences.len());
+    let entries = experiences
+        .into_iter()
+        .map(|experience| {
+            let hash = content_hash(&experience);
+            let link = chain_link(&prev, &hash);
+            let entry = LedgerEntry {
+                experience,
+                hash: to_hex(&hash),
+                prev: to_hex(&prev),
+                link: to_hex(&link),
+            };
+            hashes.push(hash);
+            prev = link;
+            entry
+        })
+        .collect();
+
+    Ledger {
+        entries,
+        head: to_hex(&prev),
+        merkle_root: to_hex(&merkle_root(&hashes)),
+    }
+}
+
+fn verify(entries: &[LedgerEntry], trusted_head: &str, expected_head: Option<&str>, expected_root: Option<&str>) -> ChainVerification {
+    let mut prev = GENESIS;
+    let mut hashes = Vec::with_capacity(entries.len());
+    let mut failure: Option<(usize, String)> = None;
+
+    for (index, entry) in entries.iter().enumerate() {
+        let hash = content_hash(&entry.experience);
+        let link = chain_link(&prev, &hash);
+        if failure.is_none() {
+            let problem = if from_hex(&entry.hash).as_deref() != Some(&hash[..]) {
+                Some("content hash mismatch: experience was modified")
+            } else if from_hex(&entry.prev).as_deref() != Some(&prev[..]) {
+                Some("previous link mismatch: entries were removed or reordered")
+            } else if from_hex(&entry.link).as_deref() != Some(&link[..]) {
+                Some("chain link mismatch")
+            } else {
+                None
+            };
+            failure = problem.map(|reason| (index, reason.to_string()));
+        }
+        hashes.push(hash);
+        prev = link;
+    }
+
+    let head = to_hex(&prev);
+    let root = to_hex(&merkle_root(&hashes));
+    if failure.is_none() {
+        if !trusted_head.eq_ignore_ascii_case(&head) {
+            failure = Some((entries.len(), "trusted head mismatch: log was rewritten, truncated or extended".to_string()));
+        } else if expected_head.is_some_and(|h| !h.eq_ignore_ascii_case(&head)) {
+            failure = Some((entries.len(), "head mismatch: log was truncated or extended".to_string()));
+        } else if expected_root.is_some_and(|r| !r.eq_ignore_ascii_case(&root)) {
+            failure = Some((entries.len(), "merkle root mismatch".to_string()));
+        }
+    }
+
+    ChainVerification {
+        valid: failure.is_none(),
+        first_invalid: failure.as_ref().map(|(index, _)| *index),
+        reason: failure.map(|(_, reason)| reason),
+        head,
+        merkle_root: root,
+    }
+}
+
+/// SHA-256 of an experience's JCS (RFC 8785) form, as hex
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn hash_experience(json: &str) -> Result<String, Error> {
+    let experience: Value = from_json(json, "json")?;
+    Ok(to_hex(&content_hash(&experience)))
+}
+
+/// Build a hash-chained ledger over an ordered array of experiences
+/// Returns `{entries: [{experience, hash, prev, link}], head, merkle_root}`
+/// as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn build_chain(experiences_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    to_json(&build(experiences))
+}
+
+/// Verify a ledger produced by `build_chain` against `trusted_head`, the
+/// hex head recorded when the log was last known good
+/// Returns `{valid, first_invalid, reason, head, merkle_root}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn verify_chain(log_json: &str, trusted_head: &str) -> Result<String, Error> {
+    if from_hex(trusted_head).is_none_or(|head| head.len() != 32) {
+        return Err(Error::invalid("trusted_head must be a 64-digit hex SHA-256").with("argument", "trusted_head"));
+    }
+    let ledger: Ledger = from_json(log_json, "log_json")?;
+    let result = verify(&ledger.entries, trusted_head, Some(&ledger.head), Some(&ledger.merkle_root));
+    to_json(&result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn log() -> Value {
+        let experiences = json!([
+            {"id": "a", "experience": {"type": "walk", "domains": ["ecology"]}, "score": 1.0},
+            {"id": "b", "experience": {"type": "talk", "domains": ["art"]}, "é": 2, "e": 3},
+            {"id": "c", "experience": {"type": "lab", "domains": []}},
+        ]);
+        serde_json::from_str(&build_chain(&experiences.to_string()).unwrap()).unwrap()
+    }
+
+    fn verified(log: &Value, trusted_head: &str) -> Value {
+        serde_json::from_str(&verify_chain(&log.to_string(), trusted_head).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn entry_hashes_are_over_jcs() {
+        let log = log();
+        // JCS writes 1.0 as 1 and sorts "e" before "id" before "é"
+        let first = r#"{"experience":{"domains":["ecology"],"type":"walk"},"id":"a","score":1}"#;
+        assert_eq!(log["entries"][0]["hash"], to_hex(&sha256(first.as_bytes())));
+        let second = r#"{"e":3,"experience":{"domains":["art"],"type":"talk"},"id":"b","é":2}"#;
+        assert_eq!(log["entries"][1]["hash"], to_hex(&sha256(second.as_bytes())));
+        assert_eq!(hash_experience(&log["entries"][1]["experience"].to_string()).unwrap(), log["entries"][1]["hash"]);
+    }
+
+    #[test]
+    fn an_untouched_log_verifies_against_its_head() {
+        let log = log();
+        let result = verified(&log, log["head"].as_str().unwrap());
+        assert_eq!(result["valid"], true);
+        assert_eq!(result["merkle_root"], log["merkle_root"]);
+    }
+
+    #[test]
+    fn a_rebuilt_log_fails_against_the_trusted_head() {
+        let log = log();
+        let trusted = log["head"].as_str().unwrap().to_string();
+        let mut experiences: Vec<Value> = log["entries"].as_array().unwrap().iter().map(|e| e["experience"].clone()).collect();
+        experiences[1]["experience"]["type"] = json!("lecture");
+        let forged: Value = serde_json::from_str(&build_chain(&Value::from(experiences).to_string()).unwrap()).unwrap();
+
+        assert_eq!(verified(&forged, forged["head"].as_str().unwrap())["valid"], true);
+        let result = verified(&forged, &trusted);
+        assert_eq!(result["valid"], false);
+        assert_eq!(result["first_invalid"], 3);
+        assert!(result["reason"].as_str().unwrap().starts_with("trusted head mismatch"));
+    }
+
+    #[test]
+    fn an_edited_entry_is_located() {
+        let mut log = log();
+        let trusted = log["head"].as_str().unwrap().to_string();
+        log["entries"][1]["experience"]["id"] = json!("z");
+        let result = verified(&log, &trusted);
+        assert_eq!(result["valid"], false);
+        assert_eq!(result["first_invalid"], 1);
+    }
+
+    #[test]
+    fn rejects_a_malformed_trusted_head() {
+        let log = log().to_string();
+        for head in ["", "abc", &"g".repeat(64), &"0".repeat(62)] {
+            assert!(verify_chain(&log, head).is_err(), "{:?}", head);
+        }
+    }
+}
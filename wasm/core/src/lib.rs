@@ -0,0 +1,598 @@
+#![forbid(unsafe_code)]
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use error::{from_json, to_json, Error};
+use severity::{Issue, Severity};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+pub mod accessibility;
+pub mod aggregate;
+pub mod alerts;
+pub mod alignment;
+pub mod anchors;
+pub mod anomalies;
+pub mod anonymity;
+pub mod archive;
+pub mod array_stream;
+pub mod backlinks;
+pub mod badges;
+pub mod buffers;
+pub mod clock;
+pub mod clusters;
+pub mod comments;
+mod coordinate_checks;
+pub mod coverage;
+pub mod crs;
+mod crypto;
+pub mod darwin_core;
+pub mod distance_bands;
+pub mod domain_transitions;
+pub mod environment;
+pub mod error;
+pub mod eviction;
+pub mod export;
+pub mod fingerprint;
+pub mod formats;
+pub mod gaps;
+pub mod gazetteer;
+mod geo;
+mod geojson;
+pub mod goals;
+pub mod grading;
+pub mod graph_formats;
+pub mod group_geometry;
+pub mod gzip;
+pub mod heatmap;
+pub mod heavy_hitters;
+pub mod hlc;
+pub mod identity;
+pub mod ids;
+mod kernels;
+pub mod language;
+pub mod layout;
+pub mod ledger;
+pub mod map_matching;
+mod messages;
+pub mod metrics;
+pub mod mvt;
+pub mod narrative;
+pub mod network;
+pub mod numeric;
+pub mod parallel;
+pub mod parquet;
+pub mod patterns;
+pub mod places;
+pub mod poi;
+pub mod privacy;
+pub mod protocol;
+pub mod pruning;
+pub mod quality;
+pub mod query;
+pub mod reactions;
+pub mod reconcile;
+pub mod recording;
+pub mod references;
+pub mod recommend;
+#[cfg(feature = "wasm")]
+pub mod replay;
+pub mod rules;
+pub mod retry;
+pub mod schedule;
+pub mod search;
+pub mod sessions;
+mod severity;
+pub mod shapefile;
+pub mod signing;
+pub mod similarity;
+pub mod sketches;
+#[cfg(feature = "wasm")]
+pub mod store;
+pub mod stream;
+pub mod suggest;
+pub mod summary;
+pub mod sun;
+pub mod sync;
+pub mod telemetry;
+pub mod tenancy;
+pub mod tfidf;
+#[cfg(all(feature = "threads", feature = "wasm", target_arch = "wasm32"))]
+pub mod thread_pool;
+pub mod trajectories;
+pub mod triples;
+mod text;
+mod time;
+#[cfg(feature = "wasm")]
+pub mod typed;
+pub mod units;
+pub mod upload;
+pub mod usage;
+pub mod webhook;
+
+#[cfg(all(feature = "threads", feature = "wasm", target_arch = "wasm32"))]
+pub use thread_pool::init_thread_pool;
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console)]
+    fn log(s: &str);
+}
+
+/// High-performance experience validation (replaces Zod for critical path)
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct ExperienceValidator {
+    strict_mode: bool,
+    id_formats: Option<Vec<ids::IdFormat>>,
+    severity_overrides: HashMap<String, Severity>,
+    known_domains: Option<HashSet<String>>,
+    coordinate_checks: coordinate_checks::CoordinateChecks,
+    locale: String,
+    catalogs: messages::Catalogs,
+}
+
+/// Descriptions shorter than this many characters draw a warning
+const MIN_DESCRIPTION_CHARS: usize = 10;
+
+/// Experiences per partial network when building in parallel
+const NETWORK_CHUNK: usize = 1024;
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl ExperienceValidator {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(strict_mode: bool) -> Self {
+        Self {
+            strict_mode,
+            id_formats: None,
+            severity_overrides: HashMap::new(),
+            known_domains: None,
+            coordinate_checks: coordinate_checks::CoordinateChecks::default(),
+            locale: messages::DEFAULT_LOCALE.to_string(),
+            catalogs: messages::Catalogs::default(),
+        }
+    }
+
+    /// Whether this validator was constructed in strict mode
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn strict_mode(&self) -> bool {
+        self.strict_mode
+    }
+
+    /// Restrict experience ids to the given formats, a JSON array of
+    /// `"ulid"`, `"uuid_v4"` and `"uuid_v7"`. Once set, `validate` rejects
+    /// ids in any other format.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_id_formats(&mut self, formats_json: &str) -> Result<(), Error> {
+        let formats: Vec<ids::IdFormat> = from_json(formats_json, "formats_json")?;
+        if formats.is_empty() {
+            return Err(Error::invalid("at least one id format is required"));
+        }
+        self.id_formats = Some(formats);
+        Ok(())
+    }
+
+    /// Move individual rules between errors and warnings, a JSON object of
+    /// rule id (or rule family prefix) to `"error"` or `"warning"`, e.g.
+    /// `{"coordinates.missing": "error"}`. Replaces any earlier overrides.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_severity_overrides(&mut self, overrides_json: &str) -> Result<(), Error> {
+        let overrides: HashMap<String, Severity> = from_json(overrides_json, "overrides_json")?;
+        self.severity_overrides = severity::parse_overrides(overrides)?;
+        Ok(())
+    }
+
+    /// The domains this deployment expects, a JSON array of strings. Once
+    /// set, `validate` warns about experiences tagged with any other domain.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_known_domains(&mut self, domains_json: &str) -> Result<(), Error> {
+        let domains: Vec<String> = from_json(domains_json, "domains_json")?;
+        self.known_domains = Some(domains.into_iter().collect());
+        Ok(())
+    }
+
+    /// Enable the optional coordinate checks, a JSON object with
+    /// `null_island` (reject `(0, 0)`) and `max_decimals` (warn about more
+    /// decimal places than that). Replaces any earlier settings.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_coordinate_checks(&mut self, checks_json: &str) -> Result<(), Error> {
+        self.coordinate_checks = from_json(checks_json, "checks_json")?;
+        Ok(())
+    }
+
+    /// Load message templates for a locale, a JSON object of issue code to
+    /// template with `{name}` placeholders (see the English defaults for the
+    /// placeholders each code offers). Adds to any templates already loaded
+    /// for the locale.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn load_messages(&mut self, locale: &str, catalog_json: &str) -> Result<(), Error> {
+        let catalog: HashMap<String, String> = from_json(catalog_json, "catalog_json")?;
+        self.catalogs.load(locale, catalog)
+    }
+
+    /// Render messages in `tag` (e.g. `cy` or `es-419`) from now on; codes
+    /// without a loaded template fall back to English
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_locale(&mut self, tag: &str) -> Result<(), Error> {
+        self.locale = messages::normalize_tag(tag)?;
+        Ok(())
+    }
+
+    /// Locale messages are rendered in, lowercased
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn locale(&self) -> String {
+        self.locale.clone()
+    }
+
+    /// Whether `id` is in one of the configured formats (any of ULID,
+    /// UUIDv4 and UUIDv7 if none were configured)
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn validate_id_format(&self, id: &str) -> bool {
+        self.id_formats
+            .as_deref()
+            .unwrap_or(ids::DEFAULT_ID_FORMATS)
+            .iter()
+            .any(|format| format.matches(id))
+    }
+
+    /// Validate a learning experience JSON string
+    /// Returns validation result as JSON
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn validate(&self, json: &str) -> Result<String, Error> {
+        let result: Result<Experience, _> = serde_json::from_str(json);
+
+        match result {
+            Ok(exp) => {
+                let validation_result = self.validate_experience(&exp);
+                to_json(&validation_result)
+            }
+            Err(e) => to_json(&self.parse_failure(e)),
+        }
+    }
+
+    fn validate_experience(&self, exp: &Experience) -> ValidationResult {
+        let mut issues = Vec::new();
+
+        // Required fields
+        if exp.id.is_empty() {
+            issues.push(Issue::new("id.required"));
+        } else if self.id_formats.is_some() && !self.validate_id_format(&exp.id) {
+            issues.push(Issue::new("id.format"));
+        }
+        if exp.timestamp.is_empty() {
+            issues.push(Issue::new("timestamp.required"));
+        }
+        if exp.learner.id.is_empty() {
+            issues.push(Issue::new("learner.id.required"));
+        } else if let Err(e) = identity::LearnerId::parse(&exp.learner.id) {
+            issues.push(Issue::new("learner.id.invalid").with("reason", e));
+        }
+        if exp.context.location.name.is_empty() {
+            issues.push(Issue::new("location.name.required"));
+        }
+        if exp.experience.type_field.is_empty() {
+            issues.push(Issue::new("type.required"));
+        }
+        if exp.experience.description.is_empty() {
+            issues.push(Issue::new("description.required"));
+        } else if exp.experience.description.trim().chars().count() < MIN_DESCRIPTION_CHARS {
+            issues.push(Issue::new("description.short").with("min", MIN_DESCRIPTION_CHARS));
+        }
+        if let Some(ref access) = exp.experience.accessibility {
+            issues.extend(accessibility::validate_accessibility(access));
+        }
+        if let Some(ref measurements) = exp.experience.measurements {
+            issues.extend(units::validate_measurements(measurements));
+        }
+        if let (Some(known), Some(domains)) = (&self.known_domains, &exp.experience.domains) {
+            for domain in domains.iter().filter(|d| !known.contains(*d)) {
+                issues.push(Issue::new("domain.unknown").with("domain", domain));
+            }
+        }
+
+        // Coordinates are optional but expected
+        if let Some(ref coords) = exp.context.location.coordinates {
+            issues.extend(coordinate_checks::validate_coordinates(coords, &self.coordinate_checks));
+        } else {
+            issues.push(Issue::new("coordinates.missing"));
+        }
+
+        if let Some(ref tenant) = exp.tenant {
+            if let Err(e) = tenancy::validate_tenant_id(tenant) {
+                issues.push(Issue::new("tenant.invalid").with("reason", e));
+            }
+        }
+
+        if let Some(ref reactions) = exp.reactions {
+            issues.extend(reactions::validate_reactions(reactions));
+        }
+        if let Some(ref comments) = exp.comments {
+            issues.extend(comments::validate_comments(comments));
+        }
+
+        let issues: Vec<ValidationIssue> = issues
+            .into_iter()
+            .map(|issue| self.describe(issue.rule, severity::severity(issue.rule, &self.severity_overrides), &issue.params))
+            .collect();
+        let messages = |severity: Severity| {
+            issues.iter().filter(|i| i.severity == severity).map(|i| i.message.clone()).collect::<Vec<_>>()
+        };
+        let errors = messages(Severity::Error);
+        ValidationResult {
+            valid: errors.is_empty(),
+            errors,
+            warnings: messages(Severity::Warning),
+            issues,
+        }
+    }
+
+    fn describe(&self, code: &str, severity: Severity, params: &[(&'static str, String)]) -> ValidationIssue {
+        ValidationIssue {
+            code: code.to_string(),
+            severity,
+            message: self.catalogs.render(&self.locale, code, params),
+            params: params.iter().map(|(name, value)| (name.to_string(), value.clone())).collect(),
+        }
+    }
+
+    /// Result for input that could not be decoded as an experience
+    pub(crate) fn parse_failure(&self, reason: impl ToString) -> ValidationResult {
+        let issue = self.describe(messages::PARSE_ERROR, Severity::Error, &[("reason", reason.to_string())]);
+        ValidationResult {
+            valid: false,
+            errors: vec![issue.message.clone()],
+            warnings: Vec::new(),
+            issues: vec![issue],
+        }
+    }
+}
+
+/// High-performance domain network generation
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn generate_domain_network(experiences_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Experience> = from_json(experiences_json, "experiences_json")?;
+
+    let network = build_network(&experiences);
+
+    to_json(&network)
+}
+
+fn build_network(experiences: &[Experience]) -> DomainNetwork {
+    accumulate_network(experiences).into_network()
+}
+
+fn accumulate_network(experiences: &[Experience]) -> NetworkAccumulator {
+    let partials = parallel::map_chunks(experiences, NETWORK_CHUNK, |chunk| {
+        let mut acc = NetworkAccumulator::default();
+        for exp in chunk {
+            acc.add(exp);
+        }
+        acc
+    });
+    let mut partials = partials.into_iter();
+    let mut acc = partials.next().unwrap_or_default();
+    for partial in partials {
+        acc.merge(partial);
+    }
+    acc
+}
+
+/// Running node/edge co-occurrence counts, shared by the batch and streaming
+/// network builders
+#[derive(Default)]
+struct NetworkAccumulator {
+    nodes: std::collections::HashMap<String, usize>,
+    edges: std::collections::HashMap<(String, String), usize>,
+    /// Experiences that list domains, the denominator for PMI
+    experiences: usize,
+}
+
+impl NetworkAccumulator {
+    /// A domain listed twice in one experience still counts once, and never
+    /// links to itself
+    fn add(&mut self, exp: &Experience) {
+        if let Some(ref domains) = exp.experience.domains {
+            let mut domains: Vec<&String> = domains.iter().collect();
+            domains.sort();
+            domains.dedup();
+            self.experiences += 1;
+
+            // Count node occurrences
+            for domain in &domains {
+                *self.nodes.entry((*domain).clone()).or_insert(0) += 1;
+            }
+
+            // Count edge occurrences; sorted, so each pair is already ordered
+            for i in 0..domains.len() {
+                for j in (i + 1)..domains.len() {
+                    *self.edges.entry((domains[i].clone(), domains[j].clone())).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+
+    fn merge(&mut self, other: NetworkAccumulator) {
+        for (domain, count) in other.nodes {
+            *self.nodes.entry(domain).or_insert(0) += count;
+        }
+        for (pair, count) in other.edges {
+            *self.edges.entry(pair).or_insert(0) += count;
+        }
+        self.experiences += other.experiences;
+    }
+
+    /// Nodes sorted by id and edges by endpoints, so identical input always
+    /// serializes identically
+    fn into_network(self) -> DomainNetwork {
+        let mut network_nodes: Vec<NetworkNode> = self
+            .nodes
+            .into_iter()
+            .map(|(id, size)| NetworkNode { id, size })
+            .collect();
+        network_nodes.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut network_edges: Vec<NetworkEdge> = self
+            .edges
+            .into_iter()
+            .map(|((source, target), weight)| NetworkEdge {
+                source,
+                target,
+                weight,
+                score: None,
+            })
+            .collect();
+        network_edges.sort_by(|a, b| (&a.source, &a.target).cmp(&(&b.source, &b.target)));
+
+        DomainNetwork {
+            nodes: network_nodes,
+            edges: network_edges,
+        }
+    }
+}
+
+/// High-performance Jaccard similarity calculation
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn jaccard_similarity(set1_json: &str, set2_json: &str) -> Result<f64, Error> {
+    let set1: Vec<String> = from_json(set1_json, "set1_json")?;
+    let set2: Vec<String> = from_json(set2_json, "set2_json")?;
+
+    let set1: std::collections::HashSet<_> = set1.into_iter().collect();
+    let set2: std::collections::HashSet<_> = set2.into_iter().collect();
+
+    let intersection = set1.intersection(&set2).count();
+    let union = set1.union(&set2).count();
+
+    if union == 0 {
+        Ok(0.0)
+    } else {
+        Ok(intersection as f64 / union as f64)
+    }
+}
+
+// Data structures
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify_next::Tsify))]
+struct Experience {
+    id: String,
+    timestamp: String,
+    learner: Learner,
+    context: Context,
+    experience: ExperienceData,
+    /// Owning tenant (school / organisation) in multi-tenant deployments
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tenant: Option<String>,
+    /// Reactions from other learners on shared experiences
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reactions: Option<Vec<reactions::Reaction>>,
+    /// Threaded discussion on shared experiences
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    comments: Option<Vec<comments::Comment>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify_next::Tsify))]
+struct Learner {
+    id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify_next::Tsify))]
+struct Context {
+    location: Location,
+}
+
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify_next::Tsify))]
+struct Location {
+    name: String,
+    #[cfg_attr(feature = "wasm", tsify(optional))]
+    coordinates: Option<Coordinates>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify_next::Tsify))]
+struct Coordinates {
+    latitude: f64,
+    longitude: f64,
+    /// Horizontal accuracy radius in metres
+    #[serde(default, alias = "accuracyMeters", skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "wasm", tsify(optional))]
+    accuracy: Option<f64>,
+    /// Metres above sea level
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "wasm", tsify(optional))]
+    altitude: Option<f64>,
+    /// How the position was obtained: `gps`, `network`, `manual` or
+    /// `imputed`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "wasm", tsify(optional))]
+    source: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify_next::Tsify))]
+struct ExperienceData {
+    #[serde(rename = "type")]
+    type_field: String,
+    description: String,
+    #[cfg_attr(feature = "wasm", tsify(optional))]
+    domains: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    accessibility: Option<accessibility::Accessibility>,
+    /// Readings taken during the activity, by name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    measurements: Option<BTreeMap<String, units::Measurement>>,
+}
+
+/// Outcome of validating one experience
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify_next::Tsify), tsify(into_wasm_abi))]
+pub struct ValidationResult {
+    valid: bool,
+    errors: Vec<String>,
+    /// Problems that do not make the experience invalid
+    #[serde(default)]
+    warnings: Vec<String>,
+    /// Every error and warning with its stable code, for programmatic
+    /// handling; `errors` and `warnings` hold the same messages
+    #[serde(default)]
+    issues: Vec<ValidationIssue>,
+}
+
+/// One error or warning: `code` is the rule id (or `parse`) and never
+/// changes with the locale, `message` is rendered in the validator's locale
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify_next::Tsify))]
+struct ValidationIssue {
+    code: String,
+    severity: Severity,
+    message: String,
+    params: BTreeMap<String, String>,
+}
+
+/// Domain co-occurrence network: one node per domain, one edge per pair
+/// of domains recorded together
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify_next::Tsify), tsify(into_wasm_abi))]
+pub struct DomainNetwork {
+    nodes: Vec<NetworkNode>,
+    edges: Vec<NetworkEdge>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify_next::Tsify))]
+struct NetworkNode {
+    id: String,
+    size: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify_next::Tsify))]
+struct NetworkEdge {
+    source: String,
+    target: String,
+    weight: usize,
+    /// Normalised strength, from `generate_domain_network_with_options`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    score: Option<f64>,
+}
@@ -0,0 +1,475 @@
+//! Snapping GPS trajectories to trails
+//!
+//! [`map_match`] matches a noisy trajectory to host-supplied path geometry
+//! (GeoJSON LineStrings, e.g. a nature reserve's trail network) with the
+//! hidden Markov model of Newson & Krumm: each fix's candidates are its
+//! projections onto nearby path segments, scored by distance from the fix
+//! (Gaussian, `sigma_m`), and consecutive candidates are scored by how far
+//! the route between them along the paths differs from the straight line
+//! between the fixes (exponential, `beta_m`). Viterbi picks the most likely
+//! sequence. Paths connect where they share a vertex (to within a metre).
+//!
+//! Fixes with no segment within `search_radius_m`, or unreachable from the
+//! previous match, are left unmatched and the match restarts after them.
+//! Adherence metrics follow: the share of fixes on a path, their offsets,
+//! and how much of each path was walked.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::geo::{haversine_m, EARTH_RADIUS_M};
+use crate::geojson::{parse_features, Feature, Geometry, Position};
+use crate::numeric::{self, cos};
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct MatchParams {
+    /// GPS noise, metres
+    sigma_m: f64,
+    /// Tolerated difference between route and straight-line distance, metres
+    beta_m: f64,
+    search_radius_m: f64,
+    max_candidates: usize,
+}
+
+impl Default for MatchParams {
+    fn default() -> Self {
+        Self {
+            sigma_m: 10.0,
+            beta_m: 10.0,
+            search_radius_m: 50.0,
+            max_candidates: 8,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Fix {
+    latitude: f64,
+    longitude: f64,
+}
+
+/// Local equirectangular plane in metres around an origin
+struct Plane {
+    lat0: f64,
+    lon0: f64,
+    cos_lat: f64,
+    scale: f64,
+}
+
+impl Plane {
+    fn new(lat0: f64, lon0: f64) -> Self {
+        Self {
+            lat0,
+            lon0,
+            cos_lat: cos(lat0.to_radians()),
+            scale: EARTH_RADIUS_M * std::f64::consts::PI / 180.0,
+        }
+    }
+
+    fn project(&self, lat: f64, lon: f64) -> (f64, f64) {
+        ((lon - self.lon0) * self.cos_lat * self.scale, (lat - self.lat0) * self.scale)
+    }
+
+    fn unproject(&self, x: f64, y: f64) -> (f64, f64) {
+        (self.lat0 + y / self.scale, self.lon0 + x / (self.cos_lat * self.scale))
+    }
+}
+
+struct Segment {
+    /// Index into the path list
+    path: usize,
+    a: usize,
+    b: usize,
+    ax: f64,
+    ay: f64,
+    bx: f64,
+    by: f64,
+    length: f64,
+    /// Distance along the path at `a`
+    offset: f64,
+}
+
+/// Trail network: segments plus a vertex graph for routing
+struct Network {
+    ids: Vec<String>,
+    lengths: Vec<f64>,
+    segments: Vec<Segment>,
+    adjacency: Vec<Vec<(usize, f64)>>,
+}
+
+#[derive(Clone, Copy)]
+struct Candidate {
+    segment: usize,
+    t: f64,
+    x: f64,
+    y: f64,
+    distance: f64,
+}
+
+/// One Viterbi step: the fix, its candidates, their best log-probability
+/// and back-pointers into the previous step
+struct Layer {
+    fix: usize,
+    candidates: Vec<Candidate>,
+    scores: Vec<f64>,
+    back: Vec<Option<usize>>,
+}
+
+#[derive(PartialEq)]
+struct Visit(f64, usize);
+
+impl Eq for Visit {}
+
+impl Ord for Visit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.total_cmp(&self.0).then_with(|| other.1.cmp(&self.1))
+    }
+}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Network {
+    fn build(features: &[Feature], plane: &Plane) -> Result<Self, Error> {
+        let mut network = Network { ids: Vec::new(), lengths: Vec::new(), segments: Vec::new(), adjacency: Vec::new() };
+        let mut nodes: HashMap<(i64, i64), usize> = HashMap::new();
+        for (i, feature) in features.iter().enumerate() {
+            let lines: Vec<&Vec<Position>> = match feature.geometry {
+                Geometry::LineString(ref line) => vec![line],
+                Geometry::MultiLineString(ref lines) => lines.iter().collect(),
+                _ => return Err(Error::invalid(format!("path {} is not a LineString or MultiLineString", i)).with("index", i)),
+            };
+            let id = match (&feature.id, feature.properties.get("name").and_then(Value::as_str)) {
+                (Some(Value::String(id)), _) => id.clone(),
+                (Some(id), _) => id.to_string(),
+                (None, Some(name)) => name.to_string(),
+                (None, None) => i.to_string(),
+            };
+            let path = network.ids.len();
+            network.ids.push(id);
+            let mut offset = 0.0;
+            for line in lines {
+                let mut node = |p: &Position, adjacency: &mut Vec<Vec<(usize, f64)>>| -> (usize, f64, f64) {
+                    let (x, y) = plane.project(p[1], p[0]);
+                    let key = (x.round() as i64, y.round() as i64);
+                    let id = *nodes.entry(key).or_insert_with(|| {
+                        adjacency.push(Vec::new());
+                        adjacency.len() - 1
+                    });
+                    (id, x, y)
+                };
+                for pair in line.windows(2) {
+                    let (a, ax, ay) = node(&pair[0], &mut network.adjacency);
+                    let (b, bx, by) = node(&pair[1], &mut network.adjacency);
+                    let length = (numeric::powi(bx - ax, 2) + numeric::powi(by - ay, 2)).sqrt();
+                    network.adjacency[a].push((b, length));
+                    network.adjacency[b].push((a, length));
+                    network.segments.push(Segment { path, a, b, ax, ay, bx, by, length, offset });
+                    offset += length;
+                }
+            }
+            network.lengths.push(offset);
+        }
+        Ok(network)
+    }
+
+    fn candidates(&self, x: f64, y: f64, params: &MatchParams) -> Vec<Candidate> {
+        let mut found: Vec<Candidate> = self
+            .segments
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| {
+                let (dx, dy) = (s.bx - s.ax, s.by - s.ay);
+                let length2 = dx * dx + dy * dy;
+                let t = if length2 > 0.0 { (((x - s.ax) * dx + (y - s.ay) * dy) / length2).clamp(0.0, 1.0) } else { 0.0 };
+                let (px, py) = (s.ax + t * dx, s.ay + t * dy);
+                let distance = (numeric::powi(x - px, 2) + numeric::powi(y - py, 2)).sqrt();
+                (distance <= params.search_radius_m).then_some(Candidate { segment: i, t, x: px, y: py, distance })
+            })
+            .collect();
+        found.sort_by(|a, b| a.distance.total_cmp(&b.distance).then(a.segment.cmp(&b.segment)));
+        found.truncate(params.max_candidates);
+        found
+    }
+
+    /// Shortest distances from `source` over the vertex graph, up to `limit`
+    fn distances(&self, source: usize, limit: f64) -> HashMap<usize, f64> {
+        let mut best: HashMap<usize, f64> = HashMap::from([(source, 0.0)]);
+        let mut heap = BinaryHeap::from([Visit(0.0, source)]);
+        while let Some(Visit(d, node)) = heap.pop() {
+            if best.get(&node).is_some_and(|&b| d > b) {
+                continue;
+            }
+            for &(next, w) in &self.adjacency[node] {
+                let nd = d + w;
+                if nd <= limit && best.get(&next).is_none_or(|&b| nd < b) {
+                    best.insert(next, nd);
+                    heap.push(Visit(nd, next));
+                }
+            }
+        }
+        best
+    }
+
+    /// Distance along the network between two candidates
+    fn route(&self, from: &Candidate, to: &Candidate, limit: f64, cache: &mut HashMap<usize, HashMap<usize, f64>>) -> Option<f64> {
+        let (s1, s2) = (&self.segments[from.segment], &self.segments[to.segment]);
+        if from.segment == to.segment {
+            return Some((from.t - to.t).abs() * s1.length);
+        }
+        let mut best = f64::INFINITY;
+        for (start, lead) in [(s1.a, from.t * s1.length), (s1.b, (1.0 - from.t) * s1.length)] {
+            let reach = cache.entry(start).or_insert_with(|| self.distances(start, limit));
+            for (end, tail) in [(s2.a, to.t * s2.length), (s2.b, (1.0 - to.t) * s2.length)] {
+                if let Some(&mid) = reach.get(&end) {
+                    best = best.min(lead + mid + tail);
+                }
+            }
+        }
+        best.is_finite().then_some(best)
+    }
+}
+
+#[derive(Serialize)]
+struct MatchedPoint {
+    index: usize,
+    matched: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latitude: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    longitude: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset_m: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct PathCoverage {
+    path: String,
+    length_m: f64,
+    covered_m: f64,
+    fraction: f64,
+}
+
+#[derive(Serialize)]
+struct MatchReport {
+    points: Vec<MatchedPoint>,
+    matched_fraction: f64,
+    mean_offset_m: Option<f64>,
+    max_offset_m: Option<f64>,
+    /// Matched positions as `[longitude, latitude]`, ready for a LineString
+    matched_line: Vec<[f64; 2]>,
+    paths: Vec<PathCoverage>,
+}
+
+/// Viterbi over one run of fixes; returns the chosen candidate per fix, or
+/// `None` where the chain had to restart
+fn viterbi(network: &Network, fixes: &[(f64, f64)], params: &MatchParams) -> Vec<Option<Candidate>> {
+    let emission = |c: &Candidate| -0.5 * numeric::powi(c.distance / params.sigma_m, 2);
+    let mut chosen: Vec<Option<Candidate>> = vec![None; fixes.len()];
+    let mut layers: Vec<Layer> = Vec::new();
+
+    let finish = |layers: &mut Vec<Layer>, chosen: &mut Vec<Option<Candidate>>| {
+        let Some(Layer { scores, .. }) = layers.last() else {
+            return;
+        };
+        let mut pick = scores
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1).then(b.0.cmp(&a.0)))
+            .map(|(i, _)| i);
+        for layer in layers.iter().rev() {
+            let Some(i) = pick else {
+                break;
+            };
+            chosen[layer.fix] = Some(layer.candidates[i]);
+            pick = layer.back[i];
+        }
+        layers.clear();
+    };
+
+    for (k, &(x, y)) in fixes.iter().enumerate() {
+        let candidates = network.candidates(x, y, params);
+        if candidates.is_empty() {
+            finish(&mut layers, &mut chosen);
+            continue;
+        }
+        let Some(prev) = layers.last() else {
+            let scores = candidates.iter().map(emission).collect();
+            let back = vec![None; candidates.len()];
+            layers.push(Layer { fix: k, candidates, scores, back });
+            continue;
+        };
+        let (px, py) = fixes[prev.fix];
+        let straight = (numeric::powi(x - px, 2) + numeric::powi(y - py, 2)).sqrt();
+        let limit = straight * 3.0 + 2.0 * params.search_radius_m + 10.0 * params.beta_m;
+        let mut cache = HashMap::new();
+        let mut scores = Vec::with_capacity(candidates.len());
+        let mut back = Vec::with_capacity(candidates.len());
+        for c in &candidates {
+            let best = prev
+                .candidates
+                .iter()
+                .zip(&prev.scores)
+                .enumerate()
+                .filter_map(|(i, (p, &score))| {
+                    let route = network.route(p, c, limit, &mut cache)?;
+                    Some((i, score - (route - straight).abs() / params.beta_m))
+                })
+                .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(&a.0)));
+            match best {
+                Some((i, score)) => {
+                    scores.push(score + emission(c));
+                    back.push(Some(i));
+                }
+                None => {
+                    scores.push(f64::NEG_INFINITY);
+                    back.push(None);
+                }
+            }
+        }
+        if scores.iter().all(|s| s.is_infinite()) {
+            // Unreachable from the previous match: close that chain and start
+            // a new one here
+            finish(&mut layers, &mut chosen);
+            scores = candidates.iter().map(emission).collect();
+            back = vec![None; candidates.len()];
+        }
+        layers.push(Layer { fix: k, candidates, scores, back });
+    }
+    finish(&mut layers, &mut chosen);
+    chosen
+}
+
+/// Walked intervals along each path, merged
+fn coverage(network: &Network, chosen: &[Option<Candidate>]) -> Vec<PathCoverage> {
+    let mut intervals: BTreeMap<usize, Vec<(f64, f64)>> = BTreeMap::new();
+    for pair in chosen.windows(2) {
+        let (Some(a), Some(b)) = (pair[0], pair[1]) else {
+            continue;
+        };
+        let (sa, sb) = (&network.segments[a.segment], &network.segments[b.segment]);
+        if sa.path != sb.path {
+            continue;
+        }
+        let (pa, pb) = (sa.offset + a.t * sa.length, sb.offset + b.t * sb.length);
+        intervals.entry(sa.path).or_default().push((pa.min(pb), pa.max(pb)));
+    }
+    (0..network.ids.len())
+        .map(|path| {
+            let mut spans = intervals.remove(&path).unwrap_or_default();
+            spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut covered = Vec::new();
+            let mut current: Option<(f64, f64)> = None;
+            for (start, end) in spans {
+                current = match current {
+                    Some((s, e)) if start <= e => Some((s, e.max(end))),
+                    Some(done) => {
+                        covered.push(done.1 - done.0);
+                        Some((start, end))
+                    }
+                    None => Some((start, end)),
+                };
+            }
+            covered.extend(current.map(|(s, e)| e - s));
+            let covered_m = numeric::sum(covered);
+            let length_m = network.lengths[path];
+            PathCoverage {
+                path: network.ids[path].clone(),
+                length_m,
+                covered_m,
+                fraction: if length_m > 0.0 { (covered_m / length_m).min(1.0) } else { 0.0 },
+            }
+        })
+        .collect()
+}
+
+/// Positions of a trajectory: an array of `{latitude, longitude}` fixes or
+/// a GeoJSON LineString (bare, or as a Feature)
+fn trajectory_positions(value: &Value) -> Result<Vec<(f64, f64)>, Error> {
+    if value.is_array() {
+        let fixes: Vec<Fix> = serde_json::from_value(value.clone()).map_err(|e| Error::parse(e.to_string()).with("argument", "trajectory"))?;
+        return Ok(fixes.into_iter().map(|f| (f.latitude, f.longitude)).collect());
+    }
+    let features = parse_features(value).map_err(|e| Error::invalid(e).with("argument", "trajectory"))?;
+    match features.first().map(|f| &f.geometry) {
+        Some(Geometry::LineString(line)) => Ok(line.iter().map(|p| (p[1], p[0])).collect()),
+        _ => Err(Error::invalid("trajectory must be an array of fixes or a LineString").with("argument", "trajectory")),
+    }
+}
+
+/// Snap a GPS trajectory to trail geometry and measure route adherence
+/// `params_json` sets `sigma_m`, `beta_m`, `search_radius_m` and
+/// `max_candidates` (empty for 10, 10, 50 and 8)
+/// Returns `{points: [{index, matched, path, latitude, longitude,
+/// offset_m}], matched_fraction, mean_offset_m, max_offset_m,
+/// matched_line, paths: [{path, length_m, covered_m, fraction}]}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn map_match(trajectory: &str, paths_geojson: &str, params_json: &str) -> Result<String, Error> {
+    let trajectory: Value = from_json(trajectory, "trajectory")?;
+    let fixes = trajectory_positions(&trajectory)?;
+    let paths: Value = from_json(paths_geojson, "paths_geojson")?;
+    let features = parse_features(&paths).map_err(|e| Error::invalid(e).with("argument", "paths_geojson"))?;
+    let params: MatchParams = if params_json.trim().is_empty() {
+        MatchParams::default()
+    } else {
+        from_json(params_json, "params_json")?
+    };
+    for (name, value) in [("sigma_m", params.sigma_m), ("beta_m", params.beta_m), ("search_radius_m", params.search_radius_m)] {
+        if value.is_nan() || value <= 0.0 {
+            return Err(Error::invalid(format!("{} must be positive", name)).with("field", name));
+        }
+    }
+    if params.max_candidates == 0 {
+        return Err(Error::invalid("max_candidates must be positive"));
+    }
+    if let Some(i) = fixes.iter().position(|&(lat, lon)| !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon)) {
+        return Err(Error::invalid(format!("trajectory point {} is out of range", i)).with("index", i));
+    }
+
+    let plane = fixes.first().map_or(Plane::new(0.0, 0.0), |&(lat, lon)| Plane::new(lat, lon));
+    let network = Network::build(&features, &plane)?;
+    let projected: Vec<(f64, f64)> = fixes.iter().map(|&(lat, lon)| plane.project(lat, lon)).collect();
+    let chosen = viterbi(&network, &projected, &params);
+
+    let mut points = Vec::with_capacity(fixes.len());
+    let mut matched_line = Vec::new();
+    let mut offsets = Vec::new();
+    for (index, (c, &(lat, lon))) in chosen.iter().zip(&fixes).enumerate() {
+        match c {
+            Some(c) => {
+                let (m_lat, m_lon) = plane.unproject(c.x, c.y);
+                let offset = haversine_m(lat, lon, m_lat, m_lon);
+                offsets.push(offset);
+                matched_line.push([m_lon, m_lat]);
+                points.push(MatchedPoint {
+                    index,
+                    matched: true,
+                    path: Some(network.ids[network.segments[c.segment].path].clone()),
+                    latitude: Some(m_lat),
+                    longitude: Some(m_lon),
+                    offset_m: Some(offset),
+                });
+            }
+            None => points.push(MatchedPoint { index, matched: false, path: None, latitude: None, longitude: None, offset_m: None }),
+        }
+    }
+    to_json(&MatchReport {
+        matched_fraction: if fixes.is_empty() { 0.0 } else { offsets.len() as f64 / fixes.len() as f64 },
+        mean_offset_m: numeric::mean(&offsets),
+        max_offset_m: offsets.iter().copied().reduce(f64::max),
+        matched_line,
+        paths: coverage(&network, &chosen),
+        points,
+    })
+}
@@ -0,0 +1,124 @@
+//! Localized validation messages
+//!
+//! Every issue in a validation result carries a stable code (the rule id,
+//! or `parse` when the input could not be decoded) for programmatic
+//! handling, and a message rendered from a template for display. English
+//! templates are built in; further locales are loaded at runtime with
+//! `ExperienceValidator::load_messages` as a JSON object of code to
+//! template:
+//!
+//! ```json
+//! {"id.required": "Mae angen id", "reactions.actor.required": "Mae angen actor ar reactions[{index}]"}
+//! ```
+//!
+//! `{name}` placeholders are filled from the issue's params. A catalog may
+//! cover only some codes: lookup tries the full locale tag, then shorter
+//! prefixes (`cy-GB`, then `cy`), then English, per code. Params such as
+//! `{reason}` that quote another module's error are not translated.
+
+use std::collections::HashMap;
+
+use crate::error::Error;
+use crate::severity::RULES;
+
+/// Code of the issue reported when the input is not an experience at all
+pub(crate) const PARSE_ERROR: &str = "parse";
+const PARSE_TEMPLATE: &str = "Parse error: {reason}";
+
+/// Locale used when no other has been selected
+pub(crate) const DEFAULT_LOCALE: &str = "en";
+
+fn default_template(code: &str) -> Option<&'static str> {
+    if code == PARSE_ERROR {
+        return Some(PARSE_TEMPLATE);
+    }
+    RULES.iter().find(|(rule, _, _)| *rule == code).map(|&(_, _, template)| template)
+}
+
+/// Names of the `{name}` placeholders in a template
+fn placeholders(template: &str) -> impl Iterator<Item = &str> {
+    template.split('{').skip(1).filter_map(|part| part.split_once('}').map(|(name, _)| name))
+}
+
+fn fill(template: &str, params: &[(&str, String)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        match after.split_once('}') {
+            Some((name, tail)) => {
+                match params.iter().find(|(param, _)| *param == name) {
+                    Some((_, value)) => out.push_str(value),
+                    None => {
+                        out.push('{');
+                        out.push_str(name);
+                        out.push('}');
+                    }
+                }
+                rest = tail;
+            }
+            None => {
+                out.push_str(&rest[open..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Lowercased BCP 47 tag (`cy`, `es-419`, `en-GB`); rejects anything that
+/// is not a well-formed sequence of subtags
+pub(crate) fn normalize_tag(tag: &str) -> Result<String, Error> {
+    let invalid = || Error::invalid(format!("invalid locale tag: {}", tag)).with("locale", tag);
+    let mut subtags = tag.split('-');
+    let language = subtags.next().unwrap_or_default();
+    if !(2..=8).contains(&language.len()) || language.len() == 4 || !language.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(invalid());
+    }
+    if !subtags.all(|s| (1..=8).contains(&s.len()) && s.chars().all(|c| c.is_ascii_alphanumeric())) {
+        return Err(invalid());
+    }
+    Ok(tag.to_ascii_lowercase())
+}
+
+/// Message templates loaded at runtime, by lowercased locale tag
+#[derive(Default)]
+pub(crate) struct Catalogs {
+    locales: HashMap<String, HashMap<String, String>>,
+}
+
+impl Catalogs {
+    /// Add or replace templates for `tag`; every code must be known and
+    /// every placeholder one the English template also uses
+    pub(crate) fn load(&mut self, tag: &str, catalog: HashMap<String, String>) -> Result<(), Error> {
+        let tag = normalize_tag(tag)?;
+        for (code, template) in &catalog {
+            let default = default_template(code)
+                .ok_or_else(|| Error::invalid(format!("unknown message code: {}", code)).with("code", code.as_str()))?;
+            if let Some(name) = placeholders(template).find(|name| !placeholders(default).any(|known| known == *name)) {
+                return Err(Error::invalid(format!("unknown placeholder {{{}}} in message {}", name, code))
+                    .with("code", code.as_str())
+                    .with("placeholder", name));
+            }
+        }
+        self.locales.entry(tag).or_default().extend(catalog);
+        Ok(())
+    }
+
+    /// Message for `code` in the best available locale
+    pub(crate) fn render(&self, locale: &str, code: &str, params: &[(&str, String)]) -> String {
+        let mut tag = locale;
+        let template = loop {
+            if let Some(template) = self.locales.get(tag).and_then(|catalog| catalog.get(code)) {
+                break Some(template.as_str());
+            }
+            match tag.rfind('-') {
+                Some(end) => tag = &tag[..end],
+                None => break None,
+            }
+        };
+        fill(template.or_else(|| default_template(code)).unwrap_or(code), params)
+    }
+}
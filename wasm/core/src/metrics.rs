@@ -0,0 +1,472 @@
+//! Rolling real-time metrics for live dashboards
+//!
+//! [`MetricsWindow`] keeps per-bucket counts (one minute by default) for the
+//! longest configured window and sums the buckets a window covers when
+//! asked, so a dashboard refreshing every few seconds during a field trip
+//! costs one pass over at most a window's buckets rather than a pass over
+//! the whole dataset. Records arrive one at a time (`insert`), in bulk, or
+//! from the store's change feed (`apply_change`); late records are placed
+//! in their own bucket. Windows are exact to bucket resolution.
+//!
+//! Alongside the windows it keeps the last activity per group and the last
+//! seen position per learner, which the chaperone alerts read.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::query::{coordinates, domains};
+use crate::time::{format_timestamp, now_ms, parse_timestamp, MS_PER_HOUR, MS_PER_MINUTE, MS_PER_SECOND};
+
+const MAX_WINDOWS: usize = 16;
+
+#[derive(Deserialize, Clone)]
+struct WindowSpec {
+    name: String,
+    minutes: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct MetricsConfig {
+    windows: Vec<WindowSpec>,
+    /// Bucket width; windows are exact to this resolution
+    bucket_seconds: u32,
+    /// Dotted path of the field naming a record's group (e.g.
+    /// `metadata.group`); without it no per-group metrics are kept
+    group_path: Option<String>,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        let window = |name: &str, minutes| WindowSpec { name: name.to_string(), minutes };
+        Self {
+            windows: vec![window("hour", 60), window("day", 24 * 60), window("week", 7 * 24 * 60)],
+            bucket_seconds: 60,
+            group_path: None,
+        }
+    }
+}
+
+impl MetricsConfig {
+    fn check(&self) -> Result<(), Error> {
+        if self.windows.is_empty() || self.windows.len() > MAX_WINDOWS {
+            return Err(Error::invalid(format!("between 1 and {} windows are required", MAX_WINDOWS)));
+        }
+        if self.bucket_seconds == 0 {
+            return Err(Error::invalid("bucket_seconds must be positive"));
+        }
+        for window in &self.windows {
+            if (window.minutes as i64 * MS_PER_MINUTE) < self.bucket_ms() {
+                return Err(Error::invalid(format!("window {} is shorter than one bucket", window.name))
+                    .with("window", window.name.as_str()));
+            }
+        }
+        Ok(())
+    }
+
+    fn bucket_ms(&self) -> i64 {
+        self.bucket_seconds as i64 * MS_PER_SECOND
+    }
+
+    fn longest_ms(&self) -> i64 {
+        self.windows.iter().map(|w| w.minutes as i64 * MS_PER_MINUTE).max().unwrap_or(0)
+    }
+}
+
+/// Counts for one bucket; keys are dropped when they reach zero
+#[derive(Default)]
+struct Bucket {
+    count: u64,
+    learners: HashMap<String, u64>,
+    domains: HashMap<String, u64>,
+    groups: HashMap<String, u64>,
+}
+
+fn bump(counts: &mut HashMap<String, u64>, key: &str, add: bool) {
+    if add {
+        *counts.entry(key.to_string()).or_insert(0) += 1;
+    } else if let Some(n) = counts.get_mut(key) {
+        *n -= 1;
+        if *n == 0 {
+            counts.remove(key);
+        }
+    }
+}
+
+/// The parts of a record the metrics look at
+struct Event<'a> {
+    ms: i64,
+    learner: Option<&'a str>,
+    domains: BTreeSet<&'a str>,
+    group: Option<&'a str>,
+}
+
+struct LearnerState {
+    group: Option<String>,
+    last_seen_ms: i64,
+    position: Option<(f64, f64)>,
+}
+
+#[derive(Serialize)]
+struct WindowMetrics {
+    name: String,
+    minutes: u32,
+    count: u64,
+    rate_per_hour: f64,
+    learners: usize,
+    domains: BTreeMap<String, u64>,
+    groups: BTreeMap<String, u64>,
+}
+
+#[derive(Serialize)]
+struct GroupActivity {
+    last_activity: String,
+    learners: usize,
+}
+
+#[derive(Serialize)]
+struct LearnerActivity {
+    group: Option<String>,
+    last_seen: String,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct MetricsSnapshot {
+    as_of: String,
+    windows: Vec<WindowMetrics>,
+    groups: BTreeMap<String, GroupActivity>,
+    learners: BTreeMap<String, LearnerActivity>,
+}
+
+/// Change feed entry as emitted by `ExperienceStore.subscribe`
+#[derive(Deserialize)]
+struct Change {
+    op: String,
+    #[serde(default)]
+    record: Option<Value>,
+    #[serde(default)]
+    previous: Option<Value>,
+}
+
+/// Rolling counts and rates over hour/day/week (or configured) windows
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct MetricsWindow {
+    config: MetricsConfig,
+    buckets: BTreeMap<i64, Bucket>,
+    newest_ms: Option<i64>,
+    group_activity: BTreeMap<String, i64>,
+    learners: BTreeMap<String, LearnerState>,
+}
+
+impl MetricsWindow {
+    fn event<'a>(&self, record: &'a Value) -> Option<Event<'a>> {
+        let ms = lookup(record, "timestamp").and_then(Value::as_str).and_then(parse_timestamp)?;
+        Some(Event {
+            ms,
+            learner: lookup(record, "learner.id").and_then(Value::as_str),
+            domains: domains(record).collect(),
+            group: self.config.group_path.as_deref().and_then(|path| lookup(record, path)).and_then(Value::as_str),
+        })
+    }
+
+    /// Whether a record at `ms` is older than every window can reach
+    fn expired(&self, ms: i64) -> bool {
+        self.newest_ms.is_some_and(|newest| ms <= newest - self.config.longest_ms())
+    }
+
+    fn count(&mut self, event: &Event, add: bool) {
+        let bucket_ms = self.config.bucket_ms();
+        let start = event.ms.div_euclid(bucket_ms) * bucket_ms;
+        let bucket = self.buckets.entry(start).or_default();
+        if add {
+            bucket.count += 1;
+        } else {
+            bucket.count = bucket.count.saturating_sub(1);
+        }
+        if let Some(learner) = event.learner {
+            bump(&mut bucket.learners, learner, add);
+        }
+        for domain in &event.domains {
+            bump(&mut bucket.domains, domain, add);
+        }
+        if let Some(group) = event.group {
+            bump(&mut bucket.groups, group, add);
+        }
+        if bucket.count == 0 {
+            self.buckets.remove(&start);
+        }
+    }
+
+    fn add(&mut self, record: &Value) -> bool {
+        let Some(event) = self.event(record) else {
+            return false;
+        };
+        if self.expired(event.ms) {
+            return false;
+        }
+        self.count(&event, true);
+        if let Some(group) = event.group {
+            let last = self.group_activity.entry(group.to_string()).or_insert(event.ms);
+            *last = (*last).max(event.ms);
+        }
+        if let Some(learner) = event.learner {
+            let state = self.learners.entry(learner.to_string()).or_insert(LearnerState {
+                group: None,
+                last_seen_ms: i64::MIN,
+                position: None,
+            });
+            if event.ms >= state.last_seen_ms {
+                state.last_seen_ms = event.ms;
+                state.group = event.group.map(str::to_string).or(state.group.take());
+                state.position = coordinates(record).or(state.position);
+            }
+        }
+        if self.newest_ms.is_none_or(|newest| event.ms > newest) {
+            self.newest_ms = Some(event.ms);
+            self.prune();
+        }
+        true
+    }
+
+    fn remove(&mut self, record: &Value) {
+        if let Some(event) = self.event(record) {
+            if !self.expired(event.ms) {
+                self.count(&event, false);
+            }
+        }
+    }
+
+    /// Drop buckets, groups and learners no window can reach any more
+    fn prune(&mut self) {
+        let Some(newest) = self.newest_ms else {
+            return;
+        };
+        let cutoff = newest - self.config.longest_ms() - self.config.bucket_ms();
+        self.buckets = self.buckets.split_off(&cutoff);
+        self.group_activity.retain(|_, ms| *ms > cutoff);
+        self.learners.retain(|_, state| state.last_seen_ms > cutoff);
+    }
+
+    fn window(&self, spec: &WindowSpec, as_of: i64) -> WindowMetrics {
+        let span = spec.minutes as i64 * MS_PER_MINUTE;
+        let mut metrics = WindowMetrics {
+            name: spec.name.clone(),
+            minutes: spec.minutes,
+            count: 0,
+            rate_per_hour: 0.0,
+            learners: 0,
+            domains: BTreeMap::new(),
+            groups: BTreeMap::new(),
+        };
+        let mut learners = BTreeSet::new();
+        for (_, bucket) in self.buckets.range(as_of - span + 1..=as_of) {
+            metrics.count += bucket.count;
+            learners.extend(bucket.learners.keys());
+            for (domain, n) in &bucket.domains {
+                *metrics.domains.entry(domain.clone()).or_insert(0) += n;
+            }
+            for (group, n) in &bucket.groups {
+                *metrics.groups.entry(group.clone()).or_insert(0) += n;
+            }
+        }
+        metrics.learners = learners.len();
+        metrics.rate_per_hour = metrics.count as f64 * MS_PER_HOUR as f64 / span as f64;
+        metrics
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl MetricsWindow {
+    /// Metrics engine from `{windows: [{name, minutes}], bucket_seconds,
+    /// group_path}`; an empty string gives hour/day/week windows over
+    /// one-minute buckets with no grouping
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(config_json: &str) -> Result<MetricsWindow, Error> {
+        let config: MetricsConfig = if config_json.trim().is_empty() {
+            MetricsConfig::default()
+        } else {
+            from_json(config_json, "config_json")?
+        };
+        config.check()?;
+        Ok(Self {
+            config,
+            buckets: BTreeMap::new(),
+            newest_ms: None,
+            group_activity: BTreeMap::new(),
+            learners: BTreeMap::new(),
+        })
+    }
+
+    /// Count one experience
+    /// Returns false if it was skipped: no parseable timestamp, or older
+    /// than the longest window
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn insert(&mut self, experience_json: &str) -> Result<bool, Error> {
+        let record: Value = from_json(experience_json, "experience_json")?;
+        Ok(self.add(&record))
+    }
+
+    /// Count a JSON array of experiences
+    /// Returns the number counted
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn insert_many(&mut self, experiences_json: &str) -> Result<usize, Error> {
+        let records: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+        Ok(records.iter().filter(|record| self.add(record)).count())
+    }
+
+    /// Apply one change feed entry: `{op: "put", record, previous}` counts
+    /// `record` and uncounts `previous`; `{op: "delete", previous}` uncounts.
+    /// Last-seen times and positions are not rolled back.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn apply_change(&mut self, change_json: &str) -> Result<(), Error> {
+        let change: Change = from_json(change_json, "change_json")?;
+        let previous = change.previous.filter(|v| !v.is_null());
+        match change.op.as_str() {
+            "put" => {
+                let record = change
+                    .record
+                    .filter(|v| v.is_object())
+                    .ok_or_else(|| Error::invalid("put change needs a record object"))?;
+                if let Some(previous) = previous {
+                    self.remove(&previous);
+                }
+                self.add(&record);
+            }
+            "delete" => {
+                if let Some(previous) = previous {
+                    self.remove(&previous);
+                }
+            }
+            other => {
+                return Err(Error::invalid(format!("unknown change op: {} (expected put or delete)", other)).with("op", other))
+            }
+        }
+        Ok(())
+    }
+
+    /// Metrics for every window ending at `as_of` (an RFC 3339 timestamp;
+    /// empty for now)
+    /// Returns `{as_of, windows: [{name, minutes, count, rate_per_hour,
+    /// learners, domains, groups}], groups: {name: {last_activity,
+    /// learners}}, learners: {id: {group, last_seen, latitude, longitude}}}`
+    /// as JSON
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn snapshot(&self, as_of: &str) -> Result<String, Error> {
+        let as_of = if as_of.trim().is_empty() {
+            now_ms()
+        } else {
+            parse_timestamp(as_of).ok_or_else(|| Error::invalid(format!("invalid as_of: {}", as_of)).with("as_of", as_of))?
+        };
+        let mut group_learners: HashMap<&str, usize> = HashMap::new();
+        for state in self.learners.values() {
+            if let Some(ref group) = state.group {
+                *group_learners.entry(group).or_insert(0) += 1;
+            }
+        }
+        to_json(&MetricsSnapshot {
+            as_of: format_timestamp(as_of),
+            windows: self.config.windows.iter().map(|spec| self.window(spec, as_of)).collect(),
+            groups: self
+                .group_activity
+                .iter()
+                .map(|(group, &ms)| {
+                    let activity = GroupActivity {
+                        last_activity: format_timestamp(ms),
+                        learners: group_learners.get(group.as_str()).copied().unwrap_or(0),
+                    };
+                    (group.clone(), activity)
+                })
+                .collect(),
+            learners: self
+                .learners
+                .iter()
+                .map(|(id, state)| {
+                    let activity = LearnerActivity {
+                        group: state.group.clone(),
+                        last_seen: format_timestamp(state.last_seen_ms),
+                        latitude: state.position.map(|(lat, _)| lat),
+                        longitude: state.position.map(|(_, lon)| lon),
+                    };
+                    (id.clone(), activity)
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record(id: &str, learner: &str, timestamp: &str) -> Value {
+        json!({"id": id, "timestamp": timestamp, "learner": {"id": learner}, "experience": {"domains": ["art"]}})
+    }
+
+    fn snapshot(metrics: &MetricsWindow, as_of: &str) -> Value {
+        serde_json::from_str(&metrics.snapshot(as_of).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn windows_cover_the_buckets_starting_inside_them() {
+        let config = r#"{"windows": [{"name": "hour", "minutes": 60}, {"name": "day", "minutes": 1440}]}"#;
+        let mut metrics = MetricsWindow::new(config).unwrap();
+        let records = json!([
+            record("a", "ada", "2024-05-01T11:00:59Z"),
+            record("b", "bo", "2024-05-01T11:01:00Z"),
+            record("c", "bo", "2024-05-01T12:00:30Z"),
+            record("d", "cy", "2024-05-01T12:00:59.999Z"),
+            record("e", "ed", "2024-05-01T12:01:00Z"),
+        ]);
+        assert_eq!(metrics.insert_many(&records.to_string()).unwrap(), 5);
+
+        // (11:00:30, 12:00:30] at minute resolution: the buckets from 11:01
+        // to 12:00, so the 11:00 bucket is out and all of 12:00 is in
+        let hour = &snapshot(&metrics, "2024-05-01T12:00:30Z")["windows"][0];
+        assert_eq!(hour["count"], 3);
+        assert_eq!(hour["learners"], 2);
+        assert_eq!(hour["rate_per_hour"], 3.0);
+        assert_eq!(hour["domains"], json!({"art": 3}));
+        // A minute on, the 11:01 bucket leaves as the 12:01 one arrives
+        let later = &snapshot(&metrics, "2024-05-01T12:01:00Z")["windows"][0];
+        assert_eq!((later["count"].as_u64(), later["learners"].as_u64()), (Some(3), Some(3)));
+        assert_eq!(snapshot(&metrics, "2024-05-01T12:00:30Z")["windows"][1]["count"], 4);
+    }
+
+    #[test]
+    fn records_older_than_the_longest_window_are_skipped() {
+        let mut metrics = MetricsWindow::new(r#"{"windows": [{"name": "hour", "minutes": 60}]}"#).unwrap();
+        assert!(metrics.insert(&record("a", "ada", "2024-05-01T12:00:00Z").to_string()).unwrap());
+        assert!(!metrics.insert(&record("b", "ada", "2024-05-01T11:00:00Z").to_string()).unwrap());
+        assert!(metrics.insert(&record("c", "ada", "2024-05-01T11:00:00.001Z").to_string()).unwrap());
+        assert!(!metrics.insert(&json!({"id": "d", "timestamp": "later"}).to_string()).unwrap());
+    }
+
+    #[test]
+    fn change_feed_moves_counts() {
+        let mut metrics = MetricsWindow::new("").unwrap();
+        let (old, new) = (record("a", "ada", "2024-05-01T11:00:00Z"), record("a", "bo", "2024-05-01T11:30:00Z"));
+        metrics.apply_change(&json!({"op": "put", "record": old}).to_string()).unwrap();
+        metrics.apply_change(&json!({"op": "put", "record": new, "previous": old}).to_string()).unwrap();
+        let hour = &snapshot(&metrics, "2024-05-01T11:45:00Z")["windows"][0];
+        assert_eq!((hour["count"].as_u64(), hour["learners"].as_u64()), (Some(1), Some(1)));
+
+        metrics.apply_change(&json!({"op": "delete", "previous": new}).to_string()).unwrap();
+        assert_eq!(snapshot(&metrics, "2024-05-01T11:45:00Z")["windows"][0]["count"], 0);
+        assert!(metrics.apply_change(r#"{"op": "patch"}"#).is_err());
+    }
+
+    #[test]
+    fn rejects_windows_shorter_than_a_bucket() {
+        assert!(MetricsWindow::new(r#"{"windows": [{"name": "m", "minutes": 1}], "bucket_seconds": 120}"#).is_err());
+        assert!(MetricsWindow::new(r#"{"windows": [{"name": "m", "minutes": 2}], "bucket_seconds": 120}"#).is_ok());
+        assert!(MetricsWindow::new(r#"{"windows": []}"#).is_err());
+    }
+}
@@ -0,0 +1,287 @@
+//! Mapbox Vector Tiles of experience layers
+//!
+//! A map drawing 50k experiences as GeoJSON parses and styles every point
+//! on the main thread at every zoom. [`to_mvt`] cuts one `z/x/y` tile in a
+//! worker instead, as the MVT 2.1 protobuf that MapLibre and Mapbox GL load
+//! directly through a custom protocol or tile URL:
+//!
+//! - an array of experiences becomes an `experiences` layer of points with
+//!   `id`, `type`, `timestamp` and `domains` (comma-separated) properties;
+//! - a `heatmap` result becomes a `heatmap` layer of cell squares with
+//!   `count` and `intensity`, clipped to the tile plus a 64-pixel buffer so
+//!   cells stitch across tile edges.
+//!
+//! Tiles use the standard Web Mercator grid and a 4096-unit extent. A tile
+//! with nothing in it is empty (zero bytes), which renderers accept.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, Error};
+use crate::export::lookup;
+use crate::numeric::{cos, ln, tan};
+use crate::query::{coordinates, domains};
+
+const EXTENT: u32 = 4096;
+/// Pixels beyond the tile edge that clipped polygons may reach
+const BUFFER: f64 = 64.0;
+const MAX_ZOOM: u32 = 24;
+/// Web Mercator's latitude limit
+const MAX_LATITUDE: f64 = 85.051_128_779_806_59;
+
+const POINT: u64 = 1;
+const POLYGON: u64 = 3;
+const MOVE_TO: u32 = 1;
+const LINE_TO: u32 = 2;
+const CLOSE_PATH: u32 = 7;
+
+/// Protocol Buffers writer for the wire types MVT uses
+#[derive(Default)]
+struct Proto {
+    out: Vec<u8>,
+}
+
+impl Proto {
+    fn varint(&mut self, mut v: u64) {
+        while v >= 0x80 {
+            self.out.push(v as u8 | 0x80);
+            v >>= 7;
+        }
+        self.out.push(v as u8);
+    }
+
+    fn key(&mut self, field: u32, wire_type: u8) {
+        self.varint(u64::from(field << 3 | u32::from(wire_type)));
+    }
+
+    fn uint(&mut self, field: u32, v: u64) {
+        self.key(field, 0);
+        self.varint(v);
+    }
+
+    fn bytes(&mut self, field: u32, v: &[u8]) {
+        self.key(field, 2);
+        self.varint(v.len() as u64);
+        self.out.extend_from_slice(v);
+    }
+
+    fn double(&mut self, field: u32, v: f64) {
+        self.key(field, 1);
+        self.out.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn packed(&mut self, field: u32, values: &[u32]) {
+        let mut inner = Proto::default();
+        for &v in values {
+            inner.varint(u64::from(v));
+        }
+        self.bytes(field, &inner.out);
+    }
+}
+
+fn command(id: u32, count: u32) -> u32 {
+    id & 0x7 | count << 3
+}
+
+fn zigzag(v: i64) -> u32 {
+    ((v << 1) ^ (v >> 63)) as u32
+}
+
+/// Property value, floats by their bits so equal values share an entry
+#[derive(PartialEq, Eq, Hash)]
+enum Tag {
+    Text(String),
+    Double(u64),
+    Int(i64),
+}
+
+/// One layer's features with its shared key and value tables
+struct Layer {
+    name: &'static str,
+    features: Vec<Vec<u8>>,
+    keys: Vec<&'static str>,
+    values: Vec<Tag>,
+    value_index: HashMap<Tag, u32>,
+}
+
+impl Layer {
+    fn new(name: &'static str) -> Self {
+        Layer { name, features: Vec::new(), keys: Vec::new(), values: Vec::new(), value_index: HashMap::new() }
+    }
+
+    fn add(&mut self, geom_type: u64, geometry: &[u32], properties: Vec<(&'static str, Tag)>) {
+        let mut tags = Vec::with_capacity(properties.len() * 2);
+        for (key, value) in properties {
+            let key_id = match self.keys.iter().position(|k| *k == key) {
+                Some(i) => i,
+                None => {
+                    self.keys.push(key);
+                    self.keys.len() - 1
+                }
+            };
+            let next = self.values.len() as u32;
+            let value_id = match self.value_index.get(&value) {
+                Some(&id) => id,
+                None => {
+                    self.value_index.insert(clone_tag(&value), next);
+                    self.values.push(value);
+                    next
+                }
+            };
+            tags.extend([key_id as u32, value_id]);
+        }
+        let mut feature = Proto::default();
+        feature.packed(2, &tags);
+        feature.uint(3, geom_type);
+        feature.packed(4, geometry);
+        self.features.push(feature.out);
+    }
+
+    fn encode(self, tile: &mut Proto) {
+        if self.features.is_empty() {
+            return;
+        }
+        let mut layer = Proto::default();
+        layer.uint(15, 2);
+        layer.bytes(1, self.name.as_bytes());
+        for feature in &self.features {
+            layer.bytes(2, feature);
+        }
+        for key in &self.keys {
+            layer.bytes(3, key.as_bytes());
+        }
+        for value in &self.values {
+            let mut encoded = Proto::default();
+            match value {
+                Tag::Text(s) => encoded.bytes(1, s.as_bytes()),
+                Tag::Double(bits) => encoded.double(3, f64::from_bits(*bits)),
+                Tag::Int(i) => encoded.uint(4, *i as u64),
+            }
+            layer.bytes(4, &encoded.out);
+        }
+        layer.uint(5, u64::from(EXTENT));
+        tile.bytes(3, &layer.out);
+    }
+}
+
+fn clone_tag(tag: &Tag) -> Tag {
+    match tag {
+        Tag::Text(s) => Tag::Text(s.clone()),
+        Tag::Double(bits) => Tag::Double(*bits),
+        Tag::Int(i) => Tag::Int(*i),
+    }
+}
+
+/// Web Mercator position of a point in tile units
+struct TileGrid {
+    scale: f64,
+    x: f64,
+    y: f64,
+}
+
+impl TileGrid {
+    fn project(&self, lat: f64, lon: f64) -> (f64, f64) {
+        let phi = lat.clamp(-MAX_LATITUDE, MAX_LATITUDE).to_radians();
+        let world_x = (lon + 180.0) / 360.0;
+        let world_y = (1.0 - ln(tan(phi) + 1.0 / cos(phi)) / PI) / 2.0;
+        ((world_x * self.scale - self.x) * f64::from(EXTENT), (world_y * self.scale - self.y) * f64::from(EXTENT))
+    }
+}
+
+fn experience_layer(experiences: &[Value], grid: &TileGrid) -> Layer {
+    let mut layer = Layer::new("experiences");
+    for exp in experiences {
+        let Some((lat, lon)) = coordinates(exp) else { continue };
+        let (px, py) = grid.project(lat, lon);
+        let (px, py) = (px.floor(), py.floor());
+        if !(0.0..f64::from(EXTENT)).contains(&px) || !(0.0..f64::from(EXTENT)).contains(&py) {
+            continue;
+        }
+        let mut properties = Vec::new();
+        for (key, path) in [("id", "id"), ("type", "experience.type"), ("timestamp", "timestamp")] {
+            if let Some(s) = lookup(exp, path).and_then(Value::as_str) {
+                properties.push((key, Tag::Text(s.to_string())));
+            }
+        }
+        let domains: Vec<&str> = domains(exp).collect();
+        if !domains.is_empty() {
+            properties.push(("domains", Tag::Text(domains.join(","))));
+        }
+        layer.add(POINT, &[command(MOVE_TO, 1), zigzag(px as i64), zigzag(py as i64)], properties);
+    }
+    layer
+}
+
+fn heatmap_layer(cells: &[Value], grid: &TileGrid) -> Result<Layer, Error> {
+    let mut layer = Layer::new("heatmap");
+    let (low, high) = (-BUFFER, f64::from(EXTENT) + BUFFER);
+    for (index, cell) in cells.iter().enumerate() {
+        let bounds: Option<Vec<f64>> =
+            cell.get("bounds").and_then(Value::as_array).map(|b| b.iter().filter_map(Value::as_f64).collect());
+        let Some(&[west, south, east, north]) = bounds.as_deref() else {
+            return Err(Error::invalid(format!("cell {} needs bounds [west, south, east, north]", index)).with("cell", index));
+        };
+        let (left, top) = grid.project(north, west);
+        let (right, bottom) = grid.project(south, east);
+        if right < low || left > high || bottom < low || top > high {
+            continue;
+        }
+        let clip = |v: f64| v.clamp(low, high).round() as i64;
+        let (left, top, right, bottom) = (clip(left), clip(top), clip(right), clip(bottom));
+        if right <= left || bottom <= top {
+            continue;
+        }
+        // Clockwise on screen, which MVT takes as an exterior ring
+        let geometry = [
+            command(MOVE_TO, 1),
+            zigzag(left),
+            zigzag(top),
+            command(LINE_TO, 3),
+            zigzag(right - left),
+            0,
+            0,
+            zigzag(bottom - top),
+            zigzag(left - right),
+            0,
+            command(CLOSE_PATH, 1),
+        ];
+        let mut properties = Vec::new();
+        if let Some(count) = cell.get("count").and_then(Value::as_i64) {
+            properties.push(("count", Tag::Int(count)));
+        }
+        if let Some(intensity) = cell.get("intensity").and_then(Value::as_f64) {
+            properties.push(("intensity", Tag::Double(intensity.to_bits())));
+        }
+        layer.add(POLYGON, &geometry, properties);
+    }
+    Ok(layer)
+}
+
+/// Encode experience points, or the cells of a `heatmap` result, as the
+/// Mapbox Vector Tile `z/x/y` (zoom 0-24)
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn to_mvt(experiences_or_grid_json: &str, z: u32, x: u32, y: u32) -> Result<Vec<u8>, Error> {
+    if z > MAX_ZOOM {
+        return Err(Error::invalid(format!("zoom must be at most {}", MAX_ZOOM)).with("z", z));
+    }
+    let tiles = 1u64 << z;
+    if u64::from(x) >= tiles || u64::from(y) >= tiles {
+        return Err(Error::invalid(format!("tile {}/{}/{} is outside the grid", z, x, y)).with("z", z));
+    }
+    let input: Value = from_json(experiences_or_grid_json, "experiences_or_grid_json")?;
+    let grid = TileGrid { scale: tiles as f64, x: f64::from(x), y: f64::from(y) };
+    let layer = match &input {
+        Value::Array(experiences) => experience_layer(experiences, &grid),
+        Value::Object(object) => match object.get("cells").and_then(Value::as_array) {
+            Some(cells) => heatmap_layer(cells, &grid)?,
+            None => return Err(Error::invalid("expected an array of experiences or a heatmap with cells")),
+        },
+        _ => return Err(Error::invalid("expected an array of experiences or a heatmap with cells")),
+    };
+    let mut tile = Proto::default();
+    layer.encode(&mut tile);
+    Ok(tile.out)
+}
@@ -0,0 +1,238 @@
+//! Learner-facing narrative summaries at a chosen reading level
+//!
+//! `narrate_learning` turns a learner's experiences into a few plain
+//! sentences. The reading level (`child`, `teen` or `adult`) picks both the
+//! sentence templates and the vocabulary used for experience types, so every
+//! client gets age-appropriate wording from the same call:
+//!
+//! ```json
+//! {"reading_level": "child", "sentences": [
+//!   "You went on 4 learning adventures!",
+//!   "You learned the most about plants, birds and maps.",
+//!   "You visited 3 different places."
+//! ]}
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::query::domains;
+use crate::time::parse_timestamp;
+
+/// Most frequent domains named in the summary
+const TOP_DOMAINS: usize = 3;
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum ReadingLevel {
+    Child,
+    Teen,
+    Adult,
+}
+
+#[derive(Serialize)]
+struct Narrative {
+    reading_level: ReadingLevel,
+    sentences: Vec<String>,
+    text: String,
+}
+
+/// Facts the templates draw on
+#[derive(Default)]
+struct Facts {
+    count: usize,
+    days: usize,
+    top_domains: Vec<String>,
+    top_type: Option<String>,
+    places: usize,
+    connections: usize,
+    failures: usize,
+}
+
+fn collect_facts(experiences: &[Value]) -> Facts {
+    let mut domain_counts: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut type_counts: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut places = BTreeSet::new();
+    let mut days = BTreeSet::new();
+    let mut facts = Facts { count: experiences.len(), ..Facts::default() };
+
+    for exp in experiences {
+        let exp_domains: BTreeSet<&str> = domains(exp).collect();
+        if exp_domains.len() > 1 {
+            facts.connections += 1;
+        }
+        for d in exp_domains {
+            *domain_counts.entry(d).or_insert(0) += 1;
+        }
+        if let Some(t) = lookup(exp, "experience.type").and_then(Value::as_str) {
+            *type_counts.entry(t).or_insert(0) += 1;
+            if t == "failure" {
+                facts.failures += 1;
+            }
+        }
+        if let Some(name) = lookup(exp, "context.location.name").and_then(Value::as_str) {
+            places.insert(name.trim().to_lowercase());
+        }
+        if let Some(ms) = exp.get("timestamp").and_then(Value::as_str).and_then(parse_timestamp) {
+            days.insert(ms.div_euclid(86_400_000));
+        }
+    }
+
+    let mut ranked: Vec<(&str, usize)> = domain_counts.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    facts.top_domains = ranked.into_iter().take(TOP_DOMAINS).map(|(d, _)| d.replace('_', " ")).collect();
+    facts.top_type = type_counts
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+        .map(|(t, _)| t.to_string());
+    facts.places = places.len();
+    facts.days = days.len();
+    facts
+}
+
+/// "a", "a and b", "a, b and c"
+fn join_list(items: &[String]) -> String {
+    match items {
+        [] => String::new(),
+        [one] => one.clone(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+fn plural(n: usize, one: &str, many: &str) -> String {
+    format!("{} {}", n, if n == 1 { one } else { many })
+}
+
+impl ReadingLevel {
+    /// Level-appropriate wording for an experience type
+    fn type_phrase(self, experience_type: &str) -> &'static str {
+        match (self, experience_type) {
+            (ReadingLevel::Child, "observation") => "looking closely at things",
+            (ReadingLevel::Child, "experiment") => "trying things out",
+            (ReadingLevel::Child, "failure") => "trying again when things went wrong",
+            (ReadingLevel::Child, "discovery") => "finding new things",
+            (ReadingLevel::Child, "collaboration") => "working with others",
+            (ReadingLevel::Child, "insight") => "having big ideas",
+            (ReadingLevel::Child, "question") => "asking questions",
+            (ReadingLevel::Child, "practice") => "practising",
+            (ReadingLevel::Child, "reflection") => "thinking about what you learned",
+            (ReadingLevel::Teen, "observation") => "observing",
+            (ReadingLevel::Teen, "experiment") => "experimenting",
+            (ReadingLevel::Teen, "failure") => "learning from things that didn't work",
+            (ReadingLevel::Teen, "discovery") => "discovering new things",
+            (ReadingLevel::Teen, "collaboration") => "working with other people",
+            (ReadingLevel::Teen, "insight") => "having insights",
+            (ReadingLevel::Teen, "question") => "asking questions",
+            (ReadingLevel::Teen, "practice") => "practising skills",
+            (ReadingLevel::Teen, "reflection") => "reflecting",
+            (ReadingLevel::Adult, "observation") => "observation",
+            (ReadingLevel::Adult, "experiment") => "experimentation",
+            (ReadingLevel::Adult, "failure") => "productive failure",
+            (ReadingLevel::Adult, "discovery") => "discovery",
+            (ReadingLevel::Adult, "collaboration") => "collaboration",
+            (ReadingLevel::Adult, "insight") => "insight",
+            (ReadingLevel::Adult, "question") => "inquiry",
+            (ReadingLevel::Adult, "practice") => "deliberate practice",
+            (ReadingLevel::Adult, "reflection") => "reflection",
+            (ReadingLevel::Child, _) => "exploring",
+            (ReadingLevel::Teen, _) => "exploring",
+            (ReadingLevel::Adult, _) => "exploration",
+        }
+    }
+
+    fn sentences(self, facts: &Facts) -> Vec<String> {
+        if facts.count == 0 {
+            return vec![match self {
+                ReadingLevel::Child => "You have not saved any learning adventures yet.".to_string(),
+                ReadingLevel::Teen => "You haven't recorded any learning experiences yet.".to_string(),
+                ReadingLevel::Adult => "No learning experiences have been recorded yet.".to_string(),
+            }];
+        }
+
+        let mut out = vec![match self {
+            ReadingLevel::Child => format!("You went on {}!", plural(facts.count, "learning adventure", "learning adventures")),
+            ReadingLevel::Teen => format!(
+                "You recorded {} over {}.",
+                plural(facts.count, "learning experience", "learning experiences"),
+                plural(facts.days, "day", "days")
+            ),
+            ReadingLevel::Adult => format!(
+                "You recorded {} across {}.",
+                plural(facts.count, "learning experience", "learning experiences"),
+                plural(facts.days, "distinct day", "distinct days")
+            ),
+        }];
+
+        if !facts.top_domains.is_empty() {
+            let list = join_list(&facts.top_domains);
+            out.push(match self {
+                ReadingLevel::Child => format!("You learned the most about {}.", list),
+                ReadingLevel::Teen => format!("Your main topics were {}.", list),
+                ReadingLevel::Adult => format!("Your most frequent domains were {}.", list),
+            });
+        }
+
+        if let Some(t) = &facts.top_type {
+            let phrase = self.type_phrase(t);
+            out.push(match self {
+                ReadingLevel::Child => format!("You spent lots of time {}.", phrase),
+                ReadingLevel::Teen => format!("Most of the time you were {}.", phrase),
+                ReadingLevel::Adult => format!("Your learning was characterised mostly by {}.", phrase),
+            });
+        }
+
+        if facts.places > 0 {
+            out.push(match self {
+                ReadingLevel::Child => format!("You visited {}.", plural(facts.places, "place", "different places")),
+                ReadingLevel::Teen => format!("You learned in {}.", plural(facts.places, "place", "different places")),
+                ReadingLevel::Adult => format!("These experiences took place in {}.", plural(facts.places, "location", "distinct locations")),
+            });
+        }
+
+        if facts.connections > 0 {
+            out.push(match self {
+                ReadingLevel::Child => "Sometimes you mixed different subjects together, which is a great way to learn!".to_string(),
+                ReadingLevel::Teen => format!("{} connected more than one topic.", plural(facts.connections, "experience", "experiences")),
+                ReadingLevel::Adult => format!(
+                    "{} spanned more than one domain, suggesting interdisciplinary connections.",
+                    plural(facts.connections, "experience", "experiences")
+                ),
+            });
+        }
+
+        if facts.failures > 0 {
+            out.push(match self {
+                ReadingLevel::Child => "When something didn't work, you kept going. Well done!".to_string(),
+                ReadingLevel::Teen => "Some things didn't go to plan, and that's part of learning.".to_string(),
+                ReadingLevel::Adult if facts.failures == 1 => {
+                    "One experience was recorded as a failure, which is valuable evidence of experimentation.".to_string()
+                }
+                ReadingLevel::Adult => format!(
+                    "{} experiences were recorded as failures, which are valuable evidence of experimentation.",
+                    facts.failures
+                ),
+            });
+        }
+
+        out
+    }
+}
+
+/// Summarise experiences as learner-facing prose at a reading level
+/// (`child`, `teen` or `adult`)
+/// Returns `{reading_level, sentences, text}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn narrate_learning(experiences_json: &str, reading_level: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let reading_level: ReadingLevel = serde_json::from_value(Value::String(reading_level.to_string()))
+        .map_err(|_| Error::invalid(format!("unknown reading level: {}", reading_level)))?;
+
+    let sentences = reading_level.sentences(&collect_facts(&experiences));
+    let narrative = Narrative { reading_level, text: sentences.join(" "), sentences };
+    to_json(&narrative)
+}
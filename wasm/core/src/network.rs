@@ -0,0 +1,369 @@
+//! Path and connectivity queries over a domain network
+//!
+//! Both exports take the network JSON produced by
+//! [`crate::generate_domain_network`]. Edge weights count co-occurrences, so
+//! [`shortest_path`] treats `1 / weight` as the length of an edge: the path
+//! it returns between two domains runs through the strongest links, which
+//! answers "how is astronomy connected to cooking in my learning?".
+//! [`network_metrics`] reports the topology measures used to compare
+//! learners' knowledge graphs: clustering, path length, density and degree
+//! assortativity. These are unweighted, matching their textbook definitions.
+
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, BinaryHeap, HashMap, VecDeque};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::DomainNetwork;
+use crate::error::{from_json, to_json, Error};
+use crate::numeric;
+use crate::parallel;
+
+/// Adjacency-list view of a domain network
+pub(crate) struct Graph<'a> {
+    pub(crate) ids: Vec<&'a str>,
+    pub(crate) index: HashMap<&'a str, usize>,
+    /// Neighbours with co-occurrence weight, zero-weight edges dropped
+    pub(crate) adjacency: Vec<Vec<(usize, f64)>>,
+}
+
+impl<'a> Graph<'a> {
+    pub(crate) fn new(network: &'a DomainNetwork) -> Result<Self, String> {
+        let ids: Vec<&str> = network.nodes.iter().map(|n| n.id.as_str()).collect();
+        let index: HashMap<&str, usize> = ids.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+        let mut adjacency = vec![Vec::new(); ids.len()];
+        for edge in &network.edges {
+            let node = |id: &str| index.get(id).copied().ok_or_else(|| format!("edge refers to unknown node {}", id));
+            let (a, b) = (node(&edge.source)?, node(&edge.target)?);
+            if edge.weight > 0 && a != b {
+                adjacency[a].push((b, edge.weight as f64));
+                adjacency[b].push((a, edge.weight as f64));
+            }
+        }
+        Ok(Graph { ids, index, adjacency })
+    }
+
+    pub(crate) fn node(&self, id: &str) -> Result<usize, Error> {
+        self.index
+            .get(id)
+            .copied()
+            .ok_or_else(|| Error::not_found(format!("unknown domain: {}", id)).with("domain", id))
+    }
+}
+
+/// Connected component of each node, numbered largest component first;
+/// ties go to the component whose first node comes first
+pub(crate) fn component_groups(network: &DomainNetwork) -> Vec<usize> {
+    let index: HashMap<&str, usize> = network.nodes.iter().enumerate().map(|(i, n)| (n.id.as_str(), i)).collect();
+    let mut parent: Vec<usize> = (0..network.nodes.len()).collect();
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for edge in &network.edges {
+        if let (Some(&a), Some(&b)) = (index.get(edge.source.as_str()), index.get(edge.target.as_str())) {
+            let (ra, rb) = (find(&mut parent, a), find(&mut parent, b));
+            parent[ra.max(rb)] = ra.min(rb);
+        }
+    }
+
+    let roots: Vec<usize> = (0..parent.len()).map(|i| find(&mut parent, i)).collect();
+    let mut sizes: HashMap<usize, usize> = HashMap::new();
+    for &root in &roots {
+        *sizes.entry(root).or_insert(0) += 1;
+    }
+    let mut order: Vec<(usize, usize)> = sizes.into_iter().collect();
+    order.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let group: HashMap<usize, usize> = order.into_iter().enumerate().map(|(g, (root, _))| (root, g)).collect();
+    roots.iter().map(|root| group[root]).collect()
+}
+
+/// Heap entry ordered by smallest distance first
+struct Frontier {
+    distance: f64,
+    node: usize,
+}
+
+impl PartialEq for Frontier {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Frontier {}
+
+impl PartialOrd for Frontier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Frontier {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance.total_cmp(&self.distance).then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+/// Dijkstra over `1 / weight` edge lengths from `source`
+/// Returns distances and predecessors for every node
+pub(crate) fn dijkstra(graph: &Graph, source: usize) -> (Vec<f64>, Vec<Option<usize>>) {
+    let n = graph.ids.len();
+    let mut distance = vec![f64::INFINITY; n];
+    let mut previous = vec![None; n];
+    let mut heap = BinaryHeap::new();
+    distance[source] = 0.0;
+    heap.push(Frontier { distance: 0.0, node: source });
+
+    while let Some(Frontier { distance: d, node }) = heap.pop() {
+        if d > distance[node] {
+            continue;
+        }
+        for &(next, weight) in &graph.adjacency[node] {
+            let candidate = d + 1.0 / weight;
+            if candidate < distance[next] {
+                distance[next] = candidate;
+                previous[next] = Some(node);
+                heap.push(Frontier { distance: candidate, node: next });
+            }
+        }
+    }
+    (distance, previous)
+}
+
+#[derive(Serialize)]
+struct PathStep {
+    source: String,
+    target: String,
+    weight: f64,
+}
+
+#[derive(Serialize)]
+struct PathResult {
+    found: bool,
+    path: Vec<String>,
+    hops: usize,
+    cost: Option<f64>,
+    edges: Vec<PathStep>,
+}
+
+fn find_path(network: &DomainNetwork, from: &str, to: &str) -> Result<PathResult, Error> {
+    let graph = Graph::new(network).map_err(Error::invalid)?;
+    let (source, target) = (graph.node(from)?, graph.node(to)?);
+    let (distance, previous) = dijkstra(&graph, source);
+    if distance[target].is_infinite() {
+        return Ok(PathResult { found: false, path: Vec::new(), hops: 0, cost: None, edges: Vec::new() });
+    }
+
+    let mut nodes = vec![target];
+    let mut current = target;
+    while let Some(p) = previous[current] {
+        nodes.push(p);
+        current = p;
+    }
+    nodes.reverse();
+
+    let edges = nodes
+        .windows(2)
+        .map(|pair| PathStep {
+            source: graph.ids[pair[0]].to_string(),
+            target: graph.ids[pair[1]].to_string(),
+            weight: graph.adjacency[pair[0]]
+                .iter()
+                .filter(|&&(next, _)| next == pair[1])
+                .map(|&(_, w)| w)
+                .fold(0.0, f64::max),
+        })
+        .collect();
+    Ok(PathResult {
+        found: true,
+        hops: nodes.len() - 1,
+        path: nodes.iter().map(|&i| graph.ids[i].to_string()).collect(),
+        cost: Some(distance[target]),
+        edges,
+    })
+}
+
+#[derive(Serialize)]
+struct Component {
+    size: usize,
+    nodes: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Components {
+    count: usize,
+    components: Vec<Component>,
+}
+
+fn components(network: &DomainNetwork) -> Components {
+    let groups = component_groups(network);
+    let mut components: Vec<Component> = Vec::new();
+    for (node, group) in network.nodes.iter().zip(groups) {
+        if components.len() <= group {
+            components.resize_with(group + 1, || Component { size: 0, nodes: Vec::new() });
+        }
+        components[group].size += 1;
+        components[group].nodes.push(node.id.clone());
+    }
+    for c in &mut components {
+        c.nodes.sort();
+    }
+    Components { count: components.len(), components }
+}
+
+/// Strongest-link path between two domains of a domain network
+/// Returns `{found, path, hops, cost, edges}` as JSON; `cost` sums
+/// `1 / weight` along the path
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn shortest_path(network_json: &str, from_domain: &str, to_domain: &str) -> Result<String, Error> {
+    let network: DomainNetwork = from_json(network_json, "network_json")?;
+
+    let result = find_path(&network, from_domain, to_domain)?;
+    to_json(&result)
+}
+
+/// Connected components of a domain network, largest first
+/// Returns `{count, components: [{size, nodes}]}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn connected_components(network_json: &str) -> Result<String, Error> {
+    let network: DomainNetwork = from_json(network_json, "network_json")?;
+
+    to_json(&components(&network))
+}
+
+#[derive(Serialize)]
+struct NodeMetrics {
+    id: String,
+    degree: usize,
+    strength: f64,
+    clustering: f64,
+}
+
+#[derive(Serialize)]
+struct NetworkMetrics {
+    nodes: usize,
+    edges: usize,
+    density: f64,
+    average_degree: f64,
+    average_clustering: f64,
+    transitivity: f64,
+    /// Mean hop count over connected pairs
+    average_path_length: Option<f64>,
+    diameter: usize,
+    connected_pairs: usize,
+    /// Pearson correlation of degrees at either end of an edge
+    assortativity: Option<f64>,
+    per_node: Vec<NodeMetrics>,
+}
+
+fn metrics(network: &DomainNetwork) -> Result<NetworkMetrics, String> {
+    let graph = Graph::new(network)?;
+    let n = graph.ids.len();
+    let neighbours: Vec<BTreeSet<usize>> = graph
+        .adjacency
+        .iter()
+        .map(|adj| adj.iter().map(|&(next, _)| next).collect())
+        .collect();
+    let degree: Vec<usize> = neighbours.iter().map(BTreeSet::len).collect();
+    let edge_count = degree.iter().sum::<usize>() / 2;
+
+    // Triangles through each node, and the pairs of neighbours that could
+    // close one
+    let triangles: Vec<(usize, usize)> = parallel::map_range(n, |i| {
+        let links = neighbours[i]
+            .iter()
+            .map(|&a| neighbours[i].range(a + 1..).filter(|b| neighbours[a].contains(b)).count())
+            .sum::<usize>();
+        (links, degree[i] * degree[i].saturating_sub(1) / 2)
+    });
+    let triangles_total: usize = triangles.iter().map(|t| t.0).sum();
+    let triples_total: usize = triangles.iter().map(|t| t.1).sum();
+    let mut per_node: Vec<NodeMetrics> = triangles
+        .iter()
+        .enumerate()
+        .map(|(i, &(links, possible))| NodeMetrics {
+            id: graph.ids[i].to_string(),
+            degree: degree[i],
+            strength: graph.adjacency[i].iter().fold(0.0, |acc, &(_, w)| acc + w),
+            clustering: if possible == 0 { 0.0 } else { links as f64 / possible as f64 },
+        })
+        .collect();
+
+    // Breadth-first search from every node, giving each source's total
+    // hops, reachable nodes and eccentricity
+    let searches: Vec<(usize, usize, usize)> = parallel::map_range(n, |source| {
+        let (mut hops, mut reached, mut furthest) = (0, 0, 0);
+        let mut depth = vec![usize::MAX; n];
+        depth[source] = 0;
+        let mut queue = VecDeque::from([source]);
+        while let Some(node) = queue.pop_front() {
+            for &next in &neighbours[node] {
+                if depth[next] == usize::MAX {
+                    depth[next] = depth[node] + 1;
+                    hops += depth[next];
+                    reached += 1;
+                    furthest = furthest.max(depth[next]);
+                    queue.push_back(next);
+                }
+            }
+        }
+        (hops, reached, furthest)
+    });
+    let hops_total: usize = searches.iter().map(|s| s.0).sum();
+    let connected_pairs: usize = searches.iter().map(|s| s.1).sum();
+    let diameter = searches.iter().map(|s| s.2).max().unwrap_or(0);
+
+    // Each undirected edge contributes both orientations, so the two
+    // degree series share one mean and variance
+    let (mut sum, mut sum_sq, mut sum_prod, mut ends) = (0.0, 0.0, 0.0, 0.0);
+    for (i, adj) in neighbours.iter().enumerate() {
+        for &j in adj {
+            let (a, b) = (degree[i] as f64, degree[j] as f64);
+            sum += a;
+            sum_sq += a * a;
+            sum_prod += a * b;
+            ends += 1.0;
+        }
+    }
+    let assortativity = if ends > 0.0 {
+        let mean = sum / ends;
+        let variance = sum_sq / ends - mean * mean;
+        // Undefined when every endpoint has the same degree
+        Some((sum_prod / ends - mean * mean) / variance).filter(|r| r.is_finite())
+    } else {
+        None
+    };
+
+    per_node.sort_by(|a, b| a.id.cmp(&b.id));
+    let pairs = (n * n.saturating_sub(1) / 2).max(1) as f64;
+    Ok(NetworkMetrics {
+        nodes: n,
+        edges: edge_count,
+        density: if n < 2 { 0.0 } else { edge_count as f64 / pairs },
+        average_degree: if n == 0 { 0.0 } else { 2.0 * edge_count as f64 / n as f64 },
+        average_clustering: if n == 0 { 0.0 } else { numeric::sum(per_node.iter().map(|m| m.clustering)) / n as f64 },
+        // Summed per-node triangle counts are already 3x the triangle count
+        transitivity: if triples_total == 0 { 0.0 } else { triangles_total as f64 / triples_total as f64 },
+        average_path_length: (connected_pairs > 0).then(|| hops_total as f64 / connected_pairs as f64),
+        diameter,
+        connected_pairs: connected_pairs / 2,
+        assortativity,
+        per_node,
+    })
+}
+
+/// Topology metrics of a domain network: per-node degree, strength and
+/// clustering coefficient plus density, average path length, transitivity
+/// and degree assortativity
+/// Returns the metrics as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn network_metrics(network_json: &str) -> Result<String, Error> {
+    let network: DomainNetwork = from_json(network_json, "network_json")?;
+
+    let result = metrics(&network).map_err(Error::invalid)?;
+    to_json(&result)
+}
@@ -0,0 +1,147 @@
+//! Floating-point handling and the deterministic numeric mode
+//!
+//! Results must be reproducible bit for bit across the native build, the
+//! WASM build and ports to other languages. The rules every module follows:
+//!
+//! - No fast-math: Rust never reassociates or contracts float operations
+//!   (no implicit FMA), so `a * b + c` rounds twice on every target.
+//! - Fixed summation order: sums run left to right over slices or ordered
+//!   maps, never over `HashMap` iteration, through [`sum`], which uses
+//!   Neumaier compensation (the same algorithm as Python 3.12+'s `sum` on
+//!   floats, so small-count sums agree with the Python port exactly).
+//! - `+ - * /` and `sqrt` are correctly rounded by IEEE 754 and agree
+//!   everywhere. Transcendentals (`sin`, `exp`, `ln`, ...) do not: the
+//!   platform libm is used natively and a different one in WASM, and they
+//!   may differ in the last bit. With the `deterministic` feature they go
+//!   through the pure-Rust `libm` crate (a port of musl) on every target.
+//! - With the `fixed-coordinates` feature, coordinates are snapped to a
+//!   1e-7 degree grid (about 1 cm, the E7 integers used by most location
+//!   APIs) before any distance or geohash is computed, so inputs that
+//!   differ below that precision after a decimal round trip produce
+//!   identical results.
+//!
+//! JSON output uses shortest round-trip formatting, and parsing is exact
+//! (serde_json `float_roundtrip`), so a float read back is the float written.
+
+use serde::Serialize;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{to_json, Error};
+
+/// Grid coordinates snap to under `fixed-coordinates`, in steps per degree
+const COORDINATE_SCALE: f64 = 1e7;
+
+/// Sum in iteration order with Neumaier compensation
+pub(crate) fn sum(values: impl IntoIterator<Item = f64>) -> f64 {
+    let (mut total, mut compensation) = (0.0f64, 0.0f64);
+    for x in values {
+        let t = total + x;
+        if total.abs() >= x.abs() {
+            compensation += (total - t) + x;
+        } else {
+            compensation += (x - t) + total;
+        }
+        total = t;
+    }
+    total + compensation
+}
+
+/// Mean of the values, `None` when there are none
+pub(crate) fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| sum(values.iter().copied()) / values.len() as f64)
+}
+
+/// A latitude or longitude as the analytics see it
+pub(crate) fn coordinate(degrees: f64) -> f64 {
+    if cfg!(feature = "fixed-coordinates") && degrees.is_finite() {
+        (degrees * COORDINATE_SCALE).round() / COORDINATE_SCALE
+    } else {
+        degrees
+    }
+}
+
+#[cfg(feature = "deterministic")]
+mod imp {
+    pub(crate) use libm::{acos, asin, atan2, cos, exp, log as ln, log2, sin, tan};
+
+    /// Integer power by binary exponentiation, the same multiplications on
+    /// every target
+    pub(crate) fn powi(x: f64, n: i32) -> f64 {
+        let (mut base, mut exponent, mut result) = (x, n.unsigned_abs(), 1.0);
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result *= base;
+            }
+            base *= base;
+            exponent >>= 1;
+        }
+        if n < 0 {
+            1.0 / result
+        } else {
+            result
+        }
+    }
+}
+
+#[cfg(not(feature = "deterministic"))]
+mod imp {
+    pub(crate) fn sin(x: f64) -> f64 {
+        x.sin()
+    }
+    pub(crate) fn cos(x: f64) -> f64 {
+        x.cos()
+    }
+    pub(crate) fn tan(x: f64) -> f64 {
+        x.tan()
+    }
+    pub(crate) fn asin(x: f64) -> f64 {
+        x.asin()
+    }
+    pub(crate) fn acos(x: f64) -> f64 {
+        x.acos()
+    }
+    pub(crate) fn atan2(y: f64, x: f64) -> f64 {
+        y.atan2(x)
+    }
+    pub(crate) fn exp(x: f64) -> f64 {
+        x.exp()
+    }
+    pub(crate) fn ln(x: f64) -> f64 {
+        x.ln()
+    }
+    pub(crate) fn log2(x: f64) -> f64 {
+        x.log2()
+    }
+    pub(crate) fn powi(x: f64, n: i32) -> f64 {
+        x.powi(n)
+    }
+}
+
+pub(crate) use imp::{acos, asin, atan2, cos, exp, ln, log2, powi, sin, tan};
+
+#[derive(Serialize)]
+struct NumericMode {
+    deterministic: bool,
+    fixed_coordinates: bool,
+    /// Degrees per coordinate step when `fixed_coordinates` is on
+    coordinate_resolution: f64,
+    summation: &'static str,
+    transcendentals: &'static str,
+}
+
+/// How this build handles floating point, for recording next to results in
+/// replication studies
+/// Returns `{deterministic, fixed_coordinates, coordinate_resolution,
+/// summation, transcendentals}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn numeric_mode() -> Result<String, Error> {
+    let deterministic = cfg!(feature = "deterministic");
+    to_json(&NumericMode {
+        deterministic,
+        fixed_coordinates: cfg!(feature = "fixed-coordinates"),
+        coordinate_resolution: 1.0 / COORDINATE_SCALE,
+        summation: "neumaier",
+        transcendentals: if deterministic { "libm" } else { "platform" },
+    })
+}
@@ -0,0 +1,64 @@
+//! Optional data parallelism for the heavy analytics
+//!
+//! With the `threads` feature, network building, similarity matrices and
+//! network metrics split their hot loops across rayon's global pool. In the
+//! browser that pool lives in Web Workers over shared memory, so it needs
+//! a build with atomics and cross-origin isolation, and the host must start
+//! the workers with `init_thread_pool` before the first call (see
+//! `thread_pool.rs` for the build). Without the feature, or when no threads
+//! could be started, rayon runs everything on the calling thread.
+//!
+//! Work is split into independent pieces whose results are combined in
+//! input order, so output is identical with and without threads and the
+//! rules in `numeric.rs` still hold.
+
+use serde::Serialize;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{to_json, Error};
+
+/// `f` over `0..n`, results in index order
+pub(crate) fn map_range<R: Send>(n: usize, f: impl Fn(usize) -> R + Sync + Send) -> Vec<R> {
+    #[cfg(feature = "threads")]
+    {
+        use rayon::prelude::*;
+        (0..n).into_par_iter().map(f).collect()
+    }
+    #[cfg(not(feature = "threads"))]
+    {
+        (0..n).map(f).collect()
+    }
+}
+
+/// `f` over chunks of `items` of at most `size`, results in chunk order
+pub(crate) fn map_chunks<T: Sync, R: Send>(items: &[T], size: usize, f: impl Fn(&[T]) -> R + Sync + Send) -> Vec<R> {
+    #[cfg(feature = "threads")]
+    {
+        use rayon::prelude::*;
+        items.par_chunks(size.max(1)).map(f).collect()
+    }
+    #[cfg(not(feature = "threads"))]
+    {
+        items.chunks(size.max(1)).map(f).collect()
+    }
+}
+
+#[derive(Serialize)]
+struct Parallelism {
+    /// Whether this build has the `threads` feature
+    threads: bool,
+    /// Threads in rayon's pool; 1 when work runs on the calling thread
+    pool_size: usize,
+}
+
+/// Whether heavy analytics run in parallel in this build and environment
+/// Returns `{threads, pool_size}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn parallelism() -> Result<String, Error> {
+    #[cfg(feature = "threads")]
+    let pool_size = rayon::current_num_threads();
+    #[cfg(not(feature = "threads"))]
+    let pool_size = 1;
+    to_json(&Parallelism { threads: cfg!(feature = "threads"), pool_size })
+}
@@ -0,0 +1,392 @@
+//! Parquet export for research pipelines
+//!
+//! [`export_parquet`] writes experiences as one flat table that pandas,
+//! polars, DuckDB and Spark read directly:
+//!
+//! | Column          | Type                | From                                    |
+//! |-----------------|---------------------|-----------------------------------------|
+//! | `id`            | string              | `id`                                    |
+//! | `timestamp`     | timestamp (ms, UTC) | `timestamp`, null if unparseable        |
+//! | `learner_id`    | string              | `learner.id`                            |
+//! | `tenant`        | string              | `tenant`                                |
+//! | `location_name` | string              | `context.location.name`                 |
+//! | `lat`, `lon`    | double              | `context.location.coordinates`          |
+//! | `accuracy_m`    | double              | `context.location.coordinates.accuracy` |
+//! | `type`          | string              | `experience.type`                       |
+//! | `description`   | string              | `experience.description`                |
+//! | `domains`       | list of string      | `experience.domains`                    |
+//!
+//! Every column is nullable. The file has a single row group with one
+//! gzip-compressed, PLAIN-encoded data page per column, which is all the
+//! format asks of a writer, so it is produced here rather than through the
+//! arrow crates and their dependency tree. Metadata uses the Thrift compact
+//! protocol as the format requires.
+
+use serde_json::Value;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, Error};
+use crate::export::lookup;
+use crate::gzip::gzip;
+use crate::query::accuracy;
+use crate::time::parse_timestamp;
+
+const MAGIC: &[u8; 4] = b"PAR1";
+
+// Thrift compact protocol type ids
+const T_I32: u8 = 5;
+const T_I64: u8 = 6;
+const T_BINARY: u8 = 8;
+const T_LIST: u8 = 9;
+const T_STRUCT: u8 = 12;
+
+// Parquet enum values
+const INT64: i32 = 2;
+const DOUBLE: i32 = 5;
+const BYTE_ARRAY: i32 = 6;
+const REQUIRED: i32 = 0;
+const OPTIONAL: i32 = 1;
+const REPEATED: i32 = 2;
+const UTF8: i32 = 0;
+const LIST: i32 = 3;
+const TIMESTAMP_MILLIS: i32 = 9;
+const PLAIN: i32 = 0;
+const RLE: i32 = 3;
+const GZIP: i32 = 2;
+const DATA_PAGE: i32 = 0;
+
+/// Thrift compact protocol writer
+struct Thrift {
+    out: Vec<u8>,
+    /// Last field id written in each open struct
+    last_field: Vec<i16>,
+}
+
+impl Thrift {
+    fn new() -> Self {
+        Thrift { out: Vec::new(), last_field: vec![0] }
+    }
+
+    fn varint(&mut self, mut v: u64) {
+        while v >= 0x80 {
+            self.out.push(v as u8 | 0x80);
+            v >>= 7;
+        }
+        self.out.push(v as u8);
+    }
+
+    fn field(&mut self, id: i16, kind: u8) {
+        let last = self.last_field.last_mut().expect("a struct is open");
+        let delta = id - std::mem::replace(last, id);
+        if (1..=15).contains(&delta) {
+            self.out.push((delta as u8) << 4 | kind);
+        } else {
+            self.out.push(kind);
+            self.varint(((id << 1) ^ (id >> 15)) as u16 as u64);
+        }
+    }
+
+    fn i32(&mut self, id: i16, v: i32) {
+        self.field(id, T_I32);
+        self.varint(((v << 1) ^ (v >> 31)) as u32 as u64);
+    }
+
+    fn i64(&mut self, id: i16, v: i64) {
+        self.field(id, T_I64);
+        self.varint(((v << 1) ^ (v >> 63)) as u64);
+    }
+
+    fn bytes(&mut self, v: &[u8]) {
+        self.varint(v.len() as u64);
+        self.out.extend_from_slice(v);
+    }
+
+    fn string(&mut self, id: i16, v: &str) {
+        self.field(id, T_BINARY);
+        self.bytes(v.as_bytes());
+    }
+
+    fn list(&mut self, id: i16, element: u8, len: usize) {
+        self.field(id, T_LIST);
+        if len < 15 {
+            self.out.push((len as u8) << 4 | element);
+        } else {
+            self.out.push(0xF0 | element);
+            self.varint(len as u64);
+        }
+    }
+
+    /// Open a struct, as field `id` or (with `None`) as a list element
+    fn begin(&mut self, id: Option<i16>) {
+        if let Some(id) = id {
+            self.field(id, T_STRUCT);
+        }
+        self.last_field.push(0);
+    }
+
+    fn end(&mut self) {
+        self.out.push(0);
+        self.last_field.pop();
+    }
+}
+
+/// RLE runs of the levels (the run-only subset of the hybrid encoding),
+/// length-prefixed as in a v1 data page
+fn encode_levels(out: &mut Vec<u8>, levels: &[u8]) {
+    let mut runs = Vec::new();
+    let mut rest = levels;
+    while let Some(&level) = rest.first() {
+        let run = rest.iter().take_while(|&&l| l == level).count();
+        let mut header = (run as u64) << 1;
+        while header >= 0x80 {
+            runs.push(header as u8 | 0x80);
+            header >>= 7;
+        }
+        runs.push(header as u8);
+        runs.push(level);
+        rest = &rest[run..];
+    }
+    out.extend_from_slice(&(runs.len() as u32).to_le_bytes());
+    out.extend_from_slice(&runs);
+}
+
+enum Cell {
+    Text(Option<String>),
+    Millis(Option<i64>),
+    Number(Option<f64>),
+    List(Option<Vec<String>>),
+}
+
+struct Column {
+    name: &'static str,
+    physical: i32,
+    converted: Option<i32>,
+    read: fn(&Value) -> Cell,
+}
+
+fn text(exp: &Value, path: &str) -> Cell {
+    Cell::Text(lookup(exp, path).and_then(Value::as_str).map(str::to_string))
+}
+
+fn number(exp: &Value, path: &str) -> Cell {
+    Cell::Number(lookup(exp, path).and_then(Value::as_f64))
+}
+
+const COLUMNS: &[Column] = &[
+    Column { name: "id", physical: BYTE_ARRAY, converted: Some(UTF8), read: |e| text(e, "id") },
+    Column {
+        name: "timestamp",
+        physical: INT64,
+        converted: Some(TIMESTAMP_MILLIS),
+        read: |e| Cell::Millis(lookup(e, "timestamp").and_then(Value::as_str).and_then(parse_timestamp)),
+    },
+    Column { name: "learner_id", physical: BYTE_ARRAY, converted: Some(UTF8), read: |e| text(e, "learner.id") },
+    Column { name: "tenant", physical: BYTE_ARRAY, converted: Some(UTF8), read: |e| text(e, "tenant") },
+    Column { name: "location_name", physical: BYTE_ARRAY, converted: Some(UTF8), read: |e| text(e, "context.location.name") },
+    Column { name: "lat", physical: DOUBLE, converted: None, read: |e| number(e, "context.location.coordinates.latitude") },
+    Column { name: "lon", physical: DOUBLE, converted: None, read: |e| number(e, "context.location.coordinates.longitude") },
+    Column { name: "accuracy_m", physical: DOUBLE, converted: None, read: |e| Cell::Number(accuracy(e)) },
+    Column { name: "type", physical: BYTE_ARRAY, converted: Some(UTF8), read: |e| text(e, "experience.type") },
+    Column { name: "description", physical: BYTE_ARRAY, converted: Some(UTF8), read: |e| text(e, "experience.description") },
+    Column {
+        name: "domains",
+        physical: BYTE_ARRAY,
+        converted: Some(UTF8),
+        read: |e| {
+            Cell::List(
+                lookup(e, "experience.domains")
+                    .and_then(Value::as_array)
+                    .map(|items| items.iter().filter_map(Value::as_str).map(str::to_string).collect()),
+            )
+        },
+    },
+];
+
+/// Levels and PLAIN values of one column
+#[derive(Default)]
+struct Page {
+    repetition: Vec<u8>,
+    definition: Vec<u8>,
+    values: Vec<u8>,
+}
+
+impl Page {
+    fn push_bytes(&mut self, s: &str) {
+        self.values.extend_from_slice(&(s.len() as u32).to_le_bytes());
+        self.values.extend_from_slice(s.as_bytes());
+    }
+
+    fn push(&mut self, cell: Cell) {
+        match cell {
+            Cell::List(None) => {
+                self.repetition.push(0);
+                self.definition.push(0);
+            }
+            Cell::List(Some(items)) if items.is_empty() => {
+                self.repetition.push(0);
+                self.definition.push(1);
+            }
+            Cell::List(Some(items)) => {
+                for (i, item) in items.iter().enumerate() {
+                    self.repetition.push(u8::from(i > 0));
+                    self.definition.push(2);
+                    self.push_bytes(item);
+                }
+            }
+            Cell::Text(v) => {
+                self.definition.push(u8::from(v.is_some()));
+                if let Some(s) = v {
+                    self.push_bytes(&s);
+                }
+            }
+            Cell::Millis(v) => {
+                self.definition.push(u8::from(v.is_some()));
+                if let Some(ms) = v {
+                    self.values.extend_from_slice(&ms.to_le_bytes());
+                }
+            }
+            Cell::Number(v) => {
+                self.definition.push(u8::from(v.is_some()));
+                if let Some(x) = v {
+                    self.values.extend_from_slice(&x.to_le_bytes());
+                }
+            }
+        }
+    }
+}
+
+struct ChunkMeta {
+    column: &'static Column,
+    offset: usize,
+    num_values: usize,
+    uncompressed: usize,
+    compressed: usize,
+}
+
+fn write_chunk(out: &mut Vec<u8>, column: &'static Column, page: Page) -> Result<ChunkMeta, Error> {
+    let is_list = column.name == "domains";
+    let mut data = Vec::new();
+    if is_list {
+        encode_levels(&mut data, &page.repetition);
+    }
+    encode_levels(&mut data, &page.definition);
+    data.extend_from_slice(&page.values);
+    let compressed = gzip(&data)?;
+
+    let mut header = Thrift::new();
+    header.i32(1, DATA_PAGE);
+    header.i32(2, data.len() as i32);
+    header.i32(3, compressed.len() as i32);
+    header.begin(Some(5));
+    header.i32(1, page.definition.len() as i32);
+    header.i32(2, PLAIN);
+    header.i32(3, RLE);
+    header.i32(4, RLE);
+    header.end();
+    header.end();
+
+    let meta = ChunkMeta {
+        column,
+        offset: out.len(),
+        num_values: page.definition.len(),
+        uncompressed: header.out.len() + data.len(),
+        compressed: header.out.len() + compressed.len(),
+    };
+    out.extend_from_slice(&header.out);
+    out.extend_from_slice(&compressed);
+    Ok(meta)
+}
+
+fn write_footer(out: &mut Vec<u8>, rows: usize, chunks: &[ChunkMeta]) {
+    let mut t = Thrift::new();
+    t.i32(1, 1);
+
+    t.list(2, T_STRUCT, COLUMNS.len() + 3);
+    t.begin(None);
+    t.string(4, "schema");
+    t.i32(5, COLUMNS.len() as i32);
+    t.end();
+    for column in COLUMNS {
+        if column.name == "domains" {
+            t.begin(None);
+            t.i32(3, OPTIONAL);
+            t.string(4, column.name);
+            t.i32(5, 1);
+            t.i32(6, LIST);
+            t.end();
+            t.begin(None);
+            t.i32(3, REPEATED);
+            t.string(4, "list");
+            t.i32(5, 1);
+            t.end();
+        }
+        t.begin(None);
+        t.i32(1, column.physical);
+        let (repetition, name) = if column.name == "domains" { (REQUIRED, "element") } else { (OPTIONAL, column.name) };
+        t.i32(3, repetition);
+        t.string(4, name);
+        if let Some(converted) = column.converted {
+            t.i32(6, converted);
+        }
+        t.end();
+    }
+
+    t.i64(3, rows as i64);
+    t.list(4, T_STRUCT, usize::from(!chunks.is_empty()));
+    if !chunks.is_empty() {
+        t.begin(None);
+        t.list(1, T_STRUCT, chunks.len());
+        for chunk in chunks {
+            t.begin(None);
+            t.i64(2, chunk.offset as i64);
+            t.begin(Some(3));
+            t.i32(1, chunk.column.physical);
+            let encodings: &[i32] = &[PLAIN, RLE];
+            t.list(2, T_I32, encodings.len());
+            for &encoding in encodings {
+                t.varint(((encoding << 1) ^ (encoding >> 31)) as u32 as u64);
+            }
+            let path: &[&str] = if chunk.column.name == "domains" { &["domains", "list", "element"] } else { &[chunk.column.name] };
+            t.list(3, T_BINARY, path.len());
+            for part in path {
+                t.bytes(part.as_bytes());
+            }
+            t.i32(4, GZIP);
+            t.i64(5, chunk.num_values as i64);
+            t.i64(6, chunk.uncompressed as i64);
+            t.i64(7, chunk.compressed as i64);
+            t.i64(9, chunk.offset as i64);
+            t.end();
+            t.end();
+        }
+        t.i64(2, chunks.iter().map(|c| c.uncompressed as i64).sum());
+        t.i64(3, rows as i64);
+        t.end();
+    }
+    t.string(6, concat!("ubicity-core version ", env!("CARGO_PKG_VERSION")));
+    t.end();
+
+    out.extend_from_slice(&t.out);
+    out.extend_from_slice(&(t.out.len() as u32).to_le_bytes());
+    out.extend_from_slice(MAGIC);
+}
+
+/// Export experiences as a Parquet file with nested fields flattened
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn export_parquet(experiences_json: &str) -> Result<Vec<u8>, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let mut out = MAGIC.to_vec();
+    let mut chunks = Vec::new();
+    if !experiences.is_empty() {
+        for column in COLUMNS {
+            let mut page = Page::default();
+            for exp in &experiences {
+                page.push((column.read)(exp));
+            }
+            chunks.push(write_chunk(&mut out, column, page)?);
+        }
+    }
+    write_footer(&mut out, experiences.len(), &chunks);
+    Ok(out)
+}
@@ -0,0 +1,448 @@
+//! Basic graph pattern queries over the triple view
+//!
+//! A query is a list of `[subject, predicate, object]` patterns matched
+//! against the [`crate::triples`] graph, plus optional filters over the
+//! variables they bind:
+//!
+//! ```json
+//! {
+//!   "where": [
+//!     ["?visit", "ubi:learner", "?learner"],
+//!     ["?visit", "ubi:place", "?place"],
+//!     ["?place", "ubi:name", "?place_name"],
+//!     ["?visit", "ubi:timestamp", "?visited"],
+//!     ["?tag", "ubi:learner", "?learner"],
+//!     ["?tag", "ubi:domain", "?domain"],
+//!     ["?domain", "ubi:name", "chemistry"],
+//!     ["?tag", "ubi:timestamp", "?tagged"]
+//!   ],
+//!   "filters": [
+//!     {"left": "?place_name", "op": "contains", "right": "wetland"},
+//!     {"left": "?visited", "op": "lt", "right": "?tagged"}
+//!   ],
+//!   "select": ["?learner"],
+//!   "distinct": true
+//! }
+//! ```
+//!
+//! Terms starting with `?` are variables. `a` in predicate position is
+//! `rdf:type`, and `prefix:name` expands for the built-in `ubi`, `rdf` and
+//! `xsd` prefixes plus any given under `prefixes`; `<...>` is a full IRI.
+//! Any other term must equal the node's IRI or literal value exactly.
+//!
+//! Filter operators are those of the query DSL (`eq`, `ne`, `lt`, `lte`,
+//! `gt`, `gte`, `contains`). Values compare as instants when both are
+//! timestamps, as numbers when both are numeric and as text otherwise;
+//! `contains` is a case-insensitive substring test.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::time::parse_timestamp;
+use crate::triples::{build_triples, Object, Triple, RDF_TYPE, VOCAB, XSD};
+
+const RDF: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+
+#[derive(Deserialize)]
+struct PatternQuery {
+    #[serde(rename = "where")]
+    patterns: Vec<[String; 3]>,
+    #[serde(default)]
+    filters: Vec<PatternFilter>,
+    #[serde(default)]
+    select: Vec<String>,
+    #[serde(default)]
+    prefixes: BTreeMap<String, String>,
+    #[serde(default)]
+    distinct: bool,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct MatchResult<'a> {
+    variables: Vec<&'a str>,
+    rows: Vec<Map<String, Value>>,
+    count: usize,
+}
+
+#[derive(Deserialize)]
+struct PatternFilter {
+    left: String,
+    op: FilterOp,
+    right: String,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum FilterOp {
+    Eq,
+    Ne,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    Contains,
+}
+
+#[derive(Clone)]
+enum Term {
+    Var(usize),
+    Const(String),
+}
+
+/// A graph node as bound to a variable
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Node<'a> {
+    value: &'a str,
+    literal: bool,
+}
+
+impl<'a> Node<'a> {
+    fn iri(value: &'a str) -> Self {
+        Node { value, literal: false }
+    }
+
+    fn object(object: &'a Object) -> Self {
+        Node {
+            value: object.value(),
+            literal: matches!(object, Object::Literal { .. }),
+        }
+    }
+}
+
+struct Filter {
+    left: Term,
+    op: FilterOp,
+    right: Term,
+}
+
+/// A compiled query: patterns in evaluation order, each followed by the
+/// filters whose variables it completes
+struct Plan {
+    variables: Vec<String>,
+    steps: Vec<([Term; 3], Vec<Filter>)>,
+    select: Vec<usize>,
+    distinct: bool,
+    limit: Option<usize>,
+}
+
+struct Compiler {
+    prefixes: BTreeMap<String, String>,
+    variables: Vec<String>,
+}
+
+impl Compiler {
+    fn term(&mut self, text: &str, position: usize) -> Result<Term, Error> {
+        if let Some(name) = text.strip_prefix('?') {
+            if name.is_empty() {
+                return Err(Error::invalid("variable needs a name after ?"));
+            }
+            let index = match self.variables.iter().position(|v| v == name) {
+                Some(index) => index,
+                None => {
+                    self.variables.push(name.to_string());
+                    self.variables.len() - 1
+                }
+            };
+            return Ok(Term::Var(index));
+        }
+        if position == 1 && text == "a" {
+            return Ok(Term::Const(RDF_TYPE.to_string()));
+        }
+        Ok(Term::Const(self.expand(text)))
+    }
+
+    fn expand(&self, text: &str) -> String {
+        if let Some(iri) = text.strip_prefix('<').and_then(|t| t.strip_suffix('>')) {
+            return iri.to_string();
+        }
+        if let Some((prefix, local)) = text.split_once(':') {
+            if let Some(namespace) = self.prefixes.get(prefix) {
+                return format!("{}{}", namespace, local);
+            }
+        }
+        text.to_string()
+    }
+
+    fn variable(&self, text: &str) -> Result<usize, Error> {
+        let name = text.strip_prefix('?').unwrap_or(text);
+        self.variables
+            .iter()
+            .position(|v| v == name)
+            .ok_or_else(|| Error::invalid(format!("variable ?{} does not appear in any pattern", name)).with("variable", name))
+    }
+
+    /// Filter operands: variables must already be bound by a pattern;
+    /// anything else is a constant compared as-is
+    fn operand(&self, text: &str) -> Result<Term, Error> {
+        match text.strip_prefix('?') {
+            Some(_) => self.variable(text).map(Term::Var),
+            None => Ok(Term::Const(text.to_string())),
+        }
+    }
+}
+
+fn compile(query: PatternQuery) -> Result<Plan, Error> {
+    if query.patterns.is_empty() {
+        return Err(Error::invalid("query needs at least one pattern"));
+    }
+    let mut prefixes: BTreeMap<String, String> = [("ubi", VOCAB), ("rdf", RDF), ("xsd", XSD)]
+        .into_iter()
+        .map(|(prefix, namespace)| (prefix.to_string(), namespace.to_string()))
+        .collect();
+    prefixes.extend(query.prefixes);
+    let mut compiler = Compiler { prefixes, variables: Vec::new() };
+
+    let mut patterns = Vec::with_capacity(query.patterns.len());
+    for pattern in &query.patterns {
+        patterns.push([
+            compiler.term(&pattern[0], 0)?,
+            compiler.term(&pattern[1], 1)?,
+            compiler.term(&pattern[2], 2)?,
+        ]);
+    }
+
+    let mut filters = Vec::with_capacity(query.filters.len());
+    for filter in &query.filters {
+        filters.push(Filter {
+            left: compiler.operand(&filter.left)?,
+            op: filter.op,
+            right: compiler.operand(&filter.right)?,
+        });
+    }
+
+    let select = if query.select.is_empty() {
+        (0..compiler.variables.len()).collect()
+    } else {
+        query.select.iter().map(|name| compiler.variable(name)).collect::<Result<_, _>>()?
+    };
+
+    // Greedy join order: always evaluate next the pattern with the most
+    // positions already fixed, preferring a known subject, then object
+    let mut bound = vec![false; compiler.variables.len()];
+    let known = |term: &Term, bound: &[bool]| match term {
+        Term::Const(_) => true,
+        Term::Var(v) => bound[*v],
+    };
+    let mut remaining: Vec<Option<[Term; 3]>> = patterns.into_iter().map(Some).collect();
+    let mut pending: Vec<Option<Filter>> = filters.into_iter().map(Some).collect();
+    let mut steps = Vec::with_capacity(remaining.len());
+    while let Some(next) = remaining
+        .iter()
+        .enumerate()
+        .filter_map(|(i, p)| p.as_ref().map(|p| (i, p)))
+        .max_by_key(|(i, [s, p, o])| {
+            let score = 4 * u8::from(known(s, &bound)) + 2 * u8::from(known(o, &bound)) + u8::from(known(p, &bound));
+            (score, std::cmp::Reverse(*i))
+        })
+        .map(|(i, _)| i)
+    {
+        let Some(pattern) = remaining[next].take() else { break };
+        for term in &pattern {
+            if let Term::Var(v) = term {
+                bound[*v] = true;
+            }
+        }
+        let ready = pending
+            .iter_mut()
+            .filter(|f| f.as_ref().is_some_and(|f| known(&f.left, &bound) && known(&f.right, &bound)))
+            .filter_map(Option::take)
+            .collect();
+        steps.push((pattern, ready));
+    }
+
+    Ok(Plan {
+        variables: compiler.variables,
+        steps,
+        select,
+        distinct: query.distinct,
+        limit: query.limit,
+    })
+}
+
+/// Order two values as instants, then numbers, then text
+pub(crate) fn compare_values(a: &str, b: &str) -> Ordering {
+    if let (Some(x), Some(y)) = (parse_timestamp(a), parse_timestamp(b)) {
+        return x.cmp(&y);
+    }
+    if let (Ok(x), Ok(y)) = (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+        if let Some(ordering) = x.partial_cmp(&y) {
+            return ordering;
+        }
+    }
+    a.cmp(b)
+}
+
+struct Matcher<'a> {
+    triples: &'a [Triple],
+    by_subject: HashMap<&'a str, Vec<usize>>,
+    by_predicate: HashMap<&'a str, Vec<usize>>,
+    plan: &'a Plan,
+    rows: Vec<Vec<Node<'a>>>,
+    seen: HashSet<Vec<Node<'a>>>,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(triples: &'a [Triple], plan: &'a Plan) -> Self {
+        let mut by_subject: HashMap<&str, Vec<usize>> = HashMap::new();
+        let mut by_predicate: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, t) in triples.iter().enumerate() {
+            by_subject.entry(t.subject.as_str()).or_default().push(i);
+            by_predicate.entry(t.predicate.as_str()).or_default().push(i);
+        }
+        Matcher { triples, by_subject, by_predicate, plan, rows: Vec::new(), seen: HashSet::new() }
+    }
+
+    fn full(&self) -> bool {
+        self.plan.limit.is_some_and(|limit| self.rows.len() >= limit)
+    }
+
+    /// The constant or bound value a term must take, if fixed
+    fn fixed<'b>(term: &'b Term, binding: &[Option<Node<'b>>]) -> Option<&'b str> {
+        match term {
+            Term::Const(c) => Some(c),
+            Term::Var(v) => binding[*v].map(|n| n.value),
+        }
+    }
+
+    fn resolve(term: &'a Term, binding: &[Option<Node<'a>>]) -> Node<'a> {
+        match term {
+            Term::Const(c) => Node { value: c, literal: true },
+            Term::Var(v) => binding[*v].unwrap_or(Node { value: "", literal: true }),
+        }
+    }
+
+    fn passes(filter: &'a Filter, binding: &[Option<Node<'a>>]) -> bool {
+        let left = Self::resolve(&filter.left, binding);
+        let right = Self::resolve(&filter.right, binding);
+        let ordering = compare_values(left.value, right.value);
+        match filter.op {
+            FilterOp::Eq => ordering == Ordering::Equal,
+            FilterOp::Ne => ordering != Ordering::Equal,
+            FilterOp::Lt => ordering == Ordering::Less,
+            FilterOp::Lte => ordering != Ordering::Greater,
+            FilterOp::Gt => ordering == Ordering::Greater,
+            FilterOp::Gte => ordering != Ordering::Less,
+            FilterOp::Contains => left.value.to_lowercase().contains(&right.value.to_lowercase()),
+        }
+    }
+
+    /// Bind `term` to `node`, or check it against an existing binding;
+    /// returns whether the variable was newly bound
+    fn unify(term: &Term, node: Node<'a>, binding: &mut [Option<Node<'a>>]) -> Option<bool> {
+        match term {
+            Term::Const(c) => (c == node.value).then_some(false),
+            Term::Var(v) => match binding[*v] {
+                Some(existing) => (existing == node).then_some(false),
+                None => {
+                    binding[*v] = Some(node);
+                    Some(true)
+                }
+            },
+        }
+    }
+
+    fn search(&mut self, step: usize, binding: &mut Vec<Option<Node<'a>>>) {
+        if self.full() {
+            return;
+        }
+        let Some((pattern, filters)) = self.plan.steps.get(step) else {
+            let row: Vec<Node> = self.plan.select.iter().filter_map(|v| binding[*v]).collect();
+            if !self.plan.distinct || self.seen.insert(row.clone()) {
+                self.rows.push(row);
+            }
+            return;
+        };
+
+        let [s, p, o] = pattern;
+        let candidates: Vec<usize> = if let Some(subject) = Self::fixed(s, binding) {
+            self.by_subject.get(subject).cloned().unwrap_or_default()
+        } else if let Some(predicate) = Self::fixed(p, binding) {
+            self.by_predicate.get(predicate).cloned().unwrap_or_default()
+        } else {
+            (0..self.triples.len()).collect()
+        };
+
+        for i in candidates {
+            let triple = &self.triples[i];
+            let mut newly = Vec::with_capacity(3);
+            let positions = [
+                (s, Node::iri(&triple.subject)),
+                (p, Node::iri(&triple.predicate)),
+                (o, Node::object(&triple.object)),
+            ];
+            let mut matched = true;
+            for (term, node) in positions {
+                match Self::unify(term, node, binding) {
+                    Some(true) => {
+                        if let Term::Var(v) = term {
+                            newly.push(*v);
+                        }
+                    }
+                    Some(false) => {}
+                    None => {
+                        matched = false;
+                        break;
+                    }
+                }
+            }
+            if matched && filters.iter().all(|f| Self::passes(f, binding)) {
+                self.search(step + 1, binding);
+            }
+            for v in newly {
+                binding[v] = None;
+            }
+            if self.full() {
+                return;
+            }
+        }
+    }
+}
+
+/// Accept either a `to_triples` array or experiences to convert
+fn load_triples(json: &str) -> Result<Vec<Triple>, Error> {
+    let items: Vec<Value> = from_json(json, "triples_or_experiences_json")?;
+    if !items.is_empty() && items.iter().all(|item| item.get("subject").is_some() && item.get("predicate").is_some()) {
+        return serde_json::from_value(Value::Array(items))
+            .map_err(|e| Error::parse(e).with("argument", "triples_or_experiences_json"));
+    }
+    Ok(build_triples(&items))
+}
+
+/// Match a basic graph pattern against triples from `to_triples`, or
+/// against experiences (converted on the fly)
+/// Returns `{variables, rows, count}` as JSON; each row maps the selected
+/// variable names (without `?`) to IRIs or literal values
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn match_patterns(triples_or_experiences_json: &str, pattern_json: &str) -> Result<String, Error> {
+    let triples = load_triples(triples_or_experiences_json)?;
+    let query: PatternQuery = from_json(pattern_json, "pattern_json")?;
+    let plan = compile(query)?;
+
+    let mut matcher = Matcher::new(&triples, &plan);
+    let mut binding = vec![None; plan.variables.len()];
+    matcher.search(0, &mut binding);
+
+    let rows: Vec<Map<String, Value>> = matcher
+        .rows
+        .iter()
+        .map(|row| {
+            plan.select
+                .iter()
+                .zip(row)
+                .map(|(v, node)| (plan.variables[*v].clone(), Value::from(node.value)))
+                .collect()
+        })
+        .collect();
+
+    to_json(&MatchResult {
+        variables: plan.select.iter().map(|v| plan.variables[*v].as_str()).collect(),
+        count: rows.len(),
+        rows,
+    })
+}
@@ -0,0 +1,222 @@
+//! Known learning places and nearby suggestions
+//!
+//! Places are supplied by the host app:
+//!
+//! ```json
+//! {"id": "kew", "name": "Kew Gardens", "latitude": 51.478, "longitude": -0.295,
+//!  "type": "fieldwork", "domains": ["botany", "ecology"]}
+//! ```
+//!
+//! A place stands for the experience a visit would produce, so goal filters
+//! (see [`crate::goals`]) can be evaluated against it. [`suggest_places`]
+//! ranks places by estimated travel time, relevance to unmet goals, and
+//! novelty relative to where the learner has already been.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::geo::haversine_m;
+use crate::goals::{check_goals, Goal};
+use crate::query::{coordinates, precise_within};
+
+/// Experiences within this distance of a place count as visits to it, if
+/// their fix is at least this accurate
+const VISIT_RADIUS_M: f64 = 100.0;
+
+#[derive(Deserialize)]
+pub(crate) struct Place {
+    pub(crate) id: String,
+    #[serde(default)]
+    pub(crate) name: Option<String>,
+    pub(crate) latitude: f64,
+    pub(crate) longitude: f64,
+    #[serde(default, rename = "type")]
+    pub(crate) type_field: Option<String>,
+    #[serde(default)]
+    pub(crate) domains: Vec<String>,
+    /// Typical visit length, used by the scheduler
+    #[serde(default)]
+    pub(crate) duration_minutes: Option<f64>,
+}
+
+impl Place {
+    /// The experience a visit to this place would produce
+    pub(crate) fn template(&self) -> Value {
+        json!({
+            "context": {"location": {
+                "name": self.name.clone().unwrap_or_else(|| self.id.clone()),
+                "coordinates": {"latitude": self.latitude, "longitude": self.longitude},
+            }},
+            "experience": {"type": self.type_field, "domains": self.domains},
+        })
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub(crate) struct Point {
+    pub(crate) latitude: f64,
+    pub(crate) longitude: f64,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum TravelMode {
+    Walk,
+    Bike,
+    Transit,
+    Car,
+}
+
+impl TravelMode {
+    fn speed_kmh(self) -> f64 {
+        match self {
+            TravelMode::Walk => 5.0,
+            TravelMode::Bike => 15.0,
+            TravelMode::Transit => 20.0,
+            TravelMode::Car => 35.0,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct Weights {
+    travel: f64,
+    relevance: f64,
+    novelty: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            travel: 1.0,
+            relevance: 1.0,
+            novelty: 0.5,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct Profile {
+    mode: TravelMode,
+    /// Ratio of network distance to straight-line distance
+    detour_factor: f64,
+    goals: Vec<Goal>,
+    /// The learner's experiences, for goal progress and past visits
+    experiences: Vec<Value>,
+    weights: Weights,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            mode: TravelMode::Walk,
+            detour_factor: 1.3,
+            goals: Vec::new(),
+            experiences: Vec::new(),
+            weights: Weights::default(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Suggestion {
+    id: String,
+    name: Option<String>,
+    distance_m: f64,
+    travel_minutes: f64,
+    relevance: f64,
+    novelty: f64,
+    visits: usize,
+    score: f64,
+    /// Unmet goals a visit would count towards
+    goals: Vec<String>,
+}
+
+fn rank(location: Point, places: &[Place], profile: &Profile, max_minutes: f64) -> Vec<Suggestion> {
+    let unmet: Vec<&Goal> = profile
+        .goals
+        .iter()
+        .filter(|goal| {
+            let done = profile.experiences.iter().filter(|exp| goal.counts(exp)).count();
+            done < goal.target as usize
+        })
+        .collect();
+    let visited: Vec<(f64, f64)> = profile
+        .experiences
+        .iter()
+        .filter(|exp| precise_within(exp, VISIT_RADIUS_M))
+        .filter_map(coordinates)
+        .collect();
+    let speed_m_per_min = profile.mode.speed_kmh() * 1000.0 / 60.0;
+
+    let mut suggestions: Vec<Suggestion> = places
+        .iter()
+        .filter_map(|place| {
+            let distance_m = haversine_m(location.latitude, location.longitude, place.latitude, place.longitude);
+            let travel_minutes = distance_m * profile.detour_factor / speed_m_per_min;
+            if travel_minutes > max_minutes {
+                return None;
+            }
+            let template = place.template();
+            let goals: Vec<String> = unmet
+                .iter()
+                .filter(|goal| goal.counts(&template))
+                .map(|goal| goal.id.clone())
+                .collect();
+            let relevance = if unmet.is_empty() { 0.0 } else { goals.len() as f64 / unmet.len() as f64 };
+            let visits = visited
+                .iter()
+                .filter(|(lat, lon)| haversine_m(*lat, *lon, place.latitude, place.longitude) <= VISIT_RADIUS_M)
+                .count();
+            let novelty = 1.0 / (1.0 + visits as f64);
+            let time_score = if max_minutes > 0.0 { 1.0 - travel_minutes / max_minutes } else { 1.0 };
+            let w = &profile.weights;
+            Some(Suggestion {
+                id: place.id.clone(),
+                name: place.name.clone(),
+                distance_m,
+                travel_minutes,
+                relevance,
+                novelty,
+                visits,
+                score: w.travel * time_score + w.relevance * relevance + w.novelty * novelty,
+                goals,
+            })
+        })
+        .collect();
+    suggestions.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.id.cmp(&b.id)));
+    suggestions
+}
+
+/// Rank known places reachable from `current_location_json`
+/// (`{latitude, longitude}`) within `max_minutes`. `profile_json` may set
+/// `mode` (`walk`, `bike`, `transit`, `car`), `detour_factor`, `goals`,
+/// `experiences` and `weights`; pass an empty string for defaults
+/// Returns the ranked suggestions as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn suggest_places(
+    current_location_json: &str,
+    places_json: &str,
+    profile_json: &str,
+    max_minutes: f64,
+) -> Result<String, Error> {
+    let location: Point = from_json(current_location_json, "current_location_json")?;
+    let places: Vec<Place> = from_json(places_json, "places_json")?;
+    let profile: Profile = if profile_json.trim().is_empty() {
+        Profile::default()
+    } else {
+        from_json(profile_json, "profile_json")?
+    };
+    check_goals(&profile.goals).map_err(Error::invalid)?;
+    if max_minutes.is_nan() || max_minutes < 0.0 {
+        return Err(Error::invalid("max_minutes must be non-negative"));
+    }
+
+    let suggestions = rank(location, &places, &profile, max_minutes);
+    to_json(&suggestions)
+}
@@ -0,0 +1,102 @@
+//! Context matching against points of interest
+//!
+//! [`match_context`] matches learner locations against a host-supplied
+//! GeoJSON of places (museums, parks, libraries, ...), entirely offline.
+//! Polygons match the locations they contain; points and lines match within
+//! their `radius_m` property (default 100 m). A POI's category comes from
+//! its `category` property or, for OpenStreetMap extracts, from the first of
+//! its `amenity`, `tourism`, `leisure`, `historic` or `shop` tags.
+//!
+//! A match is `certain` when the location's whole accuracy circle falls
+//! inside the matched area, so a 500 m network fix beside a park can be
+//! told apart from a 3 m GPS fix in the middle of it. Locations without an
+//! accuracy are taken as exact.
+
+use serde::Serialize;
+use serde_json::Value;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error, ErrorKind};
+use crate::geojson::{parse_features, Feature};
+use crate::query::{accuracy, accuracy_of, coordinates};
+
+const DEFAULT_RADIUS_M: f64 = 100.0;
+const CATEGORY_KEYS: &[&str] = &["category", "amenity", "tourism", "leisure", "historic", "shop"];
+
+#[derive(Serialize)]
+struct PoiMatch {
+    id: Option<Value>,
+    name: Option<String>,
+    category: Option<String>,
+    distance_m: f64,
+    /// Whether the match holds wherever in its accuracy circle the
+    /// location really is
+    certain: bool,
+}
+
+fn matches_at(pois: &[Feature], lat: f64, lon: f64, accuracy_m: Option<f64>) -> Vec<PoiMatch> {
+    let accuracy_m = accuracy_m.unwrap_or(0.0);
+    let mut found: Vec<PoiMatch> = pois
+        .iter()
+        .filter_map(|poi| {
+            let radius = poi.properties.get("radius_m").and_then(Value::as_f64).unwrap_or(DEFAULT_RADIUS_M);
+            let distance_m = poi.geometry.distance_m(lon, lat);
+            (distance_m <= radius).then(|| {
+                let certain = if distance_m == 0.0 && poi.geometry.contains(lon, lat) {
+                    accuracy_m <= poi.geometry.edge_distance_m(lon, lat) + radius
+                } else {
+                    distance_m + accuracy_m <= radius
+                };
+                PoiMatch {
+                    id: poi.id.clone(),
+                    name: poi.properties.get("name").and_then(Value::as_str).map(str::to_string),
+                    category: CATEGORY_KEYS
+                        .iter()
+                        .find_map(|key| poi.properties.get(*key).and_then(Value::as_str))
+                        .map(str::to_string),
+                    distance_m,
+                    certain,
+                }
+            })
+        })
+        .collect();
+    found.sort_by(|a, b| a.distance_m.total_cmp(&b.distance_m));
+    found
+}
+
+/// Match an array of experiences, or a single `{latitude, longitude}`
+/// location, against POI GeoJSON; an `accuracy` in metres on the location
+/// decides whether each match is `certain`
+/// Returns the experiences with matches (`[{id, name, category, distance_m,
+/// certain}]`, nearest first) added as `context.pois`, or for a location
+/// the matches themselves, as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn match_context(experiences_or_location_json: &str, poi_geojson: &str) -> Result<String, Error> {
+    let input: Value = from_json(experiences_or_location_json, "experiences_or_location_json")?;
+    let geojson: Value = from_json(poi_geojson, "poi_geojson")?;
+    let pois = parse_features(&geojson).map_err(Error::invalid)?;
+
+    let output = match input {
+        Value::Array(mut experiences) => {
+            for exp in &mut experiences {
+                let Some((lat, lon)) = coordinates(exp) else { continue };
+                let found = matches_at(&pois, lat, lon, accuracy(exp));
+                if let Some(context) = exp.get_mut("context").and_then(Value::as_object_mut) {
+                    let found = serde_json::to_value(found).map_err(|e| Error::new(ErrorKind::Serialization, e))?;
+                    context.insert("pois".to_string(), found);
+                }
+            }
+            Value::Array(experiences)
+        }
+        location => {
+            let lat = location.get("latitude").and_then(Value::as_f64);
+            let lon = location.get("longitude").and_then(Value::as_f64);
+            let (Some(lat), Some(lon)) = (lat, lon) else {
+                return Err(Error::invalid("expected an array of experiences or {latitude, longitude}"));
+            };
+            serde_json::to_value(matches_at(&pois, lat, lon, accuracy_of(&location))).map_err(|e| Error::new(ErrorKind::Serialization, e))?
+        }
+    };
+    to_json(&output)
+}
@@ -0,0 +1,369 @@
+//! Privacy transforms applied before data leaves the learner's device
+//!
+//! Paths are dotted (`learner.id`, `context.connections.id`); arrays met
+//! along a path are traversed element by element.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::OnceLock;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::crypto::{hmac_sha256, to_hex};
+use crate::error::{from_json, to_json, Error};
+use crate::identity::canonical_learner_id;
+
+const PSEUDONYM_PREFIX: &str = "pseud-";
+/// Bytes of the HMAC kept in a pseudonym (128 bits)
+const PSEUDONYM_BYTES: usize = 16;
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct PseudonymizeOptions {
+    /// Identifier fields to replace with pseudonyms
+    fields: Vec<String>,
+    /// Fields removed entirely
+    strip_fields: Vec<String>,
+}
+
+impl Default for PseudonymizeOptions {
+    fn default() -> Self {
+        Self {
+            fields: vec!["learner.id".to_string(), "context.connections.id".to_string()],
+            strip_fields: Vec::new(),
+        }
+    }
+}
+
+/// Apply `f` to every value reachable at `path`
+pub(crate) fn for_each_at_path_mut(value: &mut Value, path: &[&str], f: &mut dyn FnMut(&mut Value)) {
+    match value {
+        Value::Array(items) => items.iter_mut().for_each(|item| for_each_at_path_mut(item, path, f)),
+        Value::Object(map) => match path.split_first() {
+            None => f(value),
+            Some((key, rest)) => {
+                if let Some(child) = map.get_mut(*key) {
+                    for_each_at_path_mut(child, rest, f);
+                }
+            }
+        },
+        _ if path.is_empty() => f(value),
+        _ => {}
+    }
+}
+
+/// Remove every value reachable at `path`
+pub(crate) fn remove_at_path(value: &mut Value, path: &[&str]) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    for_each_at_path_mut(value, parents, &mut |parent| {
+        if let Value::Object(map) = parent {
+            map.remove(*last);
+        }
+    });
+}
+
+/// Deterministic keyed pseudonym for an identifier. Ids are canonicalized
+/// first so equivalent DID / IRI spellings map to the same pseudonym.
+pub(crate) fn pseudonym(key: &[u8], id: &str) -> String {
+    let mac = hmac_sha256(key, canonical_learner_id(id).as_bytes());
+    format!("{}{}", PSEUDONYM_PREFIX, to_hex(&mac[..PSEUDONYM_BYTES]))
+}
+
+fn pseudonymize_all(experiences: &mut [Value], key: &[u8], options: &PseudonymizeOptions) {
+    for exp in experiences.iter_mut() {
+        for field in &options.strip_fields {
+            remove_at_path(exp, &field.split('.').collect::<Vec<_>>());
+        }
+        for field in &options.fields {
+            for_each_at_path_mut(exp, &field.split('.').collect::<Vec<_>>(), &mut |value| {
+                if let Some(id) = value.as_str().filter(|id| !id.is_empty()) {
+                    *value = Value::from(pseudonym(key, id));
+                }
+            });
+        }
+    }
+}
+
+/// Replace identifiers with HMAC-SHA256 pseudonyms
+/// `options_json` may set `fields` (default `learner.id` and
+/// `context.connections.id`) and `strip_fields`; the same key always yields
+/// the same pseudonym, so longitudinal analysis still works
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn pseudonymize(experiences_json: &str, key: &str, options_json: &str) -> Result<String, Error> {
+    if key.is_empty() {
+        return Err(Error::invalid("pseudonymization key must not be empty"));
+    }
+    let mut experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let options: PseudonymizeOptions = if options_json.trim().is_empty() {
+        PseudonymizeOptions::default()
+    } else {
+        from_json(options_json, "options_json")?
+    };
+
+    pseudonymize_all(&mut experiences, key.as_bytes(), &options);
+    to_json(&experiences)
+}
+
+/// Free-text fields scanned for PII
+const TEXT_FIELDS: &[&str] = &[
+    "experience.description",
+    "context.situation",
+    "experience.artifacts.description",
+    "experience.outcome.next_questions",
+    "experience.outcome.connections_made",
+];
+
+/// Findings below this confidence are reported by `scan` but not masked by
+/// default in `redact`
+const DEFAULT_REDACT_CONFIDENCE: f64 = 0.5;
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum PiiKind {
+    Email,
+    Phone,
+    CardNumber,
+    IpAddress,
+    Postcode,
+    Name,
+}
+
+impl PiiKind {
+    fn mask(self) -> &'static str {
+        match self {
+            PiiKind::Email => "[EMAIL]",
+            PiiKind::Phone => "[PHONE]",
+            PiiKind::CardNumber => "[CARD]",
+            PiiKind::IpAddress => "[IP]",
+            PiiKind::Postcode => "[POSTCODE]",
+            PiiKind::Name => "[NAME]",
+        }
+    }
+}
+
+struct Match {
+    kind: PiiKind,
+    start: usize,
+    end: usize,
+    confidence: f64,
+}
+
+/// One PII finding. Offsets are UTF-16 code units into the original string,
+/// i.e. directly usable as JS string indices. The matched text itself is
+/// deliberately not echoed into the report.
+#[derive(Serialize)]
+struct PiiFinding {
+    id: String,
+    field: String,
+    kind: PiiKind,
+    start: usize,
+    end: usize,
+    confidence: f64,
+}
+
+struct Detectors {
+    email: Regex,
+    phone: Regex,
+    card: Regex,
+    ip: Regex,
+    postcode: Regex,
+    honorific_name: Regex,
+    introduced_name: Regex,
+    capitalized_pair: Regex,
+}
+
+fn detectors() -> &'static Detectors {
+    static DETECTORS: OnceLock<Detectors> = OnceLock::new();
+    DETECTORS.get_or_init(|| {
+        let re = |pattern: &str| Regex::new(pattern).expect("PII pattern compiles");
+        Detectors {
+            email: re(r"(?-u)\b[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}\b"),
+            phone: re(r"(?-u)(?:\+[0-9]{1,3}[ .-]?)?(?:\([0-9]{2,5}\)[ .-]?)?[0-9]{2,6}(?:[ .-]?[0-9]{2,6}){1,4}\b"),
+            card: re(r"(?-u)\b[0-9](?:[ -]?[0-9]){12,18}\b"),
+            ip: re(r"(?-u)\b(?:[0-9]{1,3}\.){3}[0-9]{1,3}\b"),
+            postcode: re(r"(?-u)\b[A-Z]{1,2}[0-9][A-Z0-9]? ?[0-9][A-Z]{2}\b"),
+            honorific_name: re(r"(?-u:\b)(?:Mr|Mrs|Ms|Mx|Miss|Dr|Prof)\.? \p{Lu}\p{Ll}+(?: \p{Lu}\p{Ll}+)?"),
+            introduced_name: re(
+                r"(?-u:\b)(?:named|called|with|friend|teacher|mentor|mum|mom|dad|sister|brother) (\p{Lu}\p{Ll}+(?: \p{Lu}\p{Ll}+)?)",
+            ),
+            capitalized_pair: re(r"(?-u:\b)\p{Lu}\p{Ll}+ \p{Lu}\p{Ll}+"),
+        }
+    })
+}
+
+fn digit_count(s: &str) -> usize {
+    s.bytes().filter(u8::is_ascii_digit).count()
+}
+
+fn luhn_valid(s: &str) -> bool {
+    let digits: Vec<u32> = s.chars().filter_map(|c| c.to_digit(10)).collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| if i % 2 == 1 { if d * 2 > 9 { d * 2 - 9 } else { d * 2 } } else { d })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// Is the byte offset at the start of a sentence (only whitespace since the
+/// last terminator)?
+fn sentence_start(text: &str, offset: usize) -> bool {
+    text[..offset]
+        .trim_end()
+        .chars()
+        .last()
+        .is_none_or(|c| matches!(c, '.' | '!' | '?' | '\n'))
+}
+
+fn find_pii(text: &str) -> Vec<Match> {
+    let d = detectors();
+    let mut matches = Vec::new();
+    let mut push = |kind, start, end, confidence| {
+        matches.push(Match {
+            kind,
+            start,
+            end,
+            confidence,
+        })
+    };
+
+    for m in d.email.find_iter(text) {
+        push(PiiKind::Email, m.start(), m.end(), 0.99);
+    }
+    for m in d.card.find_iter(text) {
+        if luhn_valid(m.as_str()) {
+            push(PiiKind::CardNumber, m.start(), m.end(), 0.9);
+        }
+    }
+    for m in d.ip.find_iter(text) {
+        if m.as_str().split('.').all(|octet| octet.parse::<u8>().is_ok()) {
+            push(PiiKind::IpAddress, m.start(), m.end(), 0.8);
+        }
+    }
+    for m in d.phone.find_iter(text) {
+        // Short digit runs are years, counts and measurements, not phones
+        if (9..=15).contains(&digit_count(m.as_str())) {
+            push(PiiKind::Phone, m.start(), m.end(), 0.75);
+        }
+    }
+    for m in d.postcode.find_iter(text) {
+        push(PiiKind::Postcode, m.start(), m.end(), 0.7);
+    }
+    for m in d.honorific_name.find_iter(text) {
+        push(PiiKind::Name, m.start(), m.end(), 0.85);
+    }
+    for caps in d.introduced_name.captures_iter(text) {
+        if let Some(name) = caps.get(1) {
+            push(PiiKind::Name, name.start(), name.end(), 0.6);
+        }
+    }
+    for m in d.capitalized_pair.find_iter(text) {
+        if !sentence_start(text, m.start()) {
+            push(PiiKind::Name, m.start(), m.end(), 0.35);
+        }
+    }
+
+    // Resolve overlaps: the most confident (then longest) match wins
+    matches.sort_by(|a, b| {
+        b.confidence
+            .total_cmp(&a.confidence)
+            .then((b.end - b.start).cmp(&(a.end - a.start)))
+    });
+    let mut kept: Vec<Match> = Vec::new();
+    for m in matches {
+        if kept.iter().all(|k| m.end <= k.start || m.start >= k.end) {
+            kept.push(m);
+        }
+    }
+    kept.sort_by_key(|m| m.start);
+    kept
+}
+
+fn utf16_offset(text: &str, byte_offset: usize) -> usize {
+    text[..byte_offset].encode_utf16().count()
+}
+
+fn mask(text: &str, matches: &[&Match]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for m in matches {
+        out.push_str(&text[last..m.start]);
+        out.push_str(m.kind.mask());
+        last = m.end;
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+/// Scan (and optionally mask) every free-text field of one experience
+fn scan_experience(exp: &mut Value, redact_at: Option<f64>, findings: &mut Vec<PiiFinding>) {
+    let id = exp.get("id").and_then(Value::as_str).unwrap_or_default().to_string();
+    for field in TEXT_FIELDS {
+        let path: Vec<&str> = field.split('.').collect();
+        for_each_at_path_mut(exp, &path, &mut |value| {
+            let Some(text) = value.as_str() else {
+                return;
+            };
+            let matches = find_pii(text);
+            for m in &matches {
+                findings.push(PiiFinding {
+                    id: id.clone(),
+                    field: field.to_string(),
+                    kind: m.kind,
+                    start: utf16_offset(text, m.start),
+                    end: utf16_offset(text, m.end),
+                    confidence: m.confidence,
+                });
+            }
+            if let Some(threshold) = redact_at {
+                let masked: Vec<&Match> = matches.iter().filter(|m| m.confidence >= threshold).collect();
+                if !masked.is_empty() {
+                    *value = Value::from(mask(text, &masked));
+                }
+            }
+        });
+    }
+}
+
+#[derive(Serialize)]
+struct RedactionResult {
+    experiences: Vec<Value>,
+    report: Vec<PiiFinding>,
+}
+
+/// Flag likely PII in free-text fields
+/// Returns `[{id, field, kind, start, end, confidence}]` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn scan(experiences_json: &str) -> Result<String, Error> {
+    let mut experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+
+    let mut findings = Vec::new();
+    for exp in &mut experiences {
+        scan_experience(exp, None, &mut findings);
+    }
+    to_json(&findings)
+}
+
+/// Mask likely PII at or above `min_confidence` (pass a negative value for
+/// the default of 0.5)
+/// Returns `{experiences, report}` as JSON; report offsets refer to the
+/// original, unmasked text
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn redact(experiences_json: &str, min_confidence: f64) -> Result<String, Error> {
+    let mut experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let threshold = if min_confidence < 0.0 {
+        DEFAULT_REDACT_CONFIDENCE
+    } else {
+        min_confidence
+    };
+
+    let mut report = Vec::new();
+    for exp in &mut experiences {
+        scan_experience(exp, Some(threshold), &mut report);
+    }
+    to_json(&RedactionResult { experiences, report })
+}
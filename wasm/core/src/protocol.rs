@@ -0,0 +1,185 @@
+//! Sync wire protocol frame codec
+//!
+//! Every frame is an 8-byte header followed by a CBOR payload:
+//!
+//! ```text
+//! 0      2         3       4                8
+//! +------+---------+-------+----------------+-------------------+
+//! | "UB" | version | type  | length (u32 BE)| payload (CBOR)    |
+//! +------+---------+-------+----------------+-------------------+
+//! ```
+//!
+//! Frame types: `hello` (1), `digest` (2), `delta` (3), `ack` (4),
+//! `error` (5). On the JS side frames are plain objects tagged with `type`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+
+pub(crate) const MAGIC: [u8; 2] = *b"UB";
+pub(crate) const PROTOCOL_VERSION: u8 = 1;
+pub(crate) const HEADER_LEN: usize = 8;
+/// Frames larger than this are rejected rather than buffered
+pub(crate) const MAX_PAYLOAD_LEN: usize = 16 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Hello {
+    pub(crate) node_id: String,
+    #[serde(default)]
+    pub(crate) capabilities: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct DigestEntry {
+    pub(crate) id: String,
+    pub(crate) hash: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Digest {
+    pub(crate) entries: Vec<DigestEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Delta {
+    pub(crate) seq: u64,
+    #[serde(default)]
+    pub(crate) upserts: Vec<Value>,
+    #[serde(default)]
+    pub(crate) removed: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Ack {
+    pub(crate) seq: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ErrorFrame {
+    pub(crate) code: u16,
+    pub(crate) message: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum Frame {
+    Hello(Hello),
+    Digest(Digest),
+    Delta(Delta),
+    Ack(Ack),
+    Error(ErrorFrame),
+}
+
+fn cbor<T: Serialize>(value: &T) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    ciborium::ser::into_writer(value, &mut out).map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
+fn from_cbor<T: for<'de> Deserialize<'de>>(bytes: &[u8]) -> Result<T, String> {
+    ciborium::de::from_reader(bytes).map_err(|e| e.to_string())
+}
+
+pub(crate) fn encode(frame: &Frame) -> Result<Vec<u8>, String> {
+    let (kind, payload) = match frame {
+        Frame::Hello(body) => (1u8, cbor(body)?),
+        Frame::Digest(body) => (2, cbor(body)?),
+        Frame::Delta(body) => (3, cbor(body)?),
+        Frame::Ack(body) => (4, cbor(body)?),
+        Frame::Error(body) => (5, cbor(body)?),
+    };
+    if payload.len() > MAX_PAYLOAD_LEN {
+        return Err(format!("frame payload exceeds {} bytes", MAX_PAYLOAD_LEN));
+    }
+
+    let mut out = Vec::with_capacity(HEADER_LEN + payload.len());
+    out.extend_from_slice(&MAGIC);
+    out.push(PROTOCOL_VERSION);
+    out.push(kind);
+    out.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    out.extend_from_slice(&payload);
+    Ok(out)
+}
+
+/// Decode one frame from the front of `buf`. Returns `Ok(None)` if more bytes
+/// are needed, otherwise the frame and the number of bytes it occupied.
+pub(crate) fn decode(buf: &[u8]) -> Result<Option<(Frame, usize)>, String> {
+    if buf.len() < HEADER_LEN {
+        return Ok(None);
+    }
+    if buf[0..2] != MAGIC {
+        return Err("bad frame magic".to_string());
+    }
+    if buf[2] != PROTOCOL_VERSION {
+        return Err(format!("unsupported protocol version {}", buf[2]));
+    }
+    let len = u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]) as usize;
+    if len > MAX_PAYLOAD_LEN {
+        return Err(format!("frame payload exceeds {} bytes", MAX_PAYLOAD_LEN));
+    }
+    let Some(payload) = buf.get(HEADER_LEN..HEADER_LEN + len) else {
+        return Ok(None);
+    };
+
+    let frame = match buf[3] {
+        1 => Frame::Hello(from_cbor(payload)?),
+        2 => Frame::Digest(from_cbor(payload)?),
+        3 => Frame::Delta(from_cbor(payload)?),
+        4 => Frame::Ack(from_cbor(payload)?),
+        5 => Frame::Error(from_cbor(payload)?),
+        other => return Err(format!("unknown frame type {}", other)),
+    };
+    Ok(Some((frame, HEADER_LEN + len)))
+}
+
+/// Encode a frame object (`{"type": "hello", ...}`) to wire bytes
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn encode_frame(frame_json: &str) -> Result<Vec<u8>, Error> {
+    let frame: Frame = from_json(frame_json, "frame_json")?;
+    encode(&frame).map_err(Error::invalid)
+}
+
+/// Incremental decoder for a byte stream of frames
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl FrameDecoder {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> Self {
+        Self { buffer: Vec::new() }
+    }
+
+    /// Append received bytes and return every complete frame as a JSON array
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn push(&mut self, bytes: &[u8]) -> Result<String, Error> {
+        self.buffer.extend_from_slice(bytes);
+
+        let mut frames = Vec::new();
+        let mut offset = 0;
+        while let Some((frame, used)) = decode(&self.buffer[offset..]).map_err(Error::parse)? {
+            frames.push(frame);
+            offset += used;
+        }
+        self.buffer.drain(..offset);
+
+        to_json(&frames)
+    }
+
+    /// Bytes received but not yet part of a complete frame
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,130 @@
+//! Simplifying dense domain networks for display
+//!
+//! A long log links nearly every domain to every other, and the raw
+//! co-occurrence graph draws as a hairball. [`NetworkOptions`] trims it in
+//! this order:
+//!
+//! 1. domains seen fewer than `min_node_size` times are dropped, with their edges;
+//! 2. edges with fewer than `min_edge_weight` co-occurrences are dropped;
+//! 3. with `normalize`, each edge gets a `score`: pointwise mutual information
+//!    `ln(P(a, b) / (P(a) P(b)))` (`pmi`), or that divided by `-ln P(a, b)`
+//!    to fall in [-1, 1] (`npmi`), probabilities taken over the experiences
+//!    that list domains. Unlike raw counts, these do not favour pairs that
+//!    are merely both common;
+//! 4. with `top_k`, an edge is kept only if it is among the `top_k`
+//!    strongest of at least one of its ends, by score when normalised and by
+//!    weight otherwise.
+//!
+//! Node sizes and edge weights stay raw counts, so the result still works
+//! wherever a [`crate::generate_domain_network`] result does. The same
+//! options are taken by the streaming, CBOR, MessagePack and tenant-scoped
+//! builders through their `_with_options` variants, and
+//! [`crate::export::export_network_csv`] carries `score` as an extra column.
+//! The Cytoscape, D3 and GraphML shapes in [`crate::graph_formats`] always
+//! describe the full network.
+
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::numeric::ln;
+use crate::{accumulate_network, DomainNetwork, Experience, NetworkAccumulator};
+
+#[derive(Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Normalization {
+    #[default]
+    None,
+    Pmi,
+    Npmi,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct NetworkOptions {
+    min_edge_weight: usize,
+    /// Strongest edges kept per node; 0 keeps all
+    top_k: usize,
+    min_node_size: usize,
+    normalize: Normalization,
+}
+
+/// Apply `options` to a network built from `experiences` experiences with
+/// domains
+fn simplify(network: &mut DomainNetwork, experiences: usize, options: &NetworkOptions) {
+    network.nodes.retain(|node| node.size >= options.min_node_size);
+    network.nodes.sort_by(|a, b| a.id.cmp(&b.id));
+    let sizes: HashMap<&str, usize> = network.nodes.iter().map(|node| (node.id.as_str(), node.size)).collect();
+    network.edges.retain(|edge| {
+        edge.weight >= options.min_edge_weight
+            && sizes.contains_key(edge.source.as_str())
+            && sizes.contains_key(edge.target.as_str())
+    });
+    network.edges.sort_by(|a, b| (&a.source, &a.target).cmp(&(&b.source, &b.target)));
+
+    if options.normalize != Normalization::None {
+        let total = experiences as f64;
+        for edge in &mut network.edges {
+            let (joint, a, b) = (edge.weight as f64, sizes[edge.source.as_str()] as f64, sizes[edge.target.as_str()] as f64);
+            let pmi = ln(joint * total / (a * b));
+            edge.score = Some(match options.normalize {
+                Normalization::Npmi if joint >= total => 1.0,
+                Normalization::Npmi => pmi / -ln(joint / total),
+                _ => pmi,
+            });
+        }
+    }
+
+    if options.top_k > 0 {
+        let strength = |i: usize| network.edges[i].score.unwrap_or(network.edges[i].weight as f64);
+        let mut incident: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, edge) in network.edges.iter().enumerate() {
+            incident.entry(edge.source.as_str()).or_default().push(i);
+            incident.entry(edge.target.as_str()).or_default().push(i);
+        }
+        let mut keep = HashSet::new();
+        for mut edges in incident.into_values() {
+            // Edges are sorted by endpoints, so ties go to the first pair
+            edges.sort_by(|&x, &y| strength(y).total_cmp(&strength(x)).then(x.cmp(&y)));
+            keep.extend(edges.into_iter().take(options.top_k));
+        }
+        let mut index = 0;
+        network.edges.retain(|_| {
+            index += 1;
+            keep.contains(&(index - 1))
+        });
+    }
+}
+
+/// Network options from JSON; an empty string means the full network
+pub(crate) fn parse_options(options_json: &str) -> Result<NetworkOptions, Error> {
+    if options_json.trim().is_empty() {
+        Ok(NetworkOptions::default())
+    } else {
+        from_json(options_json, "options_json")
+    }
+}
+
+/// The accumulated network with `options` applied
+pub(crate) fn simplified(acc: NetworkAccumulator, options: &NetworkOptions) -> DomainNetwork {
+    let experiences = acc.experiences;
+    let mut network = acc.into_network();
+    simplify(&mut network, experiences, options);
+    network
+}
+
+/// `generate_domain_network` simplified for display
+/// `options_json` sets `min_edge_weight`, `top_k` (strongest edges per
+/// node), `min_node_size` and `normalize` (`pmi` or `npmi`), or is empty
+/// for the full network
+/// Returns `{nodes, edges}` as JSON, each edge with a `score` when
+/// normalised
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn generate_domain_network_with_options(experiences_json: &str, options_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Experience> = from_json(experiences_json, "experiences_json")?;
+    let options = parse_options(options_json)?;
+
+    to_json(&simplified(accumulate_network(&experiences), &options))
+}
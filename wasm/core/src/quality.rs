@@ -0,0 +1,273 @@
+//! Data quality scoring
+//!
+//! [`quality_report`] scores every record from 0 to 1 on three axes and
+//! tells coordinators what to chase first:
+//!
+//! - completeness: coordinates, domains, a description of useful length,
+//!   a location name and a type,
+//! - consistency: validation errors and warnings, unparseable or future
+//!   timestamps and duplicate ids,
+//! - timeliness: how long after the event the record was created, read from
+//!   ULID and UUIDv7 ids (other ids have no timeliness score).
+//!
+//! A record's score is the mean of its axes; the dataset score is the mean
+//! over records. Suggestions rank problems by how many records they affect.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::ids::id_created_ms;
+use crate::numeric;
+use crate::severity::Severity;
+use crate::time::{parse_timestamp, MS_PER_DAY, MS_PER_MINUTE};
+use crate::{Experience, ExperienceValidator};
+
+/// Validation rules already scored under completeness
+const COMPLETENESS_RULES: &[&str] = &[
+    "coordinates.missing",
+    "description.required",
+    "description.short",
+    "location.name.required",
+    "type.required",
+];
+
+/// Remediation advice per problem code
+const ADVICE: &[(&str, &str)] = &[
+    ("coordinates.missing", "Enable location capture or add coordinates to records"),
+    ("domains.missing", "Tag records with at least one learning domain"),
+    ("description.missing", "Ask learners to describe what they did"),
+    ("description.short", "Encourage fuller descriptions of each experience"),
+    ("location.name.missing", "Name the place where the experience happened"),
+    ("type.missing", "Set the experience type"),
+    ("timestamp.invalid", "Record timestamps as RFC 3339 date-times"),
+    ("timestamp.future", "Check device clocks: the event is dated after the record was created"),
+    ("timestamp.late", "Log experiences closer to when they happen"),
+    ("id.duplicate", "Give every record its own id"),
+    ("record.schema", "Fix records that do not match the experience schema"),
+];
+
+/// Ids may be minted slightly before the event is stamped
+const CLOCK_SKEW_MS: i64 = 5 * MS_PER_MINUTE;
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct QualityOptions {
+    /// Description length that earns full marks; under half of it is
+    /// reported as short
+    good_description_chars: usize,
+    /// Records created within this many days of the event are on time
+    on_time_days: f64,
+    /// Records created this many days or more after the event score 0
+    max_lag_days: f64,
+    top_suggestions: usize,
+}
+
+impl Default for QualityOptions {
+    fn default() -> Self {
+        Self {
+            good_description_chars: 80,
+            on_time_days: 1.0,
+            max_lag_days: 30.0,
+            top_suggestions: 5,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RecordQuality<'a> {
+    index: usize,
+    id: Option<&'a str>,
+    score: f64,
+    completeness: f64,
+    consistency: f64,
+    timeliness: Option<f64>,
+    problems: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Suggestion {
+    code: String,
+    records: usize,
+    advice: String,
+}
+
+#[derive(Serialize)]
+struct QualityReport<'a> {
+    records: usize,
+    score: Option<f64>,
+    completeness: Option<f64>,
+    consistency: Option<f64>,
+    timeliness: Option<f64>,
+    /// Records scoring at least 0.8, from 0.5 and below 0.5
+    good: usize,
+    fair: usize,
+    poor: usize,
+    suggestions: Vec<Suggestion>,
+    scores: Vec<RecordQuality<'a>>,
+}
+
+fn present(exp: &Value, path: &str) -> bool {
+    match lookup(exp, path) {
+        None | Some(Value::Null) => false,
+        Some(Value::String(s)) => !s.trim().is_empty(),
+        Some(Value::Array(items)) => !items.is_empty(),
+        Some(_) => true,
+    }
+}
+
+fn completeness(exp: &Value, options: &QualityOptions, problems: &mut Vec<String>) -> f64 {
+    let mut score = 0.0;
+    for (path, weight, code) in [
+        ("context.location.coordinates", 0.25, "coordinates.missing"),
+        ("experience.domains", 0.25, "domains.missing"),
+        ("context.location.name", 0.1, "location.name.missing"),
+        ("experience.type", 0.1, "type.missing"),
+    ] {
+        if present(exp, path) {
+            score += weight;
+        } else {
+            problems.push(code.to_string());
+        }
+    }
+    let length = lookup(exp, "experience.description").and_then(Value::as_str).map_or(0, |d| d.trim().chars().count());
+    let good = options.good_description_chars.max(1);
+    if length == 0 {
+        problems.push("description.missing".to_string());
+    } else if length * 2 < good {
+        problems.push("description.short".to_string());
+    }
+    score + 0.3 * (length.min(good) as f64 / good as f64)
+}
+
+fn consistency(exp: &Value, validator: &ExperienceValidator, duplicate: bool, problems: &mut Vec<String>) -> f64 {
+    let mut penalty: f64 = 0.0;
+    match serde_json::from_value::<Experience>(exp.clone()) {
+        Ok(parsed) => {
+            for issue in validator.validate_experience(&parsed).issues {
+                if COMPLETENESS_RULES.contains(&issue.code.as_str()) {
+                    continue;
+                }
+                penalty += if issue.severity == Severity::Error { 0.25 } else { 0.1 };
+                problems.push(issue.code);
+            }
+        }
+        Err(_) => {
+            penalty += 0.25;
+            problems.push("record.schema".to_string());
+        }
+    }
+    let timestamp = lookup(exp, "timestamp").and_then(Value::as_str);
+    if timestamp.is_some_and(|t| parse_timestamp(t).is_none()) {
+        penalty += 0.25;
+        problems.push("timestamp.invalid".to_string());
+    }
+    if duplicate {
+        penalty += 0.25;
+        problems.push("id.duplicate".to_string());
+    }
+    (1.0 - penalty).max(0.0)
+}
+
+fn timeliness(exp: &Value, options: &QualityOptions, problems: &mut Vec<String>) -> Option<f64> {
+    let created = lookup(exp, "id").and_then(Value::as_str).and_then(id_created_ms)?;
+    let event = lookup(exp, "timestamp").and_then(Value::as_str).and_then(parse_timestamp)?;
+    let lag = created - event;
+    if lag < -CLOCK_SKEW_MS {
+        problems.push("timestamp.future".to_string());
+        return Some(0.0);
+    }
+    let lag_days = lag.max(0) as f64 / MS_PER_DAY as f64;
+    if lag_days <= options.on_time_days {
+        return Some(1.0);
+    }
+    problems.push("timestamp.late".to_string());
+    Some((1.0 - (lag_days - options.on_time_days) / (options.max_lag_days - options.on_time_days)).clamp(0.0, 1.0))
+}
+
+/// Quality scores per record and for the dataset, with the most common
+/// problems and what to do about them
+/// `options_json` sets `good_description_chars`, `on_time_days`,
+/// `max_lag_days` and `top_suggestions` (empty for 80, 1, 30 and 5)
+/// Returns `{records, score, completeness, consistency, timeliness, good,
+/// fair, poor, suggestions: [{code, records, advice}], scores: [{index,
+/// id, score, completeness, consistency, timeliness, problems}]}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn quality_report(experiences_json: &str, options_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let options: QualityOptions = if options_json.trim().is_empty() {
+        QualityOptions::default()
+    } else {
+        from_json(options_json, "options_json")?
+    };
+    if !(options.on_time_days >= 0.0 && options.max_lag_days > options.on_time_days) {
+        return Err(Error::invalid("max_lag_days must be greater than on_time_days, which must not be negative"));
+    }
+
+    let validator = ExperienceValidator::new(false);
+    let mut id_counts: HashMap<&str, usize> = HashMap::new();
+    for id in experiences.iter().filter_map(|exp| lookup(exp, "id").and_then(Value::as_str)) {
+        *id_counts.entry(id).or_insert(0) += 1;
+    }
+
+    let scores: Vec<RecordQuality> = experiences
+        .iter()
+        .enumerate()
+        .map(|(index, exp)| {
+            let id = lookup(exp, "id").and_then(Value::as_str);
+            let duplicate = id.is_some_and(|id| !id.is_empty() && id_counts[id] > 1);
+            let mut problems = Vec::new();
+            let completeness = completeness(exp, &options, &mut problems);
+            let consistency = consistency(exp, &validator, duplicate, &mut problems);
+            let timeliness = timeliness(exp, &options, &mut problems);
+            let axes: Vec<f64> = [Some(completeness), Some(consistency), timeliness].into_iter().flatten().collect();
+            problems.sort();
+            problems.dedup();
+            RecordQuality {
+                index,
+                id,
+                score: numeric::mean(&axes).unwrap_or(0.0),
+                completeness,
+                consistency,
+                timeliness,
+                problems,
+            }
+        })
+        .collect();
+
+    let mut affected: BTreeMap<&str, usize> = BTreeMap::new();
+    for code in scores.iter().flat_map(|s| s.problems.iter()) {
+        *affected.entry(code).or_insert(0) += 1;
+    }
+    let mut suggestions: Vec<Suggestion> = affected
+        .into_iter()
+        .map(|(code, records)| Suggestion {
+            code: code.to_string(),
+            records,
+            advice: ADVICE
+                .iter()
+                .find(|(c, _)| *c == code)
+                .map_or_else(|| format!("Fix records failing the {} rule", code), |(_, advice)| advice.to_string()),
+        })
+        .collect();
+    suggestions.sort_by(|a, b| b.records.cmp(&a.records).then_with(|| a.code.cmp(&b.code)));
+    suggestions.truncate(options.top_suggestions);
+
+    let mean_of = |f: &dyn Fn(&RecordQuality) -> Option<f64>| numeric::mean(&scores.iter().filter_map(f).collect::<Vec<_>>());
+    to_json(&QualityReport {
+        records: scores.len(),
+        score: mean_of(&|s| Some(s.score)),
+        completeness: mean_of(&|s| Some(s.completeness)),
+        consistency: mean_of(&|s| Some(s.consistency)),
+        timeliness: mean_of(&|s| s.timeliness),
+        good: scores.iter().filter(|s| s.score >= 0.8).count(),
+        fair: scores.iter().filter(|s| (0.5..0.8).contains(&s.score)).count(),
+        poor: scores.iter().filter(|s| s.score < 0.5).count(),
+        suggestions,
+        scores,
+    })
+}
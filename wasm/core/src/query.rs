@@ -0,0 +1,291 @@
+//! Filter DSL over experience collections
+//!
+//! A query is a JSON filter expression:
+//!
+//! ```json
+//! {
+//!   "filter": {"and": [
+//!     {"field": {"path": "experience.type", "op": "eq", "value": "observation"}},
+//!     {"date_range": {"from": "2024-01-01", "to": "2024-07-01"}},
+//!     {"domain": "ecology"},
+//!     {"near": {"latitude": 51.5, "longitude": -0.12, "radius_m": 2000}},
+//!     {"not": {"domains_any": ["art", "music"]}}
+//!   ]},
+//!   "ids_only": false
+//! }
+//! ```
+//!
+//! Filters evaluate against the raw JSON so any field, including extension
+//! fields the typed schema does not know about, can be queried.
+
+use serde::Deserialize;
+use serde_json::Value;
+use std::cmp::Ordering;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::geo::haversine_m;
+use crate::numeric::coordinate;
+use crate::tenancy::TenantScope;
+use crate::time::parse_timestamp;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Filter {
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+    Field(FieldCondition),
+    DateRange(DateRange),
+    Domain(String),
+    DomainsAny(Vec<String>),
+    DomainsAll(Vec<String>),
+    Near(GeoRadius),
+}
+
+#[derive(Deserialize)]
+pub(crate) struct FieldCondition {
+    path: String,
+    op: CompareOp,
+    #[serde(default)]
+    value: Value,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    Contains,
+    Exists,
+}
+
+/// Half-open `[from, to)` range over `timestamp`; either bound may be omitted
+#[derive(Deserialize)]
+pub(crate) struct DateRange {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct GeoRadius {
+    latitude: f64,
+    longitude: f64,
+    radius_m: f64,
+}
+
+#[derive(Deserialize)]
+struct Query {
+    filter: Filter,
+    #[serde(default)]
+    ids_only: bool,
+}
+
+pub(crate) fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64()?.partial_cmp(&y.as_f64()?),
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        (Value::Bool(x), Value::Bool(y)) => Some(x.cmp(y)),
+        _ => None,
+    }
+}
+
+pub(crate) fn domains(exp: &Value) -> impl Iterator<Item = &str> {
+    lookup(exp, "experience.domains")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+}
+
+pub(crate) fn coordinates(exp: &Value) -> Option<(f64, f64)> {
+    let coords = lookup(exp, "context.location.coordinates")?;
+    Some((
+        coordinate(coords.get("latitude")?.as_f64()?),
+        coordinate(coords.get("longitude")?.as_f64()?),
+    ))
+}
+
+/// Horizontal accuracy radius in metres of a coordinates object, under
+/// either spelling
+pub(crate) fn accuracy_of(coords: &Value) -> Option<f64> {
+    coords.get("accuracy").or_else(|| coords.get("accuracyMeters")).and_then(Value::as_f64).filter(|a| *a >= 0.0)
+}
+
+pub(crate) fn accuracy(exp: &Value) -> Option<f64> {
+    lookup(exp, "context.location.coordinates").and_then(accuracy_of)
+}
+
+/// Whether an experience's fix can place it within `radius_m`: its accuracy
+/// is no coarser than the radius and the position was not imputed. Fixes
+/// without an accuracy are taken as exact.
+pub(crate) fn precise_within(exp: &Value, radius_m: f64) -> bool {
+    accuracy(exp).is_none_or(|a| a <= radius_m)
+        && lookup(exp, "context.location.coordinates.source").and_then(Value::as_str) != Some("imputed")
+}
+
+impl FieldCondition {
+    fn matches(&self, exp: &Value) -> bool {
+        let actual = match lookup(exp, &self.path) {
+            Some(Value::Null) | None => return matches!(self.op, CompareOp::Ne),
+            Some(v) => v,
+        };
+        match self.op {
+            CompareOp::Exists => true,
+            CompareOp::Eq => actual == &self.value,
+            CompareOp::Ne => actual != &self.value,
+            CompareOp::Lt => compare(actual, &self.value) == Some(Ordering::Less),
+            CompareOp::Lte => matches!(compare(actual, &self.value), Some(Ordering::Less | Ordering::Equal)),
+            CompareOp::Gt => compare(actual, &self.value) == Some(Ordering::Greater),
+            CompareOp::Gte => matches!(compare(actual, &self.value), Some(Ordering::Greater | Ordering::Equal)),
+            CompareOp::Contains => match (actual, &self.value) {
+                (Value::Array(items), needle) => items.contains(needle),
+                (Value::String(haystack), Value::String(needle)) => haystack.contains(needle.as_str()),
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Filter {
+    /// Reject bounds that can never match (unparseable dates, negative radii)
+    /// up front instead of silently matching nothing
+    pub(crate) fn check(&self) -> Result<(), String> {
+        match self {
+            Filter::And(filters) | Filter::Or(filters) => filters.iter().try_for_each(Filter::check),
+            Filter::Not(inner) => inner.check(),
+            Filter::DateRange(range) => {
+                for bound in [&range.from, &range.to].into_iter().flatten() {
+                    parse_timestamp(bound).ok_or_else(|| format!("invalid date bound: {}", bound))?;
+                }
+                Ok(())
+            }
+            Filter::Near(near) if near.radius_m.is_nan() || near.radius_m < 0.0 => Err("near.radius_m must be non-negative".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn matches(&self, exp: &Value) -> bool {
+        match self {
+            Filter::And(filters) => filters.iter().all(|f| f.matches(exp)),
+            Filter::Or(filters) => filters.iter().any(|f| f.matches(exp)),
+            Filter::Not(inner) => !inner.matches(exp),
+            Filter::Field(cond) => cond.matches(exp),
+            Filter::DateRange(range) => {
+                let Some(ts) = exp.get("timestamp").and_then(Value::as_str).and_then(parse_timestamp) else {
+                    return false;
+                };
+                let after_from = range.from.as_deref().and_then(parse_timestamp).is_none_or(|from| ts >= from);
+                let before_to = range.to.as_deref().and_then(parse_timestamp).is_none_or(|to| ts < to);
+                after_from && before_to
+            }
+            Filter::Domain(domain) => domains(exp).any(|d| d == domain),
+            Filter::DomainsAny(wanted) => domains(exp).any(|d| wanted.iter().any(|w| w == d)),
+            Filter::DomainsAll(wanted) => wanted.iter().all(|w| domains(exp).any(|d| d == w)),
+            Filter::Near(near) => coordinates(exp).is_some_and(|(lat, lon)| {
+                haversine_m(near.latitude, near.longitude, lat, lon) <= near.radius_m
+            }),
+        }
+    }
+}
+
+fn run_query(experiences: Vec<Value>, query: &Query) -> Value {
+    let matching = experiences.into_iter().filter(|exp| query.filter.matches(exp));
+    if query.ids_only {
+        Value::Array(
+            matching
+                .map(|exp| exp.get("id").cloned().unwrap_or(Value::Null))
+                .collect(),
+        )
+    } else {
+        Value::Array(matching.collect())
+    }
+}
+
+fn parse_query(query_json: &str) -> Result<Query, Error> {
+    let query: Query = from_json(query_json, "query_json")?;
+    query.filter.check().map_err(Error::invalid)?;
+    Ok(query)
+}
+
+/// Filter experiences with a query expression
+/// Returns the matching experiences (or their ids with `ids_only`) as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn query_experiences(experiences_json: &str, query_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let query = parse_query(query_json)?;
+
+    to_json(&run_query(experiences, &query))
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl TenantScope {
+    /// Tenant-scoped `query_experiences`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn query_experiences(&self, experiences_json: &str, query_json: &str) -> Result<String, Error> {
+        let experiences = self.parse_values(experiences_json)?;
+        let query = parse_query(query_json)?;
+
+        to_json(&run_query(experiences, &query))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn experiences() -> Vec<Value> {
+        vec![
+            json!({"id": "a", "timestamp": "2024-01-01T00:00:00Z", "experience": {"type": "walk", "domains": ["art"]}}),
+            json!({"id": "b", "timestamp": "2024-06-30T23:59:59Z", "experience": {"type": "lab", "domains": ["ecology"]}}),
+            json!({"id": "c", "timestamp": "2024-07-01T00:00:00Z", "experience": {"type": "walk", "domains": ["ecology", "art"]}}),
+        ]
+    }
+
+    fn ids(query: Value) -> Value {
+        let query = parse_query(&json!({"filter": query, "ids_only": true}).to_string()).unwrap();
+        run_query(experiences(), &query)
+    }
+
+    #[test]
+    fn nesting_decides_grouping() {
+        let walk = json!({"field": {"path": "experience.type", "op": "eq", "value": "walk"}});
+        let (art, ecology) = (json!({"domain": "art"}), json!({"domain": "ecology"}));
+
+        // walk or (art and ecology)  vs  (walk or art) and ecology
+        assert_eq!(ids(json!({"or": [walk, {"and": [art, ecology]}]})), json!(["a", "c"]));
+        assert_eq!(ids(json!({"and": [{"or": [walk, art]}, ecology]})), json!(["c"]));
+        // not applies to its whole operand, not just the first term
+        assert_eq!(ids(json!({"not": {"and": [walk, art]}})), json!(["b"]));
+        assert_eq!(ids(json!({"and": [{"not": walk}, art]})), json!([]));
+        assert_eq!(ids(json!({"not": {"not": ecology}})), json!(["b", "c"]));
+        // Empty conjunctions match everything, empty disjunctions nothing
+        assert_eq!(ids(json!({"and": []})), json!(["a", "b", "c"]));
+        assert_eq!(ids(json!({"or": []})), json!([]));
+    }
+
+    #[test]
+    fn boundaries() {
+        assert_eq!(ids(json!({"date_range": {"from": "2024-01-01", "to": "2024-07-01"}})), json!(["a", "b"]));
+        assert_eq!(ids(json!({"date_range": {"from": "2024-07-01T00:00:00Z"}})), json!(["c"]));
+        assert_eq!(ids(json!({"field": {"path": "experience.mood", "op": "ne", "value": "calm"}})), json!(["a", "b", "c"]));
+        assert_eq!(ids(json!({"field": {"path": "timestamp", "op": "gte", "value": "2024-07-01T00:00:00Z"}})), json!(["c"]));
+        assert_eq!(ids(json!({"domains_all": ["art", "ecology"]})), json!(["c"]));
+
+        let near = |radius_m: f64| json!({"near": {"latitude": 0.0, "longitude": 0.0, "radius_m": radius_m}});
+        let at = json!({"id": "x", "context": {"location": {"coordinates": {"latitude": 0.0, "longitude": 0.001}}}});
+        let metres = haversine_m(0.0, 0.0, 0.0, 0.001);
+        let matches = |radius_m| serde_json::from_value::<Filter>(near(radius_m)).unwrap().matches(&at);
+        assert!(matches(metres) && !matches(metres - 0.01));
+
+        assert!(parse_query(&json!({"filter": near(-1.0)}).to_string()).is_err());
+        assert!(parse_query(&json!({"filter": {"or": [{"date_range": {"to": "someday"}}]}}).to_string()).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+//! Emoji reactions on shared experiences
+//!
+//! Shared experiences may carry reactions from other learners:
+//!
+//! ```json
+//! "reactions": [{"actor": "sam", "emoji": "🌱", "timestamp": "2024-05-01T10:00:00Z"}]
+//! ```
+//!
+//! An actor reacts with a given emoji at most once per experience.
+//! [`reaction_summary`] rolls reactions up per experience, per learner
+//! (received on their experiences and given to others) and per UTC day.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::identity::{canonical_learner_id, LearnerId};
+use crate::severity::Issue;
+use crate::time::{format_timestamp, parse_timestamp};
+
+/// Longest accepted emoji in chars; ZWJ family sequences run to about ten
+const MAX_EMOJI_CHARS: usize = 16;
+
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify_next::Tsify))]
+pub(crate) struct Reaction {
+    pub(crate) actor: String,
+    pub(crate) emoji: String,
+    pub(crate) timestamp: String,
+}
+
+/// A single emoji or emoji sequence: no letters, digits, whitespace or
+/// control characters
+fn is_emoji(s: &str) -> bool {
+    let n = s.chars().count();
+    n > 0
+        && n <= MAX_EMOJI_CHARS
+        && s.chars().all(|c| !c.is_alphanumeric() && !c.is_whitespace() && !c.is_control())
+        && !s.is_ascii()
+}
+
+/// Validation errors for an experience's reactions
+pub(crate) fn validate_reactions(reactions: &[Reaction]) -> Vec<Issue> {
+    let mut errors = Vec::new();
+    let mut seen = BTreeSet::new();
+    for (i, r) in reactions.iter().enumerate() {
+        if r.actor.is_empty() {
+            errors.push(Issue::new("reactions.actor.required").with("index", i));
+        } else if let Err(e) = LearnerId::parse(&r.actor) {
+            errors.push(Issue::new("reactions.actor.invalid").with("index", i).with("reason", e));
+        }
+        if !is_emoji(&r.emoji) {
+            errors.push(Issue::new("reactions.emoji.invalid").with("index", i));
+        }
+        if parse_timestamp(&r.timestamp).is_none() {
+            errors.push(Issue::new("reactions.timestamp.invalid").with("index", i));
+        }
+        if !seen.insert((canonical_learner_id(&r.actor), r.emoji.as_str())) {
+            errors.push(Issue::new("reactions.duplicate").with("index", i));
+        }
+    }
+    errors
+}
+
+#[derive(Serialize)]
+struct ExperienceReactions {
+    id: Value,
+    total: usize,
+    actors: usize,
+    by_emoji: BTreeMap<String, usize>,
+}
+
+#[derive(Serialize, Default)]
+struct LearnerReactions {
+    received: usize,
+    given: usize,
+    by_emoji_received: BTreeMap<String, usize>,
+}
+
+#[derive(Serialize, Default)]
+struct ReactionSummary {
+    total: usize,
+    by_emoji: BTreeMap<String, usize>,
+    by_experience: Vec<ExperienceReactions>,
+    by_learner: BTreeMap<String, LearnerReactions>,
+    by_day: BTreeMap<String, BTreeMap<String, usize>>,
+}
+
+fn summarise(experiences: &[Value]) -> ReactionSummary {
+    let mut summary = ReactionSummary::default();
+
+    for exp in experiences {
+        let reactions: Vec<&Value> = exp.get("reactions").and_then(Value::as_array).into_iter().flatten().collect();
+        if reactions.is_empty() {
+            continue;
+        }
+        let owner = lookup(exp, "learner.id").and_then(Value::as_str).map(canonical_learner_id);
+        let mut by_emoji = BTreeMap::new();
+        let mut actors = BTreeSet::new();
+
+        for r in reactions {
+            let (Some(actor), Some(emoji)) = (r.get("actor").and_then(Value::as_str), r.get("emoji").and_then(Value::as_str)) else {
+                continue;
+            };
+            let actor = canonical_learner_id(actor);
+            *by_emoji.entry(emoji.to_string()).or_insert(0) += 1;
+            *summary.by_emoji.entry(emoji.to_string()).or_insert(0) += 1;
+            summary.total += 1;
+
+            if let Some(ref owner) = owner {
+                let learner = summary.by_learner.entry(owner.clone()).or_default();
+                learner.received += 1;
+                *learner.by_emoji_received.entry(emoji.to_string()).or_insert(0) += 1;
+            }
+            summary.by_learner.entry(actor.clone()).or_default().given += 1;
+
+            if let Some(ms) = r.get("timestamp").and_then(Value::as_str).and_then(parse_timestamp) {
+                let day = format_timestamp(ms)[..10].to_string();
+                *summary.by_day.entry(day).or_default().entry(emoji.to_string()).or_insert(0) += 1;
+            }
+            actors.insert(actor);
+        }
+
+        summary.by_experience.push(ExperienceReactions {
+            id: exp.get("id").cloned().unwrap_or(Value::Null),
+            total: by_emoji.values().sum(),
+            actors: actors.len(),
+            by_emoji,
+        });
+    }
+
+    summary.by_experience.sort_by_key(|e| std::cmp::Reverse(e.total));
+    summary
+}
+
+/// Aggregate reactions per experience, per learner and per UTC day
+/// Returns `{total, by_emoji, by_experience, by_learner, by_day}` as JSON,
+/// experiences with the most reactions first
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn reaction_summary(experiences_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+
+    to_json(&summarise(&experiences))
+}
@@ -0,0 +1,121 @@
+//! Next-domain recommendations
+//!
+//! Item-based collaborative filtering over the domain co-occurrence network
+//! of a corpus: each domain is represented by its row of the adjacency
+//! matrix (co-occurrence counts with every domain, its own occurrence count
+//! on the diagonal so direct neighbours count as similar), and domains are
+//! similar when those rows point the same way (cosine similarity). A
+//! candidate's score is the similarity to the learner's domains, weighted by
+//! how often the learner engaged with each; the strongest contributions are
+//! returned as the explanation ("because you did X and Y").
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::numeric::sum;
+use crate::query::domains;
+
+/// Explanation edges kept per recommendation
+const MAX_REASONS: usize = 3;
+
+#[derive(Serialize)]
+struct Reason {
+    source: String,
+    target: String,
+    similarity: f64,
+}
+
+#[derive(Serialize)]
+struct Recommendation {
+    domain: String,
+    score: f64,
+    because: Vec<String>,
+    edges: Vec<Reason>,
+}
+
+type Adjacency = BTreeMap<String, BTreeMap<String, f64>>;
+
+fn adjacency(corpus: &[Value]) -> Adjacency {
+    let mut adj: Adjacency = BTreeMap::new();
+    for exp in corpus {
+        let set: BTreeSet<&str> = domains(exp).collect();
+        for &a in &set {
+            let row = adj.entry(a.to_string()).or_default();
+            for &b in &set {
+                *row.entry(b.to_string()).or_insert(0.0) += 1.0;
+            }
+        }
+    }
+    adj
+}
+
+fn cosine(a: &BTreeMap<String, f64>, b: &BTreeMap<String, f64>) -> f64 {
+    let dot = sum(a.iter().filter_map(|(k, x)| b.get(k).map(|y| x * y)));
+    let norm = |v: &BTreeMap<String, f64>| sum(v.values().map(|x| x * x)).sqrt();
+    let denominator = norm(a) * norm(b);
+    if denominator == 0.0 {
+        0.0
+    } else {
+        dot / denominator
+    }
+}
+
+fn recommend(learner: &[Value], corpus: &[Value], k: usize) -> Vec<Recommendation> {
+    let adj = adjacency(corpus);
+    let mut engaged: BTreeMap<&str, f64> = BTreeMap::new();
+    for exp in learner {
+        for d in domains(exp).collect::<BTreeSet<_>>() {
+            *engaged.entry(d).or_insert(0.0) += 1.0;
+        }
+    }
+    let total = sum(engaged.values().copied());
+
+    let mut recommendations: Vec<Recommendation> = adj
+        .iter()
+        .filter(|(candidate, _)| !engaged.contains_key(candidate.as_str()))
+        .filter_map(|(candidate, row)| {
+            let mut reasons: Vec<Reason> = engaged
+                .keys()
+                .filter_map(|&d| {
+                    let similarity = cosine(row, adj.get(d)?);
+                    (similarity > 0.0).then(|| Reason {
+                        source: d.to_string(),
+                        target: candidate.clone(),
+                        similarity,
+                    })
+                })
+                .collect();
+            if reasons.is_empty() {
+                return None;
+            }
+            let score = sum(reasons.iter().map(|r| r.similarity * engaged[r.source.as_str()])) / total;
+            reasons.sort_by(|a, b| b.similarity.total_cmp(&a.similarity).then_with(|| a.source.cmp(&b.source)));
+            reasons.truncate(MAX_REASONS);
+            Some(Recommendation {
+                domain: candidate.clone(),
+                score,
+                because: reasons.iter().map(|r| r.source.clone()).collect(),
+                edges: reasons,
+            })
+        })
+        .collect();
+
+    recommendations.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.domain.cmp(&b.domain)));
+    recommendations.truncate(k);
+    recommendations
+}
+
+/// Suggest up to `k` domains a learner has not explored yet, based on
+/// co-occurrence patterns in the corpus
+/// Returns `[{domain, score, because, edges}]` as JSON, best first
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn recommend_domains(learner_experiences_json: &str, corpus_experiences_json: &str, k: usize) -> Result<String, Error> {
+    let learner: Vec<Value> = from_json(learner_experiences_json, "learner_experiences_json")?;
+    let corpus: Vec<Value> = from_json(corpus_experiences_json, "corpus_experiences_json")?;
+
+    to_json(&recommend(&learner, &corpus, k))
+}
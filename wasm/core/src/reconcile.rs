@@ -0,0 +1,180 @@
+//! Place name reconciliation against an authoritative gazetteer
+//!
+//! Learners type place names by hand, so the same park turns up as "Kew",
+//! "kew gardens" and "Richmond park" (when it was Kew). Data stewards
+//! re-check records in bulk with [`reconcile_locations`]: each located
+//! record is matched to the authoritative place at its coordinates, and a
+//! record is reported when its stored name is missing or too unlike that
+//! place's name to be a spelling variant.
+//!
+//! Authoritative places are either place records (`[{id, name, latitude,
+//! longitude}]`, as for `suggest_places`) or GeoJSON features with a `name`
+//! property. Polygons match the coordinates they contain; points and lines
+//! match within their `radius_m` property or `max_distance_m`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::geojson::{parse_features, Feature, Geometry};
+use crate::places::Place;
+use crate::query::coordinates;
+use crate::text::{stem, tokenize};
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct ReconcileOptions {
+    max_distance_m: f64,
+    /// Names at least this similar (0-1) are treated as the same place
+    min_similarity: f64,
+}
+
+impl Default for ReconcileOptions {
+    fn default() -> Self {
+        Self {
+            max_distance_m: 100.0,
+            min_similarity: 0.8,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Authoritative<'a> {
+    id: Option<Value>,
+    name: &'a str,
+    distance_m: f64,
+}
+
+#[derive(Serialize)]
+struct Discrepancy<'a> {
+    id: Option<&'a str>,
+    /// `mismatch` or `missing`
+    reason: &'static str,
+    stored_name: Option<&'a str>,
+    authoritative: Authoritative<'a>,
+    similarity: f64,
+}
+
+#[derive(Serialize)]
+struct ReconcileReport<'a> {
+    checked: usize,
+    consistent: usize,
+    /// Located records with no authoritative place in range
+    unmatched: usize,
+    discrepancies: Vec<Discrepancy<'a>>,
+}
+
+/// Authoritative places as GeoJSON features; place records become points
+fn authorities(value: Value) -> Result<Vec<Feature>, Error> {
+    if value.is_array() {
+        let places: Vec<Place> = serde_json::from_value(value)
+            .map_err(|e| Error::parse(e.to_string()).with("argument", "authoritative_places_json"))?;
+        return Ok(places
+            .into_iter()
+            .map(|p| {
+                let mut properties = Map::new();
+                properties.insert("name".to_string(), Value::from(p.name.unwrap_or_else(|| p.id.clone())));
+                Feature {
+                    id: Some(Value::from(p.id)),
+                    geometry: Geometry::Point([p.longitude, p.latitude]),
+                    properties,
+                }
+            })
+            .collect());
+    }
+    parse_features(&value).map_err(|e| Error::invalid(e).with("argument", "authoritative_places_json"))
+}
+
+/// Levenshtein distance over characters
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (diagonal + usize::from(ca != cb)).min(row[j] + 1).min(above + 1);
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// Similarity of two place names, 0-1: the better of character edit
+/// similarity and stemmed token overlap, so both typos ("Kew Gardnes") and
+/// reordered or extended names ("Gardens at Kew") score high
+fn name_similarity(a: &str, b: &str) -> f64 {
+    let (ta, tb) = (tokenize(a), tokenize(b));
+    let (ca, cb): (Vec<char>, Vec<char>) = (ta.join(" ").chars().collect(), tb.join(" ").chars().collect());
+    let longest = ca.len().max(cb.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    let edit = 1.0 - edit_distance(&ca, &cb) as f64 / longest as f64;
+    let (sa, sb): (BTreeSet<String>, BTreeSet<String>) = (ta.iter().map(|t| stem(t)).collect(), tb.iter().map(|t| stem(t)).collect());
+    let overlap = sa.intersection(&sb).count() as f64 / sa.len().min(sb.len()).max(1) as f64;
+    edit.max(overlap)
+}
+
+/// Records whose stored place name conflicts with the authoritative place
+/// at their coordinates
+/// `options_json` sets `max_distance_m` and `min_similarity` (empty for
+/// 100 and 0.8)
+/// Returns `{checked, consistent, unmatched, discrepancies: [{id, reason,
+/// stored_name, authoritative: {id, name, distance_m}, similarity}]}` as
+/// JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn reconcile_locations(experiences_json: &str, authoritative_places_json: &str, options_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let places = authorities(from_json(authoritative_places_json, "authoritative_places_json")?)?;
+    let options: ReconcileOptions = if options_json.trim().is_empty() {
+        ReconcileOptions::default()
+    } else {
+        from_json(options_json, "options_json")?
+    };
+    if options.max_distance_m.is_nan() || options.max_distance_m < 0.0 {
+        return Err(Error::invalid("max_distance_m must not be negative"));
+    }
+    if !(0.0..=1.0).contains(&options.min_similarity) {
+        return Err(Error::invalid("min_similarity must be between 0 and 1"));
+    }
+
+    let mut report = ReconcileReport { checked: 0, consistent: 0, unmatched: 0, discrepancies: Vec::new() };
+    for exp in &experiences {
+        let Some((lat, lon)) = coordinates(exp) else {
+            continue;
+        };
+        report.checked += 1;
+        let nearest = places
+            .iter()
+            .filter_map(|place| {
+                let name = place.properties.get("name").and_then(Value::as_str)?;
+                let radius = place.properties.get("radius_m").and_then(Value::as_f64).unwrap_or(options.max_distance_m);
+                let distance_m = place.geometry.distance_m(lon, lat);
+                (distance_m <= radius).then_some((place, name, distance_m))
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2));
+        let Some((place, name, distance_m)) = nearest else {
+            report.unmatched += 1;
+            continue;
+        };
+        let stored = lookup(exp, "context.location.name").and_then(Value::as_str).filter(|s| !s.trim().is_empty());
+        let similarity = stored.map_or(0.0, |s| name_similarity(s, name));
+        if stored.is_some() && similarity >= options.min_similarity {
+            report.consistent += 1;
+            continue;
+        }
+        report.discrepancies.push(Discrepancy {
+            id: lookup(exp, "id").and_then(Value::as_str),
+            reason: if stored.is_some() { "mismatch" } else { "missing" },
+            stored_name: stored,
+            authoritative: Authoritative { id: place.id.clone(), name, distance_m },
+            similarity,
+        });
+    }
+    to_json(&report)
+}
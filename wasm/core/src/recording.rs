@@ -0,0 +1,179 @@
+//! Low-power location sampling
+//!
+//! Mobile clients get far more GPS fixes than are worth keeping: a learner
+//! standing still produces a cloud of jittering points, and every stored
+//! point costs battery and sync bandwidth. [`should_record`] decides per
+//! fix, with the same rules on every platform:
+//!
+//! 1. Fixes less accurate than `max_accuracy_m` are dropped.
+//! 2. The first fix is always kept.
+//! 3. Fixes older than the last kept one, or within `min_interval_s` of it,
+//!    are dropped.
+//! 4. A fix `max_interval_s` after the last one is kept as a heartbeat.
+//! 5. Fixes implying more than `max_speed_kmh` are dropped as GPS jumps.
+//! 6. A fix `min_distance_m` away is kept.
+//! 7. A turn of at least `min_heading_change_deg` against the previous leg
+//!    is kept once the learner has moved `min_turn_distance_m`, so paths keep
+//!    their corners without recording every step.
+//! 8. Anything else is dropped as stationary.
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::geo::{bearing_deg, haversine_m};
+use crate::time::{parse_timestamp, MS_PER_SECOND};
+
+#[derive(Deserialize)]
+struct Fix {
+    latitude: f64,
+    longitude: f64,
+    timestamp: String,
+    /// Horizontal accuracy radius in metres
+    #[serde(default)]
+    accuracy: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct SamplingPolicy {
+    min_distance_m: f64,
+    min_interval_s: f64,
+    max_interval_s: f64,
+    min_heading_change_deg: f64,
+    min_turn_distance_m: f64,
+    max_accuracy_m: f64,
+    max_speed_kmh: f64,
+}
+
+impl Default for SamplingPolicy {
+    fn default() -> Self {
+        Self {
+            min_distance_m: 20.0,
+            min_interval_s: 1.0,
+            max_interval_s: 300.0,
+            min_heading_change_deg: 30.0,
+            min_turn_distance_m: 5.0,
+            max_accuracy_m: 50.0,
+            max_speed_kmh: 150.0,
+        }
+    }
+}
+
+impl SamplingPolicy {
+    fn check(&self) -> Result<(), Error> {
+        let fields = [
+            ("min_distance_m", self.min_distance_m),
+            ("min_interval_s", self.min_interval_s),
+            ("max_interval_s", self.max_interval_s),
+            ("min_heading_change_deg", self.min_heading_change_deg),
+            ("min_turn_distance_m", self.min_turn_distance_m),
+            ("max_accuracy_m", self.max_accuracy_m),
+            ("max_speed_kmh", self.max_speed_kmh),
+        ];
+        if let Some((name, _)) = fields.iter().find(|(_, v)| v.is_nan() || *v < 0.0) {
+            return Err(Error::invalid(format!("{} must not be negative", name)).with("field", *name));
+        }
+        if self.max_interval_s < self.min_interval_s {
+            return Err(Error::invalid("max_interval_s must not be less than min_interval_s"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Default)]
+struct Decision {
+    record: bool,
+    reason: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    distance_m: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    elapsed_s: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    heading_change_deg: Option<f64>,
+}
+
+fn parse_fix(fix: &Fix, what: &str) -> Result<i64, Error> {
+    if !(-90.0..=90.0).contains(&fix.latitude) || !(-180.0..=180.0).contains(&fix.longitude) {
+        return Err(Error::invalid(format!("{} coordinates are out of range", what)));
+    }
+    parse_timestamp(&fix.timestamp)
+        .ok_or_else(|| Error::invalid(format!("{} timestamp is not a valid date-time", what)).with("timestamp", fix.timestamp.as_str()))
+}
+
+/// Smallest angle between two bearings, 0-180
+fn heading_change(a: f64, b: f64) -> f64 {
+    let diff = (a - b).rem_euclid(360.0);
+    diff.min(360.0 - diff)
+}
+
+fn decide(last: &[Fix], candidate: &Fix, policy: &SamplingPolicy) -> Result<Decision, Error> {
+    let candidate_ms = parse_fix(candidate, "candidate")?;
+    if candidate.accuracy.is_some_and(|a| a > policy.max_accuracy_m) {
+        return Ok(Decision { reason: "inaccurate", ..Decision::default() });
+    }
+    let Some(prev) = last.last() else {
+        return Ok(Decision { record: true, reason: "first", ..Decision::default() });
+    };
+    let prev_ms = parse_fix(prev, "last point")?;
+
+    let elapsed_s = (candidate_ms - prev_ms) as f64 / MS_PER_SECOND as f64;
+    let distance_m = haversine_m(prev.latitude, prev.longitude, candidate.latitude, candidate.longitude);
+    let mut decision = Decision {
+        distance_m: Some(distance_m),
+        elapsed_s: Some(elapsed_s),
+        ..Decision::default()
+    };
+    let (record, reason) = if elapsed_s < 0.0 {
+        (false, "out_of_order")
+    } else if elapsed_s < policy.min_interval_s {
+        (false, "too_soon")
+    } else if elapsed_s >= policy.max_interval_s {
+        (true, "heartbeat")
+    } else if elapsed_s > 0.0 && distance_m / elapsed_s * 3.6 > policy.max_speed_kmh {
+        (false, "implausible_speed")
+    } else if distance_m >= policy.min_distance_m {
+        (true, "moved")
+    } else {
+        let turn = last.len().checked_sub(2).map(|i| &last[i]).and_then(|before| {
+            let leg = haversine_m(before.latitude, before.longitude, prev.latitude, prev.longitude);
+            (leg > 0.0 && distance_m > 0.0).then(|| {
+                heading_change(
+                    bearing_deg(before.latitude, before.longitude, prev.latitude, prev.longitude),
+                    bearing_deg(prev.latitude, prev.longitude, candidate.latitude, candidate.longitude),
+                )
+            })
+        });
+        decision.heading_change_deg = turn;
+        match turn {
+            Some(change) if change >= policy.min_heading_change_deg && distance_m >= policy.min_turn_distance_m => {
+                (true, "turned")
+            }
+            _ => (false, "stationary"),
+        }
+    };
+    decision.record = record;
+    decision.reason = reason;
+    Ok(decision)
+}
+
+/// Whether a GPS fix is worth recording, given the fixes already recorded
+/// (oldest first; only the last two are used), the candidate
+/// `{latitude, longitude, timestamp, accuracy}` and a policy (empty for the
+/// defaults: 20 m, 1-300 s, 30° turns after 5 m, 50 m accuracy, 150 km/h)
+/// Returns `{record, reason, distance_m, elapsed_s, heading_change_deg}` as
+/// JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn should_record(last_points_json: &str, candidate_point_json: &str, policy_json: &str) -> Result<String, Error> {
+    let last: Vec<Fix> = from_json(last_points_json, "last_points_json")?;
+    let candidate: Fix = from_json(candidate_point_json, "candidate_point_json")?;
+    let policy: SamplingPolicy = if policy_json.trim().is_empty() {
+        SamplingPolicy::default()
+    } else {
+        from_json(policy_json, "policy_json")?
+    };
+    policy.check()?;
+    let tail = &last[last.len().saturating_sub(2)..];
+    to_json(&decide(tail, &candidate, &policy)?)
+}
@@ -0,0 +1,170 @@
+//! Mentions, hashtags and cross-references in experience descriptions
+//!
+//! Descriptions can point at other learners (`@sam`), domains
+//! (`#urban_ecology`) and other experiences by id. [`extract_references`]
+//! finds all three and returns them as a reference graph, so notifications
+//! and backlinks are derived the same way everywhere:
+//!
+//! ```json
+//! {"edges": [
+//!   {"source": "exp-2", "target": "sam", "kind": "mention", "resolved": true},
+//!   {"source": "exp-2", "target": "urban_ecology", "kind": "hashtag", "resolved": true},
+//!   {"source": "exp-2", "target": "exp-1", "kind": "experience", "resolved": true}
+//! ]}
+//! ```
+//!
+//! A marker only counts after whitespace or opening punctuation, so e-mail
+//! addresses and URL fragments are not mistaken for mentions or hashtags. Experience ids are
+//! recognised when they match an experience in the input or look like a
+//! ubicity id (ULID or UUID, optionally `ubi-` prefixed); the latter are
+//! reported with `resolved: false`.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashSet};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::ids::DEFAULT_ID_FORMATS;
+use crate::query::domains;
+
+/// References found in one piece of text, each list deduplicated in order of
+/// first appearance
+#[derive(Default)]
+pub(crate) struct References {
+    pub(crate) mentions: Vec<String>,
+    pub(crate) hashtags: Vec<String>,
+    pub(crate) experiences: Vec<String>,
+}
+
+fn push_unique(list: &mut Vec<String>, item: String) {
+    if !list.contains(&item) {
+        list.push(item);
+    }
+}
+
+fn is_handle_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':')
+}
+
+fn looks_like_experience_id(token: &str) -> bool {
+    let bare = token.strip_prefix("ubi-").unwrap_or(token);
+    DEFAULT_ID_FORMATS.iter().any(|format| format.matches(bare))
+}
+
+/// Scan text for `@mentions`, `#hashtags` and experience ids
+pub(crate) fn scan(text: &str, known_ids: &HashSet<&str>) -> References {
+    let mut refs = References::default();
+    let mut previous: Option<char> = None;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let at_word_start = previous.is_none_or(|p| p.is_whitespace() || matches!(p, '(' | '[' | '{' | '"' | '\'' | ',' | ';'));
+        previous = Some(c);
+        if !(at_word_start && (c == '@' || c == '#')) {
+            continue;
+        }
+        let start = i + c.len_utf8();
+        let mut end = start;
+        while let Some(&(j, next)) = chars.peek() {
+            if !is_handle_char(next) {
+                break;
+            }
+            end = j + next.len_utf8();
+            previous = Some(next);
+            chars.next();
+        }
+        // Sentence punctuation is not part of the handle
+        let token = text[start..end].trim_end_matches(['.', ':', '-']);
+        if token.is_empty() {
+            continue;
+        }
+        if c == '@' {
+            push_unique(&mut refs.mentions, token.to_string());
+        } else {
+            push_unique(&mut refs.hashtags, token.to_lowercase());
+        }
+    }
+
+    for token in text.split(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | ':'))) {
+        let token = token.trim_matches(['-', ':']);
+        if !token.is_empty() && (known_ids.contains(token) || looks_like_experience_id(token)) {
+            push_unique(&mut refs.experiences, token.to_string());
+        }
+    }
+    refs
+}
+
+/// Description text of a raw experience
+pub(crate) fn description(exp: &Value) -> &str {
+    lookup(exp, "experience.description").and_then(Value::as_str).unwrap_or("")
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum ReferenceKind {
+    Mention,
+    Hashtag,
+    Experience,
+}
+
+#[derive(Serialize)]
+struct ReferenceEdge {
+    source: Value,
+    target: String,
+    kind: ReferenceKind,
+    /// Whether the target exists in the input (a learner, domain or
+    /// experience seen there)
+    resolved: bool,
+}
+
+#[derive(Serialize)]
+struct ReferenceGraph {
+    edges: Vec<ReferenceEdge>,
+    mentioned_learners: BTreeSet<String>,
+    hashtags: BTreeSet<String>,
+}
+
+fn reference_graph(experiences: &[Value]) -> ReferenceGraph {
+    let known_ids: HashSet<&str> = experiences.iter().filter_map(|e| e.get("id").and_then(Value::as_str)).collect();
+    let learners: HashSet<&str> = experiences.iter().filter_map(|e| lookup(e, "learner.id").and_then(Value::as_str)).collect();
+    let domains: HashSet<String> = experiences.iter().flat_map(domains).map(str::to_lowercase).collect();
+
+    let mut graph = ReferenceGraph { edges: Vec::new(), mentioned_learners: BTreeSet::new(), hashtags: BTreeSet::new() };
+    for exp in experiences {
+        let source = exp.get("id").cloned().unwrap_or(Value::Null);
+        let own_id = source.as_str();
+        let refs = scan(description(exp), &known_ids);
+
+        let mut edge = |target: String, kind, resolved| {
+            graph.edges.push(ReferenceEdge { source: source.clone(), target, kind, resolved });
+        };
+        for m in refs.mentions {
+            graph.mentioned_learners.insert(m.clone());
+            let resolved = learners.contains(m.as_str());
+            edge(m, ReferenceKind::Mention, resolved);
+        }
+        for h in refs.hashtags {
+            graph.hashtags.insert(h.clone());
+            let resolved = domains.contains(&h);
+            edge(h, ReferenceKind::Hashtag, resolved);
+        }
+        for id in refs.experiences.into_iter().filter(|id| Some(id.as_str()) != own_id) {
+            let resolved = known_ids.contains(id.as_str());
+            edge(id, ReferenceKind::Experience, resolved);
+        }
+    }
+    graph
+}
+
+/// Extract `@learner` mentions, `#domain` hashtags and experience-id
+/// references from descriptions
+/// Returns `{edges, mentioned_learners, hashtags}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn extract_references(experiences_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+
+    to_json(&reference_graph(&experiences))
+}
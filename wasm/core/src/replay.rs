@@ -0,0 +1,84 @@
+//! Replay of historical experiences for testing downstream consumers
+//!
+//! [`replay`] re-emits a dataset to a JS callback in timestamp order,
+//! preserving the gaps between records scaled down by `speed`, so dashboard
+//! and notification code can be exercised against a realistic event stream
+//! without a backend.
+
+use js_sys::{Function, Promise, Reflect};
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+use crate::error::{from_json, to_json, Error};
+use crate::time::parse_timestamp;
+
+/// Resolve after `ms` milliseconds via the host's `setTimeout`
+async fn sleep(ms: f64) -> Result<(), JsValue> {
+    let set_timeout: Function = Reflect::get(&js_sys::global(), &JsValue::from_str("setTimeout"))?.dyn_into()?;
+    let mut failure = None;
+    let promise = Promise::new(&mut |resolve, _reject| {
+        if let Err(e) = set_timeout.call2(&JsValue::NULL, &resolve, &JsValue::from_f64(ms)) {
+            failure = Some(e);
+        }
+    });
+    if let Some(e) = failure {
+        return Err(e);
+    }
+    JsFuture::from(promise).await.map(|_| ())
+}
+
+/// Sort experiences by timestamp, pairing each with its time in ms
+fn schedule(experiences: Vec<Value>) -> Result<Vec<(i64, Value)>, Error> {
+    let mut timed = experiences
+        .into_iter()
+        .map(|exp| {
+            let ts = exp.get("timestamp").and_then(Value::as_str).unwrap_or_default();
+            match parse_timestamp(ts) {
+                Some(ms) => Ok((ms, exp)),
+                None => Err(Error::invalid(format!("invalid timestamp: {:?}", ts))),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    // Stable, so records sharing a timestamp keep their input order
+    timed.sort_by_key(|(ms, _)| *ms);
+    Ok(timed)
+}
+
+/// Re-emit experiences to `callback` in timestamp order, `speed` times
+/// faster than they happened (`Infinity` for no delay). The callback gets
+/// each experience's JSON; returning `false` stops the replay and a returned
+/// promise is awaited before the next record.
+/// Resolves to the number of experiences emitted
+#[wasm_bindgen]
+pub fn replay(experiences_json: &str, speed: f64, callback: Function) -> Promise {
+    let scheduled = from_json::<Vec<Value>>(experiences_json, "experiences_json").and_then(schedule);
+    future_to_promise(async move {
+        if speed.is_nan() || speed <= 0.0 {
+            return Err(Error::invalid("speed must be positive").into());
+        }
+        let scheduled = scheduled?;
+
+        let mut emitted = 0u32;
+        let mut previous = scheduled.first().map_or(0, |(ms, _)| *ms);
+        for (ms, exp) in scheduled {
+            let delay = (ms - previous) as f64 / speed;
+            if delay > 0.0 {
+                sleep(delay).await?;
+            }
+            previous = ms;
+
+            let json = to_json(&exp)?;
+            let mut outcome = callback.call1(&JsValue::NULL, &JsValue::from_str(&json))?;
+            if let Some(pending) = outcome.dyn_ref::<Promise>() {
+                outcome = JsFuture::from(pending.clone()).await?;
+            }
+            emitted += 1;
+            if outcome.as_bool() == Some(false) {
+                break;
+            }
+        }
+        Ok(JsValue::from(emitted))
+    })
+}
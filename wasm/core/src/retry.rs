@@ -0,0 +1,240 @@
+//! Retry / backoff state machine for sync operations
+//!
+//! `next_delay` is a pure function of the policy, the previous state and the
+//! outcome of the last attempt, so every platform's sync loop makes identical
+//! decisions. Jitter comes from a xorshift generator whose state travels in
+//! the retry state rather than from ambient randomness, which keeps runs
+//! reproducible in tests.
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+struct RetryState {
+    /// Failed attempts so far
+    attempt: u32,
+    /// Jitter generator state (never zero)
+    rng: u32,
+}
+
+impl RetryState {
+    fn initial(seed: u32) -> Self {
+        Self {
+            attempt: 0,
+            rng: if seed == 0 { 0x9e37_79b9 } else { seed },
+        }
+    }
+
+    /// Advance the generator and return a sample in [0, 1)
+    fn next_unit(&mut self) -> f64 {
+        let mut x = self.rng;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.rng = x;
+        f64::from(x) / (f64::from(u32::MAX) + 1.0)
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum OutcomeKind {
+    Success,
+    Retryable,
+    Fatal,
+}
+
+/// Result of the last attempt: an explicit `kind`, or an HTTP `status` to be
+/// classified, plus an optional server-provided `retry_after_ms`
+#[derive(Deserialize)]
+struct Outcome {
+    kind: Option<OutcomeKind>,
+    status: Option<u16>,
+    retry_after_ms: Option<u64>,
+}
+
+impl Outcome {
+    fn kind(&self) -> OutcomeKind {
+        if let Some(kind) = self.kind {
+            return kind;
+        }
+        match self.status {
+            Some(200..=299) => OutcomeKind::Success,
+            Some(408 | 425 | 429 | 500..=599) | None => OutcomeKind::Retryable,
+            Some(_) => OutcomeKind::Fatal,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum Decision {
+    Done { state: RetryState },
+    Retry { delay_ms: u64, state: RetryState },
+    GiveUp { reason: &'static str, state: RetryState },
+}
+
+/// Exponential backoff with jitter and Retry-After support
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct RetryPolicy {
+    base_ms: u64,
+    max_ms: u64,
+    multiplier: f64,
+    max_attempts: u32,
+    jitter: f64,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl RetryPolicy {
+    /// `jitter` is the fraction of each delay that is randomized: 0 gives
+    /// plain exponential backoff, 1 gives "full jitter"
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(base_ms: u64, max_ms: u64, multiplier: f64, max_attempts: u32, jitter: f64) -> Result<RetryPolicy, Error> {
+        if multiplier.is_nan() || multiplier < 1.0 {
+            return Err(Error::invalid("multiplier must be at least 1"));
+        }
+        if !(0.0..=1.0).contains(&jitter) {
+            return Err(Error::invalid("jitter must be between 0 and 1"));
+        }
+        Ok(Self {
+            base_ms,
+            max_ms: max_ms.max(base_ms),
+            multiplier,
+            max_attempts,
+            jitter,
+        })
+    }
+
+    /// Fresh retry state as JSON, seeding the jitter generator
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn initial_state(&self, seed: u32) -> Result<String, Error> {
+        to_json(&RetryState::initial(seed))
+    }
+
+    /// Decide what to do after an attempt
+    /// Returns `{action: "done" | "retry" | "give_up", delay_ms?, reason?, state}`
+    /// as JSON; feed `state` back into the next call
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn next_delay(&self, state_json: &str, outcome_json: &str) -> Result<String, Error> {
+        let state: RetryState = if state_json.trim().is_empty() {
+            RetryState::initial(0)
+        } else {
+            from_json(state_json, "state_json")?
+        };
+        let outcome: Outcome = from_json(outcome_json, "outcome_json")?;
+
+        to_json(&self.decide(state, &outcome))
+    }
+
+    fn decide(&self, mut state: RetryState, outcome: &Outcome) -> Decision {
+        match outcome.kind() {
+            OutcomeKind::Success => Decision::Done {
+                state: RetryState::initial(state.rng),
+            },
+            OutcomeKind::Fatal => Decision::GiveUp { reason: "fatal", state },
+            OutcomeKind::Retryable => {
+                state.attempt = state.attempt.saturating_add(1);
+                if state.attempt >= self.max_attempts {
+                    return Decision::GiveUp {
+                        reason: "max_attempts",
+                        state,
+                    };
+                }
+
+                let exponent = (state.attempt - 1).min(1023) as i32;
+                let backoff = (self.base_ms as f64 * self.multiplier.powi(exponent)).min(self.max_ms as f64);
+                let jittered = backoff * (1.0 - self.jitter * state.next_unit());
+                // A server's Retry-After is a floor, never shortened by jitter
+                let delay_ms = outcome.retry_after_ms.map_or(jittered as u64, |after| after.max(jittered as u64));
+
+                Decision::Retry { delay_ms, state }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn retryable(retry_after_ms: Option<u64>) -> Outcome {
+        Outcome {
+            kind: None,
+            status: Some(503),
+            retry_after_ms,
+        }
+    }
+
+    fn delays(policy: &RetryPolicy, seed: u32, n: usize) -> Vec<u64> {
+        let mut state = RetryState::initial(seed);
+        (0..n)
+            .map(|_| match policy.decide(state, &retryable(None)) {
+                Decision::Retry { delay_ms, state: next } => {
+                    state = next;
+                    delay_ms
+                }
+                _ => panic!("gave up early"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn backoff_grows_to_the_cap() {
+        let policy = RetryPolicy::new(100, 1_000, 2.0, u32::MAX, 0.0).unwrap();
+        assert_eq!(delays(&policy, 7, 7), [100, 200, 400, 800, 1_000, 1_000, 1_000]);
+
+        // Far past the cap the exponent saturates instead of overflowing
+        let state = RetryState { attempt: 5_000, rng: 7 };
+        assert!(matches!(policy.decide(state, &retryable(None)), Decision::Retry { delay_ms: 1_000, .. }));
+        assert!(matches!(policy.decide(state, &retryable(Some(60_000))), Decision::Retry { delay_ms: 60_000, .. }));
+    }
+
+    #[test]
+    fn gives_up_after_max_attempts_or_fatal_status() {
+        let policy = RetryPolicy::new(100, 1_000, 2.0, 3, 0.5).unwrap();
+        let mut state = RetryState::initial(1);
+        for _ in 0..2 {
+            let Decision::Retry { state: next, .. } = policy.decide(state, &retryable(None)) else {
+                panic!("expected retry")
+            };
+            state = next;
+        }
+        assert!(matches!(policy.decide(state, &retryable(None)), Decision::GiveUp { reason: "max_attempts", .. }));
+
+        let fatal = Outcome { kind: None, status: Some(404), retry_after_ms: None };
+        assert!(matches!(policy.decide(state, &fatal), Decision::GiveUp { reason: "fatal", .. }));
+        let ok = Outcome { kind: None, status: Some(204), retry_after_ms: None };
+        assert!(matches!(policy.decide(state, &ok), Decision::Done { state } if state.attempt == 0));
+    }
+
+    #[test]
+    fn rejects_bad_policies() {
+        assert!(RetryPolicy::new(100, 1_000, 0.5, 3, 0.0).is_err());
+        assert!(RetryPolicy::new(100, 1_000, f64::NAN, 3, 0.0).is_err());
+        assert!(RetryPolicy::new(100, 1_000, 2.0, 3, 1.01).is_err());
+        assert_eq!(RetryPolicy::new(500, 100, 2.0, 3, 0.0).unwrap().max_ms, 500);
+    }
+
+    proptest! {
+        #[test]
+        fn jitter_stays_within_its_fraction(seed in any::<u32>(), jitter in 0.0..=1.0f64, retry_after in prop::option::of(0..2_000u64)) {
+            let policy = RetryPolicy::new(100, 1_000, 2.0, u32::MAX, jitter).unwrap();
+            let mut state = RetryState::initial(seed);
+            for attempt in 0..8 {
+                let backoff = (100.0 * 2f64.powi(attempt)).min(1_000.0);
+                let Decision::Retry { delay_ms, state: next } = policy.decide(state, &retryable(retry_after)) else {
+                    panic!("expected retry")
+                };
+                prop_assert!(delay_ms as f64 <= backoff.max(retry_after.unwrap_or(0) as f64));
+                prop_assert!(delay_ms as f64 >= (backoff * (1.0 - jitter)).floor());
+                prop_assert!(delay_ms >= retry_after.unwrap_or(0));
+                prop_assert_ne!(next.rng, 0);
+                state = next;
+            }
+        }
+    }
+}
@@ -0,0 +1,912 @@
+//! Datalog-style rule engine over experiences
+//!
+//! Researchers derive facts with rules instead of new releases:
+//!
+//! ```text
+//! same_week(L, D1, D2) :- experience(E1, L), experience(E2, L),
+//!                         week(E1, W), week(E2, W),
+//!                         domain(E1, D1), domain(E2, D2), D1 != D2.
+//! interdisciplinary(L) :- same_week(L, D1, D2),
+//!                         branch(D1, B1), branch(D2, B2), B1 != B2.
+//! branch(ecology, life_science).
+//! branch(chemistry, physical_science).
+//! ```
+//!
+//! Every experience contributes base facts:
+//!
+//! | Predicate          | Arguments                                   |
+//! |--------------------|---------------------------------------------|
+//! | `experience(E, L)` | experience id, canonical learner id         |
+//! | `timestamp(E, T)`  | RFC 3339 timestamp as given                 |
+//! | `day(E, D)`        | UTC date, `2024-05-01`                      |
+//! | `week(E, W)`       | ISO week, `2024-W18`                        |
+//! | `type(E, T)`       | `experience.type`                           |
+//! | `domain(E, D)`     | one fact per `experience.domains` entry     |
+//! | `place(E, P)`      | trimmed `context.location.name`             |
+//! | `related(E, R)`    | one fact per `metadata.related_experiences` |
+//!
+//! Variables start with an uppercase letter or `_` (a lone `_` matches
+//! anything); constants are lowercase identifiers, numbers or double-quoted
+//! strings. Bodies may use `not p(...)` and the comparisons `=`, `!=`, `<`,
+//! `<=`, `>`, `>=`, which order values as instants, numbers or text like
+//! [`crate::patterns`] filters. `%` starts a comment.
+//!
+//! Rules must be safe (every variable in the head, a negation or a
+//! comparison also appears in a positive body atom) and stratified (no
+//! predicate depends negatively on itself); each stratum is evaluated
+//! semi-naively to a fixpoint. A `_` inside a negation is existential, so
+//! `not attended(L, _)` holds when `L` attended nothing.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::identity::canonical_learner_id;
+use crate::patterns::compare_values;
+use crate::query::domains;
+use crate::time::{format_timestamp, iso_week, parse_timestamp};
+
+const BASE_PREDICATES: [(&str, usize); 8] = [
+    ("experience", 2),
+    ("timestamp", 2),
+    ("day", 2),
+    ("week", 2),
+    ("type", 2),
+    ("domain", 2),
+    ("place", 2),
+    ("related", 2),
+];
+
+#[derive(Deserialize)]
+struct RuleSet {
+    rules: Vec<String>,
+    /// Extra base facts, `{"branch": [["ecology", "life_science"]]}`
+    #[serde(default)]
+    facts: BTreeMap<String, Vec<Vec<Value>>>,
+    /// Derived predicates to return; all of them when empty
+    #[serde(default)]
+    output: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct RuleResult {
+    facts: BTreeMap<String, Vec<Vec<String>>>,
+    counts: BTreeMap<String, usize>,
+    strata: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(String),
+    Punct(&'static str),
+}
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, String> {
+    let chars: Vec<(usize, char)> = source.char_indices().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while let Some(&(at, c)) = chars.get(i) {
+        let next = chars.get(i + 1).map(|&(_, c)| c);
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '%' => {
+                while chars.get(i).is_some_and(|&(_, c)| c != '\n') {
+                    i += 1;
+                }
+            }
+            '"' => {
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i).map(|&(_, c)| c) {
+                        None => return Err(format!("unterminated string at offset {}", at)),
+                        Some('"') => break,
+                        Some('\\') => {
+                            match chars.get(i + 1).map(|&(_, c)| c) {
+                                Some('n') => text.push('\n'),
+                                Some(c @ ('"' | '\\')) => text.push(c),
+                                _ => return Err(format!("invalid escape at offset {}", chars[i].0)),
+                            }
+                            i += 1;
+                        }
+                        Some(c) => text.push(c),
+                    }
+                    i += 1;
+                }
+                i += 1;
+                tokens.push((at, Token::Str(text)));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while chars.get(i).is_some_and(|&(_, c)| c.is_alphanumeric() || c == '_') {
+                    i += 1;
+                }
+                tokens.push((at, Token::Ident(chars[start..i].iter().map(|&(_, c)| c).collect())));
+            }
+            c if c.is_ascii_digit() || (c == '-' && next.is_some_and(|n| n.is_ascii_digit())) => {
+                let start = i;
+                let digit = |i: usize| chars.get(i).is_some_and(|&(_, c)| c.is_ascii_digit());
+                i += 1;
+                while digit(i) {
+                    i += 1;
+                }
+                if chars.get(i).is_some_and(|&(_, c)| c == '.') && digit(i + 1) {
+                    i += 1;
+                    while digit(i) {
+                        i += 1;
+                    }
+                }
+                tokens.push((at, Token::Num(chars[start..i].iter().map(|&(_, c)| c).collect())));
+            }
+            _ => {
+                let punct = match (c, next) {
+                    (':', Some('-')) => ":-",
+                    ('!', Some('=')) => "!=",
+                    ('<', Some('=')) => "<=",
+                    ('>', Some('=')) => ">=",
+                    ('(', _) => "(",
+                    (')', _) => ")",
+                    (',', _) => ",",
+                    ('.', _) => ".",
+                    ('=', _) => "=",
+                    ('<', _) => "<",
+                    ('>', _) => ">",
+                    ('!', _) => "!",
+                    _ => return Err(format!("unexpected character {:?} at offset {}", c, at)),
+                };
+                i += punct.len();
+                tokens.push((at, Token::Punct(punct)));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Clone, Debug)]
+enum Term {
+    Var(usize),
+    Const(String),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+}
+
+impl CmpOp {
+    fn parse(punct: &str) -> Option<Self> {
+        Some(match punct {
+            "=" => CmpOp::Eq,
+            "!=" => CmpOp::Ne,
+            "<" => CmpOp::Lt,
+            "<=" => CmpOp::Lte,
+            ">" => CmpOp::Gt,
+            ">=" => CmpOp::Gte,
+            _ => return None,
+        })
+    }
+
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            CmpOp::Eq => ordering == Ordering::Equal,
+            CmpOp::Ne => ordering != Ordering::Equal,
+            CmpOp::Lt => ordering == Ordering::Less,
+            CmpOp::Lte => ordering != Ordering::Greater,
+            CmpOp::Gt => ordering == Ordering::Greater,
+            CmpOp::Gte => ordering != Ordering::Less,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Atom {
+    predicate: String,
+    args: Vec<Term>,
+}
+
+#[derive(Debug)]
+enum Literal {
+    Positive(Atom),
+    Negative(Atom),
+    Compare(Term, CmpOp, Term),
+}
+
+#[derive(Debug)]
+struct Rule {
+    head: Atom,
+    /// Body literals in evaluation order: negations and comparisons are
+    /// moved just after the positive atoms that bind their variables
+    body: Vec<Literal>,
+    variables: Vec<String>,
+    text: String,
+}
+
+struct Parser<'a> {
+    tokens: &'a [(usize, Token)],
+    pos: usize,
+    variables: Vec<String>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn peek_at(&self, ahead: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + ahead).map(|(_, t)| t)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens.get(self.pos).map_or(usize::MAX, |(at, _)| *at)
+    }
+
+    fn error(&self, expected: &str) -> String {
+        match self.tokens.get(self.pos) {
+            Some((at, token)) => format!("expected {} at offset {}, found {:?}", expected, at, token),
+            None => format!("expected {} at end of input", expected),
+        }
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Punct(p)) if *p == punct);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn variable(&mut self, name: &str) -> Term {
+        // Every `_` is a fresh variable, named `_` so it is never looked up
+        if name == "_" {
+            self.variables.push(name.to_string());
+            return Term::Var(self.variables.len() - 1);
+        }
+        match self.variables.iter().position(|v| v == name) {
+            Some(index) => Term::Var(index),
+            None => {
+                self.variables.push(name.to_string());
+                Term::Var(self.variables.len() - 1)
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<Term, String> {
+        let term = match self.peek().cloned() {
+            Some(Token::Ident(name)) if name.starts_with(|c: char| c.is_uppercase() || c == '_') => self.variable(&name),
+            Some(Token::Ident(name) | Token::Str(name) | Token::Num(name)) => Term::Const(name),
+            _ => return Err(self.error("a variable or constant")),
+        };
+        self.pos += 1;
+        Ok(term)
+    }
+
+    fn atom(&mut self) -> Result<Atom, String> {
+        let predicate = match self.peek() {
+            Some(Token::Ident(name)) if name.starts_with(|c: char| c.is_lowercase()) => name.clone(),
+            _ => return Err(self.error("a predicate")),
+        };
+        self.pos += 1;
+        let mut args = Vec::new();
+        if self.eat("(") {
+            loop {
+                args.push(self.term()?);
+                if self.eat(")") {
+                    break;
+                }
+                if !self.eat(",") {
+                    return Err(self.error("',' or ')'"));
+                }
+            }
+        }
+        Ok(Atom { predicate, args })
+    }
+
+    fn literal(&mut self) -> Result<Literal, String> {
+        if self.peek() == Some(&Token::Punct("!")) || (self.peek() == Some(&Token::Ident("not".to_string())) && matches!(self.peek_at(1), Some(Token::Ident(_)))) {
+            self.pos += 1;
+            return Ok(Literal::Negative(self.atom()?));
+        }
+        let comparison = matches!(self.peek_at(1), Some(Token::Punct(p)) if CmpOp::parse(p).is_some());
+        if !comparison {
+            return Ok(Literal::Positive(self.atom()?));
+        }
+        let left = self.term()?;
+        let Some(Token::Punct(p)) = self.peek() else {
+            return Err(self.error("a comparison"));
+        };
+        let op = CmpOp::parse(p).ok_or_else(|| self.error("a comparison"))?;
+        self.pos += 1;
+        Ok(Literal::Compare(left, op, self.term()?))
+    }
+
+    fn rule(&mut self) -> Result<Rule, String> {
+        self.variables.clear();
+        let head = self.atom()?;
+        let mut body = Vec::new();
+        if self.eat(":-") {
+            loop {
+                body.push(self.literal()?);
+                if !self.eat(",") {
+                    break;
+                }
+            }
+        }
+        if !self.eat(".") && self.peek().is_some() {
+            return Err(self.error("'.'"));
+        }
+        Ok(Rule {
+            head,
+            body,
+            variables: std::mem::take(&mut self.variables),
+            text: String::new(),
+        })
+    }
+}
+
+fn term_vars(term: &Term) -> Option<usize> {
+    match term {
+        Term::Var(v) => Some(*v),
+        Term::Const(_) => None,
+    }
+}
+
+/// Variables a literal needs bound before it runs (or, for a positive
+/// atom, binds); anonymous variables in a negation match anything
+fn literal_vars(literal: &Literal, variables: &[String]) -> Vec<usize> {
+    match literal {
+        Literal::Positive(atom) => atom.args.iter().filter_map(term_vars).collect(),
+        Literal::Negative(atom) => atom.args.iter().filter_map(term_vars).filter(|v| variables[*v] != "_").collect(),
+        Literal::Compare(left, _, right) => [left, right].into_iter().filter_map(term_vars).collect(),
+    }
+}
+
+/// Check safety and reorder the body so every negation and comparison runs
+/// once its variables are bound
+fn prepare(mut rule: Rule) -> Result<Rule, String> {
+    let mut bound = vec![false; rule.variables.len()];
+    for literal in &rule.body {
+        if let Literal::Positive(atom) = literal {
+            for v in atom.args.iter().filter_map(term_vars) {
+                bound[v] = true;
+            }
+        }
+    }
+    let unsafe_var = rule
+        .head
+        .args
+        .iter()
+        .filter_map(term_vars)
+        .chain(rule.body.iter().filter(|l| !matches!(l, Literal::Positive(_))).flat_map(|l| literal_vars(l, &rule.variables)))
+        .find(|v| !bound[*v]);
+    if let Some(v) = unsafe_var {
+        return Err(format!(
+            "unsafe rule: variable {} must appear in a positive body atom",
+            rule.variables[v]
+        ));
+    }
+
+    let mut bound = vec![false; rule.variables.len()];
+    let (positives, mut checks): (Vec<Literal>, Vec<Literal>) =
+        rule.body.into_iter().partition(|l| matches!(l, Literal::Positive(_)));
+    let mut body = Vec::new();
+    let variables = &rule.variables;
+    let place_checks = |bound: &[bool], body: &mut Vec<Literal>, checks: &mut Vec<Literal>| {
+        let (ready, waiting) = std::mem::take(checks)
+            .into_iter()
+            .partition(|l| literal_vars(l, variables).iter().all(|v| bound[*v]));
+        body.extend::<Vec<Literal>>(ready);
+        *checks = waiting;
+    };
+    place_checks(&bound, &mut body, &mut checks);
+    for literal in positives {
+        for v in literal_vars(&literal, variables) {
+            bound[v] = true;
+        }
+        body.push(literal);
+        place_checks(&bound, &mut body, &mut checks);
+    }
+    rule.body = body;
+    Ok(rule)
+}
+
+fn parse_rules(sources: &[String]) -> Result<Vec<Rule>, Error> {
+    let mut rules = Vec::new();
+    for (index, source) in sources.iter().enumerate() {
+        let fail = |message: String| Error::parse(message).with("rule", index);
+        let tokens = tokenize(source).map_err(fail)?;
+        let mut parser = Parser { tokens: &tokens, pos: 0, variables: Vec::new() };
+        while parser.peek().is_some() {
+            let start = parser.offset();
+            let mut rule = parser.rule().map_err(fail)?;
+            let end = parser.offset().min(source.len());
+            rule.text = source[start..end].trim().to_string();
+            rules.push(prepare(rule).map_err(|e| Error::invalid(e).with("rule", index))?);
+        }
+    }
+    Ok(rules)
+}
+
+/// A set of tuples with a per-column index for joins
+#[derive(Default)]
+struct Relation {
+    tuples: Vec<Vec<String>>,
+    set: HashSet<Vec<String>>,
+    index: Vec<HashMap<String, Vec<usize>>>,
+}
+
+impl Relation {
+    fn insert(&mut self, tuple: Vec<String>) -> bool {
+        if self.set.contains(&tuple) {
+            return false;
+        }
+        if self.index.len() < tuple.len() {
+            self.index.resize_with(tuple.len(), HashMap::new);
+        }
+        let at = self.tuples.len();
+        for (column, value) in tuple.iter().enumerate() {
+            self.index[column].entry(value.clone()).or_default().push(at);
+        }
+        self.set.insert(tuple.clone());
+        self.tuples.push(tuple);
+        true
+    }
+
+    fn len(&self) -> usize {
+        self.tuples.len()
+    }
+
+    /// Tuples that can match `atom` under `binding`, narrowed by the first
+    /// fixed argument
+    fn candidates<'a>(&'a self, atom: &Atom, binding: &[Option<&str>]) -> Box<dyn Iterator<Item = &'a Vec<String>> + 'a> {
+        let fixed = atom.args.iter().enumerate().find_map(|(column, term)| match term {
+            Term::Const(c) => Some((column, c.as_str())),
+            Term::Var(v) => binding[*v].map(|value| (column, value)),
+        });
+        match fixed {
+            Some((column, value)) => {
+                let rows = self.index.get(column).and_then(|index| index.get(value));
+                Box::new(rows.into_iter().flatten().map(move |&i| &self.tuples[i]))
+            }
+            None => Box::new(self.tuples.iter()),
+        }
+    }
+}
+
+type Database = HashMap<String, Relation>;
+
+fn base_facts(experiences: &[Value]) -> Database {
+    let mut db: Database = BASE_PREDICATES.iter().map(|(name, _)| (name.to_string(), Relation::default())).collect();
+    let mut add = |predicate: &str, id: &str, value: String| {
+        if let Some(relation) = db.get_mut(predicate) {
+            relation.insert(vec![id.to_string(), value]);
+        }
+    };
+
+    for exp in experiences {
+        let Some(id) = exp.get("id").and_then(Value::as_str) else {
+            continue;
+        };
+        if let Some(learner) = lookup(exp, "learner.id").and_then(Value::as_str) {
+            add("experience", id, canonical_learner_id(learner));
+        }
+        if let Some(ts) = exp.get("timestamp").and_then(Value::as_str) {
+            add("timestamp", id, ts.to_string());
+            if let Some(ms) = parse_timestamp(ts) {
+                add("day", id, format_timestamp(ms)[..10].to_string());
+                let (year, week) = iso_week(ms);
+                add("week", id, format!("{}-W{:02}", year, week));
+            }
+        }
+        if let Some(t) = lookup(exp, "experience.type").and_then(Value::as_str) {
+            add("type", id, t.to_string());
+        }
+        for domain in domains(exp) {
+            add("domain", id, domain.to_string());
+        }
+        if let Some(place) = lookup(exp, "context.location.name").and_then(Value::as_str).map(str::trim).filter(|p| !p.is_empty()) {
+            add("place", id, place.to_string());
+        }
+        for related in lookup(exp, "metadata.related_experiences").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str) {
+            add("related", id, related.to_string());
+        }
+    }
+    db
+}
+
+fn fact_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Assign each derived predicate a stratum so negated predicates are
+/// complete before they are used
+fn stratify(rules: &[Rule], derived: &BTreeSet<String>) -> Result<HashMap<String, usize>, Error> {
+    let mut strata: HashMap<String, usize> = derived.iter().map(|p| (p.clone(), 0)).collect();
+    let limit = derived.len();
+    loop {
+        let mut changed = false;
+        for rule in rules {
+            let mut level = strata[&rule.head.predicate];
+            for literal in &rule.body {
+                let (atom, step) = match literal {
+                    Literal::Positive(atom) => (atom, 0),
+                    Literal::Negative(atom) => (atom, 1),
+                    Literal::Compare(..) => continue,
+                };
+                if let Some(&s) = strata.get(&atom.predicate) {
+                    level = level.max(s + step);
+                }
+            }
+            if level > limit {
+                return Err(Error::invalid(format!(
+                    "rules are not stratified: {} depends negatively on itself",
+                    rule.head.predicate
+                ))
+                .with("predicate", rule.head.predicate.as_str()));
+            }
+            if level > strata[&rule.head.predicate] {
+                strata.insert(rule.head.predicate.clone(), level);
+                changed = true;
+            }
+        }
+        if !changed {
+            return Ok(strata);
+        }
+    }
+}
+
+/// Check predicate arities agree across base facts, extra facts and rules
+fn check_arities(rules: &[Rule], db: &Database) -> Result<(), Error> {
+    let mut arities: HashMap<&str, usize> = BASE_PREDICATES.iter().map(|&(name, arity)| (name, arity)).collect();
+    for (name, relation) in db {
+        if let Some(tuple) = relation.tuples.first() {
+            arities.entry(name).or_insert(tuple.len());
+        }
+    }
+    for rule in rules {
+        let atoms = std::iter::once(&rule.head).chain(rule.body.iter().filter_map(|l| match l {
+            Literal::Positive(atom) | Literal::Negative(atom) => Some(atom),
+            Literal::Compare(..) => None,
+        }));
+        for atom in atoms {
+            let arity = *arities.entry(&atom.predicate).or_insert(atom.args.len());
+            if arity != atom.args.len() {
+                return Err(Error::invalid(format!(
+                    "{} is used with {} arguments but has arity {} in: {}",
+                    atom.predicate,
+                    atom.args.len(),
+                    arity,
+                    rule.text
+                ))
+                .with("predicate", atom.predicate.as_str()));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn resolve<'a>(term: &'a Term, binding: &[Option<&'a str>]) -> Option<&'a str> {
+    match term {
+        Term::Const(c) => Some(c),
+        Term::Var(v) => binding[*v],
+    }
+}
+
+/// Join the body from literal `at` on, collecting head tuples. `delta`
+/// replaces the relation of the positive literal at that index.
+fn join<'a>(
+    rule: &'a Rule,
+    at: usize,
+    db: &'a Database,
+    delta: Option<(usize, &'a Relation)>,
+    binding: &mut Vec<Option<&'a str>>,
+    out: &mut Vec<Vec<String>>,
+) {
+    let Some(literal) = rule.body.get(at) else {
+        out.push(rule.head.args.iter().map(|t| resolve(t, binding).unwrap_or_default().to_string()).collect());
+        return;
+    };
+    match literal {
+        Literal::Compare(left, op, right) => {
+            if let (Some(l), Some(r)) = (resolve(left, binding), resolve(right, binding)) {
+                if op.holds(compare_values(l, r)) {
+                    join(rule, at + 1, db, delta, binding, out);
+                }
+            }
+        }
+        Literal::Negative(atom) => {
+            // Only anonymous variables are still unbound here
+            let present = db.get(&atom.predicate).is_some_and(|relation| {
+                let tuple: Option<Vec<String>> = atom.args.iter().map(|t| resolve(t, binding).map(str::to_string)).collect();
+                match tuple {
+                    Some(tuple) => relation.set.contains(&tuple),
+                    None => relation.candidates(atom, binding).any(|tuple| {
+                        atom.args.iter().zip(tuple).all(|(term, value)| resolve(term, binding).is_none_or(|r| r == value))
+                    }),
+                }
+            });
+            if !present {
+                join(rule, at + 1, db, delta, binding, out);
+            }
+        }
+        Literal::Positive(atom) => {
+            let relation = match delta {
+                Some((index, relation)) if index == at => relation,
+                _ => match db.get(&atom.predicate) {
+                    Some(relation) => relation,
+                    None => return,
+                },
+            };
+            for tuple in relation.candidates(atom, binding) {
+                let mut newly = Vec::new();
+                let mut matched = true;
+                for (term, value) in atom.args.iter().zip(tuple) {
+                    match term {
+                        Term::Const(c) => matched = c == value,
+                        Term::Var(v) => match binding[*v] {
+                            Some(existing) => matched = existing == value,
+                            None => {
+                                binding[*v] = Some(value);
+                                newly.push(*v);
+                            }
+                        },
+                    }
+                    if !matched {
+                        break;
+                    }
+                }
+                if matched {
+                    join(rule, at + 1, db, delta, binding, out);
+                }
+                for v in newly {
+                    binding[v] = None;
+                }
+            }
+        }
+    }
+}
+
+fn evaluate(rules: &[Rule], db: &mut Database, strata: &HashMap<String, usize>) -> usize {
+    let levels = strata.values().max().map_or(0, |m| m + 1);
+    for level in 0..levels {
+        let stratum: Vec<&Rule> = rules.iter().filter(|r| strata[&r.head.predicate] == level).collect();
+        let recursive = |atom: &Atom| strata.get(&atom.predicate) == Some(&level);
+
+        // First round over the full relations, then semi-naive rounds that
+        // join at least one literal against the previous round's new facts
+        let mut delta: HashMap<String, Relation> = HashMap::new();
+        for rule in &stratum {
+            let mut out = Vec::new();
+            join(rule, 0, db, None, &mut vec![None; rule.variables.len()], &mut out);
+            for tuple in out {
+                delta.entry(rule.head.predicate.clone()).or_default().insert(tuple);
+            }
+        }
+        loop {
+            let mut fresh: HashMap<String, Relation> = HashMap::new();
+            for (predicate, relation) in delta.drain() {
+                let target = db.entry(predicate.clone()).or_default();
+                for tuple in relation.tuples {
+                    if target.insert(tuple.clone()) {
+                        fresh.entry(predicate.clone()).or_default().insert(tuple);
+                    }
+                }
+            }
+            if fresh.is_empty() {
+                break;
+            }
+            for rule in &stratum {
+                let mut out = Vec::new();
+                for (index, literal) in rule.body.iter().enumerate() {
+                    let Literal::Positive(atom) = literal else { continue };
+                    if !recursive(atom) {
+                        continue;
+                    }
+                    if let Some(relation) = fresh.get(&atom.predicate) {
+                        join(rule, 0, db, Some((index, relation)), &mut vec![None; rule.variables.len()], &mut out);
+                    }
+                }
+                for tuple in out {
+                    if !db.get(&rule.head.predicate).is_some_and(|r| r.set.contains(&tuple)) {
+                        delta.entry(rule.head.predicate.clone()).or_default().insert(tuple);
+                    }
+                }
+            }
+        }
+    }
+    levels
+}
+
+/// Derive facts from experiences with stratified Datalog rules
+/// `{rules: [program text], facts?: {predicate: [[args]]}, output?: [predicate]}`
+/// Returns `{facts: {predicate: [[args]]}, counts, strata}` as JSON for the
+/// derived predicates (or those named in `output`), tuples sorted
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn apply_rules(experiences_json: &str, rules_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let rule_set: RuleSet = from_json(rules_json, "rules_json")?;
+    let rules = parse_rules(&rule_set.rules)?;
+
+    let mut db = base_facts(&experiences);
+    for (predicate, tuples) in &rule_set.facts {
+        if BASE_PREDICATES.iter().any(|(name, _)| name == predicate) {
+            return Err(Error::invalid(format!("{} is a base predicate", predicate)).with("predicate", predicate.as_str()));
+        }
+        let relation = db.entry(predicate.clone()).or_default();
+        for tuple in tuples {
+            relation.insert(tuple.iter().map(fact_value).collect());
+        }
+    }
+    for (predicate, relation) in &db {
+        if let Some(tuple) = relation.tuples.iter().find(|t| t.len() != relation.tuples[0].len()) {
+            return Err(Error::invalid(format!("{} facts have mixed arities ({} and {})", predicate, relation.tuples[0].len(), tuple.len()))
+                .with("predicate", predicate.as_str()));
+        }
+    }
+    check_arities(&rules, &db)?;
+
+    if let Some(rule) = rules.iter().find(|r| BASE_PREDICATES.iter().any(|(name, _)| *name == r.head.predicate)) {
+        return Err(Error::invalid(format!("rules cannot derive base predicate {}: {}", rule.head.predicate, rule.text))
+            .with("predicate", rule.head.predicate.as_str()));
+    }
+    let derived: BTreeSet<String> = rules.iter().map(|r| r.head.predicate.clone()).collect();
+    let strata = stratify(&rules, &derived)?;
+    let levels = evaluate(&rules, &mut db, &strata);
+
+    let output: Vec<String> = if rule_set.output.is_empty() {
+        derived.into_iter().collect()
+    } else {
+        rule_set.output
+    };
+    let mut result = RuleResult { facts: BTreeMap::new(), counts: BTreeMap::new(), strata: levels };
+    for predicate in output {
+        let relation = db
+            .get(&predicate)
+            .ok_or_else(|| Error::not_found(format!("unknown predicate {}", predicate)).with("predicate", predicate.as_str()))?;
+        let mut tuples = relation.tuples.clone();
+        tuples.sort();
+        result.counts.insert(predicate.clone(), relation.len());
+        result.facts.insert(predicate, tuples);
+    }
+    to_json(&result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use serde_json::json;
+
+    fn experience(id: &str, learner: &str, timestamp: &str, domains: &[&str]) -> Value {
+        json!({
+            "id": id,
+            "timestamp": timestamp,
+            "learner": {"id": learner},
+            "context": {"location": {"name": "library"}},
+            "experience": {"type": "observation", "description": "d", "domains": domains},
+        })
+    }
+
+    fn run(experiences: &[Value], rules: Value) -> Result<Value, Error> {
+        let result = apply_rules(&Value::from(experiences.to_vec()).to_string(), &rules.to_string())?;
+        Ok(serde_json::from_str(&result).unwrap())
+    }
+
+    #[test]
+    fn derives_interdisciplinary_learners() {
+        let experiences = [
+            experience("e1", "alice", "2024-05-01T10:00:00Z", &["ecology"]),
+            experience("e2", "alice", "2024-05-03T10:00:00Z", &["chemistry"]),
+            experience("e3", "bob", "2024-05-01T10:00:00Z", &["ecology"]),
+            experience("e4", "bob", "2024-05-20T10:00:00Z", &["chemistry"]),
+        ];
+        let result = run(
+            &experiences,
+            json!({
+                "rules": [
+                    "same_week(L, D1, D2) :- experience(E1, L), experience(E2, L), week(E1, W), week(E2, W),
+                                             domain(E1, D1), domain(E2, D2), D1 != D2.",
+                    "interdisciplinary(L) :- same_week(L, D1, D2), branch(D1, B1), branch(D2, B2), B1 != B2.",
+                ],
+                "facts": {"branch": [["ecology", "life_science"], ["chemistry", "physical_science"]]},
+                "output": ["interdisciplinary"],
+            }),
+        )
+        .unwrap();
+        assert_eq!(result["facts"]["interdisciplinary"], json!([["alice"]]));
+        assert_eq!(result["counts"]["interdisciplinary"], 1);
+    }
+
+    #[test]
+    fn recursive_rules_reach_a_fixpoint() {
+        let result = run(
+            &[],
+            json!({
+                "rules": ["path(A, B) :- edge(A, B).", "path(A, C) :- path(A, B), edge(B, C)."],
+                "facts": {"edge": [["a", "b"], ["b", "c"], ["c", "d"], ["d", "b"]]},
+            }),
+        )
+        .unwrap();
+        let paths = &result["facts"]["path"];
+        assert_eq!(paths.as_array().unwrap().len(), 12);
+        assert!(paths.as_array().unwrap().contains(&json!(["a", "d"])));
+        assert!(paths.as_array().unwrap().contains(&json!(["b", "b"])));
+        assert!(!paths.as_array().unwrap().contains(&json!(["b", "a"])));
+        assert_eq!(result["strata"], 1);
+    }
+
+    #[test]
+    fn negation_runs_in_a_later_stratum() {
+        let result = run(
+            &[],
+            json!({
+                "rules": [
+                    "reachable(X) :- start(X).",
+                    "reachable(Y) :- reachable(X), edge(X, Y).",
+                    "unreachable(X) :- node(X), not reachable(X).",
+                ],
+                "facts": {
+                    "start": [["a"]],
+                    "edge": [["a", "b"], ["c", "d"]],
+                    "node": [["a"], ["b"], ["c"], ["d"]],
+                },
+                "output": ["unreachable"],
+            }),
+        )
+        .unwrap();
+        assert_eq!(result["facts"]["unreachable"], json!([["c"], ["d"]]));
+        assert_eq!(result["strata"], 2);
+    }
+
+    #[test]
+    fn anonymous_variables_in_negations_match_anything() {
+        let result = run(
+            &[],
+            json!({
+                "rules": ["idle(L) :- learner(L), not attended(L, _)."],
+                "facts": {"learner": [["alice"], ["bob"]], "attended": [["alice", "walk"]]},
+            }),
+        )
+        .unwrap();
+        assert_eq!(result["facts"]["idle"], json!([["bob"]]));
+    }
+
+    #[test]
+    fn rejects_unsafe_rules() {
+        let err = run(&[], json!({"rules": ["lonely(L) :- not attended(L, _)."]})).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert!(err.message().contains("variable L"), "{}", err.message());
+
+        let err = run(&[], json!({"rules": ["early(E) :- experience(E, _), T < \"2024\"."]})).unwrap_err();
+        assert!(err.message().contains("variable T"), "{}", err.message());
+    }
+
+    #[test]
+    fn rejects_unstratifiable_programs() {
+        let err = run(
+            &[],
+            json!({
+                "rules": ["win(X) :- move(X, Y), not win(Y)."],
+                "facts": {"move": [["a", "b"], ["b", "a"]]},
+            }),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert!(err.message().contains("not stratified"), "{}", err.message());
+        assert_eq!(err.context()["predicate"], "win");
+    }
+}
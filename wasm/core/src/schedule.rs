@@ -0,0 +1,331 @@
+//! Multi-objective weekly activity scheduling
+//!
+//! [`suggest_schedule`] fills a learner's free slots with visits to known
+//! places. Each visit stands for the experience the place would produce
+//! (its `type` and `domains`), scored against the learner's goals (see
+//! [`crate::goals`]). A schedule's score balances goal progress, variety of
+//! places and travel distance; schedules are built greedily from several
+//! starting choices, improved by single-slot local search, and the best
+//! distinct ones are returned in rank order.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::geo::haversine_m;
+use crate::goals::{parse_goals, Goal};
+use crate::numeric::sum;
+use crate::places::{Place, Point};
+use crate::time::{format_timestamp, parse_timestamp, MS_PER_MINUTE};
+
+#[derive(Deserialize)]
+struct Slot {
+    start: String,
+    end: String,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct Weights {
+    progress: f64,
+    variety: f64,
+    travel: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            progress: 1.0,
+            variety: 0.5,
+            travel: 0.5,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct Constraints {
+    /// Where each trip starts; trips chain from the previous visit otherwise
+    home: Option<Point>,
+    duration_minutes: f64,
+    speed_kmh: f64,
+    max_activities: Option<usize>,
+    /// Travel distance per activity that costs as much as one full goal
+    travel_scale_km: f64,
+    options: usize,
+    weights: Weights,
+}
+
+impl Default for Constraints {
+    fn default() -> Self {
+        Self {
+            home: None,
+            duration_minutes: 60.0,
+            speed_kmh: 15.0,
+            max_activities: None,
+            travel_scale_km: 10.0,
+            options: 3,
+            weights: Weights::default(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Activity {
+    start: String,
+    end: String,
+    place: String,
+    place_name: Option<String>,
+    travel_km: f64,
+    travel_minutes: f64,
+}
+
+#[derive(Serialize)]
+struct ScheduleOption {
+    rank: usize,
+    score: f64,
+    progress: f64,
+    variety: f64,
+    travel_km: f64,
+    goal_progress: BTreeMap<String, u32>,
+    activities: Vec<Activity>,
+}
+
+struct Problem<'a> {
+    slots: Vec<(i64, i64)>,
+    places: &'a [Place],
+    /// `contributes[p][g]`: whether a visit to place `p` counts towards goal `g`
+    contributes: Vec<Vec<bool>>,
+    goals: &'a [Goal],
+    c: &'a Constraints,
+}
+
+/// Slot index → place index
+type Assignment = Vec<Option<usize>>;
+
+impl Problem<'_> {
+    fn duration_ms(&self, place: usize) -> i64 {
+        (self.places[place].duration_minutes.unwrap_or(self.c.duration_minutes) * MS_PER_MINUTE as f64) as i64
+    }
+
+    /// Legs of the schedule: `(slot, place, km)`
+    fn legs(&self, assignment: &Assignment) -> Vec<(usize, usize, f64)> {
+        let mut from = self.c.home;
+        let mut legs = Vec::new();
+        for (slot, place) in assignment.iter().enumerate() {
+            let Some(place) = *place else { continue };
+            let p = &self.places[place];
+            let km = from.map_or(0.0, |f| haversine_m(f.latitude, f.longitude, p.latitude, p.longitude) / 1000.0);
+            legs.push((slot, place, km));
+            if self.c.home.is_none() {
+                from = Some(Point {
+                    latitude: p.latitude,
+                    longitude: p.longitude,
+                });
+            }
+        }
+        legs
+    }
+
+    fn travel_ms(&self, km: f64) -> i64 {
+        (km / self.c.speed_kmh * 60.0 * MS_PER_MINUTE as f64) as i64
+    }
+
+    fn feasible(&self, assignment: &Assignment) -> bool {
+        let count = assignment.iter().flatten().count();
+        if self.c.max_activities.is_some_and(|max| count > max) {
+            return false;
+        }
+        self.legs(assignment).iter().all(|&(slot, place, km)| {
+            let (start, end) = self.slots[slot];
+            start + self.travel_ms(km) + self.duration_ms(place) <= end
+        })
+    }
+
+    /// `(score, progress, variety, travel_km)`
+    fn evaluate(&self, assignment: &Assignment) -> (f64, f64, f64, f64) {
+        let visits: Vec<usize> = assignment.iter().flatten().copied().collect();
+        if visits.is_empty() {
+            return (0.0, 0.0, 0.0, 0.0);
+        }
+        let progress = sum(self.goals.iter().enumerate().map(|(g, goal)| {
+            let count = visits.iter().filter(|&&p| self.contributes[p][g]).count() as f64;
+            count.min(f64::from(goal.target)) / f64::from(goal.target.max(1))
+        }));
+        let variety = visits.iter().collect::<HashSet<_>>().len() as f64 / visits.len() as f64;
+        let travel_km = sum(self.legs(assignment).iter().map(|leg| leg.2));
+        let w = &self.c.weights;
+        let score = w.progress * progress + w.variety * variety
+            - w.travel * travel_km / (self.c.travel_scale_km * visits.len() as f64);
+        (score, progress, variety, travel_km)
+    }
+
+    /// Fill slots in time order with the best feasible improvement
+    fn greedy(&self, mut assignment: Assignment) -> Assignment {
+        for slot in 0..self.slots.len() {
+            if assignment[slot].is_some() {
+                continue;
+            }
+            let mut best = (self.evaluate(&assignment).0, None);
+            for place in 0..self.places.len() {
+                assignment[slot] = Some(place);
+                if self.feasible(&assignment) {
+                    let score = self.evaluate(&assignment).0;
+                    if score > best.0 {
+                        best = (score, Some(place));
+                    }
+                }
+            }
+            assignment[slot] = best.1;
+        }
+        assignment
+    }
+
+    /// Change one slot at a time while that improves the score
+    fn improve(&self, mut assignment: Assignment) -> Assignment {
+        let mut score = self.evaluate(&assignment).0;
+        let mut improved = true;
+        while improved {
+            improved = false;
+            for slot in 0..self.slots.len() {
+                let current = assignment[slot];
+                for choice in std::iter::once(None).chain((0..self.places.len()).map(Some)) {
+                    if choice == current {
+                        continue;
+                    }
+                    assignment[slot] = choice;
+                    if self.feasible(&assignment) {
+                        let candidate = self.evaluate(&assignment).0;
+                        if candidate > score + 1e-9 {
+                            score = candidate;
+                            improved = true;
+                            break;
+                        }
+                    }
+                    assignment[slot] = current;
+                }
+            }
+        }
+        assignment
+    }
+
+    fn option(&self, assignment: &Assignment) -> ScheduleOption {
+        let (score, progress, variety, travel_km) = self.evaluate(assignment);
+        let mut goal_progress = BTreeMap::new();
+        for (g, goal) in self.goals.iter().enumerate() {
+            let count = assignment.iter().flatten().filter(|&&p| self.contributes[p][g]).count() as u32;
+            goal_progress.insert(goal.id.clone(), count);
+        }
+        let activities = self
+            .legs(assignment)
+            .into_iter()
+            .map(|(slot, place, km)| {
+                let start = self.slots[slot].0 + self.travel_ms(km);
+                Activity {
+                    start: format_timestamp(start),
+                    end: format_timestamp(start + self.duration_ms(place)),
+                    place: self.places[place].id.clone(),
+                    place_name: self.places[place].name.clone(),
+                    travel_km: km,
+                    travel_minutes: km / self.c.speed_kmh * 60.0,
+                }
+            })
+            .collect();
+        ScheduleOption {
+            rank: 0,
+            score,
+            progress,
+            variety,
+            travel_km,
+            goal_progress,
+            activities,
+        }
+    }
+}
+
+fn plan(slots: &[Slot], goals: &[Goal], places: &[Place], c: &Constraints) -> Result<Vec<ScheduleOption>, String> {
+    if c.speed_kmh.is_nan() || c.speed_kmh <= 0.0 {
+        return Err("speed_kmh must be positive".to_string());
+    }
+    let mut parsed = Vec::with_capacity(slots.len());
+    for slot in slots {
+        let start = parse_timestamp(&slot.start).ok_or_else(|| format!("invalid slot start: {}", slot.start))?;
+        let end = parse_timestamp(&slot.end).ok_or_else(|| format!("invalid slot end: {}", slot.end))?;
+        parsed.push((start, end));
+    }
+    parsed.sort_unstable();
+
+    let problem = Problem {
+        slots: parsed,
+        contributes: places
+            .iter()
+            .map(|place| {
+                let exp = place.template();
+                goals.iter().map(|goal| goal.counts(&exp)).collect()
+            })
+            .collect(),
+        places,
+        goals,
+        c,
+    };
+
+    // One start from scratch plus one seeded with each place in the first
+    // slot it fits, for a spread of distinct local optima
+    let empty: Assignment = vec![None; problem.slots.len()];
+    let mut seeds = vec![empty.clone()];
+    for place in 0..places.len() {
+        if let Some(seeded) = (0..problem.slots.len())
+            .map(|slot| {
+                let mut a = empty.clone();
+                a[slot] = Some(place);
+                a
+            })
+            .find(|a| problem.feasible(a))
+        {
+            seeds.push(seeded);
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut options: Vec<ScheduleOption> = seeds
+        .into_iter()
+        .map(|seed| problem.improve(problem.greedy(seed)))
+        .filter(|assignment| seen.insert(assignment.clone()))
+        .map(|assignment| problem.option(&assignment))
+        .collect();
+    options.sort_by(|a, b| b.score.total_cmp(&a.score));
+    options.truncate(c.options.max(1));
+    for (i, option) in options.iter_mut().enumerate() {
+        option.rank = i + 1;
+    }
+    Ok(options)
+}
+
+/// Propose ranked schedules filling `availability_json` slots
+/// (`[{start, end}]`) with visits to `places_json`
+/// (`[{id, name, latitude, longitude, type, domains, duration_minutes}]`)
+/// towards the goals. `constraints_json` may set `home`, `duration_minutes`,
+/// `speed_kmh`, `max_activities`, `travel_scale_km`, `options` and
+/// `weights`; pass an empty string for defaults
+/// Returns the schedule options as JSON, best first
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn suggest_schedule(
+    availability_json: &str,
+    goals_json: &str,
+    places_json: &str,
+    constraints_json: &str,
+) -> Result<String, Error> {
+    let slots: Vec<Slot> = from_json(availability_json, "availability_json")?;
+    let goals = parse_goals(goals_json).map_err(Error::invalid)?;
+    let places: Vec<Place> = from_json(places_json, "places_json")?;
+    let constraints: Constraints = if constraints_json.trim().is_empty() {
+        Constraints::default()
+    } else {
+        from_json(constraints_json, "constraints_json")?
+    };
+
+    let options = plan(&slots, &goals, &places, &constraints).map_err(Error::invalid)?;
+    to_json(&options)
+}
@@ -0,0 +1,123 @@
+//! Full-text search over experience descriptions
+//!
+//! An inverted index ranked with Okapi BM25. The index is built once from a
+//! JSON array and then queried many times from JS.
+
+use serde::Serialize;
+use std::collections::HashMap;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::Experience;
+use crate::error::{from_json, to_json, Error};
+use crate::numeric::ln;
+use crate::text::terms;
+
+/// BM25 term-frequency saturation
+const K1: f64 = 1.2;
+/// BM25 length normalisation
+const B: f64 = 0.75;
+
+#[derive(Serialize)]
+struct SearchHit {
+    id: String,
+    score: f64,
+}
+
+/// Inverted index over experience descriptions
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct SearchIndex {
+    ids: Vec<String>,
+    doc_lengths: Vec<u32>,
+    avg_doc_length: f64,
+    postings: HashMap<String, Vec<(u32, u32)>>,
+    stemming: bool,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl SearchIndex {
+    /// Build an index from a JSON array of experiences
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn build(experiences_json: &str, stemming: bool) -> Result<SearchIndex, Error> {
+        let experiences: Vec<Experience> = from_json(experiences_json, "experiences_json")?;
+
+        let mut index = SearchIndex {
+            ids: Vec::with_capacity(experiences.len()),
+            doc_lengths: Vec::with_capacity(experiences.len()),
+            avg_doc_length: 0.0,
+            postings: HashMap::new(),
+            stemming,
+        };
+
+        for (doc, exp) in experiences.iter().enumerate() {
+            let doc_terms = terms(&exp.experience.description, stemming);
+            let mut frequencies: HashMap<String, u32> = HashMap::new();
+            for term in &doc_terms {
+                *frequencies.entry(term.clone()).or_insert(0) += 1;
+            }
+            for (term, tf) in frequencies {
+                index.postings.entry(term).or_default().push((doc as u32, tf));
+            }
+            index.ids.push(exp.id.clone());
+            index.doc_lengths.push(doc_terms.len() as u32);
+        }
+
+        let total: u64 = index.doc_lengths.iter().map(|&l| u64::from(l)).sum();
+        index.avg_doc_length = if index.ids.is_empty() {
+            0.0
+        } else {
+            total as f64 / index.ids.len() as f64
+        };
+
+        Ok(index)
+    }
+
+    /// Number of indexed experiences
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn size(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Ranked search; returns `[{id, score}]` as JSON, best match first
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn search(&self, query: &str, limit: usize) -> Result<String, Error> {
+        to_json(&self.rank(query, limit))
+    }
+
+    fn rank(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let n = self.ids.len() as f64;
+        let mut query_terms = terms(query, self.stemming);
+        query_terms.sort();
+        query_terms.dedup();
+
+        let mut scores: HashMap<u32, f64> = HashMap::new();
+        for term in &query_terms {
+            let Some(postings) = self.postings.get(term) else {
+                continue;
+            };
+            let df = postings.len() as f64;
+            let idf = ln((n - df + 0.5) / (df + 0.5) + 1.0);
+            for &(doc, tf) in postings {
+                let tf = f64::from(tf);
+                let len_norm = if self.avg_doc_length > 0.0 {
+                    f64::from(self.doc_lengths[doc as usize]) / self.avg_doc_length
+                } else {
+                    0.0
+                };
+                *scores.entry(doc).or_insert(0.0) += idf * tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * len_norm));
+            }
+        }
+
+        let mut ranked: Vec<(u32, f64)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked.truncate(limit);
+
+        ranked
+            .into_iter()
+            .map(|(doc, score)| SearchHit {
+                id: self.ids[doc as usize].clone(),
+                score,
+            })
+            .collect()
+    }
+}
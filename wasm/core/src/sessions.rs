@@ -0,0 +1,262 @@
+//! Learning sessions
+//!
+//! [`detect_sessions`] groups each learner's experiences into sessions: a
+//! session ends when the next experience comes more than `max_gap_minutes`
+//! later, or more than `max_distance_m` from the previous located one.
+//! Experiences without coordinates only split on time. Most reports start
+//! from sessions rather than raw experiences, so the summary carries what
+//! they need: duration, dominant domains and the places visited in order.
+//!
+//! A session id is derived from its learner and first experience, so it
+//! stays the same when later experiences are added to the session.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::crypto::{sha256, to_hex};
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::geo::haversine_m;
+use crate::identity::canonical_learner_id;
+use crate::query::{coordinates, domains};
+use crate::time::{format_timestamp, parse_timestamp, MS_PER_MINUTE, MS_PER_SECOND};
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct SessionConfig {
+    max_gap_minutes: f64,
+    max_distance_m: f64,
+    /// Domains listed per session, most frequent first
+    top_domains: usize,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            max_gap_minutes: 30.0,
+            max_distance_m: 500.0,
+            top_domains: 3,
+        }
+    }
+}
+
+struct Entry<'a> {
+    ms: i64,
+    id: &'a str,
+    position: Option<(f64, f64)>,
+    place: Option<&'a str>,
+    domains: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+struct DomainCount<'a> {
+    domain: &'a str,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct Session<'a> {
+    id: String,
+    learner: String,
+    start: String,
+    end: String,
+    duration_s: f64,
+    experiences: Vec<&'a str>,
+    dominant_domains: Vec<DomainCount<'a>>,
+    /// `context.location.name` values in visiting order, repeats collapsed
+    locations: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+struct Transition<'a> {
+    from: &'a str,
+    to: &'a str,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct SessionReport<'a> {
+    sessions: Vec<Session<'a>>,
+    /// Moves between named locations within sessions, most frequent first
+    transitions: Vec<Transition<'a>>,
+}
+
+/// Splits time-ordered entries into runs
+fn split<'e, 'a>(entries: &'e [Entry<'a>], config: &SessionConfig) -> Vec<&'e [Entry<'a>]> {
+    let max_gap_ms = config.max_gap_minutes * MS_PER_MINUTE as f64;
+    let mut runs = Vec::new();
+    let mut start = 0;
+    let mut last_position: Option<(f64, f64)> = None;
+    for (i, entry) in entries.iter().enumerate() {
+        if i > start {
+            let gap = (entry.ms - entries[i - 1].ms) as f64 > max_gap_ms;
+            let moved = match (last_position, entry.position) {
+                (Some((lat1, lon1)), Some((lat2, lon2))) => haversine_m(lat1, lon1, lat2, lon2) > config.max_distance_m,
+                _ => false,
+            };
+            if gap || moved {
+                runs.push(&entries[start..i]);
+                start = i;
+                last_position = None;
+            }
+        }
+        last_position = entry.position.or(last_position);
+    }
+    if start < entries.len() {
+        runs.push(&entries[start..]);
+    }
+    runs
+}
+
+fn session<'a>(learner: &str, run: &[Entry<'a>], config: &SessionConfig) -> Session<'a> {
+    let (first, last) = (&run[0], &run[run.len() - 1]);
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for domain in run.iter().flat_map(|e| e.domains.iter()) {
+        *counts.entry(domain).or_insert(0) += 1;
+    }
+    let mut dominant: Vec<DomainCount> = counts.into_iter().map(|(domain, count)| DomainCount { domain, count }).collect();
+    dominant.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.domain.cmp(b.domain)));
+    dominant.truncate(config.top_domains);
+    let mut locations: Vec<&str> = Vec::new();
+    for place in run.iter().filter_map(|e| e.place) {
+        if locations.last() != Some(&place) {
+            locations.push(place);
+        }
+    }
+    Session {
+        id: format!("s_{}", to_hex(&sha256(format!("{}\n{}\n{}", learner, first.ms, first.id).as_bytes())[..8])),
+        learner: learner.to_string(),
+        start: format_timestamp(first.ms),
+        end: format_timestamp(last.ms),
+        duration_s: (last.ms - first.ms) as f64 / MS_PER_SECOND as f64,
+        experiences: run.iter().map(|e| e.id).collect(),
+        dominant_domains: dominant,
+        locations,
+    }
+}
+
+/// Group experiences into per-learner sessions
+/// `config_json` sets `max_gap_minutes`, `max_distance_m` and `top_domains`
+/// (empty for 30, 500 and 3)
+/// Returns `{sessions: [{id, learner, start, end, duration_s, experiences,
+/// dominant_domains: [{domain, count}], locations}], transitions: [{from,
+/// to, count}]}` as JSON, sessions ordered by learner then start
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn detect_sessions(experiences_json: &str, config_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let config: SessionConfig = if config_json.trim().is_empty() {
+        SessionConfig::default()
+    } else {
+        from_json(config_json, "config_json")?
+    };
+    for (name, value) in [("max_gap_minutes", config.max_gap_minutes), ("max_distance_m", config.max_distance_m)] {
+        if value.is_nan() || value < 0.0 {
+            return Err(Error::invalid(format!("{} must not be negative", name)).with("field", name));
+        }
+    }
+
+    let mut by_learner: BTreeMap<String, Vec<Entry>> = BTreeMap::new();
+    for exp in &experiences {
+        let (Some(learner), Some(ms)) = (
+            lookup(exp, "learner.id").and_then(Value::as_str),
+            lookup(exp, "timestamp").and_then(Value::as_str).and_then(parse_timestamp),
+        ) else {
+            continue;
+        };
+        by_learner.entry(canonical_learner_id(learner)).or_default().push(Entry {
+            ms,
+            id: lookup(exp, "id").and_then(Value::as_str).unwrap_or_default(),
+            position: coordinates(exp),
+            place: lookup(exp, "context.location.name").and_then(Value::as_str),
+            domains: domains(exp).collect(),
+        });
+    }
+
+    let mut sessions = Vec::new();
+    for (learner, entries) in &mut by_learner {
+        entries.sort_by(|a, b| a.ms.cmp(&b.ms).then_with(|| a.id.cmp(b.id)));
+        sessions.extend(split(entries, &config).into_iter().map(|run| session(learner, run, &config)));
+    }
+    let mut counts: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+    for s in &sessions {
+        for pair in s.locations.windows(2) {
+            *counts.entry((pair[0], pair[1])).or_insert(0) += 1;
+        }
+    }
+    let mut transitions: Vec<Transition> = counts.into_iter().map(|((from, to), count)| Transition { from, to, count }).collect();
+    transitions.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| (a.from, a.to).cmp(&(b.from, b.to))));
+    to_json(&SessionReport { sessions, transitions })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn experience(id: &str, learner: &str, timestamp: &str, place: Option<(&str, f64)>) -> Value {
+        let location = match place {
+            Some((name, lat)) => json!({"name": name, "coordinates": {"latitude": lat, "longitude": 0.0}}),
+            None => json!({}),
+        };
+        json!({"id": id, "timestamp": timestamp, "learner": {"id": learner}, "context": {"location": location},
+               "experience": {"domains": ["ecology"]}})
+    }
+
+    fn sessions(experiences: &[Value], config: &str) -> Value {
+        serde_json::from_str(&detect_sessions(&Value::from(experiences.to_vec()).to_string(), config).unwrap()).unwrap()
+    }
+
+    fn runs(report: &Value) -> Vec<Vec<&str>> {
+        report["sessions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["experiences"].as_array().unwrap().iter().map(|e| e.as_str().unwrap()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn splits_only_on_gaps_longer_than_the_limit() {
+        let experiences = [
+            experience("a", "ada", "2024-05-01T10:00:00Z", None),
+            experience("b", "ada", "2024-05-01T10:30:00Z", None),
+            experience("c", "ada", "2024-05-01T11:00:00.001Z", None),
+            experience("d", "bo", "2024-05-01T10:00:00Z", None),
+        ];
+        let report = sessions(&experiences, "");
+        assert_eq!(runs(&report), [vec!["a", "b"], vec!["c"], vec!["d"]]);
+        assert_eq!(report["sessions"][0]["duration_s"], 1_800.0);
+        assert_eq!(report["sessions"][1]["duration_s"], 0.0);
+
+        assert_eq!(runs(&sessions(&experiences[..2], r#"{"max_gap_minutes": 29.99}"#)), [vec!["a"], vec!["b"]]);
+        // A zero limit still keeps simultaneous experiences together
+        assert_eq!(runs(&sessions(&[experiences[0].clone(), experiences[0].clone()], r#"{"max_gap_minutes": 0}"#)).len(), 1);
+    }
+
+    #[test]
+    fn splits_on_distance_from_the_last_located_experience() {
+        // 0.004° of latitude is about 445 m
+        let experiences = [
+            experience("a", "ada", "2024-05-01T10:00:00Z", Some(("pond", 51.0))),
+            experience("b", "ada", "2024-05-01T10:05:00Z", None),
+            experience("c", "ada", "2024-05-01T10:10:00Z", Some(("meadow", 51.004))),
+            experience("d", "ada", "2024-05-01T10:15:00Z", Some(("wood", 51.01))),
+        ];
+        let report = sessions(&experiences, "");
+        assert_eq!(runs(&report), [vec!["a", "b", "c"], vec!["d"]]);
+        assert_eq!(report["sessions"][0]["locations"], json!(["pond", "meadow"]));
+        assert_eq!(report["transitions"], json!([{"from": "pond", "to": "meadow", "count": 1}]));
+        assert_eq!(runs(&sessions(&experiences, r#"{"max_distance_m": 400}"#)), [vec!["a", "b"], vec!["c"], vec!["d"]]);
+    }
+
+    #[test]
+    fn session_ids_survive_later_additions() {
+        let first = [experience("a", "ada", "2024-05-01T10:00:00Z", None)];
+        let more = [first[0].clone(), experience("b", "ada", "2024-05-01T10:10:00Z", None)];
+        assert_eq!(sessions(&first, "")["sessions"][0]["id"], sessions(&more, "")["sessions"][0]["id"]);
+        assert!(detect_sessions("[]", r#"{"max_gap_minutes": -1}"#).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+//! Validation rules and their severities
+//!
+//! Every check the validator runs has a stable rule id. Most rules are
+//! errors and make an experience invalid; a few flag data that is usable but
+//! worth a second look (missing coordinates, very short descriptions,
+//! domains outside the deployment's list) and only warn. Deployments move
+//! rules between the two tiers with `ExperienceValidator::set_severity_overrides`:
+//!
+//! ```json
+//! {"coordinates.missing": "error", "reactions": "warning"}
+//! ```
+//!
+//! A key names one rule or, as a prefix, a whole family (`reactions` covers
+//! `reactions.actor.required`, ...); the most specific key wins.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::error::Error;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify_next::Tsify))]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Severity {
+    Error,
+    Warning,
+}
+
+/// Every rule with its default severity and English message template;
+/// `{name}` placeholders are filled from the issue's params
+pub(crate) const RULES: &[(&str, Severity, &str)] = &[
+    ("id.required", Severity::Error, "id is required"),
+    ("id.format", Severity::Error, "id is not in an accepted format"),
+    ("timestamp.required", Severity::Error, "timestamp is required"),
+    ("learner.id.required", Severity::Error, "learner.id is required"),
+    ("learner.id.invalid", Severity::Error, "learner.id is not a valid identifier: {reason}"),
+    ("location.name.required", Severity::Error, "context.location.name is required"),
+    ("type.required", Severity::Error, "experience.type is required"),
+    ("description.required", Severity::Error, "experience.description is required"),
+    ("description.short", Severity::Warning, "experience.description is shorter than {min} characters"),
+    ("coordinates.missing", Severity::Warning, "context.location.coordinates are missing"),
+    ("coordinates.latitude.range", Severity::Error, "latitude must be between -90 and 90"),
+    ("coordinates.longitude.range", Severity::Error, "longitude must be between -180 and 180"),
+    ("coordinates.null_island", Severity::Error, "context.location.coordinates are (0, 0), a placeholder position"),
+    (
+        "coordinates.precision",
+        Severity::Warning,
+        "context.location.coordinates have more than {max} decimal places, which suggests fabricated data",
+    ),
+    ("coordinates.accuracy.range", Severity::Error, "context.location.coordinates.accuracy must be a non-negative distance"),
+    (
+        "coordinates.altitude.range",
+        Severity::Error,
+        "context.location.coordinates.altitude must be between {min} and {max} metres",
+    ),
+    (
+        "coordinates.source.unknown",
+        Severity::Error,
+        "context.location.coordinates.source {source} is not one of gps, network, manual or imputed",
+    ),
+    ("domain.unknown", Severity::Warning, "experience.domains has unknown domain {domain}"),
+    ("tenant.invalid", Severity::Error, "tenant {reason}"),
+    ("accessibility.modality.format", Severity::Error, "experience.accessibility.modality must be a lowercase token"),
+    (
+        "accessibility.accommodations.format",
+        Severity::Error,
+        "experience.accessibility.accommodations must be lowercase tokens",
+    ),
+    (
+        "accessibility.accommodations.duplicate",
+        Severity::Error,
+        "experience.accessibility.accommodations must not repeat",
+    ),
+    (
+        "measurements.unit.invalid",
+        Severity::Error,
+        "experience.measurements.{name}.unit {unit} is not a UCUM unit: {reason}",
+    ),
+    (
+        "measurements.quantity.unknown",
+        Severity::Error,
+        "experience.measurements.{name}.quantity {quantity} is not a known quantity",
+    ),
+    (
+        "measurements.quantity.mismatch",
+        Severity::Error,
+        "experience.measurements.{name}.unit {unit} does not measure {quantity}",
+    ),
+    ("measurements.value.range", Severity::Error, "experience.measurements.{name} is below absolute zero"),
+    ("reactions.actor.required", Severity::Error, "reactions[{index}].actor is required"),
+    ("reactions.actor.invalid", Severity::Error, "reactions[{index}].actor is not a valid identifier: {reason}"),
+    ("reactions.emoji.invalid", Severity::Error, "reactions[{index}].emoji must be a single emoji"),
+    ("reactions.timestamp.invalid", Severity::Error, "reactions[{index}].timestamp is not a valid date-time"),
+    ("reactions.duplicate", Severity::Error, "reactions[{index}] repeats an earlier reaction by the same actor"),
+    ("comments.id.required", Severity::Error, "comments[{index}].id is required"),
+    ("comments.id.duplicate", Severity::Error, "comments[{index}].id {id} is not unique"),
+    ("comments.author.required", Severity::Error, "comments[{index}].author is required"),
+    ("comments.author.invalid", Severity::Error, "comments[{index}].author is not a valid identifier: {reason}"),
+    ("comments.text.required", Severity::Error, "comments[{index}].text is required"),
+    ("comments.timestamp.invalid", Severity::Error, "comments[{index}].timestamp is not a valid date-time"),
+    ("comments.parent.missing", Severity::Error, "comments[{index}].parent {parent} does not exist"),
+    ("comments.parent.order", Severity::Error, "comments[{index}] is earlier than its parent"),
+    ("comments.cycle", Severity::Error, "comments[{index}] is part of a reply cycle"),
+];
+
+/// One failed check: a rule id plus the values its message refers to
+pub(crate) struct Issue {
+    pub(crate) rule: &'static str,
+    pub(crate) params: Vec<(&'static str, String)>,
+}
+
+impl Issue {
+    pub(crate) fn new(rule: &'static str) -> Self {
+        Issue { rule, params: Vec::new() }
+    }
+
+    pub(crate) fn with(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.params.push((name, value.to_string()));
+        self
+    }
+}
+
+/// Whether an override key names `rule` itself or a family containing it
+fn covers(key: &str, rule: &str) -> bool {
+    rule.strip_prefix(key).is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+/// Parse and check an overrides object; every key must cover some rule
+pub(crate) fn parse_overrides(overrides: HashMap<String, Severity>) -> Result<HashMap<String, Severity>, Error> {
+    for key in overrides.keys() {
+        if !RULES.iter().any(|(rule, _, _)| covers(key, rule)) {
+            return Err(Error::invalid(format!("unknown validation rule: {}", key)).with("rule", key.as_str()));
+        }
+    }
+    Ok(overrides)
+}
+
+/// Effective severity of a rule under the given overrides
+pub(crate) fn severity(rule: &str, overrides: &HashMap<String, Severity>) -> Severity {
+    overrides
+        .iter()
+        .filter(|(key, _)| covers(key, rule))
+        .max_by_key(|(key, _)| key.len())
+        .map(|(_, &severity)| severity)
+        .or_else(|| RULES.iter().find(|(r, _, _)| *r == rule).map(|&(_, severity, _)| severity))
+        .unwrap_or(Severity::Error)
+}
@@ -0,0 +1,272 @@
+//! Geofences from ESRI Shapefiles
+//!
+//! Municipal boundary data (parks, school grounds, wards) usually arrives as
+//! a Shapefile. [`read_shapefile`] turns the `.shp` geometry and `.dbf`
+//! attribute table into the GeoJSON FeatureCollection that `match_context`,
+//! `coverage_of_region` and `reconcile_locations` take, reprojecting to
+//! WGS84 on the way when the layer's CRS is given (see `crs.rs` for the
+//! supported grids).
+//!
+//! Points, multipoints, polylines and polygons are read, with or without Z
+//! and M values, which are dropped. Polygon rings are grouped the way the
+//! format defines them: clockwise rings are outer boundaries and
+//! anticlockwise rings are holes in the outer ring that contains them; the
+//! output winds them the other way round, as RFC 7946 asks. Null shapes and
+//! records marked deleted in the `.dbf` are skipped.
+//!
+//! Attribute text is decoded as UTF-8 when valid and as Latin-1 otherwise,
+//! which covers the encodings `.cpg` files name in practice.
+
+use serde_json::{json, Map, Value};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::crs::reproject;
+use crate::error::{to_json, Error};
+
+const FILE_CODE: i32 = 9994;
+const HEADER_BYTES: usize = 100;
+/// Field descriptor array terminator in a `.dbf` header
+const DBF_TERMINATOR: u8 = 0x0D;
+
+type Ring = Vec<[f64; 2]>;
+
+fn take<'a>(bytes: &'a [u8], offset: usize, len: usize, file: &str) -> Result<&'a [u8], Error> {
+    offset
+        .checked_add(len)
+        .and_then(|end| bytes.get(offset..end))
+        .ok_or_else(|| Error::parse(format!("{} ends early at byte {}", file, offset)).with("offset", offset))
+}
+
+fn be_i32(bytes: &[u8], offset: usize) -> Result<i32, Error> {
+    Ok(i32::from_be_bytes(take(bytes, offset, 4, ".shp")?.try_into().expect("4 bytes")))
+}
+
+fn le_i32(bytes: &[u8], offset: usize) -> Result<i32, Error> {
+    Ok(i32::from_le_bytes(take(bytes, offset, 4, ".shp")?.try_into().expect("4 bytes")))
+}
+
+fn le_f64(bytes: &[u8], offset: usize) -> Result<f64, Error> {
+    Ok(f64::from_le_bytes(take(bytes, offset, 8, ".shp")?.try_into().expect("8 bytes")))
+}
+
+fn count(bytes: &[u8], offset: usize) -> Result<usize, Error> {
+    usize::try_from(le_i32(bytes, offset)?).map_err(|_| Error::parse(format!("negative count at byte {}", offset)).with("offset", offset))
+}
+
+/// `n` XY points starting at `offset`
+fn points(content: &[u8], offset: usize, n: usize) -> Result<Vec<[f64; 2]>, Error> {
+    take(content, offset, n.saturating_mul(16), ".shp")?;
+    (0..n).map(|i| Ok([le_f64(content, offset + 16 * i)?, le_f64(content, offset + 16 * i + 8)?])).collect()
+}
+
+/// Parts of a polyline or polygon record, after its bounding box
+fn parts(content: &[u8]) -> Result<Vec<Ring>, Error> {
+    let (num_parts, num_points) = (count(content, 36)?, count(content, 40)?);
+    let starts: Vec<usize> = (0..num_parts).map(|i| count(content, 44 + 4 * i)).collect::<Result<_, _>>()?;
+    let all = points(content, 44 + 4 * num_parts, num_points)?;
+    let mut parts = Vec::with_capacity(num_parts);
+    for (i, &start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or(num_points);
+        if start > end || end > num_points {
+            return Err(Error::parse(format!("part {} has invalid bounds", i)));
+        }
+        parts.push(all[start..end].to_vec());
+    }
+    Ok(parts)
+}
+
+/// Twice the signed area; negative for clockwise rings
+fn signed_area(ring: &[[f64; 2]]) -> f64 {
+    ring.windows(2).map(|w| w[0][0] * w[1][1] - w[1][0] * w[0][1]).sum()
+}
+
+fn ring_contains(ring: &[[f64; 2]], [x, y]: [f64; 2]) -> bool {
+    let mut inside = false;
+    let mut j = ring.len().wrapping_sub(1);
+    for i in 0..ring.len() {
+        let ([xi, yi], [xj, yj]) = (ring[i], ring[j]);
+        if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+fn coordinates(rings: &[Ring]) -> Value {
+    Value::from(rings.iter().map(|ring| ring.iter().map(|p| json!(p)).collect::<Vec<_>>()).collect::<Vec<_>>())
+}
+
+/// Group rings into polygons of an outer ring and its holes
+fn polygon(mut rings: Vec<Ring>) -> Option<Value> {
+    rings.retain(|ring| ring.len() >= 4);
+    let (outers, holes): (Vec<Ring>, Vec<Ring>) = rings.into_iter().partition(|ring| signed_area(ring) <= 0.0);
+    let mut polygons: Vec<Vec<Ring>> = Vec::new();
+    for mut outer in outers {
+        outer.reverse();
+        polygons.push(vec![outer]);
+    }
+    for mut hole in holes {
+        hole.reverse();
+        match polygons.iter_mut().find(|polygon| ring_contains(&polygon[0], hole[0])) {
+            Some(polygon) => polygon.push(hole),
+            // A hole outside every outer ring is taken for a mis-wound outer ring
+            None => {
+                hole.reverse();
+                polygons.push(vec![hole]);
+            }
+        }
+    }
+    match polygons.len() {
+        0 => None,
+        1 => Some(json!({"type": "Polygon", "coordinates": coordinates(&polygons[0])})),
+        _ => Some(json!({
+            "type": "MultiPolygon",
+            "coordinates": polygons.iter().map(|p| coordinates(p)).collect::<Vec<_>>(),
+        })),
+    }
+}
+
+/// GeoJSON geometry of one record's content, `None` for a null shape
+fn geometry(content: &[u8]) -> Result<Option<Value>, Error> {
+    let shape_type = le_i32(content, 0)?;
+    Ok(match shape_type {
+        0 => None,
+        1 | 11 | 21 => Some(json!({"type": "Point", "coordinates": points(content, 4, 1)?[0]})),
+        8 | 18 | 28 => {
+            let points = points(content, 40, count(content, 36)?)?;
+            (!points.is_empty()).then(|| json!({"type": "MultiPoint", "coordinates": points}))
+        }
+        3 | 13 | 23 => {
+            let mut lines = parts(content)?;
+            lines.retain(|line| line.len() >= 2);
+            match lines.len() {
+                0 => None,
+                1 => Some(json!({"type": "LineString", "coordinates": lines[0]})),
+                _ => Some(json!({"type": "MultiLineString", "coordinates": lines})),
+            }
+        }
+        5 | 15 | 25 => polygon(parts(content)?),
+        other => return Err(Error::invalid(format!("unsupported shape type {}", other)).with("shape_type", other)),
+    })
+}
+
+fn text(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.to_string(),
+        Err(_) => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+struct Field {
+    name: String,
+    kind: u8,
+    length: usize,
+    decimals: u8,
+}
+
+fn field_value(field: &Field, raw: &[u8]) -> Value {
+    let raw = text(raw);
+    let raw = raw.trim_matches(|c: char| c == ' ' || c == '\0');
+    match field.kind {
+        _ if raw.is_empty() => Value::Null,
+        b'N' | b'F' => {
+            if field.decimals == 0 {
+                if let Ok(n) = raw.parse::<i64>() {
+                    return Value::from(n);
+                }
+            }
+            raw.parse::<f64>().ok().filter(|n| n.is_finite()).map_or(Value::Null, Value::from)
+        }
+        b'L' => match raw {
+            "T" | "t" | "Y" | "y" => Value::Bool(true),
+            "F" | "f" | "N" | "n" => Value::Bool(false),
+            _ => Value::Null,
+        },
+        b'D' if raw.len() == 8 && raw.bytes().all(|b| b.is_ascii_digit()) => {
+            Value::from(format!("{}-{}-{}", &raw[..4], &raw[4..6], &raw[6..]))
+        }
+        _ => Value::from(raw),
+    }
+}
+
+/// Attribute rows of a `.dbf` file, `None` for deleted records
+fn attributes(dbf: &[u8]) -> Result<Vec<Option<Map<String, Value>>>, Error> {
+    let header = take(dbf, 0, 32, ".dbf")?;
+    let records = u32::from_le_bytes(header[4..8].try_into().expect("4 bytes")) as usize;
+    let header_len = u16::from_le_bytes(header[8..10].try_into().expect("2 bytes")) as usize;
+    let record_len = u16::from_le_bytes(header[10..12].try_into().expect("2 bytes")) as usize;
+
+    let mut fields = Vec::new();
+    let mut offset = 32;
+    while offset < header_len && take(dbf, offset, 1, ".dbf")?[0] != DBF_TERMINATOR {
+        let descriptor = take(dbf, offset, 32, ".dbf")?;
+        let name = text(descriptor[..11].split(|&b| b == 0).next().unwrap_or_default()).trim().to_string();
+        fields.push(Field { name, kind: descriptor[11], length: descriptor[16] as usize, decimals: descriptor[17] });
+        offset += 32;
+    }
+    if fields.iter().map(|f| f.length).sum::<usize>() + 1 > record_len {
+        return Err(Error::parse("dbf fields are longer than its records"));
+    }
+
+    let mut rows = Vec::with_capacity(records.min(dbf.len() / record_len.max(1)));
+    for index in 0..records {
+        let record = take(dbf, header_len + index * record_len, record_len, ".dbf")?;
+        if record[0] == b'*' {
+            rows.push(None);
+            continue;
+        }
+        let mut properties = Map::new();
+        let mut at = 1;
+        for field in &fields {
+            properties.insert(field.name.clone(), field_value(field, &record[at..at + field.length]));
+            at += field.length;
+        }
+        rows.push(Some(properties));
+    }
+    Ok(rows)
+}
+
+/// Read a Shapefile as a GeoJSON FeatureCollection of geofences
+/// `dbf` is the attribute table (empty for no properties) and `from_crs`
+/// the layer's CRS as in `reproject_geojson` (empty when it is already
+/// WGS84 longitude/latitude)
+/// Returns the FeatureCollection, each feature's `id` its record number,
+/// as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn read_shapefile(shp: &[u8], dbf: &[u8], from_crs: &str) -> Result<String, Error> {
+    if be_i32(shp, 0)? != FILE_CODE || le_i32(shp, 28)? != 1000 {
+        return Err(Error::parse("not a Shapefile (.shp)"));
+    }
+    let rows = if dbf.is_empty() { Vec::new() } else { attributes(dbf)? };
+    // The header's length is in 16-bit words; trust the bytes we have
+    let declared = usize::try_from(be_i32(shp, 24)?).unwrap_or(0).saturating_mul(2);
+    let end = declared.min(shp.len());
+
+    let mut features = Vec::new();
+    let (mut offset, mut index) = (HEADER_BYTES, 0);
+    while offset + 8 <= end {
+        let number = be_i32(shp, offset)?;
+        let length = usize::try_from(be_i32(shp, offset + 4)?).map_err(|_| Error::parse("negative record length"))?.saturating_mul(2);
+        let content = take(shp, offset + 8, length, ".shp")?;
+        let geometry = geometry(content).map_err(|e| e.with("record", number))?;
+        let properties = match rows.get(index) {
+            Some(None) => None,
+            Some(Some(properties)) => Some(properties.clone()),
+            None if rows.is_empty() => Some(Map::new()),
+            None => return Err(Error::invalid(format!("the .dbf has no row for record {}", number)).with("record", number)),
+        };
+        if let (Some(geometry), Some(properties)) = (geometry, properties) {
+            features.push(json!({"type": "Feature", "id": number, "geometry": geometry, "properties": properties}));
+        }
+        offset += 8 + length;
+        index += 1;
+    }
+
+    let mut collection = json!({"type": "FeatureCollection", "features": features});
+    if !from_crs.trim().is_empty() {
+        reproject(&mut collection, from_crs, "EPSG:4326")?;
+    }
+    to_json(&collection)
+}
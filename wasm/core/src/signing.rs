@@ -0,0 +1,82 @@
+//! Ed25519 signatures over experiences
+//!
+//! The signed payload is the experience's canonical JSON with the learner id
+//! canonicalized (so equivalent DID / IRI spellings verify identically) and
+//! any top-level `proof` removed, so a signature can be embedded in the
+//! record it covers. Keys and signatures cross the boundary as raw bytes
+//! (`Uint8Array`): 32-byte secret seed, 32-byte public key, 64-byte
+//! signature.
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde_json::Value;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::crypto::{canonical_json, random_bytes};
+use crate::error::{from_json, Error};
+use crate::identity::canonical_learner_id;
+
+/// Canonical bytes covered by an experience signature
+pub(crate) fn signing_payload(experience: &Value) -> Vec<u8> {
+    let mut payload = experience.clone();
+    if let Some(obj) = payload.as_object_mut() {
+        obj.remove("proof");
+        if let Some(id) = obj.get_mut("learner").and_then(|learner| learner.get_mut("id")) {
+            if let Some(canonical) = id.as_str().map(canonical_learner_id) {
+                *id = Value::from(canonical);
+            }
+        }
+    }
+    canonical_json(&payload).into_bytes()
+}
+
+pub(crate) fn signing_key(private_key: &[u8]) -> Result<SigningKey, String> {
+    let seed: [u8; 32] = private_key
+        .try_into()
+        .map_err(|_| "private key must be 32 bytes".to_string())?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+pub(crate) fn verifying_key(public_key: &[u8]) -> Result<VerifyingKey, String> {
+    let bytes: [u8; 32] = public_key
+        .try_into()
+        .map_err(|_| "public key must be 32 bytes".to_string())?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| e.to_string())
+}
+
+pub(crate) fn sign_value(experience: &Value, key: &SigningKey) -> [u8; 64] {
+    key.sign(&signing_payload(experience)).to_bytes()
+}
+
+pub(crate) fn verify_value(experience: &Value, signature: &[u8], key: &VerifyingKey) -> bool {
+    Signature::from_slice(signature).is_ok_and(|sig| key.verify(&signing_payload(experience), &sig).is_ok())
+}
+
+/// Generate a fresh 32-byte Ed25519 secret seed from the host CSPRNG
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn generate_signing_key() -> Result<Vec<u8>, Error> {
+    random_bytes::<32>().map(Vec::from).map_err(Error::host)
+}
+
+/// Public key for a 32-byte secret seed
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn public_key_for(private_key: &[u8]) -> Result<Vec<u8>, Error> {
+    let key = signing_key(private_key).map_err(Error::crypto)?;
+    Ok(key.verifying_key().to_bytes().to_vec())
+}
+
+/// Sign an experience; returns the 64-byte signature
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn sign_experience(json: &str, private_key: &[u8]) -> Result<Vec<u8>, Error> {
+    let experience: Value = from_json(json, "json")?;
+    let key = signing_key(private_key).map_err(Error::crypto)?;
+    Ok(sign_value(&experience, &key).to_vec())
+}
+
+/// Verify an experience signature against a 32-byte public key
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn verify_experience(json: &str, signature: &[u8], public_key: &[u8]) -> Result<bool, Error> {
+    let experience: Value = from_json(json, "json")?;
+    let key = verifying_key(public_key).map_err(Error::crypto)?;
+    Ok(verify_value(&experience, signature, &key))
+}
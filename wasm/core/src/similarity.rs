@@ -0,0 +1,184 @@
+//! Frequency-aware similarity between domain profiles
+//!
+//! [`crate::jaccard_similarity`] compares sets, so ten visits to a domain
+//! weigh the same as one. These measures compare multisets instead: either
+//! domain→count maps (`{"ecology": 10, "art": 1}`) or plain arrays whose
+//! repeats are counted (`["ecology", "ecology", "art"]`).
+//! [`similarity_matrix`] compares every pair in a set of profiles, and
+//! [`similarity_matrix_typed`] returns the matrix as a `Float64Array`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::buffers::Buffers;
+use crate::error::{from_json, to_json, Error};
+use crate::kernels::{pair_sums, PairSums};
+use crate::numeric::sum;
+use crate::parallel;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MultisetIn {
+    Counts(BTreeMap<String, f64>),
+    Items(Vec<String>),
+}
+
+/// Profiles in a matrix, past which the caller should sample or cluster
+const MAX_MATRIX_PROFILES: usize = 4_096;
+/// Largest profiles × keys table a matrix spreads into dense rows (128 MB)
+const MAX_DENSE_CELLS: usize = 16 * 1024 * 1024;
+
+fn parse_multiset(json: &str, argument: &str) -> Result<BTreeMap<String, f64>, Error> {
+    counts(from_json(json, argument)?)
+}
+
+fn counts(parsed: MultisetIn) -> Result<BTreeMap<String, f64>, Error> {
+    match parsed {
+        MultisetIn::Counts(counts) => {
+            if let Some((key, _)) = counts.iter().find(|(_, &c)| !c.is_finite() || c < 0.0) {
+                return Err(Error::invalid(format!("count for {} must be a non-negative number", key)));
+            }
+            Ok(counts)
+        }
+        MultisetIn::Items(items) => {
+            let mut counts = BTreeMap::new();
+            for item in items {
+                *counts.entry(item).or_insert(0.0) += 1.0;
+            }
+            Ok(counts)
+        }
+    }
+}
+
+fn ratio(numerator: f64, denominator: f64) -> f64 {
+    if denominator > 0.0 {
+        numerator / denominator
+    } else {
+        0.0
+    }
+}
+
+/// Total count and L2 norm of a multiset
+fn stats(m: &BTreeMap<String, f64>) -> (f64, f64) {
+    (sum(m.values().copied()), sum(m.values().map(|c| c * c)).sqrt())
+}
+
+/// Position of each key of any of the multisets, in key order
+fn key_index<'a>(multisets: &[&'a BTreeMap<String, f64>]) -> BTreeMap<&'a str, usize> {
+    let keys: BTreeSet<&str> = multisets.iter().flat_map(|m| m.keys().map(String::as_str)).collect();
+    keys.into_iter().enumerate().map(|(i, k)| (k, i)).collect()
+}
+
+fn dense(m: &BTreeMap<String, f64>, index: &BTreeMap<&str, usize>) -> Vec<f64> {
+    let mut row = vec![0.0; index.len()];
+    for (k, &c) in m {
+        row[index[k.as_str()]] = c;
+    }
+    row
+}
+
+/// The named measure from a pair's sums and each side's `stats`
+fn score(sums: PairSums, (total_a, norm_a): (f64, f64), (total_b, norm_b): (f64, f64), measure: &str) -> Result<f64, String> {
+    Ok(match measure {
+        "jaccard" => ratio(sums.min, sums.max),
+        "dice" => ratio(2.0 * sums.min, total_a + total_b),
+        "overlap" => ratio(sums.min, total_a.min(total_b)),
+        "cosine" => ratio(sums.dot, norm_a * norm_b),
+        other => return Err(format!("unknown measure: {} (expected jaccard, dice, overlap or cosine)", other)),
+    })
+}
+
+/// Similarity of two multisets under the named measure
+fn similarity(a: &BTreeMap<String, f64>, b: &BTreeMap<String, f64>, measure: &str) -> Result<f64, String> {
+    let index = key_index(&[a, b]);
+    score(pair_sums(&dense(a, &index), &dense(b, &index)), stats(a), stats(b), measure)
+}
+
+/// Weighted Jaccard similarity of two domain→count maps:
+/// Σ min(a, b) / Σ max(a, b)
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn weighted_jaccard(map1_json: &str, map2_json: &str) -> Result<f64, Error> {
+    multiset_similarity(map1_json, map2_json, "jaccard")
+}
+
+/// Similarity of two multisets (count maps or arrays with repeats) by
+/// `measure`: `jaccard` (min/max), `dice`, `overlap` or `cosine`
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn multiset_similarity(a_json: &str, b_json: &str, measure: &str) -> Result<f64, Error> {
+    let (a, b) = (parse_multiset(a_json, "a_json")?, parse_multiset(b_json, "b_json")?);
+    similarity(&a, &b, measure).map_err(Error::invalid)
+}
+
+#[derive(Serialize)]
+struct SimilarityMatrix<'a> {
+    ids: &'a [String],
+    /// Row-major, `matrix[i][j]` comparing `ids[i]` with `ids[j]`
+    matrix: Vec<&'a [f64]>,
+}
+
+#[derive(Serialize)]
+struct MatrixIndex<'a> {
+    /// Row and column order of the float buffer
+    ids: &'a [String],
+    size: usize,
+    measure: &'a str,
+}
+
+/// Ids in key order and the row-major matrix of pairwise similarities
+fn matrix(profiles_json: &str, measure: &str) -> Result<(Vec<String>, Vec<f64>), Error> {
+    let parsed: BTreeMap<String, MultisetIn> = from_json(profiles_json, "profiles_json")?;
+    if parsed.len() > MAX_MATRIX_PROFILES {
+        return Err(Error::invalid(format!("at most {} profiles fit in a matrix", MAX_MATRIX_PROFILES)).with("profiles", parsed.len()));
+    }
+    score(PairSums::default(), (0.0, 0.0), (0.0, 0.0), measure).map_err(Error::invalid)?;
+    let mut ids = Vec::with_capacity(parsed.len());
+    let mut profiles = Vec::with_capacity(parsed.len());
+    for (id, multiset) in parsed {
+        profiles.push(counts(multiset).map_err(|e| e.with("id", &*id))?);
+        ids.push(id);
+    }
+    let n = profiles.len();
+    let stats: Vec<(f64, f64)> = profiles.iter().map(stats).collect();
+    // Spread every profile over the shared keys once, so each pair is one
+    // pass of the vector kernel; for vast key sets compare pairwise instead
+    let index = key_index(&profiles.iter().collect::<Vec<_>>());
+    let rows: Option<Vec<Vec<f64>>> =
+        (n.saturating_mul(index.len()) <= MAX_DENSE_CELLS).then(|| profiles.iter().map(|p| dense(p, &index)).collect());
+    let upper = parallel::map_range(n, |i| {
+        (i..n)
+            .map(|j| match &rows {
+                Some(rows) => score(pair_sums(&rows[i], &rows[j]), stats[i], stats[j], measure),
+                None => similarity(&profiles[i], &profiles[j], measure),
+            })
+            .collect::<Result<Vec<f64>, String>>()
+    });
+    let mut values = vec![0.0; n * n];
+    for (i, row) in upper.into_iter().enumerate() {
+        for (offset, value) in row.map_err(Error::invalid)?.into_iter().enumerate() {
+            values[i * n + i + offset] = value;
+            values[(i + offset) * n + i] = value;
+        }
+    }
+    Ok((ids, values))
+}
+
+/// Pairwise similarity of named multisets, `{id: counts or items}`, by
+/// `measure` as in `multiset_similarity`
+/// Returns `{ids, matrix}` as JSON, ids sorted and `matrix[i][j]` comparing
+/// `ids[i]` with `ids[j]`
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn similarity_matrix(profiles_json: &str, measure: &str) -> Result<String, Error> {
+    let (ids, values) = matrix(profiles_json, measure)?;
+    let matrix = values.chunks(ids.len().max(1)).collect();
+    to_json(&SimilarityMatrix { ids: &ids, matrix })
+}
+
+/// `similarity_matrix` as typed arrays: the matrix row-major as floats
+/// Returns Buffers indexed by `{ids, size, measure}`
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn similarity_matrix_typed(profiles_json: &str, measure: &str) -> Result<Buffers, Error> {
+    let (ids, values) = matrix(profiles_json, measure)?;
+    Buffers::new(&MatrixIndex { ids: &ids, size: ids.len(), measure }, values, Vec::new())
+}
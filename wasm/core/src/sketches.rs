@@ -0,0 +1,527 @@
+//! Mergeable sketches for on-device partial aggregates
+//!
+//! Each device summarises its own experiences into small fixed-size
+//! sketches and ships only those; the server merges sketches from many
+//! devices and answers the aggregate question without seeing raw records:
+//!
+//! - [`HyperLogLog`]: distinct counts (learners, domains, places)
+//! - [`TDigest`]: percentiles of a numeric field (durations, scores)
+//! - [`CountMinSketch`]: approximate frequencies (tags, domains)
+//!
+//! Merging is associative and commutative, so partials can be combined in
+//! any order or tree shape. Items are hashed with SHA-256, which makes
+//! sketches portable between builds and languages and keeps the raw values
+//! out of them. `to_bytes` / `from_bytes` use CBOR.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::crypto::sha256;
+use crate::error::{from_json, Error, ErrorKind};
+use crate::export::lookup;
+use crate::numeric::{asin, ln, sum};
+
+/// Bumped whenever an encoding below changes incompatibly
+const FORMAT_VERSION: u32 = 1;
+
+/// Serialized form shared by all sketches; `kind` stops a t-digest being
+/// decoded as a HyperLogLog
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    kind: String,
+    version: u32,
+    sketch: T,
+}
+
+fn encode<T: Serialize>(kind: &str, sketch: &T) -> Result<Vec<u8>, Error> {
+    let envelope = Envelope {
+        kind: kind.to_string(),
+        version: FORMAT_VERSION,
+        sketch,
+    };
+    let mut out = Vec::new();
+    ciborium::ser::into_writer(&envelope, &mut out).map_err(|e| Error::new(ErrorKind::Serialization, e))?;
+    Ok(out)
+}
+
+fn decode<T: DeserializeOwned>(kind: &str, bytes: &[u8]) -> Result<T, Error> {
+    let envelope: Envelope<ciborium::Value> =
+        ciborium::de::from_reader(bytes).map_err(|e| Error::parse(e).with("argument", "bytes"))?;
+    if envelope.kind != kind {
+        return Err(Error::invalid(format!("expected a {} sketch, got {}", kind, envelope.kind)).with("kind", envelope.kind));
+    }
+    if envelope.version != FORMAT_VERSION {
+        return Err(Error::invalid(format!("unsupported {} sketch version {}", kind, envelope.version))
+            .with("version", envelope.version));
+    }
+    envelope
+        .sketch
+        .deserialized()
+        .map_err(|e| Error::parse(e).with("argument", "bytes"))
+}
+
+/// Values at a dotted path in every experience; arrays contribute each
+/// element, nulls nothing
+fn field_values<'a>(experiences: &'a [Value], path: &'a str) -> impl Iterator<Item = &'a Value> {
+    experiences
+        .iter()
+        .filter_map(move |exp| lookup(exp, path))
+        .flat_map(|value| match value {
+            Value::Array(items) => items.iter().collect(),
+            Value::Null => Vec::new(),
+            other => vec![other],
+        })
+}
+
+/// Text form of a value for hashing: strings as-is, anything else as JSON
+fn item_text(value: &Value) -> String {
+    value.as_str().map_or_else(|| value.to_string(), str::to_string)
+}
+
+fn hash64(item: &str, seed: u8) -> u64 {
+    let mut data = Vec::with_capacity(item.len() + 1);
+    data.push(seed);
+    data.extend_from_slice(item.as_bytes());
+    let digest = sha256(&data);
+    u64::from_be_bytes(digest[..8].try_into().expect("digest is 32 bytes"))
+}
+
+/// HyperLogLog distinct counter
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HyperLogLog {
+    precision: u8,
+    registers: Vec<u8>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl HyperLogLog {
+    /// Empty counter with `2^precision` registers (4-18); 12 gives about
+    /// 1.6% standard error in 4 KiB
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(precision: u8) -> Result<HyperLogLog, Error> {
+        if !(4..=18).contains(&precision) {
+            return Err(Error::invalid("precision must be between 4 and 18").with("precision", precision));
+        }
+        Ok(Self {
+            precision,
+            registers: vec![0; 1 << precision],
+        })
+    }
+
+    /// Count one item
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn add(&mut self, item: &str) {
+        let hash = hash64(item, 0);
+        let index = (hash >> (64 - self.precision)) as usize;
+        let rest = hash << self.precision;
+        let rank = (rest.leading_zeros() + 1).min(u32::from(65 - self.precision)) as u8;
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    /// Count the values at a dotted path (e.g. `learner.id`,
+    /// `experience.domains`) of every experience
+    /// Returns the number of values added
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn add_experiences(&mut self, experiences_json: &str, path: &str) -> Result<usize, Error> {
+        let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+        let mut added = 0;
+        for value in field_values(&experiences, path) {
+            self.add(&item_text(value));
+            added += 1;
+        }
+        Ok(added)
+    }
+
+    /// Estimated number of distinct items
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn estimate(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let harmonic = sum(self.registers.iter().map(|&r| 1.0 / (1u64 << r) as f64));
+        let raw = alpha * m * m / harmonic;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        // Linear counting is more accurate while many registers are empty
+        if raw <= 2.5 * m && zeros > 0 {
+            m * ln(m / zeros as f64)
+        } else {
+            raw
+        }
+    }
+
+    /// Standard error of `estimate()` relative to the true count
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn relative_error(&self) -> f64 {
+        1.04 / (self.registers.len() as f64).sqrt()
+    }
+
+    /// Fold another counter of the same precision into this one
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn merge(&mut self, other: &HyperLogLog) -> Result<(), Error> {
+        if other.precision != self.precision {
+            return Err(Error::invalid("cannot merge HyperLogLogs of different precision")
+                .with("precision", self.precision)
+                .with("other_precision", other.precision));
+        }
+        for (mine, theirs) in self.registers.iter_mut().zip(&other.registers) {
+            *mine = (*mine).max(*theirs);
+        }
+        Ok(())
+    }
+
+    /// Serialize to CBOR for transfer or storage
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        encode("hyperloglog", self)
+    }
+
+    /// Restore a counter produced by `to_bytes`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<HyperLogLog, Error> {
+        let sketch: HyperLogLog = decode("hyperloglog", bytes)?;
+        if !(4..=18).contains(&sketch.precision)
+            || sketch.registers.len() != 1 << sketch.precision
+            || sketch.registers.iter().any(|&r| r > 65 - sketch.precision)
+        {
+            return Err(Error::invalid("HyperLogLog registers do not match its precision"));
+        }
+        Ok(sketch)
+    }
+}
+
+/// Centroid of a t-digest: mean and weight of the points it absorbed
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+/// Merging t-digest for percentiles of a numeric stream
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TDigest {
+    compression: f64,
+    centroids: Vec<Centroid>,
+    #[serde(skip)]
+    buffer: Vec<Centroid>,
+    count: f64,
+    min: f64,
+    max: f64,
+}
+
+impl TDigest {
+    /// Scale function k1: centroids near the tails stay small, which keeps
+    /// extreme percentiles accurate
+    fn scale(&self, q: f64) -> f64 {
+        self.compression / (2.0 * std::f64::consts::PI) * asin(2.0 * q.clamp(0.0, 1.0) - 1.0)
+    }
+
+    fn flush(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        let mut all = std::mem::take(&mut self.centroids);
+        all.append(&mut self.buffer);
+        all.sort_by(|a, b| a.mean.total_cmp(&b.mean));
+
+        let total = sum(all.iter().map(|c| c.weight));
+        let mut merged = Vec::with_capacity(all.len());
+        let mut current = all[0];
+        let mut before = 0.0;
+        let mut k_lower = self.scale(0.0);
+        for next in all.into_iter().skip(1) {
+            let weight = current.weight + next.weight;
+            if self.scale((before + weight) / total) - k_lower <= 1.0 {
+                current.mean += (next.mean - current.mean) * next.weight / weight;
+                current.weight = weight;
+            } else {
+                before += current.weight;
+                k_lower = self.scale(before / total);
+                merged.push(current);
+                current = next;
+            }
+        }
+        merged.push(current);
+        self.centroids = merged;
+    }
+
+    fn push(&mut self, centroid: Centroid) {
+        self.buffer.push(centroid);
+        if self.buffer.len() as f64 >= 4.0 * self.compression {
+            self.flush();
+        }
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl TDigest {
+    /// Empty digest; `compression` (20-1000, typically 100) bounds the
+    /// number of centroids and trades size for accuracy
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(compression: f64) -> Result<TDigest, Error> {
+        if !(20.0..=1000.0).contains(&compression) {
+            return Err(Error::invalid("compression must be between 20 and 1000").with("compression", compression));
+        }
+        Ok(Self {
+            compression,
+            centroids: Vec::new(),
+            buffer: Vec::new(),
+            count: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        })
+    }
+
+    /// Add one observation; non-finite values are ignored
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn add(&mut self, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+        self.count += 1.0;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.push(Centroid {
+            mean: value,
+            weight: 1.0,
+        });
+    }
+
+    /// Add the numbers at a dotted path (e.g. `experience.duration_minutes`)
+    /// of every experience; other values are skipped
+    /// Returns the number of values added
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn add_experiences(&mut self, experiences_json: &str, path: &str) -> Result<usize, Error> {
+        let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+        let mut added = 0;
+        for x in field_values(&experiences, path).filter_map(Value::as_f64).filter(|x| x.is_finite()) {
+            self.add(x);
+            added += 1;
+        }
+        Ok(added)
+    }
+
+    /// Number of observations
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn count(&self) -> f64 {
+        self.count
+    }
+
+    /// Estimated value at quantile `q` (0-1), `NaN` when empty
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn quantile(&mut self, q: f64) -> f64 {
+        self.flush();
+        if self.centroids.is_empty() || q.is_nan() {
+            return f64::NAN;
+        }
+        let target = q.clamp(0.0, 1.0) * self.count;
+
+        // Interpolate through (0, min), each centroid's mean at the middle
+        // of its weight, and (count, max)
+        let mut previous = (0.0, self.min);
+        let mut cumulative = 0.0;
+        for c in &self.centroids {
+            let point = (cumulative + c.weight / 2.0, c.mean);
+            if target <= point.0 {
+                return interpolate(previous, point, target);
+            }
+            previous = point;
+            cumulative += c.weight;
+        }
+        interpolate(previous, (self.count, self.max), target)
+    }
+
+    /// Estimated fraction of observations at or below `value`, `NaN` when
+    /// empty
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn cdf(&mut self, value: f64) -> f64 {
+        self.flush();
+        if self.centroids.is_empty() || value.is_nan() {
+            return f64::NAN;
+        }
+        if value < self.min {
+            return 0.0;
+        }
+        if value >= self.max {
+            return 1.0;
+        }
+        let mut previous = (self.min, 0.0);
+        let mut cumulative = 0.0;
+        for c in &self.centroids {
+            let point = (c.mean, cumulative + c.weight / 2.0);
+            if value <= point.0 {
+                return interpolate(previous, point, value) / self.count;
+            }
+            previous = point;
+            cumulative += c.weight;
+        }
+        interpolate(previous, (self.max, self.count), value) / self.count
+    }
+
+    /// Fold another digest into this one, keeping this digest's compression
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn merge(&mut self, other: &TDigest) {
+        for &c in other.centroids.iter().chain(&other.buffer) {
+            self.push(c);
+        }
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.flush();
+    }
+
+    /// Serialize to CBOR for transfer or storage
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut flushed = self.clone();
+        flushed.flush();
+        encode("tdigest", &flushed)
+    }
+
+    /// Restore a digest produced by `to_bytes`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<TDigest, Error> {
+        let sketch: TDigest = decode("tdigest", bytes)?;
+        let weight = sum(sketch.centroids.iter().map(|c| c.weight));
+        let sorted = sketch.centroids.windows(2).all(|w| w[0].mean <= w[1].mean);
+        if !(20.0..=1000.0).contains(&sketch.compression)
+            || !sketch.count.is_finite()
+            || (!sketch.centroids.is_empty() && (sketch.min.is_nan() || sketch.max.is_nan() || sketch.min > sketch.max))
+            || !sorted
+            || sketch.centroids.iter().any(|c| !c.mean.is_finite() || c.weight.is_nan() || c.weight <= 0.0)
+            || (weight - sketch.count).abs() > 1e-6 * sketch.count.max(1.0)
+        {
+            return Err(Error::invalid("t-digest centroids are inconsistent"));
+        }
+        Ok(sketch)
+    }
+}
+
+/// Linear interpolation through two points at `x`
+fn interpolate((x0, y0): (f64, f64), (x1, y1): (f64, f64), x: f64) -> f64 {
+    if x1 <= x0 {
+        return y1;
+    }
+    y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+}
+
+/// Count-min sketch for approximate item frequencies
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CountMinSketch {
+    width: u32,
+    depth: u32,
+    counters: Vec<u64>,
+    total: u64,
+}
+
+impl CountMinSketch {
+    /// Counter index of an item in each row (double hashing)
+    fn cells(&self, item: &str) -> impl Iterator<Item = usize> + '_ {
+        let (h1, h2) = (hash64(item, 1), hash64(item, 2) | 1);
+        (0..u64::from(self.depth)).map(move |row| {
+            let column = h1.wrapping_add(row.wrapping_mul(h2)) % u64::from(self.width);
+            (row * u64::from(self.width) + column) as usize
+        })
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl CountMinSketch {
+    /// Empty sketch of `depth` rows of `width` counters. Estimates overshoot
+    /// by at most `e / width` of the total count with probability
+    /// `1 - e^-depth`; 2048 x 5 is a good default
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(width: u32, depth: u32) -> Result<CountMinSketch, Error> {
+        if width == 0 || depth == 0 || u64::from(width) * u64::from(depth) > 1 << 24 {
+            return Err(Error::invalid("width and depth must be positive with at most 2^24 counters")
+                .with("width", width)
+                .with("depth", depth));
+        }
+        Ok(Self {
+            width,
+            depth,
+            counters: vec![0; (width * depth) as usize],
+            total: 0,
+        })
+    }
+
+    /// Count `count` occurrences of an item
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn add(&mut self, item: &str, count: u32) {
+        for cell in self.cells(item).collect::<Vec<_>>() {
+            self.counters[cell] = self.counters[cell].saturating_add(u64::from(count));
+        }
+        self.total = self.total.saturating_add(u64::from(count));
+    }
+
+    /// Count the values at a dotted path (e.g. `experience.tags`) of every
+    /// experience
+    /// Returns the number of values added
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn add_experiences(&mut self, experiences_json: &str, path: &str) -> Result<usize, Error> {
+        let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+        let mut added = 0;
+        for value in field_values(&experiences, path) {
+            self.add(&item_text(value), 1);
+            added += 1;
+        }
+        Ok(added)
+    }
+
+    /// Estimated occurrences of an item (never an underestimate)
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn estimate(&self, item: &str) -> f64 {
+        self.cells(item).map(|cell| self.counters[cell]).min().unwrap_or(0) as f64
+    }
+
+    /// Total count added
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn total(&self) -> f64 {
+        self.total as f64
+    }
+
+    /// Fold another sketch of the same dimensions into this one
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn merge(&mut self, other: &CountMinSketch) -> Result<(), Error> {
+        if (other.width, other.depth) != (self.width, self.depth) {
+            return Err(Error::invalid("cannot merge count-min sketches of different dimensions")
+                .with("width", self.width)
+                .with("depth", self.depth)
+                .with("other_width", other.width)
+                .with("other_depth", other.depth));
+        }
+        for (mine, theirs) in self.counters.iter_mut().zip(&other.counters) {
+            *mine = mine.saturating_add(*theirs);
+        }
+        self.total = self.total.saturating_add(other.total);
+        Ok(())
+    }
+
+    /// Serialize to CBOR for transfer or storage
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        encode("count_min", self)
+    }
+
+    /// Restore a sketch produced by `to_bytes`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<CountMinSketch, Error> {
+        let sketch: CountMinSketch = decode("count_min", bytes)?;
+        if sketch.width == 0
+            || sketch.depth == 0
+            || sketch.counters.len() as u64 != u64::from(sketch.width) * u64::from(sketch.depth)
+        {
+            return Err(Error::invalid("count-min counters do not match its dimensions"));
+        }
+        Ok(sketch)
+    }
+}
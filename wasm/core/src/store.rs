@@ -0,0 +1,407 @@
+//! IndexedDB-backed experience persistence
+//!
+//! [`ExperienceStore`] validates every record on write and indexes it by
+//! timestamp and learner, so the browser store and the validator share one
+//! code path. Records are kept as their original JSON text inside a small
+//! envelope carrying the indexed fields; all methods return promises.
+//! Subscribers receive every committed write as a change feed, so live
+//! views (e.g. [`crate::heavy_hitters::HeavyHitters`]) update incrementally.
+//!
+//! A store opened with [`ExperienceStore::open_for_tenant`] lives in a
+//! database of its own and only accepts that tenant's records, so none of
+//! its reads can reach another tenant's data.
+
+use js_sys::{Array, Function, Promise, Reflect, JSON};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+use web_sys::{
+    IdbCursorWithValue, IdbDatabase, IdbFactory, IdbIndexParameters, IdbKeyRange, IdbObjectStore,
+    IdbObjectStoreParameters, IdbOpenDbRequest, IdbRequest, IdbTransaction, IdbTransactionMode,
+};
+
+use crate::error::{from_json, to_json, Error};
+use crate::tenancy::{enforce_tenant_experiences, validate_tenant_id};
+use crate::time::parse_timestamp;
+use crate::{Experience, ExperienceValidator};
+
+const DB_VERSION: u32 = 1;
+const STORE: &str = "experiences";
+const TIMESTAMP_INDEX: &str = "timestamp";
+const LEARNER_INDEX: &str = "learner";
+
+/// Stored envelope; `ts` and `learner` back the indexes
+#[derive(Serialize, Deserialize)]
+struct StoredRecord {
+    id: String,
+    ts: f64,
+    learner: String,
+    record: String,
+}
+
+type Handlers = (Closure<dyn FnMut()>, Closure<dyn FnMut()>);
+
+/// Await an IndexedDB request. `step` runs on every success event with the
+/// request's result and returns `Some(value)` once the request is done;
+/// cursor requests fire repeatedly until then.
+async fn await_request<F>(request: &IdbRequest, step: F) -> Result<JsValue, JsValue>
+where
+    F: FnMut(JsValue) -> Result<Option<JsValue>, JsValue> + 'static,
+{
+    let mut step = Some(step);
+    let mut handlers: Option<Handlers> = None;
+    let promise = Promise::new(&mut |resolve, reject| {
+        let Some(mut step) = step.take() else { return };
+        let req = request.clone();
+        let reject_step = reject.clone();
+        let on_success = Closure::<dyn FnMut()>::new(move || match req.result().and_then(&mut step) {
+            Ok(Some(value)) => {
+                let _ = resolve.call1(&JsValue::UNDEFINED, &value);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                let _ = reject_step.call1(&JsValue::UNDEFINED, &e);
+            }
+        });
+        let req = request.clone();
+        let on_error = Closure::<dyn FnMut()>::new(move || {
+            let error = match req.error().ok().flatten() {
+                Some(e) => Error::host(e.message()).with("name", e.name()),
+                None => Error::host("IndexedDB request failed"),
+            };
+            let _ = reject.call1(&JsValue::UNDEFINED, &error.into());
+        });
+        request.set_onsuccess(Some(on_success.as_ref().unchecked_ref()));
+        request.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+        handlers = Some((on_success, on_error));
+    });
+
+    let result = JsFuture::from(promise).await;
+    request.set_onsuccess(None);
+    request.set_onerror(None);
+    drop(handlers);
+    result
+}
+
+/// Commit of a readwrite transaction. Created before any of its requests
+/// are awaited, so the `complete` event cannot be missed.
+struct Commit {
+    transaction: IdbTransaction,
+    future: JsFuture,
+    _handlers: Handlers,
+}
+
+impl Commit {
+    fn watch(transaction: &IdbTransaction) -> Commit {
+        let mut handlers: Option<Handlers> = None;
+        let promise = Promise::new(&mut |resolve, reject| {
+            let on_complete = Closure::<dyn FnMut()>::new(move || {
+                let _ = resolve.call0(&JsValue::UNDEFINED);
+            });
+            let tx = transaction.clone();
+            let on_abort = Closure::<dyn FnMut()>::new(move || {
+                let error = match tx.error() {
+                    Some(e) => Error::host(e.message()).with("name", e.name()),
+                    None => Error::host("IndexedDB transaction aborted"),
+                };
+                let _ = reject.call1(&JsValue::UNDEFINED, &error.into());
+            });
+            transaction.set_oncomplete(Some(on_complete.as_ref().unchecked_ref()));
+            transaction.set_onabort(Some(on_abort.as_ref().unchecked_ref()));
+            handlers = Some((on_complete, on_abort));
+        });
+        Commit {
+            transaction: transaction.clone(),
+            future: JsFuture::from(promise),
+            _handlers: handlers.expect("Promise::new runs its executor synchronously"),
+        }
+    }
+
+    /// Resolve once the transaction has committed
+    async fn wait(&mut self) -> Result<(), JsValue> {
+        (&mut self.future).await.map(|_| ())
+    }
+}
+
+impl Drop for Commit {
+    // Also runs when a request fails first, before the handlers are freed
+    fn drop(&mut self) {
+        self.transaction.set_oncomplete(None);
+        self.transaction.set_onabort(None);
+    }
+}
+
+fn done(value: JsValue) -> Result<Option<JsValue>, JsValue> {
+    Ok(Some(value))
+}
+
+fn factory() -> Result<IdbFactory, JsValue> {
+    Reflect::get(&js_sys::global(), &JsValue::from_str("indexedDB"))?
+        .dyn_into::<IdbFactory>()
+        .map_err(|_| Error::host("IndexedDB is not available in this environment").into())
+}
+
+fn upgrade(request: &IdbOpenDbRequest) -> Result<(), JsValue> {
+    let db: IdbDatabase = request.result()?.dyn_into()?;
+    if db.object_store_names().contains(STORE) {
+        return Ok(());
+    }
+    let params = IdbObjectStoreParameters::new();
+    params.set_key_path(&JsValue::from_str("id"));
+    let store = db.create_object_store_with_optional_parameters(STORE, &params)?;
+    let index_params = IdbIndexParameters::new();
+    store.create_index_with_str_and_optional_parameters(TIMESTAMP_INDEX, "ts", &index_params)?;
+    store.create_index_with_str_and_optional_parameters(LEARNER_INDEX, "learner", &index_params)?;
+    Ok(())
+}
+
+fn object_store(db: &IdbDatabase, mode: IdbTransactionMode) -> Result<IdbObjectStore, JsValue> {
+    db.transaction_with_str_and_mode(STORE, mode)?.object_store(STORE)
+}
+
+fn to_record(value: &JsValue) -> Result<String, JsValue> {
+    let text: String = JSON::stringify(value)?.into();
+    let stored: StoredRecord = serde_json::from_str(&text).map_err(Error::parse)?;
+    Ok(stored.record)
+}
+
+/// Validate an experience and wrap it for storage; a tenant-bound store
+/// only takes its own tenant's records
+fn envelope(json: &str, tenant: Option<&str>) -> Result<JsValue, JsValue> {
+    let exp: Experience = from_json(json, "json")?;
+    let result = ExperienceValidator::new(true).validate_experience(&exp);
+    if !result.valid {
+        return Err(Error::invalid(result.errors.join("; ")).with("errors", result.errors).into());
+    }
+    if let Some(tenant) = tenant {
+        enforce_tenant_experiences(tenant, std::slice::from_ref(&exp)).map_err(Error::invalid)?;
+    }
+    let ts = parse_timestamp(&exp.timestamp).ok_or_else(|| Error::invalid("timestamp is not RFC 3339"))?;
+
+    let stored = StoredRecord {
+        id: exp.id,
+        ts: ts as f64,
+        learner: exp.learner.id,
+        record: json.to_string(),
+    };
+    let text = to_json(&stored)?;
+    JSON::parse(&text)
+}
+
+/// Half-open `[from, to)` key range over the timestamp index; empty bounds
+/// are open
+fn time_range(from: &str, to: &str) -> Result<Option<IdbKeyRange>, JsValue> {
+    let bound = |s: &str| -> Result<Option<JsValue>, JsValue> {
+        if s.is_empty() {
+            return Ok(None);
+        }
+        parse_timestamp(s)
+            .map(|ms| Some(JsValue::from_f64(ms as f64)))
+            .ok_or_else(|| Error::invalid(format!("invalid timestamp: {}", s)).into())
+    };
+    match (bound(from)?, bound(to)?) {
+        (None, None) => Ok(None),
+        (Some(lower), None) => IdbKeyRange::lower_bound(&lower).map(Some),
+        (None, Some(upper)) => IdbKeyRange::upper_bound_with_open(&upper, true).map(Some),
+        (Some(lower), Some(upper)) => IdbKeyRange::bound_with_lower_open_and_upper_open(&lower, &upper, false, true).map(Some),
+    }
+}
+
+/// Persistent experience store over an IndexedDB database
+#[wasm_bindgen]
+pub struct ExperienceStore {
+    db: IdbDatabase,
+    tenant: Option<String>,
+    listeners: Rc<RefCell<Vec<(u32, Function)>>>,
+    next_listener: Cell<u32>,
+}
+
+#[wasm_bindgen]
+impl ExperienceStore {
+    /// Open (creating or upgrading if needed) the named database
+    /// Resolves to an `ExperienceStore`
+    #[wasm_bindgen]
+    pub fn open(name: &str) -> Promise {
+        Self::open_database(name.to_string(), None)
+    }
+
+    /// Open the named store for one tenant. Its records live in a separate
+    /// database (`name/tenant`), and `put` rejects experiences whose
+    /// `tenant` is another or missing
+    /// Resolves to an `ExperienceStore`
+    #[wasm_bindgen]
+    pub fn open_for_tenant(name: &str, tenant: &str) -> Promise {
+        if let Err(e) = validate_tenant_id(tenant) {
+            return Promise::reject(&Error::invalid(format!("tenant {}", e)).into());
+        }
+        Self::open_database(format!("{}/{}", name, tenant), Some(tenant.to_string()))
+    }
+
+    /// The tenant this store is bound to, if any
+    #[wasm_bindgen(getter)]
+    pub fn tenant(&self) -> Option<String> {
+        self.tenant.clone()
+    }
+
+    fn open_database(name: String, tenant: Option<String>) -> Promise {
+        future_to_promise(async move {
+            let request = factory()?.open_with_u32(&name, DB_VERSION)?;
+            let upgrade_request = request.clone();
+            let on_upgrade = Closure::<dyn FnMut()>::new(move || {
+                // Errors surface through the open request's error event
+                let _ = upgrade(&upgrade_request);
+            });
+            request.set_onupgradeneeded(Some(on_upgrade.as_ref().unchecked_ref()));
+            let db = await_request(&request, done).await;
+            request.set_onupgradeneeded(None);
+
+            Ok(ExperienceStore {
+                db: db?.dyn_into()?,
+                tenant,
+                listeners: Rc::default(),
+                next_listener: Cell::new(0),
+            }
+            .into())
+        })
+    }
+
+    /// Validate and store an experience, replacing any record with the same id
+    /// Rejects with the validation errors if the experience is invalid
+    #[wasm_bindgen]
+    pub fn put(&self, json: &str) -> Promise {
+        let db = self.db.clone();
+        let envelope = envelope(json, self.tenant.as_deref());
+        let json = json.to_string();
+        let listeners = self.listeners.clone();
+        future_to_promise(async move {
+            let envelope = envelope?;
+            let transaction = db.transaction_with_str_and_mode(STORE, IdbTransactionMode::Readwrite)?;
+            let mut commit = Commit::watch(&transaction);
+            let store = transaction.object_store(STORE)?;
+            // Requests in one transaction run in order, so this reads the
+            // record being replaced; it is done by the time the put is
+            let previous_request = store.get(&Reflect::get(&envelope, &JsValue::from_str("id"))?)?;
+            let request = store.put(&envelope)?;
+            await_request(&request, done).await?;
+            // A successful request can still be rolled back; listeners only
+            // hear about writes that are durable
+            commit.wait().await?;
+
+            let previous = previous_request.result()?;
+            let previous = if previous.is_undefined() {
+                "null".to_string()
+            } else {
+                to_record(&previous)?
+            };
+            let change = JsValue::from_str(&format!(r#"{{"op":"put","record":{},"previous":{}}}"#, json, previous));
+            // Clone first so a listener may (un)subscribe while being called
+            let callbacks: Vec<Function> = listeners.borrow().iter().map(|(_, f)| f.clone()).collect();
+            for callback in callbacks {
+                // The write is committed; a throwing listener must not undo
+                // the promise's success
+                let _ = callback.call1(&JsValue::NULL, &change);
+            }
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Call `callback` after every committed write with the change as JSON:
+    /// `{op: "put", record, previous}`, where `previous` is the replaced
+    /// record or `null`. Exceptions thrown by the callback are ignored.
+    /// Returns an id for `unsubscribe`
+    #[wasm_bindgen]
+    pub fn subscribe(&self, callback: Function) -> u32 {
+        let id = self.next_listener.get();
+        self.next_listener.set(id.wrapping_add(1));
+        self.listeners.borrow_mut().push((id, callback));
+        id
+    }
+
+    /// Stop calling a subscribed callback
+    /// Returns whether the subscription existed
+    #[wasm_bindgen]
+    pub fn unsubscribe(&self, id: u32) -> bool {
+        let mut listeners = self.listeners.borrow_mut();
+        let before = listeners.len();
+        listeners.retain(|(listener, _)| *listener != id);
+        listeners.len() != before
+    }
+
+    /// Look up an experience by id
+    /// Resolves to its JSON, or `undefined` if absent
+    #[wasm_bindgen]
+    pub fn get(&self, id: &str) -> Promise {
+        let db = self.db.clone();
+        let id = JsValue::from_str(id);
+        future_to_promise(async move {
+            let request = object_store(&db, IdbTransactionMode::Readonly)?.get(&id)?;
+            let value = await_request(&request, done).await?;
+            if value.is_undefined() {
+                return Ok(JsValue::UNDEFINED);
+            }
+            Ok(JsValue::from_str(&to_record(&value)?))
+        })
+    }
+
+    /// Experiences with `from <= timestamp < to` in timestamp order; either
+    /// bound may be empty
+    /// Resolves to the experiences as a JSON array
+    #[wasm_bindgen]
+    pub fn query_by_time_range(&self, from: &str, to: &str) -> Promise {
+        let db = self.db.clone();
+        let range = time_range(from, to);
+        future_to_promise(async move {
+            let index = object_store(&db, IdbTransactionMode::Readonly)?.index(TIMESTAMP_INDEX)?;
+            let request = match range? {
+                Some(range) => index.get_all_with_key(&range)?,
+                None => index.get_all()?,
+            };
+            let values: Array = await_request(&request, done).await?.dyn_into()?;
+
+            let mut out = String::from("[");
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&to_record(&value)?);
+            }
+            out.push(']');
+            Ok(JsValue::from_str(&out))
+        })
+    }
+
+    /// Walk every experience in timestamp order, calling `callback` with each
+    /// one's JSON; returning `false` from the callback stops the walk
+    /// Resolves to the number of experiences visited
+    #[wasm_bindgen]
+    pub fn iterate(&self, callback: Function) -> Promise {
+        let db = self.db.clone();
+        future_to_promise(async move {
+            let index = object_store(&db, IdbTransactionMode::Readonly)?.index(TIMESTAMP_INDEX)?;
+            let request = index.open_cursor()?;
+            let visited = Rc::new(RefCell::new(0u32));
+            let count = visited.clone();
+
+            await_request(&request, move |result| {
+                if result.is_null() {
+                    return Ok(Some(JsValue::UNDEFINED));
+                }
+                let cursor: IdbCursorWithValue = result.dyn_into()?;
+                *count.borrow_mut() += 1;
+                let keep_going = callback.call1(&JsValue::NULL, &JsValue::from_str(&to_record(&cursor.value()?)?))?;
+                if keep_going.as_bool() == Some(false) {
+                    return Ok(Some(JsValue::UNDEFINED));
+                }
+                cursor.continue_()?;
+                Ok(None)
+            })
+            .await?;
+
+            let visited = *visited.borrow();
+            Ok(JsValue::from(visited))
+        })
+    }
+}
@@ -0,0 +1,132 @@
+//! Incremental NDJSON / JSON Lines ingestion
+//!
+//! Large exports do not fit in a single JS string, so records are pushed in
+//! arbitrary byte chunks (e.g. straight from a `ReadableStream`) and folded
+//! into the running network one line at a time. Input whose first
+//! non-whitespace byte is `[` is read as one JSON array instead, one element
+//! at a time through [`ArrayStream`].
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::array_stream::ArrayStream;
+use crate::error::{to_json, Error};
+use crate::pruning;
+use crate::{Experience, NetworkAccumulator};
+
+/// Streaming domain network builder over NDJSON input
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct NetworkStreamBuilder {
+    pending: Vec<u8>,
+    /// Set once the input turns out to be a JSON array
+    array: Option<ArrayStream>,
+    network: NetworkAccumulator,
+    lines: usize,
+    records: usize,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl NetworkStreamBuilder {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> Self {
+        Self {
+            pending: Vec::new(),
+            array: None,
+            network: NetworkAccumulator::default(),
+            lines: 0,
+            records: 0,
+        }
+    }
+
+    /// Feed the next chunk of NDJSON bytes. Chunks may split lines (and
+    /// multi-byte characters) anywhere; incomplete trailing data is held
+    /// until the next chunk or `finish()`.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn push_chunk(&mut self, chunk: &[u8]) -> Result<(), Error> {
+        if let Some(ref mut array) = self.array {
+            let network = &mut self.network;
+            let records = &mut self.records;
+            return array.feed(chunk, |element, index| {
+                let exp: Experience = serde_json::from_slice(element)
+                    .map_err(|e| Error::parse(format!("element {}: {}", index, e)).with("element", index))?;
+                network.add(&exp);
+                *records += 1;
+                Ok(())
+            });
+        }
+        if self.records == 0 && self.pending.iter().chain(chunk).find(|b| !b.is_ascii_whitespace()) == Some(&b'[') {
+            self.array = Some(ArrayStream::new());
+            let buffered = std::mem::take(&mut self.pending);
+            self.push_chunk(&buffered)?;
+            return self.push_chunk(chunk);
+        }
+        self.pending.extend_from_slice(chunk);
+
+        let mut start = 0;
+        while let Some(offset) = self.pending[start..].iter().position(|&b| b == b'\n') {
+            let end = start + offset;
+            let line = self.pending[start..end].to_vec();
+            start = end + 1;
+            self.ingest_line(&line)?;
+        }
+        self.pending.drain(..start);
+
+        Ok(())
+    }
+
+    /// Number of experiences ingested so far
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    /// Flush any final unterminated line and return the network as JSON
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn finish(self) -> Result<String, Error> {
+        to_json(&self.flush()?.into_network())
+    }
+
+    /// `finish`, with the `generate_domain_network_with_options` options
+    /// applied to the network
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn finish_with_options(self, options_json: &str) -> Result<String, Error> {
+        let options = pruning::parse_options(options_json)?;
+        to_json(&pruning::simplified(self.flush()?, &options))
+    }
+
+    fn flush(mut self) -> Result<NetworkAccumulator, Error> {
+        if let Some(ref array) = self.array {
+            array.finish()?;
+            return Ok(self.network);
+        }
+        let rest = std::mem::take(&mut self.pending);
+        self.ingest_line(&rest)?;
+
+        Ok(self.network)
+    }
+
+    fn ingest_line(&mut self, line: &[u8]) -> Result<(), Error> {
+        self.lines += 1;
+
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.iter().all(u8::is_ascii_whitespace) {
+            return Ok(());
+        }
+
+        let exp: Experience = serde_json::from_slice(line).map_err(|e| {
+            Error::parse(format!("line {}: {}", self.lines, e))
+                .with("line", self.lines)
+                .with("column", e.column())
+        })?;
+        self.network.add(&exp);
+        self.records += 1;
+
+        Ok(())
+    }
+}
+
+impl Default for NetworkStreamBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,74 @@
+//! Domain suggestions from free-text descriptions
+//!
+//! The vocabulary maps each domain to the keywords and synonyms that signal
+//! it, e.g. `{"ecology": ["plant", "soil", "wildlife", "food web"]}`. Both
+//! description and keywords are tokenized and stemmed, so "planting" matches
+//! "plant" and multi-word keywords match as phrases.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::text::terms;
+
+/// Weight of the domain's own name appearing in the text
+const DOMAIN_NAME_WEIGHT: f64 = 2.0;
+/// Extra weight per additional token in a matched phrase keyword
+const PHRASE_BONUS: f64 = 0.5;
+
+#[derive(Serialize)]
+struct DomainSuggestion {
+    domain: String,
+    confidence: f64,
+    matched: Vec<String>,
+}
+
+fn contains_phrase(haystack: &[String], phrase: &[String]) -> bool {
+    !phrase.is_empty() && haystack.windows(phrase.len()).any(|window| window == phrase)
+}
+
+fn suggest(description: &str, vocabulary: &BTreeMap<String, Vec<String>>) -> Vec<DomainSuggestion> {
+    let text = terms(description, true);
+
+    let mut suggestions: Vec<DomainSuggestion> = vocabulary
+        .iter()
+        .filter_map(|(domain, keywords)| {
+            let mut score = 0.0;
+            let mut matched = Vec::new();
+
+            if contains_phrase(&text, &terms(domain, true)) {
+                score += DOMAIN_NAME_WEIGHT;
+                matched.push(domain.clone());
+            }
+            for keyword in keywords {
+                let phrase = terms(keyword, true);
+                if contains_phrase(&text, &phrase) && !matched.contains(keyword) {
+                    score += 1.0 + PHRASE_BONUS * (phrase.len() - 1) as f64;
+                    matched.push(keyword.clone());
+                }
+            }
+
+            (score > 0.0).then(|| DomainSuggestion {
+                domain: domain.clone(),
+                // Saturating map of the evidence score into (0, 1)
+                confidence: score / (score + 1.0),
+                matched,
+            })
+        })
+        .collect();
+
+    suggestions.sort_by(|a, b| b.confidence.total_cmp(&a.confidence).then_with(|| a.domain.cmp(&b.domain)));
+    suggestions
+}
+
+/// Suggest domains for a description against a `{domain: [keywords]}`
+/// vocabulary
+/// Returns `[{domain, confidence, matched}]` as JSON, most confident first
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn suggest_domains(description: &str, vocabulary_json: &str) -> Result<String, Error> {
+    let vocabulary: BTreeMap<String, Vec<String>> = from_json(vocabulary_json, "vocabulary_json")?;
+
+    to_json(&suggest(description, &vocabulary))
+}
@@ -0,0 +1,160 @@
+//! Dataset summary for dashboards
+//!
+//! [`summary_stats`] computes the headline numbers a dashboard shows in a
+//! single pass over the records, instead of one pass per widget: counts by
+//! type, top domains, distinct learners and locations, the date range,
+//! experiences per ISO week, the median description length and how often
+//! each commonly filled field is missing.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::identity::canonical_learner_id;
+use crate::query::domains;
+use crate::time::{format_timestamp, iso_week, parse_timestamp, MS_PER_DAY};
+
+const TOP_DOMAINS: usize = 10;
+/// Longer date ranges list only the weeks with experiences
+const MAX_FILLED_WEEKS: i64 = 1_040;
+
+/// Fields whose missing rate is reported; empty strings and arrays count
+/// as missing
+const TRACKED_FIELDS: &[&str] = &[
+    "learner.id",
+    "timestamp",
+    "context.location.name",
+    "context.location.coordinates",
+    "experience.type",
+    "experience.description",
+    "experience.domains",
+];
+
+#[derive(Serialize)]
+struct DomainCount<'a> {
+    domain: &'a str,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct DateRange {
+    from: String,
+    to: String,
+}
+
+#[derive(Serialize)]
+struct Summary<'a> {
+    records: usize,
+    learners: usize,
+    by_type: BTreeMap<&'a str, usize>,
+    top_domains: Vec<DomainCount<'a>>,
+    distinct_domains: usize,
+    /// Distinct `context.location.name` values, compared case-insensitively
+    unique_locations: usize,
+    located: usize,
+    date_range: Option<DateRange>,
+    /// Every ISO week (`2024-W07`) of the date range, empty ones included
+    /// for ranges up to 20 years
+    per_week: BTreeMap<String, usize>,
+    mean_per_week: Option<f64>,
+    /// In characters
+    median_description_length: Option<f64>,
+    missing_field_rates: BTreeMap<&'static str, f64>,
+}
+
+fn is_missing(value: Option<&Value>) -> bool {
+    match value {
+        None | Some(Value::Null) => true,
+        Some(Value::String(s)) => s.trim().is_empty(),
+        Some(Value::Array(items)) => items.is_empty(),
+        Some(_) => false,
+    }
+}
+
+/// Headline statistics for a dataset in one pass
+/// Returns `{records, learners, by_type, top_domains: [{domain, count}],
+/// distinct_domains, unique_locations, located, date_range: {from, to},
+/// per_week, mean_per_week, median_description_length,
+/// missing_field_rates}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn summary_stats(experiences_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let mut learners: BTreeSet<String> = BTreeSet::new();
+    let mut by_type: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut domain_counts: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut locations: BTreeSet<String> = BTreeSet::new();
+    let mut located = 0;
+    let mut range: Option<(i64, i64)> = None;
+    let mut per_week: BTreeMap<String, usize> = BTreeMap::new();
+    let mut description_lengths: Vec<usize> = Vec::new();
+    let mut missing = vec![0usize; TRACKED_FIELDS.len()];
+
+    for exp in &experiences {
+        for (count, path) in missing.iter_mut().zip(TRACKED_FIELDS) {
+            *count += usize::from(is_missing(lookup(exp, path)));
+        }
+        if let Some(learner) = lookup(exp, "learner.id").and_then(Value::as_str) {
+            learners.insert(canonical_learner_id(learner));
+        }
+        if let Some(kind) = lookup(exp, "experience.type").and_then(Value::as_str) {
+            *by_type.entry(kind).or_insert(0) += 1;
+        }
+        for domain in domains(exp) {
+            *domain_counts.entry(domain).or_insert(0) += 1;
+        }
+        if let Some(name) = lookup(exp, "context.location.name").and_then(Value::as_str).filter(|n| !n.trim().is_empty()) {
+            locations.insert(name.trim().to_lowercase());
+        }
+        located += usize::from(!is_missing(lookup(exp, "context.location.coordinates")));
+        if let Some(ms) = lookup(exp, "timestamp").and_then(Value::as_str).and_then(parse_timestamp) {
+            range = Some(range.map_or((ms, ms), |(from, to)| (from.min(ms), to.max(ms))));
+            let (year, week) = iso_week(ms);
+            *per_week.entry(format!("{:04}-W{:02}", year, week)).or_insert(0) += 1;
+        }
+        if let Some(description) = lookup(exp, "experience.description").and_then(Value::as_str) {
+            description_lengths.push(description.chars().count());
+        }
+    }
+
+    let mut top_domains: Vec<DomainCount> = domain_counts.iter().map(|(&domain, &count)| DomainCount { domain, count }).collect();
+    top_domains.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.domain.cmp(b.domain)));
+    top_domains.truncate(TOP_DOMAINS);
+    description_lengths.sort_unstable();
+    let median_description_length = match description_lengths.len() {
+        0 => None,
+        n if n % 2 == 1 => Some(description_lengths[n / 2] as f64),
+        n => Some((description_lengths[n / 2 - 1] + description_lengths[n / 2]) as f64 / 2.0),
+    };
+    let dated: usize = per_week.values().sum();
+    if let Some((from, to)) = range.filter(|(from, to)| (to - from) / (7 * MS_PER_DAY) < MAX_FILLED_WEEKS) {
+        let last = iso_week(to);
+        let mut ms = from;
+        while iso_week(ms) <= last {
+            let (year, week) = iso_week(ms);
+            per_week.entry(format!("{:04}-W{:02}", year, week)).or_insert(0);
+            ms += 7 * MS_PER_DAY;
+        }
+    }
+    to_json(&Summary {
+        records: experiences.len(),
+        learners: learners.len(),
+        by_type,
+        top_domains,
+        distinct_domains: domain_counts.len(),
+        unique_locations: locations.len(),
+        located,
+        date_range: range.map(|(from, to)| DateRange { from: format_timestamp(from), to: format_timestamp(to) }),
+        mean_per_week: (!per_week.is_empty()).then(|| dated as f64 / per_week.len() as f64),
+        per_week,
+        median_description_length,
+        missing_field_rates: TRACKED_FIELDS
+            .iter()
+            .zip(missing)
+            .map(|(path, count)| (*path, count as f64 / experiences.len().max(1) as f64))
+            .collect(),
+    })
+}
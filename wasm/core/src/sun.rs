@@ -0,0 +1,254 @@
+//! Solar position, daylight times and day/night flags
+//!
+//! Implements NOAA's solar calculator equations (after Meeus, *Astronomical
+//! Algorithms*), accurate to about a minute for latitudes within ±72°.
+//! Sunrise and sunset use the standard -0.833° apparent elevation
+//! (refraction plus solar radius), civil twilight -6° and golden hour +6°.
+
+use serde::Serialize;
+use serde_json::Value;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::numeric::{acos, asin, cos, powi, sin, tan};
+use crate::query::coordinates;
+use crate::time::{format_timestamp, parse_timestamp, MS_PER_DAY, MS_PER_MINUTE};
+
+const SUNRISE_ELEVATION: f64 = -0.833;
+const CIVIL_TWILIGHT_ELEVATION: f64 = -6.0;
+const GOLDEN_HOUR_ELEVATION: f64 = 6.0;
+/// Julian date of the Unix epoch
+const JD_UNIX_EPOCH: f64 = 2_440_587.5;
+
+/// Solar declination (degrees) and equation of time (minutes) at an instant
+fn declination_and_eot(ms: f64) -> (f64, f64) {
+    let jd = ms / MS_PER_DAY as f64 + JD_UNIX_EPOCH;
+    let t = (jd - 2451545.0) / 36525.0;
+
+    let l0 = (280.46646 + t * (36000.76983 + t * 0.0003032)).rem_euclid(360.0);
+    let m = 357.52911 + t * (35999.05029 - 0.0001537 * t);
+    let e = 0.016708634 - t * (0.000042037 + 0.0000001267 * t);
+    let m_rad = m.to_radians();
+    let center = sin(m_rad) * (1.914602 - t * (0.004817 + 0.000014 * t))
+        + sin(2.0 * m_rad) * (0.019993 - 0.000101 * t)
+        + sin(3.0 * m_rad) * 0.000289;
+    let omega = (125.04 - 1934.136 * t).to_radians();
+    let apparent_longitude = (l0 + center - 0.00569 - 0.00478 * sin(omega)).to_radians();
+    let mean_obliquity = 23.0 + (26.0 + (21.448 - t * (46.815 + t * (0.00059 - t * 0.001813))) / 60.0) / 60.0;
+    let obliquity = (mean_obliquity + 0.00256 * cos(omega)).to_radians();
+
+    let declination = asin(sin(obliquity) * sin(apparent_longitude)).to_degrees();
+    let y = powi(tan(obliquity / 2.0), 2);
+    let l0_rad = l0.to_radians();
+    let eot = 4.0
+        * (y * sin(2.0 * l0_rad) - 2.0 * e * sin(m_rad) + 4.0 * e * y * sin(m_rad) * cos(2.0 * l0_rad)
+            - 0.5 * y * y * sin(4.0 * l0_rad)
+            - 1.25 * e * e * sin(2.0 * m_rad))
+        .to_degrees();
+    (declination, eot)
+}
+
+/// Apparent solar elevation in degrees at an instant (refraction ignored)
+pub(crate) fn solar_elevation(ms: i64, lat: f64, lon: f64) -> f64 {
+    let (declination, eot) = declination_and_eot(ms as f64);
+    let minutes_utc = ms.rem_euclid(MS_PER_DAY) as f64 / MS_PER_MINUTE as f64;
+    let true_solar_minutes = (minutes_utc + eot + 4.0 * lon).rem_euclid(1440.0);
+    let hour_angle = (true_solar_minutes / 4.0 - 180.0).to_radians();
+    let (lat, dec) = (lat.to_radians(), declination.to_radians());
+    asin((sin(lat) * sin(dec) + cos(lat) * cos(dec) * cos(hour_angle)).clamp(-1.0, 1.0)).to_degrees()
+}
+
+/// Outcome of solving for the times the sun crosses an elevation
+enum Crossing {
+    /// Morning and evening crossing, epoch ms
+    Times(f64, f64),
+    /// The sun stays above the elevation all day
+    AlwaysAbove,
+    /// The sun stays below it all day
+    AlwaysBelow,
+}
+
+/// Solar noon (epoch ms) for the UTC day starting at `day_ms`, refined once
+/// with the equation of time at noon itself
+fn solar_noon(day_ms: i64, lon: f64) -> f64 {
+    let mut noon = day_ms as f64 + (720.0 - 4.0 * lon) * MS_PER_MINUTE as f64;
+    for _ in 0..2 {
+        let (_, eot) = declination_and_eot(noon);
+        noon = day_ms as f64 + (720.0 - 4.0 * lon - eot) * MS_PER_MINUTE as f64;
+    }
+    noon
+}
+
+fn crossing(noon: f64, lat: f64, elevation: f64) -> Crossing {
+    // Evaluate each side at its own approximate time for better accuracy
+    let mut times = [0.0; 2];
+    for (i, sign) in [-1.0, 1.0].into_iter().enumerate() {
+        let mut at = noon;
+        for _ in 0..2 {
+            let (declination, _) = declination_and_eot(at);
+            let (lat_r, dec) = (lat.to_radians(), declination.to_radians());
+            let cos_ha = (sin(elevation.to_radians()) - sin(lat_r) * sin(dec)) / (cos(lat_r) * cos(dec));
+            if cos_ha > 1.0 {
+                return Crossing::AlwaysBelow;
+            }
+            if cos_ha < -1.0 {
+                return Crossing::AlwaysAbove;
+            }
+            let hour_angle = acos(cos_ha).to_degrees();
+            at = noon + sign * 4.0 * hour_angle * MS_PER_MINUTE as f64;
+        }
+        times[i] = at;
+    }
+    Crossing::Times(times[0], times[1])
+}
+
+#[derive(Serialize)]
+struct SunTimes {
+    date: String,
+    solar_noon: String,
+    sunrise: Option<String>,
+    sunset: Option<String>,
+    civil_dawn: Option<String>,
+    civil_dusk: Option<String>,
+    /// Morning golden hour runs from sunrise to this time
+    golden_hour_end: Option<String>,
+    /// Evening golden hour runs from this time to sunset
+    golden_hour_start: Option<String>,
+    day_length_minutes: f64,
+    /// `"midnight_sun"` or `"polar_night"` when the sun does not rise or set
+    polar: Option<&'static str>,
+}
+
+fn sun_times_for(day_ms: i64, lat: f64, lon: f64) -> SunTimes {
+    let noon = solar_noon(day_ms, lon);
+    let at = |ms: f64| Some(format_timestamp(ms.round() as i64));
+    let pair = |elevation: f64| match crossing(noon, lat, elevation) {
+        Crossing::Times(morning, evening) => (at(morning), at(evening)),
+        _ => (None, None),
+    };
+
+    let sun = crossing(noon, lat, SUNRISE_ELEVATION);
+    let (day_length_minutes, polar) = match sun {
+        Crossing::Times(rise, set) => ((set - rise) / MS_PER_MINUTE as f64, None),
+        Crossing::AlwaysAbove => (1440.0, Some("midnight_sun")),
+        Crossing::AlwaysBelow => (0.0, Some("polar_night")),
+    };
+    let (sunrise, sunset) = pair(SUNRISE_ELEVATION);
+    let (civil_dawn, civil_dusk) = pair(CIVIL_TWILIGHT_ELEVATION);
+    let (golden_hour_end, golden_hour_start) = pair(GOLDEN_HOUR_ELEVATION);
+
+    SunTimes {
+        date: format_timestamp(day_ms)[..10].to_string(),
+        solar_noon: format_timestamp(noon.round() as i64),
+        sunrise,
+        sunset,
+        civil_dawn,
+        civil_dusk,
+        golden_hour_end,
+        golden_hour_start,
+        day_length_minutes,
+        polar,
+    }
+}
+
+/// Sunrise, sunset, civil twilight and golden hour for a location, for the
+/// solar day whose noon falls on the UTC date `YYYY-MM-DD`
+/// Returns the times as RFC 3339 UTC timestamps in JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn sun_times(lat: f64, lon: f64, date: &str) -> Result<String, Error> {
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+        return Err(Error::invalid("coordinates out of range"));
+    }
+    let ms = parse_timestamp(date).ok_or_else(|| Error::invalid(format!("invalid date: {}", date)))?;
+    let day_ms = ms.div_euclid(MS_PER_DAY) * MS_PER_DAY;
+    to_json(&sun_times_for(day_ms, lat, lon))
+}
+
+/// Stamp `context.daylight` (`"day"`, `"twilight"` or `"night"`) and
+/// `context.solar_elevation` onto geo-tagged experiences from the sun's
+/// position at their timestamp
+/// Returns the experiences as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn stamp_daylight(experiences_json: &str) -> Result<String, Error> {
+    let mut experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+
+    for exp in &mut experiences {
+        let ts = exp.get("timestamp").and_then(Value::as_str).and_then(parse_timestamp);
+        let (Some(ms), Some((lat, lon))) = (ts, coordinates(exp)) else {
+            continue;
+        };
+        let elevation = solar_elevation(ms, lat, lon);
+        let daylight = if elevation >= SUNRISE_ELEVATION {
+            "day"
+        } else if elevation >= CIVIL_TWILIGHT_ELEVATION {
+            "twilight"
+        } else {
+            "night"
+        };
+        if let Some(context) = exp.get_mut("context").and_then(Value::as_object_mut) {
+            context.insert("daylight".to_string(), Value::from(daylight));
+            context.insert("solar_elevation".to_string(), Value::from(elevation));
+        }
+    }
+
+    to_json(&experiences)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn times(lat: f64, lon: f64, date: &str) -> Value {
+        serde_json::from_str(&sun_times(lat, lon, date).unwrap()).unwrap()
+    }
+
+    /// Assert `field` is within two minutes of NOAA's published time
+    fn assert_near(times: &Value, field: &str, expected: &str) {
+        let actual = parse_timestamp(times[field].as_str().unwrap()).unwrap();
+        let expected = parse_timestamp(expected).unwrap();
+        assert!((actual - expected).abs() <= 2 * MS_PER_MINUTE, "{}: {} vs {}", field, times[field], expected);
+    }
+
+    #[test]
+    fn matches_noaa_published_times() {
+        // London, summer solstice: 04:43 and 21:21 BST
+        let london = times(51.5074, -0.1278, "2024-06-21");
+        assert_near(&london, "sunrise", "2024-06-21T03:43:00Z");
+        assert_near(&london, "sunset", "2024-06-21T20:21:00Z");
+        assert_near(&london, "solar_noon", "2024-06-21T12:02:00Z");
+
+        // New York, winter solstice: 07:16 and 16:32 EST
+        let new_york = times(40.7128, -74.006, "2024-12-21");
+        assert_near(&new_york, "sunrise", "2024-12-21T12:16:00Z");
+        assert_near(&new_york, "sunset", "2024-12-21T21:32:00Z");
+
+        // Sydney, southern summer: 05:41 and 20:05 AEDT, so sunrise falls on
+        // the previous UTC date
+        let sydney = times(-33.8688, 151.2093, "2024-12-21");
+        assert_near(&sydney, "sunrise", "2024-12-20T18:41:00Z");
+        assert_near(&sydney, "sunset", "2024-12-21T09:05:00Z");
+        assert!(london["polar"].is_null() && new_york["polar"].is_null() && sydney["polar"].is_null());
+    }
+
+    #[test]
+    fn polar_day_and_night_have_no_crossings() {
+        for (date, polar, length) in [("2024-06-21", "midnight_sun", 1440.0), ("2024-12-21", "polar_night", 0.0)] {
+            // Tromsø
+            let tromso = times(69.6492, 18.9553, date);
+            assert_eq!(tromso["polar"], polar);
+            assert_eq!(tromso["sunrise"], Value::Null);
+            assert_eq!(tromso["sunset"], Value::Null);
+            assert_eq!(tromso["day_length_minutes"], length);
+            assert!(tromso["solar_noon"].is_string());
+        }
+
+        // The poles themselves must not divide their way to NaN
+        for lat in [90.0, -90.0] {
+            let sun = sun_times_for(parse_timestamp("2024-06-21").unwrap(), lat, 0.0);
+            assert!(sun.sunrise.is_none() && sun.sunset.is_none() && sun.polar.is_some());
+            assert!(sun.day_length_minutes.is_finite());
+        }
+        assert!(solar_elevation(parse_timestamp("2024-06-21T12:00:00Z").unwrap(), 90.0, 0.0).is_finite());
+    }
+}
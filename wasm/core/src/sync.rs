@@ -0,0 +1,352 @@
+//! Offline merge of experience logs
+//!
+//! Each log is treated as a state-based CRDT: a grow-only set of experience
+//! ids, each mapped to a last-writer-wins register holding the record. A
+//! record's version is, in order of precedence, its `hlc` stamp (see
+//! [`crate::hlc`]), its `timestamp`, and finally its content hash, so any two
+//! replicas that have seen the same records converge on the same log no
+//! matter the order in which they merged.
+//!
+//! For plain delta sync, [`diff_logs`] and [`apply_changeset`] exchange only
+//! the records that were added, changed or removed.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::crypto::to_hex;
+use crate::error::{from_json, to_json, Error};
+use crate::hlc::Hlc;
+use crate::ledger::content_hash;
+use crate::time::parse_timestamp;
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Side {
+    Local,
+    Remote,
+}
+
+/// LWW register version; compared field by field
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct Version {
+    hlc: Option<Hlc>,
+    timestamp: Option<i64>,
+    hash: [u8; 32],
+}
+
+impl Version {
+    fn of(exp: &Value) -> Self {
+        Self {
+            hlc: exp.get("hlc").and_then(Value::as_str).and_then(|s| Hlc::parse(s).ok()),
+            timestamp: exp.get("timestamp").and_then(Value::as_str).and_then(parse_timestamp),
+            hash: content_hash(exp),
+        }
+    }
+
+    /// Which component decided between two differing versions
+    fn decided_by(&self, other: &Self) -> &'static str {
+        if self.hlc != other.hlc {
+            "hlc"
+        } else if self.timestamp != other.timestamp {
+            "timestamp"
+        } else {
+            "content_hash"
+        }
+    }
+}
+
+struct Register {
+    value: Value,
+    version: Version,
+    side: Side,
+}
+
+#[derive(Serialize)]
+struct Conflict {
+    id: String,
+    winner: Side,
+    decided_by: &'static str,
+    local_hash: String,
+    remote_hash: String,
+}
+
+#[derive(Default, Serialize)]
+struct MergeStats {
+    local: usize,
+    remote: usize,
+    merged: usize,
+    duplicates: usize,
+    conflicts: usize,
+}
+
+#[derive(Serialize)]
+struct MergeResult {
+    experiences: Vec<Value>,
+    conflicts: Vec<Conflict>,
+    stats: MergeStats,
+}
+
+/// Fold one log into the register map. Conflicts are only recorded between
+/// the two sides; repeats within one log simply resolve by version.
+fn absorb(
+    registers: &mut BTreeMap<String, Register>,
+    log: Vec<Value>,
+    side: Side,
+    stats: &mut MergeStats,
+    conflicts: &mut BTreeMap<String, Conflict>,
+) -> Result<(), String> {
+    for value in log {
+        let id = log_id(&value)?.to_string();
+        let version = Version::of(&value);
+
+        let Some(current) = registers.get_mut(&id) else {
+            registers.insert(id, Register { value, version, side });
+            continue;
+        };
+        if current.version == version {
+            stats.duplicates += 1;
+            continue;
+        }
+        if current.side != side {
+            let (local, remote) = match side {
+                Side::Remote => (&current.version, &version),
+                Side::Local => (&version, &current.version),
+            };
+            let winner = if version > current.version { side } else { current.side };
+            conflicts.insert(
+                id.clone(),
+                Conflict {
+                    id: id.clone(),
+                    winner,
+                    decided_by: local.decided_by(remote),
+                    local_hash: to_hex(&local.hash),
+                    remote_hash: to_hex(&remote.hash),
+                },
+            );
+        }
+        if version > current.version {
+            *current = Register { value, version, side };
+        }
+    }
+    Ok(())
+}
+
+fn merge_logs(local: Vec<Value>, remote: Vec<Value>) -> Result<MergeResult, String> {
+    let mut stats = MergeStats {
+        local: local.len(),
+        remote: remote.len(),
+        ..MergeStats::default()
+    };
+    let mut registers = BTreeMap::new();
+    let mut conflicts = BTreeMap::new();
+    absorb(&mut registers, local, Side::Local, &mut stats, &mut conflicts)?;
+    absorb(&mut registers, remote, Side::Remote, &mut stats, &mut conflicts)?;
+
+    let mut merged: Vec<Register> = registers.into_values().collect();
+    merged.sort_by(|a, b| {
+        a.version
+            .timestamp
+            .cmp(&b.version.timestamp)
+            .then_with(|| compare_ids(&a.value, &b.value))
+    });
+
+    stats.merged = merged.len();
+    stats.conflicts = conflicts.len();
+    Ok(MergeResult {
+        experiences: merged.into_iter().map(|r| r.value).collect(),
+        conflicts: conflicts.into_values().collect(),
+        stats,
+    })
+}
+
+fn compare_ids(a: &Value, b: &Value) -> Ordering {
+    a.get("id").and_then(Value::as_str).cmp(&b.get("id").and_then(Value::as_str))
+}
+
+/// Merge two experience logs keyed by id; the result is the same whichever
+/// side is passed as local
+/// Returns `{experiences, conflicts, stats}` as JSON, with experiences in
+/// timestamp order
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn merge(local_json: &str, remote_json: &str) -> Result<String, Error> {
+    let local: Vec<Value> = from_json(local_json, "local_json")?;
+    let remote: Vec<Value> = from_json(remote_json, "remote_json")?;
+
+    let result = merge_logs(local, remote).map_err(Error::invalid)?;
+    to_json(&result)
+}
+
+/// Delta between two logs. Updates carry an RFC 7396 merge patch against
+/// the old record, or the full `record` when a patch cannot express the
+/// change (e.g. values that are themselves `null`).
+#[derive(Default, Serialize, Deserialize)]
+struct Changeset {
+    #[serde(default)]
+    added: Vec<Value>,
+    #[serde(default)]
+    updated: Vec<Update>,
+    #[serde(default)]
+    removed: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Update {
+    id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    patch: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    record: Option<Value>,
+}
+
+fn merge_patch(old: &Value, new: &Value) -> Value {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut patch = Map::new();
+            for (key, value) in new {
+                match old.get(key) {
+                    Some(previous) if previous == value => {}
+                    Some(previous) => {
+                        patch.insert(key.clone(), merge_patch(previous, value));
+                    }
+                    None => {
+                        patch.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            for key in old.keys().filter(|key| !new.contains_key(*key)) {
+                patch.insert(key.clone(), Value::Null);
+            }
+            Value::Object(patch)
+        }
+        _ => new.clone(),
+    }
+}
+
+fn apply_merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                apply_merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+fn log_id(exp: &Value) -> Result<&str, String> {
+    exp.get("id")
+        .and_then(Value::as_str)
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| "every experience needs a string id".to_string())
+}
+
+fn diff(old: &[Value], new: &[Value]) -> Result<Changeset, String> {
+    let mut previous: HashMap<&str, &Value> = HashMap::new();
+    for exp in old {
+        previous.insert(log_id(exp)?, exp);
+    }
+    let mut seen = HashSet::new();
+    let mut changeset = Changeset::default();
+
+    for exp in new {
+        let id = log_id(exp)?;
+        seen.insert(id);
+        match previous.get(id) {
+            None => changeset.added.push(exp.clone()),
+            Some(&before) if before == exp => {}
+            Some(&before) => {
+                let patch = merge_patch(before, exp);
+                let mut check = before.clone();
+                apply_merge_patch(&mut check, &patch);
+                let (patch, record) = if check == *exp { (Some(patch), None) } else { (None, Some(exp.clone())) };
+                changeset.updated.push(Update {
+                    id: id.to_string(),
+                    patch,
+                    record,
+                });
+            }
+        }
+    }
+    changeset.removed = old
+        .iter()
+        .filter_map(|exp| log_id(exp).ok())
+        .filter(|id| !seen.contains(id))
+        .map(str::to_string)
+        .collect();
+    Ok(changeset)
+}
+
+/// Apply a changeset in place of the log's records; removals and updates
+/// must name ids present in the log and additions must not
+fn apply(log: Vec<Value>, changeset: Changeset) -> Result<Vec<Value>, Error> {
+    let removed: HashSet<&str> = changeset.removed.iter().map(String::as_str).collect();
+    let mut updates: HashMap<&str, &Update> = changeset.updated.iter().map(|u| (u.id.as_str(), u)).collect();
+    let mut present = HashSet::new();
+    let mut out = Vec::with_capacity(log.len() + changeset.added.len());
+
+    for mut exp in log {
+        let id = log_id(&exp).map_err(Error::invalid)?.to_string();
+        if removed.contains(id.as_str()) {
+            present.insert(id);
+            continue;
+        }
+        if let Some(update) = updates.remove(id.as_str()) {
+            match (&update.record, &update.patch) {
+                (Some(record), _) => exp = record.clone(),
+                (None, Some(patch)) => apply_merge_patch(&mut exp, patch),
+                (None, None) => return Err(Error::invalid(format!("update for {} has neither patch nor record", id))),
+            }
+        }
+        present.insert(id);
+        out.push(exp);
+    }
+
+    if let Some(id) = updates.keys().next() {
+        return Err(Error::conflict(format!("changeset updates unknown id {}", id)).with("id", id.to_string()));
+    }
+    if let Some(id) = removed.iter().find(|id| !present.contains(**id)) {
+        return Err(Error::conflict(format!("changeset removes unknown id {}", id)).with("id", id.to_string()));
+    }
+    for exp in changeset.added {
+        let id = log_id(&exp).map_err(Error::invalid)?.to_string();
+        if !present.insert(id.clone()) {
+            return Err(Error::conflict(format!("changeset adds existing id {}", id)).with("id", id.to_string()));
+        }
+        out.push(exp);
+    }
+    Ok(out)
+}
+
+/// Compute the changeset that turns `old_json` into `new_json`
+/// Returns `{added, updated, removed}` as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn diff_logs(old_json: &str, new_json: &str) -> Result<String, Error> {
+    let old: Vec<Value> = from_json(old_json, "old_json")?;
+    let new: Vec<Value> = from_json(new_json, "new_json")?;
+
+    let changeset = diff(&old, &new).map_err(Error::invalid)?;
+    to_json(&changeset)
+}
+
+/// Apply a changeset from [`diff_logs`] to a log
+/// Returns the updated log as JSON; new records are appended
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn apply_changeset(log_json: &str, changeset_json: &str) -> Result<String, Error> {
+    let log: Vec<Value> = from_json(log_json, "log_json")?;
+    let changeset: Changeset = from_json(changeset_json, "changeset_json")?;
+
+    let log = apply(log, changeset)?;
+    to_json(&log)
+}
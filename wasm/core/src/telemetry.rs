@@ -0,0 +1,124 @@
+//! Privacy-preserving schema usage telemetry
+//!
+//! [`usage_telemetry`] reduces a dataset to aggregate counters that say how
+//! the schema is used without saying anything about who used it: optional
+//! field usage rates, extension key frequencies and validation error codes.
+//! No values, ids or free text leave this module. Extension keys seen in
+//! fewer than five records are suppressed, as are keys that do not
+//! look like identifiers, since a rare or odd key can itself be identifying.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::messages::PARSE_ERROR;
+use crate::severity::Severity;
+use crate::{Experience, ExperienceValidator};
+
+/// Optional schema fields whose usage rate is reported
+const OPTIONAL_FIELDS: &[&str] = &[
+    "context.location.coordinates",
+    "experience.domains",
+    "experience.accessibility",
+    "experience.measurements",
+    "tenant",
+    "reactions",
+    "comments",
+    "proof",
+];
+
+/// Objects scanned for extension keys, with the keys the schema defines there
+const SCHEMA_KEYS: &[(&str, &[&str])] = &[
+    ("", &["id", "timestamp", "learner", "context", "experience", "tenant", "reactions", "comments", "proof"]),
+    ("learner", &["id"]),
+    ("context", &["location"]),
+    ("context.location", &["name", "coordinates"]),
+    ("context.location.coordinates", &["latitude", "longitude"]),
+    ("experience", &["type", "description", "domains", "accessibility", "measurements"]),
+    ("experience.accessibility", &["modality", "accommodations"]),
+];
+
+const MAX_KEY_LEN: usize = 64;
+/// Minimum number of records an extension key must appear in to be reported
+const MIN_KEY_COUNT: usize = 5;
+
+#[derive(Serialize)]
+struct Telemetry {
+    records: usize,
+    optional_fields: BTreeMap<&'static str, f64>,
+    extension_keys: BTreeMap<String, usize>,
+    suppressed_extension_keys: usize,
+    /// Error-severity issues by rule code, as in `ValidationResult.issues`;
+    /// `parse` for records that are not experiences
+    error_codes: BTreeMap<String, usize>,
+}
+
+fn is_reportable_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= MAX_KEY_LEN
+        && key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && key.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+}
+
+fn telemetry(experiences: &[Value]) -> Telemetry {
+    let validator = ExperienceValidator::new(true);
+    let mut field_counts = vec![0usize; OPTIONAL_FIELDS.len()];
+    let mut extension_keys: BTreeMap<String, usize> = BTreeMap::new();
+    let mut error_codes: BTreeMap<String, usize> = BTreeMap::new();
+
+    for exp in experiences {
+        for (count, path) in field_counts.iter_mut().zip(OPTIONAL_FIELDS) {
+            *count += usize::from(lookup(exp, path).is_some_and(|v| !v.is_null()));
+        }
+
+        for (path, known) in SCHEMA_KEYS {
+            let object = if path.is_empty() { Some(exp) } else { lookup(exp, path) };
+            for key in object.and_then(Value::as_object).into_iter().flat_map(|o| o.keys()) {
+                if !known.contains(&key.as_str()) {
+                    let full = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                    *extension_keys.entry(full).or_insert(0) += 1;
+                }
+            }
+        }
+
+        match serde_json::from_value::<Experience>(exp.clone()) {
+            Ok(parsed) => {
+                let issues = validator.validate_experience(&parsed).issues;
+                for issue in issues.into_iter().filter(|issue| issue.severity == Severity::Error) {
+                    *error_codes.entry(issue.code).or_insert(0) += 1;
+                }
+            }
+            Err(_) => *error_codes.entry(PARSE_ERROR.to_string()).or_insert(0) += 1,
+        }
+    }
+
+    let total = extension_keys.len();
+    extension_keys.retain(|key, count| {
+        *count >= MIN_KEY_COUNT && key.split('.').all(is_reportable_key)
+    });
+
+    Telemetry {
+        records: experiences.len(),
+        optional_fields: OPTIONAL_FIELDS
+            .iter()
+            .zip(field_counts)
+            .map(|(path, count)| (*path, count as f64 / experiences.len().max(1) as f64))
+            .collect(),
+        suppressed_extension_keys: total - extension_keys.len(),
+        extension_keys,
+        error_codes,
+    }
+}
+
+/// Aggregate, non-identifying schema usage counters for opt-in telemetry
+/// Returns the counters as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn usage_telemetry(experiences_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+
+    to_json(&telemetry(&experiences))
+}
@@ -0,0 +1,206 @@
+//! Multi-tenant namespace isolation
+//!
+//! Experiences may carry a top-level `tenant` id naming the school or
+//! organisation that owns them. A [`TenantScope`] runs operations on behalf of
+//! exactly one tenant: records belonging to another tenant, or to none, are an
+//! error rather than being silently filtered, so a mis-scoped query can never
+//! leak another school's data into a report.
+//!
+//! Every read path has a scoped form: the network builders and CSV export
+//! here, `query_experiences`, `aggregate` and `usage_report` next to their
+//! unscoped versions, and `ExperienceStore::open_for_tenant` for the browser
+//! store, which keeps each tenant in its own database.
+
+use serde_json::Value;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::{accumulate_network, build_network, export, pruning, Experience};
+
+const MAX_TENANT_LEN: usize = 63;
+
+/// Tenant ids are DNS-label shaped: lowercase ASCII letters, digits and
+/// hyphens, starting with a letter or digit
+pub(crate) fn validate_tenant_id(tenant: &str) -> Result<(), String> {
+    if tenant.is_empty() || tenant.len() > MAX_TENANT_LEN {
+        return Err(format!("must be 1-{} characters", MAX_TENANT_LEN));
+    }
+    if tenant.starts_with('-') {
+        return Err("must start with a letter or digit".to_string());
+    }
+    if !tenant
+        .bytes()
+        .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+    {
+        return Err("must contain only lowercase letters, digits and hyphens".to_string());
+    }
+    Ok(())
+}
+
+/// Fail on the first record whose tenant is not `tenant`
+pub(crate) fn enforce_tenant<'a, I>(tenant: &str, record_tenants: I) -> Result<(), String>
+where
+    I: IntoIterator<Item = (&'a str, Option<&'a str>)>,
+{
+    for (id, record_tenant) in record_tenants {
+        match record_tenant {
+            Some(t) if t == tenant => {}
+            Some(t) => {
+                return Err(format!(
+                    "cross-tenant access: experience {} belongs to tenant {}, not {}",
+                    id, t, tenant
+                ))
+            }
+            None => return Err(format!("experience {} has no tenant", id)),
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn enforce_tenant_experiences(tenant: &str, experiences: &[Experience]) -> Result<(), String> {
+    enforce_tenant(
+        tenant,
+        experiences
+            .iter()
+            .map(|exp| (exp.id.as_str(), exp.tenant.as_deref())),
+    )
+}
+
+pub(crate) fn enforce_tenant_values(tenant: &str, experiences: &[Value]) -> Result<(), String> {
+    enforce_tenant(
+        tenant,
+        experiences.iter().map(|exp| {
+            (
+                exp.get("id").and_then(Value::as_str).unwrap_or("<no id>"),
+                exp.get("tenant").and_then(Value::as_str),
+            )
+        }),
+    )
+}
+
+/// Operations scoped to a single tenant
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct TenantScope {
+    tenant: String,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl TenantScope {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(tenant: &str) -> Result<TenantScope, Error> {
+        validate_tenant_id(tenant).map_err(|e| Error::invalid(format!("tenant {}", e)))?;
+        Ok(Self {
+            tenant: tenant.to_string(),
+        })
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn tenant(&self) -> String {
+        self.tenant.clone()
+    }
+
+    /// Check that every experience in the array belongs to this tenant
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn check(&self, experiences_json: &str) -> Result<(), Error> {
+        self.parse_values(experiences_json).map(|_| ())
+    }
+
+    /// Tenant-scoped `generate_domain_network`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn generate_domain_network(&self, experiences_json: &str) -> Result<String, Error> {
+        let experiences: Vec<Experience> = from_json(experiences_json, "experiences_json")?;
+        enforce_tenant_experiences(&self.tenant, &experiences).map_err(Error::invalid)?;
+
+        to_json(&build_network(&experiences))
+    }
+
+    /// Tenant-scoped `generate_domain_network_with_options`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn generate_domain_network_with_options(&self, experiences_json: &str, options_json: &str) -> Result<String, Error> {
+        let experiences: Vec<Experience> = from_json(experiences_json, "experiences_json")?;
+        enforce_tenant_experiences(&self.tenant, &experiences).map_err(Error::invalid)?;
+        let options = pruning::parse_options(options_json)?;
+
+        to_json(&pruning::simplified(accumulate_network(&experiences), &options))
+    }
+
+    /// Tenant-scoped `export_experiences_csv`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn export_experiences_csv(&self, experiences_json: &str, columns_spec: &str) -> Result<String, Error> {
+        let experiences = self.parse_values(experiences_json)?;
+        let columns = export::parse_columns(columns_spec).map_err(Error::invalid)?;
+        Ok(export::experiences_csv(&experiences, &columns))
+    }
+
+    pub(crate) fn parse_values(&self, experiences_json: &str) -> Result<Vec<Value>, Error> {
+        let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+        enforce_tenant_values(&self.tenant, &experiences).map_err(Error::invalid)?;
+        Ok(experiences)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn experiences(tenants: &[Option<&str>]) -> String {
+        let records: Vec<Value> = tenants
+            .iter()
+            .enumerate()
+            .map(|(i, tenant)| {
+                let mut exp = json!({
+                    "id": format!("e{}", i),
+                    "timestamp": "2024-05-01T10:00:00Z",
+                    "learner": {"id": format!("l{}", i)},
+                    "context": {"location": {"name": "library"}},
+                    "experience": {"type": "observation", "description": "d", "domains": ["ecology"]},
+                });
+                if let Some(tenant) = tenant {
+                    exp["tenant"] = json!(tenant);
+                }
+                exp
+            })
+            .collect();
+        Value::from(records).to_string()
+    }
+
+    #[test]
+    fn rejects_invalid_tenant_ids() {
+        for tenant in ["", "-school", "School", "a_b", &"a".repeat(64)] {
+            assert!(TenantScope::new(tenant).is_err(), "{:?}", tenant);
+        }
+        assert_eq!(TenantScope::new("school-7").unwrap().tenant(), "school-7");
+    }
+
+    #[test]
+    fn every_scoped_read_rejects_other_tenants() {
+        let scope = TenantScope::new("north").unwrap();
+        let own = experiences(&[Some("north"), Some("north")]);
+        let pipeline = json!([{"group": {"by": "domain", "accumulators": {"learners": {"distinct": "learner.id"}}}}]).to_string();
+        let query = json!({"filter": {"domain": "ecology"}}).to_string();
+
+        assert!(scope.generate_domain_network(&own).is_ok());
+        assert!(scope.export_experiences_csv(&own, "").is_ok());
+        assert!(scope.query_experiences(&own, &query).is_ok());
+        assert!(scope.aggregate(&own, &pipeline).is_ok());
+        assert!(scope.usage_report(&own, "").is_ok());
+
+        for mixed in [experiences(&[Some("north"), Some("south")]), experiences(&[Some("north"), None])] {
+            let results = [
+                scope.check(&mixed).err(),
+                scope.generate_domain_network(&mixed).err(),
+                scope.generate_domain_network_with_options(&mixed, "").err(),
+                scope.export_experiences_csv(&mixed, "").err(),
+                scope.query_experiences(&mixed, &query).err(),
+                scope.aggregate(&mixed, &pipeline).err(),
+                scope.usage_report(&mixed, "").err(),
+            ];
+            for error in results {
+                let error = error.expect("mixed tenants are rejected");
+                assert!(error.message().contains("e1"), "{}", error.message());
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Text normalisation shared by search, suggestion and similarity features
+
+/// Split text into lowercase alphanumeric tokens (Unicode-aware)
+pub(crate) fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Light English suffix-stripping stemmer
+///
+/// Deliberately conservative compared to Porter: it only folds common
+/// inflections (plurals, -ing, -ed, -ly and a few derivational endings) so
+/// `gardens`, `gardening` and `gardened` meet at `garden` without mangling
+/// short words or non-English tokens.
+pub(crate) fn stem(token: &str) -> String {
+    if token.chars().count() <= 3 || !token.is_ascii() {
+        return token.to_string();
+    }
+
+    const RULES: &[(&str, &str)] = &[
+        ("ational", "ate"),
+        ("ization", "ize"),
+        ("fulness", "ful"),
+        ("ousness", "ous"),
+        ("iveness", "ive"),
+        ("ments", "ment"),
+        ("ation", "ate"),
+        ("ness", ""),
+        ("ies", "y"),
+        ("ied", "y"),
+        ("sses", "ss"),
+        ("ing", ""),
+        ("edly", ""),
+        ("ly", ""),
+        ("ed", ""),
+        ("es", ""),
+        ("s", ""),
+    ];
+
+    for (suffix, replacement) in RULES {
+        if let Some(base) = token.strip_suffix(suffix) {
+            // Keep at least three characters and a vowel in the stem
+            if base.len() < 3 || !base.contains(['a', 'e', 'i', 'o', 'u', 'y']) {
+                continue;
+            }
+            if *suffix == "s" && (base.ends_with('s') || base.ends_with('u') || base.ends_with('i')) {
+                return token.to_string();
+            }
+            if *suffix == "es" && !base.ends_with(['x', 'z']) && !base.ends_with("ch") && !base.ends_with("sh") {
+                // "gardens" -> strip only the "s" below
+                continue;
+            }
+            let mut stemmed = format!("{}{}", base, replacement);
+            // running -> run, stopped -> stop
+            if matches!(*suffix, "ing" | "ed") {
+                let bytes = stemmed.as_bytes();
+                let n = bytes.len();
+                if n >= 2 && bytes[n - 1] == bytes[n - 2] && !matches!(bytes[n - 1], b'l' | b's' | b'z') {
+                    stemmed.pop();
+                }
+            }
+            return stemmed;
+        }
+    }
+
+    token.to_string()
+}
+
+/// Tokenize and optionally stem
+pub(crate) fn terms(text: &str, stemming: bool) -> Vec<String> {
+    let tokens = tokenize(text);
+    if stemming {
+        tokens.iter().map(|t| stem(t)).collect()
+    } else {
+        tokens
+    }
+}
@@ -0,0 +1,149 @@
+//! TF-IDF vectors and "more like this" search
+//!
+//! Each experience becomes a sparse vector over its description terms
+//! (stemmed) plus one `domain:<name>` feature per domain, so shared domains
+//! count alongside shared vocabulary. Weights are sublinear term frequency
+//! `1 + ln(tf)` times smoothed inverse document frequency
+//! `ln((1 + n) / (1 + df)) + 1`, and vectors are L2-normalised so cosine
+//! similarity is a dot product.
+
+use serde::Serialize;
+use std::collections::HashMap;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::Experience;
+use crate::error::{from_json, to_json, Error};
+use crate::kernels::sparse_dot;
+use crate::numeric::{ln, sum};
+use crate::text::terms;
+
+type SparseVector = Vec<(u32, f32)>;
+
+#[derive(Serialize)]
+struct SimilarHit<'a> {
+    id: &'a str,
+    score: f64,
+}
+
+/// TF-IDF vectors over an experience collection
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct TfidfIndex {
+    ids: Vec<String>,
+    positions: HashMap<String, usize>,
+    vocabulary: Vec<String>,
+    vectors: Vec<SparseVector>,
+}
+
+fn features(exp: &Experience) -> Vec<String> {
+    let mut features = terms(&exp.experience.description, true);
+    features.extend(
+        exp.experience.domains.iter().flatten().filter(|d| !d.is_empty()).map(|d| format!("domain:{}", d.to_lowercase())),
+    );
+    features
+}
+
+/// Build TF-IDF vectors over experience descriptions and domains
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn build_tfidf(experiences_json: &str) -> Result<TfidfIndex, Error> {
+    let experiences: Vec<Experience> = from_json(experiences_json, "experiences_json")?;
+
+    let mut feature_ids: HashMap<String, u32> = HashMap::new();
+    let mut vocabulary = Vec::new();
+    let mut counts: Vec<HashMap<u32, u32>> = Vec::with_capacity(experiences.len());
+    let mut df: Vec<u32> = Vec::new();
+    for exp in &experiences {
+        let mut doc: HashMap<u32, u32> = HashMap::new();
+        for feature in features(exp) {
+            let id = *feature_ids.entry(feature.clone()).or_insert_with(|| {
+                vocabulary.push(feature);
+                df.push(0);
+                (vocabulary.len() - 1) as u32
+            });
+            *doc.entry(id).or_insert(0) += 1;
+        }
+        for &id in doc.keys() {
+            df[id as usize] += 1;
+        }
+        counts.push(doc);
+    }
+
+    let n = experiences.len() as f64;
+    let vectors = counts
+        .into_iter()
+        .map(|doc| {
+            let mut vector: Vec<(u32, f64)> = doc
+                .into_iter()
+                .map(|(id, tf)| {
+                    let idf = ln((1.0 + n) / (1.0 + f64::from(df[id as usize]))) + 1.0;
+                    (id, (1.0 + ln(f64::from(tf))) * idf)
+                })
+                .collect();
+            vector.sort_by_key(|&(id, _)| id);
+            let norm = sum(vector.iter().map(|&(_, w)| w * w)).sqrt();
+            vector.into_iter().map(|(id, w)| (id, (w / norm) as f32)).collect()
+        })
+        .collect();
+
+    let ids: Vec<String> = experiences.into_iter().map(|e| e.id).collect();
+    let mut positions = HashMap::new();
+    for (i, id) in ids.iter().enumerate() {
+        positions.entry(id.clone()).or_insert(i);
+    }
+    Ok(TfidfIndex { ids, positions, vocabulary, vectors })
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl TfidfIndex {
+    /// Number of indexed experiences
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn size(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// The `k` experiences most similar to `experience_id` by cosine
+    /// Returns `[{id, score}]` as JSON, best match first
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn most_similar(&self, experience_id: &str, k: usize) -> Result<String, Error> {
+        let target = self.position(experience_id)?;
+        let mut hits: Vec<(usize, f64)> = self
+            .vectors
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != target)
+            .map(|(i, v)| (i, sparse_dot(&self.vectors[target], v)))
+            .filter(|&(_, score)| score > 0.0)
+            .collect();
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        hits.truncate(k);
+
+        let hits: Vec<SimilarHit> = hits.into_iter().map(|(i, score)| SimilarHit { id: &self.ids[i], score }).collect();
+        to_json(&hits)
+    }
+
+    /// Dense TF-IDF vector of an experience, indexed like `vocabulary()`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn vector(&self, experience_id: &str) -> Result<Vec<f32>, Error> {
+        let mut dense = vec![0.0; self.vocabulary.len()];
+        for &(id, w) in &self.vectors[self.position(experience_id)?] {
+            dense[id as usize] = w;
+        }
+        Ok(dense)
+    }
+
+    /// Feature names (stemmed terms and `domain:` features) in vector order
+    /// Returns a JSON array of strings
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn vocabulary(&self) -> Result<String, Error> {
+        to_json(&self.vocabulary)
+    }
+}
+
+impl TfidfIndex {
+    fn position(&self, experience_id: &str) -> Result<usize, Error> {
+        self.positions
+            .get(experience_id)
+            .copied()
+            .ok_or_else(|| Error::not_found(format!("unknown experience: {}", experience_id)).with("id", experience_id))
+    }
+}
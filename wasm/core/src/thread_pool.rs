@@ -0,0 +1,113 @@
+//! rayon's global pool on Web Workers
+//!
+//! Browsers do not let wasm spawn threads itself, so with the `threads`
+//! feature on wasm32 the host calls [`init_thread_pool`] once, before the
+//! first parallel call. It starts the workers from `src/thread_workers.js`;
+//! each imports the generated JS glue by the URL the main thread loaded it
+//! from, instantiates it over the same shared memory and parks in
+//! [`start_thread_pool_worker`] as one rayon thread. This is the scheme of
+//! `wasm-bindgen-rayon`, kept in-tree so the build needs no extra crate
+//! (and so the glue is found by URL rather than by a bundler resolving a
+//! relative path, which `--target web` output cannot do).
+//!
+//! Pages that are not cross-origin isolated have no `SharedArrayBuffer`, so
+//! there `init_thread_pool` starts no workers and resolves to `false`; rayon
+//! then builds a one-thread pool on the calling thread at the first
+//! parallel call, which `parallelism()` reports as `pool_size: 1`.
+//!
+//! Shared memory needs a std built with atomics, which only nightly can do:
+//!
+//! ```text
+//! RUSTFLAGS="-C target-feature=+atomics,+bulk-memory,+mutable-globals" \
+//!   rustup run nightly wasm-pack build --target web -- --features threads \
+//!   -Z build-std=panic_abort,std
+//! ```
+//!
+//! and the page must be cross-origin isolated (`Cross-Origin-Opener-Policy:
+//! same-origin`, `Cross-Origin-Embedder-Policy: require-corp`) for
+//! `SharedArrayBuffer` to exist.
+
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Mutex, OnceLock};
+
+use js_sys::{Promise, Reflect};
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+
+/// Threads handed out by rayon's spawn handler, waiting for a worker
+static THREADS: OnceLock<Mutex<Receiver<rayon::ThreadBuilder>>> = OnceLock::new();
+
+#[wasm_bindgen(module = "/src/thread_workers.js")]
+extern "C" {
+    #[wasm_bindgen(js_name = startWorkers)]
+    fn start_workers(url: &str, module: JsValue, memory: JsValue, builder: ThreadPoolBuilder) -> Promise;
+}
+
+#[wasm_bindgen]
+extern "C" {
+    /// URL of the generated JS glue; `import.meta` is read in the glue
+    /// module itself
+    #[wasm_bindgen(thread_local_v2, js_namespace = ["import", "meta"], js_name = url)]
+    static GLUE_URL: String;
+}
+
+/// Whether `SharedArrayBuffer` can be shared with workers here
+fn cross_origin_isolated() -> bool {
+    Reflect::get(&js_sys::global(), &JsValue::from_str("crossOriginIsolated")).is_ok_and(|v| v.is_truthy())
+}
+
+/// Handle passed to `startWorkers`, which builds the pool once every
+/// worker is listening
+#[wasm_bindgen]
+pub struct ThreadPoolBuilder {
+    num_threads: usize,
+    sender: Sender<rayon::ThreadBuilder>,
+}
+
+#[wasm_bindgen]
+impl ThreadPoolBuilder {
+    #[wasm_bindgen(getter)]
+    pub fn num_threads(&self) -> usize {
+        self.num_threads
+    }
+
+    /// Install rayon's global pool, sending each of its threads to a worker
+    pub fn build(&self) -> Result<(), Error> {
+        let sender = self.sender.clone();
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(self.num_threads)
+            .spawn_handler(move |thread| sender.send(thread).map_err(|e| std::io::Error::other(e.to_string())))
+            .build_global()
+            .map_err(|e| Error::host(e.to_string()))
+    }
+}
+
+/// Start `num_threads` Web Workers as rayon's global pool
+/// Resolves to `true` once the pool is ready, or at once to `false` when
+/// the page is not cross-origin isolated and work stays on the calling
+/// thread; may only be called once
+#[wasm_bindgen]
+pub fn init_thread_pool(num_threads: usize) -> Promise {
+    if num_threads == 0 {
+        return Promise::reject(&Error::invalid("num_threads must be at least 1").into());
+    }
+    let (sender, receiver) = channel();
+    if THREADS.set(Mutex::new(receiver)).is_err() {
+        return Promise::reject(&Error::invalid("thread pool already started").into());
+    }
+    if !cross_origin_isolated() {
+        return Promise::resolve(&JsValue::FALSE);
+    }
+    let url = GLUE_URL.with(String::clone);
+    start_workers(&url, wasm_bindgen::module(), wasm_bindgen::memory(), ThreadPoolBuilder { num_threads, sender })
+}
+
+/// Worker entry point: run one rayon thread until the pool shuts down
+#[wasm_bindgen]
+pub fn start_thread_pool_worker() {
+    let thread = THREADS.get().and_then(|threads| threads.lock().ok()?.recv().ok());
+    if let Some(thread) = thread {
+        thread.run();
+    }
+}
@@ -0,0 +1,41 @@
+// SPDX-License-Identifier: MPL-2.0
+// Web Workers for rayon's thread pool (see thread_pool.rs). This file runs
+// both on the main thread, where startWorkers spawns the workers, and in
+// each worker, where it waits for the module and shared memory.
+
+function waitForMessage(target, type) {
+  return new Promise((resolve) => {
+    target.addEventListener('message', function onMessage({ data }) {
+      if (data?.type !== type) return;
+      target.removeEventListener('message', onMessage);
+      resolve(data);
+    });
+  });
+}
+
+// In a worker: instantiate the package on the shared memory, report ready,
+// then block as one pool thread. The main thread sends the URL it loaded
+// the package's JS glue from, so this works without a bundler.
+if (typeof WorkerGlobalScope !== 'undefined' && self instanceof WorkerGlobalScope) {
+  waitForMessage(self, 'ubicity_thread_init').then(async ({ url, module, memory }) => {
+    const pkg = await import(url);
+    await pkg.default({ module_or_path: module, memory });
+    postMessage({ type: 'ubicity_thread_ready' });
+    pkg.start_thread_pool_worker();
+  });
+}
+
+export async function startWorkers(url, module, memory, builder) {
+  const workers = await Promise.all(
+    Array.from({ length: builder.num_threads }, async () => {
+      const worker = new Worker(new URL('./thread_workers.js', import.meta.url), { type: 'module' });
+      worker.postMessage({ type: 'ubicity_thread_init', url, module, memory });
+      await waitForMessage(worker, 'ubicity_thread_ready');
+      return worker;
+    })
+  );
+  builder.build();
+  return true;
+  // Keep the workers reachable for the lifetime of the pool
+  startWorkers.workers = workers;
+}
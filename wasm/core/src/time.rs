@@ -0,0 +1,176 @@
+//! Timestamp helpers
+//!
+//! Experiences carry RFC 3339 timestamps. Analytics need them as comparable
+//! instants, so they are converted to milliseconds since the Unix epoch (UTC)
+//! without pulling a full date-time library into the WASM binary.
+
+pub(crate) const MS_PER_SECOND: i64 = 1_000;
+pub(crate) const MS_PER_MINUTE: i64 = 60 * MS_PER_SECOND;
+pub(crate) const MS_PER_HOUR: i64 = 60 * MS_PER_MINUTE;
+pub(crate) const MS_PER_DAY: i64 = 24 * MS_PER_HOUR;
+
+/// Days since 1970-01-01 for a proleptic Gregorian date
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Inverse of [`days_from_civil`]: `(year, month, day)`
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn digits(s: &str, range: std::ops::Range<usize>) -> Option<u32> {
+    let part = s.get(range)?;
+    if part.bytes().all(|b| b.is_ascii_digit()) {
+        part.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Parse an RFC 3339 timestamp (`2024-05-01T14:30:00Z`,
+/// `2024-05-01T14:30:00.250+01:00`) or a bare date (`2024-05-01`, midnight
+/// UTC) into epoch milliseconds
+pub(crate) fn parse_timestamp(s: &str) -> Option<i64> {
+    let s = s.trim();
+    let year = digits(s, 0..4)? as i64;
+    if s.get(4..5)? != "-" || s.get(7..8)? != "-" {
+        return None;
+    }
+    let month = digits(s, 5..7)?;
+    let day = digits(s, 8..10)?;
+    if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+    let date_ms = days_from_civil(year, month, day) * MS_PER_DAY;
+
+    if s.len() == 10 {
+        return Some(date_ms);
+    }
+    if !matches!(s.get(10..11)?, "T" | "t" | " ") || s.get(13..14)? != ":" || s.get(16..17)? != ":" {
+        return None;
+    }
+    let hour = digits(s, 11..13)?;
+    let minute = digits(s, 14..16)?;
+    let second = digits(s, 17..19)?;
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let mut rest = &s[19..];
+    let mut millis = 0i64;
+    if let Some(frac) = rest.strip_prefix('.') {
+        let len = frac.bytes().take_while(u8::is_ascii_digit).count();
+        if len == 0 {
+            return None;
+        }
+        let padded = format!("{:0<3}", &frac[..len.min(3)]);
+        millis = padded.parse().ok()?;
+        rest = &frac[len..];
+    }
+
+    let offset_ms = match rest {
+        "Z" | "z" => 0,
+        _ => {
+            let sign = match rest.get(0..1)? {
+                "+" => 1,
+                "-" => -1,
+                _ => return None,
+            };
+            if rest.len() != 6 || rest.get(3..4)? != ":" {
+                return None;
+            }
+            let oh = digits(rest, 1..3)? as i64;
+            let om = digits(rest, 4..6)? as i64;
+            sign * (oh * MS_PER_HOUR + om * MS_PER_MINUTE)
+        }
+    };
+
+    Some(
+        date_ms
+            + hour as i64 * MS_PER_HOUR
+            + minute as i64 * MS_PER_MINUTE
+            + second as i64 * MS_PER_SECOND
+            + millis
+            - offset_ms,
+    )
+}
+
+/// Wall-clock time of a timestamp in its own UTC offset, in epoch
+/// milliseconds as if that wall clock were UTC (`2024-05-01T22:00:00+02:00`
+/// gives 22:00 on 1 May); for reading local hours and weekdays
+pub(crate) fn local_ms(s: &str) -> Option<i64> {
+    let instant = parse_timestamp(s)?;
+    let s = s.trim();
+    if s.len() == 10 {
+        return Some(instant);
+    }
+    parse_timestamp(&format!("{}Z", s.get(..19)?))
+}
+
+/// Format epoch milliseconds as an RFC 3339 UTC timestamp with millisecond
+/// precision
+pub(crate) fn format_timestamp(ms: i64) -> String {
+    let days = ms.div_euclid(MS_PER_DAY);
+    let rem = ms.rem_euclid(MS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / MS_PER_HOUR,
+        rem % MS_PER_HOUR / MS_PER_MINUTE,
+        rem % MS_PER_MINUTE / MS_PER_SECOND,
+        rem % MS_PER_SECOND
+    )
+}
+
+/// ISO 8601 week date `(week-based year, week)` of epoch milliseconds
+pub(crate) fn iso_week(ms: i64) -> (i64, u32) {
+    let days = ms.div_euclid(MS_PER_DAY);
+    // 1970-01-01 was a Thursday; weeks belong to the year of their Thursday
+    let thursday = days - (days + 3).rem_euclid(7) + 3;
+    let (year, _, _) = civil_from_days(thursday);
+    let week = (thursday - days_from_civil(year, 1, 1)) / 7 + 1;
+    (year, week as u32)
+}
+
+/// Current wall-clock time in epoch milliseconds
+pub(crate) fn now_ms() -> i64 {
+    #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+    {
+        js_sys::Date::now() as i64
+    }
+    #[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0)
+    }
+}
@@ -0,0 +1,169 @@
+//! Learner movement paths between experiences
+//!
+//! [`build_trajectories`] orders each learner's geo-tagged experiences by
+//! time and returns one GeoJSON feature per learner, ready for a map layer:
+//! a LineString through the experience locations (a Point when there is
+//! only one), with the path length and the learner's stops in its
+//! properties.
+//!
+//! A stop is a run of consecutive experiences within 100 m of where the run
+//! began; its dwell time runs from the first to the last experience of the
+//! run. Stops at the same spot on different occasions are merged into one
+//! location cluster, so `clusters` says where a learner spent their time
+//! overall. Fixes less accurate than 100 m, or imputed, are drawn in the
+//! path but take no part in stops and are counted as `imprecise`.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::geo::haversine_m;
+use crate::group_geometry::centroid;
+use crate::identity::canonical_learner_id;
+use crate::numeric;
+use crate::query::{coordinates, precise_within};
+use crate::time::{format_timestamp, parse_timestamp, MS_PER_SECOND};
+
+/// Experiences this close to a stop's first location belong to the stop
+const CLUSTER_RADIUS_M: f64 = 100.0;
+
+struct Point<'a> {
+    ms: i64,
+    id: &'a str,
+    lat: f64,
+    lon: f64,
+    place: Option<&'a str>,
+    /// Accurate enough to place the experience at a stop
+    precise: bool,
+}
+
+/// A run of consecutive points at one spot
+struct Stop {
+    lat: f64,
+    lon: f64,
+    members: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct Cluster {
+    latitude: f64,
+    longitude: f64,
+    /// Most frequent `context.location.name` among the cluster's experiences
+    place: Option<String>,
+    visits: usize,
+    experiences: usize,
+    dwell_s: f64,
+    first_arrival: String,
+    last_departure: String,
+}
+
+fn stops(points: &[Point]) -> Vec<Stop> {
+    let mut stops: Vec<Stop> = Vec::new();
+    for (i, p) in points.iter().enumerate().filter(|(_, p)| p.precise) {
+        match stops.last_mut() {
+            Some(stop) if haversine_m(stop.lat, stop.lon, p.lat, p.lon) <= CLUSTER_RADIUS_M => stop.members.push(i),
+            _ => stops.push(Stop { lat: p.lat, lon: p.lon, members: vec![i] }),
+        }
+    }
+    stops
+}
+
+fn clusters(points: &[Point]) -> Vec<Cluster> {
+    // Merge stops whose anchors fall within the radius of an earlier one
+    let mut merged: Vec<(f64, f64, Vec<Stop>)> = Vec::new();
+    for stop in stops(points) {
+        match merged.iter_mut().find(|(lat, lon, _)| haversine_m(*lat, *lon, stop.lat, stop.lon) <= CLUSTER_RADIUS_M) {
+            Some((_, _, visits)) => visits.push(stop),
+            None => merged.push((stop.lat, stop.lon, vec![stop])),
+        }
+    }
+    merged
+        .into_iter()
+        .map(|(_, _, visits)| {
+            let members: Vec<&Point> = visits.iter().flat_map(|v| v.members.iter().map(|&i| &points[i])).collect();
+            let positions: Vec<(f64, f64)> = members.iter().map(|p| (p.lat, p.lon)).collect();
+            let (latitude, longitude) = centroid(&positions).unwrap_or_default();
+            let mut names: BTreeMap<&str, usize> = BTreeMap::new();
+            for name in members.iter().filter_map(|p| p.place) {
+                *names.entry(name).or_insert(0) += 1;
+            }
+            let place = names
+                .into_iter()
+                .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+                .map(|(name, _)| name.to_string());
+            let dwell_ms = numeric::sum(visits.iter().map(|v| {
+                let (first, last) = (v.members[0], v.members[v.members.len() - 1]);
+                (points[last].ms - points[first].ms) as f64
+            }));
+            Cluster {
+                latitude,
+                longitude,
+                place,
+                visits: visits.len(),
+                experiences: members.len(),
+                dwell_s: dwell_ms / MS_PER_SECOND as f64,
+                first_arrival: format_timestamp(members.iter().map(|p| p.ms).min().unwrap_or(0)),
+                last_departure: format_timestamp(members.iter().map(|p| p.ms).max().unwrap_or(0)),
+            }
+        })
+        .collect()
+}
+
+fn trajectory(learner: &str, mut points: Vec<Point>) -> Value {
+    points.sort_by(|a, b| a.ms.cmp(&b.ms).then_with(|| a.id.cmp(b.id)));
+    let distance_m = numeric::sum(points.windows(2).map(|w| haversine_m(w[0].lat, w[0].lon, w[1].lat, w[1].lon)));
+    let positions: Vec<[f64; 2]> = points.iter().map(|p| [p.lon, p.lat]).collect();
+    let geometry = if positions.len() == 1 {
+        json!({"type": "Point", "coordinates": positions[0]})
+    } else {
+        json!({"type": "LineString", "coordinates": positions})
+    };
+    json!({
+        "type": "Feature",
+        "geometry": geometry,
+        "properties": {
+            "learner": learner,
+            "experiences": points.iter().map(|p| p.id).collect::<Vec<_>>(),
+            "start": format_timestamp(points[0].ms),
+            "end": format_timestamp(points[points.len() - 1].ms),
+            "distance_m": distance_m,
+            "imprecise": points.iter().filter(|p| !p.precise).count(),
+            "clusters": clusters(&points),
+        },
+    })
+}
+
+/// Per-learner movement paths through their geo-tagged experiences
+/// Returns a GeoJSON FeatureCollection with one LineString feature per
+/// learner, whose properties hold `learner`, `experiences` (ids in path
+/// order), `start`, `end`, `distance_m`, `imprecise` and `clusters`
+/// (`[{latitude, longitude, place, visits, experiences, dwell_s,
+/// first_arrival, last_departure}]`), as JSON
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn build_trajectories(experiences_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let mut by_learner: BTreeMap<String, Vec<Point>> = BTreeMap::new();
+    for exp in &experiences {
+        let (Some(learner), Some(ms), Some((lat, lon))) = (
+            lookup(exp, "learner.id").and_then(Value::as_str),
+            lookup(exp, "timestamp").and_then(Value::as_str).and_then(parse_timestamp),
+            coordinates(exp),
+        ) else {
+            continue;
+        };
+        by_learner.entry(canonical_learner_id(learner)).or_default().push(Point {
+            ms,
+            id: lookup(exp, "id").and_then(Value::as_str).unwrap_or_default(),
+            lat,
+            lon,
+            place: lookup(exp, "context.location.name").and_then(Value::as_str),
+            precise: precise_within(exp, CLUSTER_RADIUS_M),
+        });
+    }
+    let features: Vec<Value> = by_learner.into_iter().map(|(learner, points)| trajectory(&learner, points)).collect();
+    to_json(&json!({"type": "FeatureCollection", "features": features}))
+}
@@ -0,0 +1,216 @@
+//! Knowledge-graph triple export
+//!
+//! Experiences become RDF-style `(subject, predicate, object)` triples over
+//! four kinds of node, each with a stable IRI:
+//!
+//! | Node       | IRI                                                      |
+//! |------------|----------------------------------------------------------|
+//! | experience | `urn:ubicity:experience:<id>`                            |
+//! | learner    | the learner's DID / https IRI, else `urn:ubicity:learner:<id>` |
+//! | place      | `urn:ubicity:place:<lowercased location name>`           |
+//! | domain     | `urn:ubicity:domain:<domain>`                            |
+//!
+//! Predicates live in the `https://ubicity.learning/vocab#` namespace:
+//!
+//! | Predicate        | Subject    | Object                          |
+//! |------------------|------------|---------------------------------|
+//! | `rdf:type`       | any        | `ubi:Experience`, `ubi:Learner`, `ubi:Place`, `ubi:Domain` |
+//! | `ubi:learner`    | experience | learner                         |
+//! | `ubi:place`      | experience | place                           |
+//! | `ubi:domain`     | experience | domain                          |
+//! | `ubi:relatedTo`  | experience | experience (`metadata.related_experiences`) |
+//! | `ubi:timestamp`  | experience | `xsd:dateTime` literal          |
+//! | `ubi:experienceType` | experience | string literal              |
+//! | `ubi:description` | experience | string literal                 |
+//! | `ubi:name`       | place, domain | string literal               |
+//! | `ubi:latitude`, `ubi:longitude` | place | `xsd:double` literal   |
+//!
+//! [`to_triples`] returns the triples as JSON; [`to_ntriples`] returns the
+//! same graph as an N-Triples document for direct bulk loading.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::identity::{canonical_learner_id, LearnerId};
+use crate::query::{coordinates, domains};
+
+pub(crate) const VOCAB: &str = "https://ubicity.learning/vocab#";
+pub(crate) const RDF_TYPE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+pub(crate) const XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum Object {
+    Iri { value: String },
+    Literal {
+        value: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        datatype: Option<String>,
+    },
+}
+
+impl Object {
+    pub(crate) fn value(&self) -> &str {
+        match self {
+            Object::Iri { value } | Object::Literal { value, .. } => value,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Triple {
+    pub(crate) subject: String,
+    pub(crate) predicate: String,
+    pub(crate) object: Object,
+}
+
+/// Percent-encode everything outside RFC 3986 unreserved characters
+fn encode_segment(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            out.push(b as char);
+        } else {
+            let _ = write!(out, "%{:02X}", b);
+        }
+    }
+    out
+}
+
+fn learner_iri(id: &str) -> String {
+    match LearnerId::parse(id) {
+        Ok(LearnerId::Local(_)) | Err(_) => format!("urn:ubicity:learner:{}", encode_segment(id)),
+        Ok(parsed) => parsed.canonical(),
+    }
+}
+
+struct Graph {
+    triples: BTreeSet<Triple>,
+}
+
+impl Graph {
+    fn iri(&mut self, subject: &str, predicate: &str, object: String) {
+        self.triples.insert(Triple {
+            subject: subject.to_string(),
+            predicate: predicate.to_string(),
+            object: Object::Iri { value: object },
+        });
+    }
+
+    fn literal(&mut self, subject: &str, predicate: &str, value: String, datatype: Option<&str>) {
+        self.triples.insert(Triple {
+            subject: subject.to_string(),
+            predicate: predicate.to_string(),
+            object: Object::Literal { value, datatype: datatype.map(|t| format!("{}{}", XSD, t)) },
+        });
+    }
+
+    fn typed(&mut self, subject: &str, class: &str) {
+        self.iri(subject, RDF_TYPE, format!("{}{}", VOCAB, class));
+    }
+}
+
+pub(crate) fn build_triples(experiences: &[Value]) -> Vec<Triple> {
+    let mut graph = Graph { triples: BTreeSet::new() };
+    let p = |name: &str| format!("{}{}", VOCAB, name);
+
+    for exp in experiences {
+        let Some(id) = exp.get("id").and_then(Value::as_str) else {
+            continue;
+        };
+        let subject = format!("urn:ubicity:experience:{}", encode_segment(id));
+        graph.typed(&subject, "Experience");
+
+        if let Some(ts) = exp.get("timestamp").and_then(Value::as_str) {
+            graph.literal(&subject, &p("timestamp"), ts.to_string(), Some("dateTime"));
+        }
+        if let Some(t) = lookup(exp, "experience.type").and_then(Value::as_str) {
+            graph.literal(&subject, &p("experienceType"), t.to_string(), None);
+        }
+        if let Some(d) = lookup(exp, "experience.description").and_then(Value::as_str) {
+            graph.literal(&subject, &p("description"), d.to_string(), None);
+        }
+
+        if let Some(learner) = lookup(exp, "learner.id").and_then(Value::as_str) {
+            let iri = learner_iri(&canonical_learner_id(learner));
+            graph.typed(&iri, "Learner");
+            graph.iri(&subject, &p("learner"), iri);
+        }
+
+        if let Some(name) = lookup(exp, "context.location.name").and_then(Value::as_str).filter(|n| !n.trim().is_empty()) {
+            let place = format!("urn:ubicity:place:{}", encode_segment(&name.trim().to_lowercase()));
+            graph.typed(&place, "Place");
+            graph.literal(&place, &p("name"), name.trim().to_string(), None);
+            if let Some((lat, lon)) = coordinates(exp) {
+                graph.literal(&place, &p("latitude"), lat.to_string(), Some("double"));
+                graph.literal(&place, &p("longitude"), lon.to_string(), Some("double"));
+            }
+            graph.iri(&subject, &p("place"), place);
+        }
+
+        for domain in domains(exp) {
+            let iri = format!("urn:ubicity:domain:{}", encode_segment(domain));
+            graph.typed(&iri, "Domain");
+            graph.literal(&iri, &p("name"), domain.to_string(), None);
+            graph.iri(&subject, &p("domain"), iri);
+        }
+
+        for related in lookup(exp, "metadata.related_experiences").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str) {
+            graph.iri(&subject, &p("relatedTo"), format!("urn:ubicity:experience:{}", encode_segment(related)));
+        }
+    }
+
+    graph.triples.into_iter().collect()
+}
+
+/// Escape a literal per the N-Triples `STRING_LITERAL_QUOTE` production
+fn escape_literal(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn ntriples(triples: &[Triple]) -> String {
+    let mut out = String::new();
+    for t in triples {
+        let object = match &t.object {
+            Object::Iri { value } => format!("<{}>", value),
+            Object::Literal { value, datatype: Some(datatype) } => format!("\"{}\"^^<{}>", escape_literal(value), datatype),
+            Object::Literal { value, datatype: None } => format!("\"{}\"", escape_literal(value)),
+        };
+        let _ = writeln!(out, "<{}> <{}> {} .", t.subject, t.predicate, object);
+    }
+    out
+}
+
+/// Knowledge-graph triples over experiences, learners, places and domains
+/// Returns `[{subject, predicate, object: {kind, value, datatype?}}]` as
+/// JSON, deduplicated and sorted
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn to_triples(experiences_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+
+    to_json(&build_triples(&experiences))
+}
+
+/// The `to_triples` graph as an N-Triples document
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn to_ntriples(experiences_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+
+    Ok(ntriples(&build_triples(&experiences)))
+}
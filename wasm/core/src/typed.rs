@@ -0,0 +1,41 @@
+//! Typed object entry points for TypeScript callers
+//!
+//! The JSON-string exports remain the language-neutral API. These variants
+//! take and return plain JS objects instead, and the `Experience`,
+//! `ValidationResult` and `DomainNetwork` interfaces in the generated
+//! `.d.ts` come from the Rust types via tsify, so a TS caller that passes
+//! the wrong shape fails to compile rather than at runtime. Inputs are
+//! still checked on arrival since JS callers are not type-checked.
+
+use serde::de::DeserializeOwned;
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::{build_network, DomainNetwork, Experience, ExperienceValidator, ValidationResult};
+
+fn from_js<T: DeserializeOwned>(value: JsValue) -> Result<T, String> {
+    // The deserializer's errors are JS `Error`s; keep just the message
+    serde_wasm_bindgen::from_value(value).map_err(|e| Error::from(JsValue::from(e)).message().to_string())
+}
+
+#[wasm_bindgen]
+impl ExperienceValidator {
+    /// Validate a learning experience object
+    #[wasm_bindgen]
+    pub fn validate_js(&self, #[wasm_bindgen(unchecked_param_type = "Experience")] experience: JsValue) -> ValidationResult {
+        match from_js::<Experience>(experience) {
+            Ok(exp) => self.validate_experience(&exp),
+            Err(e) => self.parse_failure(e),
+        }
+    }
+}
+
+/// Domain network generation from an array of experience objects
+#[wasm_bindgen]
+pub fn generate_domain_network_js(
+    #[wasm_bindgen(unchecked_param_type = "Experience[]")] experiences: JsValue,
+) -> Result<DomainNetwork, Error> {
+    let experiences: Vec<Experience> =
+        from_js(experiences).map_err(|e| Error::parse(e).with("argument", "experiences"))?;
+    Ok(build_network(&experiences))
+}
@@ -0,0 +1,529 @@
+//! Measurements with units
+//!
+//! Science activities record what learners measured next to the experience,
+//! each reading with its UCUM unit code and optionally the quantity it is
+//! meant to be:
+//!
+//! ```json
+//! "experience": {"measurements": {"water_temperature": {"value": 14.5, "unit": "Cel"},
+//!                                 "stream_width": {"value": 3.2, "unit": "m", "quantity": "distance"}}}
+//! ```
+//!
+//! Units are parsed as UCUM case-sensitive codes: the common metric atoms
+//! with their prefixes (`km`, `mg`, `mL`), customary units in brackets
+//! (`[in_i]`, `[lb_av]`, `[degF]`), integer exponents, `.` and `/` with
+//! parentheses (`m/s2`, `kg.m-3`) and `{annotations}`. Celsius and
+//! Fahrenheit have an offset from kelvin and so must stand alone. Readings
+//! are validated with the experience; [`normalize_measurements`] converts a
+//! dataset to one unit per quantity so classes that measured in inches and
+//! centimetres can be analysed together.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::numeric::powi;
+use crate::severity::Issue;
+
+/// Exponents of length, mass, time, temperature and plane angle
+type Dimension = [i32; 5];
+
+const DIMENSIONLESS: Dimension = [0, 0, 0, 0, 0];
+const TEMPERATURE: Dimension = [0, 0, 0, 1, 0];
+
+/// Named quantities; `quantity` on a measurement may use any of these names
+const QUANTITIES: &[(&str, Dimension)] = &[
+    ("length", [1, 0, 0, 0, 0]),
+    ("distance", [1, 0, 0, 0, 0]),
+    ("area", [2, 0, 0, 0, 0]),
+    ("volume", [3, 0, 0, 0, 0]),
+    ("mass", [0, 1, 0, 0, 0]),
+    ("time", [0, 0, 1, 0, 0]),
+    ("temperature", TEMPERATURE),
+    ("angle", [0, 0, 0, 0, 1]),
+    ("dimensionless", DIMENSIONLESS),
+    ("speed", [1, 0, -1, 0, 0]),
+    ("acceleration", [1, 0, -2, 0, 0]),
+    ("frequency", [0, 0, -1, 0, 0]),
+    ("force", [1, 1, -2, 0, 0]),
+    ("pressure", [-1, 1, -2, 0, 0]),
+    ("energy", [2, 1, -2, 0, 0]),
+    ("power", [2, 1, -3, 0, 0]),
+    ("density", [-3, 1, 0, 0, 0]),
+];
+
+/// Unit each quantity is converted to when no target is given
+const DEFAULT_TARGETS: &[(&str, &str)] = &[
+    ("length", "m"),
+    ("area", "m2"),
+    ("volume", "L"),
+    ("mass", "kg"),
+    ("time", "s"),
+    ("temperature", "Cel"),
+    ("speed", "m/s"),
+];
+
+/// UCUM atoms: code, dimension, factor to SI and whether metric prefixes
+/// apply
+const ATOMS: &[(&str, Dimension, f64, bool)] = &[
+    ("m", [1, 0, 0, 0, 0], 1.0, true),
+    ("g", [0, 1, 0, 0, 0], 1e-3, true),
+    ("s", [0, 0, 1, 0, 0], 1.0, true),
+    ("K", TEMPERATURE, 1.0, true),
+    ("rad", [0, 0, 0, 0, 1], 1.0, true),
+    ("L", [3, 0, 0, 0, 0], 1e-3, true),
+    ("l", [3, 0, 0, 0, 0], 1e-3, true),
+    ("t", [0, 1, 0, 0, 0], 1e3, true),
+    ("ar", [2, 0, 0, 0, 0], 1e2, true),
+    ("Hz", [0, 0, -1, 0, 0], 1.0, true),
+    ("N", [1, 1, -2, 0, 0], 1.0, true),
+    ("Pa", [-1, 1, -2, 0, 0], 1.0, true),
+    ("bar", [-1, 1, -2, 0, 0], 1e5, true),
+    ("m[Hg]", [-1, 1, -2, 0, 0], 133_322.387_415, true),
+    ("J", [2, 1, -2, 0, 0], 1.0, true),
+    ("cal", [2, 1, -2, 0, 0], 4.184, true),
+    ("W", [2, 1, -3, 0, 0], 1.0, true),
+    ("min", [0, 0, 1, 0, 0], 60.0, false),
+    ("h", [0, 0, 1, 0, 0], 3_600.0, false),
+    ("d", [0, 0, 1, 0, 0], 86_400.0, false),
+    ("wk", [0, 0, 1, 0, 0], 604_800.0, false),
+    ("deg", [0, 0, 0, 0, 1], std::f64::consts::PI / 180.0, false),
+    ("atm", [-1, 1, -2, 0, 0], 101_325.0, false),
+    ("%", DIMENSIONLESS, 1e-2, false),
+    ("[ppm]", DIMENSIONLESS, 1e-6, false),
+    ("[in_i]", [1, 0, 0, 0, 0], 0.0254, false),
+    ("[ft_i]", [1, 0, 0, 0, 0], 0.3048, false),
+    ("[yd_i]", [1, 0, 0, 0, 0], 0.9144, false),
+    ("[mi_i]", [1, 0, 0, 0, 0], 1_609.344, false),
+    ("[nmi_i]", [1, 0, 0, 0, 0], 1_852.0, false),
+    ("[kn_i]", [1, 0, -1, 0, 0], 1_852.0 / 3_600.0, false),
+    ("[lb_av]", [0, 1, 0, 0, 0], 0.453_592_37, false),
+    ("[oz_av]", [0, 1, 0, 0, 0], 0.028_349_523_125, false),
+    ("[gal_us]", [3, 0, 0, 0, 0], 0.003_785_411_784, false),
+    ("[foz_us]", [3, 0, 0, 0, 0], 2.957_352_956_25e-5, false),
+];
+
+/// Units with an offset from kelvin: code, factor and offset
+const OFFSET_UNITS: &[(&str, f64, f64)] = &[("Cel", 1.0, 273.15), ("[degF]", 5.0 / 9.0, 459.67 * 5.0 / 9.0)];
+
+const PREFIXES: &[(&str, f64)] = &[
+    ("da", 1e1),
+    ("Y", 1e24),
+    ("Z", 1e21),
+    ("E", 1e18),
+    ("P", 1e15),
+    ("T", 1e12),
+    ("G", 1e9),
+    ("M", 1e6),
+    ("k", 1e3),
+    ("h", 1e2),
+    ("d", 1e-1),
+    ("c", 1e-2),
+    ("m", 1e-3),
+    ("u", 1e-6),
+    ("n", 1e-9),
+    ("p", 1e-12),
+    ("f", 1e-15),
+    ("a", 1e-18),
+    ("z", 1e-21),
+    ("y", 1e-24),
+];
+
+const MAX_UNIT_LEN: usize = 64;
+const MAX_EXPONENT: i32 = 9;
+
+/// One reading: a number in a UCUM unit
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify_next::Tsify))]
+pub(crate) struct Measurement {
+    pub(crate) value: f64,
+    pub(crate) unit: String,
+    /// What the reading measures (`distance`, `temperature`, `mass`, ...);
+    /// the unit must measure it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) quantity: Option<String>,
+}
+
+/// A parsed unit: SI value = value * factor + offset
+#[derive(Clone, Copy)]
+struct Unit {
+    dimension: Dimension,
+    factor: f64,
+    offset: f64,
+}
+
+impl Unit {
+    const ONE: Unit = Unit { dimension: DIMENSIONLESS, factor: 1.0, offset: 0.0 };
+
+    fn pow(self, exponent: i32) -> Unit {
+        Unit {
+            dimension: self.dimension.map(|d| d * exponent),
+            factor: powi(self.factor, exponent),
+            offset: 0.0,
+        }
+    }
+
+    fn times(self, other: Unit) -> Unit {
+        let mut dimension = self.dimension;
+        for (d, o) in dimension.iter_mut().zip(other.dimension) {
+            *d += o;
+        }
+        Unit { dimension, factor: self.factor * other.factor, offset: 0.0 }
+    }
+
+    fn si(self, value: f64) -> f64 {
+        value * self.factor + self.offset
+    }
+
+    fn value_of(self, value: f64) -> f64 {
+        (value - self.offset) / self.factor
+    }
+
+    fn quantity(self) -> Option<&'static str> {
+        QUANTITIES.iter().find(|(name, d)| *d == self.dimension && *name != "distance").map(|(name, _)| *name)
+    }
+}
+
+fn atom(code: &str) -> Option<Unit> {
+    let unit = |&(_, dimension, factor, _): &(&str, Dimension, f64, bool)| Unit { dimension, factor, offset: 0.0 };
+    if let Some(found) = ATOMS.iter().find(|(c, ..)| *c == code) {
+        return Some(unit(found));
+    }
+    PREFIXES.iter().find_map(|(prefix, scale)| {
+        let rest = code.strip_prefix(prefix)?;
+        let found = ATOMS.iter().find(|(c, _, _, metric)| *metric && *c == rest)?;
+        let mut parsed = unit(found);
+        parsed.factor *= scale;
+        Some(parsed)
+    })
+}
+
+/// Recursive descent over a UCUM term
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    fn term(&mut self) -> Result<Unit, String> {
+        let mut unit = if self.peek() == Some(b'/') { Unit::ONE } else { self.component()? };
+        while let Some(op @ (b'.' | b'/')) = self.peek() {
+            self.pos += 1;
+            let next = self.component()?;
+            unit = unit.times(if op == b'/' { next.pow(-1) } else { next });
+        }
+        Ok(unit)
+    }
+
+    fn component(&mut self) -> Result<Unit, String> {
+        match self.peek() {
+            Some(b'(') => {
+                self.pos += 1;
+                let inner = self.term()?;
+                if self.peek() != Some(b')') {
+                    return Err(format!("unclosed parenthesis at {}", self.pos));
+                }
+                self.pos += 1;
+                Ok(inner.pow(self.exponent()?))
+            }
+            Some(b'{') => {
+                self.annotation()?;
+                Ok(Unit::ONE)
+            }
+            _ => {
+                let unit = self.simple()?;
+                if self.peek() == Some(b'{') {
+                    self.annotation()?;
+                }
+                Ok(unit)
+            }
+        }
+    }
+
+    fn annotation(&mut self) -> Result<(), String> {
+        let rest = &self.input[self.pos + 1..];
+        let end = rest.find('}').ok_or_else(|| format!("unclosed annotation at {}", self.pos))?;
+        if !rest[..end].bytes().all(|b| (b' '..=b'~').contains(&b) && b != b'{') {
+            return Err("annotations must be printable ASCII".to_string());
+        }
+        self.pos += end + 2;
+        Ok(())
+    }
+
+    /// An atom with optional prefix and exponent, or an integer factor
+    fn simple(&mut self) -> Result<Unit, String> {
+        let start = self.pos;
+        let mut depth = 0;
+        while let Some(b) = self.peek() {
+            match b {
+                b'[' => depth += 1,
+                b']' => depth -= 1,
+                b'.' | b'/' | b'(' | b')' | b'{' | b'}' if depth == 0 => break,
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        let text = &self.input[start..self.pos];
+        if text.is_empty() {
+            return Err(format!("expected a unit at {}", start));
+        }
+        if let Some((base, power)) = text.split_once(['*', '^']) {
+            let base: f64 = base.parse().map_err(|_| format!("{} is not a power of ten", text))?;
+            let power: i32 = power.parse().map_err(|_| format!("{} is not a power of ten", text))?;
+            if base != 10.0 || power.abs() > 24 {
+                return Err(format!("{} is not a power of ten", text));
+            }
+            return Ok(Unit { factor: powi(10.0, power), ..Unit::ONE });
+        }
+        if text.bytes().all(|b| b.is_ascii_digit()) {
+            let factor: f64 = text.parse().map_err(|_| format!("{} is not a number", text))?;
+            return Ok(Unit { factor, ..Unit::ONE });
+        }
+        let digits = text.trim_end_matches(|c: char| c.is_ascii_digit());
+        let code = digits.strip_suffix(['+', '-']).unwrap_or(digits);
+        if code.is_empty() {
+            return Err(format!("{} has no unit", text));
+        }
+        let exponent = if code.len() == text.len() {
+            1
+        } else {
+            text[code.len()..].parse::<i32>().map_err(|_| format!("bad exponent in {}", text))?
+        };
+        if exponent.abs() > MAX_EXPONENT {
+            return Err(format!("exponent {} is too large", exponent));
+        }
+        if OFFSET_UNITS.iter().any(|(c, ..)| *c == code) {
+            return Err(format!("{} cannot be combined with other units", code));
+        }
+        let unit = atom(code).ok_or_else(|| format!("{} is not a known unit", code))?;
+        Ok(unit.pow(exponent))
+    }
+
+    fn exponent(&mut self) -> Result<i32, String> {
+        let start = self.pos;
+        if matches!(self.peek(), Some(b'+' | b'-')) {
+            self.pos += 1;
+        }
+        while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        match &self.input[start..self.pos] {
+            "" => Ok(1),
+            text => match text.parse::<i32>() {
+                Ok(e) if e.abs() <= MAX_EXPONENT => Ok(e),
+                _ => Err(format!("bad exponent {}", text)),
+            },
+        }
+    }
+}
+
+/// Parse a UCUM unit code, or say why it is not one
+fn parse(code: &str) -> Result<Unit, String> {
+    if code.is_empty() || code.len() > MAX_UNIT_LEN || !code.bytes().all(|b| (b'!'..=b'~').contains(&b)) {
+        return Err(format!("units are 1 to {} printable ASCII characters without spaces", MAX_UNIT_LEN));
+    }
+    let bare = code.split_once('{').map_or(code, |(bare, _)| bare);
+    if let Some(&(_, factor, offset)) = OFFSET_UNITS.iter().find(|(c, ..)| *c == bare) {
+        let mut parser = Parser { input: code, pos: bare.len() };
+        if parser.peek() == Some(b'{') {
+            parser.annotation()?;
+        }
+        if parser.pos != code.len() {
+            return Err(format!("{} cannot be combined with other units", bare));
+        }
+        return Ok(Unit { dimension: TEMPERATURE, factor, offset });
+    }
+    let mut parser = Parser { input: code, pos: 0 };
+    let unit = parser.term()?;
+    if parser.pos != code.len() {
+        return Err(format!("unexpected {} at {}", &code[parser.pos..], parser.pos));
+    }
+    Ok(unit)
+}
+
+fn quantity_dimension(name: &str) -> Option<Dimension> {
+    QUANTITIES.iter().find(|(n, _)| *n == name).map(|&(_, d)| d)
+}
+
+/// Validation errors for an experience's measurements
+pub(crate) fn validate_measurements(measurements: &BTreeMap<String, Measurement>) -> Vec<Issue> {
+    let mut errors = Vec::new();
+    for (name, m) in measurements {
+        let unit = match parse(&m.unit) {
+            Ok(unit) => unit,
+            Err(reason) => {
+                errors.push(Issue::new("measurements.unit.invalid").with("name", name).with("unit", &m.unit).with("reason", reason));
+                continue;
+            }
+        };
+        if let Some(ref quantity) = m.quantity {
+            match quantity_dimension(quantity) {
+                None => errors.push(Issue::new("measurements.quantity.unknown").with("name", name).with("quantity", quantity)),
+                Some(dimension) if dimension != unit.dimension => errors.push(
+                    Issue::new("measurements.quantity.mismatch")
+                        .with("name", name)
+                        .with("unit", &m.unit)
+                        .with("quantity", quantity),
+                ),
+                Some(_) => {}
+            }
+        }
+        if unit.dimension == TEMPERATURE && unit.si(m.value) < 0.0 {
+            errors.push(Issue::new("measurements.value.range").with("name", name));
+        }
+    }
+    errors
+}
+
+#[derive(Serialize)]
+struct UnitInfo<'a> {
+    unit: &'a str,
+    /// Named quantity, or null for other combinations
+    quantity: Option<&'static str>,
+    /// Exponents of m, kg, s, K and rad
+    dimension: Dimension,
+    factor: f64,
+    offset: f64,
+}
+
+/// Parse and describe a UCUM unit code
+/// Returns `{unit, quantity, dimension, factor, offset}` as JSON, where a
+/// value in `unit` is `value * factor + offset` in SI units and
+/// `dimension` holds the exponents of m, kg, s, K and rad
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn parse_unit(unit: &str) -> Result<String, Error> {
+    let parsed = parse(unit).map_err(|reason| Error::invalid(reason).with("unit", unit))?;
+    to_json(&UnitInfo {
+        unit,
+        quantity: parsed.quantity(),
+        dimension: parsed.dimension,
+        factor: parsed.factor,
+        offset: parsed.offset,
+    })
+}
+
+fn convert(value: f64, from: &str, to: &str) -> Result<f64, Error> {
+    let source = parse(from).map_err(|reason| Error::invalid(reason).with("unit", from))?;
+    let target = parse(to).map_err(|reason| Error::invalid(reason).with("unit", to))?;
+    if source.dimension != target.dimension {
+        return Err(Error::invalid(format!("cannot convert {} to {}", from, to)).with("from", from).with("to", to));
+    }
+    Ok(target.value_of(source.si(value)))
+}
+
+/// Convert a value between two UCUM units of the same quantity
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn convert_unit(value: f64, from_unit: &str, to_unit: &str) -> Result<f64, Error> {
+    convert(value, from_unit, to_unit)
+}
+
+#[derive(Serialize)]
+struct InvalidMeasurement {
+    index: usize,
+    id: Option<String>,
+    name: String,
+    reason: String,
+}
+
+#[derive(Serialize)]
+struct Normalized<'a> {
+    converted: usize,
+    /// Valid readings of a quantity without a target unit, left as recorded
+    unchanged: usize,
+    invalid: Vec<InvalidMeasurement>,
+    experiences: &'a [Value],
+}
+
+/// Target unit per named quantity: the defaults overridden by `targets`
+fn targets(overrides: HashMap<String, String>) -> Result<HashMap<String, (String, Unit)>, Error> {
+    let mut targets: HashMap<String, String> = DEFAULT_TARGETS.iter().map(|(q, u)| (q.to_string(), u.to_string())).collect();
+    for (quantity, unit) in overrides {
+        let quantity = if quantity == "distance" { "length".to_string() } else { quantity };
+        targets.insert(quantity, unit);
+    }
+    targets
+        .into_iter()
+        .map(|(quantity, code)| {
+            let dimension = quantity_dimension(&quantity)
+                .ok_or_else(|| Error::invalid(format!("{} is not a known quantity", quantity)).with("argument", "targets_json"))?;
+            let unit = parse(&code).map_err(|reason| Error::invalid(reason).with("unit", &*code))?;
+            if unit.dimension != dimension {
+                return Err(Error::invalid(format!("{} does not measure {}", code, quantity)).with("argument", "targets_json"));
+            }
+            Ok((quantity, (code, unit)))
+        })
+        .collect()
+}
+
+/// Why a raw measurement entry is not a valid reading
+fn check(entry: &Value) -> Result<(f64, Unit), String> {
+    let value = entry.get("value").and_then(Value::as_f64).ok_or("value must be a number")?;
+    let code = entry.get("unit").and_then(Value::as_str).ok_or("unit must be a string")?;
+    let unit = parse(code)?;
+    if let Some(quantity) = entry.get("quantity") {
+        let quantity = quantity.as_str().ok_or("quantity must be a string")?;
+        let dimension = quantity_dimension(quantity).ok_or_else(|| format!("{} is not a known quantity", quantity))?;
+        if dimension != unit.dimension {
+            return Err(format!("{} does not measure {}", code, quantity));
+        }
+    }
+    if unit.dimension == TEMPERATURE && unit.si(value) < 0.0 {
+        return Err("temperature is below absolute zero".to_string());
+    }
+    Ok((value, unit))
+}
+
+/// Convert every recorded measurement to one unit per quantity
+/// `targets_json` maps quantity names to UCUM units, overriding the
+/// defaults (m, m2, L, kg, s, Cel and m/s); empty for the defaults
+/// Returns `{converted, unchanged, invalid: [{index, id, name, reason}],
+/// experiences}` as JSON, with invalid readings left as recorded
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn normalize_measurements(experiences_json: &str, targets_json: &str) -> Result<String, Error> {
+    let mut experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    let overrides: HashMap<String, String> = if targets_json.trim().is_empty() {
+        HashMap::new()
+    } else {
+        from_json(targets_json, "targets_json")?
+    };
+    let targets = targets(overrides)?;
+
+    let (mut converted, mut unchanged) = (0, 0);
+    let mut invalid = Vec::new();
+    for (index, exp) in experiences.iter_mut().enumerate() {
+        let id = exp.get("id").and_then(Value::as_str).map(str::to_string);
+        let Some(measurements) = exp.pointer_mut("/experience/measurements").and_then(Value::as_object_mut) else {
+            continue;
+        };
+        for (name, entry) in measurements.iter_mut() {
+            let (value, unit) = match check(entry) {
+                Ok(reading) => reading,
+                Err(reason) => {
+                    invalid.push(InvalidMeasurement { index, id: id.clone(), name: name.clone(), reason });
+                    continue;
+                }
+            };
+            let Some((code, target)) = unit.quantity().and_then(|q| targets.get(q)) else {
+                unchanged += 1;
+                continue;
+            };
+            entry["value"] = Value::from(target.value_of(unit.si(value)));
+            entry["unit"] = Value::from(code.as_str());
+            converted += 1;
+        }
+    }
+
+    to_json(&Normalized {
+        converted,
+        unchanged,
+        invalid,
+        experiences: &experiences,
+    })
+}
@@ -0,0 +1,140 @@
+//! Upload batching planner
+//!
+//! Packs pending sync operations into request-sized batches. Planning is
+//! deterministic: operations keep their queue order within each batch kind,
+//! and record batches are always scheduled before attachment batches so the
+//! server has seen a record before any attachment that refers to it.
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+
+#[derive(Deserialize)]
+struct PendingOp {
+    id: String,
+    size_bytes: u64,
+    #[serde(default)]
+    attachment: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct UploadConstraints {
+    max_bytes: u64,
+    max_records: usize,
+    /// Keep attachments out of record batches
+    separate_attachments: bool,
+    /// Cap on attachments per batch (attachments are usually the large,
+    /// failure-prone part of an upload)
+    max_attachments_per_batch: usize,
+}
+
+impl Default for UploadConstraints {
+    fn default() -> Self {
+        Self {
+            max_bytes: 1024 * 1024,
+            max_records: 500,
+            separate_attachments: true,
+            max_attachments_per_batch: 1,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum BatchKind {
+    Records,
+    Attachments,
+    Mixed,
+}
+
+#[derive(Serialize)]
+struct Batch {
+    kind: BatchKind,
+    op_ids: Vec<String>,
+    bytes: u64,
+    /// A single operation larger than `max_bytes`; needs chunked transfer
+    oversized: bool,
+}
+
+#[derive(Serialize)]
+struct UploadPlan {
+    batches: Vec<Batch>,
+    total_bytes: u64,
+}
+
+fn pack<'a>(ops: impl Iterator<Item = &'a PendingOp>, kind: BatchKind, c: &UploadConstraints, out: &mut Vec<Batch>) {
+    let max_records = c.max_records.max(1);
+    let max_attachments = c.max_attachments_per_batch.max(1);
+    let mut current: Option<(Batch, usize)> = None;
+
+    for op in ops {
+        if op.size_bytes > c.max_bytes {
+            out.push(Batch {
+                kind,
+                op_ids: vec![op.id.clone()],
+                bytes: op.size_bytes,
+                oversized: true,
+            });
+            continue;
+        }
+
+        if let Some((batch, attachments)) = &current {
+            let full = batch.op_ids.len() >= max_records
+                || batch.bytes + op.size_bytes > c.max_bytes
+                || (op.attachment && *attachments >= max_attachments);
+            if full {
+                out.extend(current.take().map(|(batch, _)| batch));
+            }
+        }
+
+        let (batch, attachments) = current.get_or_insert_with(|| {
+            (
+                Batch {
+                    kind,
+                    op_ids: Vec::new(),
+                    bytes: 0,
+                    oversized: false,
+                },
+                0,
+            )
+        });
+        batch.op_ids.push(op.id.clone());
+        batch.bytes += op.size_bytes;
+        *attachments += usize::from(op.attachment);
+    }
+
+    out.extend(current.map(|(batch, _)| batch));
+}
+
+fn plan(ops: &[PendingOp], c: &UploadConstraints) -> UploadPlan {
+    let mut batches = Vec::new();
+    if c.separate_attachments {
+        pack(ops.iter().filter(|op| !op.attachment), BatchKind::Records, c, &mut batches);
+        pack(ops.iter().filter(|op| op.attachment), BatchKind::Attachments, c, &mut batches);
+    } else {
+        pack(ops.iter(), BatchKind::Mixed, c, &mut batches);
+    }
+
+    UploadPlan {
+        total_bytes: ops.iter().map(|op| op.size_bytes).sum(),
+        batches,
+    }
+}
+
+/// Plan upload batches for `[{id, size_bytes, attachment}]` pending ops
+/// `constraints_json` may set `max_bytes`, `max_records`,
+/// `separate_attachments` and `max_attachments_per_batch`
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn plan_upload(pending_ops_json: &str, constraints_json: &str) -> Result<String, Error> {
+    let ops: Vec<PendingOp> = from_json(pending_ops_json, "pending_ops_json")?;
+    let constraints: UploadConstraints = if constraints_json.trim().is_empty() {
+        UploadConstraints::default()
+    } else {
+        from_json(constraints_json, "constraints_json")?
+    };
+
+    to_json(&plan(&ops, &constraints))
+}
@@ -0,0 +1,218 @@
+//! Quota and usage accounting
+//!
+//! Counts records and attachment bytes per learner and per tenant so clients
+//! can enforce fair-use limits before syncing. Attachment sizes come from the
+//! optional `size_bytes` field on `experience.artifacts[]`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::error::{from_json, to_json, Error};
+use crate::export::lookup;
+use crate::tenancy::TenantScope;
+use crate::time::{format_timestamp, parse_timestamp, MS_PER_DAY};
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct UsageLimits {
+    max_records_per_learner: Option<u64>,
+    max_bytes_per_learner: Option<u64>,
+    max_records_per_tenant: Option<u64>,
+    max_bytes_per_tenant: Option<u64>,
+    /// Fraction of a limit at which `near_*` flags are raised
+    warn_ratio: f64,
+    /// Length of the growth comparison window in days
+    window_days: u32,
+    /// Reference instant for growth windows; defaults to the newest record
+    as_of: Option<String>,
+}
+
+impl Default for UsageLimits {
+    fn default() -> Self {
+        Self {
+            max_records_per_learner: None,
+            max_bytes_per_learner: None,
+            max_records_per_tenant: None,
+            max_bytes_per_tenant: None,
+            warn_ratio: 0.8,
+            window_days: 7,
+            as_of: None,
+        }
+    }
+}
+
+#[derive(Default, Serialize)]
+struct UsageEntry {
+    records: u64,
+    attachment_bytes: u64,
+    records_current_window: u64,
+    records_previous_window: u64,
+    /// Relative change between the previous and current window; `null` when
+    /// the previous window is empty
+    growth_rate: Option<f64>,
+    flags: Vec<&'static str>,
+}
+
+impl UsageEntry {
+    fn finish(&mut self, max_records: Option<u64>, max_bytes: Option<u64>, warn_ratio: f64) {
+        if self.records_previous_window > 0 {
+            self.growth_rate = Some(
+                (self.records_current_window as f64 - self.records_previous_window as f64)
+                    / self.records_previous_window as f64,
+            );
+        }
+        for (used, limit, near, over) in [
+            (self.records, max_records, "near_record_limit", "over_record_limit"),
+            (self.attachment_bytes, max_bytes, "near_byte_limit", "over_byte_limit"),
+        ] {
+            if let Some(limit) = limit {
+                if used > limit {
+                    self.flags.push(over);
+                } else if used as f64 >= limit as f64 * warn_ratio {
+                    self.flags.push(near);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct LearnerUsage {
+    learner: String,
+    tenant: Option<String>,
+    #[serde(flatten)]
+    usage: UsageEntry,
+}
+
+#[derive(Serialize)]
+struct TenantUsage {
+    tenant: Option<String>,
+    learners: u64,
+    #[serde(flatten)]
+    usage: UsageEntry,
+}
+
+#[derive(Serialize)]
+struct UsageReport {
+    as_of: Option<String>,
+    window_days: u32,
+    learners: Vec<LearnerUsage>,
+    tenants: Vec<TenantUsage>,
+}
+
+fn attachment_bytes(exp: &Value) -> u64 {
+    lookup(exp, "experience.artifacts")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|artifact| artifact.get("size_bytes").and_then(Value::as_u64))
+        .sum()
+}
+
+fn build_report(experiences: &[Value], limits: &UsageLimits) -> Result<UsageReport, String> {
+    let as_of = match limits.as_of {
+        Some(ref s) => Some(parse_timestamp(s).ok_or_else(|| format!("invalid as_of: {}", s))?),
+        None => experiences
+            .iter()
+            .filter_map(|exp| exp.get("timestamp").and_then(Value::as_str).and_then(parse_timestamp))
+            .max(),
+    };
+    let window_ms = i64::from(limits.window_days.max(1)) * MS_PER_DAY;
+
+    let mut learners: BTreeMap<(Option<String>, String), UsageEntry> = BTreeMap::new();
+    let mut tenants: BTreeMap<Option<String>, UsageEntry> = BTreeMap::new();
+
+    for exp in experiences {
+        let learner = lookup(exp, "learner.id").and_then(Value::as_str).unwrap_or_default();
+        let tenant = exp.get("tenant").and_then(Value::as_str).map(str::to_string);
+        let bytes = attachment_bytes(exp);
+        let ts = exp.get("timestamp").and_then(Value::as_str).and_then(parse_timestamp);
+        let (current, previous) = match (ts, as_of) {
+            (Some(ts), Some(as_of)) if ts <= as_of => {
+                let age = as_of - ts;
+                (age < window_ms, (window_ms..2 * window_ms).contains(&age))
+            }
+            _ => (false, false),
+        };
+
+        for entry in [
+            learners.entry((tenant.clone(), learner.to_string())).or_default(),
+            tenants.entry(tenant).or_default(),
+        ] {
+            entry.records += 1;
+            entry.attachment_bytes += bytes;
+            entry.records_current_window += u64::from(current);
+            entry.records_previous_window += u64::from(previous);
+        }
+    }
+
+    let mut learner_counts: BTreeMap<Option<String>, u64> = BTreeMap::new();
+    let learners: Vec<LearnerUsage> = learners
+        .into_iter()
+        .map(|((tenant, learner), mut usage)| {
+            usage.finish(
+                limits.max_records_per_learner,
+                limits.max_bytes_per_learner,
+                limits.warn_ratio,
+            );
+            *learner_counts.entry(tenant.clone()).or_default() += 1;
+            LearnerUsage { learner, tenant, usage }
+        })
+        .collect();
+
+    let tenants = tenants
+        .into_iter()
+        .map(|(tenant, mut usage)| {
+            usage.finish(
+                limits.max_records_per_tenant,
+                limits.max_bytes_per_tenant,
+                limits.warn_ratio,
+            );
+            TenantUsage {
+                learners: learner_counts.get(&tenant).copied().unwrap_or(0),
+                tenant,
+                usage,
+            }
+        })
+        .collect();
+
+    Ok(UsageReport {
+        as_of: as_of.map(format_timestamp),
+        window_days: limits.window_days.max(1),
+        learners,
+        tenants,
+    })
+}
+
+/// Usage accounting per learner and tenant
+/// `limits_json` may set `max_records_per_learner`, `max_bytes_per_learner`,
+/// `max_records_per_tenant`, `max_bytes_per_tenant`, `warn_ratio`,
+/// `window_days` and `as_of`; pass an empty string for no limits
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn usage_report(experiences_json: &str, limits_json: &str) -> Result<String, Error> {
+    let experiences: Vec<Value> = from_json(experiences_json, "experiences_json")?;
+    report(&experiences, limits_json)
+}
+
+fn report(experiences: &[Value], limits_json: &str) -> Result<String, Error> {
+    let limits: UsageLimits = if limits_json.trim().is_empty() {
+        UsageLimits::default()
+    } else {
+        from_json(limits_json, "limits_json")?
+    };
+
+    let report = build_report(experiences, &limits).map_err(Error::invalid)?;
+    to_json(&report)
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl TenantScope {
+    /// Tenant-scoped `usage_report`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn usage_report(&self, experiences_json: &str, limits_json: &str) -> Result<String, Error> {
+        report(&self.parse_values(experiences_json)?, limits_json)
+    }
+}
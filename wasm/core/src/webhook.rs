@@ -0,0 +1,190 @@
+//! Signed webhook envelopes
+//!
+//! Both the sync client and the server build and check webhook deliveries
+//! through this module so they agree on one format:
+//!
+//! - body: canonical JSON `{version, id, event, created_at, data}`
+//! - `webhook-id`: random 128-bit hex id, also used for replay protection
+//! - `webhook-timestamp`: Unix seconds at signing time
+//! - `webhook-signature`: `v1=<hex HMAC-SHA256>` over `"{id}.{timestamp}.{body}"`;
+//!   several space-separated signatures may be present during secret rotation
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::crypto::{canonical_json, from_hex, hmac_sha256, random_bytes, to_hex, verify_hmac_sha256};
+use crate::error::{from_json, to_json, Error};
+use crate::time::{format_timestamp, now_ms, MS_PER_SECOND};
+
+/// Envelope format version
+const WEBHOOK_VERSION: u32 = 1;
+const SIGNATURE_SCHEME: &str = "v1=";
+
+#[derive(Serialize)]
+struct WebhookPayload {
+    body: String,
+    headers: BTreeMap<&'static str, String>,
+}
+
+#[derive(Deserialize)]
+struct WebhookHeaders {
+    #[serde(rename = "webhook-id")]
+    id: String,
+    #[serde(rename = "webhook-timestamp")]
+    timestamp: String,
+    #[serde(rename = "webhook-signature")]
+    signature: String,
+}
+
+fn validate_event_name(event: &str) -> Result<(), String> {
+    if event.is_empty()
+        || !event
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || matches!(b, b'.' | b'_' | b'-'))
+    {
+        return Err("event must be non-empty lowercase letters, digits, '.', '_' or '-'".to_string());
+    }
+    Ok(())
+}
+
+fn signed_content(id: &str, timestamp: &str, body: &str) -> String {
+    format!("{}.{}.{}", id, timestamp, body)
+}
+
+fn build_payload(event: &str, data: Value, secret: &[u8], now: i64, id: String) -> Result<WebhookPayload, String> {
+    validate_event_name(event)?;
+
+    let mut envelope = serde_json::Map::new();
+    envelope.insert("version".to_string(), Value::from(WEBHOOK_VERSION));
+    envelope.insert("id".to_string(), Value::from(id.clone()));
+    envelope.insert("event".to_string(), Value::from(event));
+    envelope.insert("created_at".to_string(), Value::from(format_timestamp(now)));
+    envelope.insert("data".to_string(), data);
+    let body = canonical_json(&Value::Object(envelope));
+
+    let timestamp = now.div_euclid(MS_PER_SECOND).to_string();
+    let signature = hmac_sha256(secret, signed_content(&id, &timestamp, &body).as_bytes());
+
+    let mut headers = BTreeMap::new();
+    headers.insert("content-type", "application/json".to_string());
+    headers.insert("webhook-id", id);
+    headers.insert("webhook-timestamp", timestamp);
+    headers.insert("webhook-signature", format!("{}{}", SIGNATURE_SCHEME, to_hex(&signature)));
+
+    Ok(WebhookPayload { body, headers })
+}
+
+fn verify_payload(body: &str, headers: &WebhookHeaders, secret: &[u8], tolerance_secs: u32, now: i64) -> bool {
+    let Ok(timestamp) = headers.timestamp.parse::<i64>() else {
+        return false;
+    };
+    if (now.div_euclid(MS_PER_SECOND) - timestamp).abs() > i64::from(tolerance_secs) {
+        return false;
+    }
+
+    let content = signed_content(&headers.id, &headers.timestamp, body);
+    headers
+        .signature
+        .split_whitespace()
+        .filter_map(|sig| sig.strip_prefix(SIGNATURE_SCHEME))
+        .filter_map(from_hex)
+        .any(|tag| verify_hmac_sha256(secret, content.as_bytes(), &tag))
+}
+
+/// An empty secret would sign with a key anyone can reproduce
+fn check_secret(secret: &str) -> Result<(), Error> {
+    if secret.is_empty() {
+        return Err(Error::invalid("webhook secret must not be empty"));
+    }
+    Ok(())
+}
+
+/// Build a signed webhook delivery for `event` carrying `data_json`
+/// Returns `{body, headers}` as JSON; send `body` verbatim
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn build_webhook_payload(event: &str, data_json: &str, secret: &str) -> Result<String, Error> {
+    check_secret(secret)?;
+    let data: Value = from_json(data_json, "data_json")?;
+    let id = to_hex(&random_bytes::<16>().map_err(Error::host)?);
+
+    let payload = build_payload(event, data, secret.as_bytes(), now_ms(), id).map_err(Error::invalid)?;
+    to_json(&payload)
+}
+
+/// Check a received webhook against its headers (JSON object of the three
+/// `webhook-*` headers); rejects signatures older or newer than
+/// `tolerance_secs`
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn verify_webhook_payload(
+    body: &str,
+    headers_json: &str,
+    secret: &str,
+    tolerance_secs: u32,
+) -> Result<bool, Error> {
+    check_secret(secret)?;
+    let headers: WebhookHeaders = from_json(headers_json, "headers_json")?;
+    Ok(verify_payload(body, &headers, secret.as_bytes(), tolerance_secs, now_ms()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const NOW: i64 = 1_714_557_600_000;
+    const SECRET: &[u8] = b"whsec-test";
+
+    fn delivery() -> (String, WebhookHeaders) {
+        let payload = build_payload("experience.created", json!({"id": "a"}), SECRET, NOW, "00ff".repeat(8)).unwrap();
+        let headers = serde_json::from_value(json!(payload.headers)).unwrap();
+        (payload.body, headers)
+    }
+
+    #[test]
+    fn accepts_an_untouched_delivery() {
+        let (body, headers) = delivery();
+        assert!(verify_payload(&body, &headers, SECRET, 300, NOW));
+        assert!(verify_payload(&body, &headers, SECRET, 300, NOW + 300 * MS_PER_SECOND));
+    }
+
+    #[test]
+    fn rejects_a_tampered_body() {
+        let (body, headers) = delivery();
+        let tampered = body.replace("\"a\"", "\"b\"");
+        assert_ne!(tampered, body);
+        assert!(!verify_payload(&tampered, &headers, SECRET, 300, NOW));
+        assert!(!verify_payload(&format!("{} ", body), &headers, SECRET, 300, NOW));
+    }
+
+    #[test]
+    fn rejects_a_wrong_secret_unless_another_signature_matches() {
+        let (body, mut headers) = delivery();
+        assert!(!verify_payload(&body, &headers, b"whsec-other", 300, NOW));
+
+        // During rotation a second signature under the new secret is accepted
+        let content = signed_content(&headers.id, &headers.timestamp, &body);
+        let rotated = to_hex(&hmac_sha256(b"whsec-other", content.as_bytes()));
+        headers.signature = format!("{} v1={}", headers.signature, rotated);
+        assert!(verify_payload(&body, &headers, b"whsec-other", 300, NOW));
+        assert!(verify_payload(&body, &headers, SECRET, 300, NOW));
+        assert!(!verify_payload(&body, &headers, b"whsec-third", 300, NOW));
+    }
+
+    #[test]
+    fn rejects_timestamps_outside_the_tolerance() {
+        let (body, mut headers) = delivery();
+        let late = NOW + 301 * MS_PER_SECOND;
+        let early = NOW - 301 * MS_PER_SECOND;
+        assert!(!verify_payload(&body, &headers, SECRET, 300, late));
+        assert!(!verify_payload(&body, &headers, SECRET, 300, early));
+
+        // Moving the header into the window breaks the signature instead
+        headers.timestamp = (late / MS_PER_SECOND).to_string();
+        assert!(!verify_payload(&body, &headers, SECRET, 300, late));
+        headers.timestamp = "soon".to_string();
+        assert!(!verify_payload(&body, &headers, SECRET, u32::MAX, NOW));
+    }
+}
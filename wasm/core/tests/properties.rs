@@ -0,0 +1,845 @@
+//! Property tests for the parser-facing exports: serialize → parse →
+//! serialize round trips, agreement between the JSON, binary and streaming
+//! entry points, sketch merges matching a single pass, and no panics on
+//! arbitrary input.
+
+use proptest::collection::{btree_map, vec};
+use proptest::option;
+use proptest::prelude::*;
+use serde_json::{json, Value};
+
+use ubicity_core::archive::{archive, read_archive};
+use ubicity_core::array_stream::ArrayStream;
+use ubicity_core::clusters::PointClusterIndex;
+use ubicity_core::crs::{reproject_geojson, transform_coordinates};
+use ubicity_core::darwin_core::to_darwin_core;
+use ubicity_core::export::export_experiences_csv;
+use ubicity_core::formats::{generate_domain_network_cbor, generate_domain_network_cbor_with_options};
+use ubicity_core::gazetteer::Gazetteer;
+use ubicity_core::graph_formats::{generate_domain_network_cytoscape, generate_domain_network_d3};
+use ubicity_core::gzip::{
+    compress_gzip, decompress_gzip, generate_domain_network_cytoscape_gzip, generate_domain_network_d3_gzip,
+    to_darwin_core_gzip, to_triples_gzip,
+};
+use ubicity_core::identity::canonical_learner_id;
+use ubicity_core::map_matching::map_match;
+use ubicity_core::mvt::to_mvt;
+use ubicity_core::parquet::export_parquet;
+use ubicity_core::privacy::{pseudonymize, scan};
+use ubicity_core::protocol::{encode_frame, FrameDecoder};
+use ubicity_core::pruning::generate_domain_network_with_options;
+use ubicity_core::search::SearchIndex;
+use ubicity_core::shapefile::read_shapefile;
+use ubicity_core::signing::{public_key_for, sign_experience, verify_experience};
+use ubicity_core::similarity::{similarity_matrix, similarity_matrix_typed};
+use ubicity_core::sketches::{CountMinSketch, HyperLogLog, TDigest};
+use ubicity_core::stream::NetworkStreamBuilder;
+use ubicity_core::sync::{apply_changeset, diff_logs, merge};
+use ubicity_core::triples::{to_ntriples, to_triples};
+use ubicity_core::units::{convert_unit, parse_unit};
+use ubicity_core::{generate_domain_network, ExperienceValidator};
+
+fn timestamp() -> impl Strategy<Value = String> {
+    (2000u32..2100, 1u32..=12, 1u32..=28, 0u32..24, 0u32..60, 0u32..60).prop_map(|(y, mo, d, h, mi, s)| {
+        format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", y, mo, d, h, mi, s)
+    })
+}
+
+/// Experiences with arbitrary (including multi-byte) text in every string
+/// field
+fn experience_with_id(id: String) -> impl Strategy<Value = Value> {
+    (
+        timestamp(),
+        "\\PC{1,16}",
+        "\\PC{1,24}",
+        option::of((-90.0f64..=90.0, -180.0f64..=180.0)),
+        "\\PC{1,12}",
+        "\\PC{1,64}",
+        vec("\\PC{1,12}", 0..4),
+    )
+        .prop_map(move |(timestamp, learner, place, coordinates, kind, description, domains)| {
+            let mut location = json!({"name": place});
+            if let Some((latitude, longitude)) = coordinates {
+                location["coordinates"] = json!({"latitude": latitude, "longitude": longitude});
+            }
+            json!({
+                "id": id,
+                "timestamp": timestamp,
+                "learner": {"id": learner},
+                "context": {"location": location},
+                "experience": {"type": kind, "description": description, "domains": domains},
+            })
+        })
+}
+
+fn experience() -> impl Strategy<Value = Value> {
+    "[a-z0-9-]{1,12}".prop_flat_map(experience_with_id)
+}
+
+/// Logs with unique ids
+fn log() -> impl Strategy<Value = Vec<Value>> {
+    btree_map("[a-z0-9]{1,6}", Just(()), 0..8).prop_flat_map(|ids| {
+        ids.into_keys().map(experience_with_id).collect::<Vec<_>>()
+    })
+}
+
+/// Logs drawing ids from a small set, so replicas hold competing versions
+/// of the same records
+fn overlapping_log() -> impl Strategy<Value = Vec<Value>> {
+    btree_map("[a-d]", Just(()), 0..4).prop_flat_map(|ids| {
+        ids.into_keys().map(experience_with_id).collect::<Vec<_>>()
+    })
+}
+
+/// Value read back with the Thrift compact protocol, as Parquet metadata
+/// is written
+#[derive(Debug, PartialEq)]
+enum Thrift {
+    Int(i64),
+    Double(f64),
+    Bytes(Vec<u8>),
+    List(Vec<Thrift>),
+    Struct(std::collections::BTreeMap<i16, Thrift>),
+}
+
+fn varint(bytes: &[u8], pos: &mut usize) -> u64 {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = bytes[*pos];
+        *pos += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    value
+}
+
+fn zigzag(bytes: &[u8], pos: &mut usize) -> i64 {
+    let v = varint(bytes, pos);
+    (v >> 1) as i64 ^ -((v & 1) as i64)
+}
+
+fn thrift_value(bytes: &[u8], pos: &mut usize, kind: u8) -> Thrift {
+    match kind {
+        1 => Thrift::Int(1),
+        2 => Thrift::Int(0),
+        3 => {
+            *pos += 1;
+            Thrift::Int(i64::from(bytes[*pos - 1] as i8))
+        }
+        4..=6 => Thrift::Int(zigzag(bytes, pos)),
+        7 => {
+            *pos += 8;
+            Thrift::Double(f64::from_le_bytes(bytes[*pos - 8..*pos].try_into().unwrap()))
+        }
+        8 => {
+            let len = varint(bytes, pos) as usize;
+            *pos += len;
+            Thrift::Bytes(bytes[*pos - len..*pos].to_vec())
+        }
+        9 | 10 => {
+            let header = bytes[*pos];
+            *pos += 1;
+            let len = if header >> 4 == 15 { varint(bytes, pos) as usize } else { usize::from(header >> 4) };
+            Thrift::List((0..len).map(|_| thrift_value(bytes, pos, header & 0x0f)).collect())
+        }
+        12 => Thrift::Struct(thrift_struct(bytes, pos)),
+        other => panic!("unexpected Thrift type {}", other),
+    }
+}
+
+fn thrift_struct(bytes: &[u8], pos: &mut usize) -> std::collections::BTreeMap<i16, Thrift> {
+    let mut fields = std::collections::BTreeMap::new();
+    let mut id = 0i16;
+    loop {
+        let header = bytes[*pos];
+        *pos += 1;
+        if header == 0 {
+            return fields;
+        }
+        id = match header >> 4 {
+            0 => zigzag(bytes, pos) as i16,
+            delta => id + i16::from(delta),
+        };
+        fields.insert(id, thrift_value(bytes, pos, header & 0x0f));
+    }
+}
+
+fn parse(json: &str) -> Value {
+    serde_json::from_str(json).expect("exports return valid JSON")
+}
+
+fn by_id(mut log: Vec<Value>) -> Vec<Value> {
+    log.sort_by(|a, b| a["id"].as_str().cmp(&b["id"].as_str()));
+    log
+}
+
+proptest! {
+    #[test]
+    fn generated_experiences_validate(exp in experience()) {
+        let result = parse(&ExperienceValidator::new(false).validate(&exp.to_string()).unwrap());
+        prop_assert_eq!(&result["valid"], &json!(true), "{}", result);
+    }
+
+    #[test]
+    fn frames_round_trip(upserts in vec(experience(), 0..4), node in "\\PC{1,16}", seq in any::<u64>(), split in any::<prop::sample::Index>()) {
+        for frame in [
+            json!({"type": "hello", "node_id": node, "capabilities": ["delta"]}),
+            json!({"type": "delta", "seq": seq, "upserts": upserts, "removed": []}),
+        ] {
+            let bytes = encode_frame(&frame.to_string()).unwrap();
+            let (head, tail) = bytes.split_at(split.index(bytes.len() + 1));
+            let mut decoder = FrameDecoder::new();
+            let mut frames = parse(&decoder.push(head).unwrap()).as_array().unwrap().clone();
+            frames.extend(parse(&decoder.push(tail).unwrap()).as_array().unwrap().clone());
+            prop_assert_eq!(frames.len(), 1);
+            prop_assert_eq!(&frames[0], &frame);
+            prop_assert_eq!(encode_frame(&frames[0].to_string()).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn archives_round_trip(log in log(), deflate in any::<bool>()) {
+        let codec = if deflate { "deflate" } else { "none" };
+        let input = Value::Array(log.clone()).to_string();
+        let bytes = archive(&input, codec).unwrap();
+        let read = read_archive(&bytes, "").unwrap();
+        let Value::Array(records) = parse(&read) else { unreachable!() };
+        prop_assert_eq!(by_id(records), by_id(log));
+        let reread = read_archive(&archive(&read, codec).unwrap(), "").unwrap();
+        prop_assert_eq!(reread, read);
+    }
+
+    #[test]
+    fn changesets_round_trip(old in log(), new in log()) {
+        let (old, new) = (Value::Array(old).to_string(), Value::Array(new).to_string());
+        let changeset = diff_logs(&old, &new).unwrap();
+        let applied = parse(&apply_changeset(&old, &changeset).unwrap());
+        let Value::Array(applied) = applied else { unreachable!() };
+        let Value::Array(expected) = parse(&new) else { unreachable!() };
+        prop_assert_eq!(by_id(applied), by_id(expected));
+        prop_assert_eq!(diff_logs(&old, &new).unwrap(), changeset);
+    }
+
+    #[test]
+    fn streaming_matches_batch(log in vec(experience(), 0..8), chunk in 1usize..64) {
+        let ndjson: String = log.iter().map(|exp| format!("{}\n", exp)).collect();
+        let mut builder = NetworkStreamBuilder::new();
+        for part in ndjson.as_bytes().chunks(chunk) {
+            builder.push_chunk(part).unwrap();
+        }
+        let streamed = builder.finish().unwrap();
+        prop_assert_eq!(streamed, generate_domain_network(&Value::Array(log).to_string()).unwrap());
+    }
+
+    #[test]
+    fn array_stream_matches_batch(log in vec(experience(), 0..8), chunk in 1usize..64) {
+        let json = serde_json::to_string_pretty(&log).unwrap();
+        let mut stream = ArrayStream::new();
+        let mut elements = Vec::new();
+        for part in json.as_bytes().chunks(chunk) {
+            elements.extend(serde_json::from_str::<Vec<Value>>(&stream.push(part).unwrap()).unwrap());
+        }
+        stream.finish().unwrap();
+        prop_assert_eq!(elements, log);
+    }
+
+    #[test]
+    fn cbor_network_matches_json(log in vec(experience(), 0..8)) {
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(&log, &mut bytes).unwrap();
+        let network: Value = ciborium::de::from_reader(&generate_domain_network_cbor(&bytes).unwrap()[..]).unwrap();
+        prop_assert_eq!(network, parse(&generate_domain_network(&Value::Array(log).to_string()).unwrap()));
+    }
+
+    #[test]
+    fn network_options_agree_across_builders(log in vec(experience(), 0..8), top_k in 0usize..3) {
+        let options = json!({"top_k": top_k, "normalize": "pmi"}).to_string();
+        let expected = parse(&generate_domain_network_with_options(&Value::Array(log.clone()).to_string(), &options).unwrap());
+        let mut builder = NetworkStreamBuilder::new();
+        let ndjson: String = log.iter().map(|exp| format!("{}\n", exp)).collect();
+        builder.push_chunk(ndjson.as_bytes()).unwrap();
+        prop_assert_eq!(parse(&builder.finish_with_options(&options).unwrap()), expected.clone());
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(&log, &mut bytes).unwrap();
+        let network: Value =
+            ciborium::de::from_reader(&generate_domain_network_cbor_with_options(&bytes, &options).unwrap()[..]).unwrap();
+        prop_assert_eq!(network, expected);
+    }
+
+    #[test]
+    fn csv_cells_never_start_a_formula(exp in experience(), formula in "[=+@-]\\PC{0,12}", number in -1e6f64..1e6) {
+        prop_assume!(formula.parse::<f64>().is_err());
+        let mut exp = exp;
+        exp["experience"]["description"] = json!(formula);
+        exp["context"]["location"]["name"] = json!(number.to_string());
+        let columns = json!(["experience.description", "context.location.name"]).to_string();
+        let csv = export_experiences_csv(&Value::Array(vec![exp]).to_string(), &columns).unwrap();
+        let row = csv.split("\r\n").nth(1).unwrap();
+        prop_assert!(row.starts_with('\'') || row.starts_with("\"'"), "{}", row);
+        prop_assert!(row.ends_with(&format!(",{}", number)), "{}", row);
+    }
+
+    #[test]
+    fn noisy_walk_snaps_onto_its_trail(jitter in vec(-0.0001f64..0.0001, 2..12)) {
+        let trail = json!({"type": "FeatureCollection", "features": [{
+            "type": "Feature",
+            "properties": {"name": "towpath"},
+            "geometry": {"type": "LineString", "coordinates": [[-0.1, 51.5], [-0.09, 51.5]]},
+        }]});
+        let step = 0.009 / jitter.len() as f64;
+        let fixes: Vec<Value> = jitter
+            .iter()
+            .enumerate()
+            .map(|(i, dy)| json!({"latitude": 51.5 + dy, "longitude": -0.0995 + step * i as f64}))
+            .collect();
+        let result = parse(&map_match(&json!(fixes).to_string(), &trail.to_string(), "").unwrap());
+        prop_assert_eq!(&result["matched_fraction"], &json!(1.0));
+        for (point, dy) in result["points"].as_array().unwrap().iter().zip(&jitter) {
+            prop_assert_eq!(&point["path"], &json!("towpath"));
+            prop_assert!((point["latitude"].as_f64().unwrap() - 51.5).abs() < 1e-9, "{}", point);
+            // 1e-4 degrees of latitude is about 11 m
+            prop_assert!((point["offset_m"].as_f64().unwrap() - dy.abs() * 111_195.0).abs() < 0.5, "{}", point);
+        }
+    }
+
+    #[test]
+    fn search_ranks_every_matching_description(docs in vec(vec(0usize..4, 1..6), 1..8), query in 0usize..4) {
+        const WORDS: [&str; 4] = ["otter", "heron", "willow", "lichen"];
+        let log: Vec<Value> = docs
+            .iter()
+            .enumerate()
+            .map(|(i, words)| {
+                let description: Vec<&str> = words.iter().map(|&w| WORDS[w]).collect();
+                json!({
+                    "id": format!("e{}", i),
+                    "timestamp": "2024-05-01T10:00:00Z",
+                    "learner": {"id": "l"},
+                    "context": {"location": {"name": "park"}},
+                    "experience": {"type": "observation", "description": description.join(" ")},
+                })
+            })
+            .collect();
+        let index = SearchIndex::build(&Value::Array(log).to_string(), false).unwrap();
+        let hits = parse(&index.search(WORDS[query], 100).unwrap());
+        let hits = hits.as_array().unwrap();
+        let expected: Vec<String> =
+            (0..docs.len()).filter(|&i| docs[i].contains(&query)).map(|i| format!("e{}", i)).collect();
+        let mut found: Vec<String> = hits.iter().map(|h| h["id"].as_str().unwrap().to_string()).collect();
+        found.sort();
+        prop_assert_eq!(found, expected);
+        let scores: Vec<f64> = hits.iter().map(|h| h["score"].as_f64().unwrap()).collect();
+        prop_assert!(scores.iter().all(|&score| score > 0.0));
+        prop_assert!(scores.windows(2).all(|pair| pair[0] >= pair[1]));
+        // Among descriptions of equal length, more occurrences rank higher
+        for a in hits {
+            for b in hits {
+                let doc = |hit: &Value| &docs[hit["id"].as_str().unwrap()[1..].parse::<usize>().unwrap()];
+                let tf = |hit: &Value| doc(hit).iter().filter(|&&w| w == query).count();
+                if doc(a).len() == doc(b).len() && tf(a) > tf(b) {
+                    prop_assert!(a["score"].as_f64() > b["score"].as_f64());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pseudonyms_are_consistent_and_keyed(log in log(), key in "\\PC{1,16}", other in "\\PC{1,16}") {
+        prop_assume!(key != other);
+        let input = Value::Array(log.clone()).to_string();
+        let Value::Array(first) = parse(&pseudonymize(&input, &key, "").unwrap()) else { unreachable!() };
+        let Value::Array(again) = parse(&pseudonymize(&input, &key, "").unwrap()) else { unreachable!() };
+        let Value::Array(rekeyed) = parse(&pseudonymize(&input, &other, "").unwrap()) else { unreachable!() };
+        prop_assert_eq!(&first, &again);
+        let mut by_learner: std::collections::BTreeMap<String, &str> = std::collections::BTreeMap::new();
+        for ((original, pseudonymized), rekeyed) in log.iter().zip(&first).zip(&rekeyed) {
+            let learner = canonical_learner_id(original["learner"]["id"].as_str().unwrap());
+            let pseudonym = pseudonymized["learner"]["id"].as_str().unwrap();
+            prop_assert!(pseudonym.strip_prefix("pseud-").is_some_and(|hex| hex.len() == 32), "{}", pseudonym);
+            prop_assert_ne!(pseudonym, rekeyed["learner"]["id"].as_str().unwrap());
+            prop_assert_eq!(&pseudonymized["experience"], &original["experience"]);
+            // One pseudonym per learner, and different learners never share one
+            let previous = by_learner.insert(learner, pseudonym);
+            prop_assert!(previous.is_none_or(|p| p == pseudonym));
+        }
+        let mut pseudonyms: Vec<&str> = by_learner.values().copied().collect();
+        pseudonyms.sort();
+        pseudonyms.dedup();
+        prop_assert_eq!(pseudonyms.len(), by_learner.len());
+        prop_assert!(pseudonymize(&input, "", "").is_err());
+    }
+
+    #[test]
+    fn pii_offsets_are_utf16_spans_of_the_text(exp in experience(), before in "\\PC{0,16}", after in "\\PC{0,16}", email in "[a-z]{1,8}@[a-z]{1,8}\\.(org|net)") {
+        let description = format!("{} {} {}", before, email, after);
+        let mut exp = exp;
+        exp["experience"]["description"] = json!(description);
+        let findings = parse(&scan(&Value::Array(vec![exp]).to_string()).unwrap());
+        let utf16: Vec<u16> = description.encode_utf16().collect();
+        let span = |finding: &Value| {
+            let (start, end) = (finding["start"].as_u64().unwrap() as usize, finding["end"].as_u64().unwrap() as usize);
+            String::from_utf16(&utf16[start..end]).unwrap()
+        };
+        let in_description: Vec<&Value> = findings
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|finding| finding["field"] == "experience.description")
+            .collect();
+        prop_assert!(in_description.iter().any(|finding| finding["kind"] == "email" && span(finding) == email));
+        for finding in in_description {
+            prop_assert!(!span(finding).is_empty());
+        }
+    }
+
+    #[test]
+    fn signatures_verify_only_the_signed_record(exp in experience(), seed in any::<[u8; 32]>(), other in any::<[u8; 32]>()) {
+        prop_assume!(seed != other);
+        let json = exp.to_string();
+        let signature = sign_experience(&json, &seed).unwrap();
+        let public_key = public_key_for(&seed).unwrap();
+        prop_assert_eq!(signature.len(), 64);
+        prop_assert!(verify_experience(&json, &signature, &public_key).unwrap());
+        // An embedded proof is not part of the signed payload
+        let mut with_proof = exp.clone();
+        with_proof["proof"] = json!({"signature": "ignored"});
+        prop_assert!(verify_experience(&with_proof.to_string(), &signature, &public_key).unwrap());
+        let mut tampered = exp.clone();
+        tampered["experience"]["description"] = json!(format!("{}!", exp["experience"]["description"].as_str().unwrap()));
+        prop_assert!(!verify_experience(&tampered.to_string(), &signature, &public_key).unwrap());
+        prop_assert!(!verify_experience(&json, &signature, &public_key_for(&other).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn merges_commute_associate_and_are_idempotent(a in overlapping_log(), b in overlapping_log(), c in overlapping_log()) {
+        let (a, b, c) = (Value::Array(a).to_string(), Value::Array(b).to_string(), Value::Array(c).to_string());
+        let merged = |x: &str, y: &str| parse(&merge(x, y).unwrap())["experiences"].clone();
+        let ab = merged(&a, &b);
+        prop_assert_eq!(&ab, &merged(&b, &a));
+        let bc = merged(&b, &c);
+        prop_assert_eq!(merged(&ab.to_string(), &c), merged(&a, &bc.to_string()));
+        prop_assert_eq!(merged(&ab.to_string(), &ab.to_string()), ab.clone());
+        prop_assert_eq!(merged(&ab.to_string(), &a), ab);
+    }
+
+    #[test]
+    fn parquet_files_have_a_consistent_layout(log in log()) {
+        let bytes = export_parquet(&Value::Array(log.clone()).to_string()).unwrap();
+        prop_assert_eq!(&bytes[..4], b"PAR1");
+        prop_assert_eq!(&bytes[bytes.len() - 4..], b"PAR1");
+        let footer_len = u32::from_le_bytes(bytes[bytes.len() - 8..bytes.len() - 4].try_into().unwrap()) as usize;
+        let footer_start = bytes.len() - 8 - footer_len;
+        let metadata = thrift_struct(&bytes, &mut footer_start.clone());
+
+        prop_assert_eq!(&metadata[&3], &Thrift::Int(log.len() as i64));
+        let Thrift::List(schema) = &metadata[&2] else { panic!("schema is a list") };
+        let names: Vec<&[u8]> = schema
+            .iter()
+            .map(|element| match element {
+                Thrift::Struct(fields) => match &fields[&4] {
+                    Thrift::Bytes(name) => &name[..],
+                    _ => panic!("name is binary"),
+                },
+                _ => panic!("schema elements are structs"),
+            })
+            .collect();
+        let expected: Vec<&[u8]> = [
+            "schema", "id", "timestamp", "learner_id", "tenant", "location_name", "lat", "lon", "accuracy_m", "type",
+            "description", "domains", "list", "element",
+        ]
+        .iter()
+        .map(|name| name.as_bytes())
+        .collect();
+        prop_assert_eq!(names, expected);
+
+        // An empty log has no row group; otherwise one whose column chunks
+        // tile the file between the magic and the footer, each a single gzip
+        // data page
+        let Thrift::List(row_groups) = &metadata[&4] else { panic!("row groups are a list") };
+        prop_assert_eq!(row_groups.len(), usize::from(!log.is_empty()));
+        let Some(Thrift::Struct(row_group)) = row_groups.first() else {
+            prop_assert_eq!(footer_start, 4);
+            return Ok(());
+        };
+        let Thrift::List(chunks) = &row_group[&1] else { panic!("columns are a list") };
+        prop_assert_eq!(chunks.len(), 11);
+        let mut next = 4;
+        for chunk in chunks {
+            let Thrift::Struct(chunk) = chunk else { panic!("column chunk is a struct") };
+            let Thrift::Struct(meta) = &chunk[&3] else { panic!("column metadata is a struct") };
+            let (Thrift::Int(offset), Thrift::Int(size), Thrift::Int(values)) = (&meta[&9], &meta[&7], &meta[&5]) else {
+                panic!("offsets and sizes are integers")
+            };
+            prop_assert_eq!(*offset as usize, next);
+            let mut pos = next;
+            let header = thrift_struct(&bytes, &mut pos);
+            let (Thrift::Int(uncompressed), Thrift::Int(compressed)) = (&header[&2], &header[&3]) else {
+                panic!("page sizes are integers")
+            };
+            let page = decompress_gzip(&bytes[pos..pos + *compressed as usize]).unwrap();
+            prop_assert_eq!(page.len(), *uncompressed as usize);
+            let Thrift::Struct(data_page) = &header[&5] else { panic!("data page header is a struct") };
+            prop_assert_eq!(&data_page[&1], &Thrift::Int(*values));
+            next = pos + *compressed as usize;
+            prop_assert_eq!(next - *offset as usize, *size as usize);
+        }
+        prop_assert_eq!(next, footer_start);
+    }
+
+    #[test]
+    fn pruned_network_is_a_subgraph(log in vec(experience(), 0..8), min_edge_weight in 0usize..3, top_k in 0usize..3) {
+        let log = Value::Array(log).to_string();
+        let full = parse(&generate_domain_network(&log).unwrap());
+        let options = json!({"min_edge_weight": min_edge_weight, "top_k": top_k, "normalize": "npmi"}).to_string();
+        let pruned = parse(&generate_domain_network_with_options(&log, &options).unwrap());
+        prop_assert_eq!(&pruned["nodes"], &full["nodes"]);
+        for edge in pruned["edges"].as_array().unwrap() {
+            let score = edge["score"].as_f64().unwrap();
+            prop_assert!((-1.0 - 1e-9..=1.0 + 1e-9).contains(&score));
+            let mut unscored = edge.clone();
+            unscored.as_object_mut().unwrap().remove("score");
+            prop_assert!(full["edges"].as_array().unwrap().contains(&unscored));
+        }
+    }
+
+    #[test]
+    fn ntriples_has_one_line_per_triple(log in vec(experience(), 0..8)) {
+        let input = Value::Array(log).to_string();
+        let triples = parse(&to_triples(&input).unwrap());
+        prop_assert_eq!(to_ntriples(&input).unwrap().lines().count(), triples.as_array().unwrap().len());
+    }
+
+    #[test]
+    fn sketch_merges_match_single_pass(items in vec("\\PC{0,8}", 0..64), split in any::<prop::sample::Index>()) {
+        let (left, right) = items.split_at(split.index(items.len() + 1));
+        let (mut hll, mut hll_left, mut hll_right) = (HyperLogLog::new(8).unwrap(), HyperLogLog::new(8).unwrap(), HyperLogLog::new(8).unwrap());
+        let (mut cms, mut cms_left, mut cms_right) =
+            (CountMinSketch::new(64, 3).unwrap(), CountMinSketch::new(64, 3).unwrap(), CountMinSketch::new(64, 3).unwrap());
+        for item in &items {
+            hll.add(item);
+            cms.add(item, 1);
+        }
+        for item in left {
+            hll_left.add(item);
+            cms_left.add(item, 1);
+        }
+        for item in right {
+            hll_right.add(item);
+            cms_right.add(item, 1);
+        }
+        hll_left.merge(&HyperLogLog::from_bytes(&hll_right.to_bytes().unwrap()).unwrap()).unwrap();
+        cms_left.merge(&CountMinSketch::from_bytes(&cms_right.to_bytes().unwrap()).unwrap()).unwrap();
+        prop_assert_eq!(hll_left.to_bytes().unwrap(), hll.to_bytes().unwrap());
+        prop_assert_eq!(cms_left.to_bytes().unwrap(), cms.to_bytes().unwrap());
+
+        let mut digest = TDigest::new(20.0).unwrap();
+        let mut other = TDigest::new(20.0).unwrap();
+        for (i, item) in items.iter().enumerate() {
+            let target = if i < left.len() { &mut digest } else { &mut other };
+            target.add(item.len() as f64);
+        }
+        digest.merge(&TDigest::from_bytes(&other.to_bytes().unwrap()).unwrap());
+        prop_assert_eq!(digest.count(), items.len() as f64);
+        let lengths: Vec<f64> = items.iter().map(|i| i.len() as f64).collect();
+        if let (Some(min), Some(max)) = (lengths.iter().copied().reduce(f64::min), lengths.iter().copied().reduce(f64::max)) {
+            prop_assert_eq!(digest.quantile(0.0), min);
+            prop_assert_eq!(digest.quantile(1.0), max);
+        }
+    }
+
+    #[test]
+    fn gzip_round_trips(data in vec(any::<u8>(), 0..1024)) {
+        prop_assert_eq!(decompress_gzip(&compress_gzip(&data).unwrap()).unwrap(), data);
+    }
+
+    #[test]
+    fn gzip_exports_match_plain(log in log()) {
+        let log = Value::Array(log).to_string();
+        let inflate = |bytes: Vec<u8>| String::from_utf8(decompress_gzip(&bytes).unwrap()).unwrap();
+        prop_assert_eq!(inflate(to_triples_gzip(&log).unwrap()), to_triples(&log).unwrap());
+        prop_assert_eq!(inflate(to_darwin_core_gzip(&log, "").unwrap()), to_darwin_core(&log, "").unwrap());
+        prop_assert_eq!(
+            inflate(generate_domain_network_cytoscape_gzip(&log).unwrap()),
+            generate_domain_network_cytoscape(&log).unwrap()
+        );
+        prop_assert_eq!(inflate(generate_domain_network_d3_gzip(&log).unwrap()), generate_domain_network_d3(&log).unwrap());
+    }
+
+    #[test]
+    fn typed_matrix_matches_json(profiles in btree_map("[a-e]{1,3}", vec("[a-c]", 0..6), 0..6)) {
+        let profiles = json!(profiles).to_string();
+        let matrix = parse(&similarity_matrix(&profiles, "jaccard").unwrap());
+        let mut typed = similarity_matrix_typed(&profiles, "jaccard").unwrap();
+        prop_assert_eq!(&parse(&typed.index())["ids"], &matrix["ids"]);
+        let rows: Vec<f64> = matrix["matrix"].as_array().unwrap().iter().flat_map(|row| row.as_array().unwrap().clone()).map(|v| v.as_f64().unwrap()).collect();
+        prop_assert_eq!(typed.take_floats(), rows);
+    }
+
+    #[test]
+    fn clusters_account_for_every_point(log in log(), zoom in 0.0f64..20.0) {
+        let mut batch = PointClusterIndex::new("").unwrap();
+        let indexed = batch.add_experiences(&json!(log).to_string()).unwrap();
+        let mut fed = PointClusterIndex::new("").unwrap();
+        for exp in &log {
+            fed.apply_change(&json!({"op": "put", "record": exp, "previous": null}).to_string()).unwrap();
+        }
+        let clusters = batch.get_clusters("[-180, -90, 180, 90]", zoom).unwrap();
+        prop_assert_eq!(&clusters, &fed.get_clusters("[-180, -90, 180, 90]", zoom).unwrap());
+        let total: u64 = parse(&clusters).as_array().unwrap().iter().map(|f| f["properties"]["point_count"].as_u64().unwrap_or(1)).sum();
+        prop_assert_eq!(total, indexed as u64);
+    }
+
+    #[test]
+    fn utm_round_trips(zone in 1u32..=60, offset in -3.0f64..3.0, lat in -80.0f64..84.0) {
+        let (code, lon) = (if lat < 0.0 { 32700 + zone } else { 32600 + zone }, zone as f64 * 6.0 - 183.0 + offset);
+        let projected = transform_coordinates(&json!([[lon, lat]]).to_string(), "EPSG:4326", &format!("EPSG:{}", code)).unwrap();
+        let back = parse(&transform_coordinates(&projected, &format!("EPSG:{}", code), "EPSG:4326").unwrap());
+        prop_assert!((back[0][0].as_f64().unwrap() - lon).abs() < 1e-9);
+        prop_assert!((back[0][1].as_f64().unwrap() - lat).abs() < 1e-9);
+    }
+
+    #[test]
+    fn exports_do_not_panic_on_arbitrary_input(data in vec(any::<u8>(), 0..256), text in "\\PC{0,64}") {
+        let lossy = String::from_utf8_lossy(&data);
+        let validator = ExperienceValidator::new(true);
+        for input in [lossy.as_ref(), text.as_str()] {
+            let _ = validator.validate(input);
+            let _ = generate_domain_network(input);
+            let _ = to_ntriples(input);
+            let _ = encode_frame(input);
+            let _ = parse_unit(input);
+            let _ = convert_unit(1.0, input, "m");
+            let _ = transform_coordinates(input, "EPSG:32631", "EPSG:4326");
+            let _ = reproject_geojson(input, "", "EPSG:3857");
+            let _ = to_mvt(input, 2, 1, 3);
+            let _ = PointClusterIndex::new(input).map(|mut index| index.get_clusters(input, 3.0));
+        }
+        let _ = validator.validate_cbor(&data);
+        let _ = generate_domain_network_cbor(&data);
+        let _ = FrameDecoder::new().push(&data);
+        let _ = read_archive(&data, "");
+        let _ = ArrayStream::new().push(&data);
+        let _ = decompress_gzip(&data);
+        let mut shp = vec![0, 0, 0x27, 0x0a, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x7f, 0xff, 0xff, 0xff, 0xe8, 0x03, 0, 0];
+        shp.extend_from_slice(&data);
+        let _ = read_shapefile(&shp, &data, "");
+        let _ = HyperLogLog::from_bytes(&data).map(|h| h.estimate());
+        let _ = TDigest::from_bytes(&data).map(|mut t| t.quantile(0.5));
+        let _ = CountMinSketch::from_bytes(&data).map(|c| c.estimate(&text));
+        let _ = Gazetteer::from_bytes(&data).map(|g| g.nearest_place(0.0, 0.0));
+        let mut builder = NetworkStreamBuilder::new();
+        if builder.push_chunk(&data).is_ok() {
+            let _ = builder.finish();
+        }
+    }
+}
+
+/// A domain listed twice in one experience counts once and gets no self-edge
+#[test]
+fn repeated_domains_count_once() {
+    let log = json!([{
+        "id": "a",
+        "timestamp": "2024-01-01T00:00:00Z",
+        "learner": {"id": "l"},
+        "context": {"location": {"name": "p"}},
+        "experience": {"type": "t", "description": "d", "domains": ["=", "=", "\u{1e14e}"]},
+    }])
+    .to_string();
+    let options = json!({"min_edge_weight": 0, "top_k": 1, "normalize": "npmi"}).to_string();
+    let network = parse(&generate_domain_network_with_options(&log, &options).unwrap());
+    assert_eq!(network["nodes"], json!([{"id": "=", "size": 1}, {"id": "\u{1e14e}", "size": 1}]));
+    assert_eq!(network["edges"], json!([{"source": "=", "target": "\u{1e14e}", "weight": 1, "score": 1.0}]));
+}
+
+/// The Ordnance Survey's worked example (Caister water tower, in "A guide
+/// to coordinate systems in Great Britain") lands within the Helmert
+/// transformation's 5 m of its OSTN15 grid reference, and comes back
+#[cfg(feature = "grid-osgb36")]
+#[test]
+fn osgb36_matches_the_ordnance_survey_control_point() {
+    let lon = 1.0 + 42.0 / 60.0 + 57.8663 / 3600.0;
+    let lat = 52.0 + 39.0 / 60.0 + 28.8282 / 3600.0;
+    let grid = parse(&transform_coordinates(&json!([[lon, lat]]).to_string(), "EPSG:4326", "EPSG:27700").unwrap());
+    let (easting, northing) = (grid[0][0].as_f64().unwrap(), grid[0][1].as_f64().unwrap());
+    assert!((easting - 651_409.792).hypot(northing - 313_177.448) < 5.0, "{} {}", easting, northing);
+
+    let back = parse(&transform_coordinates(&grid.to_string(), "EPSG:27700", "EPSG:4326").unwrap());
+    assert!((back[0][0].as_f64().unwrap() - lon).abs() < 1e-6);
+    assert!((back[0][1].as_f64().unwrap() - lat).abs() < 1e-6);
+}
+
+/// Rows covering a missing, an empty and a multi-element `domains` list,
+/// with their Parquet columns as a reader should return them
+fn parquet_rows() -> (Vec<Value>, Value) {
+    let rows = vec![
+        json!({
+            "id": "a",
+            "timestamp": "2024-05-01T10:00:00Z",
+            "learner": {"id": "ada"},
+            "tenant": "north",
+            "context": {"location": {"name": "pond", "coordinates": {"latitude": 51.5, "longitude": -0.25, "accuracy": 12.0}}},
+            "experience": {"type": "walk", "description": "newts", "domains": ["ecology", "art", "ecology"]},
+        }),
+        json!({
+            "id": "b",
+            "timestamp": "yesterday",
+            "learner": {"id": "bo"},
+            "context": {"location": {"name": "lab"}},
+            "experience": {"type": "lab", "description": "", "domains": []},
+        }),
+        json!({
+            "id": "c",
+            "timestamp": "2024-05-01T10:00:00Z",
+            "learner": {"id": "cy"},
+            "context": {"location": {"name": "ménagerie"}},
+            "experience": {"type": "visit", "description": "owls"},
+        }),
+    ];
+    let columns = json!({
+        "id": ["a", "b", "c"],
+        "timestamp": [1_714_557_600_000i64, null, 1_714_557_600_000i64],
+        "learner_id": ["ada", "bo", "cy"],
+        "tenant": ["north", null, null],
+        "location_name": ["pond", "lab", "ménagerie"],
+        "lat": [51.5, null, null],
+        "lon": [-0.25, null, null],
+        "accuracy_m": [12.0, null, null],
+        "type": ["walk", "lab", "visit"],
+        "description": ["newts", "", "owls"],
+        "domains": [["ecology", "art", "ecology"], [], null],
+    });
+    (rows, columns)
+}
+
+/// `count` levels in the RLE/bit-packed hybrid encoding, length-prefixed
+/// as in a v1 data page
+fn hybrid_levels(bytes: &[u8], pos: &mut usize, bit_width: usize, count: usize) -> Vec<u8> {
+    let len = u32::from_le_bytes(bytes[*pos..*pos + 4].try_into().unwrap()) as usize;
+    *pos += 4;
+    let end = *pos + len;
+    let mut levels = Vec::new();
+    while levels.len() < count {
+        let header = varint(bytes, pos) as usize;
+        if header & 1 == 0 {
+            // Levels here are at most two bits wide, so the run value is one byte
+            levels.extend(std::iter::repeat_n(bytes[*pos], header >> 1));
+            *pos += 1;
+        } else {
+            // Groups of eight values, least significant bit first
+            let packed = &bytes[*pos..*pos + (header >> 1) * bit_width];
+            *pos += packed.len();
+            for i in 0..(header >> 1) * 8 {
+                let bit = |b: usize| (packed[(i * bit_width + b) / 8] >> ((i * bit_width + b) % 8)) & 1;
+                levels.push((0..bit_width).fold(0, |v, b| v | bit(b) << b));
+            }
+        }
+    }
+    assert_eq!(*pos, end, "levels fill their length prefix");
+    levels.truncate(count);
+    levels
+}
+
+/// Every column of a single-row-group file, rebuilt from its pages as a
+/// reader does: levels, then PLAIN values, with a three-level LIST column
+/// assembled from its repetition and definition levels
+fn parquet_columns(bytes: &[u8]) -> Value {
+    let footer_len = u32::from_le_bytes(bytes[bytes.len() - 8..bytes.len() - 4].try_into().unwrap()) as usize;
+    let metadata = thrift_struct(bytes, &mut (bytes.len() - 8 - footer_len));
+    let Thrift::List(row_groups) = &metadata[&4] else { panic!("row groups are a list") };
+    let Thrift::Struct(row_group) = &row_groups[0] else { panic!("row group is a struct") };
+    let Thrift::List(chunks) = &row_group[&1] else { panic!("columns are a list") };
+
+    let mut columns = serde_json::Map::new();
+    for chunk in chunks {
+        let Thrift::Struct(chunk) = chunk else { panic!("column chunk is a struct") };
+        let Thrift::Struct(meta) = &chunk[&3] else { panic!("column metadata is a struct") };
+        let (Thrift::Int(physical), Thrift::List(path), Thrift::Int(offset), Thrift::Int(values)) =
+            (&meta[&1], &meta[&3], &meta[&9], &meta[&5])
+        else {
+            panic!("column metadata fields")
+        };
+        let name = match &path[0] {
+            Thrift::Bytes(name) => String::from_utf8(name.clone()).unwrap(),
+            _ => panic!("path is binary"),
+        };
+        let mut pos = *offset as usize;
+        let header = thrift_struct(bytes, &mut pos);
+        let Thrift::Int(compressed) = header[&3] else { panic!("page size is an integer") };
+        let data = decompress_gzip(&bytes[pos..pos + compressed as usize]).unwrap();
+
+        let (count, nested) = (*values as usize, path.len() == 3);
+        let mut pos = 0;
+        let repetition = if nested { hybrid_levels(&data, &mut pos, 1, count) } else { vec![0; count] };
+        let definition = hybrid_levels(&data, &mut pos, if nested { 2 } else { 1 }, count);
+        let max = if nested { 2 } else { 1 };
+        let mut rows: Vec<Value> = Vec::new();
+        {
+            let mut next_value = || -> Value {
+                let value = match physical {
+                    2 => json!(i64::from_le_bytes(data[pos..pos + 8].try_into().unwrap())),
+                    5 => json!(f64::from_le_bytes(data[pos..pos + 8].try_into().unwrap())),
+                    6 => {
+                        let len = u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+                        pos += 4;
+                        json!(std::str::from_utf8(&data[pos..pos + len]).unwrap())
+                    }
+                    other => panic!("unexpected physical type {}", other),
+                };
+                pos += match physical {
+                    6 => value.as_str().unwrap().len(),
+                    _ => 8,
+                };
+                value
+            };
+
+            for (&r, &d) in repetition.iter().zip(&definition) {
+                let value = if d == max { next_value() } else { Value::Null };
+                match (nested, r, d) {
+                    (false, _, _) => rows.push(value),
+                    (true, 0, 0) => rows.push(Value::Null),
+                    (true, 0, 1) => rows.push(json!([])),
+                    (true, 0, _) => rows.push(json!([value])),
+                    (true, _, _) => rows.last_mut().unwrap().as_array_mut().unwrap().push(value),
+                }
+            }
+        }
+        assert_eq!(pos, data.len(), "{} values fill the page", name);
+        columns.insert(name, Value::Array(rows));
+    }
+    Value::Object(columns)
+}
+
+/// Decoding by the format's rules gives back every value, including a
+/// null, an empty and a multi-element list
+#[test]
+fn parquet_pages_decode_to_the_rows() {
+    let (rows, columns) = parquet_rows();
+    let bytes = export_parquet(&Value::from(rows).to_string()).unwrap();
+    assert_eq!(parquet_columns(&bytes), columns);
+}
+
+/// pyarrow, when installed, reads the same table
+#[test]
+fn pyarrow_reads_the_parquet_export() {
+    let has_pyarrow = std::process::Command::new("python3")
+        .args(["-c", "import pyarrow.parquet"])
+        .output()
+        .is_ok_and(|output| output.status.success());
+    if !has_pyarrow {
+        eprintln!("pyarrow is not installed; skipping");
+        return;
+    }
+    let (rows, columns) = parquet_rows();
+    let path = std::env::temp_dir().join(format!("ubicity-parquet-{}.parquet", std::process::id()));
+    std::fs::write(&path, export_parquet(&Value::from(rows).to_string()).unwrap()).unwrap();
+    let script = "import json, sys, pyarrow.parquet as pq\n\
+                  table = pq.read_table(sys.argv[1])\n\
+                  index = table.schema.get_field_index('timestamp')\n\
+                  table = table.set_column(index, 'timestamp', table.column(index).cast('int64'))\n\
+                  print(json.dumps(table.to_pydict()))";
+    let output = std::process::Command::new("python3").args(["-c", script]).arg(&path).output().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(serde_json::from_slice::<Value>(&output.stdout).unwrap(), columns);
+}
@@ -0,0 +1,34 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Fuzz target for domain network generation, batch and streaming
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ubicity_core::formats::{generate_domain_network_cbor, generate_domain_network_msgpack};
+use ubicity_core::generate_domain_network;
+use ubicity_core::graph_formats::{generate_domain_network_cytoscape, generate_domain_network_d3};
+use ubicity_core::pruning::generate_domain_network_with_options;
+use ubicity_core::stream::NetworkStreamBuilder;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(input) = std::str::from_utf8(data) {
+        let _ = generate_domain_network(input);
+        let _ = generate_domain_network_with_options("[]", input);
+        let _ = generate_domain_network_cytoscape(input);
+        let _ = generate_domain_network_d3(input);
+    }
+    let _ = generate_domain_network_cbor(data);
+    let _ = generate_domain_network_msgpack(data);
+
+    // The first byte picks a chunk size so splits land inside lines and
+    // multi-byte characters
+    if let Some((&size, rest)) = data.split_first() {
+        let mut builder = NetworkStreamBuilder::new();
+        let ok = rest
+            .chunks(usize::from(size).max(1))
+            .all(|chunk| builder.push_chunk(chunk).is_ok());
+        if ok {
+            let _ = builder.finish();
+        }
+    }
+});
@@ -0,0 +1,27 @@
+// SPDX-License-Identifier: MPL-2.0
+//! Fuzz target for the export and interchange format converters
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ubicity_core::archive::{archive, read_archive};
+use ubicity_core::export::{export_experiences_csv, export_network_csv};
+use ubicity_core::protocol::{encode_frame, FrameDecoder};
+use ubicity_core::triples::{to_ntriples, to_triples};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = FrameDecoder::new().push(data);
+    let _ = read_archive(data, "");
+
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+    let _ = encode_frame(input);
+    let _ = export_experiences_csv(input, "");
+    let _ = export_network_csv(input);
+    let _ = to_triples(input);
+    let _ = to_ntriples(input);
+    if let Ok(bytes) = archive(input, "deflate") {
+        let _ = read_archive(&bytes, "");
+    }
+});